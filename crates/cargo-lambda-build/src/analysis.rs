@@ -0,0 +1,260 @@
+use cargo_lambda_interactive::progress::HumanBytes;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use object::{Object, ObjectSection, ObjectSymbol, SymbolKind, read::File as ObjectFile};
+use rustc_demangle::demangle;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs::{File, read},
+    path::Path,
+};
+
+/// Number of crates and symbols to include in the analysis.
+const ANALYSIS_ENTRIES: usize = 20;
+
+/// Name used for symbols that cannot be attributed to a crate,
+/// like symbols from C libraries linked into the binary.
+const UNKNOWN_CRATE: &str = "[unknown]";
+
+/// Space that a crate takes in the binary's code.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CrateSize {
+    pub name: String,
+    pub size: u64,
+    /// Percentage of the code section that the crate takes
+    pub percentage: f64,
+}
+
+/// Space that a function takes in the binary's code.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SymbolSize {
+    pub name: String,
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub size: u64,
+    /// Percentage of the code section that the function takes
+    pub percentage: f64,
+}
+
+/// Breakdown of a binary's code by crate and function. Less code
+/// to load means faster cold starts, so this shows what to trim first.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BinaryAnalysis {
+    pub name: String,
+    pub file_size: u64,
+    /// Size of the `.text` section, where the code is
+    pub text_size: u64,
+    /// Whether the binary includes the symbols that the analysis needs
+    pub has_symbols: bool,
+    pub crates: Vec<CrateSize>,
+    pub symbols: Vec<SymbolSize>,
+}
+
+impl BinaryAnalysis {
+    /// Analyze the code in a binary. Binaries without symbols
+    /// only report the size of the file and its code section.
+    pub fn new(name: &str, binary: &Path) -> Result<BinaryAnalysis> {
+        let data = read(binary)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read binary file `{binary:?}`"))?;
+        let object = ObjectFile::parse(&*data)
+            .into_diagnostic()
+            .wrap_err("the provided function file is not a valid Linux binary")?;
+
+        let text_size = object
+            .section_by_name(".text")
+            .map(|s| s.size())
+            .unwrap_or_default();
+
+        let mut crates: HashMap<String, u64> = HashMap::new();
+        let mut symbols: HashMap<String, SymbolSize> = HashMap::new();
+        for symbol in object.symbols() {
+            if symbol.kind() != SymbolKind::Text || !symbol.is_definition() || symbol.size() == 0 {
+                continue;
+            }
+            let Ok(name) = symbol.name() else {
+                continue;
+            };
+
+            let name = format!("{:#}", demangle(name));
+            let crate_name = symbol_crate(&name);
+            *crates.entry(crate_name.clone()).or_default() += symbol.size();
+
+            // Generic functions and closures can have several copies
+            // with the same name, count them as a single function.
+            symbols
+                .entry(name.clone())
+                .or_insert_with(|| SymbolSize {
+                    name,
+                    crate_name,
+                    size: 0,
+                    percentage: 0.0,
+                })
+                .size += symbol.size();
+        }
+
+        let has_symbols = !symbols.is_empty();
+
+        let mut crates = crates
+            .into_iter()
+            .map(|(name, size)| CrateSize {
+                name,
+                size,
+                percentage: percentage(size, text_size),
+            })
+            .collect::<Vec<_>>();
+        crates.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        crates.truncate(ANALYSIS_ENTRIES);
+
+        let mut symbols = symbols
+            .into_values()
+            .map(|mut symbol| {
+                symbol.percentage = percentage(symbol.size, text_size);
+                symbol
+            })
+            .collect::<Vec<_>>();
+        symbols.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        symbols.truncate(ANALYSIS_ENTRIES);
+
+        Ok(BinaryAnalysis {
+            name: name.to_string(),
+            file_size: data.len() as u64,
+            text_size,
+            has_symbols,
+            crates,
+            symbols,
+        })
+    }
+
+    /// Print the binary's size, and the crates and symbols that take the most space.
+    pub fn print(&self) {
+        eprintln!(
+            "🔍 {}: {} binary, {} of code",
+            self.name,
+            HumanBytes(self.file_size),
+            HumanBytes(self.text_size)
+        );
+
+        if !self.has_symbols {
+            eprintln!(
+                "   ⚠️  the binary doesn't include symbols, remove `strip` from the build profile to analyze its code"
+            );
+            return;
+        }
+
+        eprintln!("   largest crates:");
+        for c in &self.crates {
+            eprintln!(
+                "   {:>6.2}% {:>12}  {}",
+                c.percentage,
+                HumanBytes(c.size).to_string(),
+                c.name
+            );
+        }
+
+        eprintln!("   largest functions:");
+        for s in &self.symbols {
+            eprintln!(
+                "   {:>6.2}% {:>12}  {}",
+                s.percentage,
+                HumanBytes(s.size).to_string(),
+                s.name
+            );
+        }
+    }
+}
+
+/// Write the analysis of the binaries as JSON, so CI jobs can check their size.
+pub fn write_analysis(path: &Path, analysis: &[BinaryAnalysis]) -> Result<()> {
+    let file = File::create(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to create analysis file `{path:?}`"))?;
+    serde_json::to_writer_pretty(file, analysis)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write analysis file `{path:?}`"))
+}
+
+/// Name of the crate that a demangled symbol belongs to. Trait implementations,
+/// like `<serde_json::Value as core::fmt::Debug>::fmt`, belong to the crate of the type.
+fn symbol_crate(symbol: &str) -> String {
+    let mut path = symbol.trim_start_matches(['<', '&', '*']);
+    for prefix in ["mut ", "const ", "dyn "] {
+        path = path.trim_start_matches(prefix);
+    }
+
+    match path.split_once("::") {
+        Some((name, _))
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') =>
+        {
+            name.to_string()
+        }
+        _ => UNKNOWN_CRATE.to_string(),
+    }
+}
+
+fn percentage(size: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    (size as f64 * 10000.0 / total as f64).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("core::fmt::write", "core")]
+    #[case("<serde_json::value::Value as core::fmt::Debug>::fmt", "serde_json")]
+    #[case("<&T as core::fmt::Display>::fmt", UNKNOWN_CRATE)]
+    #[case("<&mut tokio::runtime::Runtime as core::fmt::Debug>::fmt", "tokio")]
+    #[case("<dyn core::any::Any>::type_id", "core")]
+    #[case("lambda_runtime::run::{{closure}}", "lambda_runtime")]
+    #[case("memcpy", UNKNOWN_CRATE)]
+    fn test_symbol_crate(#[case] symbol: &str, #[case] expected: &str) {
+        assert_eq!(expected, symbol_crate(symbol));
+    }
+
+    #[test]
+    fn test_percentage() {
+        assert_eq!(25.0, percentage(1, 4));
+        assert_eq!(33.33, percentage(1, 3));
+        assert_eq!(0.0, percentage(1, 0));
+    }
+
+    #[test]
+    fn test_analysis_without_symbols() {
+        let binary = Path::new("../../tests/binaries/binary-x86-64");
+        let analysis = BinaryAnalysis::new("basic-lambda", binary).unwrap();
+
+        assert!(!analysis.has_symbols);
+        assert!(analysis.text_size > 0);
+        assert_eq!(binary.metadata().unwrap().len(), analysis.file_size);
+        assert!(analysis.crates.is_empty());
+        assert!(analysis.symbols.is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_analysis_with_symbols() {
+        // The test binary is built with symbols, so it can analyze itself.
+        let binary = std::env::current_exe().unwrap();
+        let analysis = BinaryAnalysis::new("tests", &binary).unwrap();
+
+        assert!(analysis.has_symbols);
+        assert_eq!(ANALYSIS_ENTRIES, analysis.crates.len());
+        assert_eq!(ANALYSIS_ENTRIES, analysis.symbols.len());
+        assert!(analysis.crates.windows(2).all(|c| c[0].size >= c[1].size));
+        assert!(analysis.symbols.windows(2).all(|s| s[0].size >= s[1].size));
+        assert!(analysis.crates.iter().any(|c| c.name == "core"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("analysis.json");
+        write_analysis(&path, &[analysis]).unwrap();
+
+        let json: serde_json::Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert_eq!("tests", json[0]["name"]);
+        assert!(json[0]["symbols"][0]["crate"].is_string());
+    }
+}
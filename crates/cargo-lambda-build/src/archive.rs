@@ -1,8 +1,8 @@
 use std::{
     collections::HashMap,
-    fmt::Debug,
-    fs::{File, Metadata, read},
-    io::{Read, Seek, Write},
+    fmt::{self, Debug},
+    fs::{File, Metadata, read, rename, write},
+    io::{Cursor, Read, Seek, Write, copy},
     path::{Path, PathBuf},
     time::{Duration, SystemTime},
 };
@@ -15,7 +15,7 @@ use cargo_lambda_remote::aws_sdk_lambda::types::Architecture as CpuArchitecture;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use chrono_humanize::HumanTime;
 use miette::{Context, IntoDiagnostic, Result};
-use object::{Architecture, Object, read::File as ObjectFile};
+use object::{Architecture, Object, ObjectSection, ReadCache, ReadRef, read::File as ObjectFile};
 use serde::{Serialize, Serializer};
 use sha2::{Digest, Sha256};
 use tracing::{debug, trace};
@@ -24,6 +24,9 @@ use zip::{HasZipMetadata, ZipArchive, ZipWriter, write::SimpleFileOptions};
 
 use crate::error::BuildError;
 
+/// Deflate compression level for the `--fast-package` flag.
+const FAST_COMPRESSION_LEVEL: i64 = 1;
+
 #[derive(Clone, Debug)]
 pub struct BinaryModifiedAt(Option<SystemTime>);
 
@@ -33,6 +36,12 @@ impl BinaryModifiedAt {
     }
 }
 
+impl From<SystemTime> for BinaryModifiedAt {
+    fn from(value: SystemTime) -> Self {
+        Self(Some(value))
+    }
+}
+
 impl From<NaiveDateTime> for BinaryModifiedAt {
     fn from(value: NaiveDateTime) -> Self {
         let dt = DateTime::<Utc>::from_naive_utc_and_offset(value, Utc);
@@ -104,6 +113,16 @@ impl<'a> BinaryData<'a> {
         }
     }
 
+    /// Name of the binary inside the zip archive when a bootstrap script wraps it.
+    /// The binary keeps its original name, and the script becomes the `bootstrap`.
+    pub fn wrapped_binary_name(&self) -> &str {
+        match self {
+            BinaryData::Function(name)
+            | BinaryData::ExternalExtension(name)
+            | BinaryData::InternalExtension(name) => name,
+        }
+    }
+
     pub(crate) fn binary_path_in_zip(&self) -> Result<String, BuildError> {
         let file_name = if let Some(parent) = self.parent_dir() {
             Path::new(parent).join(self.binary_name())
@@ -116,10 +135,34 @@ impl<'a> BinaryData<'a> {
     }
 }
 
+/// Highest version of glibc that a binary links against, like `2.34`.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct GlibcVersion(pub u32, pub u32);
+
+impl fmt::Display for GlibcVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.0, self.1)
+    }
+}
+
+impl GlibcVersion {
+    /// Parse a symbol version like `GLIBC_2.34`.
+    fn from_symbol_version(version: &str) -> Option<GlibcVersion> {
+        let version = version.strip_prefix("GLIBC_")?;
+        let (major, minor) = version.split_once('.')?;
+        // Ignore patch versions, like `GLIBC_2.2.5`
+        let minor = minor.split('.').next()?;
+        Some(GlibcVersion(major.parse().ok()?, minor.parse().ok()?))
+    }
+}
+
 pub struct BinaryArchive {
     pub architecture: String,
     pub path: PathBuf,
     pub binary_modified_at: BinaryModifiedAt,
+    /// Highest glibc version required by the binary,
+    /// None for binaries that don't link against glibc.
+    pub glibc_version: Option<GlibcVersion>,
 }
 
 impl BinaryArchive {
@@ -128,6 +171,7 @@ impl BinaryArchive {
             path,
             architecture,
             binary_modified_at,
+            glibc_version: None,
         }
     }
 
@@ -176,6 +220,9 @@ pub fn create_binary_archive<P>(
     base_dir: &Option<P>,
     data: &BinaryData,
     include: Option<Vec<String>>,
+    bootstrap_script: Option<&Path>,
+    incremental: bool,
+    fast: bool,
 ) -> Result<BinaryArchive>
 where
     P: AsRef<Path>,
@@ -190,14 +237,27 @@ where
         target_dir.join("lambda").join(data.binary_location())
     };
 
-    let binary_path = bootstrap_dir.join(data.binary_name());
+    let binary_name = match bootstrap_script {
+        Some(_) => data.wrapped_binary_name(),
+        None => data.binary_name(),
+    };
+
+    let binary_path = bootstrap_dir.join(binary_name);
     if binary_path.exists() {
-        return zip_binary(binary_path, bootstrap_dir, data, include);
+        return zip_binary(
+            binary_path,
+            bootstrap_dir,
+            data,
+            include,
+            bootstrap_script,
+            incremental,
+            fast,
+        );
     } else {
         let zip_path = bootstrap_dir.join(data.zip_name());
 
         if zip_path.exists() {
-            return use_zip_in_place(zip_path, data, include);
+            return use_zip_in_place(zip_path, data, include, bootstrap_script, fast);
         }
     }
 
@@ -208,9 +268,11 @@ fn use_zip_in_place(
     zip_path: PathBuf,
     data: &BinaryData<'_>,
     include: Option<Vec<String>>,
+    bootstrap_script: Option<&Path>,
+    fast: bool,
 ) -> Result<BinaryArchive> {
-    let binary_path_in_zip = data.binary_path_in_zip()?;
-    let (arch, binary_modified_at) =
+    let binary_path_in_zip = binary_path_in_zip(data, bootstrap_script)?;
+    let (info, binary_modified_at) =
         extract_data_from_zipped_binary(&zip_path, &binary_path_in_zip)?;
 
     if let Some(files) = include {
@@ -233,7 +295,8 @@ fn use_zip_in_place(
             zip.raw_copy_file(file).into_diagnostic()?;
         }
 
-        include_files_in_zip(&mut zip, &files)?;
+        let mut compression = ZipCompression::new(None, fast);
+        include_files_in_zip(&mut zip, &mut compression, &files)?;
 
         zip.finish()
             .into_diagnostic()
@@ -243,34 +306,64 @@ fn use_zip_in_place(
         copy_and_replace(&tmp_path, &zip_path).into_diagnostic()?;
     }
 
-    Ok(BinaryArchive::new(
-        zip_path.clone(),
-        arch.to_string(),
-        binary_modified_at,
-    ))
+    Ok(BinaryArchive {
+        glibc_version: info.glibc_version,
+        ..BinaryArchive::new(zip_path.clone(), info.arch.to_string(), binary_modified_at)
+    })
 }
 
 /// Create a zip file from a function binary.
 /// The binary inside the zip file is called `bootstrap` for function binaries.
 /// The binary inside the zip file is called by its name, and put inside the `extensions`
 /// directory, for extension binaries.
+/// When there is a bootstrap script, the script is called `bootstrap`, and the binary
+/// keeps its name next to it.
+/// When `incremental` is true, the files that didn't change since the previous zip file
+/// was created are copied from that file, without compressing them again.
+/// When `fast` is true, the files are compressed with the fastest compression level.
 pub fn zip_binary<BP: AsRef<Path>, DD: AsRef<Path>>(
     binary_path: BP,
     destination_directory: DD,
     data: &BinaryData,
     include: Option<Vec<String>>,
+    bootstrap_script: Option<&Path>,
+    incremental: bool,
+    fast: bool,
 ) -> Result<BinaryArchive> {
     let path = binary_path.as_ref();
     let dir = destination_directory.as_ref();
 
     let zipped = dir.join(data.zip_name());
-    debug!(?data, ?path, ?dir, ?zipped, "zipping binary");
+    debug!(
+        ?data,
+        ?path,
+        ?dir,
+        ?zipped,
+        incremental,
+        fast,
+        "zipping binary"
+    );
+
+    let previous = if incremental {
+        PreviousArchive::open(&zipped)
+    } else {
+        None
+    };
+    let mut compression = ZipCompression::new(previous, fast);
 
-    let zipped_binary = File::create(&zipped)
+    // The previous zip file is still open to copy its files,
+    // so the new zip file is written next to it, and renamed at the end.
+    let output = if compression.previous.is_some() {
+        zipped.with_extension("zip.tmp")
+    } else {
+        zipped.clone()
+    };
+
+    let zipped_binary = File::create(&output)
         .into_diagnostic()
-        .wrap_err_with(|| format!("failed to create zip file `{zipped:?}`"))?;
+        .wrap_err_with(|| format!("failed to create zip file `{output:?}`"))?;
 
-    let mut file = File::open(path)
+    let file = File::open(path)
         .into_diagnostic()
         .wrap_err_with(|| format!("failed to open binary file `{path:?}`"))?;
 
@@ -284,11 +377,17 @@ pub fn zip_binary<BP: AsRef<Path>, DD: AsRef<Path>>(
         .ok()
         .or_else(|| file_metadata.created().ok());
 
-    let (binary_data, arch) = read_binary(&mut file, path)?;
+    // Read only the parts of the binary that we need to extract its information,
+    // the rest of the binary is streamed from disk into the zip file.
+    let info = binary_info(&ReadCache::new(&file))?;
+    (&file)
+        .rewind()
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read binary file `{path:?}`"))?;
 
     let mut zip = ZipWriter::new(zipped_binary);
     if let Some(files) = include {
-        include_files_in_zip(&mut zip, &files)?;
+        include_files_in_zip(&mut zip, &mut compression, &files)?;
     }
 
     if let Some(parent) = data.parent_dir() {
@@ -300,36 +399,260 @@ pub fn zip_binary<BP: AsRef<Path>, DD: AsRef<Path>>(
             })?;
     }
 
-    let binary_path_in_zip = data.binary_path_in_zip()?;
+    let binary_path_in_zip = binary_path_in_zip(data, bootstrap_script)?;
 
-    let options = zip_file_options(&file, path)?;
+    add_file_to_zip(
+        &mut zip,
+        &mut compression,
+        &binary_path_in_zip,
+        &mut &file,
+        &file,
+        path,
+        None,
+    )?;
+
+    if let Some(script) = bootstrap_script {
+        include_bootstrap_script(&mut zip, &mut compression, script)?;
+    }
 
-    zip.start_file(binary_path_in_zip.to_string(), options)
+    zip.finish()
         .into_diagnostic()
-        .wrap_err_with(|| format!("failed to start zip file `{binary_path_in_zip:?}`"))?;
-    zip.write_all(&binary_data)
+        .wrap_err_with(|| format!("failed to finish zip file `{binary_path_in_zip:?}`"))?;
+
+    if compression.previous.take().is_some() {
+        rename(&output, &zipped)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to replace zip file `{zipped:?}`"))?;
+    }
+
+    Ok(BinaryArchive {
+        glibc_version: info.glibc_version,
+        ..BinaryArchive::new(
+            zipped,
+            info.arch.to_string(),
+            BinaryModifiedAt(binary_modified_at),
+        )
+    })
+}
+
+/// Create a zip file for an extension that runs on several architectures.
+/// Each binary is stored in a directory named after the extension and its architecture,
+/// like `my-extension-arm64/my-extension`. The extension's usual path is a script
+/// that runs the binary for the architecture of the execution environment.
+pub fn zip_multi_arch_extension<DD: AsRef<Path>>(
+    binary_paths: &[PathBuf],
+    destination_directory: DD,
+    data: &BinaryData,
+    include: Option<Vec<String>>,
+    fast: bool,
+) -> Result<BinaryArchive> {
+    if matches!(data, BinaryData::Function(_)) {
+        return Err(BuildError::MultiArchFunction.into());
+    }
+
+    let name = data.wrapped_binary_name();
+    let zipped = destination_directory.as_ref().join(data.zip_name());
+    debug!(?data, ?binary_paths, ?zipped, fast, "zipping binaries");
+
+    let zipped_binary = File::create(&zipped)
         .into_diagnostic()
-        .wrap_err_with(|| format!("failed to write data into zip file `{binary_path_in_zip:?}`"))?;
+        .wrap_err_with(|| format!("failed to create zip file `{zipped:?}`"))?;
+
+    let mut compression = ZipCompression::new(None, fast);
+    let mut zip = ZipWriter::new(zipped_binary);
+    if let Some(files) = include {
+        include_files_in_zip(&mut zip, &mut compression, &files)?;
+    }
+
+    let mut architectures: Vec<(&'static str, &PathBuf)> = Vec::new();
+    let mut glibc_version = None;
+    let mut binary_modified_at = None;
+
+    for path in binary_paths {
+        let file = File::open(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to open binary file `{path:?}`"))?;
+
+        let info = binary_info(&ReadCache::new(&file))?;
+        (&file)
+            .rewind()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read binary file `{path:?}`"))?;
+
+        if let Some((arch, other)) = architectures.iter().find(|(arch, _)| *arch == info.arch) {
+            return Err(BuildError::DuplicatedBinaryArchitecture(
+                other.to_path_buf(),
+                path.clone(),
+                arch.to_string(),
+            )
+            .into());
+        }
+
+        add_file_to_zip(
+            &mut zip,
+            &mut compression,
+            &format!("{name}-{}/{name}", info.arch),
+            &mut &file,
+            &file,
+            path,
+            None,
+        )?;
+
+        // Report the oldest binary, so stale builds are easy to spot.
+        let modified_at = file.metadata().ok().and_then(|m| m.modified().ok());
+        binary_modified_at = match (binary_modified_at, modified_at) {
+            (Some(current), Some(modified_at)) => Some(std::cmp::min(current, modified_at)),
+            (current, modified_at) => current.or(modified_at),
+        };
+        glibc_version = glibc_version.max(info.glibc_version);
+        architectures.push((info.arch, path));
+    }
+
+    if let Some(parent) = data.parent_dir() {
+        zip.add_directory(parent, SimpleFileOptions::default())
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                format!("failed to add directory `{parent}` to zip file `{zipped:?}`")
+            })?;
+    }
+
+    let architectures = architectures
+        .into_iter()
+        .map(|(arch, _)| arch)
+        .collect::<Vec<_>>();
+
+    let launcher_path = data.binary_path_in_zip()?;
+    let options = SimpleFileOptions::default()
+        .unix_permissions(0o755)
+        .compression_level(compression.level);
+    zip.start_file(&launcher_path, options)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to add file `{launcher_path}` to the zip file"))?;
+    zip.write_all(multi_arch_launcher(name, &architectures).as_bytes())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write data into zip file `{launcher_path}`"))?;
+
     zip.finish()
         .into_diagnostic()
-        .wrap_err_with(|| format!("failed to finish zip file `{binary_path_in_zip:?}`"))?;
+        .wrap_err_with(|| format!("failed to finish zip file `{zipped:?}`"))?;
+
+    Ok(BinaryArchive {
+        glibc_version,
+        ..BinaryArchive::new(
+            zipped,
+            architectures.join(","),
+            BinaryModifiedAt(binary_modified_at),
+        )
+    })
+}
 
-    Ok(BinaryArchive::new(
-        zipped,
-        arch.to_string(),
-        BinaryModifiedAt(binary_modified_at),
-    ))
+/// Script that runs the extension binary for the architecture
+/// of the execution environment. Layers are extracted in `/opt`.
+fn multi_arch_launcher(name: &str, architectures: &[&str]) -> String {
+    let mut script = String::from("#!/bin/sh\nset -e\n\ncase \"$(uname -m)\" in\n");
+    for arch in architectures {
+        let machine = match *arch {
+            "arm64" => "aarch64",
+            other => other,
+        };
+        script.push_str(&format!(
+            "  {machine}) exec \"/opt/{name}-{arch}/{name}\" \"$@\" ;;\n"
+        ));
+    }
+    script.push_str(&format!(
+        "  *) echo \"{name}: unsupported architecture $(uname -m)\" >&2; exit 1 ;;\nesac\n"
+    ));
+    script
+}
+
+/// Path of the binary inside the zip archive. Functions wrapped
+/// by a bootstrap script keep the binary's original name.
+fn binary_path_in_zip(
+    data: &BinaryData,
+    bootstrap_script: Option<&Path>,
+) -> Result<String, BuildError> {
+    match (data, bootstrap_script) {
+        (BinaryData::Function(name), Some(_)) => Ok(name.to_string()),
+        _ => data.binary_path_in_zip(),
+    }
 }
 
-fn extract_data_from_zipped_binary<'a>(
-    zip_path: &'a Path,
-    binary_path: &'a str,
-) -> Result<(&'a str, BinaryModifiedAt)> {
+/// Read the bootstrap script, and check that Lambda can execute it.
+fn read_bootstrap_script(script: &Path) -> Result<Vec<u8>> {
+    let content = read(script)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read bootstrap script `{script:?}`"))?;
+
+    if !content.starts_with(b"#!") {
+        return Err(BuildError::InvalidBootstrapScript(script.to_path_buf()).into());
+    }
+
+    Ok(content)
+}
+
+/// Copy the bootstrap script next to the binary, as an executable file called `bootstrap`.
+pub(crate) fn copy_bootstrap_script(
+    script: &Path,
+    destination_directory: &Path,
+) -> Result<PathBuf> {
+    let content = read_bootstrap_script(script)?;
+
+    let bootstrap = destination_directory.join("bootstrap");
+    write(&bootstrap, content)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to copy bootstrap script into `{bootstrap:?}`"))?;
+
+    #[cfg(unix)]
+    {
+        use std::{
+            fs::{Permissions, set_permissions},
+            os::unix::fs::PermissionsExt,
+        };
+        set_permissions(&bootstrap, Permissions::from_mode(0o755))
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to make `{bootstrap:?}` executable"))?;
+    }
+
+    Ok(bootstrap)
+}
+
+/// Add the bootstrap script to the zip archive.
+/// The script is always executable, even if the file in disk is not.
+fn include_bootstrap_script<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    compression: &mut ZipCompression,
+    script: &Path,
+) -> Result<()> {
+    let content = read_bootstrap_script(script)?;
+
+    let file = File::open(script)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to open bootstrap script `{script:?}`"))?;
+
+    add_file_to_zip(
+        zip,
+        compression,
+        "bootstrap",
+        &mut Cursor::new(content),
+        &file,
+        script,
+        Some(0o755),
+    )
+}
+
+fn extract_data_from_zipped_binary(
+    zip_path: &Path,
+    binary_path: &str,
+) -> Result<(BinaryInfo, BinaryModifiedAt)> {
     let zipfile = File::open(zip_path).into_diagnostic()?;
     let mut archive = ZipArchive::new(zipfile).into_diagnostic()?;
 
     let mut file = archive.by_name(binary_path).into_diagnostic()?;
-    let (_, arch) = read_binary(&mut file, binary_path)?;
+    let mut binary_data = Vec::new();
+    file.read_to_end(&mut binary_data)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read binary file `{binary_path:?}`"))?;
+    let info = binary_info(&*binary_data)?;
 
     let metadata = file.get_metadata();
     let mut last_modified_at = BinaryModifiedAt(None);
@@ -338,21 +661,17 @@ fn extract_data_from_zipped_binary<'a>(
         last_modified_at = naive_dt.into();
     }
 
-    Ok((arch, last_modified_at))
+    Ok((info, last_modified_at))
 }
 
-fn read_binary<'a, R: Read, D: std::fmt::Debug>(
-    entry: &mut R,
-    binary_path: D,
-) -> Result<(Vec<u8>, &'a str)> {
-    let mut binary_data = Vec::new();
-
-    entry
-        .read_to_end(&mut binary_data)
-        .into_diagnostic()
-        .wrap_err_with(|| format!("failed to read binary file `{binary_path:?}`"))?;
+/// Information extracted from a binary's object file.
+struct BinaryInfo {
+    arch: &'static str,
+    glibc_version: Option<GlibcVersion>,
+}
 
-    let object = ObjectFile::parse(&*binary_data)
+fn binary_info<'data, R: ReadRef<'data>>(data: R) -> Result<BinaryInfo> {
+    let object = ObjectFile::parse(data)
         .into_diagnostic()
         .wrap_err("the provided function file is not a valid Linux binary")?;
 
@@ -361,26 +680,167 @@ fn read_binary<'a, R: Read, D: std::fmt::Debug>(
         Architecture::X86_64 => "x86_64",
         other => return Err(BuildError::InvalidBinaryArchitecture(other).into()),
     };
+    let glibc_version = required_glibc_version(&object);
+
+    Ok(BinaryInfo {
+        arch,
+        glibc_version,
+    })
+}
 
-    Ok((binary_data, arch))
+/// Find the highest glibc version that the binary requires.
+/// The versions of the symbols that the binary imports are
+/// stored as strings in the dynamic string table.
+fn required_glibc_version<'data, R: ReadRef<'data>>(
+    object: &ObjectFile<'data, R>,
+) -> Option<GlibcVersion> {
+    let dynstr = object.section_by_name(".dynstr")?;
+    let data = dynstr.data().ok()?;
+
+    data.split(|b| *b == 0)
+        .filter_map(|s| std::str::from_utf8(s).ok())
+        .filter_map(GlibcVersion::from_symbol_version)
+        .max()
 }
 
-fn zip_file_options(file: &File, path: &Path) -> Result<SimpleFileOptions> {
+/// Add a file to the zip archive, with the permissions and modification time of the file in disk.
+/// The permissions in `unix_permissions` replace the ones in disk.
+fn add_file_to_zip<W: Write + Seek, R: Read + Seek>(
+    zip: &mut ZipWriter<W>,
+    compression: &mut ZipCompression,
+    name: &str,
+    content: &mut R,
+    file: &File,
+    path: &Path,
+    unix_permissions: Option<u32>,
+) -> Result<()> {
     let meta = file
         .metadata()
         .into_diagnostic()
         .wrap_err_with(|| format!("failed to get metadata from file `{path:?}`"))?;
-    let perm = binary_permissions(&meta);
-    let mut options = SimpleFileOptions::default().unix_permissions(perm);
-    if let Some(mtime) = binary_mtime(&meta) {
+    let perm = unix_permissions.unwrap_or_else(|| binary_permissions(&meta));
+    let mtime = binary_mtime(&meta);
+
+    if let Some(previous) = &mut compression.previous {
+        if previous.copy_unchanged(zip, name, content, mtime, perm)? {
+            return Ok(());
+        }
+        content
+            .rewind()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read file `{path:?}`"))?;
+    }
+
+    let mut options = SimpleFileOptions::default()
+        .unix_permissions(perm)
+        .compression_level(compression.level);
+    if let Some(mtime) = mtime {
         options = options.last_modified_time(mtime);
     }
 
-    Ok(options)
+    zip.start_file(name, options)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to add file `{name}` to the zip file"))?;
+    copy(content, zip)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write data into zip file `{name}`"))?;
+
+    Ok(())
+}
+
+/// Compression settings for the files added to the zip archive.
+struct ZipCompression {
+    /// Zip file to copy the files that didn't change from.
+    previous: Option<PreviousArchive>,
+    /// Deflate compression level, `None` uses the default level.
+    level: Option<i64>,
+}
+
+impl ZipCompression {
+    fn new(previous: Option<PreviousArchive>, fast: bool) -> ZipCompression {
+        ZipCompression {
+            previous,
+            level: fast.then_some(FAST_COMPRESSION_LEVEL),
+        }
+    }
+}
+
+/// Zip file created by a previous build. The files that didn't change since then
+/// are copied into the new zip file, without compressing them again.
+struct PreviousArchive {
+    archive: ZipArchive<File>,
+}
+
+impl PreviousArchive {
+    /// Open the zip file in `path`. Missing and invalid files are ignored,
+    /// the new zip file is created from scratch in that case.
+    fn open(path: &Path) -> Option<PreviousArchive> {
+        let file = File::open(path).ok()?;
+        match ZipArchive::new(file) {
+            Ok(archive) => Some(PreviousArchive { archive }),
+            Err(error) => {
+                debug!(
+                    ?path,
+                    ?error,
+                    "ignoring invalid zip file from previous build"
+                );
+                None
+            }
+        }
+    }
+
+    /// Copy the file `name` from the previous zip file if its content is the same as `content`.
+    /// It returns `false` when the file is not in the previous zip file, or its content changed.
+    fn copy_unchanged<W: Write + Seek, R: Read>(
+        &mut self,
+        zip: &mut ZipWriter<W>,
+        name: &str,
+        content: &mut R,
+        mtime: Option<zip::DateTime>,
+        unix_permissions: u32,
+    ) -> Result<bool> {
+        let Some(index) = self.archive.index_for_name(name) else {
+            return Ok(false);
+        };
+
+        let (size, crc32) = checksum(content)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read file `{name}`"))?;
+
+        let entry = self.archive.by_index_raw(index).into_diagnostic()?;
+        if entry.size() != size || entry.crc32() != crc32 {
+            return Ok(false);
+        }
+
+        trace!(%name, "copying unchanged file from previous zip file");
+        let mtime = mtime.or_else(|| entry.last_modified()).unwrap_or_default();
+        zip.raw_copy_file_touch(entry, mtime, Some(unix_permissions))
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to copy file `{name}` from previous zip file"))?;
+
+        Ok(true)
+    }
+}
+
+/// Size and CRC32 checksum of the content, like zip files store them.
+fn checksum<R: Read>(content: &mut R) -> std::io::Result<(u64, u32)> {
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = [0; 64 * 1024];
+    let mut size = 0;
+
+    loop {
+        let read = content.read(&mut buffer)?;
+        if read == 0 {
+            return Ok((size, hasher.finalize()));
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
 }
 
 fn include_files_in_zip<W: Write + Seek>(
     zip: &mut ZipWriter<W>,
+    compression: &mut ZipCompression,
     files: &Vec<String>,
 ) -> Result<()> {
     let mut file_map = HashMap::with_capacity(files.len());
@@ -392,20 +852,20 @@ fn include_files_in_zip<W: Write + Seek>(
     }
 
     for (base, file) in file_map {
-        for entry in WalkDir::new(&file).into_iter().filter_map(|e| e.ok()) {
+        let root = Path::new(&file);
+        for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
             let path = entry.path();
-            let base = base.clone();
-            let file = file.clone();
-
-            let unix_base = convert_to_unix_path(Path::new(&base))
-                .ok_or_else(|| BuildError::InvalidUnixFileName(base.into()))?;
-            let unix_file = convert_to_unix_path(Path::new(&file))
-                .ok_or_else(|| BuildError::InvalidUnixFileName(file.into()))?;
 
-            let source_name = convert_to_unix_path(path)
-                .ok_or_else(|| BuildError::InvalidUnixFileName(path.into()))?;
-
-            let destination_name = source_name.replace(&unix_file, &unix_base);
+            // Only the part of the path inside the included directory goes into the zip file,
+            // the directories around it can have names that are not valid UTF-8.
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            let destination = if relative.as_os_str().is_empty() {
+                PathBuf::from(&base)
+            } else {
+                Path::new(&base).join(relative)
+            };
+            let destination_name = convert_to_unix_path(&destination)
+                .ok_or_else(|| BuildError::InvalidUnixFileName(destination.clone()))?;
 
             if path.is_dir() {
                 trace!(%destination_name, "creating directory in zip file");
@@ -416,29 +876,21 @@ fn include_files_in_zip<W: Write + Seek>(
                         format!("failed to add directory `{destination_name}` to zip file")
                     })?;
             } else {
-                trace!(%source_name, %destination_name, "including file in zip file");
+                trace!(?path, %destination_name, "including file in zip file");
 
-                let mut content = Vec::new();
-                let mut file = File::open(path)
+                let file = File::open(path)
                     .into_diagnostic()
                     .wrap_err_with(|| format!("failed to open file `{path:?}`"))?;
-                file.read_to_end(&mut content)
-                    .into_diagnostic()
-                    .wrap_err_with(|| format!("failed to read file `{path:?}`"))?;
-
-                let options = zip_file_options(&file, path)?;
 
-                zip.start_file(destination_name.clone(), options)
-                    .into_diagnostic()
-                    .wrap_err_with(|| {
-                        format!("failed to create zip content file `{destination_name:?}`")
-                    })?;
-
-                zip.write_all(&content)
-                    .into_diagnostic()
-                    .wrap_err_with(|| {
-                        format!("failed to write data into zip content file `{destination_name:?}`")
-                    })?;
+                add_file_to_zip(
+                    zip,
+                    compression,
+                    &destination_name,
+                    &mut &file,
+                    &file,
+                    path,
+                    None,
+                )?;
             }
         }
     }
@@ -517,7 +969,7 @@ impl Serialize for BinaryModifiedAt {
 #[cfg(test)]
 mod test {
     use std::{
-        fs::{create_dir_all, remove_dir_all},
+        fs::{create_dir_all, remove_dir_all, remove_file},
         thread::sleep,
         time::Duration,
     };
@@ -557,6 +1009,34 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_glibc_version_from_symbol_version() {
+        assert_eq!(
+            Some(GlibcVersion(2, 34)),
+            GlibcVersion::from_symbol_version("GLIBC_2.34")
+        );
+        assert_eq!(
+            Some(GlibcVersion(2, 2)),
+            GlibcVersion::from_symbol_version("GLIBC_2.2.5")
+        );
+        assert_eq!(None, GlibcVersion::from_symbol_version("GCC_3.0"));
+        assert_eq!(None, GlibcVersion::from_symbol_version("GLIBC_PRIVATE"));
+        assert!(GlibcVersion(2, 34) > GlibcVersion(2, 26));
+    }
+
+    #[rstest]
+    #[case("binary-x86-64")]
+    #[case("binary-arm64")]
+    fn test_zip_binary_glibc_version(#[case] name: &str) {
+        let data = BinaryData::new(name, false, false);
+        let bp = &format!("../../tests/binaries/{name}");
+        let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
+        let archive = zip_binary(bp, dd.path(), &data, None, None, false, false)
+            .expect("failed to create binary archive");
+
+        assert_eq!(Some(GlibcVersion(2, 18)), archive.glibc_version);
+    }
+
     #[rstest]
     #[case("binary-x86-64", "x86_64")]
     #[case("binary-arm64", "arm64")]
@@ -564,8 +1044,8 @@ mod test {
         let data = BinaryData::new(name, false, false);
         let bp = &format!("../../tests/binaries/{name}");
         let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
-        let archive =
-            zip_binary(bp, dd.path(), &data, None).expect("failed to create binary archive");
+        let archive = zip_binary(bp, dd.path(), &data, None, None, false, false)
+            .expect("failed to create binary archive");
 
         assert_eq!(arch, archive.architecture);
 
@@ -587,8 +1067,8 @@ mod test {
 
         let bp = &format!("../../tests/binaries/{name}");
         let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
-        let archive =
-            zip_binary(bp, dd.path(), &data, None).expect("failed to create binary archive");
+        let archive = zip_binary(bp, dd.path(), &data, None, None, false, false)
+            .expect("failed to create binary archive");
 
         assert_eq!(arch, archive.architecture);
 
@@ -610,8 +1090,8 @@ mod test {
 
         let bp = &format!("../../tests/binaries/{name}");
         let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
-        let archive =
-            zip_binary(bp, dd.path(), &data, None).expect("failed to create binary archive");
+        let archive = zip_binary(bp, dd.path(), &data, None, None, false, false)
+            .expect("failed to create binary archive");
 
         assert_eq!(arch, archive.architecture);
 
@@ -634,8 +1114,8 @@ mod test {
         let bp = &format!("../../tests/binaries/{name}");
         let extra = vec!["Cargo.toml".into()];
         let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
-        let archive =
-            zip_binary(bp, dd.path(), &data, Some(extra)).expect("failed to create binary archive");
+        let archive = zip_binary(bp, dd.path(), &data, Some(extra), None, false, false)
+            .expect("failed to create binary archive");
 
         assert_eq!(arch, archive.architecture);
 
@@ -652,6 +1132,166 @@ mod test {
             .expect("failed to find Cargo.toml in zip archive");
     }
 
+    #[rstest]
+    #[case("binary-x86-64", "x86_64")]
+    #[case("binary-arm64", "arm64")]
+    fn test_zip_funcion_with_bootstrap_script(#[case] name: &str, #[case] arch: &str) {
+        let data = BinaryData::new(name, false, false);
+
+        let bp = &format!("../../tests/binaries/{name}");
+        let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
+        let script = dd.path().join("wrapper.sh");
+        std::fs::write(
+            &script,
+            format!("#!/bin/sh\nexec $LAMBDA_TASK_ROOT/{name}\n"),
+        )
+        .expect("failed to write bootstrap script");
+
+        let archive = zip_binary(bp, dd.path(), &data, None, Some(&script), false, false)
+            .expect("failed to create binary archive");
+
+        assert_eq!(arch, archive.architecture);
+
+        let arch_path = dd.path().join("bootstrap.zip");
+        assert_eq!(arch_path, archive.path);
+
+        let file = File::open(arch_path).expect("failed to open zip file");
+        let mut zip = ZipArchive::new(file).expect("failed to open zip archive");
+
+        let bootstrap = zip
+            .by_name("bootstrap")
+            .expect("failed to find bootstrap in zip archive");
+        assert_eq!(Some(0o100755), bootstrap.unix_mode());
+        drop(bootstrap);
+
+        zip.by_name(name)
+            .unwrap_or_else(|_| panic!("failed to find {name} in zip archive"));
+    }
+
+    #[rstest]
+    #[case(false, "extensions/test-extension")]
+    #[case(true, "test-extension")]
+    fn test_zip_multi_arch_extension(#[case] internal: bool, #[case] launcher_path: &str) {
+        let data = BinaryData::new("test-extension", true, internal);
+        let binaries = vec![
+            PathBuf::from("../../tests/binaries/binary-x86-64"),
+            PathBuf::from("../../tests/binaries/binary-arm64"),
+        ];
+        let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
+        let archive = zip_multi_arch_extension(&binaries, dd.path(), &data, None, false)
+            .expect("failed to create binary archive");
+
+        assert_eq!("x86_64,arm64", archive.architecture);
+        assert_eq!(Some(GlibcVersion(2, 18)), archive.glibc_version);
+
+        let file = File::open(&archive.path).expect("failed to open zip file");
+        let mut zip = ZipArchive::new(file).expect("failed to open zip archive");
+
+        zip.by_name("test-extension-x86_64/test-extension")
+            .expect("failed to find x86_64 binary in zip archive");
+        zip.by_name("test-extension-arm64/test-extension")
+            .expect("failed to find arm64 binary in zip archive");
+
+        let mut launcher = zip
+            .by_name(launcher_path)
+            .expect("failed to find launcher in zip archive");
+        assert_eq!(Some(0o100755), launcher.unix_mode());
+
+        let mut script = String::new();
+        launcher.read_to_string(&mut script).unwrap();
+        assert!(script.starts_with("#!/bin/sh"));
+        assert!(
+            script.contains(r#"aarch64) exec "/opt/test-extension-arm64/test-extension" "$@" ;;"#)
+        );
+        assert!(
+            script.contains(r#"x86_64) exec "/opt/test-extension-x86_64/test-extension" "$@" ;;"#)
+        );
+    }
+
+    #[test]
+    fn test_zip_multi_arch_extension_duplicated_architecture() {
+        let data = BinaryData::new("test-extension", true, false);
+        let binaries = vec![
+            PathBuf::from("../../tests/binaries/binary-x86-64"),
+            PathBuf::from("../../tests/binaries/binary-x86-64"),
+        ];
+        let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
+        let Err(err) = zip_multi_arch_extension(&binaries, dd.path(), &data, None, false) else {
+            panic!("expected duplicated architecture error");
+        };
+        assert!(err.to_string().contains("the same architecture, x86_64"));
+
+        let data = BinaryData::new("test-function", false, false);
+        let Err(err) = zip_multi_arch_extension(&binaries, dd.path(), &data, None, false) else {
+            panic!("expected function error");
+        };
+        assert_eq!(
+            "only extensions can include binaries for several architectures",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_create_binary_archive_with_bootstrap_script() {
+        let data = BinaryData::new("binary-x86-64", false, false);
+
+        let bp = "../../tests/binaries/binary-x86-64";
+        let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
+        let bsp = dd.path().join("binary-x86-64");
+        let script = dd.path().join("wrapper.sh");
+
+        create_dir_all(&bsp).expect("failed to create dir");
+        copy_without_replace(bp, bsp.join("binary-x86-64")).expect("failed to copy binary file");
+        copy_bootstrap_script_fixture(&script);
+
+        let archive = create_binary_archive(
+            None,
+            &Some(dd.path()),
+            &data,
+            None,
+            Some(&script),
+            false,
+            false,
+        )
+        .expect("failed to create binary archive");
+        assert_eq!("x86_64", archive.architecture);
+
+        // Deploying the zip again reads the architecture from the wrapped binary
+        remove_file(bsp.join("binary-x86-64")).expect("failed to delete binary file");
+        let archive = create_binary_archive(
+            None,
+            &Some(dd.path()),
+            &data,
+            None,
+            Some(&script),
+            false,
+            false,
+        )
+        .expect("failed to use binary archive");
+        assert_eq!("x86_64", archive.architecture);
+    }
+
+    fn copy_bootstrap_script_fixture(script: &Path) {
+        std::fs::write(script, "#!/bin/sh\nexec $LAMBDA_TASK_ROOT/binary-x86-64\n")
+            .expect("failed to write bootstrap script");
+    }
+
+    #[test]
+    fn test_zip_funcion_with_invalid_bootstrap_script() {
+        let data = BinaryData::new("binary-x86-64", false, false);
+
+        let bp = "../../tests/binaries/binary-x86-64";
+        let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
+        let script = dd.path().join("wrapper.sh");
+        std::fs::write(&script, "exec ./binary-x86-64\n")
+            .expect("failed to write bootstrap script");
+
+        let Err(err) = zip_binary(bp, dd.path(), &data, None, Some(&script), false, false) else {
+            panic!("the bootstrap script should be invalid");
+        };
+        assert!(err.to_string().starts_with("invalid bootstrap script"));
+    }
+
     #[test]
     fn test_consistent_hash() {
         let data = BinaryData::new("binary-x86-64", false, false);
@@ -659,18 +1299,118 @@ mod test {
         let bp = "../../tests/binaries/binary-x86-64";
         let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
 
-        let archive1 =
-            zip_binary(bp, dd.path(), &data, None).expect("failed to create binary archive");
+        let archive1 = zip_binary(bp, dd.path(), &data, None, None, false, false)
+            .expect("failed to create binary archive");
 
         // Sleep to ensure that the mtime is different enough for the hash to change
         sleep(Duration::from_secs(2));
 
-        let archive2 =
-            zip_binary(bp, dd.path(), &data, None).expect("failed to create binary archive");
+        let archive2 = zip_binary(bp, dd.path(), &data, None, None, false, false)
+            .expect("failed to create binary archive");
 
         assert_eq!(archive1.sha256().unwrap(), archive2.sha256().unwrap());
     }
 
+    #[test]
+    fn test_incremental_zip() {
+        let data = BinaryData::new("binary-x86-64", false, false);
+
+        let bp = "../../tests/binaries/binary-x86-64";
+        let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
+        let asset = dd.path().join("asset.txt");
+        std::fs::write(&asset, "new content").expect("failed to write asset");
+
+        // Store the files without compression in the previous zip file,
+        // so we can tell which files are copied from it.
+        let previous = File::create(dd.path().join("bootstrap.zip")).expect("failed to create zip");
+        let mut zip = ZipWriter::new(previous);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("bootstrap", options).unwrap();
+        zip.write_all(&read(bp).unwrap()).unwrap();
+        zip.start_file("asset.txt", options).unwrap();
+        zip.write_all(b"old content").unwrap();
+        zip.finish().unwrap();
+
+        let include = Some(vec![format!("asset.txt:{}", asset.display())]);
+        let archive = zip_binary(bp, dd.path(), &data, include.clone(), None, true, false)
+            .expect("failed to create binary archive");
+        assert_eq!("x86_64", archive.architecture);
+        assert!(!dd.path().join("bootstrap.zip.tmp").exists());
+
+        let file = File::open(&archive.path).expect("failed to open zip file");
+        let mut zip = ZipArchive::new(file).expect("failed to open zip archive");
+
+        let bootstrap = zip.by_name("bootstrap").unwrap();
+        assert_eq!(zip::CompressionMethod::Stored, bootstrap.compression());
+        drop(bootstrap);
+
+        let mut asset = zip.by_name("asset.txt").unwrap();
+        assert_eq!(zip::CompressionMethod::Deflated, asset.compression());
+        let mut content = String::new();
+        asset.read_to_string(&mut content).unwrap();
+        assert_eq!("new content", content);
+        drop(asset);
+        drop(zip);
+
+        let archive = zip_binary(bp, dd.path(), &data, include, None, false, false)
+            .expect("failed to create binary archive");
+        let file = File::open(&archive.path).expect("failed to open zip file");
+        let mut zip = ZipArchive::new(file).expect("failed to open zip archive");
+        let bootstrap = zip.by_name("bootstrap").unwrap();
+        assert_eq!(zip::CompressionMethod::Deflated, bootstrap.compression());
+    }
+
+    #[test]
+    fn test_include_directory_with_unicode_path() {
+        let data = BinaryData::new("binary-x86-64", false, false);
+
+        let bp = "../../tests/binaries/binary-x86-64";
+        let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
+        let data_dir = dd.path().join("proyectos de José").join("data");
+        std::fs::create_dir_all(&data_dir).expect("failed to create data dir");
+        std::fs::write(data_dir.join("metadata.json"), "{}").expect("failed to write asset");
+
+        let include = Some(vec![format!("files:{}", data_dir.display())]);
+        let archive = zip_binary(bp, dd.path(), &data, include, None, false, false)
+            .expect("failed to create binary archive");
+
+        let file = File::open(&archive.path).expect("failed to open zip file");
+        let zip = ZipArchive::new(file).expect("failed to open zip archive");
+        let mut names = zip.file_names().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(vec!["bootstrap", "files/", "files/metadata.json"], names);
+    }
+
+    #[test]
+    fn test_fast_package() {
+        let data = BinaryData::new("binary-x86-64", false, false);
+
+        let bp = "../../tests/binaries/binary-x86-64";
+        let default_dir = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
+        let fast_dir = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
+
+        let default = zip_binary(bp, default_dir.path(), &data, None, None, false, false)
+            .expect("failed to create binary archive");
+        let fast = zip_binary(bp, fast_dir.path(), &data, None, None, false, true)
+            .expect("failed to create binary archive");
+        assert_eq!(default.architecture, fast.architecture);
+        assert_eq!(default.glibc_version, fast.glibc_version);
+
+        let file = File::open(&default.path).expect("failed to open zip file");
+        let mut zip = ZipArchive::new(file).expect("failed to open zip archive");
+        let default_size = zip.by_name("bootstrap").unwrap().compressed_size();
+
+        let file = File::open(&fast.path).expect("failed to open zip file");
+        let mut zip = ZipArchive::new(file).expect("failed to open zip archive");
+        let mut bootstrap = zip.by_name("bootstrap").unwrap();
+        assert!(bootstrap.compressed_size() >= default_size);
+
+        let mut content = Vec::new();
+        bootstrap.read_to_end(&mut content).unwrap();
+        assert_eq!(read(bp).unwrap(), content);
+    }
+
     #[test]
     fn test_create_binary_archive_with_base_path() {
         let data = BinaryData::new("binary-x86-64", false, false);
@@ -682,8 +1422,9 @@ mod test {
         create_dir_all(&bsp).expect("failed to create dir");
         copy_without_replace(bp, bsp.join("bootstrap")).expect("failed to copy bootstrap file");
 
-        let archive = create_binary_archive(None, &Some(dd.path()), &data, None)
-            .expect("failed to create binary archive");
+        let archive =
+            create_binary_archive(None, &Some(dd.path()), &data, None, None, false, false)
+                .expect("failed to create binary archive");
 
         let arch_path = bsp.join("bootstrap.zip");
         assert_eq!(arch_path, archive.path);
@@ -710,8 +1451,9 @@ mod test {
         copy_without_replace(bp, bsp.join("bootstrap")).expect("failed to copy bootstrap file");
 
         let base_dir: Option<&Path> = None;
-        let archive = create_binary_archive(Some(&metadata), &base_dir, &data, None)
-            .expect("failed to create binary archive");
+        let archive =
+            create_binary_archive(Some(&metadata), &base_dir, &data, None, None, false, false)
+                .expect("failed to create binary archive");
 
         let arch_path = bsp.join("bootstrap.zip");
         assert_eq!(arch_path, archive.path);
@@ -736,8 +1478,8 @@ mod test {
         let extra = vec!["source:..\\..\\tests\\fixtures\\examples-package".into()];
 
         let dd = TempDir::with_prefix("cargo-lambda-").expect("failed to create temp dir");
-        let archive =
-            zip_binary(bp, dd.path(), &data, Some(extra)).expect("failed to create binary archive");
+        let archive = zip_binary(bp, dd.path(), &data, Some(extra), None, false, false)
+            .expect("failed to create binary archive");
 
         let arch_path = dd.path().join("bootstrap.zip");
         assert_eq!(arch_path, archive.path);
@@ -767,10 +1509,10 @@ mod test {
     fn test_extract_data_from_zipped_bootstrap(#[case] name: &str, #[case] binary_path: &str) {
         let zip_path = format!("../../tests/binaries/{name}");
         let zip_path = Path::new(&zip_path);
-        let (arch, binary_modified_at) =
+        let (info, binary_modified_at) =
             extract_data_from_zipped_binary(zip_path, binary_path).unwrap();
 
-        assert_eq!("x86_64", arch);
+        assert_eq!("x86_64", info.arch);
         assertables::assert_some!(binary_modified_at.0);
     }
 }
@@ -0,0 +1,65 @@
+use chrono::{SecondsFormat, Utc};
+use std::{collections::BTreeMap, path::Path, process::Command};
+use tracing::debug;
+
+/// Environment variable with the git commit of the project.
+pub const GIT_SHA: &str = "GIT_SHA";
+
+/// Environment variable with the time when the build started.
+pub const BUILD_TIME: &str = "BUILD_TIME";
+
+/// Environment variable with the version of Cargo Lambda that built the binaries.
+pub const CARGO_LAMBDA_VERSION: &str = "CARGO_LAMBDA_VERSION";
+
+/// Information about the build that is set in the compiler's environment,
+/// so the binaries can embed it with `env!` or `option_env!`.
+/// The git commit is skipped when the project is not in a git repository.
+pub fn build_info(workspace_root: &Path) -> BTreeMap<String, String> {
+    let mut info = BTreeMap::new();
+
+    if let Some(sha) = git_sha(workspace_root) {
+        info.insert(GIT_SHA.to_string(), sha);
+    }
+    info.insert(
+        BUILD_TIME.to_string(),
+        Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    );
+    info.insert(
+        CARGO_LAMBDA_VERSION.to_string(),
+        env!("CARGO_PKG_VERSION").to_string(),
+    );
+
+    debug!(?info, "build info");
+    info
+}
+
+fn git_sha(workspace_root: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(workspace_root)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        debug!(?workspace_root, "the project is not in a git repository");
+        return None;
+    }
+
+    let sha = String::from_utf8(output.stdout).ok()?;
+    let sha = sha.trim();
+    (!sha.is_empty()).then(|| sha.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info_outside_git_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let info = build_info(dir.path());
+
+        assert!(!info.contains_key(GIT_SHA));
+        assert_eq!(env!("CARGO_PKG_VERSION"), info[CARGO_LAMBDA_VERSION]);
+        assert!(chrono::DateTime::parse_from_rfc3339(&info[BUILD_TIME]).is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::TargetArch;
+use crate::{TargetArch, error::BuildError};
 use cargo_lambda_metadata::cargo::CargoMetadata;
 use cargo_options::Build;
 use cargo_zigbuild::Build as ZigBuild;
@@ -14,17 +14,35 @@ impl CargoZigbuild {
         target_arch: &TargetArch,
         _cargo_metadata: &CargoMetadata,
         skip_target_check: bool,
+        zig_version: Option<&str>,
+        allow_unsigned_zig: bool,
+        offline: bool,
     ) -> Result<Command> {
         tracing::debug!("compiling with CargoZigbuild");
-        crate::zig::check_installation().await?;
+        let zig_env = match zig_version {
+            Some(version) => {
+                crate::zig::use_zig_version(version, allow_unsigned_zig, offline).await?
+            }
+            // Builds in offline mode cannot offer to install Zig.
+            None if offline => {
+                crate::zig::find_zig().map_err(|_| BuildError::ZigMissingOffline("Zig".into()))?;
+                Vec::new()
+            }
+            None => {
+                crate::zig::check_installation().await?;
+                Vec::new()
+            }
+        };
 
         // confirm that target component is included in host toolchain, or add
         // it with `rustup` otherwise.
         if !skip_target_check {
-            crate::toolchain::check_target_component_with_rustc_meta(target_arch).await?;
+            crate::toolchain::check_target_component_with_rustc_meta(target_arch, offline).await?;
         }
 
         let zig_build: ZigBuild = cargo.to_owned().into();
-        zig_build.build_command().map_err(|e| miette::miette!(e))
+        let mut cmd = zig_build.build_command().map_err(|e| miette::miette!(e))?;
+        cmd.envs(zig_env);
+        Ok(cmd)
     }
 }
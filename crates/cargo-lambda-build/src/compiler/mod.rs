@@ -11,16 +11,29 @@ use cargo_zigbuild::CargoZigbuild;
 mod cross;
 use cross::Cross;
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn build_command(
     compiler: &CompilerOptions,
     cargo: &Build,
     target_arch: &TargetArch,
     cargo_metadata: &CargoMetadata,
     skip_target_check: bool,
+    zig_version: Option<&str>,
+    allow_unsigned_zig: bool,
+    offline: bool,
 ) -> Result<Command> {
     match compiler {
         CompilerOptions::CargoZigbuild => {
-            CargoZigbuild::command(cargo, target_arch, cargo_metadata, skip_target_check).await
+            CargoZigbuild::command(
+                cargo,
+                target_arch,
+                cargo_metadata,
+                skip_target_check,
+                zig_version,
+                allow_unsigned_zig,
+                offline,
+            )
+            .await
         }
         CompilerOptions::Cargo(opts) => Cargo::command(cargo, opts).await,
         CompilerOptions::Cross => Cross::command(cargo, target_arch, cargo_metadata).await,
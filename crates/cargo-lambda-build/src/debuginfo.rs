@@ -0,0 +1,126 @@
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::{
+    ffi::OsString,
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing::debug;
+
+use crate::error::BuildError;
+
+/// Tools that can split the debug information from the binaries, in order of preference.
+/// `rust-objcopy` and `llvm-objcopy` understand binaries for every architecture
+/// that Lambda supports, GNU's `objcopy` might only understand the host's architecture.
+const OBJCOPY_TOOLS: [&str; 3] = ["rust-objcopy", "llvm-objcopy", "objcopy"];
+
+/// Path of the file with the debug information of a binary.
+pub fn debug_artifact_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{name}.debug"))
+}
+
+/// Move the debug information of a binary into a separate file in `dir`,
+/// and create a stripped copy of the binary next to it to package.
+/// The stripped binary keeps a link to the debug file, so debuggers can find it.
+/// It returns the path of the stripped binary.
+pub(crate) fn split_debuginfo(binary: &Path, name: &str, dir: &Path) -> Result<PathBuf> {
+    create_dir_all(dir)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("error creating debug info directory {dir:?}"))?;
+
+    let objcopy = find_objcopy()?;
+    let debug_artifact = debug_artifact_path(dir, name);
+    let stripped = binary.with_file_name(format!("{name}.stripped"));
+    debug!(?objcopy, ?debug_artifact, ?stripped, "splitting debug info");
+
+    let mut only_debug = Command::new(&objcopy);
+    only_debug
+        .arg("--only-keep-debug")
+        .arg(binary)
+        .arg(&debug_artifact);
+    run_objcopy(only_debug)?;
+
+    let mut debuglink = OsString::from("--add-gnu-debuglink=");
+    debuglink.push(&debug_artifact);
+
+    let mut strip = Command::new(&objcopy);
+    strip
+        .arg("--strip-all")
+        .arg(debuglink)
+        .arg(binary)
+        .arg(&stripped);
+    run_objcopy(strip)?;
+
+    Ok(stripped)
+}
+
+fn run_objcopy(mut cmd: Command) -> Result<()> {
+    let output = cmd
+        .output()
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to run {cmd:?}"))?;
+    if !output.status.success() {
+        return Err(BuildError::FailedDebugInfoSplit(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Find a tool to split the debug information in the PATH.
+fn find_objcopy() -> Result<PathBuf> {
+    OBJCOPY_TOOLS
+        .iter()
+        .find_map(|tool| which::which(tool).ok())
+        .ok_or_else(|| BuildError::ObjcopyMissing.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::{Object, ObjectSection, read::File as ObjectFile};
+
+    fn sections(path: &Path) -> Vec<String> {
+        let data = std::fs::read(path).unwrap();
+        let object = ObjectFile::parse(&*data).unwrap();
+        object
+            .sections()
+            .filter_map(|s| s.name().ok().map(String::from))
+            .collect()
+    }
+
+    #[test]
+    fn test_debug_artifact_path() {
+        assert_eq!(
+            PathBuf::from("target/debuginfo/basic-lambda.debug"),
+            debug_artifact_path(Path::new("target/debuginfo"), "basic-lambda")
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_split_debuginfo() {
+        if find_objcopy().is_err() {
+            eprintln!("skipping test, objcopy is not installed");
+            return;
+        }
+
+        // The test binary is built with symbols, so it can be split.
+        let tmp = tempfile::tempdir().unwrap();
+        let binary = tmp.path().join("basic-lambda");
+        std::fs::copy(std::env::current_exe().unwrap(), &binary).unwrap();
+
+        let dir = tmp.path().join("debuginfo");
+        let stripped = split_debuginfo(&binary, "basic-lambda", &dir).unwrap();
+        assert_eq!(tmp.path().join("basic-lambda.stripped"), stripped);
+
+        let debug_artifact = debug_artifact_path(&dir, "basic-lambda");
+        assert!(sections(&debug_artifact).contains(&".symtab".to_string()));
+
+        let stripped_sections = sections(&stripped);
+        assert!(stripped_sections.contains(&".gnu_debuglink".to_string()));
+        assert!(!stripped_sections.contains(&".symtab".to_string()));
+        assert!(!stripped_sections.contains(&".debug_info".to_string()));
+    }
+}
@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use cargo_lambda_metadata::error::MetadataError;
+use cargo_lambda_metadata::{cargo::build::OutputLayout, error::MetadataError};
 use miette::Diagnostic;
 use object::Architecture;
 use thiserror::Error;
@@ -18,9 +18,16 @@ pub(crate) enum BuildError {
     #[error("install Zig and run cargo-lambda again")]
     #[diagnostic()]
     ZigMissing,
-    #[error("binary target is missing from this project: {0}")]
-    #[diagnostic()]
-    FunctionBinaryMissing(String),
+    #[error("{0} is not installed, and builds in offline mode cannot download it")]
+    #[diagnostic(help("install Zig, or run the build without `--offline`"))]
+    ZigMissingOffline(String),
+    #[error(
+        "the target component `{0}` is not installed, and builds in offline mode cannot download it"
+    )]
+    #[diagnostic(help(
+        "add it with `rustup target add {0}`, or run the build without `--offline`"
+    ))]
+    TargetComponentMissingOffline(String),
     #[error("binary file for {0} not found, use `cargo lambda {1}` to create it")]
     #[diagnostic()]
     BinaryMissing(String, String),
@@ -30,9 +37,71 @@ pub(crate) enum BuildError {
     #[error("invalid or unsupported target for AWS Lambda: {0}")]
     #[diagnostic()]
     UnsupportedTarget(String),
+    #[error("packages over the size limits: {0}")]
+    #[diagnostic(help(
+        "make the packages smaller, raise the limits with `--max-zip-size` and `--max-unzipped-size`, or use `--warn-size-limits` to only warn about them"
+    ))]
+    SizeLimitsExceeded(String),
+    #[error("invalid bootstrap script: {0}")]
+    #[diagnostic(help(
+        "the script must start with a shebang line, like `#!/bin/sh`, so Lambda knows how to run it"
+    ))]
+    InvalidBootstrapScript(PathBuf),
+    #[error("the binaries {0:?} and {1:?} are built for the same architecture, {2}")]
+    #[diagnostic(help(
+        "build the extension once for each architecture, with `--x86-64` and `--arm64`"
+    ))]
+    DuplicatedBinaryArchitecture(PathBuf, PathBuf, String),
+    #[error("only extensions can include binaries for several architectures")]
+    #[diagnostic()]
+    MultiArchFunction,
     #[error("invalid unix file name: {0}")]
     #[diagnostic()]
     InvalidUnixFileName(PathBuf),
+    #[error("objcopy is not installed, it's required to split the debug information")]
+    #[diagnostic(help(
+        "install `llvm-objcopy` or `objcopy` with your system's package manager, or `rust-objcopy` with `cargo install cargo-binutils`"
+    ))]
+    ObjcopyMissing,
+    #[error("failed to split the debug information from the binary: {0}")]
+    #[diagnostic()]
+    FailedDebugInfoSplit(String),
+    #[error(
+        "the {0} output layout only includes the binary, it cannot be combined with --output-format zip"
+    )]
+    #[diagnostic(help(
+        "remove `--output-format zip`, the tool that uses the artifact packages it"
+    ))]
+    ZipOutputLayout(OutputLayout),
+    #[error(
+        "the {0} output layout only includes one function, but the build includes several: {1:?}"
+    )]
+    #[diagnostic(help("pick the function to build with `--bin`"))]
+    MultipleFunctionsOutputLayout(OutputLayout, Vec<String>),
+    #[error("{} targets failed to build: {}", .0.len(), .0.join(", "))]
+    #[diagnostic(help(
+        "the targets that built successfully were packaged, check the compiler errors above to fix the rest"
+    ))]
+    FailedTargets(Vec<String>),
+    #[error("the SHA256 checksum of {url} is {actual}, but Zig's release index expects {expected}")]
+    #[diagnostic(help(
+        "the download was corrupted or modified, try again, or check if a proxy in your network changes the downloads"
+    ))]
+    ZigChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("failed to verify the signature of {url}: {reason}")]
+    #[diagnostic(help(
+        "Zig releases are signed with the key published in https://ziglang.org/download/, the download might have been modified"
+    ))]
+    ZigSignatureMismatch { url: String, reason: String },
+    #[error("the Zig release {url} doesn't have a signature")]
+    #[diagnostic(help(
+        "use `--allow-unsigned-zig` to download Zig versions that were released without a signature"
+    ))]
+    ZigSignatureMissing { url: String },
     #[error(transparent)]
     #[diagnostic()]
     FailedBuildCommand(#[from] std::io::Error),
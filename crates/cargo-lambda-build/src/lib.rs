@@ -1,33 +1,66 @@
 use cargo_lambda_interactive::{error::InquireError, is_user_cancellation_error};
 use cargo_lambda_metadata::{
     cargo::{
-        CargoMetadata, binary_targets_from_metadata,
-        build::{Build, OutputFormat},
-        cargo_release_profile_config, target_dir_from_metadata,
+        CargoMetadata, STRIP_CONFIG,
+        build::{Build, OutputFormat, OutputLayout},
+        cargo_config::{CargoConfig, sets_target_cpu},
+        cargo_release_profile_config,
+        features::missing_required_features,
+        target_dir_from_metadata,
     },
     fs::copy_and_replace,
 };
 use miette::{IntoDiagnostic, Report, Result, WrapErr};
+use serde_json::Value;
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     fs::create_dir_all,
+    io::{BufRead, BufReader},
     path::{Path, PathBuf},
+    process::Stdio,
     str::FromStr,
 };
 use target_arch::TargetArch;
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 
 pub use cargo_zigbuild::Zig;
 
+/// Environment variable with the directory where SAM's custom builds expect the artifacts.
+const SAM_ARTIFACTS_DIR: &str = "ARTIFACTS_DIR";
+
+/// Directory where CDK's asset bundling expects the artifacts inside its containers.
+const CDK_ASSET_OUTPUT: &str = "/asset-output";
+
+mod analysis;
+use analysis::write_analysis;
+pub use analysis::{BinaryAnalysis, CrateSize, SymbolSize};
+
 mod archive;
-pub use archive::{BinaryArchive, BinaryData, BinaryModifiedAt, create_binary_archive, zip_binary};
+use archive::copy_bootstrap_script;
+pub use archive::{
+    BinaryArchive, BinaryData, BinaryModifiedAt, GlibcVersion, create_binary_archive, zip_binary,
+    zip_multi_arch_extension,
+};
+
+mod build_info;
+pub use build_info::{BUILD_TIME, CARGO_LAMBDA_VERSION, GIT_SHA, build_info};
 
 mod compiler;
 use compiler::{build_command, build_profile};
 
+mod debuginfo;
+pub use debuginfo::debug_artifact_path;
+use debuginfo::split_debuginfo;
+
 mod error;
 use error::BuildError;
 
+mod manifest;
+pub use manifest::{BuildManifest, MANIFEST_FILE, ManifestArtifact};
+
+mod size_report;
+pub use size_report::{SectionSize, SizeReport, check_size_limits};
+
 mod target_arch;
 use target_arch::validate_linux_target;
 
@@ -36,14 +69,17 @@ use toolchain::rustup_cmd;
 
 mod zig;
 pub use zig::{
-    InstallOption, check_installation, install_options, install_zig, print_install_options,
+    InstallOption, check_installation, find_zig, init_zig_env, install_options, install_zig,
+    print_install_options, zig_installation,
 };
 
 #[tracing::instrument(skip(build, metadata), target = "cargo_lambda")]
 pub async fn run(build: &mut Build, metadata: &CargoMetadata) -> Result<()> {
     tracing::trace!(options = ?build, "building project");
 
-    if (build.arm64 || build.x86_64) && !build.cargo_opts.target.is_empty() {
+    if (build.arm64 || build.x86_64) && !build.cargo_opts.target.is_empty()
+        || build.arm64 && build.x86_64
+    {
         Err(BuildError::InvalidTargetOptions)?;
     }
 
@@ -64,24 +100,13 @@ pub async fn run(build: &mut Build, metadata: &CargoMetadata) -> Result<()> {
 
     build.cargo_opts.target = vec![target_arch.to_string()];
 
-    let build_examples = build.cargo_opts.examples || !build.cargo_opts.example.is_empty();
-    let binaries = binary_targets_from_metadata(metadata, build_examples);
+    let selection = build.binary_selection();
+    let binaries = selection.select(metadata)?;
     debug!(binaries = ?binaries, "found new target binaries to build");
 
-    let binaries = if !build.cargo_opts.bin.is_empty() {
-        let mut final_binaries = HashSet::with_capacity(binaries.len());
-
-        for name in &build.cargo_opts.bin {
-            if !binaries.contains(name) {
-                return Err(BuildError::FunctionBinaryMissing(name.into()).into());
-            }
-            final_binaries.insert(name.into());
-        }
-
-        final_binaries
-    } else {
-        binaries
-    };
+    let binaries = enable_required_features(build, metadata, binaries);
+    check_output_layout(build, &binaries)?;
+    let size_limits = build.size_limits.limits(false)?;
 
     let compiler_option = build.compiler.clone().unwrap_or_default();
     if compiler_option.is_local_cargo() {
@@ -93,9 +118,23 @@ pub async fn run(build: &mut Build, metadata: &CargoMetadata) -> Result<()> {
         }
     }
 
-    if is_release_profile(build) && !build.disable_optimizations {
-        let release_optimizations =
-            cargo_release_profile_config(metadata).map_err(BuildError::MetadataError)?;
+    let cargo_config = std::env::current_dir()
+        .map(|dir| CargoConfig::load(&dir))
+        .unwrap_or_default();
+
+    let release_optimizations = if build.disable_optimizations {
+        None
+    } else {
+        release_optimizations(build, metadata)?
+    };
+
+    if let Some(mut release_optimizations) = release_optimizations {
+        // The analysis needs the symbols to attribute the code to crates and functions,
+        // and the split debug info needs them to symbolicate stack traces.
+        if build.analyze || build.split_debuginfo_out.is_some() {
+            release_optimizations.remove(STRIP_CONFIG);
+        }
+
         build.cargo_opts.config.extend(
             release_optimizations
                 .into_iter()
@@ -103,15 +142,47 @@ pub async fn run(build: &mut Build, metadata: &CargoMetadata) -> Result<()> {
                 .collect::<Vec<_>>(),
         );
 
-        let build_flags = format!(
-            "build.rustflags=[\"-C\", \"target-cpu={}\"]",
-            target_arch.target_cpu()
-        );
-        build.cargo_opts.config.push(build_flags);
+        // Keep the CPU that the project configures for the target, and
+        // let Cargo apply the other flags with its usual precedence.
+        let rustflags = cargo_config.rustflags(target_arch.rustc_target_without_glibc_version());
+        if sets_target_cpu(&rustflags) {
+            debug!(
+                ?rustflags,
+                "keeping the target CPU in the Cargo configuration"
+            );
+        } else {
+            let build_flags = format!(
+                "build.rustflags=[\"-C\", \"target-cpu={}\"]",
+                target_arch.target_cpu()
+            );
+            build.cargo_opts.config.push(build_flags);
+        }
 
         debug!(config = ?build.cargo_opts.config, "release optimizations");
     }
 
+    if build.split_debuginfo_out.is_some() {
+        let profile = cargo_profile(build).to_string();
+        build.cargo_opts.config.extend([
+            format!("profile.{profile}.debug=true"),
+            format!("profile.{profile}.strip=\"none\""),
+        ]);
+    }
+
+    let keep_going = build.cargo_opts.common.keep_going;
+    let json_messages = build.json_message_format();
+    if keep_going && !json_messages {
+        // Cargo reports the binaries that it builds in its JSON messages,
+        // and keeps rendering the compiler diagnostics for humans.
+        build
+            .cargo_opts
+            .message_format
+            .push("json-render-diagnostics".into());
+    }
+
+    let offline =
+        build.cargo_opts.common.offline || build.cargo_opts.common.frozen || cargo_config.offline;
+
     let profile = build_profile(&build.cargo_opts, &compiler_option);
     let skip_target_check = build.skip_target_check || which::which(rustup_cmd()).is_err();
     let cmd = build_command(
@@ -120,6 +191,9 @@ pub async fn run(build: &mut Build, metadata: &CargoMetadata) -> Result<()> {
         &target_arch,
         metadata,
         skip_target_check,
+        build.zig_version.as_deref(),
+        build.allow_unsigned_zig,
+        offline,
     )
     .await;
 
@@ -129,30 +203,58 @@ pub async fn run(build: &mut Build, metadata: &CargoMetadata) -> Result<()> {
         Err(err) => return Err(err),
     };
 
+    let build_info = if build.embed_build_info {
+        build_info(metadata.workspace_root.as_std_path())
+    } else {
+        BTreeMap::new()
+    };
+    cmd.envs(&build_info);
+
+    if keep_going {
+        cmd.stdout(Stdio::piped());
+    }
     let mut child = cmd.spawn().map_err(BuildError::FailedBuildCommand)?;
+    let compiled = child
+        .stdout
+        .take()
+        .map(|stdout| compiled_binaries(BufReader::new(stdout), json_messages));
     let status = child.wait().map_err(BuildError::FailedBuildCommand)?;
-    if !status.success() {
+    if !status.success() && !keep_going {
         std::process::exit(status.code().unwrap_or(1));
     }
 
-    // extract resolved target dir from cargo metadata
-    let target_dir = target_dir_from_metadata(metadata).unwrap_or_else(|_| PathBuf::from("target"));
+    // the `--target-dir` flag takes precedence over the target dir
+    // that cargo metadata resolves from the configuration
+    let target_dir = build
+        .cargo_opts
+        .common
+        .target_dir
+        .clone()
+        .or_else(|| target_dir_from_metadata(metadata).ok())
+        .unwrap_or_else(|| PathBuf::from("target"));
     let target_dir = Path::new(&target_dir);
-    let lambda_dir = if let Some(dir) = &build.lambda_dir {
-        dir.clone()
-    } else {
-        target_dir.join("lambda")
-    };
+    let lambda_dir = lambda_dir(build, target_dir);
 
     let mut base = target_dir
         .join(target_arch.rustc_target_without_glibc_version())
         .join(profile);
-    if build_examples {
+    if selection.examples {
         base = base.join("examples");
     }
 
     let mut found_binaries = false;
+    let mut analysis = Vec::new();
+    let mut violations = Vec::new();
+    let mut manifest = BuildManifest::load(&lambda_dir);
+    let mut failed = Vec::new();
     for name in &binaries {
+        // Binaries from a previous build are still in the target directory,
+        // only package the ones that Cargo built this time.
+        if compiled.as_ref().is_some_and(|c| !c.contains(name)) {
+            failed.push(name.clone());
+            continue;
+        }
+
         let binary = base.join(name);
         debug!(binary = ?binary, exists = binary.exists(), "checking function binary");
 
@@ -162,8 +264,9 @@ pub async fn run(build: &mut Build, metadata: &CargoMetadata) -> Result<()> {
             let bootstrap_dir = if build.extension {
                 lambda_dir.join("extensions")
             } else {
-                match build.flatten {
-                    Some(ref n) if n == name => lambda_dir.clone(),
+                match (build.output_layout(), &build.flatten) {
+                    (OutputLayout::Sam | OutputLayout::CdkAsset, _) => lambda_dir.clone(),
+                    (_, Some(n)) if n == name => lambda_dir.clone(),
                     _ => lambda_dir.join(name),
                 }
             };
@@ -173,31 +276,244 @@ pub async fn run(build: &mut Build, metadata: &CargoMetadata) -> Result<()> {
 
             let data = BinaryData::new(name.as_str(), build.extension, build.internal);
 
-            match build.output_format() {
+            let package_binary = match &build.split_debuginfo_out {
+                Some(dir) => split_debuginfo(&binary, name, dir)?,
+                None => binary.clone(),
+            };
+
+            let bootstrap_script = build.bootstrap_script.as_deref();
+
+            let artifact = match build.output_format() {
                 OutputFormat::Binary => {
-                    let output_location = bootstrap_dir.join(data.binary_name());
-                    copy_and_replace(&binary, &output_location)
+                    let output_name = match bootstrap_script {
+                        Some(_) => data.wrapped_binary_name(),
+                        None => data.binary_name(),
+                    };
+                    let output_location = bootstrap_dir.join(output_name);
+                    copy_and_replace(&package_binary, &output_location)
                         .into_diagnostic()
                         .wrap_err_with(|| {
-                            format!("error moving the binary `{binary:?}` into the output location `{output_location:?}`")
+                            format!("error moving the binary `{package_binary:?}` into the output location `{output_location:?}`")
                         })?;
+
+                    if let Some(script) = bootstrap_script {
+                        copy_bootstrap_script(script, &bootstrap_dir)?;
+                    }
+                    output_location
                 }
                 OutputFormat::Zip => {
-                    zip_binary(binary, bootstrap_dir, &data, build.include.clone())?;
+                    zip_binary(
+                        &package_binary,
+                        bootstrap_dir,
+                        &data,
+                        build.include.clone(),
+                        bootstrap_script,
+                        build.incremental_zip,
+                        build.fast_package,
+                    )?
+                    .path
+                }
+            };
+
+            // Only the JSON messages go to the standard output. The reports below
+            // print in the standard error to not interfere with tools that read it.
+            if json_messages {
+                println!("{}", artifact_message(name, build.extension, &artifact));
+            }
+
+            let mut manifest_artifact = ManifestArtifact::new(
+                &lambda_dir,
+                &artifact,
+                build.extension,
+                target_arch.arch().name(),
+                cargo_profile(build),
+            )?;
+            manifest_artifact.build_info = build_info.clone();
+            manifest.insert(name, manifest_artifact);
+
+            // The binary output format moves the binary out of the target directory.
+            let moved = matches!(build.output_format(), OutputFormat::Binary);
+            let package_binary = if moved {
+                artifact.clone()
+            } else {
+                package_binary
+            };
+            let binary = if moved && build.split_debuginfo_out.is_none() {
+                artifact.clone()
+            } else {
+                binary
+            };
+
+            let archive = (!moved).then_some(&artifact);
+            let optimized = !matches!(cargo_profile(build), "dev" | "test");
+            match SizeReport::new(
+                name,
+                &package_binary,
+                archive.map(|a| a.as_path()),
+                optimized,
+            ) {
+                Ok(report) => {
+                    report.print();
+                    violations.extend(report.limit_violations(&size_limits));
                 }
+                Err(error) => warn!(?error, "failed to calculate the size of the package"),
+            }
+
+            if build.analyze {
+                let binary_analysis = BinaryAnalysis::new(name, &binary)?;
+                binary_analysis.print();
+                analysis.push(binary_analysis);
             }
         }
     }
-    if !found_binaries {
+    if !found_binaries && failed.is_empty() {
         warn!(
             ?base,
             "no binaries found in target directory after build, try using the --bin, --example, or --package options to build specific binaries"
         );
+    } else if found_binaries && build.output_layout() == &OutputLayout::CargoLambda {
+        // Other layouts package everything in the directory,
+        // so the manifest would end up in the function's code.
+        manifest.write(&lambda_dir)?;
+    }
+
+    if let Some(path) = &build.analyze_output {
+        write_analysis(path, &analysis)?;
+    }
+
+    if keep_going {
+        print_build_summary(&binaries, &failed);
+    }
+    if !failed.is_empty() {
+        failed.sort();
+        return Err(BuildError::FailedTargets(failed).into());
+    }
+
+    check_size_limits(violations, &size_limits)
+}
+
+/// Names of the binaries that Cargo reports as built in its JSON messages.
+/// The messages are forwarded to the standard output when the build was asked to print them.
+fn compiled_binaries(output: impl BufRead, forward: bool) -> HashSet<String> {
+    let mut binaries = HashSet::new();
+    for line in output.lines().map_while(Result::ok) {
+        let message = serde_json::from_str::<Value>(&line).ok();
+        if forward || message.is_none() {
+            println!("{line}");
+        }
+        if let Some(name) = message.as_ref().and_then(compiled_binary) {
+            binaries.insert(name.to_string());
+        }
+    }
+    binaries
+}
+
+fn compiled_binary(message: &Value) -> Option<&str> {
+    if message["reason"] != "compiler-artifact" || message["executable"].is_null() {
+        return None;
+    }
+    message["target"]["name"].as_str()
+}
+
+/// Print which targets built, and which ones failed.
+fn print_build_summary(binaries: &HashSet<String>, failed: &[String]) {
+    let mut names = binaries.iter().collect::<Vec<_>>();
+    names.sort();
+
+    eprintln!("Build summary:");
+    for name in names {
+        if failed.contains(name) {
+            eprintln!("  ❌ {name}");
+        } else {
+            eprintln!("  ✅ {name}");
+        }
+    }
+}
+
+/// Enable the features that the binaries selected with `--bin` or `--example` require.
+/// Other binaries that require features that are not enabled are skipped,
+/// like Cargo skips them, and the build warns about them.
+fn enable_required_features(
+    build: &mut Build,
+    metadata: &CargoMetadata,
+    mut binaries: HashSet<String>,
+) -> HashSet<String> {
+    for missing in missing_required_features(metadata, &binaries, &build.cargo_opts.common) {
+        let selected = build.cargo_opts.bin.contains(&missing.binary)
+            || build.cargo_opts.example.contains(&missing.binary);
+
+        if selected {
+            info!(
+                binary = missing.binary,
+                features = ?missing.features,
+                "enabling the features that the binary requires"
+            );
+            build
+                .cargo_opts
+                .features
+                .extend(missing.qualified_features());
+        } else {
+            warn!(
+                binary = missing.binary,
+                features = ?missing.features,
+                "skipping binary because it requires features that are not enabled, build it with `--features {}`",
+                missing.features.join(",")
+            );
+            binaries.remove(&missing.binary);
+        }
+    }
+    binaries
+}
+
+/// Directory where the artifacts are placed. Each layout
+/// has its own default, when the directory is not set.
+fn lambda_dir(build: &Build, target_dir: &Path) -> PathBuf {
+    if let Some(dir) = &build.lambda_dir {
+        return dir.clone();
+    }
+
+    match build.output_layout() {
+        OutputLayout::CargoLambda => target_dir.join("lambda"),
+        OutputLayout::Sam => std::env::var_os(SAM_ARTIFACTS_DIR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| target_dir.join("lambda")),
+        OutputLayout::CdkAsset => PathBuf::from(CDK_ASSET_OUTPUT),
+    }
+}
+
+/// SAM and CDK package the directory with the artifacts themselves,
+/// and they expect a single function's binary at the root of it.
+fn check_output_layout(build: &Build, binaries: &HashSet<String>) -> Result<()> {
+    let layout = build.output_layout();
+    if layout == &OutputLayout::CargoLambda {
+        return Ok(());
+    }
+
+    if build.output_format() == &OutputFormat::Zip {
+        return Err(BuildError::ZipOutputLayout(layout.clone()).into());
+    }
+
+    if !build.extension && binaries.len() > 1 {
+        let mut binaries = binaries.iter().cloned().collect::<Vec<_>>();
+        binaries.sort();
+        return Err(BuildError::MultipleFunctionsOutputLayout(layout.clone(), binaries).into());
     }
 
     Ok(())
 }
 
+/// Message that tells tools parsing Cargo's JSON output where the
+/// Lambda artifacts are. It follows the same shape as Cargo's messages,
+/// so tools that don't know about it can skip it by its `reason`.
+fn artifact_message(name: &str, extension: bool, artifact: &Path) -> serde_json::Value {
+    serde_json::json!({
+        "reason": "lambda-artifact",
+        "name": name,
+        "kind": if extension { "extension" } else { "function" },
+        "filenames": [artifact],
+    })
+}
+
 fn downcasted_user_cancellation(err: &Report) -> bool {
     match err.root_cause().downcast_ref::<InquireError>() {
         Some(err) => is_user_cancellation_error(err),
@@ -205,11 +521,164 @@ fn downcasted_user_cancellation(err: &Report) -> bool {
     }
 }
 
-fn is_release_profile(build: &Build) -> bool {
-    build.cargo_opts.release
-        || build
-            .cargo_opts
-            .profile
-            .as_deref()
-            .is_some_and(|p| p == "release")
+/// Optimizations to add to the build when the profile is `release`,
+/// or a custom profile that inherits from `release`.
+fn release_optimizations(
+    build: &Build,
+    metadata: &CargoMetadata,
+) -> Result<Option<HashSet<&'static str>>> {
+    let profile = cargo_profile(build);
+    if matches!(profile, "dev" | "test") {
+        return Ok(None);
+    }
+
+    let optimizations =
+        cargo_release_profile_config(metadata, profile).map_err(BuildError::MetadataError)?;
+    if optimizations.is_none() {
+        debug!(
+            profile,
+            "the profile doesn't inherit from release, skipping optimizations"
+        );
+    }
+    Ok(optimizations)
+}
+
+/// Name of the profile that Cargo uses for the build.
+fn cargo_profile(build: &Build) -> &str {
+    match build.cargo_opts.profile.as_deref() {
+        Some(profile) => profile,
+        None if build.cargo_opts.release => "release",
+        None => "dev",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cargo_lambda_metadata::cargo::load_metadata;
+
+    #[test]
+    fn test_cargo_profile() {
+        let mut build = Build::default();
+        assert_eq!("dev", cargo_profile(&build));
+
+        build.cargo_opts.release = true;
+        assert_eq!("release", cargo_profile(&build));
+
+        build.cargo_opts.profile = Some("lambda-release".into());
+        assert_eq!("lambda-release", cargo_profile(&build));
+    }
+
+    #[test]
+    fn test_compiled_binaries() {
+        let output = r#"{"reason":"compiler-artifact","target":{"kind":["lib"],"name":"shared"},"executable":null}
+{"reason":"compiler-artifact","target":{"kind":["bin"],"name":"basic-lambda-1"},"executable":"/target/debug/basic-lambda-1"}
+{"reason":"compiler-message","target":{"kind":["bin"],"name":"basic-lambda-2"}}
+{"reason":"build-finished","success":false}
+"#;
+
+        let binaries = compiled_binaries(output.as_bytes(), false);
+        assert_eq!(HashSet::from(["basic-lambda-1".to_string()]), binaries);
+    }
+
+    #[test]
+    fn test_artifact_message() {
+        let message = artifact_message(
+            "basic-lambda",
+            false,
+            Path::new("target/lambda/basic-lambda/bootstrap.zip"),
+        );
+        assert_eq!(
+            serde_json::json!({
+                "reason": "lambda-artifact",
+                "name": "basic-lambda",
+                "kind": "function",
+                "filenames": ["target/lambda/basic-lambda/bootstrap.zip"],
+            }),
+            message
+        );
+
+        let message = artifact_message("logs", true, Path::new("target/lambda/extensions/logs"));
+        assert_eq!(message["kind"], "extension");
+    }
+
+    #[test]
+    fn test_lambda_dir() {
+        let target_dir = Path::new("target");
+        let mut build = Build::default();
+        assert_eq!(
+            PathBuf::from("target/lambda"),
+            lambda_dir(&build, target_dir)
+        );
+
+        build.output_layout = Some(OutputLayout::CdkAsset);
+        assert_eq!(
+            PathBuf::from("/asset-output"),
+            lambda_dir(&build, target_dir)
+        );
+
+        build.lambda_dir = Some(PathBuf::from("cdk.out/asset"));
+        assert_eq!(
+            PathBuf::from("cdk.out/asset"),
+            lambda_dir(&build, target_dir)
+        );
+    }
+
+    #[test]
+    fn test_check_output_layout() {
+        let binaries = HashSet::from(["basic-lambda".to_string(), "other-lambda".to_string()]);
+        let single = HashSet::from(["basic-lambda".to_string()]);
+
+        let mut build = Build::default();
+        assert!(check_output_layout(&build, &binaries).is_ok());
+
+        build.output_layout = Some(OutputLayout::Sam);
+        assert!(check_output_layout(&build, &single).is_ok());
+
+        let err = check_output_layout(&build, &binaries).unwrap_err();
+        assert_eq!(
+            "the sam output layout only includes one function, but the build includes several: [\"basic-lambda\", \"other-lambda\"]",
+            err.to_string()
+        );
+
+        build.extension = true;
+        assert!(check_output_layout(&build, &binaries).is_ok());
+
+        build.output_format = Some(OutputFormat::Zip);
+        let err = check_output_layout(&build, &single).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("the sam output layout only includes the binary")
+        );
+    }
+
+    #[test]
+    fn test_enable_required_features() {
+        let metadata =
+            load_metadata("../../tests/fixtures/required-features-package/Cargo.toml").unwrap();
+        let binaries = Build::default()
+            .binary_selection()
+            .select(&metadata)
+            .unwrap();
+
+        let mut build = Build::default();
+        let selected = enable_required_features(&mut build, &metadata, binaries.clone());
+        assert_eq!(
+            HashSet::from(["basic-lambda".to_string(), "default-lambda".to_string()]),
+            selected
+        );
+        assert!(build.cargo_opts.features.is_empty());
+
+        build.cargo_opts.bin = vec!["featured-lambda".into()];
+        let selected = enable_required_features(
+            &mut build,
+            &metadata,
+            HashSet::from(["featured-lambda".to_string()]),
+        );
+        assert_eq!(HashSet::from(["featured-lambda".to_string()]), selected);
+        assert_eq!(
+            vec!["required-features-package/dynamodb".to_string()],
+            build.cargo_opts.features
+        );
+    }
 }
@@ -0,0 +1,194 @@
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::{File, read},
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+/// Name of the file, in the Lambda directory, with the artifacts of the builds.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Version of the manifest's format. It changes when
+/// the format changes in a way that breaks existing readers.
+const MANIFEST_VERSION: u32 = 1;
+
+/// Artifacts created by the builds, so other tools, like CDK constructs
+/// or Terraform modules, can find them without guessing the directory layout.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct BuildManifest {
+    pub version: u32,
+    /// Artifacts by the name of their binary target
+    pub artifacts: BTreeMap<String, ManifestArtifact>,
+}
+
+impl Default for BuildManifest {
+    fn default() -> Self {
+        BuildManifest {
+            version: MANIFEST_VERSION,
+            artifacts: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ManifestArtifact {
+    /// Whether the artifact is a `function` or an `extension`
+    pub kind: String,
+    /// Path of the artifact, relative to the directory of the manifest
+    pub path: PathBuf,
+    /// Architecture of the binary, `x86_64` or `arm64`
+    pub architecture: String,
+    /// SHA256 of the artifact, encoded as hexadecimal
+    pub sha256: String,
+    /// Cargo profile that built the binary
+    pub profile: String,
+    /// Build information embedded in the binary with `--embed-build-info`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub build_info: BTreeMap<String, String>,
+}
+
+impl ManifestArtifact {
+    pub fn new(
+        lambda_dir: &Path,
+        artifact: &Path,
+        extension: bool,
+        architecture: &str,
+        profile: &str,
+    ) -> Result<ManifestArtifact> {
+        let data = read(artifact)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read artifact `{artifact:?}`"))?;
+
+        let path = artifact
+            .strip_prefix(lambda_dir)
+            .unwrap_or(artifact)
+            .to_path_buf();
+
+        Ok(ManifestArtifact {
+            kind: if extension { "extension" } else { "function" }.to_string(),
+            path,
+            architecture: architecture.to_string(),
+            sha256: format!("{:x}", Sha256::digest(&data)),
+            profile: profile.to_string(),
+            build_info: BTreeMap::new(),
+        })
+    }
+}
+
+impl BuildManifest {
+    /// Load the manifest in the Lambda directory. Builds of some binaries
+    /// keep the artifacts of the binaries built before.
+    /// Manifests that cannot be read are replaced.
+    pub fn load(lambda_dir: &Path) -> BuildManifest {
+        let path = lambda_dir.join(MANIFEST_FILE);
+        let Ok(file) = File::open(&path) else {
+            return BuildManifest::default();
+        };
+
+        match serde_json::from_reader::<_, BuildManifest>(file) {
+            Ok(manifest) if manifest.version == MANIFEST_VERSION => manifest,
+            Ok(manifest) => {
+                debug!(
+                    version = manifest.version,
+                    "replacing manifest with a different version"
+                );
+                BuildManifest::default()
+            }
+            Err(error) => {
+                warn!(
+                    ?error,
+                    ?path,
+                    "failed to read the build manifest, replacing it"
+                );
+                BuildManifest::default()
+            }
+        }
+    }
+
+    pub fn insert(&mut self, name: &str, artifact: ManifestArtifact) {
+        self.artifacts.insert(name.to_string(), artifact);
+    }
+
+    /// Write the manifest in the Lambda directory.
+    pub fn write(&self, lambda_dir: &Path) -> Result<()> {
+        let path = lambda_dir.join(MANIFEST_FILE);
+        let file = File::create(&path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to create manifest file `{path:?}`"))?;
+        serde_json::to_writer_pretty(file, self)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to write manifest file `{path:?}`"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_artifact() {
+        let dir = tempfile::tempdir().unwrap();
+        let artifact = dir.path().join("basic-lambda").join("bootstrap.zip");
+        std::fs::create_dir_all(artifact.parent().unwrap()).unwrap();
+        std::fs::write(&artifact, "hello").unwrap();
+
+        let entry =
+            ManifestArtifact::new(dir.path(), &artifact, false, "arm64", "release").unwrap();
+        assert_eq!(
+            ManifestArtifact {
+                kind: "function".into(),
+                path: PathBuf::from("basic-lambda/bootstrap.zip"),
+                architecture: "arm64".into(),
+                sha256: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".into(),
+                profile: "release".into(),
+                build_info: BTreeMap::new(),
+            },
+            entry
+        );
+
+        let other = tempfile::tempdir().unwrap();
+        let entry = ManifestArtifact::new(other.path(), &artifact, true, "x86_64", "dev").unwrap();
+        assert_eq!("extension", entry.kind);
+        assert_eq!(artifact, entry.path);
+    }
+
+    #[test]
+    fn test_manifest_keeps_previous_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(BuildManifest::default(), BuildManifest::load(dir.path()));
+
+        let artifact = ManifestArtifact {
+            kind: "function".into(),
+            path: PathBuf::from("basic-lambda/bootstrap"),
+            architecture: "x86_64".into(),
+            sha256: "abc".into(),
+            profile: "release".into(),
+            build_info: BTreeMap::from([("GIT_SHA".to_string(), "abc123".to_string())]),
+        };
+
+        let mut manifest = BuildManifest::default();
+        manifest.insert("basic-lambda", artifact.clone());
+        manifest.write(dir.path()).unwrap();
+
+        let mut manifest = BuildManifest::load(dir.path());
+        manifest.insert(
+            "other-lambda",
+            ManifestArtifact {
+                path: PathBuf::from("other-lambda/bootstrap"),
+                ..artifact.clone()
+            },
+        );
+        manifest.write(dir.path()).unwrap();
+
+        let manifest = BuildManifest::load(dir.path());
+        assert_eq!(MANIFEST_VERSION, manifest.version);
+        assert_eq!(2, manifest.artifacts.len());
+        assert_eq!(Some(&artifact), manifest.artifacts.get("basic-lambda"));
+
+        std::fs::write(dir.path().join(MANIFEST_FILE), "not json").unwrap();
+        assert_eq!(BuildManifest::default(), BuildManifest::load(dir.path()));
+    }
+}
@@ -0,0 +1,351 @@
+use cargo_lambda_interactive::progress::HumanBytes;
+use cargo_lambda_metadata::size::{SizeLimits, UNZIPPED_LIMIT, ZIP_UPLOAD_LIMIT};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use object::{Object, ObjectSection, read::File as ObjectFile};
+use serde::Serialize;
+use std::{
+    fs::{File, read},
+    path::Path,
+};
+use tracing::warn;
+use zip::ZipArchive;
+
+use crate::error::BuildError;
+
+/// Percentage of a limit where the report starts warning about it.
+const LIMIT_WARNING_PERCENT: u64 = 80;
+
+/// Number of sections to show in the report.
+const LARGEST_SECTIONS: usize = 5;
+
+/// Size of a section in the binary file.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SectionSize {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Sizes of a package after the build, with the sections
+/// of the binary that contribute the most to them.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SizeReport {
+    pub name: String,
+    /// Size of the zip file, None when the output is the binary
+    pub compressed: Option<u64>,
+    pub uncompressed: u64,
+    /// Largest sections of the binary file
+    pub sections: Vec<SectionSize>,
+    /// Size of the symbol tables
+    pub symbols: u64,
+    /// Size of the debug information
+    pub debug_info: u64,
+    /// Whether the binary was built with optimizations
+    pub optimized: bool,
+}
+
+impl SizeReport {
+    /// Build the report for a binary, and the zip file that contains it, if any.
+    pub fn new(
+        name: &str,
+        binary: &Path,
+        archive: Option<&Path>,
+        optimized: bool,
+    ) -> Result<SizeReport> {
+        let data = read(binary)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read binary file `{binary:?}`"))?;
+        let object = ObjectFile::parse(&*data)
+            .into_diagnostic()
+            .wrap_err("the provided function file is not a valid Linux binary")?;
+
+        let mut report = SizeReport {
+            name: name.to_string(),
+            uncompressed: data.len() as u64,
+            optimized,
+            ..Default::default()
+        };
+
+        for section in object.sections() {
+            let Some((_, size)) = section.file_range() else {
+                continue;
+            };
+            let name = section.name().unwrap_or_default();
+            if name.starts_with(".debug_") {
+                report.debug_info += size;
+            } else if matches!(name, ".symtab" | ".strtab") {
+                report.symbols += size;
+            }
+
+            if size > 0 && !name.is_empty() {
+                report.sections.push(SectionSize {
+                    name: name.to_string(),
+                    size,
+                });
+            }
+        }
+        report.sections.sort_by_key(|s| std::cmp::Reverse(s.size));
+        report.sections.truncate(LARGEST_SECTIONS);
+
+        if let Some(archive) = archive {
+            report.compressed = Some(
+                archive
+                    .metadata()
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("failed to read zip file `{archive:?}`"))?
+                    .len(),
+            );
+            report.uncompressed = uncompressed_size(archive)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Build the report for a zip file, without the details of the binary inside it.
+    pub fn from_archive(name: &str, archive: &Path) -> Result<SizeReport> {
+        let compressed = archive
+            .metadata()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read zip file `{archive:?}`"))?
+            .len();
+
+        Ok(SizeReport {
+            name: name.to_string(),
+            compressed: Some(compressed),
+            uncompressed: uncompressed_size(archive)?,
+            ..Default::default()
+        })
+    }
+
+    /// Sizes of the package that are over the limits.
+    pub fn limit_violations(&self, limits: &SizeLimits) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        let zip = self.compressed.zip(limits.zip);
+        if let Some((compressed, limit)) = zip.filter(|(size, limit)| size > limit) {
+            violations.push(format!(
+                "the zip file of {} is {}, over the {} limit",
+                self.name,
+                HumanBytes(compressed),
+                HumanBytes(limit)
+            ));
+        }
+        if self.uncompressed > limits.unzipped {
+            violations.push(format!(
+                "the uncompressed package of {} is {}, over the {} limit",
+                self.name,
+                HumanBytes(self.uncompressed),
+                HumanBytes(limits.unzipped)
+            ));
+        }
+
+        violations
+    }
+
+    /// Suggestions to make the package smaller, and warnings
+    /// about the package getting close to Lambda's limits.
+    pub fn guidance(&self) -> Vec<String> {
+        let mut guidance = Vec::new();
+
+        if !self.optimized {
+            guidance.push(
+                "the binary was built without optimizations, build it with `--release` for a smaller package and faster cold starts".to_string(),
+            );
+        }
+        if self.debug_info > 0 {
+            guidance.push(format!(
+                "the binary includes {} of debug information, set `debug = false` and `strip = true` in the build profile to remove it",
+                HumanBytes(self.debug_info)
+            ));
+        }
+        if self.symbols > 0 {
+            guidance.push(format!(
+                "the binary includes {} of symbols, set `strip = true` in the build profile to remove them",
+                HumanBytes(self.symbols)
+            ));
+        }
+        if let Some(compressed) = self.compressed {
+            if let Some(usage) = limit_usage(compressed, ZIP_UPLOAD_LIMIT) {
+                guidance.push(format!(
+                    "the zip file uses {usage}% of the {} limit for direct uploads, deploy it with `--s3-bucket` if it grows over the limit",
+                    HumanBytes(ZIP_UPLOAD_LIMIT)
+                ));
+            }
+        }
+        if let Some(usage) = limit_usage(self.uncompressed, UNZIPPED_LIMIT) {
+            guidance.push(format!(
+                "the uncompressed package uses {usage}% of the {} limit for functions and their layers",
+                HumanBytes(UNZIPPED_LIMIT)
+            ));
+        }
+
+        guidance
+    }
+
+    /// Print the size of the package, its largest sections, and how to make it smaller.
+    pub fn print(&self) {
+        match self.compressed {
+            Some(compressed) => eprintln!(
+                "📦 {}: {} zip file, {} uncompressed",
+                self.name,
+                HumanBytes(compressed),
+                HumanBytes(self.uncompressed)
+            ),
+            None => eprintln!("📦 {}: {} binary", self.name, HumanBytes(self.uncompressed)),
+        }
+
+        if !self.sections.is_empty() {
+            let sections = self
+                .sections
+                .iter()
+                .map(|s| format!("{} {}", s.name, HumanBytes(s.size)))
+                .collect::<Vec<_>>();
+            eprintln!("   largest sections: {}", sections.join(", "));
+        }
+
+        for message in self.guidance() {
+            eprintln!("   ⚠️  {message}");
+        }
+    }
+}
+
+/// Fail when packages are over the size limits,
+/// or only warn about them when the limits are warnings.
+pub fn check_size_limits(violations: Vec<String>, limits: &SizeLimits) -> Result<()> {
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    if limits.warn_only {
+        for violation in violations {
+            warn!("{violation}");
+        }
+        return Ok(());
+    }
+
+    Err(BuildError::SizeLimitsExceeded(violations.join(", ")).into())
+}
+
+/// Sum of the uncompressed size of the files in a zip file.
+fn uncompressed_size(archive: &Path) -> Result<u64> {
+    let file = File::open(archive)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to open zip file `{archive:?}`"))?;
+    let mut zip = ZipArchive::new(file).into_diagnostic()?;
+
+    let mut size = 0;
+    for i in 0..zip.len() {
+        size += zip.by_index_raw(i).into_diagnostic()?.size();
+    }
+    Ok(size)
+}
+
+/// Percentage of a limit that a size uses, when it's close to the limit.
+fn limit_usage(size: u64, limit: u64) -> Option<u64> {
+    let usage = size * 100 / limit;
+    (usage >= LIMIT_WARNING_PERCENT).then_some(usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BinaryData, zip_binary};
+
+    #[test]
+    fn test_size_report_for_binary() {
+        let binary = Path::new("../../tests/binaries/binary-x86-64");
+        let report = SizeReport::new("basic-lambda", binary, None, true).unwrap();
+
+        assert_eq!(None, report.compressed);
+        assert_eq!(binary.metadata().unwrap().len(), report.uncompressed);
+        assert_eq!(0, report.debug_info);
+        assert_eq!(0, report.symbols);
+        assert_eq!(LARGEST_SECTIONS, report.sections.len());
+        assert_eq!(".text", report.sections[0].name);
+        assert!(report.sections.windows(2).all(|s| s[0].size >= s[1].size));
+        assert!(report.guidance().is_empty());
+    }
+
+    #[test]
+    fn test_size_report_for_zip_file() {
+        let binary = Path::new("../../tests/binaries/binary-arm64");
+        let dir = tempfile::tempdir().unwrap();
+        let data = BinaryData::new("basic-lambda", false, false);
+        let archive = zip_binary(binary, dir.path(), &data, None, None, false, false).unwrap();
+
+        let report = SizeReport::new("basic-lambda", binary, Some(&archive.path), true).unwrap();
+        let compressed = report.compressed.unwrap();
+        assert_eq!(archive.path.metadata().unwrap().len(), compressed);
+        assert_eq!(binary.metadata().unwrap().len(), report.uncompressed);
+        assert!(compressed < report.uncompressed);
+    }
+
+    #[test]
+    fn test_size_report_guidance() {
+        let report = SizeReport {
+            name: "basic-lambda".into(),
+            compressed: Some(45 * 1024 * 1024),
+            uncompressed: 120 * 1024 * 1024,
+            debug_info: 60 * 1024 * 1024,
+            symbols: 2 * 1024 * 1024,
+            optimized: false,
+            ..Default::default()
+        };
+
+        let guidance = report.guidance();
+        assert_eq!(4, guidance.len());
+        assert!(guidance[0].contains("`--release`"));
+        assert!(guidance[1].contains("60.00 MiB of debug information"));
+        assert!(guidance[2].contains("2.00 MiB of symbols"));
+        assert!(guidance[3].starts_with("the zip file uses 90% of the 50.00 MiB limit"));
+
+        let report = SizeReport {
+            uncompressed: 240 * 1024 * 1024,
+            optimized: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec!["the uncompressed package uses 96% of the 250.00 MiB limit for functions and their layers".to_string()],
+            report.guidance()
+        );
+    }
+
+    #[test]
+    fn test_size_limit_violations() {
+        let report = SizeReport {
+            name: "basic-lambda".into(),
+            compressed: Some(60 * 1024 * 1024),
+            uncompressed: 300 * 1024 * 1024,
+            ..Default::default()
+        };
+
+        let limits = SizeLimits {
+            zip: Some(ZIP_UPLOAD_LIMIT),
+            unzipped: UNZIPPED_LIMIT,
+            warn_only: false,
+        };
+        let violations = report.limit_violations(&limits);
+        assert_eq!(
+            vec![
+                "the zip file of basic-lambda is 60.00 MiB, over the 50.00 MiB limit".to_string(),
+                "the uncompressed package of basic-lambda is 300.00 MiB, over the 250.00 MiB limit"
+                    .to_string(),
+            ],
+            violations
+        );
+        assert!(check_size_limits(violations.clone(), &limits).is_err());
+
+        let warn_only = SizeLimits {
+            warn_only: true,
+            ..limits.clone()
+        };
+        assert!(check_size_limits(violations, &warn_only).is_ok());
+
+        let s3_upload = SizeLimits {
+            zip: None,
+            unzipped: 512 * 1024 * 1024,
+            ..limits
+        };
+        assert!(report.limit_violations(&s3_upload).is_empty());
+        assert!(check_size_limits(Vec::new(), &s3_upload).is_ok());
+    }
+}
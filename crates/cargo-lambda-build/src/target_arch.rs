@@ -16,6 +16,14 @@ pub enum Arch {
 }
 
 impl Arch {
+    /// Name that Lambda uses for the architecture.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Arch::ARM64 => "arm64",
+            Arch::X86_64 => "x86_64",
+        }
+    }
+
     fn target_cpu(&self) -> &'static str {
         match self {
             Arch::ARM64 => "neoverse-n1",
@@ -6,11 +6,14 @@ use miette::{IntoDiagnostic, Result, WrapErr};
 use rustc_version::Channel;
 use std::{env, str};
 
-use crate::target_arch::TargetArch;
+use crate::{error::BuildError, target_arch::TargetArch};
 
 /// Check if the target component is installed in the host toolchain, and add
-/// it with `rustup` as needed.
-pub async fn check_target_component_with_rustc_meta(target_arch: &TargetArch) -> Result<()> {
+/// it with `rustup` as needed. Builds in offline mode fail instead of adding it.
+pub async fn check_target_component_with_rustc_meta(
+    target_arch: &TargetArch,
+    offline: bool,
+) -> Result<()> {
     let component = target_arch.rustc_target_without_glibc_version();
 
     // convert `Channel` enum to a lower-cased string representation
@@ -45,6 +48,10 @@ pub async fn check_target_component_with_rustc_meta(target_arch: &TargetArch) ->
 
     tracing::trace!(target_component_exists, "completed target search");
 
+    if !target_component_exists && offline {
+        return Err(BuildError::TargetComponentMissingOffline(component.to_string()).into());
+    }
+
     if !target_component_exists {
         // install target component using `rustup`
         let pb = Progress::start(format!("Installing target component `{component}`..."));
@@ -96,6 +103,6 @@ mod tests {
     async fn test_check_target_component() -> Result<()> {
         let component = "aarch64-unknown-linux-gnu";
         let arch = TargetArch::from_str(component)?;
-        check_target_component_with_rustc_meta(&arch).await
+        check_target_component_with_rustc_meta(&arch, false).await
     }
 }
@@ -1,9 +1,40 @@
 use crate::error::BuildError;
 use cargo_lambda_interactive::{
-    choose_option, command::silent_command, is_stdin_tty, progress::Progress,
+    choose_option, command::new_command, is_stdin_tty, progress::Progress,
 };
+use cargo_lambda_metadata::net;
 use cargo_zigbuild::Zig;
-use miette::{IntoDiagnostic, Result};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use minisign_verify::{PublicKey, Signature};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, read_dir, remove_dir_all, rename, write},
+    io::{BufReader, Cursor},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tracing::debug;
+
+/// Version of Zig that Cargo Lambda downloads when
+/// it's installed without a package manager.
+const ZIG_DOWNLOAD_VERSION: &str = "0.13.0";
+
+/// Index with the download links and checksums of all Zig releases.
+const ZIG_RELEASE_INDEX: &str = "https://ziglang.org/download/index.json";
+
+/// Variable that tells cargo-zigbuild which Zig binary to use.
+const ZIG_PATH_VAR: &str = "CARGO_ZIGBUILD_ZIG_PATH";
+
+/// Variable that tells cargo-zigbuild which Python to use to find the Zig package.
+const PYTHON_PATH_VAR: &str = "CARGO_ZIGBUILD_PYTHON_PATH";
+
+/// Directory inside each version's directory where Cargo Lambda extracts the release.
+const MANAGED_RELEASE_DIR: &str = "release";
+
+/// Minisign key that signs Zig releases, published in https://ziglang.org/download/.
+const ZIG_PUBLIC_KEY: &str = "RWSGOq2NVecA2UPNdBUZykf1CCb147pkmdtYxgb3Ti+JO/wCYvhbAb/U";
 
 /// Print information about the Zig installation.
 pub fn print_install_options(options: &[InstallOption]) {
@@ -37,7 +68,7 @@ pub async fn install_zig(options: Vec<InstallOption>) -> Result<()> {
 }
 
 pub async fn check_installation() -> Result<()> {
-    if Zig::find_zig().is_ok() {
+    if find_zig().is_ok() {
         return Ok(());
     }
 
@@ -51,30 +82,141 @@ pub async fn check_installation() -> Result<()> {
     install_zig(options).await
 }
 
+/// Find the Zig installation in the system.
+/// If Zig is not installed, it uses the version
+/// downloaded by Cargo Lambda, if there is one.
+pub fn find_zig() -> Result<(PathBuf, Vec<String>)> {
+    if let Ok(zig) = Zig::find_zig() {
+        return Ok(zig);
+    }
+
+    managed_zig_path(ZIG_DOWNLOAD_VERSION)
+        .map(|path| (path, Vec::new()))
+        .ok_or_else(|| BuildError::ZigMissing.into())
+}
+
+/// Point cargo-zigbuild to the Zig that Cargo Lambda downloads when
+/// Zig is not installed in the system. The path is set even if Zig has
+/// not been downloaded yet, so a download during this run is used too.
+///
+/// cargo-zigbuild reads this variable in this process, and not only in
+/// the commands that it runs, so it must be called before the program
+/// starts any other thread.
+pub fn init_zig_env() {
+    if std::env::var_os(ZIG_PATH_VAR).is_some() || which::which("zig").is_ok() {
+        return;
+    }
+
+    let path = managed_zig_path(ZIG_DOWNLOAD_VERSION).or_else(|| {
+        managed_zig_dir().map(|dir| {
+            dir.join(ZIG_DOWNLOAD_VERSION)
+                .join(MANAGED_RELEASE_DIR)
+                .join(zig_binary_name())
+        })
+    });
+
+    if let Some(path) = path {
+        debug!(?path, "using Zig binary");
+        // SAFETY: this function runs before the program starts other threads.
+        unsafe { std::env::set_var(ZIG_PATH_VAR, path) };
+    }
+}
+
+/// Path and version of the Zig installation that builds use, if there is one.
+pub fn zig_installation() -> Option<(PathBuf, String)> {
+    let (path, args) = find_zig().ok()?;
+    let version = installed_version(&path, &args)?;
+    Some((path, version))
+}
+
+/// Build with a specific version of Zig.
+/// If the Zig installation in the system is a different version,
+/// Cargo Lambda downloads the version into its data directory.
+/// Returns the variables that point the build command to that version.
+pub async fn use_zig_version(
+    version: &str,
+    allow_unsigned: bool,
+    offline: bool,
+) -> Result<Vec<(&'static str, PathBuf)>> {
+    if let Ok((path, args)) = Zig::find_zig() {
+        if installed_version(&path, &args).as_deref() == Some(version) {
+            return Ok(Vec::new());
+        }
+    }
+
+    let path = match managed_zig_path(version) {
+        Some(path) => path,
+        None if offline => {
+            return Err(BuildError::ZigMissingOffline(format!("Zig {version}")).into());
+        }
+        None => {
+            let pb = Progress::start(format!("Downloading Zig {version}..."));
+            let result = download_zig(version, allow_unsigned).await;
+            pb.finish_and_clear();
+            result?
+        }
+    };
+    debug!(?path, "using Zig binary");
+
+    // cargo-zigbuild prefers the Zig package for Python over
+    // the Zig binary, point it to a Python that doesn't exist
+    // so it cannot use a different version.
+    let python = path.with_file_name("no-python");
+    Ok(vec![(ZIG_PATH_VAR, path), (PYTHON_PATH_VAR, python)])
+}
+
+fn installed_version(path: &Path, args: &[String]) -> Option<String> {
+    let output = std::process::Command::new(path)
+        .args(args)
+        .arg("version")
+        .output()
+        .ok()?;
+    String::from_utf8(output.stdout)
+        .ok()
+        .map(|v| v.trim().to_string())
+}
+
 pub enum InstallOption {
+    #[cfg(target_os = "linux")]
+    Apt,
     #[cfg(not(windows))]
     Brew,
     #[cfg(windows)]
     Choco,
+    #[cfg(target_os = "linux")]
+    Dnf,
     #[cfg(not(windows))]
     Npm,
     Pip3,
     #[cfg(windows)]
     Scoop,
+    #[cfg(windows)]
+    Winget,
+    Download,
 }
 
 impl std::fmt::Display for InstallOption {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            #[cfg(target_os = "linux")]
+            InstallOption::Apt => write!(f, "Install with APT"),
             #[cfg(not(windows))]
             InstallOption::Brew => write!(f, "Install with Homebrew"),
             #[cfg(windows)]
             InstallOption::Choco => write!(f, "Install with Chocolatey"),
+            #[cfg(target_os = "linux")]
+            InstallOption::Dnf => write!(f, "Install with DNF"),
             #[cfg(not(windows))]
             InstallOption::Npm => write!(f, "Install with NPM"),
             InstallOption::Pip3 => write!(f, "Install with Pip3 (Python 3)"),
             #[cfg(windows)]
             InstallOption::Scoop => write!(f, "Install with Scoop"),
+            #[cfg(windows)]
+            InstallOption::Winget => write!(f, "Install with WinGet"),
+            InstallOption::Download => write!(
+                f,
+                "Download Zig {ZIG_DOWNLOAD_VERSION} into Cargo Lambda's data directory"
+            ),
         }
     }
 }
@@ -82,23 +224,42 @@ impl std::fmt::Display for InstallOption {
 impl InstallOption {
     pub fn usage(&self) -> &'static str {
         match self {
+            #[cfg(target_os = "linux")]
+            InstallOption::Apt => "sudo apt-get install -y zig",
             #[cfg(not(windows))]
             InstallOption::Brew => "brew install zig",
             #[cfg(windows)]
             InstallOption::Choco => "choco install zig",
+            #[cfg(target_os = "linux")]
+            InstallOption::Dnf => "sudo dnf install -y zig",
             #[cfg(not(windows))]
             InstallOption::Npm => "npm install -g @ziglang/cli",
             InstallOption::Pip3 => "pip3 install ziglang",
             #[cfg(windows)]
             InstallOption::Scoop => "scoop install zig",
+            #[cfg(windows)]
+            InstallOption::Winget => "winget install -e --id zig.zig",
+            InstallOption::Download => "cargo lambda system --setup",
         }
     }
 
     pub async fn install(self) -> Result<()> {
+        let usage = self.usage();
+
+        // Commands that run with sudo can ask for a password,
+        // so they use the terminal instead of a progress bar.
+        if usage.starts_with("sudo ") {
+            println!("Running `{usage}`");
+            run_install_command(usage, true).await?;
+            println!("Zig installed");
+            return Ok(());
+        }
+
         let pb = Progress::start("Installing Zig...");
-        let usage = self.usage().split(' ').collect::<Vec<_>>();
-        let usage = usage.as_slice();
-        let result = silent_command(usage[0], &usage[1..usage.len()]).await;
+        let result = match self {
+            InstallOption::Download => download_zig(ZIG_DOWNLOAD_VERSION, false).await.map(|_| ()),
+            _ => run_install_command(usage, false).await,
+        };
 
         let finish = if result.is_ok() {
             "Zig installed"
@@ -111,6 +272,40 @@ impl InstallOption {
     }
 }
 
+/// Run the command of an install option, and fail when the command fails.
+/// Commands that don't use the terminal only show their errors when they fail.
+async fn run_install_command(usage: &str, use_terminal: bool) -> Result<()> {
+    let args = usage.split(' ').collect::<Vec<_>>();
+    let mut cmd = new_command(args[0]);
+    cmd.args(&args[1..]);
+
+    let (status, stderr) = if use_terminal {
+        let status = cmd.status().await;
+        (status, String::new())
+    } else {
+        match cmd.stdin(Stdio::null()).output().await {
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+                (Ok(output.status), stderr)
+            }
+            Err(err) => (Err(err), String::new()),
+        }
+    };
+    let status = status
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to run `{usage}`"))?;
+
+    if status.success() {
+        return Ok(());
+    }
+
+    if stderr.is_empty() {
+        Err(miette::miette!("`{usage}` failed with {status}"))
+    } else {
+        Err(miette::miette!("`{usage}` failed with {status}:\n{stderr}"))
+    }
+}
+
 pub fn install_options() -> Vec<InstallOption> {
     let mut options = Vec::new();
 
@@ -129,6 +324,21 @@ pub fn install_options() -> Vec<InstallOption> {
         options.push(InstallOption::Scoop);
     }
 
+    #[cfg(windows)]
+    if which::which("winget").is_ok() {
+        options.push(InstallOption::Winget);
+    }
+
+    #[cfg(target_os = "linux")]
+    if which::which("apt-get").is_ok() {
+        options.push(InstallOption::Apt);
+    }
+
+    #[cfg(target_os = "linux")]
+    if which::which("dnf").is_ok() {
+        options.push(InstallOption::Dnf);
+    }
+
     if which::which("pip3").is_ok() {
         options.push(InstallOption::Pip3);
     }
@@ -137,5 +347,363 @@ pub fn install_options() -> Vec<InstallOption> {
     if which::which("npm").is_ok() {
         options.push(InstallOption::Npm);
     }
+
+    if zig_host().is_some() {
+        options.push(InstallOption::Download);
+    }
     options
 }
+
+/// Directory where Cargo Lambda keeps the versions of Zig that it downloads.
+fn managed_zig_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("cargo-lambda").join("zig"))
+}
+
+fn zig_binary_name() -> String {
+    format!("zig{}", std::env::consts::EXE_SUFFIX)
+}
+
+/// Path to the Zig binary for a version downloaded by Cargo Lambda.
+/// Releases are extracted in a directory for each version, and
+/// the binary is inside the directory included in the release archive.
+fn managed_zig_path(version: &str) -> Option<PathBuf> {
+    let dir = managed_zig_dir()?.join(version);
+    let binary = zig_binary_name();
+
+    read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().join(&binary))
+        .find(|path| path.is_file())
+}
+
+/// Key for the host in Zig's release index, like `x86_64-linux`.
+/// Returns None if there are no Zig releases for the host.
+fn zig_host() -> Option<String> {
+    release_host(std::env::consts::OS, std::env::consts::ARCH)
+}
+
+fn release_host(os: &str, arch: &str) -> Option<String> {
+    let os = match os {
+        "linux" | "macos" | "windows" | "freebsd" => os,
+        _ => return None,
+    };
+    let arch = match arch {
+        "x86_64" | "aarch64" | "x86" | "riscv64" => arch,
+        _ => return None,
+    };
+    Some(format!("{arch}-{os}"))
+}
+
+/// A Zig release archive for a specific host in Zig's release index.
+#[derive(Debug, Deserialize, PartialEq)]
+struct ZigRelease {
+    tarball: String,
+    shasum: String,
+}
+
+/// Find the release archive for a version and a host in Zig's release index.
+fn find_release(index: &[u8], version: &str, host: &str) -> Result<ZigRelease> {
+    let index: HashMap<String, serde_json::Value> = serde_json::from_slice(index)
+        .into_diagnostic()
+        .wrap_err("failed to parse Zig's release index")?;
+
+    let release = index
+        .get(version)
+        .ok_or_else(|| miette::miette!("Zig version {version} doesn't exist"))?
+        .get(host)
+        .ok_or_else(|| miette::miette!("Zig {version} is not available for {host}"))?;
+
+    serde_json::from_value(release.clone())
+        .into_diagnostic()
+        .wrap_err("failed to parse Zig's release index")
+}
+
+async fn download(url: &str) -> Result<Vec<u8>> {
+    let response = net::client()?
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to download {url}"))?;
+    let data = response
+        .bytes()
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to download {url}"))?;
+    Ok(data.to_vec())
+}
+
+/// Download the minisign signature of a release archive.
+/// Returns None for releases that were not signed.
+async fn download_signature(url: &str) -> Result<Option<String>> {
+    let url = format!("{url}.minisig");
+    let response = net::client()?
+        .get(&url)
+        .send()
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to download {url}"))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    let signature = response
+        .error_for_status()
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to download {url}"))?
+        .text()
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to download {url}"))?;
+    Ok(Some(signature))
+}
+
+/// Check the archive with the checksum in Zig's release index, and with its signature.
+/// Releases without a signature are rejected unless `allow_unsigned` is true.
+fn verify_release(
+    release: &ZigRelease,
+    data: &[u8],
+    signature: Option<&str>,
+    allow_unsigned: bool,
+) -> Result<()> {
+    let actual = format!("{:x}", Sha256::digest(data));
+    if !actual.eq_ignore_ascii_case(&release.shasum) {
+        return Err(BuildError::ZigChecksumMismatch {
+            url: release.tarball.clone(),
+            expected: release.shasum.clone(),
+            actual,
+        }
+        .into());
+    }
+
+    match signature {
+        Some(signature) => verify_signature(ZIG_PUBLIC_KEY, data, signature).map_err(|err| {
+            BuildError::ZigSignatureMismatch {
+                url: release.tarball.clone(),
+                reason: err.to_string(),
+            }
+            .into()
+        }),
+        None if !allow_unsigned => Err(BuildError::ZigSignatureMissing {
+            url: release.tarball.clone(),
+        }
+        .into()),
+        None => {
+            tracing::warn!(
+                url = release.tarball,
+                "the Zig release is not signed, it was only verified with its checksum"
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Verify a file with a signature created by `minisign`, like the ones
+/// that Zig publishes next to its release archives. Only signatures of
+/// the file's hash are accepted, which is what `minisign` creates by default.
+fn verify_signature(
+    public_key: &str,
+    data: &[u8],
+    signature: &str,
+) -> Result<(), minisign_verify::Error> {
+    let public_key = PublicKey::from_base64(public_key)?;
+    let signature = Signature::decode(signature)?;
+    public_key.verify(data, &signature, false)
+}
+
+/// Download a Zig release and extract it into Cargo Lambda's data directory.
+/// The archive is verified with the checksum in Zig's release index,
+/// and with its signature, before it's extracted.
+/// Returns the path to the Zig binary.
+async fn download_zig(version: &str, allow_unsigned: bool) -> Result<PathBuf> {
+    let host =
+        zig_host().ok_or_else(|| miette::miette!("there are no Zig releases for this system"))?;
+    let dir = managed_zig_dir()
+        .ok_or_else(|| miette::miette!("unable to find a data directory to download Zig"))?
+        .join(version);
+
+    let index = download(ZIG_RELEASE_INDEX).await?;
+    let release = find_release(&index, version, &host)?;
+    debug!(?release, ?dir, "downloading Zig");
+
+    let data = download(&release.tarball).await?;
+    let signature = download_signature(&release.tarball).await?;
+    verify_release(&release, &data, signature.as_deref(), allow_unsigned)?;
+
+    if dir.exists() {
+        remove_dir_all(&dir).into_diagnostic()?;
+    }
+    create_dir_all(&dir).into_diagnostic()?;
+
+    if release.tarball.ends_with(".zip") {
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).into_diagnostic()?;
+        archive.extract(&dir).into_diagnostic()?;
+    } else {
+        extract_tar_xz(&data, &dir).await?;
+    }
+    rename_release_dir(&dir)?;
+
+    managed_zig_path(version).ok_or_else(|| {
+        miette::miette!(
+            "the Zig binary is missing from the downloaded archive {}",
+            release.tarball
+        )
+    })
+}
+
+/// Rename the directory included in the release archive, which changes
+/// between versions, so the path to the binary is known before the download.
+fn rename_release_dir(dir: &Path) -> Result<()> {
+    let extracted = read_dir(dir)
+        .into_diagnostic()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.is_dir());
+
+    if let Some(extracted) = extracted {
+        rename(extracted, dir.join(MANAGED_RELEASE_DIR)).into_diagnostic()?;
+    }
+    Ok(())
+}
+
+/// Decompress the release and extract it with the system's `tar` command.
+/// Not every `tar` supports xz compression, so we decompress it first.
+async fn extract_tar_xz(data: &[u8], dir: &Path) -> Result<()> {
+    let mut tar = Vec::new();
+    lzma_rs::xz_decompress(&mut BufReader::new(data), &mut tar)
+        .into_diagnostic()
+        .wrap_err("failed to decompress the Zig archive")?;
+
+    let tar_path = dir.join("zig.tar");
+    write(&tar_path, tar).into_diagnostic()?;
+
+    let status = tokio::process::Command::new("tar")
+        .arg("-xf")
+        .arg(&tar_path)
+        .arg("-C")
+        .arg(dir)
+        .status()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to run `tar` to extract the Zig archive")?;
+    std::fs::remove_file(&tar_path).into_diagnostic()?;
+
+    if !status.success() {
+        return Err(miette::miette!("failed to extract the Zig archive"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_host() {
+        assert_eq!(
+            Some("x86_64-linux".to_string()),
+            release_host("linux", "x86_64")
+        );
+        assert_eq!(
+            Some("aarch64-macos".to_string()),
+            release_host("macos", "aarch64")
+        );
+        assert_eq!(None, release_host("solaris", "x86_64"));
+        assert_eq!(None, release_host("linux", "powerpc"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_install_command() {
+        run_install_command("true", false).await.unwrap();
+
+        let err = run_install_command("false", false).await.unwrap_err();
+        assert!(err.to_string().starts_with("`false` failed with"), "{err}");
+
+        let err = run_install_command("ls /cargo-lambda-missing-dir", false)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("cargo-lambda-missing-dir"),
+            "{err}"
+        );
+
+        let err = run_install_command("cargo-lambda-missing-command", false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("failed to run"), "{err}");
+    }
+
+    #[test]
+    fn test_find_release() {
+        let index = br#"{
+            "master": {"version": "0.14.0-dev"},
+            "0.13.0": {
+                "date": "2024-06-07",
+                "x86_64-linux": {
+                    "tarball": "https://ziglang.org/download/0.13.0/zig-linux-x86_64-0.13.0.tar.xz",
+                    "shasum": "d45312e61ebcc48032b77bc4cf7fd6915c11fa16e4aad116b66c9468211230ea",
+                    "size": "47082308"
+                }
+            }
+        }"#;
+
+        let release = find_release(index, "0.13.0", "x86_64-linux").unwrap();
+        assert_eq!(
+            ZigRelease {
+                tarball: "https://ziglang.org/download/0.13.0/zig-linux-x86_64-0.13.0.tar.xz"
+                    .into(),
+                shasum: "d45312e61ebcc48032b77bc4cf7fd6915c11fa16e4aad116b66c9468211230ea".into(),
+            },
+            release
+        );
+
+        assert!(find_release(index, "0.13.0", "aarch64-macos").is_err());
+        assert!(find_release(index, "0.1.0", "x86_64-linux").is_err());
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        // Test vector from the minisign-verify crate.
+        let public_key = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+        let signature = "untrusted comment: signature from minisign secret key\n\
+            RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=\n\
+            trusted comment: timestamp:1556193335\tfile:test\n\
+            y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==\n";
+
+        verify_signature(public_key, b"test", signature).unwrap();
+        assert!(verify_signature(public_key, b"tampered", signature).is_err());
+        assert!(verify_signature(ZIG_PUBLIC_KEY, b"test", signature).is_err());
+    }
+
+    #[test]
+    fn test_verify_release() {
+        let data = b"zig release archive";
+        let release = ZigRelease {
+            tarball: "https://ziglang.org/download/0.13.0/zig-linux-x86_64-0.13.0.tar.xz".into(),
+            shasum: format!("{:x}", Sha256::digest(data)),
+        };
+        verify_release(&release, data, None, true).unwrap();
+
+        let err = verify_release(&release, data, None, false).unwrap_err();
+        let err = err.downcast_ref::<BuildError>().unwrap();
+        assert!(matches!(err, BuildError::ZigSignatureMissing { .. }));
+
+        let err = verify_release(&release, b"tampered archive", None, true).unwrap_err();
+        let err = err.downcast_ref::<BuildError>().unwrap();
+        assert!(matches!(
+            err,
+            BuildError::ZigChecksumMismatch { expected, .. } if expected == &release.shasum
+        ));
+
+        let signature = "untrusted comment: signature from minisign secret key\n\
+            RUSGOq2NVecA2aW52YWxpZA==\n\
+            trusted comment: timestamp:1718000000\n\
+            aW52YWxpZA==\n";
+        let err = verify_release(&release, data, Some(signature), true).unwrap_err();
+        let err = err.downcast_ref::<BuildError>().unwrap();
+        assert!(matches!(err, BuildError::ZigSignatureMismatch { .. }));
+    }
+}
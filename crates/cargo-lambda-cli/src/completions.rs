@@ -0,0 +1,174 @@
+use cargo_lambda_metadata::cargo::{binary_targets_from_metadata, load_metadata};
+use clap::{Args, Command, CommandFactory, ValueEnum};
+use clap_complete::{
+    engine::{ArgValueCandidates, CompletionCandidate},
+    env::{Bash, CompleteEnv, EnvCompleter, Fish, Powershell, Zsh},
+};
+use miette::{IntoDiagnostic, Result};
+use std::{env, io::stdout};
+
+use crate::Lambda;
+
+/// Name of the command that the shells complete.
+/// Cargo doesn't delegate the completion of its subcommands,
+/// so the completions are for the `cargo-lambda` binary.
+const BIN_NAME: &str = "cargo-lambda";
+
+/// Environment variable that the completion scripts set to ask for completions.
+const COMPLETE_VAR: &str = "COMPLETE";
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub(crate) enum Shell {
+    Bash,
+    Fish,
+    Powershell,
+    Zsh,
+}
+
+impl Shell {
+    fn completer(&self) -> &'static dyn EnvCompleter {
+        match self {
+            Shell::Bash => &Bash,
+            Shell::Fish => &Fish,
+            Shell::Powershell => &Powershell,
+            Shell::Zsh => &Zsh,
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+#[command(
+    name = "completions",
+    after_help = "Full command documentation: https://www.cargo-lambda.info/commands/completions.html"
+)]
+pub(crate) struct Completions {
+    /// Shell to generate the completions for
+    #[arg(value_enum)]
+    shell: Shell,
+}
+
+impl Completions {
+    /// Print the script that registers the completions in the shell.
+    /// The script calls this binary to complete each argument, so
+    /// the completions for binary names and events are always up to date.
+    pub(crate) fn run(&self) -> Result<()> {
+        let program = env::current_exe().into_diagnostic()?;
+        self.shell
+            .completer()
+            .write_registration(
+                COMPLETE_VAR,
+                BIN_NAME,
+                BIN_NAME,
+                &program.to_string_lossy(),
+                &mut stdout(),
+            )
+            .into_diagnostic()
+    }
+}
+
+/// Answer the completion requests from the shells, and exit.
+/// It returns without doing anything when the program is not running for a completion request.
+pub(crate) fn complete() {
+    CompleteEnv::with_factory(command)
+        .var(COMPLETE_VAR)
+        .bin(BIN_NAME)
+        .complete();
+}
+
+/// Command to complete, with the values that can only be known at completion time.
+fn command() -> Command {
+    let binaries = || ArgValueCandidates::new(|| binary_candidates(false));
+    let examples = ArgValueCandidates::new(|| binary_candidates(true));
+    let fixtures = ArgValueCandidates::new(fixture_candidates);
+    let events = || ArgValueCandidates::new(event_candidates);
+
+    Lambda::command()
+        .name(BIN_NAME)
+        .bin_name(BIN_NAME)
+        .mut_subcommand("build", |cmd| {
+            cmd.mut_arg("bin", |arg| arg.add(binaries()))
+                .mut_arg("example", |arg| arg.add(examples))
+        })
+        .mut_subcommand("deploy", |cmd| {
+            cmd.mut_arg("binary_name", |arg| arg.add(binaries()))
+        })
+        .mut_subcommand("invoke", |cmd| {
+            cmd.mut_arg("function_name", |arg| arg.add(binaries()))
+                .mut_arg("data_example", |arg| arg.add(fixtures))
+        })
+        .mut_subcommand("watch", |cmd| cmd.mut_arg("bin", |arg| arg.add(binaries())))
+        .mut_subcommand("new", |cmd| {
+            cmd.mut_arg("event_type", |arg| arg.add(events()))
+        })
+        .mut_subcommand("init", |cmd| {
+            cmd.mut_arg("event_type", |arg| arg.add(events()))
+        })
+}
+
+/// Binaries, or examples, in the project in the current directory.
+fn binary_candidates(examples: bool) -> Vec<CompletionCandidate> {
+    let Ok(metadata) = load_metadata("Cargo.toml") else {
+        return Vec::new();
+    };
+
+    let mut names = binary_targets_from_metadata(&metadata, examples)
+        .into_iter()
+        .collect::<Vec<_>>();
+    names.sort();
+    names.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Fixtures in the crates with binaries in the project in the current directory.
+fn fixture_candidates() -> Vec<CompletionCandidate> {
+    let Ok(metadata) = load_metadata("Cargo.toml") else {
+        return Vec::new();
+    };
+
+    cargo_lambda_invoke::fixture_names(&metadata)
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Event types that `new` and `init` know how to generate functions for.
+fn event_candidates() -> Vec<CompletionCandidate> {
+    cargo_lambda_new::well_known_event_types()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dynamic_candidates() {
+        let command = command();
+        for (subcommand, arg) in [
+            ("build", "bin"),
+            ("build", "example"),
+            ("deploy", "binary_name"),
+            ("invoke", "function_name"),
+            ("invoke", "data_example"),
+            ("new", "event_type"),
+            ("init", "event_type"),
+            ("watch", "bin"),
+        ] {
+            let arg = command
+                .find_subcommand(subcommand)
+                .and_then(|cmd| cmd.get_arguments().find(|a| a.get_id() == arg))
+                .unwrap_or_else(|| panic!("missing argument {arg} in {subcommand}"));
+            assert!(arg.get::<ArgValueCandidates>().is_some());
+        }
+    }
+
+    #[test]
+    fn test_event_candidates() {
+        let events = event_candidates();
+        assert!(
+            events
+                .iter()
+                .any(|e| e.get_value().to_string_lossy() == "s3::S3Event")
+        );
+    }
+}
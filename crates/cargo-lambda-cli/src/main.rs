@@ -3,18 +3,35 @@
 use cargo_lambda_build::Zig;
 use cargo_lambda_invoke::Invoke;
 use cargo_lambda_metadata::{
-    cargo::{build::Build, deploy::Deploy, load_metadata, watch::Watch},
-    config::{Config, ConfigOptions, load_config},
+    cargo::{
+        build::Build,
+        deploy::Deploy,
+        load_metadata,
+        schema::{metadata_schema, validate_manifest},
+        validation::unknown_metadata_keys,
+        watch::Watch,
+    },
+    config::{
+        Config, ConfigOptions, load_config, load_config_without_cli_flags,
+        load_metadata_environment,
+    },
+    net::{self, HttpOptions},
 };
 use cargo_lambda_new::{Init, New};
+use cargo_lambda_symbolicate::Symbolicate;
 use cargo_lambda_system::System;
 use cargo_lambda_watch::xray_layer;
-use clap::{CommandFactory, Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use clap_cargo::style::CLAP_STYLING;
-use miette::{ErrorHook, IntoDiagnostic, Result, miette};
+use miette::{ErrorHook, IntoDiagnostic, Result, WrapErr, miette};
 use std::{boxed::Box, env, io::IsTerminal, path::PathBuf, str::FromStr};
 use strum_macros::EnumString;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{
+    fmt::writer::BoxMakeWriter, layer::SubscriberExt, util::SubscriberInitExt,
+};
+
+mod completions;
+use completions::Completions;
 
 #[derive(Parser)]
 #[command(name = "cargo", bin_name = "cargo", disable_version_flag = true)]
@@ -57,6 +74,16 @@ struct Lambda {
     #[arg(long, global = true, env = "CARGO_LAMBDA_ADMERGE")]
     admerge: bool,
 
+    /// Proxy to download templates, Zig releases, and other artifacts.
+    /// By default, the proxies in HTTP_PROXY and HTTPS_PROXY are used
+    #[arg(long, value_name = "URL", global = true, env = "CARGO_LAMBDA_PROXY")]
+    proxy: Option<String>,
+
+    /// PEM file with certificate authorities to trust when downloading artifacts,
+    /// besides the ones in the system's certificate store
+    #[arg(long, value_name = "PATH", global = true, env = "CARGO_LAMBDA_CACERT")]
+    cacert: Option<PathBuf>,
+
     /// Print version information
     #[arg(short = 'V', long)]
     version: bool,
@@ -90,6 +117,35 @@ impl Color {
     }
 }
 
+#[derive(Args, Clone, Debug)]
+#[command(
+    name = "config",
+    after_help = "Full command documentation: https://www.cargo-lambda.info/commands/config.html"
+)]
+struct ShowConfig {
+    /// Name of the binary or package to load the configuration for
+    name: Option<String>,
+
+    /// Path to Cargo.toml
+    #[arg(long, value_name = "PATH", default_value = "Cargo.toml")]
+    manifest_path: PathBuf,
+}
+
+#[derive(Args, Clone, Debug)]
+#[command(
+    name = "validate",
+    after_help = "Full command documentation: https://www.cargo-lambda.info/commands/validate.html"
+)]
+struct Validate {
+    /// Path to Cargo.toml
+    #[arg(long, value_name = "PATH", default_value = "Cargo.toml")]
+    manifest_path: PathBuf,
+
+    /// Print the JSON Schema of the Lambda metadata, instead of validating the manifests
+    #[arg(long)]
+    print_schema: bool,
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug, Subcommand)]
 enum LambdaSubcommand {
@@ -97,6 +153,12 @@ enum LambdaSubcommand {
     /// It produces artifacts which you can then upload to AWS Lambda with `cargo lambda deploy`,
     /// or use with other ecosystem tools, SAM Cli or the AWS CDK.
     Build(Build),
+    /// `cargo lambda completions` prints the script to enable completions in your shell.
+    /// The completions include the names of the binaries and fixtures in your project, and the well known event types.
+    Completions(Completions),
+    /// `cargo lambda config` prints the effective configuration for a function as JSON.
+    /// It shows the result of merging environment variables, configuration files, and package metadata.
+    Config(ShowConfig),
     /// `cargo lambda deploy` uploads functions and extensions to AWS Lambda.
     /// You can use the same command to create new functions as well as update existent functions code.
     Deploy(Deploy),
@@ -108,8 +170,14 @@ enum LambdaSubcommand {
     Invoke(Invoke),
     /// `cargo lambda new` creates Rust Lambda packages from a well defined template to help you start writing AWS Lambda functions in Rust.
     New(New),
+    /// `cargo lambda symbolicate` resolves the addresses in a backtrace from a deployed function
+    /// into functions, files, and lines, using the debug information kept by `cargo lambda build --split-debuginfo-out`.
+    Symbolicate(Symbolicate),
     /// `cargo lambda system` shows the status of the system Zig installation.
     System(System),
+    /// `cargo lambda validate` checks the Lambda configuration in the package and workspace metadata
+    /// of the Cargo.toml files in a project, and reports the line and column of each invalid value.
+    Validate(Validate),
     /// `cargo lambda watch` boots a development server that emulates interactions with the AWS Lambda control plane.
     /// This subcommand also reloads your Rust code as you work on it.
     Watch(Watch),
@@ -125,11 +193,15 @@ impl LambdaSubcommand {
     ) -> Result<()> {
         match self {
             Self::Build(b) => Self::run_build(b, global, context, admerge).await,
+            Self::Completions(c) => c.run(),
+            Self::Config(c) => Self::run_config(c, global, context, admerge),
             Self::Deploy(d) => Self::run_deploy(d, global, context, admerge).await,
             Self::Init(mut i) => i.run().await,
             Self::Invoke(i) => i.run().await,
             Self::New(mut n) => n.run().await,
-            Self::System(s) => s.run().await,
+            Self::Symbolicate(s) => s.run(),
+            Self::System(s) => s.run(&version()).await,
+            Self::Validate(v) => Self::run_validate(v),
             Self::Watch(w) => Self::run_watch(w, color, global, context, admerge).await,
         }
     }
@@ -156,6 +228,71 @@ impl LambdaSubcommand {
         cargo_lambda_build::run(&mut config.build, &metadata).await
     }
 
+    fn run_config(
+        show: ShowConfig,
+        global: Option<PathBuf>,
+        context: Option<String>,
+        admerge: bool,
+    ) -> Result<()> {
+        let metadata = load_metadata(&show.manifest_path)?;
+        for key in unknown_metadata_keys(&metadata) {
+            tracing::warn!("{key}");
+        }
+
+        let options = ConfigOptions {
+            name: show.name,
+            context,
+            global,
+            admerge,
+        };
+        let config = load_config_without_cli_flags(&metadata, &options)?;
+
+        let json = serde_json::to_string_pretty(&config).into_diagnostic()?;
+        println!("{json}");
+        Ok(())
+    }
+
+    fn run_validate(validate: Validate) -> Result<()> {
+        if validate.print_schema {
+            let json = serde_json::to_string_pretty(&metadata_schema()).into_diagnostic()?;
+            println!("{json}");
+            return Ok(());
+        }
+
+        let metadata = load_metadata(&validate.manifest_path)?;
+        let mut manifests = vec![metadata.workspace_root.join("Cargo.toml")];
+        for pkg in metadata.workspace_packages() {
+            if !manifests.contains(&pkg.manifest_path) {
+                manifests.push(pkg.manifest_path.clone());
+            }
+        }
+
+        let mut errors = 0;
+        for manifest in &manifests {
+            if !manifest.exists() {
+                continue;
+            }
+            let source = std::fs::read_to_string(manifest)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to read {manifest}"))?;
+            let violations = validate_manifest(&source)
+                .wrap_err_with(|| format!("failed to parse {manifest}"))?;
+            for violation in violations {
+                eprintln!("❌ {manifest}:{violation}");
+                errors += 1;
+            }
+        }
+
+        match errors {
+            0 => {}
+            1 => return Err(miette!("found 1 invalid value in the Lambda metadata")),
+            n => return Err(miette!("found {n} invalid values in the Lambda metadata")),
+        }
+
+        println!("✅ the Lambda metadata is valid");
+        Ok(())
+    }
+
     async fn run_watch(
         watch: Watch,
         color: &str,
@@ -164,6 +301,7 @@ impl LambdaSubcommand {
         admerge: bool,
     ) -> Result<()> {
         let name = watch.package();
+        let listen_fd = watch.listen_fd;
         let metadata = load_metadata(watch.manifest_path())?;
         let args_config = Config {
             watch,
@@ -176,7 +314,8 @@ impl LambdaSubcommand {
             global,
             admerge,
         };
-        let config = load_config(&args_config, &metadata, &options)?;
+        let mut config = load_config(&args_config, &metadata, &options)?;
+        config.watch.listen_fd = listen_fd;
         cargo_lambda_watch::run(&config.watch, &config.env, &metadata, color).await
     }
 
@@ -187,6 +326,11 @@ impl LambdaSubcommand {
         admerge: bool,
     ) -> Result<()> {
         let name = deploy.name.clone();
+        let i_know_what_im_doing = deploy.i_know_what_im_doing;
+        let yes = deploy.yes;
+        let no_interactive = deploy.no_interactive;
+        let plan_out = deploy.plan_out.clone();
+        let apply = deploy.apply.clone();
         let metadata = load_metadata(deploy.manifest_path())?;
         let args_config = Config {
             deploy,
@@ -202,19 +346,36 @@ impl LambdaSubcommand {
 
         let config = load_config(&args_config, &metadata, &options)?;
         let mut deploy = config.deploy;
-        deploy.base_env = config.env.clone();
+        deploy.i_know_what_im_doing = i_know_what_im_doing;
+        deploy.yes = yes;
+        deploy.no_interactive = no_interactive;
+        deploy.plan_out = plan_out;
+        deploy.apply = apply;
+        if deploy.env_from_metadata_only {
+            deploy.base_env = load_metadata_environment(&metadata, &options)?;
+            if let Some(env_options) = &mut deploy.function_config.env_options {
+                env_options.env_var = None;
+                env_options.env_file = None;
+            }
+        } else {
+            deploy.base_env = config.env.clone();
+        }
 
         cargo_lambda_deploy::run(&deploy, &metadata).await
     }
 }
 
 fn print_version() -> Result<()> {
-    println!(
-        "cargo-lambda {} {}",
+    println!("cargo-lambda {}", version());
+    Ok(())
+}
+
+fn version() -> String {
+    format!(
+        "{} {}",
         env!("CARGO_PKG_VERSION"),
         env!("CARGO_LAMBDA_BUILD_INFO")
-    );
-    Ok(())
+    )
 }
 
 fn print_help() -> Result<()> {
@@ -233,8 +394,20 @@ fn print_help() -> Result<()> {
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+/// Arguments to parse, adding the `lambda` subcommand when the program
+/// runs as `cargo-lambda` instead of through Cargo, like the completions do.
+fn cargo_args(program_name: &str) -> Vec<std::ffi::OsString> {
+    let mut args = env::args_os().collect::<Vec<_>>();
+    let through_cargo = args
+        .get(1)
+        .is_some_and(|arg| arg == "lambda" || arg == "zig");
+    if program_name == "cargo-lambda" && !through_cargo {
+        args.insert(1, "lambda".into());
+    }
+    args
+}
+
+fn main() -> Result<()> {
     // Zig might try to execute the same program again with "ar" as the name
     // to link specific static native libraries. We need to check
     // the program name before executing any operation to ensure
@@ -251,7 +424,9 @@ async fn main() -> Result<()> {
         };
         zig.execute().map_err(|e| miette!(e))
     } else {
-        let app = App::parse();
+        completions::complete();
+
+        let app = App::parse_from(cargo_args(&program_name.to_string_lossy()));
 
         match app {
             App::Zig(zig) => {
@@ -265,7 +440,15 @@ async fn main() -> Result<()> {
                 color.write_env_var();
                 miette::set_hook(error_hook(Some(&color)))?;
 
-                run_subcommand(lambda, color).await
+                // The environment is only modified before the async runtime
+                // starts its threads, cargo-zigbuild reads the Zig path from it.
+                cargo_lambda_build::init_zig_env();
+
+                tokio::runtime::Builder::new_multi_thread()
+                    .enable_all()
+                    .build()
+                    .into_diagnostic()?
+                    .block_on(run_subcommand(lambda, color))
             }
         }
     }
@@ -281,6 +464,11 @@ async fn run_subcommand(lambda: Lambda, color: Color) -> Result<()> {
         Some(subcommand) => subcommand,
     };
 
+    net::configure(HttpOptions {
+        proxy: lambda.proxy,
+        cacert: lambda.cacert,
+    });
+
     let log_directive = if lambda.verbose == 0 {
         std::env::var("RUST_LOG").unwrap_or_else(|_| "cargo_lambda=info".into())
     } else if lambda.verbose == 1 {
@@ -289,7 +477,20 @@ async fn run_subcommand(lambda: Lambda, color: Color) -> Result<()> {
         "cargo_lambda=trace".into()
     };
 
+    // The completions and config commands, and builds with JSON messages print JSON
+    // to stdout, so their logs cannot be mixed with it.
+    let writer = match &*subcommand {
+        LambdaSubcommand::Completions(_) | LambdaSubcommand::Config(_) => {
+            BoxMakeWriter::new(std::io::stderr)
+        }
+        LambdaSubcommand::Build(b) if b.json_message_format() => {
+            BoxMakeWriter::new(std::io::stderr)
+        }
+        _ => BoxMakeWriter::new(std::io::stdout),
+    };
+
     let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
         .with_target(false)
         .without_time()
         .with_ansi(color.is_ansi());
@@ -159,6 +159,77 @@ fn test_build_event_type_function() {
     assert!(bin.exists(), "{:?} doesn't exist", bin);
 }
 
+#[test]
+fn test_new_function_with_integration_tests() {
+    let _guard = init_root();
+    let lp = cargo_lambda_new("test-basic-function", "function-template");
+
+    lp.new_cmd()
+        .arg("--no-interactive")
+        .arg("--with-integration-tests")
+        .arg(&lp.name)
+        .assert()
+        .success();
+
+    let project = lp.test_project();
+    let tests = read_to_string(project.root().join("tests").join("integration.rs"))
+        .expect("failed to read integration tests");
+    assert!(tests.contains(&format!("const FUNCTION_NAME: &str = \"{}\";", lp.name)));
+    assert!(tests.contains(r#"{"command":"hello"}"#));
+
+    let manifest =
+        read_to_string(project.root().join("Cargo.toml")).expect("failed to read manifest");
+    assert!(manifest.contains("cargo-lambda-emulator"));
+}
+
+#[test]
+fn test_new_http_function_with_framework() {
+    let _guard = init_root();
+    let lp = cargo_lambda_new("test-http-function", "function-template");
+
+    lp.new_cmd()
+        .arg("--http-framework")
+        .arg("axum")
+        .arg(&lp.name)
+        .assert()
+        .success();
+
+    let project = lp.test_project();
+    let manifest =
+        read_to_string(project.root().join("Cargo.toml")).expect("failed to read Cargo.toml");
+    assert!(manifest.contains(r#"axum = "0.8""#));
+
+    let main =
+        read_to_string(project.root().join("src").join("main.rs")).expect("failed to read main.rs");
+    assert!(main.contains(r#".route("/health", get(health))"#));
+
+    let readme =
+        read_to_string(project.root().join("README.md")).expect("failed to read README.md");
+    assert!(readme.contains("cargo lambda watch"));
+}
+
+#[test]
+fn test_new_list_events() {
+    let _guard = init_root();
+    let lp = cargo_lambda_new("test-list-events", "config-template");
+
+    let output = lp.new_cmd().arg("--list-events").output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("EVENT TYPE"));
+    assert!(
+        stdout
+            .lines()
+            .any(|l| l.starts_with("s3::S3Event") && l.ends_with("s3"))
+    );
+    assert!(
+        stdout
+            .lines()
+            .any(|l| l.starts_with("orders::OrderCreated") && l.ends_with("orders"))
+    );
+}
+
 #[test]
 fn test_build_basic_extension() {
     let _guard = init_root();
@@ -707,3 +778,41 @@ fn test_deploy_pre_existing_zip_file() {
         );
     }
 }
+
+#[test]
+fn test_config_subcommand() {
+    let _guard = init_root();
+    let project = project().build();
+    project.change_file(
+        "Cargo.toml",
+        r#"
+[package]
+name = "config-function"
+version = "0.1.0"
+edition = "2021"
+
+[workspace.metadata.lambda.deploy]
+memory = 256
+timeout = 30
+
+[package.metadata.lambda.deploy]
+memory = 512
+
+[package.metadata.lambda.watch]
+invoke_port = 9001
+"#,
+    );
+    project.change_file("src/main.rs", "fn main() {}");
+
+    let output = cargo_lambda_config(project.root())
+        .env("CARGO_LAMBDA_DEPLOY.TRACING", "active")
+        .assert()
+        .success();
+
+    let stdout = &output.get_output().stdout;
+    let json_data: serde_json::Value = serde_json::from_slice(stdout).unwrap();
+    assert_eq!(json_data["deploy"]["memory"], 512);
+    assert_eq!(json_data["deploy"]["timeout"], 30);
+    assert_eq!(json_data["deploy"]["tracing"], "Active");
+    assert_eq!(json_data["watch"]["invoke_port"], 9001);
+}
@@ -134,6 +134,13 @@ pub fn cargo_lambda_dry_deploy<P: AsRef<Path>>(path: P) -> Command {
         .current_dir(path)
 }
 
+pub fn cargo_lambda_config<P: AsRef<Path>>(path: P) -> Command {
+    Command::cargo_lambda()
+        .arg("lambda")
+        .arg("config")
+        .current_dir(path.as_ref())
+}
+
 fn cargo_exe() -> std::path::PathBuf {
     snapbox::cmd::cargo_bin("cargo-lambda")
 }
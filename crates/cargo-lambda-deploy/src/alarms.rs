@@ -0,0 +1,203 @@
+use aws_smithy_xml::decode::{Document, ScopedDecoder, XmlDecodeError, try_data};
+use cargo_lambda_remote::aws_sdk_config::SdkConfig;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::collections::HashMap;
+use tracing::debug;
+
+use crate::query_api;
+
+pub(crate) const CLOUDWATCH_API_VERSION: &str = "2010-08-01";
+
+/// State of the alarms that are firing.
+const ALARM_STATE: &str = "ALARM";
+
+/// Check that none of the alarms are in `ALARM` state.
+/// It fails when an alarm doesn't exist, so typos in the alarm names don't disable the gate.
+pub(crate) async fn check(sdk_config: &SdkConfig, alarms: &[String]) -> Result<()> {
+    if alarms.is_empty() {
+        return Ok(());
+    }
+
+    let text = query_api::send(sdk_config, "monitoring", describe_alarms_request(alarms))
+        .await
+        .wrap_err("failed to fetch the state of the gate alarms")?
+        .map_err(|message| {
+            miette::miette!("failed to fetch the state of the gate alarms: {message}")
+        })?;
+
+    let states = parse_alarm_states(&text)
+        .into_diagnostic()
+        .wrap_err("failed to read the state of the gate alarms")?;
+    debug!(?states, "gate alarms");
+
+    check_states(alarms, &states)
+}
+
+fn check_states(alarms: &[String], states: &HashMap<String, String>) -> Result<()> {
+    let missing = alarms
+        .iter()
+        .filter(|alarm| !states.contains_key(*alarm))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(miette::miette!(
+            "the gate alarms don't exist: {}",
+            missing.join(", ")
+        ));
+    }
+
+    let firing = alarms
+        .iter()
+        .filter(|alarm| states.get(*alarm).is_some_and(|s| s == ALARM_STATE))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !firing.is_empty() {
+        return Err(miette::miette!(
+            "the gate alarms are in ALARM state: {}",
+            firing.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+fn describe_alarms_request(alarms: &[String]) -> String {
+    let mut body = format!(
+        "Action=DescribeAlarms&Version={CLOUDWATCH_API_VERSION}\
+        &AlarmTypes.member.1=MetricAlarm&AlarmTypes.member.2=CompositeAlarm"
+    );
+    for (idx, alarm) in alarms.iter().enumerate() {
+        body.push_str(&format!(
+            "&AlarmNames.member.{}={}",
+            idx + 1,
+            urlencoding::encode(alarm)
+        ));
+    }
+    body
+}
+
+/// Parse the states of the alarms from a DescribeAlarms response:
+/// DescribeAlarmsResponse > DescribeAlarmsResult > MetricAlarms|CompositeAlarms > member.
+fn parse_alarm_states(body: &str) -> Result<HashMap<String, String>, XmlDecodeError> {
+    let mut states = HashMap::new();
+
+    let mut doc = Document::new(body);
+    let mut root = doc.root_element()?;
+    while let Some(mut result) = root.next_tag() {
+        if !result.start_el().matches("DescribeAlarmsResult") {
+            continue;
+        }
+        while let Some(mut alarms) = result.next_tag() {
+            if alarms.start_el().matches("MetricAlarms")
+                || alarms.start_el().matches("CompositeAlarms")
+            {
+                parse_alarms(&mut alarms, &mut states)?;
+            }
+        }
+    }
+
+    Ok(states)
+}
+
+fn parse_alarms(
+    decoder: &mut ScopedDecoder,
+    states: &mut HashMap<String, String>,
+) -> Result<(), XmlDecodeError> {
+    while let Some(mut member) = decoder.next_tag() {
+        let mut name = None;
+        let mut state = None;
+        while let Some(mut tag) = member.next_tag() {
+            if tag.start_el().matches("AlarmName") {
+                name = Some(try_data(&mut tag)?.to_string());
+            } else if tag.start_el().matches("StateValue") {
+                state = Some(try_data(&mut tag)?.to_string());
+            }
+        }
+
+        if let (Some(name), Some(state)) = (name, state) {
+            states.insert(name, state);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIBE_ALARMS_RESPONSE: &str = r#"<DescribeAlarmsResponse xmlns="http://monitoring.amazonaws.com/doc/2010-08-01/">
+  <DescribeAlarmsResult>
+    <MetricAlarms>
+      <member>
+        <AlarmName>orders-api-errors</AlarmName>
+        <StateValue>OK</StateValue>
+        <Dimensions>
+          <member>
+            <Name>FunctionName</Name>
+            <Value>orders-api</Value>
+          </member>
+        </Dimensions>
+      </member>
+      <member>
+        <AlarmName>orders-api-latency</AlarmName>
+        <StateValue>ALARM</StateValue>
+      </member>
+    </MetricAlarms>
+    <CompositeAlarms>
+      <member>
+        <AlarmName>orders-health</AlarmName>
+        <StateValue>INSUFFICIENT_DATA</StateValue>
+      </member>
+    </CompositeAlarms>
+  </DescribeAlarmsResult>
+</DescribeAlarmsResponse>"#;
+
+    #[test]
+    fn test_parse_alarm_states() {
+        let states = parse_alarm_states(DESCRIBE_ALARMS_RESPONSE).unwrap();
+        assert_eq!(
+            HashMap::from([
+                ("orders-api-errors".to_string(), "OK".to_string()),
+                ("orders-api-latency".to_string(), "ALARM".to_string()),
+                ("orders-health".to_string(), "INSUFFICIENT_DATA".to_string()),
+            ]),
+            states
+        );
+    }
+
+    #[test]
+    fn test_check_states() {
+        let states = parse_alarm_states(DESCRIBE_ALARMS_RESPONSE).unwrap();
+
+        let alarms = vec!["orders-api-errors".to_string(), "orders-health".to_string()];
+        check_states(&alarms, &states).unwrap();
+
+        let alarms = vec![
+            "orders-api-errors".to_string(),
+            "orders-api-latency".to_string(),
+        ];
+        let err = check_states(&alarms, &states).unwrap_err();
+        assert_eq!(
+            "the gate alarms are in ALARM state: orders-api-latency",
+            err.to_string()
+        );
+
+        let alarms = vec!["orders-api-typo".to_string()];
+        let err = check_states(&alarms, &states).unwrap_err();
+        assert_eq!(
+            "the gate alarms don't exist: orders-api-typo",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_describe_alarms_request() {
+        let alarms = vec!["orders errors".to_string(), "orders-latency".to_string()];
+        assert_eq!(
+            "Action=DescribeAlarms&Version=2010-08-01\
+            &AlarmTypes.member.1=MetricAlarm&AlarmTypes.member.2=CompositeAlarm\
+            &AlarmNames.member.1=orders%20errors&AlarmNames.member.2=orders-latency",
+            describe_alarms_request(&alarms)
+        );
+    }
+}
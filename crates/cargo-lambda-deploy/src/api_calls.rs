@@ -0,0 +1,997 @@
+use cargo_lambda_build::BinaryArchive;
+use cargo_lambda_metadata::cargo::deploy::{Deploy, InvokePermission, Trigger};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use crate::{
+    custom_domain::{
+        ALL_VIEWER_EXCEPT_HOST_HEADER_POLICY_ID, CACHING_DISABLED_POLICY_ID, caller_reference,
+        request_certificate_body,
+    },
+    debuginfo::debug_artifact_key,
+    roles::{BASIC_LAMBDA_EXECUTION_POLICY, trust_policy},
+    triggers::{api_name, create_api_body, put_rule_body, put_targets_body, rule_name, target_arn},
+    upload::PART_SIZE,
+};
+
+/// Value of the secrets in the requests, like the values of the environment variables.
+const REDACTED: &str = "<redacted>";
+
+/// ARN of the function, which AWS only returns after creating it.
+const FUNCTION_ARN: &str = "<function ARN>";
+
+/// Version of the function that the deploy publishes.
+const NEW_VERSION: &str = "<new version>";
+
+const NEW_FUNCTION: &str = "when the function doesn't exist";
+const EXISTING_FUNCTION: &str = "when the function exists";
+
+/// Call to an AWS API that a deploy performs, with the payload of its request.
+/// Values that only AWS knows during the deploy, like the ARN of a new resource,
+/// are placeholders between angle brackets.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct ApiCall {
+    service: String,
+    operation: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    condition: Option<String>,
+    request: Value,
+}
+
+impl ApiCall {
+    fn new(service: &str, operation: &str, request: Value) -> ApiCall {
+        ApiCall {
+            service: service.to_string(),
+            operation: operation.to_string(),
+            condition: None,
+            request,
+        }
+    }
+
+    /// Only call the API when the condition is true during the deploy.
+    fn when(mut self, condition: impl Into<String>) -> ApiCall {
+        self.condition = Some(condition.into());
+        self
+    }
+}
+
+/// Calls to the AWS APIs that a deploy performs, in the same order that the deploy performs them.
+/// Calls that the deploy only performs to roll back a failed deploy are not included.
+pub(crate) fn plan(
+    config: &Deploy,
+    name: &str,
+    archive: &BinaryArchive,
+    debug_artifact: Option<&Path>,
+) -> Result<Vec<ApiCall>> {
+    let mut calls = Vec::new();
+
+    let stacks = config
+        .stack_outputs()?
+        .into_iter()
+        .map(|output| output.stack_name)
+        .collect::<BTreeSet<_>>();
+    for stack_name in stacks {
+        calls.push(ApiCall::new(
+            "cloudformation",
+            "DescribeStacks",
+            json!({ "StackName": stack_name }),
+        ));
+    }
+
+    if config
+        .allowed_accounts
+        .as_ref()
+        .is_some_and(|a| !a.is_empty())
+    {
+        calls.push(ApiCall::new("sts", "GetCallerIdentity", json!({})));
+    }
+
+    if config.extension {
+        extension_calls(config, name, archive, &mut calls)?;
+    } else {
+        function_calls(config, name, archive, debug_artifact, &mut calls)?;
+    }
+
+    if let Some(audit_log) = config.audit_log()? {
+        let file = format!("{name}/<deploy time>.json");
+        let key = if audit_log.prefix.is_empty() {
+            file
+        } else {
+            format!("{}/{file}", audit_log.prefix)
+        };
+
+        calls.push(ApiCall::new("sts", "GetCallerIdentity", json!({})));
+        calls.push(ApiCall::new(
+            "s3",
+            "PutObject",
+            json!({
+                "Bucket": audit_log.bucket,
+                "Key": key,
+                "ContentType": "application/json",
+                "Body": "<deploy record>",
+            }),
+        ));
+    }
+
+    Ok(calls)
+}
+
+fn extension_calls(
+    config: &Deploy,
+    name: &str,
+    archive: &BinaryArchive,
+    calls: &mut Vec<ApiCall>,
+) -> Result<()> {
+    let content = code_location(config, name, archive, None, calls)?;
+
+    let architectures = match &config.compatible_architectures {
+        Some(architectures) => architectures.clone(),
+        None => vec![archive.architecture().as_str().to_string()],
+    };
+
+    calls.push(ApiCall::new(
+        "lambda",
+        "PublishLayerVersion",
+        json!({
+            "LayerName": name,
+            "CompatibleArchitectures": architectures,
+            "CompatibleRuntimes": config.compatible_runtimes(),
+            "Content": content,
+        }),
+    ));
+    Ok(())
+}
+
+fn function_calls(
+    config: &Deploy,
+    name: &str,
+    archive: &BinaryArchive,
+    debug_artifact: Option<&Path>,
+    calls: &mut Vec<ApiCall>,
+) -> Result<()> {
+    let function_config = &config.function_config;
+    let gate_alarms = config.gate_alarms();
+    if !gate_alarms.is_empty() {
+        calls.push(describe_alarms(gate_alarms));
+    }
+
+    calls.push(ApiCall::new(
+        "lambda",
+        "GetFunction",
+        json!({ "FunctionName": name }),
+    ));
+
+    let role = match &function_config.role {
+        Some(role) => role.clone(),
+        None => {
+            role_calls(config, calls);
+            "<new role ARN>".to_string()
+        }
+    };
+
+    let code = code_location(config, name, archive, Some(NEW_FUNCTION), calls)?;
+    let mut request = json!({
+        "FunctionName": name,
+        "Runtime": function_config.runtime(),
+        "Handler": function_config.handler(),
+        "Role": role,
+        "Architectures": [archive.architecture().as_str()],
+        "Code": code,
+        "Publish": config.publish_code_without_description(),
+        "Timeout": i32::from(function_config.timeout.clone().unwrap_or_default()),
+    });
+    insert_configuration(config, &mut request, false)?;
+    if let Some(tags) = config.lambda_tags() {
+        request["Tags"] = json!(tags.into_iter().collect::<BTreeMap<_, _>>());
+    }
+    calls.push(ApiCall::new("lambda", "CreateFunction", request).when(NEW_FUNCTION));
+
+    if function_config.should_update() {
+        let mut request = json!({ "FunctionName": name });
+        insert_configuration(config, &mut request, true)?;
+        if request.as_object().is_some_and(|r| r.len() > 1) {
+            calls.push(
+                ApiCall::new("lambda", "UpdateFunctionConfiguration", request)
+                    .when("when the function exists, with the settings that changed"),
+            );
+        }
+    }
+
+    if let Some(tags) = config.lambda_tags() {
+        calls.push(
+            ApiCall::new(
+                "lambda",
+                "TagResource",
+                json!({
+                    "Resource": FUNCTION_ARN,
+                    "Tags": tags.into_iter().collect::<BTreeMap<_, _>>(),
+                }),
+            )
+            .when("when the function exists, with the tags that changed"),
+        );
+    }
+
+    if config.prune_tags {
+        calls.push(
+            ApiCall::new(
+                "lambda",
+                "UntagResource",
+                json!({
+                    "Resource": FUNCTION_ARN,
+                    "TagKeys": "<tags that the deploy doesn't set>",
+                }),
+            )
+            .when("when the function exists, and it has tags that the deploy doesn't set"),
+        );
+    }
+
+    let mut request = json!({
+        "FunctionName": name,
+        "Publish": config.publish_code_without_description(),
+    });
+    let code = code_location(config, name, archive, Some(EXISTING_FUNCTION), calls)?;
+    if let (Some(code), Some(request)) = (code.as_object(), request.as_object_mut()) {
+        request.extend(code.clone());
+    }
+    let mut condition = EXISTING_FUNCTION.to_string();
+    if config.update_architecture {
+        request["Architectures"] = json!([archive.architecture().as_str()]);
+        condition.push_str(", with the architectures when the function runs on a different one");
+    }
+    calls.push(ApiCall::new("lambda", "UpdateFunctionCode", request).when(condition));
+
+    if let Some(description) = &function_config.description {
+        calls.push(
+            ApiCall::new(
+                "lambda",
+                "GetFunctionConfiguration",
+                qualified(json!({ "FunctionName": name }), config),
+            )
+            .when("until the function is ready"),
+        );
+        calls.push(ApiCall::new(
+            "lambda",
+            "PublishVersion",
+            json!({ "FunctionName": name, "Description": description }),
+        ));
+    }
+
+    let alias = config.remote_config.alias.as_deref();
+    let canary = config.canary()?;
+    if let Some(alias) = alias {
+        let alias_request = json!({ "Name": alias, "FunctionName": name });
+        calls.push(ApiCall::new("lambda", "GetAlias", alias_request.clone()));
+
+        let mut update = alias_request.clone();
+        update["FunctionVersion"] = json!(NEW_VERSION);
+        match &canary {
+            Some(canary) => {
+                let mut shift = alias_request.clone();
+                shift["FunctionVersion"] = json!("<previous version>");
+                shift["RoutingConfig"] = json!({
+                    "AdditionalVersionWeights": { NEW_VERSION: canary.weight() },
+                });
+                calls.push(
+                    ApiCall::new("lambda", "UpdateAlias", shift)
+                        .when("when the alias points to a previous version"),
+                );
+                calls.push(
+                    ApiCall::new("lambda", "UpdateAlias", update.clone())
+                        .when("when the alias exists, and it doesn't point to a previous version"),
+                );
+            }
+            None => {
+                calls.push(
+                    ApiCall::new("lambda", "UpdateAlias", update.clone())
+                        .when("when the alias exists"),
+                );
+            }
+        }
+        calls.push(
+            ApiCall::new("lambda", "CreateAlias", update).when("when the alias doesn't exist"),
+        );
+    }
+
+    if let Some(payload) = &config.smoke_test {
+        let qualifier = match (alias, &canary) {
+            (Some(alias), None) => alias,
+            _ => NEW_VERSION,
+        };
+        calls.push(ApiCall::new(
+            "lambda",
+            "Invoke",
+            json!({
+                "FunctionName": name,
+                "Qualifier": qualifier,
+                "Payload": file_contents(payload),
+            }),
+        ));
+    }
+
+    match (alias, &canary) {
+        (Some(alias), Some(canary)) => {
+            let condition = "every minute during the canary";
+            calls.push(
+                ApiCall::new(
+                    "monitoring",
+                    "GetMetricStatistics",
+                    json!({
+                        "Namespace": "AWS/Lambda",
+                        "MetricName": "Errors",
+                        "Statistics": ["Sum"],
+                        "Period": 60,
+                        "Dimensions": [
+                            { "Name": "FunctionName", "Value": name },
+                            { "Name": "Resource", "Value": format!("{name}:{alias}") },
+                            { "Name": "ExecutedVersion", "Value": NEW_VERSION },
+                        ],
+                    }),
+                )
+                .when(condition),
+            );
+            if !gate_alarms.is_empty() {
+                calls.push(describe_alarms(gate_alarms).when(condition));
+                calls.push(
+                    describe_alarms(gate_alarms)
+                        .when("when the alias doesn't point to a previous version"),
+                );
+            }
+            calls.push(
+                ApiCall::new(
+                    "lambda",
+                    "UpdateAlias",
+                    json!({
+                        "Name": alias,
+                        "FunctionName": name,
+                        "FunctionVersion": NEW_VERSION,
+                        "RoutingConfig": { "AdditionalVersionWeights": {} },
+                    }),
+                )
+                .when(format!(
+                    "after {} seconds without errors, when the canary started",
+                    canary.interval.as_secs()
+                )),
+            );
+        }
+        _ if !gate_alarms.is_empty() => calls.push(describe_alarms(gate_alarms)),
+        _ => {}
+    }
+
+    if function_config.enable_function_url {
+        let request = qualified(json!({ "FunctionName": name }), config);
+        calls.push(ApiCall::new(
+            "lambda",
+            "GetFunctionUrlConfig",
+            request.clone(),
+        ));
+
+        let condition = "when the function doesn't have a URL";
+        let mut permission = request.clone();
+        permission["Action"] = json!("lambda:InvokeFunctionUrl");
+        permission["Principal"] = json!("*");
+        permission["StatementId"] = json!("FunctionUrlAllowPublicAccess-<uuid>");
+        permission["FunctionUrlAuthType"] = json!("NONE");
+        calls.push(ApiCall::new("lambda", "AddPermission", permission).when(condition));
+
+        let mut create = request;
+        create["AuthType"] = json!("NONE");
+        calls.push(ApiCall::new("lambda", "CreateFunctionUrlConfig", create).when(condition));
+    }
+
+    let custom_domain = config
+        .custom_domain()?
+        .filter(|_| function_config.enable_function_url);
+    if let Some(domain) = custom_domain {
+        custom_domain_calls(name, &domain, calls);
+    }
+
+    if function_config.disable_function_url {
+        calls.push(ApiCall::new(
+            "lambda",
+            "DeleteFunctionUrlConfig",
+            qualified(json!({ "FunctionName": name }), config),
+        ));
+    }
+
+    for permission in config.invoke_permissions()? {
+        calls.push(add_permission(config, name, &permission));
+    }
+
+    let target_arn = target_arn(FUNCTION_ARN, alias);
+    for trigger in config.triggers()? {
+        match trigger {
+            Trigger::Schedule(schedule) => {
+                let rule_name = rule_name(name, alias, &schedule);
+                calls.push(ApiCall::new(
+                    "events",
+                    "PutRule",
+                    put_rule_body(name, &rule_name, &schedule),
+                ));
+                calls.push(ApiCall::new(
+                    "events",
+                    "PutTargets",
+                    put_targets_body(&rule_name, &target_arn),
+                ));
+                let permission = InvokePermission {
+                    principal: "events.amazonaws.com".into(),
+                    source_arn: Some("<rule ARN>".into()),
+                    source_account: Some("<account id>".into()),
+                };
+                calls.push(add_permission(config, name, &permission));
+            }
+            Trigger::HttpApi => {
+                let api_name = api_name(name, alias);
+                calls.push(ApiCall::new("apigateway", "GetApis", json!({})));
+                calls.push(
+                    ApiCall::new(
+                        "apigateway",
+                        "CreateApi",
+                        create_api_body(&api_name, &target_arn),
+                    )
+                    .when(format!("when the API `{api_name}` doesn't exist")),
+                );
+                let permission = InvokePermission {
+                    principal: "apigateway.amazonaws.com".into(),
+                    source_arn: Some("<API ARN>/*".into()),
+                    source_account: Some("<account id>".into()),
+                };
+                calls.push(add_permission(config, name, &permission));
+            }
+        }
+    }
+
+    if let Some(retention) = function_config.log_retention {
+        let log_group_name = format!("/aws/lambda/{name}");
+        let mut request = json!({ "logGroupName": log_group_name });
+        if let Some(tags) = config.lambda_tags() {
+            request["tags"] = json!(tags.into_iter().collect::<BTreeMap<_, _>>());
+        }
+        calls.push(ApiCall::new("logs", "CreateLogGroup", request));
+        calls.push(ApiCall::new(
+            "logs",
+            "PutRetentionPolicy",
+            json!({ "logGroupName": log_group_name, "retentionInDays": retention }),
+        ));
+    }
+
+    if let (Some(path), Some(bucket)) = (debug_artifact, &config.s3_bucket) {
+        calls.push(put_object(
+            config,
+            bucket,
+            &debug_artifact_key(config, name),
+            path,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Calls to create the function's role, when the deploy doesn't use an existing role.
+fn custom_domain_calls(name: &str, domain: &str, calls: &mut Vec<ApiCall>) {
+    calls.push(ApiCall::new(
+        "route53",
+        "ListHostedZonesByName",
+        json!({ "DNSName": domain }),
+    ));
+    calls.push(ApiCall::new(
+        "acm",
+        "ListCertificates",
+        json!({ "CertificateStatuses": ["ISSUED", "PENDING_VALIDATION"] }),
+    ));
+    calls.push(
+        ApiCall::new(
+            "acm",
+            "RequestCertificate",
+            request_certificate_body(domain),
+        )
+        .when("when the domain doesn't have a certificate"),
+    );
+    calls.push(ApiCall::new(
+        "acm",
+        "DescribeCertificate",
+        json!({ "CertificateArn": "<certificate ARN>" }),
+    ));
+
+    let hosted_zone = "<hosted zone id>";
+    calls.push(
+        ApiCall::new(
+            "route53",
+            "ChangeResourceRecordSets",
+            json!({
+                "HostedZoneId": hosted_zone,
+                "Changes": [{
+                    "Action": "UPSERT",
+                    "Name": "<validation record name>",
+                    "Type": "CNAME",
+                    "Value": "<validation record value>",
+                }],
+            }),
+        )
+        .when("when the certificate is waiting for the DNS validation and the domain has a hosted zone"),
+    );
+
+    calls.push(ApiCall::new("cloudfront", "ListDistributions", json!({})));
+    calls.push(
+        ApiCall::new(
+            "cloudfront",
+            "CreateDistribution",
+            json!({
+                "CallerReference": caller_reference(domain),
+                "Aliases": [domain],
+                "Origin": "<function URL domain>",
+                "CachePolicyId": CACHING_DISABLED_POLICY_ID,
+                "OriginRequestPolicyId": ALL_VIEWER_EXCEPT_HOST_HEADER_POLICY_ID,
+                "Comment": format!("Custom domain for the {name} function"),
+                "ViewerCertificate": { "ACMCertificateArn": "<certificate ARN>" },
+            }),
+        )
+        .when("when the domain doesn't have a distribution"),
+    );
+
+    let changes = ["A", "AAAA"]
+        .iter()
+        .map(|kind| {
+            json!({
+                "Action": "UPSERT",
+                "Name": domain,
+                "Type": kind,
+                "AliasTarget": { "DNSName": "<distribution domain>" },
+            })
+        })
+        .collect::<Vec<_>>();
+    calls.push(
+        ApiCall::new(
+            "route53",
+            "ChangeResourceRecordSets",
+            json!({ "HostedZoneId": hosted_zone, "Changes": changes }),
+        )
+        .when("when the domain has a hosted zone"),
+    );
+}
+
+fn role_calls(config: &Deploy, calls: &mut Vec<ApiCall>) {
+    let role_name = "cargo-lambda-role-<uuid>";
+    let mut policy = trust_policy("<caller ARN>");
+
+    let mut request = json!({
+        "RoleName": role_name,
+        "AssumeRolePolicyDocument": policy.to_string(),
+    });
+    if let Some(tags) = config.lambda_tags() {
+        let tags = tags
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .map(|(key, value)| json!({ "Key": key, "Value": value }))
+            .collect::<Vec<_>>();
+        request["Tags"] = json!(tags);
+    }
+
+    if let Some(statements) = policy["Statement"].as_array_mut() {
+        statements.pop();
+    }
+
+    let role_calls = [
+        ApiCall::new("sts", "GetCallerIdentity", json!({})),
+        ApiCall::new("iam", "CreateRole", request),
+        ApiCall::new(
+            "iam",
+            "AttachRolePolicy",
+            json!({ "RoleName": role_name, "PolicyArn": BASIC_LAMBDA_EXECUTION_POLICY }),
+        ),
+        ApiCall::new(
+            "sts",
+            "AssumeRole",
+            json!({
+                "RoleArn": "<new role ARN>",
+                "RoleSessionName": "cargo_lambda_session_<uuid>",
+            }),
+        ),
+        ApiCall::new(
+            "iam",
+            "UpdateAssumeRolePolicy",
+            json!({ "RoleName": role_name, "PolicyDocument": policy.to_string() }),
+        ),
+    ];
+    calls.extend(role_calls.map(|call| call.when(NEW_FUNCTION)));
+}
+
+/// Settings of the function in the requests to create it, or to update its configuration.
+/// Updates only include the settings that the deploy sets explicitly.
+fn insert_configuration(config: &Deploy, request: &mut Value, update: bool) -> Result<()> {
+    let function_config = &config.function_config;
+    let Some(request) = request.as_object_mut() else {
+        return Ok(());
+    };
+
+    if update {
+        insert(request, "Role", function_config.role.as_ref());
+        insert(request, "Runtime", function_config.runtime.as_ref());
+        insert(request, "Handler", function_config.handler.as_ref());
+        insert(
+            request,
+            "Timeout",
+            function_config.timeout.clone().map(i32::from),
+        );
+    }
+    insert(
+        request,
+        "MemorySize",
+        function_config.memory.clone().map(i32::from),
+    );
+    insert(request, "Layers", function_config.layer.as_ref());
+    insert(
+        request,
+        "TracingConfig",
+        function_config
+            .tracing
+            .as_ref()
+            .map(|tracing| json!({ "Mode": tracing.to_string() })),
+    );
+
+    let mut variables = config
+        .lambda_environment()?
+        .and_then(|env| env.variables)
+        .unwrap_or_default()
+        .into_keys()
+        .collect::<BTreeSet<_>>();
+    // Dry runs don't fetch the stack outputs, but the deploy adds them to the environment.
+    variables.extend(config.stack_outputs()?.into_iter().map(|o| o.env_var));
+    if !variables.is_empty() {
+        let variables = variables
+            .into_iter()
+            .map(|key| (key, REDACTED))
+            .collect::<BTreeMap<_, _>>();
+        request.insert("Environment".into(), json!({ "Variables": variables }));
+    }
+
+    if let Some(vpc) = &function_config.vpc {
+        if !update || vpc.should_update() {
+            let mut vpc_config = Map::new();
+            insert(&mut vpc_config, "SubnetIds", vpc.subnet_ids.as_ref());
+            insert(
+                &mut vpc_config,
+                "SecurityGroupIds",
+                vpc.security_group_ids.as_ref(),
+            );
+            vpc_config.insert(
+                "Ipv6AllowedForDualStack".into(),
+                json!(vpc.ipv6_allowed_for_dual_stack),
+            );
+            request.insert("VpcConfig".into(), Value::Object(vpc_config));
+        }
+    }
+
+    Ok(())
+}
+
+/// Location of the code in the requests that deploy it, uploading it to S3 first
+/// when the deploy stores the code there.
+fn code_location(
+    config: &Deploy,
+    name: &str,
+    archive: &BinaryArchive,
+    condition: Option<&str>,
+    calls: &mut Vec<ApiCall>,
+) -> Result<Value> {
+    let Some(bucket) = &config.s3_bucket else {
+        return Ok(json!({ "ZipFile": file_contents(&archive.path) }));
+    };
+
+    let key = config.s3_key.as_deref().unwrap_or(name);
+    let mut uploads = upload_calls(config, bucket, key, &archive.path)?;
+    if let Some(condition) = condition {
+        for call in &mut uploads {
+            call.condition = Some(condition.to_string());
+        }
+    }
+    calls.extend(uploads);
+
+    Ok(json!({ "S3Bucket": bucket, "S3Key": key }))
+}
+
+/// Calls to upload the zip file to S3. Big files are uploaded in parts.
+fn upload_calls(config: &Deploy, bucket: &str, key: &str, path: &Path) -> Result<Vec<ApiCall>> {
+    let size = std::fs::metadata(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read zip file `{path:?}`"))?
+        .len();
+
+    if size <= PART_SIZE {
+        return Ok(vec![put_object(config, bucket, key, path)]);
+    }
+
+    let mut request = json!({ "Bucket": bucket, "Key": key });
+    if let Some(tagging) = config.s3_tags() {
+        request["Tagging"] = json!(tagging);
+    }
+    let mut calls = vec![ApiCall::new("s3", "CreateMultipartUpload", request)];
+
+    let parts = size.div_ceil(PART_SIZE);
+    for part in 1..=parts {
+        let start = (part - 1) * PART_SIZE;
+        let end = (part * PART_SIZE).min(size);
+        calls.push(ApiCall::new(
+            "s3",
+            "UploadPart",
+            json!({
+                "Bucket": bucket,
+                "Key": key,
+                "UploadId": "<upload id>",
+                "PartNumber": part,
+                "ContentLength": end - start,
+                "Body": format!("<bytes {start} to {end} of {}>", path.display()),
+            }),
+        ));
+    }
+
+    calls.push(ApiCall::new(
+        "s3",
+        "CompleteMultipartUpload",
+        json!({
+            "Bucket": bucket,
+            "Key": key,
+            "UploadId": "<upload id>",
+            "MultipartUpload": { "Parts": format!("<ETags of the {parts} parts>") },
+        }),
+    ));
+    Ok(calls)
+}
+
+fn put_object(config: &Deploy, bucket: &str, key: &str, path: &Path) -> ApiCall {
+    let mut request = json!({
+        "Bucket": bucket,
+        "Key": key,
+        "Body": file_contents(path),
+    });
+    if let Some(tagging) = config.s3_tags() {
+        request["Tagging"] = json!(tagging);
+    }
+    ApiCall::new("s3", "PutObject", request)
+}
+
+fn add_permission(config: &Deploy, name: &str, permission: &InvokePermission) -> ApiCall {
+    let mut request = qualified(json!({ "FunctionName": name }), config);
+    request["Action"] = json!("lambda:InvokeFunction");
+    request["Principal"] = json!(permission.principal);
+    if let Some(source_arn) = &permission.source_arn {
+        request["SourceArn"] = json!(source_arn);
+    }
+    if let Some(source_account) = &permission.source_account {
+        request["SourceAccount"] = json!(source_account);
+    }
+    request["StatementId"] = json!(permission.statement_id());
+    ApiCall::new("lambda", "AddPermission", request)
+}
+
+fn describe_alarms(alarms: &[String]) -> ApiCall {
+    ApiCall::new(
+        "monitoring",
+        "DescribeAlarms",
+        json!({
+            "AlarmNames": alarms,
+            "AlarmTypes": ["MetricAlarm", "CompositeAlarm"],
+        }),
+    )
+}
+
+/// Add the alias as the request's qualifier, when the deploy uses an alias.
+fn qualified(mut request: Value, config: &Deploy) -> Value {
+    if let Some(alias) = &config.remote_config.alias {
+        request["Qualifier"] = json!(alias);
+    }
+    request
+}
+
+fn insert<T: Serialize>(request: &mut Map<String, Value>, key: &str, value: Option<T>) {
+    if let Some(value) = value {
+        request.insert(key.into(), json!(value));
+    }
+}
+
+/// Files are not included in the requests, only their paths.
+fn file_contents(path: &Path) -> String {
+    format!("<contents of {}>", path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cargo_lambda_build::BinaryModifiedAt;
+    use cargo_lambda_metadata::env::EnvOptions;
+
+    fn archive() -> BinaryArchive {
+        BinaryArchive::new(
+            "../../tests/binaries/bootstrap.zip".into(),
+            "arm64".into(),
+            BinaryModifiedAt::now(),
+        )
+    }
+
+    fn operations(calls: &[ApiCall]) -> Vec<String> {
+        calls
+            .iter()
+            .map(|call| format!("{}:{}", call.service, call.operation))
+            .collect()
+    }
+
+    fn find<'a>(calls: &'a [ApiCall], operation: &str) -> &'a ApiCall {
+        calls
+            .iter()
+            .find(|call| call.operation == operation)
+            .unwrap_or_else(|| panic!("missing {operation} call"))
+    }
+
+    #[test]
+    fn test_plan_function() {
+        let mut config = Deploy::default();
+        config.function_config.memory = Some(512.try_into().unwrap());
+        config.function_config.env_options = Some(EnvOptions {
+            env_var: Some(vec!["API_KEY=super-secret".into()]),
+            ..Default::default()
+        });
+        config.tag = Some(vec!["team=payments".into()]);
+        config.remote_config.alias = Some("live".into());
+        config.trigger = Some(vec!["eventbridge:rate(5 minutes)".into()]);
+
+        let calls = plan(&config, "orders-api", &archive(), None).unwrap();
+        assert_eq!(
+            vec![
+                "lambda:GetFunction",
+                "sts:GetCallerIdentity",
+                "iam:CreateRole",
+                "iam:AttachRolePolicy",
+                "sts:AssumeRole",
+                "iam:UpdateAssumeRolePolicy",
+                "lambda:CreateFunction",
+                "lambda:UpdateFunctionConfiguration",
+                "lambda:TagResource",
+                "lambda:UpdateFunctionCode",
+                "lambda:GetAlias",
+                "lambda:UpdateAlias",
+                "lambda:CreateAlias",
+                "events:PutRule",
+                "events:PutTargets",
+                "lambda:AddPermission",
+            ],
+            operations(&calls)
+        );
+
+        let create = find(&calls, "CreateFunction");
+        assert_eq!(Some(NEW_FUNCTION), create.condition.as_deref());
+        assert_eq!("orders-api", create.request["FunctionName"]);
+        assert_eq!("<new role ARN>", create.request["Role"]);
+        assert_eq!(json!(["arm64"]), create.request["Architectures"]);
+        assert_eq!(512, create.request["MemorySize"]);
+        assert_eq!(json!({ "team": "payments" }), create.request["Tags"]);
+        assert_eq!(
+            json!({ "Variables": { "API_KEY": REDACTED } }),
+            create.request["Environment"]
+        );
+        assert_eq!(
+            "<contents of ../../tests/binaries/bootstrap.zip>",
+            create.request["Code"]["ZipFile"]
+        );
+
+        let targets = find(&calls, "PutTargets");
+        assert_eq!("<function ARN>:live", targets.request["Targets"][0]["Arn"]);
+
+        let json = serde_json::to_string(&calls).unwrap();
+        assert!(!json.contains("super-secret"), "{json}");
+    }
+
+    #[test]
+    fn test_plan_function_with_custom_domain() {
+        let mut config = Deploy::default();
+        config.function_config.role = Some("arn:aws:iam::123456789012:role/orders".into());
+        config.function_config.enable_function_url = true;
+        config.custom_domain = Some("api.example.com".into());
+
+        let calls = plan(&config, "orders-api", &archive(), None).unwrap();
+        let operations = operations(&calls);
+        let start = operations
+            .iter()
+            .position(|o| o == "route53:ListHostedZonesByName")
+            .unwrap();
+        assert_eq!(
+            vec![
+                "route53:ListHostedZonesByName",
+                "acm:ListCertificates",
+                "acm:RequestCertificate",
+                "acm:DescribeCertificate",
+                "route53:ChangeResourceRecordSets",
+                "cloudfront:ListDistributions",
+                "cloudfront:CreateDistribution",
+                "route53:ChangeResourceRecordSets",
+            ],
+            operations[start..]
+        );
+
+        let distribution = find(&calls, "CreateDistribution");
+        assert_eq!(json!(["api.example.com"]), distribution.request["Aliases"]);
+        assert_eq!(
+            "cargo-lambda-api.example.com",
+            distribution.request["CallerReference"]
+        );
+
+        config.function_config.enable_function_url = false;
+        let calls = plan(&config, "orders-api", &archive(), None).unwrap();
+        assert!(!calls.iter().any(|call| call.service == "cloudfront"));
+    }
+
+    #[test]
+    fn test_plan_function_with_existing_role_and_canary() {
+        let mut config = Deploy::default();
+        config.function_config.role = Some("arn:aws:iam::123456789012:role/orders".into());
+        config.remote_config.alias = Some("live".into());
+        config.canary = Some("10%:5m".into());
+        config.s3_bucket = Some("artifacts".into());
+        config.resolve_output = Some(vec!["infra:TableName=TABLE_NAME".into()]);
+
+        let calls = plan(&config, "orders-api", &archive(), None).unwrap();
+        assert_eq!(
+            vec![
+                "cloudformation:DescribeStacks",
+                "lambda:GetFunction",
+                "s3:PutObject",
+                "lambda:CreateFunction",
+                "lambda:UpdateFunctionConfiguration",
+                "s3:PutObject",
+                "lambda:UpdateFunctionCode",
+                "lambda:GetAlias",
+                "lambda:UpdateAlias",
+                "lambda:UpdateAlias",
+                "lambda:CreateAlias",
+                "monitoring:GetMetricStatistics",
+                "lambda:UpdateAlias",
+            ],
+            operations(&calls)
+        );
+
+        let create = find(&calls, "CreateFunction");
+        assert_eq!(
+            json!({ "S3Bucket": "artifacts", "S3Key": "orders-api" }),
+            create.request["Code"]
+        );
+        assert_eq!(
+            json!({ "Variables": { "TABLE_NAME": REDACTED } }),
+            create.request["Environment"]
+        );
+
+        let shift = &calls[8];
+        assert_eq!("<previous version>", shift.request["FunctionVersion"]);
+        assert_eq!(
+            json!({ "AdditionalVersionWeights": { NEW_VERSION: 0.1 } }),
+            shift.request["RoutingConfig"]
+        );
+    }
+
+    #[test]
+    fn test_plan_extension() {
+        let mut config = Deploy::default();
+        config.extension = true;
+        config.audit_log = Some("s3://deploys/audit".into());
+
+        let calls = plan(&config, "logs-extension", &archive(), None).unwrap();
+        assert_eq!(
+            vec![
+                "lambda:PublishLayerVersion",
+                "sts:GetCallerIdentity",
+                "s3:PutObject"
+            ],
+            operations(&calls)
+        );
+
+        let publish = &calls[0];
+        assert_eq!(json!(["arm64"]), publish.request["CompatibleArchitectures"]);
+        assert_eq!(
+            "audit/logs-extension/<deploy time>.json",
+            calls[2].request["Key"]
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use aws_sdk_s3::{Client as S3Client, primitives::ByteStream};
+use aws_sdk_sts::Client as StsClient;
+use cargo_lambda_build::BinaryArchive;
+use cargo_lambda_metadata::cargo::deploy::{AuditLog, Deploy};
+use cargo_lambda_remote::aws_sdk_config::SdkConfig;
+use chrono::{DateTime, SecondsFormat, Utc};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::{
+    DeployResult,
+    checksum::code_sha256,
+    git::{GitRevision, project_dir},
+};
+
+/// AuditRecord describes a deploy, so teams can
+/// track who deployed what, and when.
+#[derive(Debug, Serialize)]
+pub(crate) struct AuditRecord {
+    name: String,
+    kind: &'static str,
+    arn: String,
+    version: Option<String>,
+    alias: Option<String>,
+    deployed_by: Option<String>,
+    #[serde(serialize_with = "serialize_timestamp")]
+    deployed_at: DateTime<Utc>,
+    git_commit: Option<String>,
+    code_sha256: String,
+}
+
+impl AuditRecord {
+    /// Create a record for a deploy. Dry runs and plans don't deploy anything,
+    /// so they don't have a record.
+    pub(crate) fn new(
+        name: &str,
+        result: &DeployResult,
+        archive: &BinaryArchive,
+        deployed_at: DateTime<Utc>,
+    ) -> Result<Option<AuditRecord>> {
+        let (kind, arn, version, alias) = match result {
+            DeployResult::Function(output) => (
+                "function",
+                output.function_arn.clone(),
+                Some(output.version.clone()),
+                output.alias.clone(),
+            ),
+            DeployResult::Extension(output) => {
+                ("extension", output.extension_arn.clone(), None, None)
+            }
+            DeployResult::Dry(_) | DeployResult::Plan(_) => return Ok(None),
+        };
+
+        Ok(Some(AuditRecord {
+            name: name.to_string(),
+            kind,
+            arn,
+            version,
+            alias,
+            deployed_by: None,
+            deployed_at,
+            git_commit: None,
+            code_sha256: code_sha256(&archive.read()?),
+        }))
+    }
+}
+
+/// Write the record of a deploy to the audit log.
+/// Each record is a different object in S3, so concurrent deploys
+/// don't overwrite each other's records.
+pub(crate) async fn record(
+    config: &Deploy,
+    audit_log: &AuditLog,
+    sdk_config: &SdkConfig,
+    mut record: AuditRecord,
+) -> Result<()> {
+    record.deployed_by = caller_identity(sdk_config).await;
+    record.git_commit = GitRevision::read(project_dir(&config.manifest_path())).map(|r| r.commit);
+
+    let key = audit_key(audit_log, &record.name, record.deployed_at);
+    let s3_client = S3Client::new(sdk_config);
+
+    write_record(&s3_client, &audit_log.bucket, &key, &record).await
+}
+
+async fn write_record(
+    s3_client: &S3Client,
+    bucket: &str,
+    key: &str,
+    record: &AuditRecord,
+) -> Result<()> {
+    debug!(
+        bucket,
+        key,
+        ?record,
+        "writing deploy record to the audit log"
+    );
+
+    let body = serde_json::to_vec(record)
+        .into_diagnostic()
+        .wrap_err("failed to serialize the deploy record")?;
+
+    s3_client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .content_type("application/json")
+        .body(ByteStream::from(body))
+        .send()
+        .await
+        .into_diagnostic()
+        .wrap_err("the deploy completed, but the record couldn't be written to the audit log")?;
+
+    Ok(())
+}
+
+fn serialize_timestamp<S>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&timestamp.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+fn audit_key(audit_log: &AuditLog, name: &str, deployed_at: DateTime<Utc>) -> String {
+    let file = format!("{name}/{}.json", deployed_at.format("%Y%m%dT%H%M%S%.3fZ"));
+    if audit_log.prefix.is_empty() {
+        file
+    } else {
+        format!("{}/{file}", audit_log.prefix)
+    }
+}
+
+async fn caller_identity(sdk_config: &SdkConfig) -> Option<String> {
+    let sts_client = StsClient::new(sdk_config);
+    match sts_client.get_caller_identity().send().await {
+        Ok(identity) => identity.arn,
+        Err(error) => {
+            warn!(?error, "failed to get the identity of the user deploying");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_credential_types::Credentials;
+    use aws_sdk_s3::config::{Config as S3Config, Region};
+    use aws_smithy_runtime::client::http::test_util::{ReplayEvent, StaticReplayClient};
+    use aws_smithy_types::body::SdkBody;
+    use chrono::TimeZone;
+    use http::{Request, Response};
+
+    fn deployed_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 5, 10, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn test_audit_key() {
+        let audit_log = AuditLog {
+            bucket: "deploys".into(),
+            prefix: "audit".into(),
+        };
+        assert_eq!(
+            "audit/basic-lambda/20240305T103000.000Z.json",
+            audit_key(&audit_log, "basic-lambda", deployed_at())
+        );
+
+        let audit_log = AuditLog {
+            bucket: "deploys".into(),
+            prefix: "".into(),
+        };
+        assert_eq!(
+            "basic-lambda/20240305T103000.000Z.json",
+            audit_key(&audit_log, "basic-lambda", deployed_at())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_record() {
+        let record = AuditRecord {
+            name: "basic-lambda".into(),
+            kind: "function",
+            arn: "arn:aws:lambda:us-east-1:123456789012:function:basic-lambda".into(),
+            version: Some("3".into()),
+            alias: None,
+            deployed_by: Some("arn:aws:iam::123456789012:user/david".into()),
+            deployed_at: deployed_at(),
+            git_commit: Some("a1b2c3d".into()),
+            code_sha256: code_sha256(&[]),
+        };
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["deployed_at"], "2024-03-05T10:30:00.000Z");
+
+        let request = Request::builder()
+            .uri("https://deploys.s3.us-east-1.amazonaws.com/audit/basic-lambda/20240305T103000.000Z.json?x-id=PutObject")
+            .method("PUT")
+            .header("content-type", "application/json")
+            .body(SdkBody::from(serde_json::to_vec(&record).unwrap()))
+            .unwrap();
+        let response = Response::builder()
+            .status(200)
+            .body(SdkBody::empty())
+            .unwrap();
+
+        let http_client = StaticReplayClient::new(vec![ReplayEvent::new(request, response)]);
+        let s3_config = S3Config::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let s3_client = S3Client::from_conf(s3_config);
+
+        write_record(
+            &s3_client,
+            "deploys",
+            "audit/basic-lambda/20240305T103000.000Z.json",
+            &record,
+        )
+        .await
+        .unwrap();
+
+        http_client.assert_requests_match(&[]);
+    }
+}
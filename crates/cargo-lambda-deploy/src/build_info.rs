@@ -0,0 +1,82 @@
+use cargo_lambda_build::BuildManifest;
+use cargo_lambda_metadata::cargo::{CargoMetadata, deploy::Deploy, target_dir_from_metadata};
+use std::{collections::BTreeMap, path::PathBuf};
+use tracing::debug;
+
+use crate::binary_name_or_default;
+
+/// Build information that `cargo lambda build --embed-build-info` recorded
+/// in the build manifest for the binary to deploy.
+/// Binaries deployed from a path are not in the manifest, so they don't have it.
+pub(crate) fn load(
+    config: &Deploy,
+    metadata: &CargoMetadata,
+    name: &str,
+) -> BTreeMap<String, String> {
+    if config.binary_path.is_some() {
+        return BTreeMap::new();
+    }
+
+    let lambda_dir = config.lambda_dir.clone().unwrap_or_else(|| {
+        target_dir_from_metadata(metadata)
+            .unwrap_or_else(|_| PathBuf::from("target"))
+            .join("lambda")
+    });
+
+    let binary_name = binary_name_or_default(config, name);
+    let info = BuildManifest::load(&lambda_dir)
+        .artifacts
+        .remove(&binary_name)
+        .map(|artifact| artifact.build_info)
+        .unwrap_or_default();
+    debug!(binary_name, ?info, "build info");
+    info
+}
+
+/// Tags with the build information, in the `key=value` format of the `--tag` flag.
+pub(crate) fn tags(info: &BTreeMap<String, String>) -> Vec<String> {
+    info.iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cargo_lambda_build::ManifestArtifact;
+    use cargo_lambda_metadata::cargo::load_metadata;
+
+    #[test]
+    fn test_load_build_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let info = BTreeMap::from([
+            ("BUILD_TIME".to_string(), "2024-05-01T10:00:00Z".to_string()),
+            ("GIT_SHA".to_string(), "abc123".to_string()),
+        ]);
+
+        let mut manifest = BuildManifest::default();
+        manifest.insert(
+            "basic-lambda",
+            ManifestArtifact {
+                kind: "function".into(),
+                path: PathBuf::from("basic-lambda/bootstrap"),
+                architecture: "x86_64".into(),
+                sha256: "abc".into(),
+                profile: "release".into(),
+                build_info: info.clone(),
+            },
+        );
+        manifest.write(dir.path()).unwrap();
+
+        let metadata = load_metadata("../../tests/fixtures/examples-package/Cargo.toml").unwrap();
+        let mut config = Deploy::default();
+        config.lambda_dir = Some(dir.path().to_path_buf());
+
+        assert_eq!(info, load(&config, &metadata, "basic-lambda"));
+        assert!(load(&config, &metadata, "other-lambda").is_empty());
+        assert_eq!(
+            vec!["BUILD_TIME=2024-05-01T10:00:00Z", "GIT_SHA=abc123"],
+            tags(&info)
+        );
+    }
+}
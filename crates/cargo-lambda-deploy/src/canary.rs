@@ -0,0 +1,354 @@
+use aws_smithy_xml::decode::{Document, XmlDecodeError, try_data};
+use cargo_lambda_interactive::progress::Progress;
+use cargo_lambda_metadata::cargo::deploy::Canary;
+use cargo_lambda_remote::{
+    aws_sdk_config::SdkConfig,
+    aws_sdk_lambda::{Client as LambdaClient, types::AliasRoutingConfiguration},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::time::Duration;
+use tokio::time::{Instant, sleep};
+use tracing::{debug, warn};
+
+use crate::{
+    alarms::{self, CLOUDWATCH_API_VERSION},
+    functions::{alias_doesnt_exist_error, upsert_alias},
+    plan::{Preconditions, check_revision},
+    query_api,
+};
+
+/// Time between checks of the error metrics of the new version.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Send part of the alias traffic to the new version. When the alias doesn't point
+/// to a previous version, it points the alias to the new version without a canary.
+/// It returns the version that the alias pointed to before the deploy.
+pub(crate) async fn start(
+    client: &LambdaClient,
+    name: &str,
+    alias: &str,
+    version: &str,
+    canary: &Canary,
+    preconditions: Option<&Preconditions>,
+) -> Result<Option<String>> {
+    match alias_version(client, name, alias).await? {
+        Some(previous) if previous != version => {
+            shift_traffic(
+                client,
+                name,
+                alias,
+                version,
+                &previous,
+                canary,
+                preconditions,
+            )
+            .await?;
+            Ok(Some(previous))
+        }
+        _ => {
+            warn!(
+                alias,
+                "the alias doesn't point to a previous version of the function, skipping the canary"
+            );
+            upsert_alias(name, alias, version, preconditions, client).await
+        }
+    }
+}
+
+/// Version that the alias points to, None if the alias doesn't exist.
+async fn alias_version(client: &LambdaClient, name: &str, alias: &str) -> Result<Option<String>> {
+    match client
+        .get_alias()
+        .name(alias)
+        .function_name(name)
+        .send()
+        .await
+    {
+        Ok(current) => Ok(current.function_version),
+        Err(err) if alias_doesnt_exist_error(&err) => Ok(None),
+        Err(err) => Err(err).into_diagnostic().wrap_err("failed to fetch alias"),
+    }
+}
+
+/// Send a percentage of the alias traffic to the new version,
+/// and keep the rest on the version that the alias points to.
+async fn shift_traffic(
+    client: &LambdaClient,
+    name: &str,
+    alias: &str,
+    version: &str,
+    previous_version: &str,
+    canary: &Canary,
+    preconditions: Option<&Preconditions>,
+) -> Result<()> {
+    let routing = AliasRoutingConfiguration::builder()
+        .additional_version_weights(version, canary.weight())
+        .build();
+    let revision = preconditions
+        .map(|preconditions| preconditions.alias_revision(true))
+        .transpose()?
+        .flatten();
+
+    let result = client
+        .update_alias()
+        .name(alias)
+        .function_name(name)
+        .function_version(previous_version)
+        .routing_config(routing)
+        .set_revision_id(revision)
+        .send()
+        .await;
+    check_revision(preconditions, result)?
+        .into_diagnostic()
+        .wrap_err("failed to shift alias traffic to the new version")?;
+    Ok(())
+}
+
+/// Check the errors that the new version reports in CloudWatch until the interval finishes.
+/// It fails as soon as the new version reports any error, or any of the gate alarms goes off.
+pub(crate) async fn monitor(
+    sdk_config: &SdkConfig,
+    name: &str,
+    alias: &str,
+    version: &str,
+    canary: &Canary,
+    gate_alarms: &[String],
+    progress: &Progress,
+) -> Result<()> {
+    // Datapoints are aggregated by minute, start a minute earlier to include
+    // the invocations of the minute when the traffic was shifted.
+    let started_at = Utc::now() - CHECK_INTERVAL;
+    let deadline = Instant::now() + canary.interval;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+
+        progress.set_message(&format!(
+            "sending {}% of the traffic to version {version}. Promoting it in {} seconds if it doesn't report errors",
+            canary.percentage,
+            remaining.as_secs()
+        ));
+        sleep(remaining.min(CHECK_INTERVAL)).await;
+
+        let body = metric_statistics_request(name, alias, version, started_at, Utc::now());
+        let text = query_api::send(sdk_config, "monitoring", body)
+            .await
+            .wrap_err("failed to fetch the error metrics of the new version")?
+            .map_err(|message| {
+                miette::miette!("failed to fetch the error metrics of the new version: {message}")
+            })?;
+
+        let errors = parse_error_sum(&text)
+            .into_diagnostic()
+            .wrap_err("failed to read the error metrics of the new version")?;
+        debug!(version, errors, "canary errors");
+
+        if errors > 0.0 {
+            return Err(miette::miette!(
+                "version {version} reported {errors} errors while receiving {}% of the traffic",
+                canary.percentage
+            ));
+        }
+
+        alarms::check(sdk_config, gate_alarms).await?;
+    }
+}
+
+/// GetMetricStatistics request for the errors of a version invoked through the alias.
+fn metric_statistics_request(
+    name: &str,
+    alias: &str,
+    version: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> String {
+    let resource = format!("{name}:{alias}");
+    let dimensions = [
+        ("FunctionName", name),
+        ("Resource", resource.as_str()),
+        ("ExecutedVersion", version),
+    ];
+
+    let mut params = vec![
+        ("Action".to_string(), "GetMetricStatistics".to_string()),
+        ("Version".to_string(), CLOUDWATCH_API_VERSION.to_string()),
+        ("Namespace".to_string(), "AWS/Lambda".to_string()),
+        ("MetricName".to_string(), "Errors".to_string()),
+        (
+            "StartTime".to_string(),
+            start.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ),
+        (
+            "EndTime".to_string(),
+            end.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ),
+        ("Period".to_string(), CHECK_INTERVAL.as_secs().to_string()),
+        ("Statistics.member.1".to_string(), "Sum".to_string()),
+    ];
+    for (idx, (dimension, value)) in dimensions.into_iter().enumerate() {
+        let idx = idx + 1;
+        params.push((
+            format!("Dimensions.member.{idx}.Name"),
+            dimension.to_string(),
+        ));
+        params.push((format!("Dimensions.member.{idx}.Value"), value.to_string()));
+    }
+
+    params
+        .iter()
+        .map(|(key, value)| format!("{key}={}", urlencoding::encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Add the sums of the datapoints in a GetMetricStatistics response:
+/// GetMetricStatisticsResponse > GetMetricStatisticsResult > Datapoints > member > Sum.
+fn parse_error_sum(body: &str) -> Result<f64, XmlDecodeError> {
+    let mut sum = 0.0;
+
+    let mut doc = Document::new(body);
+    let mut root = doc.root_element()?;
+    while let Some(mut result) = root.next_tag() {
+        if !result.start_el().matches("GetMetricStatisticsResult") {
+            continue;
+        }
+        while let Some(mut datapoints) = result.next_tag() {
+            if !datapoints.start_el().matches("Datapoints") {
+                continue;
+            }
+            while let Some(mut member) = datapoints.next_tag() {
+                while let Some(mut tag) = member.next_tag() {
+                    if tag.start_el().matches("Sum") {
+                        let value = try_data(&mut tag)?;
+                        sum += value.parse::<f64>().map_err(|_| {
+                            XmlDecodeError::custom(format!("invalid datapoint sum `{value}`"))
+                        })?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_credential_types::Credentials;
+    use aws_smithy_runtime::client::http::test_util::{ReplayEvent, StaticReplayClient};
+    use aws_smithy_types::body::SdkBody;
+    use cargo_lambda_remote::aws_sdk_lambda::config::{Config as LambdaConfig, Region};
+    use http::{Request, Response};
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_start() {
+        let alias_uri = "https://lambda.us-east-1.amazonaws.com/2015-03-31/functions/test-function/aliases/live";
+        let alias_response = |version: &str| {
+            Response::builder()
+                .status(200)
+                .body(SdkBody::from(
+                    serde_json::json!({ "Name": "live", "FunctionVersion": version }).to_string(),
+                ))
+                .unwrap()
+        };
+
+        let http_client = StaticReplayClient::new(vec![
+            ReplayEvent::new(
+                Request::builder()
+                    .uri(alias_uri)
+                    .method("GET")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                alias_response("3"),
+            ),
+            ReplayEvent::new(
+                Request::builder()
+                    .uri(alias_uri)
+                    .method("PUT")
+                    .body(SdkBody::from(
+                        serde_json::json!({
+                            "FunctionVersion": "3",
+                            "RoutingConfig": { "AdditionalVersionWeights": { "4": 0.1 } }
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+                alias_response("3"),
+            ),
+        ]);
+
+        let config = LambdaConfig::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let client = LambdaClient::from_conf(config);
+
+        let canary = Canary::from_str("10%:5m").unwrap();
+        let previous = start(&client, "test-function", "live", "4", &canary, None)
+            .await
+            .unwrap();
+        assert_eq!(Some("3".to_string()), previous);
+
+        http_client.assert_requests_match(&[]);
+    }
+
+    #[test]
+    fn test_metric_statistics_request() {
+        let start = DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z")
+            .unwrap()
+            .to_utc();
+        let end = DateTime::parse_from_rfc3339("2024-05-01T10:05:30Z")
+            .unwrap()
+            .to_utc();
+
+        let body = metric_statistics_request("orders-api", "live", "7", start, end);
+        assert_eq!(
+            "Action=GetMetricStatistics&Version=2010-08-01&Namespace=AWS%2FLambda&MetricName=Errors\
+            &StartTime=2024-05-01T10%3A00%3A00Z&EndTime=2024-05-01T10%3A05%3A30Z&Period=60&Statistics.member.1=Sum\
+            &Dimensions.member.1.Name=FunctionName&Dimensions.member.1.Value=orders-api\
+            &Dimensions.member.2.Name=Resource&Dimensions.member.2.Value=orders-api%3Alive\
+            &Dimensions.member.3.Name=ExecutedVersion&Dimensions.member.3.Value=7",
+            body
+        );
+    }
+
+    #[test]
+    fn test_parse_error_sum() {
+        let body = r#"<GetMetricStatisticsResponse xmlns="http://monitoring.amazonaws.com/doc/2010-08-01/">
+  <GetMetricStatisticsResult>
+    <Datapoints>
+      <member>
+        <Timestamp>2024-05-01T10:00:00Z</Timestamp>
+        <Sum>2.0</Sum>
+        <Unit>Count</Unit>
+      </member>
+      <member>
+        <Timestamp>2024-05-01T10:01:00Z</Timestamp>
+        <Sum>1.0</Sum>
+        <Unit>Count</Unit>
+      </member>
+    </Datapoints>
+    <Label>Errors</Label>
+  </GetMetricStatisticsResult>
+  <ResponseMetadata>
+    <RequestId>b9b4b068-3a41-11e5-94eb-example</RequestId>
+  </ResponseMetadata>
+</GetMetricStatisticsResponse>"#;
+        assert_eq!(3.0, parse_error_sum(body).unwrap());
+
+        let body = r#"<GetMetricStatisticsResponse xmlns="http://monitoring.amazonaws.com/doc/2010-08-01/">
+  <GetMetricStatisticsResult>
+    <Datapoints/>
+    <Label>Errors</Label>
+  </GetMetricStatisticsResult>
+</GetMetricStatisticsResponse>"#;
+        assert_eq!(0.0, parse_error_sum(body).unwrap());
+    }
+}
@@ -0,0 +1,64 @@
+use base64::prelude::*;
+use cargo_lambda_build::BinaryArchive;
+use cargo_lambda_metadata::cargo::deploy::Deploy;
+use miette::Result;
+use sha2::{Digest, Sha256};
+
+/// Lambda reports the code hash as the base64 encoded SHA256 of the zip file,
+/// use the same format so the local hash can be compared with the function.
+pub(crate) fn code_sha256(data: &[u8]) -> String {
+    BASE64_STANDARD.encode(Sha256::digest(data))
+}
+
+/// Hash of the zip file that Lambda must report after the upload,
+/// None when the deploy doesn't verify the checksum.
+pub(crate) fn expected_code_sha256(
+    config: &Deploy,
+    archive: &BinaryArchive,
+) -> Result<Option<String>> {
+    if !config.verify_checksum {
+        return Ok(None);
+    }
+    Ok(Some(code_sha256(&archive.read()?)))
+}
+
+/// Check that the hash of the code that Lambda reports matches the hash of the local zip file.
+pub(crate) fn verify_code_sha256(expected: Option<&str>, reported: Option<&str>) -> Result<()> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+
+    match reported {
+        Some(reported) if reported == expected => Ok(()),
+        Some(reported) => Err(miette::miette!(
+            help = "the code might have been corrupted during the upload, deploy it again",
+            "the SHA256 of the deployed code doesn't match the zip file, expected {expected}, but Lambda reported {reported}"
+        )),
+        None => Err(miette::miette!(
+            "Lambda didn't report the SHA256 of the deployed code, the checksum cannot be verified"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SHA256: &str = "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=";
+
+    #[test]
+    fn test_code_sha256() {
+        assert_eq!(EMPTY_SHA256, code_sha256(&[]));
+    }
+
+    #[test]
+    fn test_verify_code_sha256() {
+        assert!(verify_code_sha256(None, None).is_ok());
+        assert!(verify_code_sha256(None, Some("abc")).is_ok());
+        assert!(verify_code_sha256(Some(EMPTY_SHA256), Some(EMPTY_SHA256)).is_ok());
+
+        let err = verify_code_sha256(Some(EMPTY_SHA256), Some("abc")).unwrap_err();
+        assert!(err.to_string().contains("but Lambda reported abc"));
+        assert!(verify_code_sha256(Some(EMPTY_SHA256), None).is_err());
+    }
+}
@@ -0,0 +1,919 @@
+use aws_sdk_sts::config::Region;
+use aws_smithy_xml::decode::{Document, ScopedDecoder, XmlDecodeError, try_data};
+use cargo_lambda_interactive::progress::Progress;
+use cargo_lambda_remote::aws_sdk_config::SdkConfig;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde::Serialize;
+use serde_json::{Value, json};
+use tokio::time::{Duration, Instant, sleep};
+use tracing::debug;
+
+use crate::query_api::{self, JsonRequest, XmlRequest};
+
+/// CloudFront only uses certificates from ACM in this region.
+const CERTIFICATE_REGION: &str = "us-east-1";
+
+/// Time to wait for ACM to validate the certificate with the DNS record.
+const CERTIFICATE_VALIDATION_TIMEOUT: Duration = Duration::from_secs(600);
+const CERTIFICATE_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Hosted zone of all the CloudFront distributions, for the alias records that point to them.
+const CLOUDFRONT_HOSTED_ZONE_ID: &str = "Z2FMTNKCKFK7TXZ";
+
+/// Managed cache policy `CachingDisabled`, the function handles every request.
+pub(crate) const CACHING_DISABLED_POLICY_ID: &str = "4135ea2d-6df8-44a3-9df3-4b5a84be39ad";
+
+/// Managed origin request policy `AllViewerExceptHostHeader`. Function URLs
+/// reject the requests that use a host header other than their own.
+pub(crate) const ALL_VIEWER_EXCEPT_HOST_HEADER_POLICY_ID: &str =
+    "b689b0a8-53d0-40ab-baf2-68738e2966ac";
+
+const CLOUDFRONT_API_VERSION: &str = "2020-05-31";
+const ROUTE53_API_VERSION: &str = "2013-04-01";
+
+/// Custom domain that serves the function URL through a CloudFront distribution.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct CustomDomainOutput {
+    domain: String,
+    certificate_arn: String,
+    distribution_id: String,
+    distribution_domain: String,
+    /// DNS records to create by hand, when the domain doesn't have a hosted zone in Route 53
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pending_records: Vec<DnsRecord>,
+}
+
+impl std::fmt::Display for CustomDomainOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "🌍 custom domain: https://{}", self.domain)?;
+        for record in &self.pending_records {
+            write!(f, "\n📝 create the DNS record `{record}` to use the domain")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct DnsRecord {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    value: String,
+}
+
+impl std::fmt::Display for DnsRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.kind, self.name, self.value)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct HostedZone {
+    id: String,
+    name: String,
+    private: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Distribution {
+    id: String,
+    domain_name: String,
+    aliases: Vec<String>,
+    origins: Vec<String>,
+}
+
+/// Serve the function URL from a custom domain. It requests a certificate for the domain,
+/// and creates a CloudFront distribution with the function URL as its origin. Certificates
+/// and distributions from previous deploys are reused. The DNS records to validate the
+/// certificate and to point the domain to the distribution are created when the domain has
+/// a public hosted zone in Route 53, otherwise they must be created by hand.
+pub(crate) async fn configure(
+    sdk_config: &SdkConfig,
+    name: &str,
+    domain: &str,
+    function_url: &str,
+    progress: &Progress,
+) -> Result<CustomDomainOutput> {
+    let acm_config = sdk_config
+        .to_builder()
+        .region(Region::new(CERTIFICATE_REGION))
+        .build();
+
+    progress.set_message("looking up the hosted zone of the custom domain");
+    let hosted_zone = find_hosted_zone(sdk_config, domain).await?;
+
+    progress.set_message("requesting certificate for the custom domain");
+    let certificate_arn = match find_certificate(&acm_config, domain).await? {
+        Some(arn) => arn,
+        None => request_certificate(&acm_config, domain).await?,
+    };
+
+    progress.set_message("validating certificate for the custom domain");
+    validate_certificate(
+        sdk_config,
+        &acm_config,
+        domain,
+        &certificate_arn,
+        hosted_zone.as_deref(),
+    )
+    .await?;
+
+    progress.set_message("creating cloudfront distribution");
+    let origin = origin_domain(function_url);
+    let distribution = match find_distribution(sdk_config, domain).await? {
+        Some(distribution) if distribution.origins.iter().any(|o| o == origin) => {
+            debug!(id = distribution.id, "the distribution already exists");
+            distribution
+        }
+        Some(distribution) => {
+            return Err(miette::miette!(
+                help = "remove the domain from the distribution, or deploy the function with a different domain",
+                "the domain `{domain}` is already used by the CloudFront distribution `{}`, which doesn't serve this function",
+                distribution.id
+            ));
+        }
+        None => create_distribution(sdk_config, name, domain, origin, &certificate_arn).await?,
+    };
+
+    let mut pending_records = Vec::new();
+    match &hosted_zone {
+        Some(zone_id) => {
+            progress.set_message("creating DNS records for the custom domain");
+            let body =
+                change_record_sets_body(&alias_record_changes(domain, &distribution.domain_name));
+            change_record_sets(sdk_config, zone_id, body).await?;
+        }
+        None => pending_records.push(DnsRecord {
+            name: domain.to_string(),
+            kind: "CNAME".into(),
+            value: distribution.domain_name.clone(),
+        }),
+    }
+
+    Ok(CustomDomainOutput {
+        domain: domain.to_string(),
+        certificate_arn,
+        distribution_id: distribution.id,
+        distribution_domain: distribution.domain_name,
+        pending_records,
+    })
+}
+
+/// Find the public hosted zone for the domain, or for any of its parent domains.
+async fn find_hosted_zone(sdk_config: &SdkConfig, domain: &str) -> Result<Option<String>> {
+    for zone_name in zone_candidates(domain) {
+        let path = format!(
+            "/{ROUTE53_API_VERSION}/hostedzonesbyname?dnsname={}&maxitems=10",
+            urlencoding::encode(zone_name)
+        );
+        let request = XmlRequest {
+            method: "GET",
+            path: &path,
+            body: None,
+        };
+        let body = send_xml(
+            sdk_config,
+            "route53",
+            request,
+            "failed to list the hosted zones",
+        )
+        .await?;
+
+        let zones = parse_hosted_zones(&body)
+            .into_diagnostic()
+            .wrap_err("failed to read the hosted zones")?;
+        let zone = zones
+            .into_iter()
+            .find(|zone| !zone.private && zone.name.trim_end_matches('.') == zone_name);
+        if let Some(zone) = zone {
+            debug!(zone_id = zone.id, zone_name, "found hosted zone");
+            return Ok(Some(zone.id));
+        }
+    }
+
+    debug!(domain, "the domain doesn't have a hosted zone in Route 53");
+    Ok(None)
+}
+
+/// The domain and its parent domains, without the top level domain.
+fn zone_candidates(domain: &str) -> Vec<&str> {
+    let mut candidates = vec![domain];
+    let mut rest = domain;
+    while let Some((_, parent)) = rest.split_once('.') {
+        if !parent.contains('.') {
+            break;
+        }
+        candidates.push(parent);
+        rest = parent;
+    }
+    candidates
+}
+
+async fn find_certificate(acm_config: &SdkConfig, domain: &str) -> Result<Option<String>> {
+    let mut next_token: Option<String> = None;
+    loop {
+        let mut body = json!({ "CertificateStatuses": ["ISSUED", "PENDING_VALIDATION"] });
+        if let Some(token) = &next_token {
+            body["NextToken"] = json!(token);
+        }
+        let request = JsonRequest {
+            method: "POST",
+            path: "/",
+            target: Some("CertificateManager.ListCertificates"),
+            body: Some(body),
+        };
+        let response = send_json(
+            acm_config,
+            "acm",
+            request,
+            "failed to list the certificates",
+        )
+        .await?;
+
+        let certificate = response
+            .get("CertificateSummaryList")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .find(|c| c.get("DomainName").and_then(Value::as_str) == Some(domain))
+            .and_then(|c| c.get("CertificateArn"))
+            .and_then(Value::as_str);
+        if let Some(arn) = certificate {
+            debug!(arn, "the certificate already exists");
+            return Ok(Some(arn.to_string()));
+        }
+
+        next_token = response
+            .get("NextToken")
+            .and_then(Value::as_str)
+            .map(String::from);
+        if next_token.is_none() {
+            return Ok(None);
+        }
+    }
+}
+
+async fn request_certificate(acm_config: &SdkConfig, domain: &str) -> Result<String> {
+    debug!(domain, "requesting certificate");
+    let request = JsonRequest {
+        method: "POST",
+        path: "/",
+        target: Some("CertificateManager.RequestCertificate"),
+        body: Some(request_certificate_body(domain)),
+    };
+    let response = send_json(
+        acm_config,
+        "acm",
+        request,
+        "failed to request the certificate",
+    )
+    .await?;
+    response
+        .get("CertificateArn")
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| miette::miette!("invalid response requesting the certificate"))
+}
+
+pub(crate) fn request_certificate_body(domain: &str) -> Value {
+    json!({
+        "DomainName": domain,
+        "ValidationMethod": "DNS",
+        "IdempotencyToken": idempotency_token(domain),
+    })
+}
+
+/// ACM only accepts up to 32 word characters in idempotency tokens.
+fn idempotency_token(domain: &str) -> String {
+    domain
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(32)
+        .collect()
+}
+
+/// Wait until ACM issues the certificate. The validation record is created in the hosted zone,
+/// without a hosted zone the deploy fails with the record to create by hand.
+async fn validate_certificate(
+    sdk_config: &SdkConfig,
+    acm_config: &SdkConfig,
+    domain: &str,
+    certificate_arn: &str,
+    hosted_zone: Option<&str>,
+) -> Result<()> {
+    let started_at = Instant::now();
+    let mut record_created = false;
+
+    loop {
+        let request = JsonRequest {
+            method: "POST",
+            path: "/",
+            target: Some("CertificateManager.DescribeCertificate"),
+            body: Some(json!({ "CertificateArn": certificate_arn })),
+        };
+        let response = send_json(
+            acm_config,
+            "acm",
+            request,
+            "failed to describe the certificate",
+        )
+        .await?;
+
+        let certificate = response.get("Certificate").unwrap_or(&Value::Null);
+        match certificate.get("Status").and_then(Value::as_str) {
+            Some("ISSUED") => return Ok(()),
+            Some("PENDING_VALIDATION") | None => {}
+            Some(status) => {
+                return Err(miette::miette!(
+                    help = "delete the certificate in ACM, and deploy again to request a new one",
+                    "the certificate for `{domain}` can't be used, its status is {status}"
+                ));
+            }
+        }
+
+        match (validation_record(certificate), hosted_zone) {
+            (Some(record), Some(zone_id)) if !record_created => {
+                debug!(record = %record, "creating the certificate validation record");
+                let body = change_record_sets_body(&[record_change(&record)]);
+                change_record_sets(sdk_config, zone_id, body).await?;
+                record_created = true;
+            }
+            (Some(record), None) => {
+                return Err(miette::miette!(
+                    help = format!(
+                        "create the DNS record `{record}`, and deploy again when ACM issues the certificate"
+                    ),
+                    "the certificate for `{domain}` is waiting for the DNS validation"
+                ));
+            }
+            _ => {}
+        }
+
+        if started_at.elapsed() > CERTIFICATE_VALIDATION_TIMEOUT {
+            return Err(miette::miette!(
+                help = "check the validation record of the certificate in ACM, and deploy again when ACM issues it",
+                "timed out waiting for ACM to issue the certificate for `{domain}`"
+            ));
+        }
+        sleep(CERTIFICATE_POLL_INTERVAL).await;
+    }
+}
+
+/// The DNS record that ACM checks to validate the domain. ACM adds it to the
+/// certificate a few seconds after the certificate is requested.
+fn validation_record(certificate: &Value) -> Option<DnsRecord> {
+    let record = certificate
+        .get("DomainValidationOptions")?
+        .as_array()?
+        .first()?
+        .get("ResourceRecord")?;
+    let field = |name: &str| record.get(name).and_then(Value::as_str).map(String::from);
+    Some(DnsRecord {
+        name: field("Name")?,
+        kind: field("Type")?,
+        value: field("Value")?,
+    })
+}
+
+/// Find the distribution that already serves the domain.
+async fn find_distribution(sdk_config: &SdkConfig, domain: &str) -> Result<Option<Distribution>> {
+    let mut marker: Option<String> = None;
+    loop {
+        let path = match &marker {
+            Some(marker) => format!(
+                "/{CLOUDFRONT_API_VERSION}/distribution?Marker={}",
+                urlencoding::encode(marker)
+            ),
+            None => format!("/{CLOUDFRONT_API_VERSION}/distribution"),
+        };
+        let request = XmlRequest {
+            method: "GET",
+            path: &path,
+            body: None,
+        };
+        let body = send_xml(
+            sdk_config,
+            "cloudfront",
+            request,
+            "failed to list the cloudfront distributions",
+        )
+        .await?;
+
+        let (distributions, next_marker) = parse_distribution_list(&body)
+            .into_diagnostic()
+            .wrap_err("failed to read the cloudfront distributions")?;
+        let distribution = distributions
+            .into_iter()
+            .find(|d| d.aliases.iter().any(|a| a.eq_ignore_ascii_case(domain)));
+        if distribution.is_some() {
+            return Ok(distribution);
+        }
+
+        marker = next_marker;
+        if marker.is_none() {
+            return Ok(None);
+        }
+    }
+}
+
+async fn create_distribution(
+    sdk_config: &SdkConfig,
+    name: &str,
+    domain: &str,
+    origin: &str,
+    certificate_arn: &str,
+) -> Result<Distribution> {
+    debug!(domain, origin, "creating cloudfront distribution");
+    let path = format!("/{CLOUDFRONT_API_VERSION}/distribution");
+    let request = XmlRequest {
+        method: "POST",
+        path: &path,
+        body: Some(distribution_config_body(
+            name,
+            domain,
+            origin,
+            certificate_arn,
+        )),
+    };
+    let body = send_xml(
+        sdk_config,
+        "cloudfront",
+        request,
+        "failed to create the cloudfront distribution",
+    )
+    .await?;
+
+    parse_distribution(&body)
+        .into_diagnostic()
+        .wrap_err("failed to read the cloudfront distribution")?
+        .ok_or_else(|| miette::miette!("invalid response creating the cloudfront distribution"))
+}
+
+/// Domain of the function URL, like `abc123.lambda-url.us-east-1.on.aws`.
+pub(crate) fn origin_domain(function_url: &str) -> &str {
+    function_url
+        .trim_start_matches("https://")
+        .trim_end_matches('/')
+}
+
+/// Caller reference of the distribution. CloudFront returns the existing distribution
+/// when a request with the same reference and the same configuration is retried.
+pub(crate) fn caller_reference(domain: &str) -> String {
+    format!("cargo-lambda-{domain}")
+}
+
+/// Configuration of a distribution that forwards all the requests to the function URL, without caching them.
+fn distribution_config_body(
+    name: &str,
+    domain: &str,
+    origin: &str,
+    certificate_arn: &str,
+) -> String {
+    let caller_reference = caller_reference(domain);
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<DistributionConfig xmlns="http://cloudfront.amazonaws.com/doc/{CLOUDFRONT_API_VERSION}/">
+  <CallerReference>{caller_reference}</CallerReference>
+  <Aliases><Quantity>1</Quantity><Items><CNAME>{domain}</CNAME></Items></Aliases>
+  <Origins>
+    <Quantity>1</Quantity>
+    <Items>
+      <Origin>
+        <Id>{origin}</Id>
+        <DomainName>{origin}</DomainName>
+        <CustomOriginConfig>
+          <HTTPPort>80</HTTPPort>
+          <HTTPSPort>443</HTTPSPort>
+          <OriginProtocolPolicy>https-only</OriginProtocolPolicy>
+          <OriginSslProtocols><Quantity>1</Quantity><Items><SslProtocol>TLSv1.2</SslProtocol></Items></OriginSslProtocols>
+        </CustomOriginConfig>
+      </Origin>
+    </Items>
+  </Origins>
+  <DefaultCacheBehavior>
+    <TargetOriginId>{origin}</TargetOriginId>
+    <ViewerProtocolPolicy>redirect-to-https</ViewerProtocolPolicy>
+    <AllowedMethods>
+      <Quantity>7</Quantity>
+      <Items><Method>GET</Method><Method>HEAD</Method><Method>OPTIONS</Method><Method>PUT</Method><Method>POST</Method><Method>PATCH</Method><Method>DELETE</Method></Items>
+      <CachedMethods><Quantity>2</Quantity><Items><Method>GET</Method><Method>HEAD</Method></Items></CachedMethods>
+    </AllowedMethods>
+    <Compress>true</Compress>
+    <CachePolicyId>{CACHING_DISABLED_POLICY_ID}</CachePolicyId>
+    <OriginRequestPolicyId>{ALL_VIEWER_EXCEPT_HOST_HEADER_POLICY_ID}</OriginRequestPolicyId>
+  </DefaultCacheBehavior>
+  <Comment>Custom domain for the {name} function</Comment>
+  <Enabled>true</Enabled>
+  <ViewerCertificate>
+    <ACMCertificateArn>{certificate_arn}</ACMCertificateArn>
+    <SSLSupportMethod>sni-only</SSLSupportMethod>
+    <MinimumProtocolVersion>TLSv1.2_2021</MinimumProtocolVersion>
+  </ViewerCertificate>
+  <HttpVersion>http2and3</HttpVersion>
+  <IsIPV6Enabled>true</IsIPV6Enabled>
+</DistributionConfig>"#
+    )
+}
+
+/// Change to a Route 53 record set, in the XML format of ChangeResourceRecordSets.
+fn record_change(record: &DnsRecord) -> String {
+    format!(
+        "<Change><Action>UPSERT</Action><ResourceRecordSet><Name>{}</Name><Type>{}</Type><TTL>300</TTL>\
+        <ResourceRecords><ResourceRecord><Value>{}</Value></ResourceRecord></ResourceRecords></ResourceRecordSet></Change>",
+        record.name, record.kind, record.value
+    )
+}
+
+/// IPv4 and IPv6 alias records that point the domain to the distribution.
+fn alias_record_changes(domain: &str, distribution_domain: &str) -> Vec<String> {
+    ["A", "AAAA"]
+        .iter()
+        .map(|kind| {
+            format!(
+                "<Change><Action>UPSERT</Action><ResourceRecordSet><Name>{domain}</Name><Type>{kind}</Type>\
+                <AliasTarget><HostedZoneId>{CLOUDFRONT_HOSTED_ZONE_ID}</HostedZoneId><DNSName>{distribution_domain}</DNSName>\
+                <EvaluateTargetHealth>false</EvaluateTargetHealth></AliasTarget></ResourceRecordSet></Change>"
+            )
+        })
+        .collect()
+}
+
+fn change_record_sets_body(changes: &[String]) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ChangeResourceRecordSetsRequest xmlns="https://route53.amazonaws.com/doc/{ROUTE53_API_VERSION}/"><ChangeBatch><Changes>{}</Changes></ChangeBatch></ChangeResourceRecordSetsRequest>"#,
+        changes.join("")
+    )
+}
+
+async fn change_record_sets(sdk_config: &SdkConfig, zone_id: &str, body: String) -> Result<()> {
+    let path = format!("/{ROUTE53_API_VERSION}/hostedzone/{zone_id}/rrset");
+    let request = XmlRequest {
+        method: "POST",
+        path: &path,
+        body: Some(body),
+    };
+    send_xml(
+        sdk_config,
+        "route53",
+        request,
+        "failed to create the DNS records",
+    )
+    .await?;
+    Ok(())
+}
+
+async fn send_json(
+    sdk_config: &SdkConfig,
+    service: &str,
+    request: JsonRequest<'_>,
+    context: &'static str,
+) -> Result<Value> {
+    query_api::send_json(sdk_config, service, request)
+        .await
+        .wrap_err(context)?
+        .map_err(|message| miette::miette!("{context}: {message}"))
+}
+
+async fn send_xml(
+    sdk_config: &SdkConfig,
+    service: &str,
+    request: XmlRequest<'_>,
+    context: &'static str,
+) -> Result<String> {
+    query_api::send_global_xml(sdk_config, service, request)
+        .await
+        .wrap_err(context)?
+        .map_err(|message| miette::miette!("{context}: {message}"))
+}
+
+/// Parse the hosted zones from a ListHostedZonesByName response:
+/// ListHostedZonesByNameResponse > HostedZones > HostedZone.
+fn parse_hosted_zones(body: &str) -> Result<Vec<HostedZone>, XmlDecodeError> {
+    let mut zones = Vec::new();
+
+    let mut doc = Document::new(body);
+    let mut root = doc.root_element()?;
+    while let Some(mut hosted_zones) = root.next_tag() {
+        if !hosted_zones.start_el().matches("HostedZones") {
+            continue;
+        }
+        while let Some(mut zone) = hosted_zones.next_tag() {
+            let mut id = None;
+            let mut name = None;
+            let mut private = false;
+            while let Some(mut tag) = zone.next_tag() {
+                if tag.start_el().matches("Id") {
+                    let value = try_data(&mut tag)?;
+                    id = Some(value.trim_start_matches("/hostedzone/").to_string());
+                } else if tag.start_el().matches("Name") {
+                    name = Some(try_data(&mut tag)?.to_string());
+                } else if tag.start_el().matches("Config") {
+                    while let Some(mut config) = tag.next_tag() {
+                        if config.start_el().matches("PrivateZone") {
+                            private = try_data(&mut config)? == "true";
+                        }
+                    }
+                }
+            }
+
+            if let (Some(id), Some(name)) = (id, name) {
+                zones.push(HostedZone { id, name, private });
+            }
+        }
+    }
+
+    Ok(zones)
+}
+
+/// Parse the distributions from a ListDistributions response, and the marker
+/// of the next page: DistributionList > Items > DistributionSummary.
+fn parse_distribution_list(
+    body: &str,
+) -> Result<(Vec<Distribution>, Option<String>), XmlDecodeError> {
+    let mut distributions = Vec::new();
+    let mut next_marker = None;
+    let mut truncated = false;
+
+    let mut doc = Document::new(body);
+    let mut root = doc.root_element()?;
+    while let Some(mut tag) = root.next_tag() {
+        if tag.start_el().matches("IsTruncated") {
+            truncated = try_data(&mut tag)? == "true";
+        } else if tag.start_el().matches("NextMarker") {
+            next_marker = Some(try_data(&mut tag)?.to_string());
+        } else if tag.start_el().matches("Items") {
+            while let Some(mut summary) = tag.next_tag() {
+                distributions.push(parse_distribution_fields(&mut summary)?);
+            }
+        }
+    }
+
+    Ok((distributions, next_marker.filter(|_| truncated)))
+}
+
+/// Parse the distribution from a CreateDistribution response.
+fn parse_distribution(body: &str) -> Result<Option<Distribution>, XmlDecodeError> {
+    let mut doc = Document::new(body);
+    let mut root = doc.root_element()?;
+    let distribution = parse_distribution_fields(&mut root)?;
+    if distribution.id.is_empty() || distribution.domain_name.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(distribution))
+}
+
+fn parse_distribution_fields(decoder: &mut ScopedDecoder) -> Result<Distribution, XmlDecodeError> {
+    let mut distribution = Distribution::default();
+    while let Some(mut tag) = decoder.next_tag() {
+        if tag.start_el().matches("Id") {
+            distribution.id = try_data(&mut tag)?.to_string();
+        } else if tag.start_el().matches("DomainName") {
+            distribution.domain_name = try_data(&mut tag)?.to_string();
+        } else if tag.start_el().matches("Aliases") {
+            distribution.aliases = parse_items(&mut tag, "CNAME")?;
+        } else if tag.start_el().matches("Origins") {
+            distribution.origins = parse_origins(&mut tag)?;
+        } else if tag.start_el().matches("DistributionConfig") {
+            let config = parse_distribution_fields(&mut tag)?;
+            distribution.aliases = config.aliases;
+            distribution.origins = config.origins;
+        }
+    }
+    Ok(distribution)
+}
+
+/// Values of a list with the format `<Quantity/><Items><Element/>...</Items>`.
+fn parse_items(decoder: &mut ScopedDecoder, element: &str) -> Result<Vec<String>, XmlDecodeError> {
+    let mut values = Vec::new();
+    while let Some(mut items) = decoder.next_tag() {
+        if !items.start_el().matches("Items") {
+            continue;
+        }
+        while let Some(mut item) = items.next_tag() {
+            if item.start_el().matches(element) {
+                values.push(try_data(&mut item)?.to_string());
+            }
+        }
+    }
+    Ok(values)
+}
+
+fn parse_origins(decoder: &mut ScopedDecoder) -> Result<Vec<String>, XmlDecodeError> {
+    let mut origins = Vec::new();
+    while let Some(mut items) = decoder.next_tag() {
+        if !items.start_el().matches("Items") {
+            continue;
+        }
+        while let Some(mut origin) = items.next_tag() {
+            while let Some(mut tag) = origin.next_tag() {
+                if tag.start_el().matches("DomainName") {
+                    origins.push(try_data(&mut tag)?.to_string());
+                }
+            }
+        }
+    }
+    Ok(origins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_candidates() {
+        assert_eq!(
+            vec!["api.eu.example.com", "eu.example.com", "example.com"],
+            zone_candidates("api.eu.example.com")
+        );
+        assert_eq!(vec!["example.com"], zone_candidates("example.com"));
+    }
+
+    #[test]
+    fn test_idempotency_token() {
+        assert_eq!("apiexamplecom", idempotency_token("api.example.com"));
+        assert_eq!(
+            32,
+            idempotency_token("orders-api.production.eu-west-1.example.com").len()
+        );
+    }
+
+    #[test]
+    fn test_origin_domain() {
+        assert_eq!(
+            "abc123.lambda-url.us-east-1.on.aws",
+            origin_domain("https://abc123.lambda-url.us-east-1.on.aws/")
+        );
+    }
+
+    #[test]
+    fn test_validation_record() {
+        let certificate = json!({
+            "Status": "PENDING_VALIDATION",
+            "DomainValidationOptions": [{
+                "DomainName": "api.example.com",
+                "ResourceRecord": {
+                    "Name": "_x1.api.example.com.",
+                    "Type": "CNAME",
+                    "Value": "_x2.acm-validations.aws."
+                }
+            }]
+        });
+        let record = validation_record(&certificate).unwrap();
+        assert_eq!(
+            "CNAME _x1.api.example.com. _x2.acm-validations.aws.",
+            record.to_string()
+        );
+
+        let certificate =
+            json!({ "DomainValidationOptions": [{ "DomainName": "api.example.com" }] });
+        assert_eq!(None, validation_record(&certificate));
+    }
+
+    #[test]
+    fn test_parse_hosted_zones() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListHostedZonesByNameResponse xmlns="https://route53.amazonaws.com/doc/2013-04-01/">
+  <HostedZones>
+    <HostedZone>
+      <Id>/hostedzone/Z1D633PJN98FT9</Id>
+      <Name>example.com.</Name>
+      <CallerReference>2014-10-01T11:22:14Z</CallerReference>
+      <Config><PrivateZone>false</PrivateZone></Config>
+      <ResourceRecordSetCount>4</ResourceRecordSetCount>
+    </HostedZone>
+    <HostedZone>
+      <Id>/hostedzone/Z2PRIVATE</Id>
+      <Name>example.com.</Name>
+      <Config><Comment>internal</Comment><PrivateZone>true</PrivateZone></Config>
+    </HostedZone>
+  </HostedZones>
+  <DNSName>example.com</DNSName>
+  <IsTruncated>true</IsTruncated>
+  <MaxItems>1</MaxItems>
+</ListHostedZonesByNameResponse>"#;
+        assert_eq!(
+            vec![
+                HostedZone {
+                    id: "Z1D633PJN98FT9".into(),
+                    name: "example.com.".into(),
+                    private: false,
+                },
+                HostedZone {
+                    id: "Z2PRIVATE".into(),
+                    name: "example.com.".into(),
+                    private: true,
+                },
+            ],
+            parse_hosted_zones(body).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_distribution_list() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<DistributionList xmlns="http://cloudfront.amazonaws.com/doc/2020-05-31/">
+  <Marker></Marker>
+  <NextMarker>E2NEXT</NextMarker>
+  <MaxItems>100</MaxItems>
+  <IsTruncated>true</IsTruncated>
+  <Quantity>1</Quantity>
+  <Items>
+    <DistributionSummary>
+      <Id>E1ORDERS</Id>
+      <ARN>arn:aws:cloudfront::123456789012:distribution/E1ORDERS</ARN>
+      <Status>Deployed</Status>
+      <DomainName>d111111abcdef8.cloudfront.net</DomainName>
+      <Aliases><Quantity>1</Quantity><Items><CNAME>api.example.com</CNAME></Items></Aliases>
+      <Origins>
+        <Quantity>1</Quantity>
+        <Items>
+          <Origin>
+            <Id>abc123.lambda-url.us-east-1.on.aws</Id>
+            <DomainName>abc123.lambda-url.us-east-1.on.aws</DomainName>
+          </Origin>
+        </Items>
+      </Origins>
+    </DistributionSummary>
+  </Items>
+</DistributionList>"#;
+        let (distributions, next_marker) = parse_distribution_list(body).unwrap();
+        assert_eq!(Some("E2NEXT".to_string()), next_marker);
+        assert_eq!(
+            vec![Distribution {
+                id: "E1ORDERS".into(),
+                domain_name: "d111111abcdef8.cloudfront.net".into(),
+                aliases: vec!["api.example.com".into()],
+                origins: vec!["abc123.lambda-url.us-east-1.on.aws".into()],
+            }],
+            distributions
+        );
+
+        let body = r#"<DistributionList><IsTruncated>false</IsTruncated><Quantity>0</Quantity></DistributionList>"#;
+        assert_eq!((Vec::new(), None), parse_distribution_list(body).unwrap());
+    }
+
+    #[test]
+    fn test_parse_distribution() {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<Distribution xmlns="http://cloudfront.amazonaws.com/doc/2020-05-31/">
+  <Id>E1ORDERS</Id>
+  <Status>InProgress</Status>
+  <DomainName>d111111abcdef8.cloudfront.net</DomainName>
+  {}
+</Distribution>"#,
+            distribution_config_body(
+                "orders",
+                "api.example.com",
+                "abc123.lambda-url.us-east-1.on.aws",
+                "arn:aws:acm:us-east-1:123456789012:certificate/1234"
+            )
+            .trim_start_matches(r#"<?xml version="1.0" encoding="UTF-8"?>"#)
+        );
+        assert_eq!(
+            Some(Distribution {
+                id: "E1ORDERS".into(),
+                domain_name: "d111111abcdef8.cloudfront.net".into(),
+                aliases: vec!["api.example.com".into()],
+                origins: vec!["abc123.lambda-url.us-east-1.on.aws".into()],
+            }),
+            parse_distribution(&body).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_change_record_sets_body() {
+        let body = change_record_sets_body(&alias_record_changes(
+            "api.example.com",
+            "d111111abcdef8.cloudfront.net",
+        ));
+        assert!(body.contains("<Type>A</Type>"), "{body}");
+        assert!(body.contains("<Type>AAAA</Type>"), "{body}");
+        assert!(
+            body.contains(&format!(
+                "<HostedZoneId>{CLOUDFRONT_HOSTED_ZONE_ID}</HostedZoneId>"
+            )),
+            "{body}"
+        );
+    }
+
+    #[test]
+    fn test_custom_domain_output() {
+        let output = CustomDomainOutput {
+            domain: "api.example.com".into(),
+            certificate_arn: "arn:aws:acm:us-east-1:123456789012:certificate/1234".into(),
+            distribution_id: "E1ORDERS".into(),
+            distribution_domain: "d111111abcdef8.cloudfront.net".into(),
+            pending_records: vec![DnsRecord {
+                name: "api.example.com".into(),
+                kind: "CNAME".into(),
+                value: "d111111abcdef8.cloudfront.net".into(),
+            }],
+        };
+        assert_eq!(
+            "🌍 custom domain: https://api.example.com\n📝 create the DNS record `CNAME api.example.com d111111abcdef8.cloudfront.net` to use the domain",
+            output.to_string()
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use aws_sdk_s3::{Client as S3Client, primitives::ByteStream};
+use cargo_lambda_build::debug_artifact_path;
+use cargo_lambda_interactive::progress::Progress;
+use cargo_lambda_metadata::cargo::deploy::Deploy;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::binary_name_or_default;
+
+/// Find the file with the debug information of the binary to deploy,
+/// when the deploy is configured with `--split-debuginfo-out`.
+pub(crate) fn find_debug_artifact(config: &Deploy, name: &str) -> Result<Option<PathBuf>> {
+    let Some(dir) = &config.split_debuginfo_out else {
+        return Ok(None);
+    };
+
+    let binary_name = binary_name_or_default(config, name);
+    let path = debug_artifact_path(dir, &binary_name);
+    if !path.is_file() {
+        return Err(miette::miette!(
+            help = format!(
+                "build the function with `cargo lambda build --split-debuginfo-out {}`",
+                dir.display()
+            ),
+            "the debug information for `{binary_name}` is missing, expected it at {path:?}"
+        ));
+    }
+
+    Ok(Some(path))
+}
+
+/// Keep the debug information next to the function's code. It's uploaded
+/// to S3 when the code is stored there, otherwise it stays in the local path.
+/// It returns the location of the debug information.
+pub(crate) async fn store_debug_artifact(
+    config: &Deploy,
+    name: &str,
+    s3_client: &S3Client,
+    path: &Path,
+    progress: &Progress,
+) -> Result<String> {
+    let Some(bucket) = &config.s3_bucket else {
+        return Ok(path.display().to_string());
+    };
+
+    let key = debug_artifact_key(config, name);
+    debug!(bucket, key, ?path, "uploading debug info to S3");
+    progress.set_message("uploading debug info to S3");
+
+    let body = ByteStream::from_path(path)
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read debug info file `{path:?}`"))?;
+
+    s3_client
+        .put_object()
+        .bucket(bucket)
+        .key(&key)
+        .body(body)
+        .set_tagging(config.s3_tags())
+        .send()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to upload debug info to S3")?;
+
+    Ok(format!("s3://{bucket}/{key}"))
+}
+
+/// S3 key of the debug information, next to the key of the function's code.
+pub(crate) fn debug_artifact_key(config: &Deploy, name: &str) -> String {
+    let key = config.s3_key.as_deref().unwrap_or(name);
+    format!("{key}.debug")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_credential_types::Credentials;
+    use aws_sdk_s3::config::{Config as S3Config, Region};
+    use aws_smithy_runtime::client::http::test_util::{ReplayEvent, StaticReplayClient};
+    use aws_smithy_types::body::SdkBody;
+    use http::{Request, Response};
+
+    #[test]
+    fn test_find_debug_artifact() {
+        let config = Deploy::default();
+        assert_eq!(None, find_debug_artifact(&config, "basic-lambda").unwrap());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut config = Deploy::default();
+        config.split_debuginfo_out = Some(tmp.path().to_path_buf());
+        let err = find_debug_artifact(&config, "basic-lambda").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("debug information for `basic-lambda` is missing")
+        );
+
+        let path = tmp.path().join("basic-lambda.debug");
+        std::fs::write(&path, "debug").unwrap();
+        assert_eq!(
+            Some(path),
+            find_debug_artifact(&config, "basic-lambda").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_debug_artifact_key() {
+        let mut config = Deploy::default();
+        assert_eq!(
+            "basic-lambda.debug",
+            debug_artifact_key(&config, "basic-lambda")
+        );
+
+        config.s3_key = Some("functions/basic-lambda-1234.zip".into());
+        assert_eq!(
+            "functions/basic-lambda-1234.zip.debug",
+            debug_artifact_key(&config, "basic-lambda")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_debug_artifact() {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), "debug").unwrap();
+
+        let request = Request::builder()
+            .uri("https://test-bucket.s3.us-east-1.amazonaws.com/basic-lambda.debug?x-id=PutObject")
+            .method("PUT")
+            .body(SdkBody::empty())
+            .unwrap();
+        let response = Response::builder()
+            .status(200)
+            .body(SdkBody::empty())
+            .unwrap();
+        let http_client = StaticReplayClient::new(vec![ReplayEvent::new(request, response)]);
+        let s3_config = S3Config::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let s3_client = S3Client::from_conf(s3_config);
+
+        let progress = Progress::start("deploying function");
+        let config = Deploy::default();
+        let location =
+            store_debug_artifact(&config, "basic-lambda", &s3_client, tmp.path(), &progress)
+                .await
+                .unwrap();
+        assert_eq!(tmp.path().display().to_string(), location);
+
+        let mut config = Deploy::default();
+        config.s3_bucket = Some("test-bucket".into());
+        let location =
+            store_debug_artifact(&config, "basic-lambda", &s3_client, tmp.path(), &progress)
+                .await
+                .unwrap();
+        progress.finish_and_clear();
+
+        assert_eq!("s3://test-bucket/basic-lambda.debug", location);
+        http_client.assert_requests_match(&[]);
+    }
+}
@@ -1,12 +1,18 @@
 use cargo_lambda_build::{BinaryArchive, BinaryModifiedAt};
 use cargo_lambda_metadata::cargo::deploy::{Deploy, FunctionDeployConfig};
 use miette::Result;
-use serde::Serialize;
-use std::{collections::HashMap, fmt::Display, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
-use crate::binary_name_or_default;
+use crate::{
+    api_calls::{self, ApiCall},
+    binary_name_or_default,
+};
 
-#[derive(PartialEq, Serialize)]
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub(crate) enum DeployKind {
     Function,
@@ -33,7 +39,117 @@ pub(crate) struct DeployOutput {
     tags: Option<String>,
     bucket: Option<String>,
     config: FunctionDeployConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function_config: Option<FunctionConfigOutput>,
     binary_modified_at: BinaryModifiedAt,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debug_artifact: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_calls: Option<Vec<ApiCall>>,
+}
+
+/// Effective function configuration that the deploy sends to AWS,
+/// after applying the default values for the options that were not set.
+#[derive(Serialize)]
+pub(crate) struct FunctionConfigOutput {
+    runtime: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    handler: Option<String>,
+    memory: Option<i32>,
+    timeout: i32,
+    tracing: String,
+    role: Option<String>,
+    layers: Vec<String>,
+    env_vars: Vec<String>,
+    log_retention: Option<i32>,
+    allow_invoke_from: Vec<String>,
+    triggers: Vec<String>,
+    enable_function_url: bool,
+    disable_function_url: bool,
+}
+
+impl FunctionConfigOutput {
+    fn new(config: &Deploy) -> Result<Self> {
+        let function_config = &config.function_config;
+
+        let mut env_vars = config
+            .lambda_environment()?
+            .and_then(|env| env.variables)
+            .map(|vars| vars.into_keys().collect::<Vec<_>>())
+            .unwrap_or_default();
+        env_vars.sort();
+
+        Ok(FunctionConfigOutput {
+            runtime: function_config.runtime(),
+            handler: function_config.handler.clone(),
+            memory: function_config.memory.as_ref().map(Into::into),
+            timeout: function_config.timeout.clone().unwrap_or_default().into(),
+            tracing: function_config
+                .tracing
+                .clone()
+                .unwrap_or_default()
+                .to_string(),
+            role: function_config.role.clone(),
+            layers: function_config.layer.clone().unwrap_or_default(),
+            env_vars,
+            log_retention: function_config.log_retention,
+            allow_invoke_from: config.allow_invoke_from.clone().unwrap_or_default(),
+            triggers: config.trigger.clone().unwrap_or_default(),
+            enable_function_url: function_config.enable_function_url,
+            disable_function_url: function_config.disable_function_url,
+        })
+    }
+}
+
+impl Display for FunctionConfigOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "🍿 function configuration:")?;
+        writeln!(f, "  - runtime: {}", self.runtime)?;
+        if let Some(handler) = &self.handler {
+            writeln!(f, "  - handler: {handler}")?;
+        }
+        match self.memory {
+            Some(memory) => writeln!(f, "  - memory: {memory} MB")?,
+            None => writeln!(f, "  - memory: AWS default")?,
+        }
+        writeln!(f, "  - timeout: {} seconds", self.timeout)?;
+        writeln!(f, "  - tracing: {}", self.tracing)?;
+        match &self.role {
+            Some(role) => writeln!(f, "  - role: {role}")?,
+            None => writeln!(f, "  - role: new role created by Cargo Lambda")?,
+        }
+        if self.layers.is_empty() {
+            writeln!(f, "  - layers: none")?;
+        } else {
+            writeln!(f, "  - layers:")?;
+            for layer in &self.layers {
+                writeln!(f, "    - {layer}")?;
+            }
+        }
+        if self.env_vars.is_empty() {
+            writeln!(f, "  - environment variables: none")?;
+        } else {
+            writeln!(f, "  - environment variables: {}", self.env_vars.join(", "))?;
+        }
+        if let Some(days) = self.log_retention {
+            writeln!(f, "  - log retention: {days} days")?;
+        }
+        if !self.allow_invoke_from.is_empty() {
+            writeln!(f, "  - invokable by:")?;
+            for principal in &self.allow_invoke_from {
+                writeln!(f, "    - {principal}")?;
+            }
+        }
+        if !self.triggers.is_empty() {
+            writeln!(f, "  - triggers:")?;
+            for trigger in &self.triggers {
+                writeln!(f, "    - {trigger}")?;
+            }
+        }
+        writeln!(f, "  - enable_function_url: {}", self.enable_function_url)?;
+        writeln!(f, "  - disable_function_url: {}", self.disable_function_url)?;
+        Ok(())
+    }
 }
 
 impl Display for DeployOutput {
@@ -45,7 +161,7 @@ impl Display for DeployOutput {
             "🛠️  binary last compiled {}",
             self.binary_modified_at.humanize()
         )?;
-        writeln!(f, "🔗 architecture {}", self.arch)?;
+        writeln!(f, "🔗 architecture {}", self.arch.replace(',', ", "))?;
 
         if let Some(tags) = &self.tags {
             writeln!(f, "🏷️  tagged with {}", tags.replace(',', ", "))?;
@@ -55,6 +171,10 @@ impl Display for DeployOutput {
             writeln!(f, "🪣 stored on S3 bucket `{}`", bucket)?;
         }
 
+        if let Some(debug_artifact) = &self.debug_artifact {
+            writeln!(f, "🐞 debug info located at {}", debug_artifact.display())?;
+        }
+
         if !self.runtimes.is_empty() {
             write!(f, "👟 compatible with {}", self.runtimes.join(", "))?;
         }
@@ -66,31 +186,15 @@ impl Display for DeployOutput {
             }
         }
 
-        if self.kind == DeployKind::Function {
-            writeln!(f, "🍿 function configuration:")?;
-            writeln!(f, "  - timeout: {:?}", self.config.timeout)?;
-            writeln!(f, "  - memory: {:?}", self.config.memory)?;
-            writeln!(
-                f,
-                "  - enable_function_url: {}",
-                self.config.enable_function_url
-            )?;
-            writeln!(
-                f,
-                "  - disable_function_url: {}",
-                self.config.disable_function_url
-            )?;
-            writeln!(f, "  - tracing: {:?}", self.config.tracing)?;
-            writeln!(f, "  - role: {:?}", self.config.role)?;
-            writeln!(f, "  - layer: {:?}", self.config.layer)?;
+        if let Some(function_config) = &self.function_config {
+            function_config.fmt(f)?;
             writeln!(f, "  - vpc: {:?}", self.config.vpc)?;
-            writeln!(f, "  - runtime: {:?}", self.config.runtime())?;
-            if let Some(env_options) = &self.config.env_options {
-                let env = env_options
-                    .lambda_environment(&HashMap::new())
-                    .unwrap_or_default();
-                writeln!(f, "  - env_options: {:?}", env)?;
-            }
+        }
+
+        if let Some(api_calls) = &self.api_calls {
+            let json = serde_json::to_string_pretty(api_calls).map_err(|_| std::fmt::Error)?;
+            writeln!(f, "📜 AWS API calls:")?;
+            writeln!(f, "{json}")?;
         }
 
         Ok(())
@@ -98,16 +202,34 @@ impl Display for DeployOutput {
 }
 
 impl DeployOutput {
-    pub(crate) fn new(config: &Deploy, name: &str, archive: &BinaryArchive) -> Result<Self> {
-        let (kind, name, runtimes) = if config.extension {
+    pub(crate) fn new(
+        config: &Deploy,
+        name: &str,
+        archive: &BinaryArchive,
+        debug_artifact: Option<&Path>,
+    ) -> Result<Self> {
+        let api_calls = if config.show_api_calls {
+            Some(api_calls::plan(config, name, archive, debug_artifact)?)
+        } else {
+            None
+        };
+
+        let (kind, name, runtimes, function_config) = if config.extension {
             (
                 DeployKind::Extension,
                 name.to_owned(),
                 config.compatible_runtimes(),
+                None,
             )
         } else {
             let binary_name = binary_name_or_default(config, name);
-            (DeployKind::Function, binary_name, vec![])
+            let function_config = FunctionConfigOutput::new(config)?;
+            (
+                DeployKind::Function,
+                binary_name,
+                vec![],
+                Some(function_config),
+            )
         };
 
         Ok(DeployOutput {
@@ -119,8 +241,60 @@ impl DeployOutput {
             bucket: config.s3_bucket.clone(),
             tags: config.s3_tags(),
             config: config.function_config.clone(),
+            function_config,
             files: archive.list()?,
             binary_modified_at: archive.binary_modified_at.clone(),
+            debug_artifact: debug_artifact.map(Path::to_path_buf),
+            api_calls,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cargo_lambda_metadata::env::EnvOptions;
+
+    use super::*;
+
+    #[test]
+    fn test_function_config_output_defaults() {
+        let config = Deploy::default();
+        let output = FunctionConfigOutput::new(&config).unwrap();
+
+        assert_eq!(output.runtime, "provided.al2023");
+        assert_eq!(output.handler, None);
+        assert_eq!(output.memory, None);
+        assert_eq!(output.timeout, 30);
+        assert_eq!(output.tracing, "PassThrough");
+        assert_eq!(output.role, None);
+        assert!(output.layers.is_empty());
+        assert!(output.env_vars.is_empty());
+        assert!(output.allow_invoke_from.is_empty());
+        assert!(output.triggers.is_empty());
+    }
+
+    #[test]
+    fn test_function_config_output_only_includes_env_var_names() {
+        let mut config = Deploy::default();
+        config.function_config.memory = Some(512.try_into().unwrap());
+        config.function_config.role = Some("arn:aws:iam::123456789012:role/test-role".into());
+        config.function_config.layer = Some(vec!["arn:aws:lambda:us-east-1:123:layer:foo".into()]);
+        config.function_config.env_options = Some(EnvOptions {
+            env_var: Some(vec![
+                "SECRET=value".into(),
+                "API_URL=http://localhost".into(),
+            ]),
+            ..Default::default()
+        });
+
+        let output = FunctionConfigOutput::new(&config).unwrap();
+        assert_eq!(output.memory, Some(512));
+        assert_eq!(output.env_vars, vec!["API_URL", "SECRET"]);
+
+        let text = output.to_string();
+        assert!(text.contains("memory: 512 MB"));
+        assert!(text.contains("role: arn:aws:iam::123456789012:role/test-role"));
+        assert!(text.contains("environment variables: API_URL, SECRET"));
+        assert!(!text.contains("value"));
+    }
+}
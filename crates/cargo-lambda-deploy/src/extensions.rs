@@ -1,22 +1,30 @@
-use aws_sdk_s3::{Client as S3Client, primitives::ByteStream};
-use cargo_lambda_build::{BinaryArchive, BinaryModifiedAt};
+use crate::{
+    binary_name_or_default,
+    checksum::{expected_code_sha256, verify_code_sha256},
+    upload::upload_archive_to_s3,
+};
+use aws_sdk_s3::Client as S3Client;
+use cargo_lambda_build::{BinaryArchive, BinaryData, BinaryModifiedAt, zip_multi_arch_extension};
 use cargo_lambda_interactive::progress::Progress;
-use cargo_lambda_metadata::cargo::deploy::Deploy;
+use cargo_lambda_metadata::cargo::{CargoMetadata, deploy::Deploy, target_dir_from_metadata};
 use cargo_lambda_remote::{
     aws_sdk_config::SdkConfig,
     aws_sdk_lambda::{
         Client as LambdaClient,
         primitives::Blob,
-        types::{LayerVersionContentInput, Runtime},
+        types::{Architecture, LayerVersionContentInput, Runtime},
     },
 };
 use miette::{IntoDiagnostic, Result, WrapErr};
 use serde::Serialize;
-use tracing::debug;
+use std::path::{Path, PathBuf};
+
+/// Profiles to look for the binaries of multi-architecture extensions, in order.
+const BUILD_PROFILES: &[&str] = &["release", "debug"];
 
 #[derive(Serialize)]
 pub(crate) struct DeployOutput {
-    extension_arn: String,
+    pub(crate) extension_arn: String,
     binary_modified_at: BinaryModifiedAt,
 }
 
@@ -45,38 +53,38 @@ pub(crate) async fn deploy(
 ) -> Result<DeployOutput> {
     let lambda_client = LambdaClient::new(sdk_config);
 
+    let compatible_architectures = match &config.compatible_architectures {
+        Some(architectures) => architectures
+            .iter()
+            .map(|arch| Architecture::from(arch.as_str()))
+            .collect(),
+        None => vec![binary_archive.architecture()],
+    };
+
     let compatible_runtimes = config
         .compatible_runtimes()
         .iter()
         .map(|runtime| Runtime::from(runtime.as_str()))
         .collect::<Vec<_>>();
 
+    let expected_sha256 = expected_code_sha256(config, binary_archive)?;
     let input = match &config.s3_bucket {
         None => LayerVersionContentInput::builder()
             .zip_file(Blob::new(binary_archive.read()?))
             .build(),
         Some(bucket) => {
-            progress.set_message("uploading binary to S3");
-
             let key = config.s3_key.as_deref().unwrap_or(name);
-            debug!(bucket, key, "uploading zip to S3");
-
             let s3_client = S3Client::new(sdk_config);
-            let mut operation = s3_client
-                .put_object()
-                .bucket(bucket)
-                .key(key)
-                .body(ByteStream::from(binary_archive.read()?));
-
-            if let Some(tags) = config.s3_tags() {
-                operation = operation.tagging(tags);
-            }
-
-            operation
-                .send()
-                .await
-                .into_diagnostic()
-                .wrap_err("failed to upload extension code to S3")?;
+            upload_archive_to_s3(
+                &s3_client,
+                bucket,
+                key,
+                config.s3_tags(),
+                binary_archive,
+                progress,
+            )
+            .await
+            .wrap_err("failed to upload extension code to S3")?;
 
             LayerVersionContentInput::builder()
                 .s3_bucket(bucket)
@@ -90,7 +98,7 @@ pub(crate) async fn deploy(
     let output = lambda_client
         .publish_layer_version()
         .layer_name(name)
-        .compatible_architectures(binary_archive.architecture())
+        .set_compatible_architectures(Some(compatible_architectures))
         .set_compatible_runtimes(Some(compatible_runtimes))
         .content(input)
         .send()
@@ -98,8 +106,174 @@ pub(crate) async fn deploy(
         .into_diagnostic()
         .wrap_err("failed to publish extension")?;
 
+    let reported_sha256 = output
+        .content
+        .as_ref()
+        .and_then(|content| content.code_sha256.as_deref());
+    verify_code_sha256(expected_sha256.as_deref(), reported_sha256)?;
+
     Ok(DeployOutput {
         extension_arn: output.layer_version_arn.expect("missing ARN"),
         binary_modified_at: binary_archive.binary_modified_at.clone(),
     })
 }
+
+/// Architectures to package in the extension, when there are more than one.
+pub(crate) fn multiple_architectures(config: &Deploy) -> Option<&[String]> {
+    match &config.compatible_architectures {
+        Some(architectures) if config.extension && architectures.len() > 1 => Some(architectures),
+        _ => None,
+    }
+}
+
+/// Check that the extension's binary runs on the architecture that the extension declares.
+pub(crate) fn check_architecture(config: &Deploy, binary_archive: &BinaryArchive) -> Result<()> {
+    match config.compatible_architectures.as_deref() {
+        Some([arch]) if *arch != binary_archive.architecture => Err(miette::miette!(
+            help = format!(
+                "build the extension with `cargo lambda build --extension {}`",
+                arch_flag(arch)
+            ),
+            "the extension is compatible with {arch}, but the binary was built for {}",
+            binary_archive.architecture
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Package the binaries built for each architecture in the same zip file.
+/// The binaries are in the target directories that Cargo uses for each Linux target.
+pub(crate) fn load_multi_arch_archive(
+    config: &Deploy,
+    metadata: &CargoMetadata,
+    name: &str,
+    architectures: &[String],
+) -> Result<BinaryArchive> {
+    if config.binary_path.is_some() {
+        return Err(miette::miette!(
+            "invalid options: --binary-path cannot be used with several --compatible-architectures"
+        ));
+    }
+
+    let binary_name = binary_name_or_default(config, name);
+    let data = BinaryData::new(&binary_name, config.extension, config.internal);
+
+    let target_dir = target_dir_from_metadata(metadata).unwrap_or_else(|_| PathBuf::from("target"));
+    let binaries = architectures
+        .iter()
+        .map(|arch| find_binary(&target_dir, arch, &binary_name))
+        .collect::<Result<Vec<_>>>()?;
+
+    let destination = match &config.lambda_dir {
+        Some(dir) => dir.join(data.binary_location()),
+        None => target_dir.join("lambda").join(data.binary_location()),
+    };
+    std::fs::create_dir_all(&destination)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to create directory {destination:?}"))?;
+
+    zip_multi_arch_extension(
+        &binaries,
+        &destination,
+        &data,
+        config.include.clone(),
+        config.fast_package,
+    )
+}
+
+/// Find the binary built for an architecture, for any of the Linux targets
+/// that Lambda supports. Release builds are preferred over debug builds.
+fn find_binary(target_dir: &Path, arch: &str, binary_name: &str) -> Result<PathBuf> {
+    for profile in BUILD_PROFILES {
+        for target in arch_targets(arch) {
+            let path = target_dir.join(target).join(profile).join(binary_name);
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+    }
+
+    Err(miette::miette!(
+        help = format!(
+            "build the extension with `cargo lambda build --extension --release {}`",
+            arch_flag(arch)
+        ),
+        "binary file for {binary_name} built for {arch} not found in {target_dir:?}"
+    ))
+}
+
+fn arch_targets(arch: &str) -> &'static [&'static str] {
+    match arch {
+        "arm64" => &["aarch64-unknown-linux-gnu", "aarch64-unknown-linux-musl"],
+        _ => &["x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"],
+    }
+}
+
+fn arch_flag(arch: &str) -> &'static str {
+    match arch {
+        "arm64" => "--arm64",
+        _ => "--x86-64",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{copy, create_dir_all};
+
+    #[test]
+    fn test_find_binary() {
+        let target_dir = tempfile::tempdir().unwrap();
+        let release = target_dir
+            .path()
+            .join("aarch64-unknown-linux-musl")
+            .join("release");
+        create_dir_all(&release).unwrap();
+        copy(
+            "../../tests/binaries/binary-arm64",
+            release.join("test-extension"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            release.join("test-extension"),
+            find_binary(target_dir.path(), "arm64", "test-extension").unwrap()
+        );
+
+        let err = find_binary(target_dir.path(), "x86_64", "test-extension").unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("binary file for test-extension built for x86_64 not found")
+        );
+    }
+
+    #[test]
+    fn test_check_architecture() {
+        let archive = BinaryArchive::new(
+            "test-extension.zip".into(),
+            "arm64".into(),
+            BinaryModifiedAt::now(),
+        );
+
+        let mut config = Deploy::default();
+        config.extension = true;
+        assert!(check_architecture(&config, &archive).is_ok());
+
+        config.compatible_architectures = Some(vec!["arm64".into()]);
+        assert!(check_architecture(&config, &archive).is_ok());
+        assert!(multiple_architectures(&config).is_none());
+
+        config.compatible_architectures = Some(vec!["x86_64".into()]);
+        let err = check_architecture(&config, &archive).unwrap_err();
+        assert_eq!(
+            "the extension is compatible with x86_64, but the binary was built for arm64",
+            err.to_string()
+        );
+
+        config.compatible_architectures = Some(vec!["x86_64".into(), "arm64".into()]);
+        assert_eq!(
+            Some(&["x86_64".to_string(), "arm64".to_string()][..]),
+            multiple_architectures(&config)
+        );
+    }
+}
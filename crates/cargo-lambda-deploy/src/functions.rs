@@ -1,33 +1,49 @@
-use crate::roles::{self, FunctionRole};
+use crate::{
+    alarms, canary,
+    checksum::{expected_code_sha256, verify_code_sha256},
+    custom_domain::{self, CustomDomainOutput},
+    debuginfo::store_debug_artifact,
+    plan::{Preconditions, check_revision},
+    replacements,
+    roles::{self, FunctionRole},
+    smoke_test,
+    triggers::{self, TriggerOutput},
+    upload::upload_archive_to_s3,
+};
 use aws_sdk_cloudwatchlogs::operation::create_log_group::CreateLogGroupError;
-use aws_sdk_s3::{Client as S3Client, primitives::ByteStream};
-use cargo_lambda_build::{BinaryArchive, BinaryModifiedAt};
+use aws_sdk_s3::Client as S3Client;
+use cargo_lambda_build::{BinaryArchive, BinaryModifiedAt, GlibcVersion};
 use cargo_lambda_interactive::progress::Progress;
-use cargo_lambda_metadata::cargo::deploy::Deploy;
+use cargo_lambda_metadata::{
+    cargo::deploy::{Deploy, InvokePermission, validate_log_retention},
+    runtime::{latest_provided_runtime, provided_runtime},
+};
 use cargo_lambda_remote::{
     aws_sdk_config::SdkConfig,
     aws_sdk_lambda::{
         Client as LambdaClient,
         error::SdkError,
         operation::{
+            add_permission::AddPermissionError,
             create_function::CreateFunctionError,
             delete_function_url_config::DeleteFunctionUrlConfigError,
             get_alias::GetAliasError,
             get_function::{GetFunctionError, GetFunctionOutput},
             get_function_url_config::GetFunctionUrlConfigError,
+            update_alias::UpdateAliasError,
         },
         primitives::Blob,
         types::{
-            FunctionCode, FunctionConfiguration, FunctionUrlAuthType, LastUpdateStatus, Runtime,
-            State, VpcConfig as LambdaVpcConfig,
+            AliasRoutingConfiguration, Architecture, FunctionCode, FunctionConfiguration,
+            FunctionUrlAuthType, LastUpdateStatus, Runtime, State, VpcConfig as LambdaVpcConfig,
         },
     },
 };
 use miette::{IntoDiagnostic, Result, WrapErr};
 use serde::Serialize;
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, path::Path, str::FromStr};
 use tokio::time::{Duration, sleep};
-use tracing::debug;
+use tracing::{debug, warn};
 use uuid::Uuid;
 
 enum FunctionAction {
@@ -37,11 +53,17 @@ enum FunctionAction {
 
 #[derive(Serialize)]
 pub(crate) struct DeployOutput {
-    function_arn: String,
+    pub(crate) function_arn: String,
     function_url: Option<String>,
     binary_modified_at: BinaryModifiedAt,
-    version: String,
-    alias: Option<String>,
+    pub(crate) version: String,
+    pub(crate) alias: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debug_artifact: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    custom_domain: Option<CustomDomainOutput>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    triggers: Vec<TriggerOutput>,
 }
 
 impl std::fmt::Display for DeployOutput {
@@ -60,26 +82,207 @@ impl std::fmt::Display for DeployOutput {
         if let Some(url) = &self.function_url {
             write!(f, "\n🔗 url: {url}")?;
         }
+        if let Some(custom_domain) = &self.custom_domain {
+            write!(f, "\n{custom_domain}")?;
+        }
+        for trigger in &self.triggers {
+            write!(f, "\n{trigger}")?;
+        }
+        if let Some(debug_artifact) = &self.debug_artifact {
+            write!(f, "\n🐞 debug info: {debug_artifact}")?;
+        }
         Ok(())
     }
 }
 
+/// Warn when the binary links against a version of glibc that the runtime doesn't include.
+/// Lambda only reports this problem when the function starts, after it's been deployed.
+pub(crate) fn check_runtime_compatibility(runtime: &str, binary_archive: &BinaryArchive) {
+    if let Some(message) = runtime_incompatibility(runtime, binary_archive) {
+        warn!("{message}");
+    }
+}
+
+/// Explain why the binary can't run in the runtime, if it can't.
+fn runtime_incompatibility(runtime: &str, binary_archive: &BinaryArchive) -> Option<String> {
+    let provided = provided_runtime(runtime)?;
+    let (major, minor) = provided.glibc_version;
+    let available = GlibcVersion(major, minor);
+
+    let required = binary_archive.glibc_version.filter(|r| *r > available)?;
+    let target_arch = match binary_archive.architecture.as_str() {
+        "arm64" => "aarch64",
+        arch => arch,
+    };
+    let latest = latest_provided_runtime();
+    let suggestion = if runtime == latest {
+        String::new()
+    } else {
+        format!("deploy it with `--runtime {latest}`, or ")
+    };
+    Some(format!(
+        "the binary requires glibc {required}, but the `{runtime}` runtime only includes glibc {available}. \
+        The function will fail to start, {suggestion}build it with `--target {target_arch}-unknown-linux-gnu.{available}`"
+    ))
+}
+
+/// Check that the architecture of an existing function matches the architecture of the binary.
+/// Lambda doesn't validate the binary, so a function with the wrong architecture
+/// fails when it starts. It returns the architecture to update the function to,
+/// if the architectures don't match and the deploy can update it.
+fn check_architecture(
+    config: &Deploy,
+    name: &str,
+    function: &FunctionConfiguration,
+    binary_archive: &BinaryArchive,
+) -> Result<Option<Architecture>> {
+    let binary_arch = binary_archive.architecture();
+    let function_archs = function.architectures();
+    if function_archs.is_empty() || function_archs.contains(&binary_arch) {
+        return Ok(None);
+    }
+
+    let function_arch = function_archs[0].as_str();
+    if !config.update_architecture {
+        return Err(miette::miette!(
+            help = format!(
+                "deploy with `--update-architecture` to change the function's architecture to {binary_arch}, or build the binary for {function_arch}"
+            ),
+            "the function `{name}` runs on {function_arch}, but the binary was built for {binary_arch}"
+        ));
+    }
+
+    warn!(
+        function = name,
+        from = function_arch,
+        to = binary_arch.as_str(),
+        "updating the function's architecture"
+    );
+    Ok(Some(binary_arch))
+}
+
 pub(crate) async fn deploy(
     config: &Deploy,
     name: &str,
     sdk_config: &SdkConfig,
     binary_archive: &BinaryArchive,
+    debug_artifact: Option<&Path>,
+    preconditions: Option<&Preconditions>,
     progress: &Progress,
 ) -> Result<DeployOutput> {
     let client = LambdaClient::new(sdk_config);
+    let smoke_test_payload = smoke_test::load_payload(config)?;
+    let canary = config.canary()?;
+    let gate_alarms = config.gate_alarms();
 
-    let (function_arn, version) =
-        upsert_function(config, name, &client, sdk_config, binary_archive, progress).await?;
+    if !gate_alarms.is_empty() {
+        progress.set_message("checking gate alarms");
+        alarms::check(sdk_config, gate_alarms)
+            .await
+            .wrap_err("the function was not deployed")?;
+    }
 
-    if let Some(alias) = &config.remote_config.alias {
-        progress.set_message("updating alias version");
+    let (function_arn, version) = upsert_function(
+        config,
+        name,
+        &client,
+        sdk_config,
+        binary_archive,
+        preconditions,
+        progress,
+    )
+    .await?;
+
+    let alias = config.remote_config.alias.as_deref();
+    let previous_version = match alias {
+        Some(alias) => {
+            progress.set_message("updating alias version");
+
+            match &canary {
+                Some(canary) => {
+                    canary::start(&client, name, alias, &version, canary, preconditions).await?
+                }
+                None => upsert_alias(name, alias, &version, preconditions, &client).await?,
+            }
+        }
+        None => None,
+    };
+    // The canary only runs when the alias pointed to a different version before the deploy.
+    let canary = canary.filter(|_| previous_version.as_ref().is_some_and(|v| *v != version));
+
+    if let Some(payload) = &smoke_test_payload {
+        progress.set_message("running smoke test");
 
-        upsert_alias(name, alias, &version, &client).await?;
+        // During a canary, the alias only sends part of the traffic to the new version.
+        let qualifier = match (alias, &canary) {
+            (Some(alias), None) => alias,
+            _ => &version,
+        };
+        if let Err(err) = smoke_test::invoke(&client, name, qualifier, payload).await {
+            progress.set_message("rolling back alias version");
+            return Err(rollback(
+                err,
+                "smoke test",
+                name,
+                alias,
+                &version,
+                previous_version.as_deref(),
+                &client,
+            )
+            .await);
+        }
+    }
+
+    if canary.is_none() && !gate_alarms.is_empty() {
+        progress.set_message("checking gate alarms");
+
+        if let Err(err) = alarms::check(sdk_config, gate_alarms).await {
+            progress.set_message("rolling back alias version");
+            return Err(rollback(
+                err,
+                "alarm gate",
+                name,
+                alias,
+                &version,
+                previous_version.as_deref(),
+                &client,
+            )
+            .await);
+        }
+    }
+
+    if let (Some(canary), Some(alias), Some(previous_version)) =
+        (&canary, alias, previous_version.as_deref())
+    {
+        let result = canary::monitor(
+            sdk_config,
+            name,
+            alias,
+            &version,
+            canary,
+            gate_alarms,
+            progress,
+        )
+        .await;
+        if let Err(err) = result {
+            progress.set_message("rolling back alias version");
+            return Err(rollback(
+                err,
+                "canary",
+                name,
+                Some(alias),
+                &version,
+                Some(previous_version),
+                &client,
+            )
+            .await);
+        }
+
+        progress.set_message("promoting new version");
+        point_alias(name, alias, &version, &client)
+            .await
+            .into_diagnostic()
+            .wrap_err("failed to promote the new version")?;
     }
 
     let function_url = if config.function_config.enable_function_url {
@@ -90,23 +293,72 @@ pub(crate) async fn deploy(
         None
     };
 
+    let custom_domain = match (config.custom_domain()?, &function_url) {
+        (Some(domain), Some(url)) => {
+            Some(custom_domain::configure(sdk_config, name, &domain, url, progress).await?)
+        }
+        _ => None,
+    };
+
     if config.function_config.disable_function_url {
         progress.set_message("deleting function url configuration");
 
         delete_function_url_config(name, &config.remote_config.alias, &client).await?;
     }
 
+    let invoke_permissions = config.invoke_permissions()?;
+    if !invoke_permissions.is_empty() {
+        progress.set_message("adding invoke permissions");
+
+        add_invoke_permissions(
+            name,
+            &config.remote_config.alias,
+            &invoke_permissions,
+            &client,
+        )
+        .await?;
+    }
+
+    let triggers = config.triggers()?;
+    let triggers = if triggers.is_empty() {
+        Vec::new()
+    } else {
+        triggers::create(
+            sdk_config,
+            &client,
+            name,
+            &config.remote_config.alias,
+            &function_arn,
+            &triggers,
+            progress,
+        )
+        .await?
+    };
+
     if let Some(retention) = config.function_config.log_retention {
+        validate_log_retention(retention)?;
+
         progress.set_message("setting log retention");
-        set_log_retention(sdk_config, retention, name).await?;
+        set_log_retention(sdk_config, retention, name, config.lambda_tags()).await?;
     }
 
+    let debug_artifact = match debug_artifact {
+        Some(path) => {
+            let s3_client = S3Client::new(sdk_config);
+            Some(store_debug_artifact(config, name, &s3_client, path, progress).await?)
+        }
+        None => None,
+    };
+
     Ok(DeployOutput {
         function_arn,
         function_url,
         version,
         alias: config.remote_config.alias.clone(),
         binary_modified_at: binary_archive.binary_modified_at.clone(),
+        debug_artifact,
+        custom_domain,
+        triggers,
     })
 }
 
@@ -117,6 +369,7 @@ async fn upsert_function(
     client: &LambdaClient,
     sdk_config: &SdkConfig,
     binary_archive: &BinaryArchive,
+    preconditions: Option<&Preconditions>,
     progress: &Progress,
 ) -> Result<(String, String)> {
     let current_function = client.get_function().function_name(name).send().await;
@@ -133,10 +386,16 @@ async fn upsert_function(
 
     let s3_client = S3Client::new(sdk_config);
 
+    let exists = matches!(action, FunctionAction::Update(_));
+    let revision = preconditions
+        .map(|preconditions| preconditions.function_revision(exists))
+        .transpose()?
+        .flatten();
+
     let (arn, version) = match action {
         FunctionAction::Create => {
             let function_role = match &config.function_config.role {
-                None => roles::create(sdk_config, progress).await?,
+                None => roles::create(sdk_config, config.lambda_tags(), progress).await?,
                 Some(role) => FunctionRole::from_existing(role.clone()),
             };
 
@@ -154,15 +413,42 @@ async fn upsert_function(
         FunctionAction::Update(fun) => {
             progress.set_message("deploying function");
 
+            let current_tags = fun.tags.clone().unwrap_or_default();
             let conf = fun
                 .configuration
                 .ok_or_else(|| miette::miette!("missing function configuration"))?;
 
-            let function_arn = update_function_config(config, name, client, progress, conf).await?;
+            let architecture = check_architecture(config, name, &conf, binary_archive)?;
+
+            let replacements = replacements::replacements(config, &conf, architecture.as_ref());
+            replacements::confirm(config, name, &replacements, progress)?;
+
+            let (function_arn, revision) = update_function_config(
+                config,
+                name,
+                client,
+                progress,
+                conf,
+                revision,
+                preconditions,
+            )
+            .await?;
 
-            tag_function(client, config.lambda_tags(), function_arn).await?;
+            let changes = tag_changes(&current_tags, config.lambda_tags(), config.prune_tags);
+            update_tags(client, changes, &function_arn, progress).await?;
 
-            update_function_code(config, name, client, &s3_client, binary_archive, progress).await?
+            update_function_code(
+                config,
+                name,
+                client,
+                &s3_client,
+                binary_archive,
+                architecture,
+                revision,
+                preconditions,
+                progress,
+            )
+            .await?
         }
     };
 
@@ -172,28 +458,77 @@ async fn upsert_function(
     ))
 }
 
-async fn tag_function(
-    client: &LambdaClient,
+/// Changes to apply to the tags of an existing function.
+#[derive(Debug, Default, PartialEq)]
+struct TagChanges {
+    /// Tags that the function doesn't have, or that have a different value
+    tag: HashMap<String, String>,
+    /// Keys of the tags to remove from the function
+    untag: Vec<String>,
+}
+
+/// Compare the tags that a function has with the tags to apply.
+/// Tags that are not in the list are only removed when `prune` is true,
+/// and tags managed by AWS are never removed.
+fn tag_changes(
+    current: &HashMap<String, String>,
     lambda_tags: Option<HashMap<String, String>>,
-    function_arn: String,
+    prune: bool,
+) -> TagChanges {
+    let tags = lambda_tags.unwrap_or_default();
+
+    let mut untag = Vec::new();
+    if prune {
+        untag = current
+            .keys()
+            .filter(|key| !tags.contains_key(*key) && !key.starts_with("aws:"))
+            .cloned()
+            .collect();
+        untag.sort();
+    }
+
+    let tag = tags
+        .into_iter()
+        .filter(|(key, value)| current.get(key) != Some(value))
+        .collect();
+
+    TagChanges { tag, untag }
+}
+
+async fn update_tags(
+    client: &LambdaClient,
+    changes: TagChanges,
+    function_arn: &str,
+    progress: &Progress,
 ) -> Result<()> {
-    let Some(tags) = lambda_tags else {
-        return Ok(());
-    };
+    if !changes.tag.is_empty() {
+        progress.set_message("tagging function");
 
-    if tags.is_empty() {
-        return Ok(());
+        client
+            .tag_resource()
+            .resource(function_arn)
+            .set_tags(Some(changes.tag))
+            .send()
+            .await
+            .into_diagnostic()
+            .wrap_err("failed to tag function")?;
     }
 
-    client
-        .tag_resource()
-        .resource(&function_arn)
-        .set_tags(Some(tags))
-        .send()
-        .await
-        .into_diagnostic()
-        .wrap_err("failed to tag function")
-        .map(|_| ())
+    if !changes.untag.is_empty() {
+        progress.set_message("removing function tags");
+        debug!(tags = ?changes.untag, "removing tags from function");
+
+        client
+            .untag_resource()
+            .resource(function_arn)
+            .set_tag_keys(Some(changes.untag))
+            .send()
+            .await
+            .into_diagnostic()
+            .wrap_err("failed to remove tags from function")?;
+    }
+
+    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -209,6 +544,7 @@ async fn create_function(
     debug!(?function_role, ?config, "creating new function");
     progress.set_message("deploying function");
 
+    let expected_sha256 = expected_code_sha256(config, binary_archive)?;
     let code = match &config.s3_bucket {
         None => {
             debug!("uploading zip to Lambda");
@@ -217,17 +553,17 @@ async fn create_function(
         }
         Some(bucket) => {
             let key = config.s3_key.as_deref().unwrap_or(name);
-            debug!(bucket, key, "uploading zip to S3");
-            s3_client
-                .put_object()
-                .bucket(bucket)
-                .key(key)
-                .body(ByteStream::from(binary_archive.read()?))
-                .set_tagging(config.s3_tags())
-                .send()
-                .await
-                .into_diagnostic()
-                .wrap_err("failed to upload function code to S3")?;
+            upload_archive_to_s3(
+                s3_client,
+                bucket,
+                key,
+                config.s3_tags(),
+                binary_archive,
+                progress,
+            )
+            .await
+            .wrap_err("failed to upload function code to S3")?;
+            progress.set_message("deploying function");
             FunctionCode::builder()
                 .s3_bucket(bucket)
                 .s3_key(key)
@@ -260,7 +596,7 @@ async fn create_function(
 
         let result = function
             .runtime(runtime.clone())
-            .handler("bootstrap")
+            .handler(config.function_config.handler())
             .function_name(name)
             .role(function_role.arn())
             .architectures(binary_archive.architecture())
@@ -300,6 +636,9 @@ async fn create_function(
         };
     }
 
+    let output = output.ok_or_else(|| miette::miette!("failed to create new lambda function"))?;
+    verify_code_sha256(expected_sha256.as_deref(), output.code_sha256.as_deref())?;
+
     if let Some(description) = &config.function_config.description {
         wait_for_ready_state(lambda_client, name, &config.remote_config.alias, progress).await?;
 
@@ -317,19 +656,21 @@ async fn create_function(
                 .wrap_err("failed to publish the new lambda version"),
         }
     } else {
-        output
-            .map(|o| (o.function_arn, o.version))
-            .ok_or_else(|| miette::miette!("failed to create new lambda function"))
+        Ok((output.function_arn, output.version))
     }
 }
 
+/// Update the configuration of the function, and return its ARN and the revision
+/// to send with the next update, when the deploy applies a plan.
 async fn update_function_config(
     config: &Deploy,
     name: &str,
     client: &LambdaClient,
     progress: &Progress,
     conf: FunctionConfiguration,
-) -> Result<String> {
+    mut revision: Option<String>,
+    preconditions: Option<&Preconditions>,
+) -> Result<(String, Option<String>)> {
     let function_arn = conf.function_arn.as_ref().expect("missing function arn");
 
     let mut wait_for_readiness = false;
@@ -352,6 +693,9 @@ async fn update_function_config(
 
     if config.function_config.should_update() {
         if let Some(iam_role) = &config.function_config.role {
+            if conf.role.as_ref() != Some(iam_role) {
+                update_config = true;
+            }
             builder = builder.role(iam_role);
         }
 
@@ -361,6 +705,21 @@ async fn update_function_config(
             builder = builder.set_memory_size(memory);
         }
 
+        if let Some(runtime) = &config.function_config.runtime {
+            let runtime = Runtime::from(runtime.as_str());
+            if conf.runtime.as_ref() != Some(&runtime) {
+                update_config = true;
+                builder = builder.runtime(runtime);
+            }
+        }
+
+        if let Some(handler) = &config.function_config.handler {
+            if conf.handler.as_ref() != Some(handler) {
+                update_config = true;
+                builder = builder.handler(handler);
+            }
+        }
+
         if let Some(timeout) = &config.function_config.timeout {
             let timeout: i32 = timeout.into();
             if conf.timeout.unwrap_or_default() != timeout {
@@ -415,31 +774,41 @@ async fn update_function_config(
 
     if update_config {
         debug!("updating function's configuration");
-        let result = builder
-            .send()
-            .await
+        let result = builder.set_revision_id(revision.clone()).send().await;
+        let result = check_revision(preconditions, result)?
             .into_diagnostic()
             .wrap_err("failed to update function configuration")?;
 
+        revision = revision.and(result.revision_id.clone());
         if result.last_update_status() == Some(&LastUpdateStatus::InProgress) {
             wait_for_ready_state(client, name, &config.remote_config.alias, progress).await?;
+            revision = latest_revision(client, name, revision).await?;
         }
         progress.set_message("deploying function");
     }
 
-    Ok(function_arn.clone())
+    Ok((function_arn.clone(), revision))
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn update_function_code(
     config: &Deploy,
     name: &str,
     lambda_client: &LambdaClient,
     s3_client: &S3Client,
     binary_archive: &BinaryArchive,
+    architecture: Option<Architecture>,
+    revision: Option<String>,
+    preconditions: Option<&Preconditions>,
     progress: &Progress,
 ) -> Result<(Option<String>, Option<String>)> {
-    let mut builder = lambda_client.update_function_code().function_name(name);
+    let mut builder = lambda_client
+        .update_function_code()
+        .function_name(name)
+        .set_architectures(architecture.map(|a| vec![a]))
+        .set_revision_id(revision.clone());
 
+    let expected_sha256 = expected_code_sha256(config, binary_archive)?;
     match &config.s3_bucket {
         None => {
             debug!("uploading zip to Lambda");
@@ -448,46 +817,44 @@ async fn update_function_code(
         }
         Some(bucket) => {
             let key = config.s3_key.as_deref().unwrap_or(name);
-
-            debug!(bucket, key, "uploading zip to S3");
-
-            let mut operation = s3_client
-                .put_object()
-                .bucket(bucket)
-                .key(key)
-                .body(ByteStream::from(binary_archive.read()?));
-
-            let s3_tags = config.s3_tags();
-            if s3_tags.is_some() {
-                operation = operation.set_tagging(s3_tags);
-            }
-            operation
-                .send()
-                .await
-                .into_diagnostic()
-                .wrap_err("failed to upload function code to S3")?;
+            upload_archive_to_s3(
+                s3_client,
+                bucket,
+                key,
+                config.s3_tags(),
+                binary_archive,
+                progress,
+            )
+            .await
+            .wrap_err("failed to upload function code to S3")?;
 
             builder = builder.s3_bucket(bucket).s3_key(key);
         }
     }
 
-    let output = builder
+    let result = builder
         .publish(config.publish_code_without_description())
         .send()
-        .await
+        .await;
+    let output = check_revision(preconditions, result)?
         .into_diagnostic()
         .wrap_err("failed to update function code")?;
+    verify_code_sha256(expected_sha256.as_deref(), output.code_sha256.as_deref())?;
 
     if let Some(description) = &config.function_config.description {
         wait_for_ready_state(lambda_client, name, &config.remote_config.alias, progress).await?;
+        let revision = revision.and(output.revision_id.clone());
+        let revision = latest_revision(lambda_client, name, revision).await?;
+
         let result = lambda_client
             .publish_version()
             .function_name(name)
             .description(description)
+            .set_revision_id(revision)
             .send()
             .await;
 
-        match result {
+        match check_revision(preconditions, result)? {
             Ok(o) => Ok((o.function_arn, o.version)),
             Err(err) => Err(err)
                 .into_diagnostic()
@@ -498,6 +865,28 @@ async fn update_function_code(
     }
 }
 
+/// Read the revision of the function again after waiting for an update to finish,
+/// because Lambda can change it while it finishes the update.
+/// It only reads it when the deploy sends revisions with its updates.
+async fn latest_revision(
+    client: &LambdaClient,
+    name: &str,
+    revision: Option<String>,
+) -> Result<Option<String>> {
+    if revision.is_none() {
+        return Ok(None);
+    }
+
+    let conf = client
+        .get_function_configuration()
+        .function_name(name)
+        .send()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to fetch the function configuration")?;
+    Ok(conf.revision_id)
+}
+
 /// Wait until the function state has been completely propagated.
 async fn wait_for_ready_state(
     client: &LambdaClient,
@@ -582,12 +971,14 @@ pub(crate) fn should_update_layers(
     }
 }
 
+/// Point the alias to the version, and return the version that it pointed to before.
 pub(crate) async fn upsert_alias(
     name: &str,
     alias: &str,
     version: &str,
+    preconditions: Option<&Preconditions>,
     client: &LambdaClient,
-) -> Result<()> {
+) -> Result<Option<String>> {
     let current_alias = client
         .get_alias()
         .name(alias)
@@ -595,19 +986,29 @@ pub(crate) async fn upsert_alias(
         .send()
         .await;
 
-    match current_alias {
-        Ok(_) => {
-            client
+    let previous_version = match current_alias {
+        Ok(current) => {
+            let revision = preconditions
+                .map(|preconditions| preconditions.alias_revision(true))
+                .transpose()?
+                .flatten();
+            let result = client
                 .update_alias()
                 .name(alias)
                 .function_name(name)
                 .function_version(version)
+                .set_revision_id(revision)
                 .send()
-                .await
+                .await;
+            check_revision(preconditions, result)?
                 .into_diagnostic()
                 .wrap_err("failed to update alias")?;
+            current.function_version
         }
         Err(no_fun) if alias_doesnt_exist_error(&no_fun) => {
+            if let Some(preconditions) = preconditions {
+                preconditions.alias_revision(false)?;
+            }
             client
                 .create_alias()
                 .name(alias)
@@ -617,6 +1018,7 @@ pub(crate) async fn upsert_alias(
                 .await
                 .into_diagnostic()
                 .wrap_err("failed to create alias")?;
+            None
         }
         Err(no_fun) => {
             return Err(no_fun)
@@ -625,9 +1027,87 @@ pub(crate) async fn upsert_alias(
         }
     };
 
+    Ok(previous_version)
+}
+
+/// Point all the alias traffic to a version, removing the traffic shifting of a canary.
+async fn point_alias(
+    name: &str,
+    alias: &str,
+    version: &str,
+    client: &LambdaClient,
+) -> Result<(), SdkError<UpdateAliasError>> {
+    let routing = AliasRoutingConfiguration::builder()
+        .set_additional_version_weights(Some(HashMap::new()))
+        .build();
+
+    client
+        .update_alias()
+        .name(alias)
+        .function_name(name)
+        .function_version(version)
+        .routing_config(routing)
+        .send()
+        .await?;
     Ok(())
 }
 
+/// Undo the alias changes after a check of the new version fails, and report the failure.
+async fn rollback(
+    err: miette::Report,
+    check: &str,
+    name: &str,
+    alias: Option<&str>,
+    version: &str,
+    previous_version: Option<&str>,
+    client: &LambdaClient,
+) -> miette::Report {
+    let Some(alias) = alias else {
+        return err.wrap_err(format!(
+            "{check} failed, version {version} of the function is already deployed"
+        ));
+    };
+
+    match rollback_alias(name, alias, previous_version, client).await {
+        Ok(rollback) => err.wrap_err(format!("{check} failed, {rollback}")),
+        Err(rollback_err) => rollback_err.wrap_err(format!("{check} failed: {err}")),
+    }
+}
+
+/// Point the alias back to the version it had before the deploy, or delete it
+/// if the deploy created it. It returns the message to report the rollback with.
+async fn rollback_alias(
+    name: &str,
+    alias: &str,
+    previous_version: Option<&str>,
+    client: &LambdaClient,
+) -> Result<String> {
+    match previous_version {
+        Some(version) => {
+            point_alias(name, alias, version, client)
+                .await
+                .into_diagnostic()
+                .wrap_err("failed to roll back the alias")?;
+            Ok(format!(
+                "the alias `{alias}` was rolled back to version {version}"
+            ))
+        }
+        None => {
+            client
+                .delete_alias()
+                .name(alias)
+                .function_name(name)
+                .send()
+                .await
+                .into_diagnostic()
+                .wrap_err("failed to delete the alias created by the deploy")?;
+            Ok(format!(
+                "the alias `{alias}` created by the deploy was deleted"
+            ))
+        }
+    }
+}
+
 pub(crate) async fn upsert_function_url_config(
     name: &str,
     alias: &Option<String>,
@@ -699,13 +1179,63 @@ pub(crate) async fn delete_function_url_config(
     }
 }
 
-async fn set_log_retention(sdk_config: &SdkConfig, retention: i32, name: &str) -> Result<()> {
+/// Add statements to the function's resource-based policy that allow the principals
+/// to invoke it. Statements that already exist from previous deploys are kept as they are.
+pub(crate) async fn add_invoke_permissions(
+    name: &str,
+    alias: &Option<String>,
+    permissions: &[InvokePermission],
+    client: &LambdaClient,
+) -> Result<()> {
+    for permission in permissions {
+        let statement_id = permission.statement_id();
+        let result = client
+            .add_permission()
+            .function_name(name)
+            .set_qualifier(alias.clone())
+            .action("lambda:InvokeFunction")
+            .principal(&permission.principal)
+            .set_source_arn(permission.source_arn.clone())
+            .set_source_account(permission.source_account.clone())
+            .statement_id(&statement_id)
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => {}
+            Err(err) if permission_already_exists_error(&err) => {
+                debug!(statement_id, "the invoke permission already exists");
+            }
+            Err(err) => {
+                return Err(err).into_diagnostic().wrap_err(format!(
+                    "failed to allow `{}` to invoke the function",
+                    permission
+                        .source_arn
+                        .as_deref()
+                        .unwrap_or(&permission.principal)
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn set_log_retention(
+    sdk_config: &SdkConfig,
+    retention: i32,
+    name: &str,
+    tags: Option<HashMap<String, String>>,
+) -> Result<()> {
     let cw_client = aws_sdk_cloudwatchlogs::Client::new(sdk_config);
     let log_group_name = format!("/aws/lambda/{name}");
 
+    // Tags are only applied when the log group is created,
+    // existing log groups keep the tags that they already have.
     match cw_client
         .create_log_group()
         .log_group_name(&log_group_name)
+        .set_tags(tags)
         .send()
         .await
     {
@@ -761,6 +1291,13 @@ pub(crate) fn delete_function_url_config_doesnt_exist_error(
     }
 }
 
+fn permission_already_exists_error(err: &SdkError<AddPermissionError>) -> bool {
+    match err {
+        SdkError::ServiceError(e) => e.err().is_resource_conflict_exception(),
+        _ => false,
+    }
+}
+
 pub(crate) fn alias_doesnt_exist_error(err: &SdkError<GetAliasError>) -> bool {
     match err {
         SdkError::ServiceError(e) => e.err().is_resource_not_found_exception(),
@@ -788,6 +1325,72 @@ mod tests {
     use http::{Request, Response};
     use std::io::Read;
 
+    #[test]
+    fn test_runtime_incompatibility() {
+        let mut archive = BinaryArchive::new(
+            "bootstrap.zip".into(),
+            "x86_64".into(),
+            BinaryModifiedAt::now(),
+        );
+        assert_eq!(None, runtime_incompatibility("provided.al2", &archive));
+
+        archive.glibc_version = Some(GlibcVersion(2, 26));
+        assert_eq!(None, runtime_incompatibility("provided.al2", &archive));
+
+        archive.glibc_version = Some(GlibcVersion(2, 34));
+        assert_eq!(
+            Some(
+                "the binary requires glibc 2.34, but the `provided.al2` runtime only includes glibc 2.26. \
+                The function will fail to start, deploy it with `--runtime provided.al2023`, or build it with `--target x86_64-unknown-linux-gnu.2.26`"
+                    .to_string()
+            ),
+            runtime_incompatibility("provided.al2", &archive)
+        );
+        assert_eq!(None, runtime_incompatibility("provided.al2023", &archive));
+        assert_eq!(None, runtime_incompatibility("nodejs20.x", &archive));
+
+        archive.glibc_version = Some(GlibcVersion(2, 38));
+        let message = runtime_incompatibility("provided.al2023", &archive).unwrap();
+        assert!(
+            message.contains("build it with `--target x86_64-unknown-linux-gnu.2.34`"),
+            "{message}"
+        );
+        assert!(!message.contains("--runtime"), "{message}");
+    }
+
+    #[test]
+    fn test_check_architecture() {
+        let archive = BinaryArchive::new(
+            "bootstrap.zip".into(),
+            "arm64".into(),
+            BinaryModifiedAt::now(),
+        );
+        let mut config = Deploy::default();
+
+        let function = FunctionConfiguration::builder()
+            .architectures(Architecture::Arm64)
+            .build();
+        assert_eq!(
+            None,
+            check_architecture(&config, "test-function", &function, &archive).unwrap()
+        );
+
+        let function = FunctionConfiguration::builder()
+            .architectures(Architecture::X8664)
+            .build();
+        let err = check_architecture(&config, "test-function", &function, &archive).unwrap_err();
+        assert_eq!(
+            "the function `test-function` runs on x86_64, but the binary was built for arm64",
+            err.to_string()
+        );
+
+        config.update_architecture = true;
+        assert_eq!(
+            Some(Architecture::Arm64),
+            check_architecture(&config, "test-function", &function, &archive).unwrap()
+        );
+    }
+
     #[tokio::test]
     async fn test_update_function_config_no_changes() {
         // Create a mock client that fails if any requests are made
@@ -812,12 +1415,16 @@ mod tests {
             .build();
 
         // This should not make any requests since no config changes are needed
-        let result = update_function_config(&config, name, &client, &progress, conf).await;
+        let result =
+            update_function_config(&config, name, &client, &progress, conf, None, None).await;
 
         assert!(result.is_ok());
         assert_eq!(
             result.unwrap(),
-            "arn:aws:lambda:us-east-1:123456789012:function:test-function"
+            (
+                "arn:aws:lambda:us-east-1:123456789012:function:test-function".to_string(),
+                None
+            )
         );
         http_client.assert_requests_match(&[]);
     }
@@ -884,6 +1491,9 @@ mod tests {
             &lambda_client,
             &s3_client,
             &binary_archive,
+            None,
+            None,
+            None,
             &progress,
         )
         .await;
@@ -897,6 +1507,74 @@ mod tests {
         http_client.assert_requests_match(&[]);
     }
 
+    #[tokio::test]
+    async fn test_update_function_code_verify_checksum() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+
+        let events = ["47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=", "corrupted"]
+            .into_iter()
+            .map(|code_sha256| {
+                let response_body = SdkBody::from(
+                    serde_json::json!({
+                        "FunctionArn": "arn:aws:lambda:us-east-1:123456789012:function:test-function",
+                        "Version": "1",
+                        "CodeSha256": code_sha256
+                    })
+                    .to_string(),
+                );
+                ReplayEvent::new(
+                    Request::builder()
+                        .uri("https://lambda.us-east-1.amazonaws.com/2015-03-31/functions/test-function/code")
+                        .body(SdkBody::empty())
+                        .unwrap(),
+                    Response::builder().status(200).body(response_body).unwrap(),
+                )
+            })
+            .collect();
+        let http_client = StaticReplayClient::new(events);
+
+        let lambda_config = LambdaConfig::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let lambda_client = LambdaClient::from_conf(lambda_config);
+
+        let s3_config = S3Config::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let s3_client = S3Client::from_conf(s3_config);
+
+        let mut deploy_config = Deploy::default();
+        deploy_config.verify_checksum = true;
+
+        let binary_archive = BinaryArchive::new(
+            temp_file.path().to_path_buf(),
+            "x86_64".to_string(),
+            BinaryModifiedAt::now(),
+        );
+
+        let progress = Progress::start("deploying function");
+
+        for expected_ok in [true, false] {
+            let result = update_function_code(
+                &deploy_config,
+                "test-function",
+                &lambda_client,
+                &s3_client,
+                &binary_archive,
+                None,
+                None,
+                None,
+                &progress,
+            )
+            .await;
+            assert_eq!(expected_ok, result.is_ok());
+        }
+    }
+
     #[tokio::test]
     async fn test_update_function_code_with_s3() {
         // Setup mock responses
@@ -976,6 +1654,9 @@ mod tests {
             &lambda_client,
             &s3_client,
             &binary_archive,
+            None,
+            None,
+            None,
             &progress,
         )
         .await;
@@ -1239,16 +1920,351 @@ mod tests {
             .timeout(30)
             .build();
 
-        let result = update_function_config(&deploy_config, name, &client, &progress, conf).await;
+        let result =
+            update_function_config(&deploy_config, name, &client, &progress, conf, None, None)
+                .await;
 
         assert!(result.is_ok());
         assert_eq!(
             result.unwrap(),
-            "arn:aws:lambda:us-east-1:123456789012:function:test-function"
+            (
+                "arn:aws:lambda:us-east-1:123456789012:function:test-function".to_string(),
+                None
+            )
         );
         http_client.assert_requests_match(&[]);
     }
 
+    #[tokio::test]
+    async fn test_update_function_config_with_revision() {
+        let request = || {
+            Request::builder()
+                .uri("https://lambda.us-east-1.amazonaws.com/2015-03-31/functions/test-function/configuration")
+                .method("PUT")
+                .body(SdkBody::from(
+                    serde_json::json!({ "RevisionId": "rev-1", "Timeout": 120 }).to_string(),
+                ))
+                .unwrap()
+        };
+
+        let http_client = StaticReplayClient::new(vec![
+            ReplayEvent::new(
+                request(),
+                Response::builder()
+                    .status(200)
+                    .body(SdkBody::from(
+                        serde_json::json!({
+                            "FunctionArn": "arn:aws:lambda:us-east-1:123456789012:function:test-function",
+                            "LastUpdateStatus": "Successful",
+                            "RevisionId": "rev-2"
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            ),
+            ReplayEvent::new(
+                request(),
+                Response::builder()
+                    .status(412)
+                    .header("x-amzn-errortype", "PreconditionFailedException")
+                    .body(SdkBody::from(
+                        serde_json::json!({
+                            "Type": "User",
+                            "message": "The Revision Id provided does not match the latest Revision Id."
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            ),
+        ]);
+
+        let config = LambdaConfig::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let client = LambdaClient::from_conf(config);
+
+        let mut deploy_config = Deploy::default();
+        deploy_config.function_config.timeout = Some(120.into());
+        let progress = Progress::start("deploying function");
+
+        let plan: crate::plan::DeployPlan = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "kind": "function",
+            "name": "test-function",
+            "account": null,
+            "region": null,
+            "code_sha256": "",
+            "remote_state": { "function_revision": "rev-1", "alias_revision": null },
+            "api_calls": []
+        }))
+        .unwrap();
+        let preconditions = plan.preconditions(Path::new("plan.json"));
+
+        let conf = FunctionConfiguration::builder()
+            .function_arn("arn:aws:lambda:us-east-1:123456789012:function:test-function")
+            .state(State::Active)
+            .last_update_status(LastUpdateStatus::Successful)
+            .timeout(30)
+            .build();
+
+        let (_, revision) = update_function_config(
+            &deploy_config,
+            "test-function",
+            &client,
+            &progress,
+            conf.clone(),
+            Some("rev-1".into()),
+            Some(&preconditions),
+        )
+        .await
+        .unwrap();
+        assert_eq!(Some("rev-2".to_string()), revision);
+
+        let err = update_function_config(
+            &deploy_config,
+            "test-function",
+            &client,
+            &progress,
+            conf,
+            Some("rev-1".into()),
+            Some(&preconditions),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            "the deploy doesn't match the plan \"plan.json\", the function in AWS changed since the plan was written",
+            err.to_string()
+        );
+
+        http_client.assert_requests_match(&[]);
+    }
+
+    #[test]
+    fn test_tag_changes() {
+        let current = HashMap::from([
+            ("team".to_string(), "lambda".to_string()),
+            ("env".to_string(), "staging".to_string()),
+            ("owner".to_string(), "david".to_string()),
+            (
+                "aws:cloudformation:stack-name".to_string(),
+                "stack".to_string(),
+            ),
+        ]);
+        let tags = HashMap::from([
+            ("team".to_string(), "lambda".to_string()),
+            ("env".to_string(), "production".to_string()),
+            ("cost-center".to_string(), "42".to_string()),
+        ]);
+
+        let changes = tag_changes(&current, Some(tags.clone()), false);
+        assert_eq!(
+            TagChanges {
+                tag: HashMap::from([
+                    ("env".to_string(), "production".to_string()),
+                    ("cost-center".to_string(), "42".to_string()),
+                ]),
+                untag: vec![],
+            },
+            changes
+        );
+
+        let changes = tag_changes(&current, Some(tags), true);
+        assert_eq!(vec!["owner".to_string()], changes.untag);
+
+        let changes = tag_changes(&current, None, true);
+        assert!(changes.tag.is_empty());
+        assert_eq!(
+            vec!["env".to_string(), "owner".to_string(), "team".to_string()],
+            changes.untag
+        );
+
+        assert_eq!(TagChanges::default(), tag_changes(&current, None, false));
+    }
+
+    #[tokio::test]
+    async fn test_rollback_alias() {
+        let alias_uri = "https://lambda.us-east-1.amazonaws.com/2015-03-31/functions/test-function/aliases/live";
+
+        let http_client = StaticReplayClient::new(vec![
+            ReplayEvent::new(
+                Request::builder()
+                    .uri(alias_uri)
+                    .method("PUT")
+                    .body(SdkBody::from(
+                        serde_json::json!({
+                            "FunctionVersion": "3",
+                            "RoutingConfig": { "AdditionalVersionWeights": {} }
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+                Response::builder()
+                    .status(200)
+                    .body(SdkBody::from(
+                        serde_json::json!({ "Name": "live", "FunctionVersion": "3" }).to_string(),
+                    ))
+                    .unwrap(),
+            ),
+            ReplayEvent::new(
+                Request::builder()
+                    .uri(alias_uri)
+                    .method("DELETE")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                Response::builder()
+                    .status(204)
+                    .body(SdkBody::empty())
+                    .unwrap(),
+            ),
+        ]);
+
+        let config = LambdaConfig::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let client = LambdaClient::from_conf(config);
+
+        let message = rollback_alias("test-function", "live", Some("3"), &client)
+            .await
+            .unwrap();
+        assert_eq!("the alias `live` was rolled back to version 3", message);
+
+        let message = rollback_alias("test-function", "live", None, &client)
+            .await
+            .unwrap();
+        assert_eq!(
+            "the alias `live` created by the deploy was deleted",
+            message
+        );
+
+        http_client.assert_requests_match(&[]);
+    }
+
+    #[tokio::test]
+    async fn test_add_invoke_permissions() {
+        let policy_uri = "https://lambda.us-east-1.amazonaws.com/2015-03-31/functions/test-function/policy?Qualifier=live";
+
+        let http_client = StaticReplayClient::new(vec![
+            ReplayEvent::new(
+                Request::builder()
+                    .uri(policy_uri)
+                    .method("POST")
+                    .body(SdkBody::from(
+                        serde_json::json!({
+                            "Action": "lambda:InvokeFunction",
+                            "Principal": "events.amazonaws.com",
+                            "SourceAccount": "123456789012",
+                            "SourceArn": "arn:aws:events:us-east-1:123456789012:rule/orders",
+                            "StatementId": "cargo-lambda-arn-aws-events-us-east-1-123456789012-rule-orders"
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+                Response::builder()
+                    .status(201)
+                    .body(SdkBody::from(
+                        serde_json::json!({ "Statement": "{}" }).to_string(),
+                    ))
+                    .unwrap(),
+            ),
+            ReplayEvent::new(
+                Request::builder()
+                    .uri(policy_uri)
+                    .method("POST")
+                    .body(SdkBody::from(
+                        serde_json::json!({
+                            "Action": "lambda:InvokeFunction",
+                            "Principal": "210987654321",
+                            "StatementId": "cargo-lambda-210987654321"
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+                Response::builder()
+                    .status(409)
+                    .header("x-amzn-errortype", "ResourceConflictException")
+                    .body(SdkBody::from(
+                        serde_json::json!({
+                            "Type": "User",
+                            "message": "The statement id provided already exists"
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            ),
+        ]);
+
+        let config = LambdaConfig::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let client = LambdaClient::from_conf(config);
+
+        let permissions = [
+            InvokePermission::from_str("arn:aws:events:us-east-1:123456789012:rule/orders")
+                .unwrap(),
+            InvokePermission::from_str("210987654321").unwrap(),
+        ];
+        add_invoke_permissions("test-function", &Some("live".into()), &permissions, &client)
+            .await
+            .unwrap();
+
+        http_client.assert_requests_match(&[]);
+    }
+
+    #[tokio::test]
+    async fn test_update_tags() {
+        let arn = "arn:aws:lambda:us-east-1:123456789012:function:test-function";
+        let tags_uri = "https://lambda.us-east-1.amazonaws.com/2017-03-31/tags/arn%3Aaws%3Alambda%3Aus-east-1%3A123456789012%3Afunction%3Atest-function";
+
+        let http_client = StaticReplayClient::new(vec![
+            ReplayEvent::new(
+                Request::builder()
+                    .uri(tags_uri)
+                    .method("POST")
+                    .body(SdkBody::from(
+                        serde_json::json!({ "Tags": { "env": "production" } }).to_string(),
+                    ))
+                    .unwrap(),
+                Response::builder()
+                    .status(204)
+                    .body(SdkBody::empty())
+                    .unwrap(),
+            ),
+            ReplayEvent::new(
+                Request::builder()
+                    .uri(format!("{tags_uri}?tagKeys=owner"))
+                    .method("DELETE")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                Response::builder()
+                    .status(204)
+                    .body(SdkBody::empty())
+                    .unwrap(),
+            ),
+        ]);
+
+        let config = LambdaConfig::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let client = LambdaClient::from_conf(config);
+        let progress = Progress::start("deploying function");
+
+        let changes = TagChanges {
+            tag: HashMap::from([("env".to_string(), "production".to_string())]),
+            untag: vec!["owner".to_string()],
+        };
+        update_tags(&client, changes, arn, &progress).await.unwrap();
+
+        http_client.assert_requests_match(&[]);
+    }
+
     #[tokio::test]
     async fn test_set_log_retention() {
         // Setup mock responses for creating log group and setting retention
@@ -1299,7 +2315,7 @@ mod tests {
             .http_client(http_client.clone())
             .build();
 
-        let result = set_log_retention(&sdk_config, 14, "test-function").await;
+        let result = set_log_retention(&sdk_config, 14, "test-function", None).await;
 
         assert!(result.is_ok());
         http_client.assert_requests_match(&[]);
@@ -1361,7 +2377,65 @@ mod tests {
             .http_client(http_client.clone())
             .build();
 
-        let result = set_log_retention(&sdk_config, 14, "test-function").await;
+        let result = set_log_retention(&sdk_config, 14, "test-function", None).await;
+
+        assert!(result.is_ok());
+        http_client.assert_requests_match(&[]);
+    }
+
+    #[tokio::test]
+    async fn test_set_log_retention_with_tags() {
+        let create_log_group_request = Request::builder()
+            .uri("https://logs.us-east-1.amazonaws.com/")
+            .method("POST")
+            .header("x-amz-target", "Logs_20140328.CreateLogGroup")
+            .body(SdkBody::from(
+                serde_json::json!({
+                    "logGroupName": "/aws/lambda/test-function",
+                    "tags": {
+                        "team": "lambda"
+                    }
+                })
+                .to_string(),
+            ))
+            .unwrap();
+
+        let create_log_group_response = Response::builder()
+            .status(200)
+            .body(SdkBody::from("{}"))
+            .unwrap();
+
+        let put_retention_request = Request::builder()
+            .uri("https://logs.us-east-1.amazonaws.com/")
+            .method("POST")
+            .header("x-amz-target", "Logs_20140328.PutRetentionPolicy")
+            .body(SdkBody::from(
+                serde_json::json!({
+                    "logGroupName": "/aws/lambda/test-function",
+                    "retentionInDays": 14
+                })
+                .to_string(),
+            ))
+            .unwrap();
+
+        let put_retention_response = Response::builder()
+            .status(200)
+            .body(SdkBody::from("{}"))
+            .unwrap();
+
+        let http_client = StaticReplayClient::new(vec![
+            ReplayEvent::new(create_log_group_request, create_log_group_response),
+            ReplayEvent::new(put_retention_request, put_retention_response),
+        ]);
+
+        let sdk_config = SdkConfig::builder()
+            .credentials_provider(SharedCredentialsProvider::new(Credentials::for_tests()))
+            .region(Region::new("us-east-1"))
+            .http_client(http_client.clone())
+            .build();
+
+        let tags = HashMap::from([("team".to_string(), "lambda".to_string())]);
+        let result = set_log_retention(&sdk_config, 14, "test-function", Some(tags)).await;
 
         assert!(result.is_ok());
         http_client.assert_requests_match(&[]);
@@ -0,0 +1,146 @@
+use miette::Result;
+use std::{path::Path, process::Command};
+
+const GIT_COMMIT_TAG: &str = "git:commit";
+const GIT_DIRTY_TAG: &str = "git:dirty";
+
+/// Maximum number of characters that Lambda accepts in a description.
+const MAX_DESCRIPTION_LEN: usize = 256;
+
+/// Revision of the git repository where the project is.
+#[derive(Debug, PartialEq)]
+pub(crate) struct GitRevision {
+    pub(crate) commit: String,
+    pub(crate) dirty: bool,
+}
+
+impl GitRevision {
+    /// Read the revision of the repository that contains `dir`.
+    /// It returns None if `dir` is not in a git repository,
+    /// or git is not installed.
+    pub(crate) fn read(dir: &Path) -> Option<GitRevision> {
+        let commit = git(dir, &["rev-parse", "HEAD"])?;
+        let status = git(dir, &["status", "--porcelain"])?;
+
+        Some(GitRevision {
+            commit,
+            dirty: !status.is_empty(),
+        })
+    }
+
+    /// Tags to trace a function back to the revision it was deployed from.
+    pub(crate) fn tags(&self) -> Vec<String> {
+        vec![
+            format!("{GIT_COMMIT_TAG}={}", self.commit),
+            format!("{GIT_DIRTY_TAG}={}", self.dirty),
+        ]
+    }
+}
+
+/// Read the revision of the repository where the project is,
+/// and fail if there is no repository.
+pub(crate) fn project_revision(manifest_path: &Path) -> Result<GitRevision> {
+    let dir = project_dir(manifest_path);
+    GitRevision::read(dir).ok_or_else(|| {
+        miette::miette!(
+            help = "the flag --git-tags requires the project to be in a git repository, and git to be installed",
+            "failed to read the git revision of the project in {}",
+            dir.display()
+        )
+    })
+}
+
+/// Read the subject of the latest commit in the repository where the project is,
+/// to describe the function versions. It fails if there is no repository.
+pub(crate) fn commit_description(manifest_path: &Path) -> Result<String> {
+    let dir = project_dir(manifest_path);
+    let subject = git(dir, &["log", "-1", "--format=%s"]).ok_or_else(|| {
+        miette::miette!(
+            help = "the flag --git-description requires the project to be in a git repository with commits, and git to be installed",
+            "failed to read the latest commit of the project in {}",
+            dir.display()
+        )
+    })?;
+    Ok(truncate_description(&subject))
+}
+
+fn truncate_description(subject: &str) -> String {
+    if subject.chars().count() <= MAX_DESCRIPTION_LEN {
+        return subject.to_string();
+    }
+
+    let mut description = subject
+        .chars()
+        .take(MAX_DESCRIPTION_LEN - 1)
+        .collect::<String>();
+    description.push('…');
+    description
+}
+
+pub(crate) fn project_dir(manifest_path: &Path) -> &Path {
+    match manifest_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revision_tags() {
+        let revision = GitRevision {
+            commit: "a1b2c3d".into(),
+            dirty: true,
+        };
+        assert_eq!(
+            vec![
+                "git:commit=a1b2c3d".to_string(),
+                "git:dirty=true".to_string()
+            ],
+            revision.tags()
+        );
+    }
+
+    #[test]
+    fn test_project_dir() {
+        assert_eq!(Path::new("."), project_dir(Path::new("Cargo.toml")));
+        assert_eq!(
+            Path::new("crates/api"),
+            project_dir(Path::new("crates/api/Cargo.toml"))
+        );
+    }
+
+    #[test]
+    fn test_truncate_description() {
+        assert_eq!(
+            "Fix the orders API",
+            truncate_description("Fix the orders API")
+        );
+
+        let description = truncate_description(&"á".repeat(300));
+        assert_eq!(MAX_DESCRIPTION_LEN, description.chars().count());
+        assert!(description.ends_with('…'));
+    }
+
+    #[test]
+    fn test_read_outside_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(None, GitRevision::read(dir.path()));
+    }
+}
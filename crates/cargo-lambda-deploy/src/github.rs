@@ -0,0 +1,234 @@
+use cargo_lambda_build::BinaryArchive;
+use cargo_lambda_interactive::progress::{HumanBytes, HumanDuration};
+use miette::{IntoDiagnostic, Report, Result, WrapErr};
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::warn;
+
+use crate::DeployResult;
+
+/// Environment variable that GitHub Actions sets to `true` in its runners.
+const GITHUB_ACTIONS: &str = "GITHUB_ACTIONS";
+
+/// Environment variable with the file where the steps write their summary.
+const GITHUB_STEP_SUMMARY: &str = "GITHUB_STEP_SUMMARY";
+
+/// Summary of a deploy for the page of a GitHub Actions job.
+#[derive(Debug)]
+pub(crate) struct StepSummary {
+    name: String,
+    kind: &'static str,
+    arn: String,
+    version: Option<String>,
+    alias: Option<String>,
+    size: Option<u64>,
+    duration: Duration,
+}
+
+impl StepSummary {
+    /// Create the summary of a deploy. Dry runs and plans don't deploy anything,
+    /// so they don't have a summary.
+    pub(crate) fn new(
+        name: &str,
+        result: &DeployResult,
+        archive: &BinaryArchive,
+        duration: Duration,
+    ) -> Option<StepSummary> {
+        let (kind, arn, version, alias) = match result {
+            DeployResult::Function(output) => (
+                "function",
+                output.function_arn.clone(),
+                Some(output.version.clone()),
+                output.alias.clone(),
+            ),
+            DeployResult::Extension(output) => {
+                ("extension", output.extension_arn.clone(), None, None)
+            }
+            DeployResult::Dry(_) | DeployResult::Plan(_) => return None,
+        };
+
+        Some(StepSummary {
+            name: name.to_string(),
+            kind,
+            arn,
+            version,
+            alias,
+            size: archive.path.metadata().ok().map(|m| m.len()),
+            duration,
+        })
+    }
+
+    fn markdown(&self) -> String {
+        let mut rows = vec![
+            (capitalize(self.kind), format!("`{}`", self.name)),
+            ("ARN".to_string(), format!("`{}`", self.arn)),
+        ];
+        rows.extend(
+            self.version
+                .as_ref()
+                .map(|v| ("Version".to_string(), format!("`{v}`"))),
+        );
+        rows.extend(
+            self.alias
+                .as_ref()
+                .map(|a| ("Alias".to_string(), format!("`{a}`"))),
+        );
+        rows.extend(
+            self.size
+                .map(|s| ("Package size".to_string(), HumanBytes(s).to_string())),
+        );
+        rows.push((
+            "Duration".to_string(),
+            HumanDuration(self.duration).to_string(),
+        ));
+
+        let mut markdown = format!(
+            "### ✅ {} `{}` deployed\n\n| | |\n| --- | --- |\n",
+            capitalize(self.kind),
+            self.name
+        );
+        for (key, value) in rows {
+            markdown.push_str(&format!("| {key} | {value} |\n"));
+        }
+        markdown.push('\n');
+        markdown
+    }
+}
+
+fn running_in_github_actions() -> bool {
+    std::env::var(GITHUB_ACTIONS).is_ok_and(|v| v == "true")
+}
+
+/// File where GitHub Actions expects the summary of the current step,
+/// None when the deploy doesn't run in GitHub Actions.
+fn step_summary_path() -> Option<PathBuf> {
+    if !running_in_github_actions() {
+        return None;
+    }
+    std::env::var_os(GITHUB_STEP_SUMMARY).map(PathBuf::from)
+}
+
+/// Write the summary of the deploy in the job's page, when the deploy runs in GitHub Actions.
+/// Failing to write the summary doesn't fail the deploy.
+pub(crate) fn write_step_summary(summary: &StepSummary) {
+    let Some(path) = step_summary_path() else {
+        return;
+    };
+
+    if let Err(err) = append_step_summary(&path, summary) {
+        warn!(?err, "failed to write the GitHub Actions step summary");
+    }
+}
+
+fn append_step_summary(path: &Path, summary: &StepSummary) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to open step summary file `{path:?}`"))?;
+
+    file.write_all(summary.markdown().as_bytes())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write step summary file `{path:?}`"))
+}
+
+/// Print an error annotation, so GitHub Actions shows why the deploy
+/// failed in the job's page, when the deploy runs in GitHub Actions.
+pub(crate) fn annotate_error(err: &Report) {
+    if running_in_github_actions() {
+        println!("{}", error_annotation(err));
+    }
+}
+
+/// Format an error as a workflow command. Messages must escape
+/// the characters that GitHub uses to parse the commands.
+fn error_annotation(err: &Report) -> String {
+    let message = err
+        .chain()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join(": ")
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    format!("::error title=cargo lambda deploy failed::{message}")
+}
+
+fn capitalize(value: &str) -> String {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function_summary() -> StepSummary {
+        StepSummary {
+            name: "basic-lambda".into(),
+            kind: "function",
+            arn: "arn:aws:lambda:us-east-1:123456789012:function:basic-lambda".into(),
+            version: Some("3".into()),
+            alias: Some("live".into()),
+            size: Some(4 * 1024 * 1024),
+            duration: Duration::from_secs(12),
+        }
+    }
+
+    #[test]
+    fn test_step_summary_markdown() {
+        let markdown = function_summary().markdown();
+        assert_eq!(
+            "### ✅ Function `basic-lambda` deployed\n\n\
+            | | |\n\
+            | --- | --- |\n\
+            | Function | `basic-lambda` |\n\
+            | ARN | `arn:aws:lambda:us-east-1:123456789012:function:basic-lambda` |\n\
+            | Version | `3` |\n\
+            | Alias | `live` |\n\
+            | Package size | 4.00 MiB |\n\
+            | Duration | 12 seconds |\n\n",
+            markdown
+        );
+
+        let summary = StepSummary {
+            kind: "extension",
+            version: None,
+            alias: None,
+            size: None,
+            ..function_summary()
+        };
+        let markdown = summary.markdown();
+        assert!(markdown.starts_with("### ✅ Extension `basic-lambda` deployed"));
+        assert!(!markdown.contains("Version"));
+        assert!(!markdown.contains("Package size"));
+    }
+
+    #[test]
+    fn test_append_step_summary() {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), "# Build\n\n").unwrap();
+
+        append_step_summary(tmp.path(), &function_summary()).unwrap();
+
+        let content = std::fs::read_to_string(tmp.path()).unwrap();
+        assert!(content.starts_with("# Build\n\n### ✅ Function `basic-lambda` deployed"));
+    }
+
+    #[test]
+    fn test_error_annotation() {
+        let err = miette::miette!("access denied\n100% sure").wrap_err("failed to create function");
+        assert_eq!(
+            "::error title=cargo lambda deploy failed::failed to create function: access denied%0A100%25 sure",
+            error_annotation(&err)
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use aws_sdk_sts::Client as StsClient;
+use cargo_lambda_metadata::cargo::deploy::Deploy;
+use cargo_lambda_remote::aws_sdk_config::SdkConfig;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use tracing::warn;
+
+const OVERRIDE_HELP: &str =
+    "check your AWS profile and region, or use --i-know-what-im-doing to deploy anyway";
+
+/// Check that the deploy goes to one of the accounts and regions
+/// allowed in the configuration, before making any change in AWS.
+pub(crate) async fn check_account_and_region(
+    config: &Deploy,
+    sdk_config: &SdkConfig,
+) -> Result<()> {
+    let allowed_accounts = config.allowed_accounts.as_deref().unwrap_or_default();
+    let allowed_regions = config.allowed_regions.as_deref().unwrap_or_default();
+    if allowed_accounts.is_empty() && allowed_regions.is_empty() {
+        return Ok(());
+    }
+
+    let account = if allowed_accounts.is_empty() {
+        None
+    } else {
+        caller_account(sdk_config).await?
+    };
+    let region = sdk_config.region().map(|r| r.as_ref());
+
+    let Err(message) = validate(
+        allowed_accounts,
+        allowed_regions,
+        account.as_deref(),
+        region,
+    ) else {
+        return Ok(());
+    };
+
+    if config.i_know_what_im_doing {
+        warn!("{message}, deploying anyway because of --i-know-what-im-doing");
+        return Ok(());
+    }
+
+    Err(miette::miette!(help = OVERRIDE_HELP, "{message}"))
+}
+
+async fn caller_account(sdk_config: &SdkConfig) -> Result<Option<String>> {
+    let identity = StsClient::new(sdk_config)
+        .get_caller_identity()
+        .send()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to get the AWS account to check the allowed accounts")?;
+    Ok(identity.account)
+}
+
+fn validate(
+    allowed_accounts: &[String],
+    allowed_regions: &[String],
+    account: Option<&str>,
+    region: Option<&str>,
+) -> Result<(), String> {
+    if !allowed_accounts.is_empty() {
+        match account {
+            Some(account) if allowed_accounts.iter().any(|a| a == account) => {}
+            Some(account) => {
+                return Err(format!(
+                    "the AWS account `{account}` is not in the list of allowed accounts: {}",
+                    allowed_accounts.join(", ")
+                ));
+            }
+            None => return Err("unable to find the AWS account for the deploy".into()),
+        }
+    }
+
+    if !allowed_regions.is_empty() {
+        match region {
+            Some(region) if allowed_regions.iter().any(|r| r == region) => {}
+            Some(region) => {
+                return Err(format!(
+                    "the AWS region `{region}` is not in the list of allowed regions: {}",
+                    allowed_regions.join(", ")
+                ));
+            }
+            None => return Err("unable to find the AWS region for the deploy".into()),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let accounts = vec!["123456789012".to_string()];
+        let regions = vec!["us-east-1".to_string(), "eu-west-1".to_string()];
+
+        validate(&[], &[], None, None).unwrap();
+        validate(&accounts, &regions, Some("123456789012"), Some("eu-west-1")).unwrap();
+        validate(&[], &regions, None, Some("us-east-1")).unwrap();
+
+        assert_eq!(
+            Err(
+                "the AWS account `210987654321` is not in the list of allowed accounts: 123456789012"
+                    .to_string()
+            ),
+            validate(&accounts, &regions, Some("210987654321"), Some("us-east-1"))
+        );
+        assert_eq!(
+            Err(
+                "the AWS region `ap-south-1` is not in the list of allowed regions: us-east-1, eu-west-1"
+                    .to_string()
+            ),
+            validate(&accounts, &regions, Some("123456789012"), Some("ap-south-1"))
+        );
+        assert_eq!(
+            Err("unable to find the AWS region for the deploy".to_string()),
+            validate(&[], &regions, None, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_without_allowed_lists() {
+        // No requests are made when there are no allowed lists
+        let sdk_config = SdkConfig::builder().build();
+        check_account_and_region(&Deploy::default(), &sdk_config)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_region_override() {
+        let sdk_config = SdkConfig::builder()
+            .region(aws_sdk_sts::config::Region::new("ap-south-1"))
+            .build();
+
+        let mut config = Deploy::default();
+        config.allowed_regions = Some(vec!["us-east-1".to_string()]);
+        let err = check_account_and_region(&config, &sdk_config)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            "the AWS region `ap-south-1` is not in the list of allowed regions: us-east-1",
+            err.to_string()
+        );
+
+        config.i_know_what_im_doing = true;
+        check_account_and_region(&config, &sdk_config)
+            .await
+            .unwrap();
+    }
+}
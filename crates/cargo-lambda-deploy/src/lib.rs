@@ -1,20 +1,47 @@
 use aws_smithy_types::retry::{RetryConfig, RetryMode};
-use cargo_lambda_build::{BinaryArchive, BinaryData, create_binary_archive, zip_binary};
+use cargo_lambda_build::{
+    BinaryArchive, BinaryData, BinaryModifiedAt, SizeReport, check_size_limits,
+    create_binary_archive, zip_binary,
+};
 use cargo_lambda_interactive::progress::Progress;
 use cargo_lambda_metadata::cargo::{
     CargoMetadata,
-    deploy::{Deploy, OutputFormat},
-    main_binary_from_metadata,
+    deploy::{Deploy, Instance, OutputFormat, validate_runtime},
 };
+use chrono::Utc;
 use miette::{IntoDiagnostic, Result, WrapErr};
 use serde::Serialize;
 use serde_json::ser::to_string_pretty;
-use std::time::Duration;
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
 
+mod alarms;
+mod api_calls;
+mod audit;
+mod build_info;
+mod canary;
+mod checksum;
+mod custom_domain;
+mod debuginfo;
 mod dry;
+mod environment;
 mod extensions;
 mod functions;
+mod git;
+mod github;
+mod guardrails;
+mod plan;
+mod profiles;
+mod query_api;
+mod replacements;
 mod roles;
+mod smoke_test;
+mod sso;
+mod stack_outputs;
+mod triggers;
+mod upload;
 
 #[derive(Serialize)]
 #[serde(untagged)]
@@ -23,6 +50,7 @@ enum DeployResult {
     Extension(extensions::DeployOutput),
     Function(functions::DeployOutput),
     Dry(dry::DeployOutput),
+    Plan(plan::PlanOutput),
 }
 
 impl std::fmt::Display for DeployResult {
@@ -31,6 +59,7 @@ impl std::fmt::Display for DeployResult {
             DeployResult::Extension(o) => o.fmt(f),
             DeployResult::Function(o) => o.fmt(f),
             DeployResult::Dry(o) => o.fmt(f),
+            DeployResult::Plan(o) => o.fmt(f),
         }
     }
 }
@@ -39,14 +68,88 @@ impl std::fmt::Display for DeployResult {
 pub async fn run(config: &Deploy, metadata: &CargoMetadata) -> Result<()> {
     tracing::trace!("deploying project");
 
+    let result = match &config.instances {
+        Some(instances) => deploy_instances(config, instances, metadata).await,
+        None => deploy_project(config, metadata).await,
+    };
+    if let Err(err) = &result {
+        github::annotate_error(err);
+    }
+    result
+}
+
+/// Deploy the same binary as several functions, one for each instance, in order.
+/// The deploy stops at the first instance that fails to deploy.
+async fn deploy_instances(
+    config: &Deploy,
+    instances: &[Instance],
+    metadata: &CargoMetadata,
+) -> Result<()> {
+    let config = instances_base_config(config, metadata)?;
+
+    for instance in instances {
+        deploy_project(&config.instance_config(instance), metadata)
+            .await
+            .wrap_err_with(|| format!("failed to deploy the instance `{}`", instance.name))?;
+    }
+    Ok(())
+}
+
+/// The instances replace the name of the function, so the binary to deploy
+/// is selected before the name changes, instead of using the instance name.
+fn instances_base_config(config: &Deploy, metadata: &CargoMetadata) -> Result<Deploy> {
+    let mut config = config.clone();
+    if config.binary_path.is_none() && config.binary_name.is_none() {
+        config.binary_name = Some(config.binary_selection().single(metadata)?);
+    }
+    Ok(config)
+}
+
+async fn deploy_project(config: &Deploy, metadata: &CargoMetadata) -> Result<()> {
+    let started_at = Instant::now();
+
     if config.function_config.enable_function_url && config.function_config.disable_function_url {
         return Err(miette::miette!(
             "invalid options: --enable-function-url and --disable-function-url cannot be set together"
         ));
     }
 
+    if config.custom_domain.is_some() && !config.function_config.enable_function_url {
+        return Err(miette::miette!(
+            "invalid options: --custom-domain requires --enable-function-url"
+        ));
+    }
+
+    if !config.extension {
+        validate_runtime(
+            &config.function_config.runtime(),
+            config.function_config.handler.as_deref(),
+        )?;
+    }
+
+    let mut config = config.clone();
+    profiles::select_profile_and_region(&mut config).await?;
+
+    if config.git_tags {
+        let revision = git::project_revision(&config.manifest_path())?;
+        config.append_tags(revision.tags());
+    }
+
+    if config.git_description && config.function_config.description.is_none() {
+        config.function_config.description =
+            Some(git::commit_description(&config.manifest_path())?);
+    }
+
+    let audit_log = config.audit_log()?;
+    let outputs = config.stack_outputs()?;
+    let deploy_plan = config
+        .apply
+        .as_deref()
+        .map(plan::DeployPlan::read)
+        .transpose()?;
+
     let progress = Progress::start("loading binary data");
-    let (name, archive) = match load_archive(config, metadata) {
+    let (name, archive) = match load_archive(&config, metadata) {
         Ok(arc) => arc,
         Err(err) => {
             progress.finish_and_clear();
@@ -54,27 +157,153 @@ pub async fn run(config: &Deploy, metadata: &CargoMetadata) -> Result<()> {
         }
     };
 
+    if let Err(err) = check_archive_size(&config, &name, &archive) {
+        progress.finish_and_clear();
+        return Err(err);
+    }
+
+    if !config.extension {
+        let info = build_info::load(&config, metadata, &name);
+        config.append_tags(build_info::tags(&info));
+        for (key, value) in info {
+            config.base_env.entry(key).or_insert(value);
+        }
+    }
+
+    if !config.extension {
+        functions::check_runtime_compatibility(&config.function_config.runtime(), &archive);
+    }
+
+    let debug_artifact = match debuginfo::find_debug_artifact(&config, &name) {
+        Ok(path) => path,
+        Err(err) => {
+            progress.finish_and_clear();
+            return Err(err);
+        }
+    };
+
     let retry = RetryConfig::standard()
         .with_retry_mode(RetryMode::Adaptive)
         .with_max_attempts(3)
         .with_initial_backoff(Duration::from_secs(5));
 
-    let sdk_config = config.remote_config.sdk_config(Some(retry)).await;
+    let mut sdk_config = config.remote_config.sdk_config(Some(retry.clone())).await;
+
+    if !config.dry {
+        sdk_config = match sso::ensure_session(&config, sdk_config, retry, &progress).await {
+            Ok(sdk_config) => sdk_config,
+            Err(err) => {
+                progress.finish_and_clear();
+                return Err(err);
+            }
+        };
+    }
+
+    if !config.dry && !outputs.is_empty() {
+        progress.set_message("fetching stack outputs");
+        match stack_outputs::resolve(&sdk_config, &outputs).await {
+            Ok(env) => config.base_env.extend(env),
+            Err(err) => {
+                progress.finish_and_clear();
+                return Err(err);
+            }
+        }
+    }
+    let config = &config;
+
+    if let Err(err) = environment::check(config) {
+        progress.finish_and_clear();
+        return Err(err);
+    }
+
+    if !config.dry {
+        progress.set_message("checking AWS account and region");
+        if let Err(err) = guardrails::check_account_and_region(config, &sdk_config).await {
+            progress.finish_and_clear();
+            return Err(err);
+        }
+    }
+
+    if let (Some(path), Some(deploy_plan)) = (&config.apply, &deploy_plan) {
+        progress.set_message("checking the deploy against the plan");
+        let current = plan::DeployPlan::new(
+            config,
+            &name,
+            &archive,
+            debug_artifact.as_deref(),
+            &sdk_config,
+        )
+        .await;
+        if let Err(err) = current.and_then(|current| deploy_plan.verify(path, &current)) {
+            progress.finish_and_clear();
+            return Err(err);
+        }
+    }
+
+    let preconditions = config
+        .apply
+        .as_deref()
+        .zip(deploy_plan.as_ref())
+        .map(|(path, deploy_plan)| deploy_plan.preconditions(path));
 
     let result = if config.dry {
-        dry::DeployOutput::new(config, &name, &archive).map(DeployResult::Dry)
+        dry::DeployOutput::new(config, &name, &archive, debug_artifact.as_deref())
+            .map(DeployResult::Dry)
+    } else if let Some(path) = &config.plan_out {
+        progress.set_message("writing deploy plan");
+        plan::DeployPlan::new(
+            config,
+            &name,
+            &archive,
+            debug_artifact.as_deref(),
+            &sdk_config,
+        )
+        .await
+        .and_then(|deploy_plan| deploy_plan.write(path))
+        .map(DeployResult::Plan)
     } else if config.extension {
         extensions::deploy(config, &name, &sdk_config, &archive, &progress)
             .await
             .map(DeployResult::Extension)
     } else {
-        functions::deploy(config, &name, &sdk_config, &archive, &progress)
-            .await
-            .map(DeployResult::Function)
+        functions::deploy(
+            config,
+            &name,
+            &sdk_config,
+            &archive,
+            debug_artifact.as_deref(),
+            preconditions.as_ref(),
+            &progress,
+        )
+        .await
+        .map(DeployResult::Function)
+    };
+
+    let output = match result {
+        Ok(output) => output,
+        Err(err) => {
+            progress.finish_and_clear();
+            return Err(err);
+        }
     };
 
+    if let Some(audit_log) = &audit_log {
+        if let Some(record) = audit::AuditRecord::new(&name, &output, &archive, Utc::now())? {
+            progress.set_message("writing deploy record to the audit log");
+            let result = audit::record(config, audit_log, &sdk_config, record).await;
+            if let Err(err) = result {
+                progress.finish_and_clear();
+                return Err(err);
+            }
+        }
+    }
+
     progress.finish_and_clear();
-    let output = result?;
+
+    if let Some(summary) = github::StepSummary::new(&name, &output, &archive, started_at.elapsed())
+    {
+        github::write_step_summary(&summary);
+    }
 
     match &config.output_format() {
         OutputFormat::Text => println!("{output}"),
@@ -90,8 +319,36 @@ pub async fn run(config: &Deploy, metadata: &CargoMetadata) -> Result<()> {
 }
 
 fn load_archive(config: &Deploy, metadata: &CargoMetadata) -> Result<(String, BinaryArchive)> {
+    if let Some(architectures) = extensions::multiple_architectures(config) {
+        let name = deploy_name(config, metadata)?;
+        let archive = extensions::load_multi_arch_archive(config, metadata, &name, architectures)?;
+        return Ok((name, archive));
+    }
+
+    let (name, archive) = load_single_archive(config, metadata)?;
+    if config.extension {
+        extensions::check_architecture(config, &archive)?;
+    }
+    Ok((name, archive))
+}
+
+fn load_single_archive(
+    config: &Deploy,
+    metadata: &CargoMetadata,
+) -> Result<(String, BinaryArchive)> {
     match &config.binary_path {
         Some(bp) if bp.is_dir() => Err(miette::miette!("invalid file {:?}", bp)),
+        Some(bp) if config.function_config.handler.is_some() => {
+            let name = match &config.name {
+                Some(name) => name.clone(),
+                None => bp
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .map(String::from)
+                    .ok_or_else(|| miette::miette!("invalid binary path {:?}", bp))?,
+            };
+            Ok((name, load_prepared_archive(bp)?))
+        }
         Some(bp) => {
             let name = match &config.name {
                 Some(name) => name.clone(),
@@ -107,15 +364,19 @@ fn load_archive(config: &Deploy, metadata: &CargoMetadata) -> Result<(String, Bi
                 .ok_or_else(|| miette::miette!("invalid binary path {:?}", bp))?;
 
             let data = BinaryData::new(&name, config.extension, config.internal);
-            let arc = zip_binary(bp, destination, &data, config.include.clone())?;
+            let arc = zip_binary(
+                bp,
+                destination,
+                &data,
+                config.include.clone(),
+                config.bootstrap_script.as_deref(),
+                config.incremental_zip,
+                config.fast_package,
+            )?;
             Ok((name, arc))
         }
         None => {
-            let name = match (&config.name, &config.binary_name) {
-                (Some(name), _) => name.clone(),
-                (None, Some(bn)) => bn.clone(),
-                (None, None) => main_binary_from_metadata(metadata)?,
-            };
+            let name = deploy_name(config, metadata)?;
             let binary_name = binary_name_or_default(config, &name);
             let data = BinaryData::new(&binary_name, config.extension, config.internal);
 
@@ -124,12 +385,56 @@ fn load_archive(config: &Deploy, metadata: &CargoMetadata) -> Result<(String, Bi
                 &config.lambda_dir,
                 &data,
                 config.include.clone(),
+                config.bootstrap_script.as_deref(),
+                config.incremental_zip,
+                config.fast_package,
             )?;
             Ok((name, arc))
         }
     }
 }
 
+/// Load a zip file prepared for a runtime other than `provided`, like a Node.js or Python package.
+/// The zip file is deployed as it is, without looking for a `bootstrap` binary inside it,
+/// so the function is deployed for the `x86_64` architecture.
+fn load_prepared_archive(path: &Path) -> Result<BinaryArchive> {
+    if path.extension().is_none_or(|ext| ext != "zip") {
+        return Err(miette::miette!(
+            "invalid file {:?}, functions with a handler are deployed from zip files",
+            path
+        ));
+    }
+
+    let modified_at = path
+        .metadata()
+        .and_then(|m| m.modified())
+        .map(BinaryModifiedAt::from)
+        .unwrap_or_else(|_| BinaryModifiedAt::now());
+
+    let archive = BinaryArchive::new(path.to_path_buf(), "x86_64".into(), modified_at);
+    archive
+        .list()
+        .wrap_err_with(|| format!("invalid zip file {path:?}"))?;
+    Ok(archive)
+}
+
+/// Check the size of the zip file before uploading it. The zip file is only checked
+/// against Lambda's limit for direct uploads when it's not uploaded to S3.
+fn check_archive_size(config: &Deploy, name: &str, archive: &BinaryArchive) -> Result<()> {
+    let limits = config.size_limits.limits(config.s3_bucket.is_none())?;
+    let report = SizeReport::from_archive(name, &archive.path)?;
+    check_size_limits(report.limit_violations(&limits), &limits)
+}
+
+/// Name to deploy the function or extension with, when it's not a binary path.
+fn deploy_name(config: &Deploy, metadata: &CargoMetadata) -> Result<String> {
+    match (&config.name, &config.binary_name) {
+        (Some(name), _) => Ok(name.clone()),
+        (None, Some(bn)) => Ok(bn.clone()),
+        (None, None) => Ok(config.binary_selection().single(metadata)?),
+    }
+}
+
 pub(crate) fn binary_name_or_default(config: &Deploy, name: &str) -> String {
     config
         .binary_name
@@ -140,7 +445,6 @@ pub(crate) fn binary_name_or_default(config: &Deploy, name: &str) -> String {
 #[cfg(test)]
 mod tests {
     use assertables::assert_contains;
-    use std::path::PathBuf;
 
     use cargo_lambda_metadata::cargo::load_metadata;
 
@@ -148,8 +452,13 @@ mod tests {
 
     #[test]
     fn test_load_archive_from_binary_path() {
+        // The archive is written next to the binary, keep it out of the fixtures.
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("binary-x86-64");
+        std::fs::copy("../../tests/binaries/binary-x86-64", &binary).unwrap();
+
         let mut config = Deploy::default();
-        config.binary_path = Some(PathBuf::from("../../tests/binaries/binary-x86-64"));
+        config.binary_path = Some(binary);
         config.include = Some(vec!["src".into()]);
 
         let metadata = load_metadata("../../tests/fixtures/examples-package/Cargo.toml").unwrap();
@@ -164,4 +473,53 @@ mod tests {
         assert_contains!(files, &"src/lib.rs".to_string());
         assert_contains!(files, &"src/roles.rs".to_string());
     }
+
+    #[test]
+    fn test_load_archive_with_handler() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip = dir.path().join("orders-api.zip");
+        std::fs::copy("../../tests/binaries/bootstrap.zip", &zip).unwrap();
+
+        let mut config = Deploy::default();
+        config.binary_path = Some(zip);
+        config.function_config.runtime = Some("nodejs20.x".into());
+        config.function_config.handler = Some("index.handler".into());
+
+        let metadata = load_metadata("../../tests/fixtures/examples-package/Cargo.toml").unwrap();
+        let (name, archive) = load_archive(&config, &metadata).unwrap();
+        assert_eq!(name, "orders-api");
+        assert_eq!(archive.architecture, "x86_64");
+        assert_eq!(archive.glibc_version, None);
+
+        let script = dir.path().join("index.mjs");
+        std::fs::write(&script, "export const handler = async () => {};").unwrap();
+        config.binary_path = Some(script);
+        let Err(err) = load_archive(&config, &metadata) else {
+            panic!("expected an error loading a file that is not a zip file");
+        };
+        assert_contains!(
+            err.to_string(),
+            "functions with a handler are deployed from zip files"
+        );
+    }
+
+    #[test]
+    fn test_instances_base_config() {
+        let metadata =
+            load_metadata("../../tests/fixtures/single-binary-package/Cargo.toml").unwrap();
+
+        let config = instances_base_config(&Deploy::default(), &metadata).unwrap();
+        assert_eq!(Some("basic-lambda".to_string()), config.binary_name);
+
+        let instance = Instance {
+            name: "orders-acme".into(),
+            env: Vec::new(),
+        };
+        let config = config.instance_config(&instance);
+        assert_eq!("orders-acme", deploy_name(&config, &metadata).unwrap());
+        assert_eq!(
+            "basic-lambda",
+            binary_name_or_default(&config, "orders-acme")
+        );
+    }
 }
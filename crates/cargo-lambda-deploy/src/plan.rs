@@ -0,0 +1,481 @@
+use aws_sdk_sts::Client as StsClient;
+use base64::prelude::*;
+use cargo_lambda_build::BinaryArchive;
+use cargo_lambda_metadata::cargo::deploy::Deploy;
+use cargo_lambda_remote::{
+    aws_sdk_config::SdkConfig,
+    aws_sdk_lambda::{
+        Client as LambdaClient,
+        error::{ProvideErrorMetadata, SdkError},
+    },
+};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    api_calls::{self, ApiCall},
+    checksum::code_sha256,
+    dry::DeployKind,
+    functions::{alias_doesnt_exist_error, function_doesnt_exist_error},
+};
+
+/// Version of the plan format. Plans written with a different format can't be applied.
+const PLAN_VERSION: u32 = 1;
+
+/// Deploy plan that `--plan-out` writes, and `--apply` deploys.
+/// It only includes the hashes of the code and the environment variables,
+/// so the plan can be reviewed and stored without leaking secrets.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct DeployPlan {
+    version: u32,
+    kind: DeployKind,
+    name: String,
+    account: Option<String>,
+    region: Option<String>,
+    code_sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    environment_sha256: Option<String>,
+    remote_state: RemoteState,
+    api_calls: Vec<ApiCall>,
+}
+
+/// State of the function in AWS when the plan was written.
+/// Lambda changes the revisions every time the function or the alias change.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+struct RemoteState {
+    /// Revision of the function, None when the function doesn't exist
+    function_revision: Option<String>,
+    /// Revision of the alias, None when the alias doesn't exist
+    alias_revision: Option<String>,
+}
+
+/// Revisions from the plan that the deploy sends with its updates when it applies the plan.
+/// Lambda rejects an update when the function or the alias changed after its revision was read,
+/// so nothing can change them between the check of the plan and the deploy.
+#[derive(Debug)]
+pub(crate) struct Preconditions {
+    path: PathBuf,
+    function_revision: Option<String>,
+    alias_revision: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct PlanOutput {
+    path: PathBuf,
+    plan: DeployPlan,
+}
+
+impl Display for PlanOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "📝 plan for {} `{}` written to {}",
+            self.plan.kind,
+            self.plan.name,
+            self.path.display()
+        )?;
+        writeln!(
+            f,
+            "🔍 {} AWS API calls, use `--show-api-calls` with `--dry` to print them",
+            self.plan.api_calls.len()
+        )?;
+        write!(
+            f,
+            "🚀 deploy it with `--apply {}`, and the same options",
+            self.path.display()
+        )
+    }
+}
+
+impl DeployPlan {
+    /// Create the plan for the deploy. It only calls the AWS APIs that read the state of the function.
+    pub(crate) async fn new(
+        config: &Deploy,
+        name: &str,
+        archive: &BinaryArchive,
+        debug_artifact: Option<&Path>,
+        sdk_config: &SdkConfig,
+    ) -> Result<DeployPlan> {
+        let kind = if config.extension {
+            DeployKind::Extension
+        } else {
+            DeployKind::Function
+        };
+
+        let remote_state = match kind {
+            DeployKind::Function => RemoteState::fetch(config, name, sdk_config).await?,
+            // Extensions always publish a new layer version, nothing else can change.
+            DeployKind::Extension => RemoteState::default(),
+        };
+
+        let account = StsClient::new(sdk_config)
+            .get_caller_identity()
+            .send()
+            .await
+            .into_diagnostic()
+            .wrap_err("failed to get the AWS account to deploy to")?
+            .account;
+
+        Ok(DeployPlan {
+            version: PLAN_VERSION,
+            kind,
+            name: name.to_string(),
+            account,
+            region: sdk_config.region().map(ToString::to_string),
+            code_sha256: code_sha256(&archive.read()?),
+            environment_sha256: environment_sha256(config)?,
+            remote_state,
+            api_calls: api_calls::plan(config, name, archive, debug_artifact)?,
+        })
+    }
+
+    pub(crate) fn read(path: &Path) -> Result<DeployPlan> {
+        let data = std::fs::read(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read the plan {path:?}"))?;
+        let plan: DeployPlan = serde_json::from_slice(&data)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("invalid plan {path:?}"))?;
+
+        if plan.version != PLAN_VERSION {
+            return Err(miette::miette!(
+                help = "write the plan again with `--plan-out`",
+                "the plan {path:?} was written with a different version of Cargo Lambda"
+            ));
+        }
+        Ok(plan)
+    }
+
+    pub(crate) fn write(self, path: &Path) -> Result<PlanOutput> {
+        let mut data = serde_json::to_vec_pretty(&self)
+            .into_diagnostic()
+            .wrap_err("failed to serialize the plan")?;
+        data.push(b'\n');
+
+        std::fs::write(path, data)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to write the plan {path:?}"))?;
+
+        Ok(PlanOutput {
+            path: path.to_path_buf(),
+            plan: self,
+        })
+    }
+
+    /// Check that the deploy is exactly the one in the plan.
+    pub(crate) fn verify(&self, path: &Path, current: &DeployPlan) -> Result<()> {
+        let differences = self.differences(current);
+        if differences.is_empty() {
+            return Ok(());
+        }
+
+        Err(mismatch(path, &differences.join(", ")))
+    }
+
+    /// Revisions to send with the deploy's updates, when the deploy applies the plan in `path`.
+    pub(crate) fn preconditions(&self, path: &Path) -> Preconditions {
+        Preconditions {
+            path: path.to_path_buf(),
+            function_revision: self.remote_state.function_revision.clone(),
+            alias_revision: self.remote_state.alias_revision.clone(),
+        }
+    }
+
+    fn differences(&self, current: &DeployPlan) -> Vec<&'static str> {
+        let mut differences = Vec::new();
+        if self.kind != current.kind
+            || self.name != current.name
+            || self.account != current.account
+            || self.region != current.region
+        {
+            differences.push("the deploy target");
+        }
+        if self.code_sha256 != current.code_sha256 {
+            differences.push("the code");
+        }
+        if self.environment_sha256 != current.environment_sha256 {
+            differences.push("the environment variables");
+        }
+        if self.remote_state != current.remote_state {
+            differences.push("the function in AWS");
+        }
+        if self.api_calls != current.api_calls {
+            differences.push("the API calls");
+        }
+        differences
+    }
+}
+
+impl RemoteState {
+    async fn fetch(config: &Deploy, name: &str, sdk_config: &SdkConfig) -> Result<RemoteState> {
+        let client = LambdaClient::new(sdk_config);
+
+        let function_revision = match client.get_function().function_name(name).send().await {
+            Ok(function) => function.configuration.and_then(|c| c.revision_id),
+            Err(err) if function_doesnt_exist_error(&err) => None,
+            Err(err) => {
+                return Err(err)
+                    .into_diagnostic()
+                    .wrap_err("failed to fetch lambda function");
+            }
+        };
+
+        let alias_revision = match &config.remote_config.alias {
+            Some(alias) if function_revision.is_some() => {
+                match client
+                    .get_alias()
+                    .name(alias)
+                    .function_name(name)
+                    .send()
+                    .await
+                {
+                    Ok(alias) => alias.revision_id,
+                    Err(err) if alias_doesnt_exist_error(&err) => None,
+                    Err(err) => {
+                        return Err(err).into_diagnostic().wrap_err("failed to fetch alias");
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        Ok(RemoteState {
+            function_revision,
+            alias_revision,
+        })
+    }
+}
+
+impl Preconditions {
+    /// Revision to send with the first update of the function. It fails when the function
+    /// was created or deleted after the plan was written, because Lambda can't check that.
+    pub(crate) fn function_revision(&self, exists: bool) -> Result<Option<String>> {
+        self.expect(&self.function_revision, exists)
+    }
+
+    /// Revision to send with the update of the alias. It fails when the alias
+    /// was created or deleted after the plan was written.
+    pub(crate) fn alias_revision(&self, exists: bool) -> Result<Option<String>> {
+        self.expect(&self.alias_revision, exists)
+    }
+
+    fn expect(&self, revision: &Option<String>, exists: bool) -> Result<Option<String>> {
+        if revision.is_some() != exists {
+            return Err(self.mismatch());
+        }
+        Ok(revision.clone())
+    }
+
+    fn mismatch(&self) -> miette::Report {
+        mismatch(&self.path, "the function in AWS")
+    }
+}
+
+/// Report the updates that Lambda rejects because their revision doesn't match anymore
+/// as a plan mismatch. The rest of the results are returned as they are.
+pub(crate) fn check_revision<T, E: ProvideErrorMetadata, R>(
+    preconditions: Option<&Preconditions>,
+    result: Result<T, SdkError<E, R>>,
+) -> Result<Result<T, SdkError<E, R>>> {
+    match (preconditions, &result) {
+        (Some(preconditions), Err(SdkError::ServiceError(err)))
+            if err.err().code() == Some("PreconditionFailedException") =>
+        {
+            Err(preconditions.mismatch())
+        }
+        _ => Ok(result),
+    }
+}
+
+fn mismatch(path: &Path, changes: &str) -> miette::Report {
+    miette::miette!(
+        help = "write the plan again with `--plan-out`, and review it before deploying",
+        "the deploy doesn't match the plan {path:?}, {changes} changed since the plan was written"
+    )
+}
+
+/// Hash of the environment variables, to detect changes in their values without storing them.
+fn environment_sha256(config: &Deploy) -> Result<Option<String>> {
+    let Some(variables) = config.lambda_environment()?.and_then(|env| env.variables) else {
+        return Ok(None);
+    };
+
+    let mut hasher = Sha256::new();
+    for (key, value) in variables.into_iter().collect::<BTreeMap<_, _>>() {
+        hasher.update(key.as_bytes());
+        hasher.update(b"=");
+        hasher.update(value.as_bytes());
+        hasher.update(b"\n");
+    }
+    Ok(Some(BASE64_STANDARD.encode(hasher.finalize())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_credential_types::Credentials;
+    use aws_sdk_s3::config::{Region, SharedCredentialsProvider};
+    use aws_smithy_runtime::client::http::test_util::{ReplayEvent, StaticReplayClient};
+    use aws_smithy_types::body::SdkBody;
+    use http::{Request, Response};
+
+    fn deploy_plan() -> DeployPlan {
+        DeployPlan {
+            version: PLAN_VERSION,
+            kind: DeployKind::Function,
+            name: "orders-api".into(),
+            account: Some("123456789012".into()),
+            region: Some("us-east-1".into()),
+            code_sha256: "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=".into(),
+            environment_sha256: None,
+            remote_state: RemoteState {
+                function_revision: Some("rev-1".into()),
+                alias_revision: None,
+            },
+            api_calls: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.json");
+
+        let output = deploy_plan().write(&path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(deploy_plan(), DeployPlan::read(&path).unwrap());
+        assert!(output.to_string().contains("--apply"));
+
+        // Writing the same plan again produces the same file.
+        deploy_plan().write(&path).unwrap();
+        assert_eq!(data, std::fs::read(&path).unwrap());
+
+        let mut plan = deploy_plan();
+        plan.version = PLAN_VERSION + 1;
+        plan.write(&path).unwrap();
+        let err = DeployPlan::read(&path).unwrap_err();
+        assert!(err.to_string().contains("different version"), "{err}");
+    }
+
+    #[test]
+    fn test_verify_plan() {
+        let path = Path::new("plan.json");
+        let plan = deploy_plan();
+        plan.verify(path, &deploy_plan()).unwrap();
+
+        let mut current = deploy_plan();
+        current.remote_state.function_revision = Some("rev-2".into());
+        current.code_sha256 = "other".into();
+        let err = plan.verify(path, &current).unwrap_err();
+        assert_eq!(
+            "the deploy doesn't match the plan \"plan.json\", the code, the function in AWS changed since the plan was written",
+            err.to_string()
+        );
+
+        let mut current = deploy_plan();
+        current.region = Some("eu-west-1".into());
+        current.environment_sha256 = Some("abc".into());
+        assert_eq!(
+            vec!["the deploy target", "the environment variables"],
+            plan.differences(&current)
+        );
+    }
+
+    #[test]
+    fn test_preconditions() {
+        let preconditions = deploy_plan().preconditions(Path::new("plan.json"));
+        assert_eq!(
+            Some("rev-1".to_string()),
+            preconditions.function_revision(true).unwrap()
+        );
+        assert_eq!(None, preconditions.alias_revision(false).unwrap());
+
+        // The function was deleted, or the alias was created, after the plan was written.
+        let err = preconditions.function_revision(false).unwrap_err();
+        assert!(
+            err.to_string().contains("the function in AWS changed"),
+            "{err}"
+        );
+        assert!(preconditions.alias_revision(true).is_err());
+    }
+
+    #[test]
+    fn test_environment_sha256() {
+        let mut config = Deploy::default();
+        assert_eq!(None, environment_sha256(&config).unwrap());
+
+        config.base_env.insert("API_KEY".into(), "secret".into());
+        let hash = environment_sha256(&config).unwrap().unwrap();
+        assert!(!hash.contains("secret"));
+
+        config.base_env.insert("API_KEY".into(), "rotated".into());
+        assert_ne!(Some(hash), environment_sha256(&config).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_remote_state() {
+        let http_client = StaticReplayClient::new(vec![
+            ReplayEvent::new(
+                Request::builder()
+                    .uri("https://lambda.us-east-1.amazonaws.com/2015-03-31/functions/orders-api")
+                    .method("GET")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                Response::builder()
+                    .status(200)
+                    .body(SdkBody::from(
+                        serde_json::json!({
+                            "Configuration": { "FunctionName": "orders-api", "RevisionId": "rev-1" }
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            ),
+            ReplayEvent::new(
+                Request::builder()
+                    .uri("https://lambda.us-east-1.amazonaws.com/2015-03-31/functions/orders-api/aliases/live")
+                    .method("GET")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                Response::builder()
+                    .status(404)
+                    .header("x-amzn-errortype", "ResourceNotFoundException")
+                    .body(SdkBody::from(
+                        serde_json::json!({
+                            "Type": "User",
+                            "Message": "Cannot find alias arn"
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            ),
+        ]);
+
+        let sdk_config = SdkConfig::builder()
+            .credentials_provider(SharedCredentialsProvider::new(Credentials::for_tests()))
+            .region(Region::new("us-east-1"))
+            .http_client(http_client.clone())
+            .build();
+
+        let mut config = Deploy::default();
+        config.remote_config.alias = Some("live".into());
+
+        let state = RemoteState::fetch(&config, "orders-api", &sdk_config)
+            .await
+            .unwrap();
+        assert_eq!(
+            RemoteState {
+                function_revision: Some("rev-1".into()),
+                alias_revision: None,
+            },
+            state
+        );
+        http_client.assert_requests_match(&[]);
+    }
+}
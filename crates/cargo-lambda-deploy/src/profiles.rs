@@ -0,0 +1,90 @@
+use cargo_lambda_interactive::{choose_option, is_stdin_tty, is_user_cancellation_error};
+use cargo_lambda_metadata::cargo::deploy::Deploy;
+use cargo_lambda_remote::profiles::{LAMBDA_REGIONS, ProfileInfo, list_profiles};
+use miette::{IntoDiagnostic, Result};
+
+/// Environment variables that select the credentials or the region without a profile.
+const PROFILE_ENV_VARS: &[&str] = &["AWS_PROFILE", "AWS_ACCESS_KEY_ID"];
+const REGION_ENV_VARS: &[&str] = &["AWS_REGION", "AWS_DEFAULT_REGION"];
+
+/// Ask the user which profile and region to deploy with, when there are
+/// several profiles to choose from, and the configuration doesn't select one.
+/// Otherwise, the SDK would pick the default profile silently.
+pub(crate) async fn select_profile_and_region(config: &mut Deploy) -> Result<()> {
+    if config.no_interactive || !is_stdin_tty() {
+        return Ok(());
+    }
+
+    let env_profile = any_env_var(PROFILE_ENV_VARS);
+    let profiles = list_profiles().await;
+    if !should_select_profile(config, env_profile, &profiles) {
+        return Ok(());
+    }
+
+    let profile = match choose_option("Which AWS profile do you want to deploy with?", profiles) {
+        Ok(profile) => profile,
+        Err(err) if is_user_cancellation_error(&err) => {
+            return Err(miette::miette!("deploy cancelled"));
+        }
+        Err(err) => return Err(err).into_diagnostic(),
+    };
+
+    if config.remote_config.region.is_none()
+        && profile.region.is_none()
+        && !any_env_var(REGION_ENV_VARS)
+    {
+        let region = match choose_option(
+            "Which AWS region do you want to deploy to?",
+            LAMBDA_REGIONS.to_vec(),
+        ) {
+            Ok(region) => region,
+            Err(err) if is_user_cancellation_error(&err) => {
+                return Err(miette::miette!("deploy cancelled"));
+            }
+            Err(err) => return Err(err).into_diagnostic(),
+        };
+        config.remote_config.region = Some(region.to_string());
+    }
+
+    config.remote_config.profile = Some(profile.name);
+    Ok(())
+}
+
+fn should_select_profile(config: &Deploy, env_profile: bool, profiles: &[ProfileInfo]) -> bool {
+    !config.dry && config.remote_config.profile.is_none() && !env_profile && profiles.len() > 1
+}
+
+fn any_env_var(names: &[&str]) -> bool {
+    names
+        .iter()
+        .any(|name| std::env::var_os(name).is_some_and(|v| !v.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str) -> ProfileInfo {
+        ProfileInfo {
+            name: name.into(),
+            region: None,
+        }
+    }
+
+    #[test]
+    fn test_should_select_profile() {
+        let profiles = vec![profile("default"), profile("production")];
+
+        let mut config = Deploy::default();
+        assert!(should_select_profile(&config, false, &profiles));
+        assert!(!should_select_profile(&config, true, &profiles));
+        assert!(!should_select_profile(&config, false, &profiles[..1]));
+
+        config.remote_config.profile = Some("production".into());
+        assert!(!should_select_profile(&config, false, &profiles));
+
+        let mut config = Deploy::default();
+        config.dry = true;
+        assert!(!should_select_profile(&config, false, &profiles));
+    }
+}
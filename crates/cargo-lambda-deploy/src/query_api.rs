@@ -0,0 +1,300 @@
+use aws_credential_types::provider::ProvideCredentials;
+use aws_sigv4::{
+    http_request::{SignableBody, SignableRequest, SigningSettings, sign},
+    sign::v4,
+};
+use aws_smithy_xml::decode::{Document, try_data};
+use cargo_lambda_remote::aws_sdk_config::SdkConfig;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde_json::Value;
+use std::time::SystemTime;
+
+const CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+const JSON_CONTENT_TYPE: &str = "application/json";
+const JSON_1_1_CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+const XML_CONTENT_TYPE: &str = "application/xml";
+
+/// Global services, like CloudFront or Route 53, sign their requests for this region.
+const GLOBAL_SIGNING_REGION: &str = "us-east-1";
+
+/// The AWS SDK doesn't include clients for some services, like CloudFormation or CloudWatch,
+/// so the requests to their Query APIs are signed and sent directly.
+/// It returns the body of the response, or the message of the error that the service returned.
+pub(crate) async fn send(
+    sdk_config: &SdkConfig,
+    service: &str,
+    body: String,
+) -> Result<Result<String, String>> {
+    let region = region(sdk_config, service)?;
+    let url = endpoint(service, &region);
+    let response = signed_request(
+        sdk_config,
+        service,
+        &region,
+        "POST",
+        &url,
+        &[("content-type", CONTENT_TYPE)],
+        body,
+    )
+    .await?;
+
+    let status = response.status();
+    let text = response.text().await.into_diagnostic()?;
+    if status.is_success() {
+        Ok(Ok(text))
+    } else {
+        Ok(Err(
+            parse_error_message(&text).unwrap_or_else(|| status.to_string())
+        ))
+    }
+}
+
+/// Request to a service with a JSON API, like EventBridge or API Gateway,
+/// that the AWS SDK doesn't include a client for.
+pub(crate) struct JsonRequest<'a> {
+    pub method: &'a str,
+    /// Path of the request, like `/v2/apis`
+    pub path: &'a str,
+    /// Operation for services with the JSON 1.1 protocol, like `AWSEvents.PutRule`
+    pub target: Option<&'a str>,
+    pub body: Option<Value>,
+}
+
+/// Sign and send a request to a JSON API. It returns the body of the response,
+/// or the message of the error that the service returned.
+pub(crate) async fn send_json(
+    sdk_config: &SdkConfig,
+    service: &str,
+    request: JsonRequest<'_>,
+) -> Result<Result<Value, String>> {
+    let region = region(sdk_config, service)?;
+    let mut url = endpoint(service, &region);
+    url.push_str(request.path.trim_start_matches('/'));
+
+    let mut headers = Vec::new();
+    match request.target {
+        Some(target) => {
+            headers.push(("content-type", JSON_1_1_CONTENT_TYPE));
+            headers.push(("x-amz-target", target));
+        }
+        None => headers.push(("content-type", JSON_CONTENT_TYPE)),
+    }
+    let body = request.body.map(|b| b.to_string()).unwrap_or_default();
+
+    let response = signed_request(
+        sdk_config,
+        service,
+        &region,
+        request.method,
+        &url,
+        &headers,
+        body,
+    )
+    .await?;
+
+    let status = response.status();
+    let text = response.text().await.into_diagnostic()?;
+    if status.is_success() {
+        let value = if text.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&text)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("invalid response from {service}"))?
+        };
+        Ok(Ok(value))
+    } else {
+        Ok(Err(
+            parse_json_error_message(&text).unwrap_or_else(|| status.to_string())
+        ))
+    }
+}
+
+/// Request to a global service with a REST XML API, like CloudFront or Route 53,
+/// that the AWS SDK doesn't include a client for.
+pub(crate) struct XmlRequest<'a> {
+    pub method: &'a str,
+    /// Path of the request with the API version, like `/2013-04-01/hostedzone`
+    pub path: &'a str,
+    pub body: Option<String>,
+}
+
+/// Sign and send a request to the REST XML API of a global service. It returns
+/// the body of the response, or the message of the error that the service returned.
+pub(crate) async fn send_global_xml(
+    sdk_config: &SdkConfig,
+    service: &str,
+    request: XmlRequest<'_>,
+) -> Result<Result<String, String>> {
+    let url = format!(
+        "https://{service}.amazonaws.com/{}",
+        request.path.trim_start_matches('/')
+    );
+    let headers: &[(&str, &str)] = match request.body {
+        Some(_) => &[("content-type", XML_CONTENT_TYPE)],
+        None => &[],
+    };
+
+    let response = signed_request(
+        sdk_config,
+        service,
+        GLOBAL_SIGNING_REGION,
+        request.method,
+        &url,
+        headers,
+        request.body.unwrap_or_default(),
+    )
+    .await?;
+
+    let status = response.status();
+    let text = response.text().await.into_diagnostic()?;
+    if status.is_success() {
+        Ok(Ok(text))
+    } else {
+        Ok(Err(
+            parse_error_message(&text).unwrap_or_else(|| status.to_string())
+        ))
+    }
+}
+
+fn region(sdk_config: &SdkConfig, service: &str) -> Result<String> {
+    Ok(sdk_config
+        .region()
+        .ok_or_else(|| miette::miette!("missing AWS region to send requests to {service}"))?
+        .to_string())
+}
+
+async fn signed_request(
+    sdk_config: &SdkConfig,
+    service: &str,
+    region: &str,
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: String,
+) -> Result<reqwest::Response> {
+    let credentials = sdk_config
+        .credentials_provider()
+        .ok_or_else(|| miette::miette!("missing AWS credentials to send requests to {service}"))?
+        .provide_credentials()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to load AWS credentials")?;
+
+    let identity = credentials.into();
+    let params = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(region)
+        .name(service)
+        .time(SystemTime::now())
+        .settings(SigningSettings::default())
+        .build()
+        .into_diagnostic()?
+        .into();
+    let signable = SignableRequest::new(
+        method,
+        url,
+        headers.iter().copied(),
+        SignableBody::Bytes(body.as_bytes()),
+    )
+    .into_diagnostic()?;
+    let (instructions, _) = sign(signable, &params).into_diagnostic()?.into_parts();
+
+    let method = reqwest::Method::from_bytes(method.as_bytes()).into_diagnostic()?;
+    let mut request = reqwest::Client::new().request(method, url);
+    for (name, value) in headers.iter().copied().chain(instructions.headers()) {
+        request = request.header(name, value);
+    }
+
+    request
+        .body(body)
+        .send()
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to send request to {service}"))
+}
+
+fn endpoint(service: &str, region: &str) -> String {
+    if region.starts_with("cn-") {
+        format!("https://{service}.{region}.amazonaws.com.cn/")
+    } else {
+        format!("https://{service}.{region}.amazonaws.com/")
+    }
+}
+
+/// Parse the message from an error response:
+/// ErrorResponse > Error > Message.
+fn parse_error_message(body: &str) -> Option<String> {
+    let mut doc = Document::new(body);
+    let mut root = doc.root_element().ok()?;
+    while let Some(mut error) = root.next_tag() {
+        if !error.start_el().matches("Error") {
+            continue;
+        }
+        while let Some(mut tag) = error.next_tag() {
+            if tag.start_el().matches("Message") {
+                return try_data(&mut tag).ok().map(|m| m.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Parse the message from a JSON error response, the services
+/// use either `message` or `Message` for the field.
+fn parse_json_error_message(body: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    value
+        .get("message")
+        .or_else(|| value.get("Message"))
+        .and_then(Value::as_str)
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_message() {
+        let body = r#"<ErrorResponse xmlns="http://cloudformation.amazonaws.com/doc/2010-05-15/">
+  <Error>
+    <Type>Sender</Type>
+    <Code>ValidationError</Code>
+    <Message>Stack with id orders does not exist</Message>
+  </Error>
+  <RequestId>b9b4b068-3a41-11e5-94eb-example</RequestId>
+</ErrorResponse>"#;
+        assert_eq!(
+            Some("Stack with id orders does not exist".to_string()),
+            parse_error_message(body)
+        );
+    }
+
+    #[test]
+    fn test_parse_json_error_message() {
+        assert_eq!(
+            Some("Rule orders does not exist".to_string()),
+            parse_json_error_message(
+                r#"{"__type":"ResourceNotFoundException","message":"Rule orders does not exist"}"#
+            )
+        );
+        assert_eq!(
+            Some("Invalid API".to_string()),
+            parse_json_error_message(r#"{"Message":"Invalid API"}"#)
+        );
+        assert_eq!(None, parse_json_error_message("<html></html>"));
+    }
+
+    #[test]
+    fn test_endpoint() {
+        assert_eq!(
+            "https://cloudformation.us-east-1.amazonaws.com/",
+            endpoint("cloudformation", "us-east-1")
+        );
+        assert_eq!(
+            "https://monitoring.cn-north-1.amazonaws.com.cn/",
+            endpoint("monitoring", "cn-north-1")
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use cargo_lambda_interactive::{
+    Confirm, is_stdin_tty, is_user_cancellation_error, progress::Progress,
+};
+use cargo_lambda_metadata::cargo::deploy::Deploy;
+use cargo_lambda_remote::aws_sdk_lambda::types::{Architecture, FunctionConfiguration, Runtime};
+use miette::{IntoDiagnostic, Result};
+use std::fmt;
+
+/// Setting of an existing function that the deploy replaces.
+/// Replacing these settings can break the function,
+/// so users need to confirm the changes before the deploy continues.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Replacement {
+    setting: &'static str,
+    from: String,
+    to: String,
+}
+
+impl fmt::Display for Replacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "  - {}: {} -> {}", self.setting, self.from, self.to)
+    }
+}
+
+/// Find the runtime, architecture, and role changes that the deploy applies to an existing function.
+pub(crate) fn replacements(
+    config: &Deploy,
+    function: &FunctionConfiguration,
+    architecture: Option<&Architecture>,
+) -> Vec<Replacement> {
+    let mut replacements = Vec::new();
+
+    if config.function_config.should_update() {
+        if let Some(runtime) = &config.function_config.runtime {
+            let runtime = Runtime::from(runtime.as_str());
+            if let Some(current) = function.runtime() {
+                if current != &runtime {
+                    replacements.push(Replacement {
+                        setting: "runtime",
+                        from: current.to_string(),
+                        to: runtime.to_string(),
+                    });
+                }
+            }
+        }
+
+        if let Some(role) = &config.function_config.role {
+            if let Some(current) = function.role() {
+                if current != role {
+                    replacements.push(Replacement {
+                        setting: "role",
+                        from: current.to_string(),
+                        to: role.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    if let Some(architecture) = architecture {
+        if let Some(current) = function.architectures().first() {
+            replacements.push(Replacement {
+                setting: "architecture",
+                from: current.to_string(),
+                to: architecture.to_string(),
+            });
+        }
+    }
+
+    replacements
+}
+
+/// Ask the user to confirm the replacements before deploying the function.
+/// The flag `--yes` skips the confirmation. The deploy fails when
+/// the confirmation is necessary, but there is no terminal to ask for it.
+pub(crate) fn confirm(
+    config: &Deploy,
+    name: &str,
+    replacements: &[Replacement],
+    progress: &Progress,
+) -> Result<()> {
+    if replacements.is_empty() || config.yes {
+        return Ok(());
+    }
+
+    let summary = summary(name, replacements);
+    if !is_stdin_tty() {
+        return Err(miette::miette!(
+            help = "deploy with `--yes` to apply these changes",
+            "{summary}"
+        ));
+    }
+
+    let answer = progress.suspend(|| {
+        println!("{summary}");
+        Confirm::new("Do you want to apply these changes?")
+            .with_default(false)
+            .prompt()
+    });
+
+    match answer {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(miette::miette!("deploy cancelled")),
+        Err(err) if is_user_cancellation_error(&err) => Err(miette::miette!("deploy cancelled")),
+        Err(err) => Err(err).into_diagnostic(),
+    }
+}
+
+fn summary(name: &str, replacements: &[Replacement]) -> String {
+    let changes = replacements
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("the deploy replaces settings of the function `{name}`:\n{changes}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function_configuration() -> FunctionConfiguration {
+        FunctionConfiguration::builder()
+            .runtime(Runtime::Providedal2)
+            .architectures(Architecture::X8664)
+            .role("arn:aws:iam::123456789012:role/lambda-role")
+            .build()
+    }
+
+    #[test]
+    fn test_replacements() {
+        let function = function_configuration();
+
+        let config = Deploy::default();
+        assert!(replacements(&config, &function, None).is_empty());
+
+        let mut config = Deploy::default();
+        config.function_config.runtime = Some("provided.al2023".into());
+        config.function_config.role = Some("arn:aws:iam::123456789012:role/other-role".into());
+
+        let replacements = replacements(&config, &function, Some(&Architecture::Arm64));
+        assert_eq!(
+            vec![
+                Replacement {
+                    setting: "runtime",
+                    from: "provided.al2".into(),
+                    to: "provided.al2023".into(),
+                },
+                Replacement {
+                    setting: "role",
+                    from: "arn:aws:iam::123456789012:role/lambda-role".into(),
+                    to: "arn:aws:iam::123456789012:role/other-role".into(),
+                },
+                Replacement {
+                    setting: "architecture",
+                    from: "x86_64".into(),
+                    to: "arm64".into(),
+                },
+            ],
+            replacements
+        );
+
+        assert_eq!(
+            "the deploy replaces settings of the function `basic-lambda`:\n  - runtime: provided.al2 -> provided.al2023\n  - role: arn:aws:iam::123456789012:role/lambda-role -> arn:aws:iam::123456789012:role/other-role\n  - architecture: x86_64 -> arm64",
+            summary("basic-lambda", &replacements)
+        );
+    }
+
+    #[test]
+    fn test_replacements_with_same_settings() {
+        let function = function_configuration();
+
+        let mut config = Deploy::default();
+        config.function_config.runtime = Some("provided.al2".into());
+        config.function_config.role = Some("arn:aws:iam::123456789012:role/lambda-role".into());
+
+        assert!(replacements(&config, &function, None).is_empty());
+    }
+
+    #[test]
+    fn test_confirm_with_yes() {
+        let mut config = Deploy::default();
+        config.yes = true;
+
+        let replacements = vec![Replacement {
+            setting: "runtime",
+            from: "provided.al2".into(),
+            to: "provided.al2023".into(),
+        }];
+
+        let progress = Progress::start("deploying function");
+        confirm(&config, "basic-lambda", &replacements, &progress).unwrap();
+        confirm(&Deploy::default(), "basic-lambda", &[], &progress).unwrap();
+        progress.finish_and_clear();
+    }
+}
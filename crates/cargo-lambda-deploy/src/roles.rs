@@ -1,12 +1,13 @@
-use aws_sdk_iam::Client as IamClient;
+use aws_sdk_iam::{Client as IamClient, types::Tag};
 use aws_sdk_sts::{Client as StsClient, Error};
 use aws_smithy_types::error::metadata::ProvideErrorMetadata;
 use cargo_lambda_interactive::progress::Progress;
 use cargo_lambda_remote::aws_sdk_config::SdkConfig;
 use miette::{IntoDiagnostic, Result, WrapErr};
+use std::collections::HashMap;
 use tokio::time::{Duration, sleep};
 
-const BASIC_LAMBDA_EXECUTION_POLICY: &str =
+pub(crate) const BASIC_LAMBDA_EXECUTION_POLICY: &str =
     "arn:aws:iam::aws:policy/service-role/AWSLambdaBasicExecutionRole";
 
 #[derive(Debug)]
@@ -32,7 +33,11 @@ impl FunctionRole {
     }
 }
 
-pub(crate) async fn create(config: &SdkConfig, progress: &Progress) -> Result<FunctionRole> {
+pub(crate) async fn create(
+    config: &SdkConfig,
+    tags: Option<HashMap<String, String>>,
+    progress: &Progress,
+) -> Result<FunctionRole> {
     progress.set_message("creating execution role");
 
     let role_name = format!("cargo-lambda-role-{}", uuid::Uuid::new_v4());
@@ -45,25 +50,7 @@ pub(crate) async fn create(config: &SdkConfig, progress: &Progress) -> Result<Fu
         .into_diagnostic()
         .wrap_err("failed to get caller identity")?;
 
-    let mut policy = serde_json::json!({
-        "Version": "2012-10-17",
-        "Statement": [
-            {
-                "Effect": "Allow",
-                "Action": ["sts:AssumeRole"],
-                "Principal": {
-                    "Service": "lambda.amazonaws.com"
-                }
-            },
-            {
-                "Effect": "Allow",
-                "Action": ["sts:AssumeRole", "sts:SetSourceIdentity", "sts:TagSession"],
-                "Principal": {
-                    "AWS": identity.arn().expect("missing account arn"),
-                }
-            }
-        ]
-    });
+    let mut policy = trust_policy(identity.arn().expect("missing account arn"));
 
     tracing::trace!(policy = ?policy, "creating role with assume policy");
 
@@ -71,6 +58,7 @@ pub(crate) async fn create(config: &SdkConfig, progress: &Progress) -> Result<Fu
         .create_role()
         .role_name(&role_name)
         .assume_role_policy_document(policy.to_string())
+        .set_tags(role_tags(tags)?)
         .send()
         .await
         .into_diagnostic()
@@ -115,6 +103,49 @@ pub(crate) async fn create(config: &SdkConfig, progress: &Progress) -> Result<Fu
     Ok(FunctionRole::new(role_arn.to_string()))
 }
 
+/// Trust policy of the new role. The identity that creates the role can assume it,
+/// to check that the role is ready. That statement is the last one, and it's removed
+/// from the policy after the check.
+pub(crate) fn trust_policy(caller_arn: &str) -> serde_json::Value {
+    serde_json::json!({
+        "Version": "2012-10-17",
+        "Statement": [
+            {
+                "Effect": "Allow",
+                "Action": ["sts:AssumeRole"],
+                "Principal": {
+                    "Service": "lambda.amazonaws.com"
+                }
+            },
+            {
+                "Effect": "Allow",
+                "Action": ["sts:AssumeRole", "sts:SetSourceIdentity", "sts:TagSession"],
+                "Principal": {
+                    "AWS": caller_arn,
+                }
+            }
+        ]
+    })
+}
+
+fn role_tags(tags: Option<HashMap<String, String>>) -> Result<Option<Vec<Tag>>> {
+    let Some(tags) = tags else {
+        return Ok(None);
+    };
+
+    tags.into_iter()
+        .map(|(key, value)| {
+            Tag::builder()
+                .key(key)
+                .value(value)
+                .build()
+                .into_diagnostic()
+                .wrap_err("invalid role tag")
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(Some)
+}
+
 async fn try_assume_role(client: &StsClient, role_arn: &str) -> Result<()> {
     sleep(Duration::from_secs(5)).await;
 
@@ -0,0 +1,127 @@
+use cargo_lambda_metadata::cargo::deploy::Deploy;
+use cargo_lambda_remote::aws_sdk_lambda::{Client as LambdaClient, primitives::Blob};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde::Deserialize;
+use std::fs::read;
+
+/// Error that the runtime reports when a function invocation fails.
+#[derive(Deserialize)]
+struct FunctionError {
+    #[serde(rename = "errorType", default)]
+    error_type: String,
+    #[serde(rename = "errorMessage", default)]
+    error_message: String,
+}
+
+/// Payload to invoke the function with after the deploy,
+/// read before deploying so a missing file doesn't leave a half finished deploy.
+pub(crate) fn load_payload(config: &Deploy) -> Result<Option<Vec<u8>>> {
+    let Some(path) = &config.smoke_test else {
+        return Ok(None);
+    };
+
+    read(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read the smoke test payload {path:?}"))
+        .map(Some)
+}
+
+/// Invoke the deployed version of the function, and fail when the invocation returns an error.
+pub(crate) async fn invoke(
+    client: &LambdaClient,
+    name: &str,
+    qualifier: &str,
+    payload: &[u8],
+) -> Result<()> {
+    let resp = client
+        .invoke()
+        .function_name(name)
+        .qualifier(qualifier)
+        .payload(Blob::new(payload))
+        .send()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to invoke the function to run the smoke test")?;
+
+    let Some(function_error) = resp.function_error else {
+        return Ok(());
+    };
+
+    let payload = resp.payload.map(Blob::into_inner).unwrap_or_default();
+    let message = match serde_json::from_slice::<FunctionError>(&payload) {
+        Ok(err) if !err.error_type.is_empty() => {
+            format!("{}: {}", err.error_type, err.error_message)
+        }
+        Ok(err) if !err.error_message.is_empty() => err.error_message,
+        _ => function_error,
+    };
+
+    Err(miette::miette!(
+        "the smoke test invocation of `{name}:{qualifier}` failed: {message}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_credential_types::Credentials;
+    use aws_smithy_runtime::client::http::test_util::{ReplayEvent, StaticReplayClient};
+    use aws_smithy_types::body::SdkBody;
+    use cargo_lambda_remote::aws_sdk_lambda::config::{Config as LambdaConfig, Region};
+    use http::{Request, Response};
+
+    fn invoke_event(function_error: Option<&str>, body: &str) -> ReplayEvent {
+        let mut response = Response::builder().status(200);
+        if let Some(function_error) = function_error {
+            response = response.header("X-Amz-Function-Error", function_error);
+        }
+        ReplayEvent::new(
+            Request::builder()
+                .uri("https://lambda.us-east-1.amazonaws.com/2015-03-31/functions/test-function/invocations?Qualifier=live")
+                .body(SdkBody::from("{}"))
+                .unwrap(),
+            response.body(SdkBody::from(body.to_string())).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_invoke() {
+        let http_client = StaticReplayClient::new(vec![
+            invoke_event(None, r#"{"statusCode": 200}"#),
+            invoke_event(
+                Some("Unhandled"),
+                r#"{"errorType": "&alloc::boxed::Box<dyn core::error::Error>", "errorMessage": "missing table"}"#,
+            ),
+            invoke_event(Some("Unhandled"), ""),
+        ]);
+
+        let config = LambdaConfig::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let client = LambdaClient::from_conf(config);
+
+        invoke(&client, "test-function", "live", b"{}")
+            .await
+            .unwrap();
+
+        let err = invoke(&client, "test-function", "live", b"{}")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            "the smoke test invocation of `test-function:live` failed: &alloc::boxed::Box<dyn core::error::Error>: missing table",
+            err.to_string()
+        );
+
+        let err = invoke(&client, "test-function", "live", b"{}")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            "the smoke test invocation of `test-function:live` failed: Unhandled",
+            err.to_string()
+        );
+
+        http_client.assert_requests_match(&[]);
+    }
+}
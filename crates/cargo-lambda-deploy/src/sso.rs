@@ -0,0 +1,51 @@
+use aws_smithy_types::retry::RetryConfig;
+use cargo_lambda_interactive::{
+    Confirm, is_stdin_tty, is_user_cancellation_error, progress::Progress,
+};
+use cargo_lambda_metadata::cargo::deploy::Deploy;
+use cargo_lambda_remote::{aws_sdk_config::SdkConfig, sso::sso_login};
+use miette::{IntoDiagnostic, Result};
+
+/// Check that the SSO session of the profile is valid before deploying.
+/// When the session expired, it asks the user to log in again,
+/// and it loads the configuration with the new credentials.
+pub(crate) async fn ensure_session(
+    config: &Deploy,
+    sdk_config: SdkConfig,
+    retry: RetryConfig,
+    progress: &Progress,
+) -> Result<SdkConfig> {
+    progress.set_message("checking AWS credentials");
+
+    let Some(profile) = config.remote_config.expired_sso_profile(&sdk_config).await else {
+        return Ok(sdk_config);
+    };
+
+    let message = format!("the AWS SSO session for the profile `{profile}` expired");
+    if !is_stdin_tty() {
+        return Err(miette::miette!(
+            help = format!("log in with `aws sso login --profile {profile}`, and try again"),
+            "{message}"
+        ));
+    }
+
+    let answer = progress.suspend(|| {
+        println!("{message}");
+        Confirm::new("Do you want to log in with `aws sso login`?")
+            .with_default(true)
+            .prompt()
+    });
+
+    match answer {
+        Ok(true) => {}
+        Ok(false) => return Err(miette::miette!("deploy cancelled")),
+        Err(err) if is_user_cancellation_error(&err) => {
+            return Err(miette::miette!("deploy cancelled"));
+        }
+        Err(err) => return Err(err).into_diagnostic(),
+    }
+
+    progress.suspend(|| sso_login(&profile))?;
+
+    Ok(config.remote_config.sdk_config(Some(retry)).await)
+}
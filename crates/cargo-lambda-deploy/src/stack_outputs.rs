@@ -0,0 +1,192 @@
+use aws_smithy_xml::decode::{Document, ScopedDecoder, XmlDecodeError, try_data};
+use cargo_lambda_metadata::cargo::deploy::StackOutput;
+use cargo_lambda_remote::aws_sdk_config::SdkConfig;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::collections::{BTreeSet, HashMap};
+use tracing::debug;
+
+use crate::query_api;
+
+const CLOUDFORMATION_API_VERSION: &str = "2010-05-15";
+
+/// Fetch the outputs of the CloudFormation stacks,
+/// and return them as environment variables.
+pub(crate) async fn resolve(
+    sdk_config: &SdkConfig,
+    outputs: &[StackOutput],
+) -> Result<HashMap<String, String>> {
+    let stacks = outputs
+        .iter()
+        .map(|o| o.stack_name.as_str())
+        .collect::<BTreeSet<_>>();
+
+    let mut stack_outputs = HashMap::new();
+    for stack_name in stacks {
+        let outputs = describe_stack_outputs(sdk_config, stack_name).await?;
+        stack_outputs.insert(stack_name, outputs);
+    }
+
+    environment(outputs, &stack_outputs)
+}
+
+fn environment(
+    outputs: &[StackOutput],
+    stack_outputs: &HashMap<&str, HashMap<String, String>>,
+) -> Result<HashMap<String, String>> {
+    let mut env = HashMap::new();
+    for output in outputs {
+        let value = stack_outputs
+            .get(output.stack_name.as_str())
+            .and_then(|o| o.get(&output.output_key))
+            .ok_or_else(|| {
+                miette::miette!(
+                    "the stack `{}` doesn't have an output named `{}`",
+                    output.stack_name,
+                    output.output_key
+                )
+            })?;
+        env.insert(output.env_var.clone(), value.clone());
+    }
+    Ok(env)
+}
+
+async fn describe_stack_outputs(
+    sdk_config: &SdkConfig,
+    stack_name: &str,
+) -> Result<HashMap<String, String>> {
+    let body = format!(
+        "Action=DescribeStacks&Version={CLOUDFORMATION_API_VERSION}&StackName={}",
+        urlencoding::encode(stack_name)
+    );
+
+    debug!(stack_name, "fetching stack outputs");
+    let text = query_api::send(sdk_config, "cloudformation", body)
+        .await
+        .wrap_err("failed to fetch the stack outputs")?
+        .map_err(|message| {
+            miette::miette!("failed to fetch the outputs of the stack `{stack_name}`: {message}")
+        })?;
+
+    parse_outputs(&text)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read the outputs of the stack `{stack_name}`"))
+}
+
+/// Parse the outputs from a DescribeStacks response:
+/// DescribeStacksResponse > DescribeStacksResult > Stacks > member > Outputs > member.
+fn parse_outputs(body: &str) -> Result<HashMap<String, String>, XmlDecodeError> {
+    let mut outputs = HashMap::new();
+
+    let mut doc = Document::new(body);
+    let mut root = doc.root_element()?;
+    while let Some(mut result) = root.next_tag() {
+        if !result.start_el().matches("DescribeStacksResult") {
+            continue;
+        }
+        while let Some(mut stacks) = result.next_tag() {
+            if !stacks.start_el().matches("Stacks") {
+                continue;
+            }
+            while let Some(mut stack) = stacks.next_tag() {
+                while let Some(mut field) = stack.next_tag() {
+                    if field.start_el().matches("Outputs") {
+                        parse_stack_outputs(&mut field, &mut outputs)?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(outputs)
+}
+
+fn parse_stack_outputs(
+    decoder: &mut ScopedDecoder,
+    outputs: &mut HashMap<String, String>,
+) -> Result<(), XmlDecodeError> {
+    while let Some(mut member) = decoder.next_tag() {
+        let mut key = None;
+        let mut value = None;
+        while let Some(mut tag) = member.next_tag() {
+            if tag.start_el().matches("OutputKey") {
+                key = Some(try_data(&mut tag)?.to_string());
+            } else if tag.start_el().matches("OutputValue") {
+                value = Some(try_data(&mut tag)?.to_string());
+            }
+        }
+
+        if let (Some(key), Some(value)) = (key, value) {
+            outputs.insert(key, value);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIBE_STACKS_RESPONSE: &str = r#"<DescribeStacksResponse xmlns="http://cloudformation.amazonaws.com/doc/2010-05-15/">
+  <DescribeStacksResult>
+    <Stacks>
+      <member>
+        <StackName>orders</StackName>
+        <Outputs>
+          <member>
+            <OutputKey>QueueUrl</OutputKey>
+            <OutputValue>https://sqs.us-east-1.amazonaws.com/123456789012/orders</OutputValue>
+            <Description>URL of the orders queue</Description>
+          </member>
+          <member>
+            <OutputKey>TableName</OutputKey>
+            <OutputValue>orders-table</OutputValue>
+          </member>
+        </Outputs>
+        <StackStatus>CREATE_COMPLETE</StackStatus>
+      </member>
+    </Stacks>
+  </DescribeStacksResult>
+  <ResponseMetadata>
+    <RequestId>b9b4b068-3a41-11e5-94eb-example</RequestId>
+  </ResponseMetadata>
+</DescribeStacksResponse>"#;
+
+    #[test]
+    fn test_parse_outputs() {
+        let outputs = parse_outputs(DESCRIBE_STACKS_RESPONSE).unwrap();
+        assert_eq!(
+            HashMap::from([
+                (
+                    "QueueUrl".to_string(),
+                    "https://sqs.us-east-1.amazonaws.com/123456789012/orders".to_string()
+                ),
+                ("TableName".to_string(), "orders-table".to_string()),
+            ]),
+            outputs
+        );
+    }
+
+    #[test]
+    fn test_environment() {
+        let outputs = vec![
+            "orders:QueueUrl=QUEUE_URL".parse::<StackOutput>().unwrap(),
+            "orders:TableName=TABLE_NAME".parse().unwrap(),
+        ];
+        let stack_outputs =
+            HashMap::from([("orders", parse_outputs(DESCRIBE_STACKS_RESPONSE).unwrap())]);
+
+        let env = environment(&outputs, &stack_outputs).unwrap();
+        assert_eq!("orders-table", env["TABLE_NAME"]);
+        assert_eq!(
+            "https://sqs.us-east-1.amazonaws.com/123456789012/orders",
+            env["QUEUE_URL"]
+        );
+
+        let outputs = vec!["orders:BucketName=BUCKET".parse::<StackOutput>().unwrap()];
+        let err = environment(&outputs, &stack_outputs).unwrap_err();
+        assert_eq!(
+            "the stack `orders` doesn't have an output named `BucketName`",
+            err.to_string()
+        );
+    }
+}
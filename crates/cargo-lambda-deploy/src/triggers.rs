@@ -0,0 +1,408 @@
+use cargo_lambda_interactive::progress::Progress;
+use cargo_lambda_metadata::cargo::deploy::{InvokePermission, Trigger};
+use cargo_lambda_remote::{aws_sdk_config::SdkConfig, aws_sdk_lambda::Client as LambdaClient};
+use miette::{Result, WrapErr};
+use serde::Serialize;
+use serde_json::{Value, json};
+use tracing::debug;
+
+use crate::{
+    functions::add_invoke_permissions,
+    query_api::{self, JsonRequest},
+};
+
+/// Rule names can't be longer than 64 characters.
+const MAX_RULE_NAME_LEN: usize = 64;
+
+/// Id of the rule's target that invokes the function.
+const RULE_TARGET_ID: &str = "cargo-lambda";
+
+/// Resource created to invoke the function.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum TriggerOutput {
+    Schedule { rule_arn: String },
+    HttpApi { api_id: String, endpoint: String },
+}
+
+impl std::fmt::Display for TriggerOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TriggerOutput::Schedule { rule_arn } => write!(f, "⏰ schedule: {rule_arn}"),
+            TriggerOutput::HttpApi { endpoint, .. } => write!(f, "🌐 api: {endpoint}"),
+        }
+    }
+}
+
+/// Create the triggers, and allow them to invoke the function.
+/// Triggers that already exist from previous deploys are updated to invoke the function.
+pub(crate) async fn create(
+    sdk_config: &SdkConfig,
+    client: &LambdaClient,
+    name: &str,
+    alias: &Option<String>,
+    function_arn: &str,
+    triggers: &[Trigger],
+    progress: &Progress,
+) -> Result<Vec<TriggerOutput>> {
+    let target_arn = target_arn(function_arn, alias.as_deref());
+    let mut outputs = Vec::with_capacity(triggers.len());
+
+    for trigger in triggers {
+        let output = match trigger {
+            Trigger::Schedule(schedule) => {
+                progress.set_message("creating schedule rule");
+                create_schedule(sdk_config, name, alias.as_deref(), &target_arn, schedule).await?
+            }
+            Trigger::HttpApi => {
+                progress.set_message("creating http api");
+                create_http_api(sdk_config, name, alias.as_deref(), &target_arn).await?
+            }
+        };
+
+        let permission = invoke_permission(&output, function_arn);
+        add_invoke_permissions(name, alias, &[permission], client).await?;
+        outputs.push(output);
+    }
+
+    Ok(outputs)
+}
+
+async fn create_schedule(
+    sdk_config: &SdkConfig,
+    name: &str,
+    alias: Option<&str>,
+    target_arn: &str,
+    schedule: &str,
+) -> Result<TriggerOutput> {
+    let rule_name = rule_name(name, alias, schedule);
+    debug!(rule_name, schedule, "creating schedule rule");
+
+    let request = JsonRequest {
+        method: "POST",
+        path: "/",
+        target: Some("AWSEvents.PutRule"),
+        body: Some(put_rule_body(name, &rule_name, schedule)),
+    };
+    let response = send(
+        sdk_config,
+        "events",
+        request,
+        "failed to create the schedule rule",
+    )
+    .await?;
+    let rule_arn = string_field(&response, "RuleArn", "the schedule rule")?;
+
+    let request = JsonRequest {
+        method: "POST",
+        path: "/",
+        target: Some("AWSEvents.PutTargets"),
+        body: Some(put_targets_body(&rule_name, target_arn)),
+    };
+    let response = send(
+        sdk_config,
+        "events",
+        request,
+        "failed to add the function to the schedule rule",
+    )
+    .await?;
+    if let Some(message) = failed_target_message(&response) {
+        return Err(miette::miette!(
+            "failed to add the function to the schedule rule: {message}"
+        ));
+    }
+
+    Ok(TriggerOutput::Schedule { rule_arn })
+}
+
+async fn create_http_api(
+    sdk_config: &SdkConfig,
+    name: &str,
+    alias: Option<&str>,
+    target_arn: &str,
+) -> Result<TriggerOutput> {
+    let api_name = api_name(name, alias);
+
+    let mut next_token: Option<String> = None;
+    loop {
+        let path = match &next_token {
+            Some(token) => format!("/v2/apis?nextToken={}", urlencoding::encode(token)),
+            None => "/v2/apis".to_string(),
+        };
+        let request = JsonRequest {
+            method: "GET",
+            path: &path,
+            target: None,
+            body: None,
+        };
+        let response = send(
+            sdk_config,
+            "apigateway",
+            request,
+            "failed to list the http apis",
+        )
+        .await?;
+
+        if let Some(api) = find_api(&response, &api_name) {
+            debug!(api_name, "the http api already exists");
+            return Ok(api);
+        }
+
+        next_token = response
+            .get("nextToken")
+            .and_then(Value::as_str)
+            .map(String::from);
+        if next_token.is_none() {
+            break;
+        }
+    }
+
+    debug!(api_name, "creating http api");
+    let request = JsonRequest {
+        method: "POST",
+        path: "/v2/apis",
+        target: None,
+        body: Some(create_api_body(&api_name, target_arn)),
+    };
+    let response = send(
+        sdk_config,
+        "apigateway",
+        request,
+        "failed to create the http api",
+    )
+    .await?;
+    api_output(&response).ok_or_else(|| miette::miette!("invalid response creating the http api"))
+}
+
+pub(crate) fn put_rule_body(name: &str, rule_name: &str, schedule: &str) -> Value {
+    json!({
+        "Name": rule_name,
+        "ScheduleExpression": schedule,
+        "State": "ENABLED",
+        "Description": format!("Invoke the {name} function on {schedule}"),
+    })
+}
+
+pub(crate) fn put_targets_body(rule_name: &str, target_arn: &str) -> Value {
+    json!({
+        "Rule": rule_name,
+        "Targets": [{"Id": RULE_TARGET_ID, "Arn": target_arn}],
+    })
+}
+
+/// Quick create makes the default route and stage that send all the requests to the target.
+pub(crate) fn create_api_body(api_name: &str, target_arn: &str) -> Value {
+    json!({
+        "name": api_name,
+        "protocolType": "HTTP",
+        "target": target_arn,
+    })
+}
+
+async fn send(
+    sdk_config: &SdkConfig,
+    service: &str,
+    request: JsonRequest<'_>,
+    context: &'static str,
+) -> Result<Value> {
+    query_api::send_json(sdk_config, service, request)
+        .await
+        .wrap_err(context)?
+        .map_err(|message| miette::miette!("{context}: {message}"))
+}
+
+fn string_field(response: &Value, field: &str, resource: &str) -> Result<String> {
+    response
+        .get(field)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| miette::miette!("missing {field} in the response for {resource}"))
+}
+
+/// Permission for the trigger's service to invoke the function.
+fn invoke_permission(output: &TriggerOutput, function_arn: &str) -> InvokePermission {
+    let account = arn_component(function_arn, 4).map(String::from);
+    match output {
+        TriggerOutput::Schedule { rule_arn } => InvokePermission {
+            principal: "events.amazonaws.com".into(),
+            source_arn: Some(rule_arn.clone()),
+            source_account: account,
+        },
+        TriggerOutput::HttpApi { api_id, .. } => {
+            let partition = arn_component(function_arn, 1).unwrap_or("aws");
+            let region = arn_component(function_arn, 3).unwrap_or_default();
+            let source_arn = format!(
+                "arn:{partition}:execute-api:{region}:{}:{api_id}/*",
+                account.as_deref().unwrap_or_default()
+            );
+            InvokePermission {
+                principal: "apigateway.amazonaws.com".into(),
+                source_arn: Some(source_arn),
+                source_account: account,
+            }
+        }
+    }
+}
+
+fn arn_component(arn: &str, idx: usize) -> Option<&str> {
+    arn.split(':').nth(idx).filter(|c| !c.is_empty())
+}
+
+/// ARN that the triggers invoke, qualified with the alias when the deploy uses one.
+pub(crate) fn target_arn(function_arn: &str, alias: Option<&str>) -> String {
+    // arn:aws:lambda:region:account:function:name, without the version.
+    let unqualified = function_arn
+        .split(':')
+        .take(7)
+        .collect::<Vec<_>>()
+        .join(":");
+    match alias {
+        Some(alias) => format!("{unqualified}:{alias}"),
+        None => unqualified,
+    }
+}
+
+/// Name of the schedule rule, derived from the function and the schedule expression,
+/// so deploying the same schedule again updates the same rule.
+pub(crate) fn rule_name(name: &str, alias: Option<&str>, schedule: &str) -> String {
+    let mut rule_name = api_name(name, alias);
+    rule_name.push('-');
+    for c in schedule.chars() {
+        if c.is_ascii_alphanumeric() {
+            rule_name.push(c);
+        } else if !rule_name.ends_with('-') {
+            rule_name.push('-');
+        }
+    }
+
+    let rule_name = rule_name.trim_end_matches('-');
+    rule_name.chars().take(MAX_RULE_NAME_LEN).collect()
+}
+
+pub(crate) fn api_name(name: &str, alias: Option<&str>) -> String {
+    match alias {
+        Some(alias) => format!("{name}-{alias}"),
+        None => name.to_string(),
+    }
+}
+
+fn find_api(response: &Value, api_name: &str) -> Option<TriggerOutput> {
+    response
+        .get("items")
+        .and_then(Value::as_array)?
+        .iter()
+        .find(|api| api.get("name").and_then(Value::as_str) == Some(api_name))
+        .and_then(api_output)
+}
+
+fn api_output(api: &Value) -> Option<TriggerOutput> {
+    let field = |name: &str| api.get(name).and_then(Value::as_str).map(String::from);
+    Some(TriggerOutput::HttpApi {
+        api_id: field("apiId")?,
+        endpoint: field("apiEndpoint")?,
+    })
+}
+
+fn failed_target_message(response: &Value) -> Option<String> {
+    let failed = response.get("FailedEntryCount").and_then(Value::as_i64)?;
+    if failed == 0 {
+        return None;
+    }
+
+    let message = response
+        .get("FailedEntries")
+        .and_then(Value::as_array)
+        .and_then(|entries| entries.first())
+        .and_then(|entry| entry.get("ErrorMessage"))
+        .and_then(Value::as_str)
+        .unwrap_or("unknown error");
+    Some(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FUNCTION_ARN: &str = "arn:aws:lambda:us-east-1:123456789012:function:orders";
+
+    #[test]
+    fn test_rule_name() {
+        assert_eq!(
+            "orders-rate-5-minutes",
+            rule_name("orders", None, "rate(5 minutes)")
+        );
+        assert_eq!(
+            "orders-live-cron-0-12-MON-FRI",
+            rule_name("orders", Some("live"), "cron(0 12 ? * MON-FRI *)")
+        );
+
+        let long = "a".repeat(80);
+        assert_eq!(64, rule_name(&long, None, "rate(1 hour)").len());
+    }
+
+    #[test]
+    fn test_target_arn() {
+        assert_eq!(FUNCTION_ARN, target_arn(FUNCTION_ARN, None));
+        assert_eq!(
+            format!("{FUNCTION_ARN}:live"),
+            target_arn(&format!("{FUNCTION_ARN}:3"), Some("live"))
+        );
+    }
+
+    #[test]
+    fn test_invoke_permission() {
+        let api = TriggerOutput::HttpApi {
+            api_id: "a1b2c3".into(),
+            endpoint: "https://a1b2c3.execute-api.us-east-1.amazonaws.com".into(),
+        };
+        assert_eq!(
+            InvokePermission {
+                principal: "apigateway.amazonaws.com".into(),
+                source_arn: Some("arn:aws:execute-api:us-east-1:123456789012:a1b2c3/*".into()),
+                source_account: Some("123456789012".into()),
+            },
+            invoke_permission(&api, FUNCTION_ARN)
+        );
+
+        let rule_arn = "arn:aws:events:us-east-1:123456789012:rule/orders-rate-5-minutes";
+        let schedule = TriggerOutput::Schedule {
+            rule_arn: rule_arn.into(),
+        };
+        let permission = invoke_permission(&schedule, FUNCTION_ARN);
+        assert_eq!("events.amazonaws.com", permission.principal);
+        assert_eq!(Some(rule_arn.to_string()), permission.source_arn);
+    }
+
+    #[test]
+    fn test_find_api() {
+        let response = json!({
+            "items": [
+                {"name": "payments", "apiId": "z9y8x7", "apiEndpoint": "https://z9y8x7.execute-api.us-east-1.amazonaws.com"},
+                {"name": "orders", "apiId": "a1b2c3", "apiEndpoint": "https://a1b2c3.execute-api.us-east-1.amazonaws.com"},
+            ]
+        });
+        assert_eq!(
+            Some(TriggerOutput::HttpApi {
+                api_id: "a1b2c3".into(),
+                endpoint: "https://a1b2c3.execute-api.us-east-1.amazonaws.com".into(),
+            }),
+            find_api(&response, "orders")
+        );
+        assert_eq!(None, find_api(&response, "orders-live"));
+    }
+
+    #[test]
+    fn test_failed_target_message() {
+        assert_eq!(
+            None,
+            failed_target_message(&json!({"FailedEntryCount": 0, "FailedEntries": []}))
+        );
+        assert_eq!(
+            Some("Rule doesn't exist".to_string()),
+            failed_target_message(&json!({
+                "FailedEntryCount": 1,
+                "FailedEntries": [{"TargetId": "cargo-lambda", "ErrorMessage": "Rule doesn't exist"}]
+            }))
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use aws_sdk_s3::{
+    Client as S3Client,
+    primitives::{ByteStream, Length},
+    types::{CompletedMultipartUpload, CompletedPart},
+};
+use cargo_lambda_build::BinaryArchive;
+use cargo_lambda_interactive::progress::{HumanBytes, HumanDuration, Progress};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+
+/// Size of each part in multipart uploads.
+/// Archives smaller than this are uploaded with a single request.
+pub(crate) const PART_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Number of times that we try to upload a part before giving up on the upload.
+const MAX_PART_ATTEMPTS: u64 = 3;
+
+/// Upload the zip archive to S3, streaming it from disk.
+/// Big archives are uploaded in parts, so a failure only retries the part that failed.
+pub(crate) async fn upload_archive_to_s3(
+    s3_client: &S3Client,
+    bucket: &str,
+    key: &str,
+    tags: Option<String>,
+    binary_archive: &BinaryArchive,
+    progress: &Progress,
+) -> Result<()> {
+    let path = binary_archive.path.as_path();
+    let size = std::fs::metadata(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read zip file `{path:?}`"))?
+        .len();
+
+    if size > PART_SIZE {
+        return upload_in_parts(
+            s3_client, bucket, key, tags, path, size, PART_SIZE, progress,
+        )
+        .await;
+    }
+
+    debug!(bucket, key, size, "uploading zip to S3");
+    progress.set_message(&format!("uploading zip to S3 ({})", HumanBytes(size)));
+
+    let body = ByteStream::from_path(path)
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read zip file `{path:?}`"))?;
+
+    s3_client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(body)
+        .set_tagging(tags)
+        .send()
+        .await
+        .into_diagnostic()?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn upload_in_parts(
+    s3_client: &S3Client,
+    bucket: &str,
+    key: &str,
+    tags: Option<String>,
+    path: &Path,
+    size: u64,
+    part_size: u64,
+    progress: &Progress,
+) -> Result<()> {
+    debug!(bucket, key, size, part_size, "uploading zip to S3 in parts");
+    progress.set_message(&upload_message(0, size, None));
+
+    let upload = s3_client
+        .create_multipart_upload()
+        .bucket(bucket)
+        .key(key)
+        .set_tagging(tags)
+        .send()
+        .await
+        .into_diagnostic()?;
+
+    let upload_id = upload
+        .upload_id()
+        .ok_or_else(|| miette::miette!("S3 didn't return an id for the multipart upload"))?;
+
+    let parts = match upload_parts(
+        s3_client, bucket, key, upload_id, path, size, part_size, progress,
+    )
+    .await
+    {
+        Ok(parts) => parts,
+        Err(err) => {
+            // Abort the upload, so S3 doesn't keep the parts that we already uploaded.
+            if let Err(abort_err) = s3_client
+                .abort_multipart_upload()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .send()
+                .await
+            {
+                warn!(error = ?abort_err, upload_id, "failed to abort multipart upload");
+            }
+            return Err(err);
+        }
+    };
+
+    s3_client
+        .complete_multipart_upload()
+        .bucket(bucket)
+        .key(key)
+        .upload_id(upload_id)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .set_parts(Some(parts))
+                .build(),
+        )
+        .send()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to complete multipart upload")?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn upload_parts(
+    s3_client: &S3Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    path: &Path,
+    size: u64,
+    part_size: u64,
+    progress: &Progress,
+) -> Result<Vec<CompletedPart>> {
+    let started_at = Instant::now();
+    let mut parts = Vec::new();
+    let mut offset = 0;
+
+    while offset < size {
+        let part_number = parts.len() as i32 + 1;
+        let length = part_size.min(size - offset);
+
+        let mut attempt = 1;
+        let e_tag = loop {
+            let body = ByteStream::read_from()
+                .path(path)
+                .offset(offset)
+                .length(Length::Exact(length))
+                .build()
+                .await
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to read zip file `{path:?}`"))?;
+
+            let result = s3_client
+                .upload_part()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(body)
+                .send()
+                .await;
+
+            match result {
+                Ok(output) => break output.e_tag,
+                Err(err) if attempt < MAX_PART_ATTEMPTS => {
+                    warn!(error = ?err, part_number, attempt, "failed to upload part, retrying");
+                    sleep(Duration::from_secs(attempt)).await;
+                    attempt += 1;
+                }
+                Err(err) => {
+                    return Err(err)
+                        .into_diagnostic()
+                        .wrap_err_with(|| format!("failed to upload part {part_number}"));
+                }
+            }
+        };
+
+        parts.push(
+            CompletedPart::builder()
+                .set_e_tag(e_tag)
+                .part_number(part_number)
+                .build(),
+        );
+
+        offset += length;
+        progress.set_message(&upload_message(offset, size, Some(started_at.elapsed())));
+    }
+
+    Ok(parts)
+}
+
+/// Progress of the upload, with an estimation of the time left
+/// based on how long it took to upload the previous parts.
+fn upload_message(uploaded: u64, size: u64, elapsed: Option<Duration>) -> String {
+    let percent = uploaded * 100 / size.max(1);
+    let mut message = format!(
+        "uploading zip to S3: {} of {} ({percent}%)",
+        HumanBytes(uploaded),
+        HumanBytes(size)
+    );
+
+    if let Some(elapsed) = elapsed {
+        if uploaded > 0 && uploaded < size {
+            let left = elapsed.mul_f64((size - uploaded) as f64 / uploaded as f64);
+            message.push_str(&format!(", {} left", HumanDuration(left)));
+        }
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_credential_types::Credentials;
+    use aws_sdk_s3::config::{Config as S3Config, Region};
+    use aws_smithy_runtime::client::http::test_util::{ReplayEvent, StaticReplayClient};
+    use aws_smithy_types::body::SdkBody;
+    use http::{Request, Response};
+
+    #[test]
+    fn test_upload_message() {
+        assert_eq!(
+            "uploading zip to S3: 0 B of 32.00 MiB (0%)",
+            upload_message(0, 32 * 1024 * 1024, None)
+        );
+        assert_eq!(
+            "uploading zip to S3: 16.00 MiB of 64.00 MiB (25%), 30 seconds left",
+            upload_message(
+                16 * 1024 * 1024,
+                64 * 1024 * 1024,
+                Some(Duration::from_secs(10))
+            )
+        );
+        assert_eq!(
+            "uploading zip to S3: 64.00 MiB of 64.00 MiB (100%)",
+            upload_message(
+                64 * 1024 * 1024,
+                64 * 1024 * 1024,
+                Some(Duration::from_secs(10))
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_in_parts() {
+        let base_uri = "https://test-bucket.s3.us-east-1.amazonaws.com/test-key";
+
+        let create_request = Request::builder()
+            .uri(format!("{base_uri}?uploads"))
+            .method("POST")
+            .header("x-amz-tagging", "env=test")
+            .body(SdkBody::empty())
+            .unwrap();
+        let create_response = Response::builder()
+            .status(200)
+            .body(SdkBody::from(
+                "<InitiateMultipartUploadResult><Bucket>test-bucket</Bucket><Key>test-key</Key><UploadId>upload-id</UploadId></InitiateMultipartUploadResult>",
+            ))
+            .unwrap();
+
+        let mut events = vec![ReplayEvent::new(create_request, create_response)];
+        for part in 1..=3 {
+            let request = Request::builder()
+                .uri(format!(
+                    "{base_uri}?x-id=UploadPart&partNumber={part}&uploadId=upload-id"
+                ))
+                .method("PUT")
+                .body(SdkBody::empty())
+                .unwrap();
+            let response = Response::builder()
+                .status(200)
+                .header("ETag", format!("etag-{part}"))
+                .body(SdkBody::empty())
+                .unwrap();
+            events.push(ReplayEvent::new(request, response));
+        }
+
+        let complete_request = Request::builder()
+            .uri(format!("{base_uri}?uploadId=upload-id"))
+            .method("POST")
+            .body(SdkBody::from(
+                "<CompleteMultipartUpload xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Part><ETag>etag-1</ETag><PartNumber>1</PartNumber></Part><Part><ETag>etag-2</ETag><PartNumber>2</PartNumber></Part><Part><ETag>etag-3</ETag><PartNumber>3</PartNumber></Part></CompleteMultipartUpload>",
+            ))
+            .unwrap();
+        let complete_response = Response::builder()
+            .status(200)
+            .body(SdkBody::from(
+                "<CompleteMultipartUploadResult><Bucket>test-bucket</Bucket><Key>test-key</Key></CompleteMultipartUploadResult>",
+            ))
+            .unwrap();
+        events.push(ReplayEvent::new(complete_request, complete_response));
+
+        let http_client = StaticReplayClient::new(events);
+        let s3_config = S3Config::builder()
+            .http_client(http_client.clone())
+            .credentials_provider(Credentials::for_tests())
+            .region(Region::new("us-east-1"))
+            .build();
+        let s3_client = S3Client::from_conf(s3_config);
+
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(temp_file.path(), "0123456789").unwrap();
+
+        let progress = Progress::start("deploying function");
+        upload_in_parts(
+            &s3_client,
+            "test-bucket",
+            "test-key",
+            Some("env=test".to_string()),
+            temp_file.path(),
+            10,
+            4,
+            &progress,
+        )
+        .await
+        .unwrap();
+        progress.finish_and_clear();
+
+        http_client.assert_requests_match(&[]);
+    }
+}
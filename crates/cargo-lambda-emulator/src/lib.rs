@@ -0,0 +1,268 @@
+//! Run the Cargo Lambda emulator inside your own programs and test suites.
+//!
+//! The emulator compiles and runs your functions like `cargo lambda watch` does,
+//! but it listens on a port that the system picks, so tests can run several
+//! emulators at the same time without fighting for the same port.
+//!
+//! ```no_run
+//! use cargo_lambda_emulator::Emulator;
+//! use serde_json::json;
+//!
+//! # async fn example() -> miette::Result<()> {
+//! let emulator = Emulator::builder().bin("orders").spawn().await?;
+//!
+//! let order = emulator.invoke("orders", &json!({"id": 1})).await?;
+//! assert_eq!(order["id"], 1);
+//!
+//! emulator.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+use cargo_lambda_metadata::{
+    cargo::{load_metadata, watch::Watch},
+    config::{Config, ConfigOptions, load_config},
+};
+use cargo_lambda_watch::ServerHandle;
+use miette::{Diagnostic, IntoDiagnostic, Result, WrapErr};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
+use thiserror::Error;
+
+/// Error that a function returns when it fails to process an invocation.
+#[derive(Debug, Deserialize, Diagnostic, Error, PartialEq)]
+#[error("{error_type}: {error_message}")]
+pub struct FunctionError {
+    #[serde(rename = "errorType")]
+    pub error_type: String,
+    #[serde(rename = "errorMessage")]
+    pub error_message: String,
+}
+
+/// Builder to configure and start an [`Emulator`].
+#[derive(Debug)]
+pub struct EmulatorBuilder {
+    config: Watch,
+}
+
+impl Default for EmulatorBuilder {
+    fn default() -> Self {
+        Self::from(Watch::default())
+    }
+}
+
+/// Start from a watch configuration, to use any of the options of `cargo lambda watch`.
+/// The emulator always listens on a port in the loopback interface that the system picks.
+impl From<Watch> for EmulatorBuilder {
+    fn from(mut config: Watch) -> Self {
+        // The functions don't reload when the code changes,
+        // tests don't expect them to restart in the middle.
+        config.ignore_changes = true;
+        EmulatorBuilder { config }
+    }
+}
+
+impl EmulatorBuilder {
+    /// Path to the Cargo.toml file of the project with the functions.
+    pub fn manifest_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.cargo_opts.manifest_path = Some(path.into());
+        self
+    }
+
+    /// Package with the functions to run, in workspaces with several packages.
+    pub fn package(mut self, name: impl Into<String>) -> Self {
+        self.config.cargo_opts.packages.push(name.into());
+        self
+    }
+
+    /// Binary to run as a function. It can be called several times
+    /// to run several functions. All the binaries run when it's not called.
+    pub fn bin(mut self, name: impl Into<String>) -> Self {
+        self.config.cargo_opts.bin.push(name.into());
+        self
+    }
+
+    /// Binary that's already built to run instead of compiling the function.
+    pub fn bin_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.bin_path = Some(path.into());
+        self
+    }
+
+    /// Environment variable to set in the functions.
+    pub fn env(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        let var = format!("{}={}", key.as_ref(), value.as_ref());
+        self.config
+            .env_options
+            .env_var
+            .get_or_insert_with(Vec::new)
+            .push(var);
+        self
+    }
+
+    /// Compile the functions in release mode.
+    pub fn release(mut self, release: bool) -> Self {
+        self.config.cargo_opts.release = release;
+        self
+    }
+
+    /// Start the emulator, and return when it accepts invocations. The configuration
+    /// in the project's metadata applies to the emulator, like in `cargo lambda watch`.
+    pub async fn spawn(self) -> Result<Emulator> {
+        let metadata = load_metadata(self.config.manifest_path())?;
+        let options = ConfigOptions {
+            name: self.config.package(),
+            ..Default::default()
+        };
+        let args_config = Config {
+            watch: self.config,
+            ..Default::default()
+        };
+        let mut config = load_config(&args_config, &metadata, &options)?;
+
+        config.watch.invoke_address = Ipv4Addr::LOCALHOST.to_string();
+        config.watch.invoke_port = Some(0);
+        config.watch.port_file = None;
+
+        let server = cargo_lambda_watch::spawn(&config.watch, &config.env, &metadata).await?;
+
+        Ok(Emulator {
+            server,
+            client: Client::new(),
+        })
+    }
+}
+
+/// Emulator that runs in the background. It stops when it's dropped,
+/// use [`Emulator::shutdown`] to wait until the functions terminate.
+#[derive(Debug)]
+pub struct Emulator {
+    server: ServerHandle,
+    client: Client,
+}
+
+impl Emulator {
+    pub fn builder() -> EmulatorBuilder {
+        EmulatorBuilder::default()
+    }
+
+    /// Address where the emulator receives invocations.
+    pub fn addr(&self) -> SocketAddr {
+        self.server.invoke_addr()
+    }
+
+    /// URL to invoke a function with the Lambda Invoke API.
+    pub fn invoke_url(&self, function_name: &str) -> String {
+        format!(
+            "http://{}/2015-03-31/functions/{function_name}/invocations",
+            self.addr()
+        )
+    }
+
+    /// Invoke a function, and return its response. When the function
+    /// fails, the error includes a [`FunctionError`] with the reason.
+    pub async fn invoke<T: Serialize + ?Sized>(
+        &self,
+        function_name: &str,
+        payload: &T,
+    ) -> Result<Value> {
+        let payload = serde_json::to_vec(payload)
+            .into_diagnostic()
+            .wrap_err("failed to serialize the invocation payload")?;
+
+        let resp = self
+            .client
+            .post(self.invoke_url(function_name))
+            .body(payload)
+            .send()
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to invoke the function `{function_name}`"))?;
+
+        let status = resp.status();
+        let body = resp
+            .bytes()
+            .await
+            .into_diagnostic()
+            .wrap_err("failed to read the function's response")?;
+
+        parse_response(status, &body)
+    }
+
+    /// Stop the emulator after the invocations in flight complete,
+    /// and wait until the functions terminate.
+    pub async fn shutdown(self) -> Result<()> {
+        self.server.shutdown().await
+    }
+}
+
+fn parse_response(status: StatusCode, body: &[u8]) -> Result<Value> {
+    if status != StatusCode::OK {
+        return match serde_json::from_slice::<FunctionError>(body) {
+            Ok(error) => Err(error.into()),
+            Err(_) => Err(miette::miette!(
+                "the emulator responded with status {status}: {}",
+                String::from_utf8_lossy(body)
+            )),
+        };
+    }
+
+    if body.is_empty() {
+        return Ok(Value::Null);
+    }
+
+    serde_json::from_slice(body)
+        .into_diagnostic()
+        .wrap_err("the function's response is not valid JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let builder = Emulator::builder()
+            .manifest_path("functions/Cargo.toml")
+            .bin("orders")
+            .bin("payments")
+            .env("TABLE_NAME", "orders")
+            .release(true);
+
+        let config = builder.config;
+        assert!(config.ignore_changes);
+        assert!(config.cargo_opts.release);
+        assert_eq!(vec!["orders", "payments"], config.cargo_opts.bin);
+        assert_eq!(
+            PathBuf::from("functions/Cargo.toml"),
+            config.manifest_path()
+        );
+        assert_eq!(
+            Some(vec!["TABLE_NAME=orders".to_string()]),
+            config.env_options.env_var
+        );
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let value = parse_response(StatusCode::OK, br#"{"id":1}"#).unwrap();
+        assert_eq!(1, value["id"]);
+        assert_eq!(Value::Null, parse_response(StatusCode::OK, b"").unwrap());
+
+        let body = br#"{"errorType":"Runtime.ExitError","errorMessage":"exit status 101"}"#;
+        let err = parse_response(StatusCode::INTERNAL_SERVER_ERROR, body).unwrap_err();
+        assert_eq!(
+            Some(&FunctionError {
+                error_type: "Runtime.ExitError".into(),
+                error_message: "exit status 101".into(),
+            }),
+            err.downcast_ref::<FunctionError>()
+        );
+
+        let err = parse_response(StatusCode::BAD_GATEWAY, b"bad gateway").unwrap_err();
+        assert!(err.to_string().contains("502"), "{err}");
+    }
+}
@@ -0,0 +1,65 @@
+#![cfg(unix)]
+
+use cargo_lambda_emulator::Emulator;
+use serde_json::json;
+use std::{os::unix::fs::PermissionsExt, path::Path};
+
+/// Runtime that answers each invocation with its own payload.
+/// It uses `curl` to talk to the Runtime API.
+const ECHO_RUNTIME: &str = r#"#!/bin/sh
+api="$AWS_LAMBDA_RUNTIME_API/2018-06-01/runtime/invocation"
+case "$api" in
+  http://*) ;;
+  *) api="http://$api" ;;
+esac
+while true; do
+  headers=$(mktemp)
+  payload=$(curl -sS -D "$headers" "$api/next")
+  id=$(grep -i '^lambda-runtime-aws-request-id:' "$headers" | cut -d' ' -f2 | tr -d '\r')
+  rm -f "$headers"
+  curl -sS -X POST -d "$payload" "$api/$id/response" > /dev/null
+done
+"#;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invoke_prebuilt_binary() {
+    if std::process::Command::new("curl")
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        eprintln!("skipping test, curl is not installed");
+        return;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let bin_path = dir.path().join("echo");
+    std::fs::write(&bin_path, ECHO_RUNTIME).unwrap();
+    std::fs::set_permissions(&bin_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let manifest_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../tests/fixtures/single-binary-package/Cargo.toml");
+
+    let first = Emulator::builder()
+        .manifest_path(&manifest_path)
+        .bin_path(&bin_path)
+        .spawn()
+        .await
+        .unwrap();
+    let second = Emulator::builder()
+        .manifest_path(&manifest_path)
+        .bin_path(&bin_path)
+        .spawn()
+        .await
+        .unwrap();
+    assert_ne!(first.addr(), second.addr());
+
+    let response = first.invoke("echo", &json!({"id": 1})).await.unwrap();
+    assert_eq!(json!({"id": 1}), response);
+
+    let response = second.invoke("echo", &json!({"id": 2})).await.unwrap();
+    assert_eq!(json!({"id": 2}), response);
+
+    first.shutdown().await.unwrap();
+    second.shutdown().await.unwrap();
+}
@@ -1,4 +1,5 @@
 use crate::is_stdout_tty;
+pub use indicatif::{HumanBytes, HumanDuration};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::{borrow::Cow, time::Duration};
 
@@ -33,6 +34,14 @@ impl Progress {
         }
     }
 
+    /// Hide the progress bar while the function runs, like when asking the user for input.
+    pub fn suspend<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        match &self.bar {
+            Some(bar) => bar.suspend(f),
+            None => f(),
+        }
+    }
+
     pub fn finish_and_clear(&self) {
         if let Some(bar) = &self.bar {
             bar.finish_and_clear();
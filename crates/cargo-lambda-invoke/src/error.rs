@@ -15,6 +15,27 @@ pub enum InvokeError {
         "no data payload provided, use one of the data flags: `--data-file`, `--data-ascii`, `--data-example`"
     )]
     MissingPayload,
+    #[error("unable to find the crate of the function `{0}` to read its fixtures")]
+    #[diagnostic(help(
+        "run the command inside the project, or use `--manifest-path` to point to the project's Cargo.toml"
+    ))]
+    FunctionCrateNotFound(String),
+    #[error("the request context can only be added to payloads that are JSON objects")]
+    InvalidPayloadForRequestContext,
+    #[error("invalid query `{0}`: {1}")]
+    #[diagnostic(help(
+        "`--query` and `--assert` use the jq syntax, see https://jqlang.org/manual for reference"
+    ))]
+    InvalidQuery(String, String),
+    #[error("the response is not valid JSON, it cannot be used with `--query` or `--assert`")]
+    InvalidResponseForQuery(#[source] serde_json::Error),
+    #[error("query `{0}` failed: {1}")]
+    QueryFailed(String, String),
+    #[error("assertion `{0}` failed, the response produced: {1}")]
+    #[diagnostic(help(
+        "assertions must produce at least one value, and none of them can be `false` or `null`"
+    ))]
+    AssertionFailed(String, String),
     #[error("invalid error payload {0}")]
     InvalidErrorPayload(#[from] serde_json::Error),
 }
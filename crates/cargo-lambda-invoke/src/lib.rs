@@ -1,8 +1,13 @@
 use base64::{Engine as _, engine::general_purpose as b64};
-use cargo_lambda_metadata::DEFAULT_PACKAGE_FUNCTION;
+use cargo_lambda_metadata::{
+    DEFAULT_PACKAGE_FUNCTION,
+    cargo::{CargoMetadata, kind_bin_filter, load_metadata},
+    lambda::RequestContext,
+    net,
+};
 use cargo_lambda_remote::{
     RemoteConfig,
-    aws_sdk_lambda::{Client as LambdaClient, primitives::Blob},
+    aws_sdk_lambda::{Client as LambdaClient, primitives::Blob, types::LogType},
     tls::TlsOptions,
 };
 use clap::{Args, ValueHint};
@@ -12,10 +17,10 @@ use serde::Serialize;
 use serde_json::{from_str, to_string_pretty, value::Value};
 use std::{
     convert::TryFrom,
-    fs::{File, create_dir_all, read_to_string},
+    fs::{File, create_dir_all, read_dir, read_to_string},
     io::copy,
     net::IpAddr,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::{FromStr, from_utf8},
 };
 use strum_macros::{Display, EnumString};
@@ -24,8 +29,14 @@ use tracing::debug;
 mod error;
 use error::*;
 
+mod query;
+use query::Query;
+
 const EXAMPLES_URL: &str = "https://event-examples.cargo-lambda.info";
 
+/// Directory inside each crate where the function's fixtures are stored.
+const FIXTURES_DIR: &str = "fixtures";
+
 const LAMBDA_RUNTIME_CLIENT_CONTEXT: &str = "lambda-runtime-client-context";
 const LAMBDA_RUNTIME_COGNITO_IDENTITY: &str = "lambda-runtime-cognito-identity";
 
@@ -37,17 +48,32 @@ const LAMBDA_RUNTIME_COGNITO_IDENTITY: &str = "lambda-runtime-cognito-identity";
 pub struct Invoke {
     #[cfg_attr(
         target_os = "windows",
-        arg(short = 'a', long, default_value = "127.0.0.1")
+        arg(
+            short = 'a',
+            long,
+            env = "CARGO_LAMBDA_INVOKE_ADDRESS",
+            default_value = "127.0.0.1"
+        )
     )]
     #[cfg_attr(
         not(target_os = "windows"),
-        arg(short = 'a', long, default_value = "::1")
+        arg(
+            short = 'a',
+            long,
+            env = "CARGO_LAMBDA_INVOKE_ADDRESS",
+            default_value = "::1"
+        )
     )]
     /// Local address host (IPv4 or IPv6) to send invoke requests
     invoke_address: String,
 
     /// Local port to send invoke requests
-    #[arg(short = 'p', long, default_value = "9000")]
+    #[arg(
+        short = 'p',
+        long,
+        env = "CARGO_LAMBDA_INVOKE_PORT",
+        default_value = "9000"
+    )]
     invoke_port: u16,
 
     /// File to read the invoke payload from
@@ -58,10 +84,16 @@ pub struct Invoke {
     #[arg(short = 'A', long)]
     data_ascii: Option<String>,
 
-    /// Example payload from AWS Lambda Events
-    #[arg(short = 'E', long)]
+    /// Example payload to send. Fixtures in the `fixtures` directory of the function's crate
+    /// are used before the examples from AWS Lambda Events.
+    /// Use the flag without a name to list the fixtures available
+    #[arg(short = 'E', long, num_args = 0..=1, default_missing_value = "")]
     data_example: Option<String>,
 
+    /// Path to Cargo.toml, to find the fixtures of the function [default: Cargo.toml]
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    manifest_path: Option<PathBuf>,
+
     /// Invoke the function already deployed on AWS Lambda
     #[arg(short = 'R', long)]
     remote: bool,
@@ -69,6 +101,11 @@ pub struct Invoke {
     #[command(flatten)]
     remote_config: RemoteConfig,
 
+    /// Print the last 4 KB of logs that the remote invocation wrote, to the standard error.
+    /// The logs come from the version or alias selected with `--qualifier`
+    #[arg(long, requires = "remote")]
+    logs: bool,
+
     /// JSON string representing the client context for the function invocation
     #[arg(long)]
     client_context_ascii: Option<String>,
@@ -84,6 +121,20 @@ pub struct Invoke {
     #[command(flatten)]
     cognito: Option<CognitoIdentity>,
 
+    /// Path to a file with JSON fields to merge into the `requestContext` of the invoke payload
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    request_context: Option<PathBuf>,
+
+    /// jq expression to extract fields from the response, e.g. `.body | fromjson | .id`
+    #[arg(long, value_name = "EXPR")]
+    query: Option<String>,
+
+    /// jq expression that must produce a truthy value for the response, e.g. `.statusCode == 200`.
+    /// The command fails if the expression produces `false`, `null`, or nothing at all.
+    /// Use the flag multiple times to check several expressions
+    #[arg(long, value_name = "EXPR")]
+    assert: Vec<String>,
+
     /// Ignore data stored in the local cache
     #[arg(long, default_value_t = false)]
     skip_cache: bool,
@@ -126,6 +177,14 @@ impl Invoke {
     pub async fn run(&self) -> Result<()> {
         tracing::trace!(options = ?self, "invoking function");
 
+        // Compile the expressions before invoking the function, so mistakes fail fast.
+        let query = self.query.as_deref().map(Query::compile).transpose()?;
+        let assertions = self
+            .assert
+            .iter()
+            .map(|expr| Query::compile(expr))
+            .collect::<Result<Vec<_>, _>>()?;
+
         let data = if let Some(file) = &self.data_file {
             read_to_string(file)
                 .into_diagnostic()
@@ -133,49 +192,102 @@ impl Invoke {
         } else if let Some(data) = &self.data_ascii {
             data.clone()
         } else if let Some(example) = &self.data_example {
-            let name = example_name(example);
-
-            let cache = dirs::cache_dir()
-                .map(|p| p.join("cargo-lambda").join("invoke-fixtures").join(&name));
+            let fixtures = self.fixtures_dir();
+            if example.is_empty() {
+                return print_fixtures(&self.function_name, fixtures.as_deref());
+            }
 
-            match cache {
-                Some(cache) if !self.skip_cache && cache.exists() => {
-                    tracing::debug!(?cache, "using example from cache");
-                    read_to_string(cache)
+            match fixtures.map(|dir| fixture_path(&dir, example)) {
+                Some(path) if path.is_file() => {
+                    tracing::debug!(?path, "using fixture from the function's crate");
+                    read_to_string(path)
                         .into_diagnostic()
-                        .wrap_err("error reading data file")?
+                        .wrap_err("error reading fixture file")?
                 }
-                _ if self.skip_cache => download_example(&name, None, None).await?,
-                _ => download_example(&name, cache, None).await?,
+                _ => self.example_data(example).await?,
             }
         } else {
             return Err(InvokeError::MissingPayload.into());
         };
 
+        let data = match &self.request_context {
+            Some(path) => merge_request_context(&data, &RequestContext::load(path)?)?,
+            None => data,
+        };
+
         let text = if self.remote {
             self.invoke_remote(&data).await?
         } else {
             self.invoke_local(&data).await?
         };
 
-        let text = match &self.output_format {
-            OutputFormat::Text => text,
-            OutputFormat::Json => {
-                let obj: Value = from_str(&text)
-                    .into_diagnostic()
-                    .wrap_err("failed to serialize response into json")?;
+        let response = if query.is_some() || !assertions.is_empty() {
+            let value: Value = from_str(&text).map_err(InvokeError::InvalidResponseForQuery)?;
+            Some(value)
+        } else {
+            None
+        };
 
-                to_string_pretty(&obj)
-                    .into_diagnostic()
-                    .wrap_err("failed to format json output")?
+        let text = match (&query, &response) {
+            (Some(query), Some(response)) => {
+                let outputs = query.run(response)?;
+                format_query_outputs(&outputs, &self.output_format)?
             }
+            _ => format_response(text, &self.output_format)?,
         };
 
         println!("{text}");
 
+        if let Some(response) = &response {
+            for assertion in &assertions {
+                assertion.assert(response)?;
+            }
+        }
+
         Ok(())
     }
 
+    /// Download an example from AWS Lambda Events, or read it from the local cache.
+    async fn example_data(&self, example: &str) -> Result<String> {
+        let name = example_name(example);
+
+        let cache =
+            dirs::cache_dir().map(|p| p.join("cargo-lambda").join("invoke-fixtures").join(&name));
+
+        match cache {
+            Some(cache) if !self.skip_cache && cache.exists() => {
+                tracing::debug!(?cache, "using example from cache");
+                read_to_string(cache)
+                    .into_diagnostic()
+                    .wrap_err("error reading data file")
+            }
+            _ if self.skip_cache => download_example(&name, None, None).await,
+            _ => download_example(&name, cache, None).await,
+        }
+    }
+
+    /// Directory with the fixtures of the function, when the command runs inside its project.
+    fn fixtures_dir(&self) -> Option<PathBuf> {
+        let manifest_path = self
+            .manifest_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("Cargo.toml"));
+        if !manifest_path.is_file() {
+            return None;
+        }
+
+        match load_metadata(&manifest_path) {
+            Ok(metadata) => function_fixtures_dir(&metadata, &self.function_name),
+            Err(error) => {
+                debug!(
+                    ?error,
+                    "failed to load the project's metadata to find fixtures"
+                );
+                None
+            }
+        }
+    }
+
     async fn invoke_remote(&self, data: &str) -> Result<String> {
         if self.function_name == DEFAULT_PACKAGE_FUNCTION {
             return Err(InvokeError::InvalidFunctionName.into());
@@ -192,11 +304,17 @@ impl Invoke {
             .set_qualifier(self.remote_config.alias.clone())
             .payload(Blob::new(data.as_bytes()))
             .set_client_context(client_context)
+            .set_log_type(self.logs.then_some(LogType::Tail))
             .send()
             .await
             .into_diagnostic()
             .wrap_err("failed to invoke remote function")?;
 
+        debug!(version = ?resp.executed_version, "remote function invoked");
+        if let Some(log_result) = &resp.log_result {
+            eprintln!("{}", decode_log_result(log_result)?);
+        }
+
         if let Some(payload) = resp.payload {
             let blob = payload.into_inner();
             let data = from_utf8(&blob)
@@ -288,6 +406,133 @@ impl Invoke {
     }
 }
 
+fn format_response(text: String, format: &OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Text => Ok(text),
+        OutputFormat::Json => {
+            let obj: Value = from_str(&text)
+                .into_diagnostic()
+                .wrap_err("failed to serialize response into json")?;
+
+            to_string_pretty(&obj)
+                .into_diagnostic()
+                .wrap_err("failed to format json output")
+        }
+    }
+}
+
+/// Lambda returns the tail of the invocation logs encoded in base64.
+fn decode_log_result(log_result: &str) -> Result<String> {
+    let logs = b64::STANDARD
+        .decode(log_result)
+        .into_diagnostic()
+        .wrap_err("failed to decode the function logs")?;
+    Ok(String::from_utf8_lossy(&logs).trim_end().to_string())
+}
+
+/// Render each value produced by `--query` in its own line.
+/// Strings are printed without quotes in the text format, like `jq --raw-output`.
+fn format_query_outputs(outputs: &[Value], format: &OutputFormat) -> Result<String> {
+    let lines = outputs
+        .iter()
+        .map(|value| match (format, value) {
+            (OutputFormat::Text, Value::String(s)) => Ok(s.clone()),
+            (OutputFormat::Text, value) => Ok(value.to_string()),
+            (OutputFormat::Json, value) => to_string_pretty(value)
+                .into_diagnostic()
+                .wrap_err("failed to format json output"),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(lines.join("\n"))
+}
+
+fn merge_request_context(data: &str, context: &RequestContext) -> Result<String> {
+    let mut payload: Value = from_str(data)
+        .into_diagnostic()
+        .wrap_err("failed to parse the invoke payload to add the request context")?;
+
+    if !context.merge_into(&mut payload) {
+        return Err(InvokeError::InvalidPayloadForRequestContext.into());
+    }
+
+    Ok(payload.to_string())
+}
+
+/// Find the fixtures directory in the crate that includes the function's binary.
+/// Functions invoked without a name use the only crate in the project with binaries.
+fn function_fixtures_dir(metadata: &CargoMetadata, function_name: &str) -> Option<PathBuf> {
+    let mut packages = metadata.packages.iter().filter(|package| {
+        package.targets.iter().any(|target| {
+            kind_bin_filter(target)
+                && (function_name == DEFAULT_PACKAGE_FUNCTION || target.name == function_name)
+        })
+    });
+
+    let package = packages.next()?;
+    if packages.next().is_some() {
+        return None;
+    }
+
+    let dir = package.manifest_path.parent()?.join(FIXTURES_DIR);
+    Some(dir.into_std_path_buf())
+}
+
+fn fixture_path(dir: &Path, fixture: &str) -> PathBuf {
+    let name = fixture.strip_suffix(".json").unwrap_or(fixture);
+    dir.join(format!("{name}.json"))
+}
+
+/// Names of the JSON files in a fixtures directory, sorted alphabetically.
+fn list_fixtures(dir: &Path) -> Vec<String> {
+    let Ok(entries) = read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut fixtures = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .collect::<Vec<_>>();
+    fixtures.sort();
+    fixtures
+}
+
+/// Names of the fixtures in all the crates with binaries in the project, sorted alphabetically.
+pub fn fixture_names(metadata: &CargoMetadata) -> Vec<String> {
+    let mut fixtures = metadata
+        .packages
+        .iter()
+        .filter(|package| package.targets.iter().any(kind_bin_filter))
+        .filter_map(|package| package.manifest_path.parent())
+        .flat_map(|dir| list_fixtures(dir.join(FIXTURES_DIR).as_std_path()))
+        .collect::<Vec<_>>();
+    fixtures.sort();
+    fixtures.dedup();
+    fixtures
+}
+
+fn print_fixtures(function_name: &str, dir: Option<&Path>) -> Result<()> {
+    let Some(dir) = dir else {
+        return Err(InvokeError::FunctionCrateNotFound(function_name.to_string()).into());
+    };
+
+    let fixtures = list_fixtures(dir);
+    if fixtures.is_empty() {
+        println!(
+            "no fixtures found in {}, add JSON files to that directory to use them with `--data-example NAME`",
+            dir.display()
+        );
+        return Ok(());
+    }
+
+    println!("fixtures available in {}:", dir.display());
+    for fixture in fixtures {
+        println!("  {fixture}");
+    }
+    Ok(())
+}
+
 fn example_name(example: &str) -> String {
     let mut name = if example.starts_with("example-") {
         example.to_string()
@@ -309,7 +554,9 @@ async fn download_example(
     let target = format!("{authority}/{name}");
 
     tracing::debug!(?target, "downloading remote example");
-    let response = reqwest::get(&target)
+    let response = net::client()?
+        .get(&target)
+        .send()
         .await
         .into_diagnostic()
         .wrap_err("error dowloading example data")?;
@@ -406,6 +653,110 @@ mod test {
         assert_eq!(content, data);
     }
 
+    #[test]
+    fn test_merge_request_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("context.json");
+        std::fs::write(&path, r#"{"identity": {"sourceIp": "10.0.0.1"}}"#).unwrap();
+        let context = RequestContext::load(&path).unwrap();
+
+        let data = merge_request_context(
+            r#"{"path": "/", "requestContext": {"stage": "prod"}}"#,
+            &context,
+        )
+        .unwrap();
+        let payload: Value = from_str(&data).unwrap();
+        assert_eq!(payload["path"], "/");
+        assert_eq!(payload["requestContext"]["stage"], "prod");
+        assert_eq!(
+            payload["requestContext"]["identity"]["sourceIp"],
+            "10.0.0.1"
+        );
+
+        let err = merge_request_context("[]", &context).unwrap_err();
+        assert_eq!(
+            "the request context can only be added to payloads that are JSON objects",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_function_fixtures_dir() {
+        let metadata = load_metadata("../../tests/fixtures/workspace-package/Cargo.toml").unwrap();
+        let root = metadata.workspace_root.clone().into_std_path_buf();
+
+        assert_eq!(
+            Some(root.join("crate-2").join("fixtures")),
+            function_fixtures_dir(&metadata, "basic-lambda-2")
+        );
+        assert_eq!(None, function_fixtures_dir(&metadata, "unknown-lambda"));
+        assert_eq!(
+            None,
+            function_fixtures_dir(&metadata, DEFAULT_PACKAGE_FUNCTION)
+        );
+
+        let metadata =
+            load_metadata("../../tests/fixtures/single-binary-package/Cargo.toml").unwrap();
+        let root = metadata.workspace_root.clone().into_std_path_buf();
+        assert_eq!(
+            Some(root.join("fixtures")),
+            function_fixtures_dir(&metadata, DEFAULT_PACKAGE_FUNCTION)
+        );
+    }
+
+    #[test]
+    fn test_list_fixtures() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(list_fixtures(&dir.path().join("fixtures")).is_empty());
+
+        std::fs::write(dir.path().join("order-created.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("apigw-request.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("README.md"), "").unwrap();
+        std::fs::create_dir(dir.path().join("nested.json")).unwrap();
+
+        assert_eq!(
+            vec!["apigw-request".to_string(), "order-created".to_string()],
+            list_fixtures(dir.path())
+        );
+        assert_eq!(
+            dir.path().join("order-created.json"),
+            fixture_path(dir.path(), "order-created")
+        );
+        assert_eq!(
+            dir.path().join("order-created.json"),
+            fixture_path(dir.path(), "order-created.json")
+        );
+    }
+
+    #[test]
+    fn test_format_query_outputs() {
+        let outputs = vec![
+            Value::from("hello"),
+            serde_json::json!({"id": 1}),
+            Value::Null,
+        ];
+
+        assert_eq!(
+            "hello\n{\"id\":1}\nnull",
+            format_query_outputs(&outputs, &OutputFormat::Text).unwrap()
+        );
+        assert_eq!(
+            "\"hello\"\n{\n  \"id\": 1\n}\nnull",
+            format_query_outputs(&outputs, &OutputFormat::Json).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_log_result() {
+        let logs = "START RequestId: 8f5a Version: 3\nhello\nEND RequestId: 8f5a\n";
+        let encoded = b64::STANDARD.encode(logs);
+        assert_eq!(
+            "START RequestId: 8f5a Version: 3\nhello\nEND RequestId: 8f5a",
+            decode_log_result(&encoded).unwrap()
+        );
+        assert!(decode_log_result("not base64!").is_err());
+    }
+
     #[test]
     fn test_example_name() {
         assert_eq!(example_name("apigw-request"), "example-apigw-request.json");
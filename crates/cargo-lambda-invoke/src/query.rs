@@ -0,0 +1,169 @@
+use jaq_core::{
+    Compiler, Ctx, Filter, Native, RcIter,
+    load::{self, Arena, File, Loader},
+};
+use jaq_json::Val;
+use miette::Result;
+use serde_json::Value;
+
+use crate::error::InvokeError;
+
+/// jq filter to extract fields from the response of a function, or to check them.
+pub(crate) struct Query {
+    expr: String,
+    filter: Filter<Native<Val>>,
+}
+
+impl Query {
+    pub(crate) fn compile(expr: &str) -> Result<Query> {
+        let invalid = |reason: String| InvokeError::InvalidQuery(expr.to_string(), reason);
+
+        let program = File {
+            code: expr,
+            path: (),
+        };
+        let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+        let arena = Arena::default();
+        let modules = loader
+            .load(&arena, program)
+            .map_err(|errors| invalid(load_error(errors)))?;
+
+        let filter = Compiler::default()
+            .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+            .compile(modules)
+            .map_err(|errors| {
+                let undefined = errors
+                    .into_iter()
+                    .flat_map(|(_, errors)| errors)
+                    .map(|(name, kind)| format!("undefined {} `{name}`", kind.as_str()))
+                    .collect::<Vec<_>>();
+                invalid(undefined.join(", "))
+            })?;
+
+        Ok(Query {
+            expr: expr.to_string(),
+            filter,
+        })
+    }
+
+    /// Values that the filter outputs for the input.
+    pub(crate) fn run(&self, input: &Value) -> Result<Vec<Value>> {
+        let inputs = RcIter::new(core::iter::empty());
+        self.filter
+            .run((Ctx::new([], &inputs), Val::from(input.clone())))
+            .map(|output| {
+                output
+                    .map(Value::from)
+                    .map_err(|e| InvokeError::QueryFailed(self.expr.clone(), e.to_string()).into())
+            })
+            .collect()
+    }
+
+    /// Check that the filter outputs at least one value, and that
+    /// none of them are `false` or `null`, like `jq --exit-status`.
+    pub(crate) fn assert(&self, input: &Value) -> Result<()> {
+        let outputs = self.run(input)?;
+        let truthy = |v: &Value| !matches!(v, Value::Null | Value::Bool(false));
+        if !outputs.is_empty() && outputs.iter().all(truthy) {
+            return Ok(());
+        }
+
+        let outputs = outputs
+            .iter()
+            .map(Value::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(InvokeError::AssertionFailed(self.expr.clone(), outputs).into())
+    }
+}
+
+fn load_error(errors: load::Errors<&str, ()>) -> String {
+    let mut reasons = Vec::new();
+    for (_, error) in errors {
+        match error {
+            load::Error::Io(errors) => reasons.extend(errors.into_iter().map(|(_, e)| e)),
+            load::Error::Lex(errors) => reasons.extend(
+                errors
+                    .into_iter()
+                    .map(|(expect, rest)| format!("expected {} at `{rest}`", expect.as_str())),
+            ),
+            load::Error::Parse(errors) => reasons.extend(
+                errors
+                    .into_iter()
+                    .map(|(expect, rest)| format!("expected {} at `{rest}`", expect.as_str())),
+            ),
+        }
+    }
+    reasons.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_query_outputs() {
+        let response = json!({ "statusCode": 200, "items": [{ "id": 1 }, { "id": 2 }] });
+
+        let query = Query::compile(".items[].id").unwrap();
+        assert_eq!(query.run(&response).unwrap(), vec![json!(1), json!(2)]);
+
+        let query = Query::compile(".items | length").unwrap();
+        assert_eq!(query.run(&response).unwrap(), vec![json!(2)]);
+
+        let query = Query::compile(".statusCode | error").unwrap();
+        let err = query.run(&response).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InvokeError>(),
+            Some(InvokeError::QueryFailed(..))
+        ));
+    }
+
+    #[test]
+    fn test_query_assertions() {
+        let response = json!({ "statusCode": 200, "body": null });
+
+        Query::compile(".statusCode == 200")
+            .unwrap()
+            .assert(&response)
+            .unwrap();
+
+        let err = Query::compile(".statusCode == 404")
+            .unwrap()
+            .assert(&response)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InvokeError>(),
+            Some(InvokeError::AssertionFailed(expr, outputs))
+                if expr == ".statusCode == 404" && outputs == "false"
+        ));
+
+        for expr in [".body", "empty"] {
+            let err = Query::compile(expr).unwrap().assert(&response).unwrap_err();
+            assert!(
+                matches!(
+                    err.downcast_ref::<InvokeError>(),
+                    Some(InvokeError::AssertionFailed(..))
+                ),
+                "{expr}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_query() {
+        for expr in [".items[", "unknown_function(1)"] {
+            assert!(
+                matches!(
+                    Query::compile(expr)
+                        .map(|_| ())
+                        .unwrap_err()
+                        .downcast_ref::<InvokeError>(),
+                    Some(InvokeError::InvalidQuery(..))
+                ),
+                "{expr}"
+            );
+        }
+    }
+}
@@ -5,7 +5,10 @@ use clap::{Args, ValueHint};
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
 
-use crate::cargo::{count_common_options, serialize_common_options};
+use crate::{
+    cargo::{count_common_options, selection::BinarySelection, serialize_common_options},
+    size::SizeLimitOptions,
+};
 
 #[derive(Args, Clone, Debug, Default, Deserialize)]
 #[command(
@@ -23,13 +26,19 @@ pub struct Build {
     #[serde(default)]
     pub lambda_dir: Option<PathBuf>,
 
-    /// Shortcut for --target aarch64-unknown-linux-gnu
+    /// Place and name the artifacts the way other tools expect them,
+    /// acceptable values are [cargo-lambda, sam, cdk-asset]
     #[arg(long)]
     #[serde(default)]
+    pub output_layout: Option<OutputLayout>,
+
+    /// Shortcut for --target aarch64-unknown-linux-gnu
+    #[arg(long, conflicts_with_all = ["x86_64", "target"])]
+    #[serde(default)]
     pub arm64: bool,
 
     /// Shortcut for --target x86_64-unknown-linux-gnu
-    #[arg(long)]
+    #[arg(long, conflicts_with = "target")]
     #[serde(default)]
     pub x86_64: bool,
 
@@ -59,6 +68,18 @@ pub struct Build {
     #[serde(default)]
     pub compiler: Option<CompilerOptions>,
 
+    /// Version of Zig to build the project with, like `0.13.0`.
+    /// Cargo Lambda downloads it if it's not installed in the system
+    #[arg(long)]
+    #[serde(default)]
+    pub zig_version: Option<String>,
+
+    /// Allow downloading a Zig version that was released without a signature.
+    /// Unsigned releases are only verified with the checksum in Zig's release index
+    #[arg(long, requires = "zig_version")]
+    #[serde(default)]
+    pub allow_unsigned_zig: bool,
+
     /// Disable all default release optimizations
     #[arg(long)]
     #[serde(default)]
@@ -69,6 +90,52 @@ pub struct Build {
     #[serde(default)]
     pub include: Option<Vec<String>>,
 
+    /// Shell script to use as the function's `bootstrap`. The script runs before the Rust binary,
+    /// and it must `exec` the binary, which keeps its original name next to the script
+    #[arg(long, conflicts_with = "extension")]
+    #[serde(default)]
+    pub bootstrap_script: Option<PathBuf>,
+
+    /// Reuse the compressed files of the zip archive from a previous build
+    /// when their content doesn't change, instead of compressing them again
+    #[arg(long)]
+    #[serde(default)]
+    pub incremental_zip: bool,
+
+    /// Compress the zip archive with the fastest compression level.
+    /// The zip archive is bigger, but it takes less time to create it
+    #[arg(long)]
+    #[serde(default)]
+    pub fast_package: bool,
+
+    /// Analyze which crates and functions take the most space in the binaries.
+    /// Release builds keep their symbols, so the code can be attributed to them
+    #[arg(long)]
+    #[serde(default)]
+    pub analyze: bool,
+
+    /// Write the analysis of the binaries as JSON into this file, to check their size in CI
+    #[arg(long, requires = "analyze", value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    pub analyze_output: Option<PathBuf>,
+
+    /// Keep the debug information of the binaries in separate files in this directory,
+    /// and package stripped binaries. Use the files to symbolicate stack traces later
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    #[serde(default)]
+    pub split_debuginfo_out: Option<PathBuf>,
+
+    /// Set the `GIT_SHA`, `BUILD_TIME`, and `CARGO_LAMBDA_VERSION` environment variables
+    /// when compiling, so the functions can read them with `env!` or `option_env!`.
+    /// Deploys add them to the function's environment and tags
+    #[arg(long)]
+    #[serde(default)]
+    pub embed_build_info: bool,
+
+    #[command(flatten)]
+    #[serde(default, flatten)]
+    pub size_limits: SizeLimitOptions,
+
     #[command(flatten)]
     #[serde(default, flatten)]
     pub cargo_opts: CargoBuild,
@@ -83,6 +150,20 @@ pub enum OutputFormat {
     Zip,
 }
 
+/// Directory layout of the build artifacts.
+#[derive(Clone, Debug, Default, Deserialize, Display, EnumString, PartialEq, Serialize)]
+#[strum(ascii_case_insensitive, serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputLayout {
+    /// Each function in its own directory, `target/lambda/FUNCTION_NAME/bootstrap`
+    #[default]
+    CargoLambda,
+    /// The function's binary in `$ARTIFACTS_DIR/bootstrap`, like SAM's custom builds expect it
+    Sam,
+    /// The function's binary in `/asset-output/bootstrap`, like CDK's asset bundling expects it
+    CdkAsset,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Display, Eq, PartialEq, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum CompilerOptions {
@@ -131,6 +212,48 @@ impl Build {
     pub fn output_format(&self) -> &OutputFormat {
         self.output_format.as_ref().unwrap_or(&OutputFormat::Binary)
     }
+
+    pub fn output_layout(&self) -> &OutputLayout {
+        self.output_layout
+            .as_ref()
+            .unwrap_or(&OutputLayout::CargoLambda)
+    }
+
+    /// Binary targets to build. Examples are built instead
+    /// of binaries with the `--example` and `--examples` flags.
+    pub fn binary_selection(&self) -> BinarySelection {
+        BinarySelection {
+            packages: self.cargo_opts.packages.clone(),
+            exclude: self.cargo_opts.exclude.clone(),
+            bins: self.cargo_opts.bin.clone(),
+            examples: self.cargo_opts.examples || !self.cargo_opts.example.is_empty(),
+        }
+    }
+
+    /// Returns whether the architecture to build for is set,
+    /// with `arm64`, `x86_64`, or `target`.
+    pub fn has_architecture(&self) -> bool {
+        self.arm64 || self.x86_64 || !self.cargo_opts.target.is_empty()
+    }
+
+    /// Replace the architecture to build for with the one in `other`.
+    /// The flags in the command line take precedence over the
+    /// architecture pinned in the package metadata.
+    pub fn override_architecture(&mut self, other: &Build) {
+        self.arm64 = other.arm64;
+        self.x86_64 = other.x86_64;
+        self.cargo_opts.target = other.cargo_opts.target.clone();
+    }
+
+    /// Returns whether Cargo prints its messages as JSON,
+    /// so nothing else can be mixed with them in stdout.
+    pub fn json_message_format(&self) -> bool {
+        self.cargo_opts
+            .message_format
+            .iter()
+            .flat_map(|f| f.split(','))
+            .any(|f| f.trim().starts_with("json"))
+    }
 }
 
 impl Serialize for Build {
@@ -143,9 +266,19 @@ impl Serialize for Build {
         // Count how many fields we'll actually serialize
         let field_count = self.output_format.is_some() as usize
             + self.lambda_dir.is_some() as usize
+            + self.output_layout.is_some() as usize
             + self.flatten.is_some() as usize
             + self.compiler.is_some() as usize
+            + self.zig_version.is_some() as usize
+            + self.allow_unsigned_zig as usize
             + self.include.is_some() as usize
+            + self.bootstrap_script.is_some() as usize
+            + self.incremental_zip as usize
+            + self.fast_package as usize
+            + self.analyze as usize
+            + self.analyze_output.is_some() as usize
+            + self.split_debuginfo_out.is_some() as usize
+            + self.embed_build_info as usize
             + self.arm64 as usize
             + self.x86_64 as usize
             + self.extension as usize
@@ -165,6 +298,7 @@ impl Serialize for Build {
             + !self.cargo_opts.test.is_empty() as usize
             + self.cargo_opts.benches as usize
             + !self.cargo_opts.bench.is_empty() as usize
+            + self.size_limits.count_fields()
             + count_common_options(&self.cargo_opts.common);
 
         let mut state = serializer.serialize_struct("Build", field_count)?;
@@ -176,15 +310,45 @@ impl Serialize for Build {
         if let Some(ref lambda_dir) = self.lambda_dir {
             state.serialize_field("lambda_dir", lambda_dir)?;
         }
+        if let Some(ref output_layout) = self.output_layout {
+            state.serialize_field("output_layout", output_layout)?;
+        }
         if let Some(ref flatten) = self.flatten {
             state.serialize_field("flatten", flatten)?;
         }
         if let Some(ref compiler) = self.compiler {
             state.serialize_field("compiler", compiler)?;
         }
+        if let Some(ref zig_version) = self.zig_version {
+            state.serialize_field("zig_version", zig_version)?;
+        }
+        if self.allow_unsigned_zig {
+            state.serialize_field("allow_unsigned_zig", &true)?;
+        }
         if let Some(ref include) = self.include {
             state.serialize_field("include", include)?;
         }
+        if let Some(ref bootstrap_script) = self.bootstrap_script {
+            state.serialize_field("bootstrap_script", bootstrap_script)?;
+        }
+        if self.incremental_zip {
+            state.serialize_field("incremental_zip", &true)?;
+        }
+        if self.fast_package {
+            state.serialize_field("fast_package", &true)?;
+        }
+        if self.analyze {
+            state.serialize_field("analyze", &true)?;
+        }
+        if let Some(ref analyze_output) = self.analyze_output {
+            state.serialize_field("analyze_output", analyze_output)?;
+        }
+        if let Some(ref split_debuginfo_out) = self.split_debuginfo_out {
+            state.serialize_field("split_debuginfo_out", split_debuginfo_out)?;
+        }
+        if self.embed_build_info {
+            state.serialize_field("embed_build_info", &true)?;
+        }
 
         // Boolean fields
         if self.arm64 {
@@ -249,6 +413,7 @@ impl Serialize for Build {
         if !self.cargo_opts.bench.is_empty() {
             state.serialize_field("bench", &self.cargo_opts.bench)?;
         }
+        self.size_limits.serialize_fields::<S>(&mut state)?;
         serialize_common_options::<S>(&mut state, &self.cargo_opts.common)?;
 
         state.end()
@@ -273,6 +438,7 @@ mod tests {
     fn test_serialize_with_optional_fields() {
         let build = Build {
             lambda_dir: Some(PathBuf::from("/tmp/lambda")),
+            output_layout: Some(OutputLayout::CdkAsset),
             compiler: Some(CompilerOptions::Cross),
             include: Some(vec!["file1.txt".to_string(), "file2.txt".to_string()]),
             ..Default::default()
@@ -284,6 +450,7 @@ mod tests {
             serialized,
             json!({
                 "lambda_dir": "/tmp/lambda",
+                "output_layout": "cdk-asset",
                 "compiler": { "type": "cross" },
                 "include": ["file1.txt", "file2.txt"]
             })
@@ -349,6 +516,8 @@ mod tests {
             arm64: true,
             extension: true,
             compiler: Some(CompilerOptions::CargoZigbuild),
+            zig_version: Some("0.13.0".to_string()),
+            allow_unsigned_zig: true,
             include: Some(vec!["include1".to_string()]),
 
             // Cargo opts
@@ -374,6 +543,8 @@ mod tests {
                 "arm64": true,
                 "extension": true,
                 "compiler": { "type": "cargo_zigbuild" },
+                "zig_version": "0.13.0",
+                "allow_unsigned_zig": true,
                 "include": ["include1"],
                 "target": ["x86_64-unknown-linux-gnu"],
                 "features": ["feature1"],
@@ -381,4 +552,31 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_json_message_format() {
+        let mut build = Build::default();
+        assert!(!build.json_message_format());
+
+        build.cargo_opts.message_format = vec!["short".into()];
+        assert!(!build.json_message_format());
+
+        build.cargo_opts.message_format = vec!["json".into()];
+        assert!(build.json_message_format());
+
+        build.cargo_opts.message_format = vec!["short,json-diagnostic-rendered-ansi".into()];
+        assert!(build.json_message_format());
+    }
+
+    #[test]
+    fn test_output_layout() {
+        assert_eq!(&OutputLayout::CargoLambda, Build::default().output_layout());
+        assert_eq!(OutputLayout::Sam, "sam".parse().unwrap());
+        assert_eq!(OutputLayout::CdkAsset, "cdk-asset".parse().unwrap());
+        assert_eq!(OutputLayout::CargoLambda, "cargo-lambda".parse().unwrap());
+        assert_eq!("cdk-asset", OutputLayout::CdkAsset.to_string());
+
+        let build: Build = serde_json::from_value(json!({"output_layout": "sam"})).unwrap();
+        assert_eq!(&OutputLayout::Sam, build.output_layout());
+    }
 }
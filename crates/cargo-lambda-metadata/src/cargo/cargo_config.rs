@@ -0,0 +1,333 @@
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+/// Names of the configuration files that Cargo reads in each `.cargo` directory.
+const CONFIG_FILES: [&str; 2] = ["config.toml", "config"];
+
+/// Settings from Cargo's configuration files and environment variables
+/// that change how Cargo Lambda drives the builds.
+/// The target directory is not here because `cargo metadata` already resolves it.
+#[derive(Debug, Default, PartialEq)]
+pub struct CargoConfig {
+    /// Whether Cargo runs without accessing the network, `net.offline`
+    pub offline: bool,
+    /// Flags in `build.rustflags`
+    pub build_rustflags: Option<Vec<String>>,
+    /// Flags in `target.<triple>.rustflags`, by target triple
+    pub target_rustflags: HashMap<String, Vec<String>>,
+    /// Flags in `CARGO_TARGET_<TRIPLE>_RUSTFLAGS`, by environment variable
+    pub target_env_rustflags: HashMap<String, Vec<String>>,
+    /// Flags in `CARGO_ENCODED_RUSTFLAGS` or `RUSTFLAGS`
+    pub env_rustflags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    build: BuildSection,
+    #[serde(default)]
+    net: NetSection,
+    #[serde(default)]
+    target: HashMap<String, TargetSection>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct BuildSection {
+    rustflags: Option<Flags>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct NetSection {
+    offline: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TargetSection {
+    rustflags: Option<Flags>,
+}
+
+/// Cargo accepts flags as a space separated string, or as an array.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Flags {
+    String(String),
+    List(Vec<String>),
+}
+
+impl Flags {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Flags::String(s) => split_flags(&s),
+            Flags::List(list) => list,
+        }
+    }
+}
+
+impl CargoConfig {
+    /// Load the configuration that Cargo uses when it runs in `dir`:
+    /// the files in the `.cargo` directories of `dir` and its parents,
+    /// the file in `CARGO_HOME`, and the `CARGO_*` environment variables.
+    pub fn load(dir: &Path) -> CargoConfig {
+        let files = config_paths(dir)
+            .into_iter()
+            .filter_map(|path| {
+                let content = read_to_string(&path).ok()?;
+                match toml::from_str::<ConfigFile>(&content) {
+                    Ok(file) => Some(file),
+                    Err(error) => {
+                        warn!(?path, %error, "ignoring invalid Cargo configuration file");
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let config = CargoConfig::from_sources(files, env::vars().collect());
+        debug!(?config, "loaded Cargo configuration");
+        config
+    }
+
+    /// Merge the configuration files, from the most specific to the least specific,
+    /// and override them with the environment variables.
+    fn from_sources(files: Vec<ConfigFile>, vars: HashMap<String, String>) -> CargoConfig {
+        let var = |key: &str| vars.get(key).cloned();
+        let mut config = CargoConfig::default();
+        let mut offline = None;
+
+        // Cargo joins the arrays of all the files, and the most
+        // specific files take precedence for the other values.
+        for file in files.into_iter().rev() {
+            offline = file.net.offline.or(offline);
+            if let Some(flags) = file.build.rustflags {
+                config
+                    .build_rustflags
+                    .get_or_insert_with(Vec::new)
+                    .extend(flags.into_vec());
+            }
+            for (target, section) in file.target {
+                if let Some(flags) = section.rustflags {
+                    config
+                        .target_rustflags
+                        .entry(target)
+                        .or_default()
+                        .extend(flags.into_vec());
+                }
+            }
+        }
+
+        if let Some(value) = var("CARGO_NET_OFFLINE") {
+            offline = Some(value == "true");
+        }
+        config.offline = offline.unwrap_or_default();
+
+        if let Some(flags) = var("CARGO_BUILD_RUSTFLAGS") {
+            config.build_rustflags = Some(split_flags(&flags));
+        }
+        config.target_env_rustflags = vars
+            .iter()
+            .filter(|(key, _)| key.starts_with("CARGO_TARGET_") && key.ends_with("_RUSTFLAGS"))
+            .map(|(key, flags)| (key.clone(), split_flags(flags)))
+            .collect();
+
+        config.env_rustflags = var("CARGO_ENCODED_RUSTFLAGS")
+            .map(|flags| {
+                flags
+                    .split('\x1f')
+                    .filter(|f| !f.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .or_else(|| var("RUSTFLAGS").map(|flags| split_flags(&flags)));
+
+        config
+    }
+
+    /// Flags that Cargo passes to the compiler when it builds for the target.
+    /// Cargo uses the first source that sets flags: `CARGO_ENCODED_RUSTFLAGS`,
+    /// `RUSTFLAGS`, `target.<triple>.rustflags`, and `build.rustflags`.
+    pub fn rustflags(&self, target: &str) -> Vec<String> {
+        if let Some(flags) = &self.env_rustflags {
+            return flags.clone();
+        }
+        if let Some(flags) = self.target_env_rustflags.get(&target_rustflags_var(target)) {
+            return flags.clone();
+        }
+        if let Some(flags) = self.target_rustflags.get(target) {
+            return flags.clone();
+        }
+        self.build_rustflags.clone().unwrap_or_default()
+    }
+}
+
+/// Environment variable that sets `target.<triple>.rustflags`.
+fn target_rustflags_var(target: &str) -> String {
+    format!(
+        "CARGO_TARGET_{}_RUSTFLAGS",
+        target.to_uppercase().replace(['-', '.'], "_")
+    )
+}
+
+fn split_flags(flags: &str) -> Vec<String> {
+    flags.split_whitespace().map(String::from).collect()
+}
+
+/// Configuration files that Cargo reads, from the most specific to the least specific.
+fn config_paths(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = dir
+        .ancestors()
+        .map(|d| d.join(".cargo"))
+        .collect::<Vec<_>>();
+
+    let cargo_home = env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".cargo")));
+    if let Some(home) = cargo_home {
+        if !dirs.contains(&home) {
+            dirs.push(home);
+        }
+    }
+
+    dirs.into_iter()
+        .filter_map(|dir| {
+            CONFIG_FILES
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|path| path.is_file())
+        })
+        .collect()
+}
+
+/// Whether the flags set the CPU that the compiler optimizes the code for.
+pub fn sets_target_cpu(flags: &[String]) -> bool {
+    flags.iter().enumerate().any(|(idx, flag)| {
+        let codegen = match flag.as_str() {
+            "-C" | "--codegen" => flags.get(idx + 1).map(String::as_str),
+            flag => flag
+                .strip_prefix("-C")
+                .or_else(|| flag.strip_prefix("--codegen=")),
+        };
+        codegen.is_some_and(|c| c.trim_start().starts_with("target-cpu="))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(content: &str) -> ConfigFile {
+        toml::from_str(content).unwrap()
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_config_files() {
+        let files = vec![
+            file(
+                r#"
+[build]
+rustflags = ["-C", "target-cpu=native"]
+
+[target.aarch64-unknown-linux-gnu]
+rustflags = "-C link-arg=-s"
+"#,
+            ),
+            file(
+                r#"
+[build]
+rustflags = "--cfg tokio_unstable"
+
+[net]
+offline = true
+"#,
+            ),
+        ];
+
+        let config = CargoConfig::from_sources(files, HashMap::new());
+        assert!(config.offline);
+        assert_eq!(
+            Some(vec![
+                "--cfg".to_string(),
+                "tokio_unstable".to_string(),
+                "-C".to_string(),
+                "target-cpu=native".to_string(),
+            ]),
+            config.build_rustflags
+        );
+        assert_eq!(
+            vec!["-C".to_string(), "link-arg=-s".to_string()],
+            config.rustflags("aarch64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            config.build_rustflags.clone().unwrap(),
+            config.rustflags("x86_64-unknown-linux-gnu")
+        );
+    }
+
+    #[test]
+    fn test_environment_overrides() {
+        let files = vec![file("[net]\noffline = true\n[build]\nrustflags = \"-g\"")];
+        let env = vars(&[
+            ("CARGO_NET_OFFLINE", "false"),
+            (
+                "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUSTFLAGS",
+                "-C opt-level=s",
+            ),
+            ("RUSTFLAGS", "-C target-cpu=neoverse-n1"),
+        ]);
+
+        let config = CargoConfig::from_sources(files, env);
+        assert!(!config.offline);
+        assert_eq!(
+            vec!["-C".to_string(), "target-cpu=neoverse-n1".to_string()],
+            config.rustflags("aarch64-unknown-linux-gnu")
+        );
+
+        let env = vars(&[(
+            "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUSTFLAGS",
+            "-C opt-level=s",
+        )]);
+        let config = CargoConfig::from_sources(vec![], env);
+        assert_eq!(
+            vec!["-C".to_string(), "opt-level=s".to_string()],
+            config.rustflags("x86_64-unknown-linux-gnu")
+        );
+        assert!(config.rustflags("aarch64-unknown-linux-gnu").is_empty());
+
+        let env = vars(&[("CARGO_ENCODED_RUSTFLAGS", "-Ctarget-cpu=x86-64-v3\x1f-g")]);
+        let config = CargoConfig::from_sources(vec![], env);
+        assert_eq!(
+            vec!["-Ctarget-cpu=x86-64-v3".to_string(), "-g".to_string()],
+            config.rustflags("x86_64-unknown-linux-gnu")
+        );
+    }
+
+    #[test]
+    fn test_sets_target_cpu() {
+        let flags = |s: &str| split_flags(s);
+        assert!(sets_target_cpu(&flags("-C target-cpu=native")));
+        assert!(sets_target_cpu(&flags("-Ctarget-cpu=native")));
+        assert!(sets_target_cpu(&flags("--codegen target-cpu=native")));
+        assert!(sets_target_cpu(&flags("-g --codegen=target-cpu=native")));
+        assert!(!sets_target_cpu(&flags("-C link-arg=-s")));
+        assert!(!sets_target_cpu(&[]));
+    }
+
+    #[test]
+    fn test_target_rustflags_var() {
+        assert_eq!(
+            "CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_RUSTFLAGS",
+            target_rustflags_var("aarch64-unknown-linux-gnu")
+        );
+    }
+}
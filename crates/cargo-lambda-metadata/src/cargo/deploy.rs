@@ -4,19 +4,23 @@ use cargo_lambda_remote::{
 };
 use clap::{ArgAction, Args, ValueHint};
 use serde::{Deserialize, Serialize, ser::SerializeStruct};
-use std::{collections::HashMap, fmt::Debug, path::PathBuf};
+use std::{collections::HashMap, fmt::Debug, path::PathBuf, str::FromStr, time::Duration};
 use strum_macros::{Display, EnumString};
 
 use crate::{
-    cargo::deserialize_vec_or_map,
+    cargo::{deserialize_vec_or_map, selection::BinarySelection, watch::parse_long_duration},
     env::EnvOptions,
     error::MetadataError,
     lambda::{Memory, Timeout, Tracing},
+    runtime::{
+        DEFAULT_COMPATIBLE_RUNTIMES, is_lambda_runtime, is_provided_runtime,
+        latest_provided_runtime,
+    },
+    size::SizeLimitOptions,
 };
 
 const DEFAULT_MANIFEST_PATH: &str = "Cargo.toml";
-const DEFAULT_COMPATIBLE_RUNTIMES: &str = "provided.al2,provided.al2023";
-const DEFAULT_RUNTIME: &str = "provided.al2023";
+const DEFAULT_HANDLER: &str = "bootstrap";
 
 #[derive(Args, Clone, Debug, Default, Deserialize)]
 #[command(
@@ -32,6 +36,10 @@ pub struct Deploy {
     #[serde(flatten)]
     pub function_config: FunctionDeployConfig,
 
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub size_limits: SizeLimitOptions,
+
     /// Directory where the lambda binaries are located
     #[arg(short, long, value_hint = ValueHint::DirPath)]
     #[serde(default)]
@@ -43,10 +51,20 @@ pub struct Deploy {
     pub manifest_path: Option<PathBuf>,
 
     /// Name of the binary to deploy if it doesn't match the name that you want to deploy it with
-    #[arg(long, conflicts_with = "binary_path")]
-    #[serde(default)]
+    #[arg(long, visible_alias = "bin", conflicts_with = "binary_path")]
+    #[serde(default, alias = "bin")]
     pub binary_name: Option<String>,
 
+    /// Package to take the binary to deploy from (see `cargo help pkgid`)
+    #[arg(long = "package", value_name = "SPEC", conflicts_with = "binary_path")]
+    #[serde(default)]
+    pub packages: Vec<String>,
+
+    /// Package to exclude from the binaries to deploy (see `cargo help pkgid`)
+    #[arg(long, value_name = "SPEC", conflicts_with = "binary_path")]
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
     /// Local path of the binary to deploy if it doesn't match the target path generated by cargo-lambda-build
     #[arg(long, conflicts_with = "binary_name")]
     #[serde(default)]
@@ -62,6 +80,12 @@ pub struct Deploy {
     #[serde(default)]
     pub s3_key: Option<String>,
 
+    /// S3 location to write a record of each deploy to, like `s3://bucket/prefix`.
+    /// Each deploy is written as a JSON object under the prefix
+    #[arg(long, value_name = "S3_URI", value_parser = parse_audit_log)]
+    #[serde(default)]
+    pub audit_log: Option<String>,
+
     /// Whether the code that you're deploying is a Lambda Extension
     #[arg(long)]
     #[serde(default)]
@@ -83,6 +107,13 @@ pub struct Deploy {
     #[serde(default)]
     compatible_runtimes: Option<Vec<String>>,
 
+    /// Comma separated list with the architectures that the Lambda Extension is compatible with
+    /// (--compatible-architectures x86_64,arm64). When it includes several architectures,
+    /// the extension includes the binary built for each one of them
+    #[arg(long, value_delimiter = ',', value_parser = ["x86_64", "arm64"], requires = "extension")]
+    #[serde(default)]
+    pub compatible_architectures: Option<Vec<String>>,
+
     /// Format to render the output (text, or json)
     #[arg(short, long)]
     #[serde(default)]
@@ -94,16 +125,185 @@ pub struct Deploy {
     #[serde(default, alias = "tags", deserialize_with = "deserialize_vec_or_map")]
     pub tag: Option<Vec<String>>,
 
+    /// Tag the function with the git commit of the project, and whether the working tree
+    /// has uncommitted changes (git:commit=<sha>, git:dirty=<true|false>)
+    #[arg(long)]
+    #[serde(default)]
+    pub git_tags: bool,
+
+    /// Use the subject of the project's latest git commit as the description
+    /// of the new function version, when the deploy doesn't set a description
+    #[arg(long, conflicts_with = "description")]
+    #[serde(default)]
+    pub git_description: bool,
+
+    /// Remove tags from an existing function when they're not in the list of tags to apply.
+    /// Tags managed by AWS, with the `aws:` prefix, are never removed
+    #[arg(long)]
+    #[serde(default)]
+    pub prune_tags: bool,
+
+    /// CloudFormation stack output to set as an environment variable in the function,
+    /// with the format `StackName:OutputKey=ENV_VAR`. It can be used multiple times
+    #[arg(long, value_name = "STACK_OUTPUT", value_parser = parse_stack_output)]
+    #[serde(default)]
+    pub resolve_output: Option<Vec<String>>,
+
+    /// Change the architecture of an existing function when it doesn't match the architecture
+    /// of the binary. Without this flag, the deploy fails when the architectures don't match
+    #[arg(long)]
+    #[serde(default)]
+    pub update_architecture: bool,
+
+    /// Only deploy the environment variables declared in the Cargo metadata. Variables from
+    /// env files, the global config file, and the command line are ignored
+    #[arg(long)]
+    #[serde(default)]
+    pub env_from_metadata_only: bool,
+
+    /// Fail the deploy when an environment variable looks like a local path or a secret,
+    /// instead of showing a warning
+    #[arg(long)]
+    #[serde(default)]
+    pub strict_env: bool,
+
+    /// Comma separated list of AWS account ids where the function can be deployed.
+    /// The deploy fails if the credentials belong to a different account
+    #[arg(long, value_delimiter = ',', visible_alias = "allowed-account")]
+    #[serde(default)]
+    pub allowed_accounts: Option<Vec<String>>,
+
+    /// Comma separated list of AWS regions where the function can be deployed.
+    /// The deploy fails if the region is not in the list
+    #[arg(long, value_delimiter = ',', visible_alias = "allowed-region")]
+    #[serde(default)]
+    pub allowed_regions: Option<Vec<String>>,
+
+    /// Deploy even if the account or the region are not in the allowed lists
+    #[arg(long)]
+    #[serde(skip)]
+    pub i_know_what_im_doing: bool,
+
+    /// Apply changes to the runtime, architecture, or role of an existing function without asking for confirmation
+    #[arg(short, long)]
+    #[serde(skip)]
+    pub yes: bool,
+
+    /// Don't ask for the AWS profile and region when they're not set,
+    /// use the default credentials chain instead
+    #[arg(long)]
+    #[serde(skip)]
+    pub no_interactive: bool,
+
     /// Option to add one or more files and directories to include in the zip file to upload.
     #[arg(short, long)]
     #[serde(default)]
     pub include: Option<Vec<String>>,
 
+    /// Shell script to use as the function's `bootstrap`. The script runs before the Rust binary,
+    /// and it must `exec` the binary, which keeps its original name next to the script
+    #[arg(long, conflicts_with = "extension")]
+    #[serde(default)]
+    pub bootstrap_script: Option<PathBuf>,
+
+    /// Reuse the compressed files of the zip archive from a previous build
+    /// when their content doesn't change, instead of compressing them again
+    #[arg(long)]
+    #[serde(default)]
+    pub incremental_zip: bool,
+
+    /// Compress the zip archive with the fastest compression level.
+    /// The zip archive is bigger, but it takes less time to create it
+    #[arg(long)]
+    #[serde(default)]
+    pub fast_package: bool,
+
+    /// Directory where `cargo lambda build --split-debuginfo-out` put the debug information
+    /// of the binary. The debug file is uploaded next to the code when it's stored in S3
+    #[arg(long, conflicts_with = "extension", value_hint = ValueHint::DirPath)]
+    #[serde(default)]
+    pub split_debuginfo_out: Option<PathBuf>,
+
+    /// Verify that the SHA256 hash of the code that Lambda reports after the upload
+    /// matches the hash of the local zip file, and fail the deploy when they don't match
+    #[arg(long)]
+    #[serde(default)]
+    pub verify_checksum: bool,
+
+    /// File with a payload to invoke the function with after it's deployed.
+    /// The deploy fails when the invocation returns an error, and the alias is
+    /// rolled back to the version that it pointed to before the deploy
+    #[arg(long, value_name = "PAYLOAD", conflicts_with = "extension", value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    pub smoke_test: Option<PathBuf>,
+
+    /// Shift a percentage of the alias traffic to the new version during an interval, like `10%:5m`.
+    /// The new version is promoted if it doesn't report errors in CloudWatch during
+    /// the interval, and the alias is rolled back otherwise
+    #[arg(long, value_name = "PERCENTAGE:INTERVAL", value_parser = parse_canary, requires = "alias", conflicts_with = "extension")]
+    #[serde(default)]
+    pub canary: Option<String>,
+
+    /// Name of a CloudWatch alarm that must not go into ALARM state while the new version is verified.
+    /// The alarms are checked during the canary interval, or after the deploy without a canary,
+    /// and the alias is rolled back when any of them goes off. Use the flag multiple times to check several alarms
+    #[arg(long, value_name = "ALARM_NAME", conflicts_with = "extension")]
+    #[serde(default)]
+    pub gate_alarm: Option<Vec<String>>,
+
+    /// Principal allowed to invoke the function, added to its resource-based policy.
+    /// It accepts an AWS account id, a service principal like `events.amazonaws.com`,
+    /// the ARN of an IAM principal in another account, or the ARN of the resource
+    /// that invokes the function, like an API Gateway API or an EventBridge rule.
+    /// Use the flag multiple times to allow several principals
+    #[arg(long, value_name = "PRINCIPAL_OR_ARN", value_parser = parse_invoke_permission, conflicts_with = "extension")]
+    #[serde(default)]
+    pub allow_invoke_from: Option<Vec<String>>,
+
+    /// Trigger to create after the deploy, with the permission to invoke the function.
+    /// Use `eventbridge:<SCHEDULE>` for an EventBridge rule with a schedule expression,
+    /// like `eventbridge:rate(5 minutes)`, or `api:HTTP` for an API Gateway HTTP API
+    /// that sends all its requests to the function. Use the flag multiple times to create several triggers
+    #[arg(long, value_name = "TYPE:CONFIG", value_parser = parse_trigger, conflicts_with = "extension")]
+    #[serde(default)]
+    pub trigger: Option<Vec<String>>,
+
+    /// Domain name to serve the function URL from, like `api.example.com`. The deploy requests a certificate for
+    /// the domain, and creates a CloudFront distribution in front of the function URL. The DNS records are created
+    /// when the domain has a public hosted zone in Route 53. It requires `--enable-function-url`
+    #[arg(long, value_name = "DOMAIN", value_parser = parse_custom_domain, conflicts_with = "extension")]
+    #[serde(default)]
+    pub custom_domain: Option<String>,
+
+    /// Deploy the same binary as several functions, one for each name in the list, like a function for each tenant.
+    /// Each function can have its own environment variables in the `instances` table of the metadata
+    #[arg(long, value_name = "NAME", value_delimiter = ',', value_parser = parse_instance, conflicts_with_all = ["extension", "name", "custom_domain", "plan_out", "apply"])]
+    #[serde(default, deserialize_with = "deserialize_instances")]
+    pub instances: Option<Vec<Instance>>,
+
     /// Perform all the operations to locate and package the binary to deploy, but don't do the final deploy.
     #[arg(long, alias = "dry-run")]
     #[serde(default)]
     pub dry: bool,
 
+    /// Print the AWS API calls that the deploy would perform, with their request payloads, during a dry run.
+    /// Secrets, like the values of the environment variables, are redacted
+    #[arg(long, requires = "dry")]
+    #[serde(default)]
+    pub show_api_calls: bool,
+
+    /// Write a plan with the AWS API calls that the deploy would perform to a file, without deploying anything.
+    /// The plan records the state of the function in AWS, so it can be reviewed, and applied later with `--apply`
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["dry", "apply"])]
+    #[serde(skip)]
+    pub plan_out: Option<PathBuf>,
+
+    /// Deploy the plan written with `--plan-out`. The deploy fails if it doesn't match the plan,
+    /// or if the function changed in AWS since the plan was written
+    #[arg(long, value_name = "PATH", conflicts_with = "dry")]
+    #[serde(skip)]
+    pub apply: Option<PathBuf>,
+
     /// Name of the function or extension to deploy
     #[arg(value_name = "NAME")]
     #[serde(default)]
@@ -121,6 +321,17 @@ impl Deploy {
             .unwrap_or_else(default_manifest_path)
     }
 
+    /// Binary targets that can be deployed, when the binary to deploy
+    /// is not set with its name, or with its path.
+    pub fn binary_selection(&self) -> BinarySelection {
+        BinarySelection {
+            packages: self.packages.clone(),
+            exclude: self.exclude.clone(),
+            bins: self.binary_name.iter().cloned().collect(),
+            examples: false,
+        }
+    }
+
     pub fn output_format(&self) -> OutputFormat {
         self.output_format.clone().unwrap_or_default()
     }
@@ -149,6 +360,11 @@ impl Deploy {
         }
     }
 
+    /// Add tags to the list of tags to apply to the function.
+    pub fn append_tags(&mut self, tags: Vec<String>) {
+        self.tag.get_or_insert_with(Vec::new).extend(tags);
+    }
+
     pub fn s3_tags(&self) -> Option<String> {
         match &self.tag {
             None => None,
@@ -172,9 +388,74 @@ impl Deploy {
         Ok(Some(builder.set_variables(Some(env)).build()))
     }
 
+    pub fn audit_log(&self) -> Result<Option<AuditLog>, MetadataError> {
+        self.audit_log
+            .as_deref()
+            .map(AuditLog::from_str)
+            .transpose()
+    }
+
+    pub fn canary(&self) -> Result<Option<Canary>, MetadataError> {
+        self.canary.as_deref().map(Canary::from_str).transpose()
+    }
+
+    pub fn gate_alarms(&self) -> &[String] {
+        self.gate_alarm.as_deref().unwrap_or_default()
+    }
+
+    pub fn invoke_permissions(&self) -> Result<Vec<InvokePermission>, MetadataError> {
+        self.allow_invoke_from
+            .iter()
+            .flatten()
+            .map(|permission| InvokePermission::from_str(permission))
+            .collect()
+    }
+
+    pub fn triggers(&self) -> Result<Vec<Trigger>, MetadataError> {
+        self.trigger
+            .iter()
+            .flatten()
+            .map(|trigger| Trigger::from_str(trigger))
+            .collect()
+    }
+
+    pub fn custom_domain(&self) -> Result<Option<String>, MetadataError> {
+        self.custom_domain
+            .as_deref()
+            .map(parse_custom_domain)
+            .transpose()
+    }
+
+    pub fn stack_outputs(&self) -> Result<Vec<StackOutput>, MetadataError> {
+        self.resolve_output
+            .iter()
+            .flatten()
+            .map(|output| StackOutput::from_str(output))
+            .collect()
+    }
+
     pub fn publish_code_without_description(&self) -> bool {
         self.function_config.description.is_none()
     }
+
+    /// Configuration to deploy one of the instances. The environment variables of the
+    /// instance take precedence over the rest of the variables of the deploy.
+    pub fn instance_config(&self, instance: &Instance) -> Deploy {
+        let mut config = self.clone();
+        config.name = Some(instance.name.clone());
+        config.instances = None;
+
+        if !instance.env.is_empty() {
+            config
+                .function_config
+                .env_options
+                .get_or_insert_with(EnvOptions::default)
+                .env_var
+                .get_or_insert_with(Vec::new)
+                .extend(instance.env.iter().cloned());
+        }
+        config
+    }
 }
 
 impl Serialize for Deploy {
@@ -188,18 +469,45 @@ impl Serialize for Deploy {
             + self.lambda_dir.is_some() as usize
             + self.binary_path.is_some() as usize
             + self.binary_name.is_some() as usize
+            + !self.packages.is_empty() as usize
+            + !self.exclude.is_empty() as usize
             + self.s3_bucket.is_some() as usize
             + self.s3_key.is_some() as usize
+            + self.audit_log.is_some() as usize
             + self.extension as usize
             + self.internal as usize
             + self.compatible_runtimes.is_some() as usize
+            + self.compatible_architectures.is_some() as usize
             + self.output_format.is_some() as usize
             + self.tag.is_some() as usize
+            + self.git_tags as usize
+            + self.git_description as usize
+            + self.prune_tags as usize
+            + self.resolve_output.is_some() as usize
+            + self.update_architecture as usize
+            + self.env_from_metadata_only as usize
+            + self.strict_env as usize
+            + self.allowed_accounts.is_some() as usize
+            + self.allowed_regions.is_some() as usize
             + self.include.is_some() as usize
+            + self.bootstrap_script.is_some() as usize
+            + self.incremental_zip as usize
+            + self.fast_package as usize
+            + self.split_debuginfo_out.is_some() as usize
+            + self.verify_checksum as usize
+            + self.smoke_test.is_some() as usize
+            + self.canary.is_some() as usize
+            + self.gate_alarm.is_some() as usize
+            + self.allow_invoke_from.is_some() as usize
+            + self.trigger.is_some() as usize
+            + self.custom_domain.is_some() as usize
+            + self.instances.is_some() as usize
             + self.dry as usize
+            + self.show_api_calls as usize
             + self.name.is_some() as usize
             + self.remote_config.count_fields()
-            + self.function_config.count_fields();
+            + self.function_config.count_fields()
+            + self.size_limits.count_fields();
 
         let mut state = serializer.serialize_struct("Deploy", len)?;
 
@@ -215,12 +523,21 @@ impl Serialize for Deploy {
         if let Some(ref name) = self.binary_name {
             state.serialize_field("binary_name", name)?;
         }
+        if !self.packages.is_empty() {
+            state.serialize_field("packages", &self.packages)?;
+        }
+        if !self.exclude.is_empty() {
+            state.serialize_field("exclude", &self.exclude)?;
+        }
         if let Some(ref bucket) = self.s3_bucket {
             state.serialize_field("s3_bucket", bucket)?;
         }
         if let Some(ref key) = self.s3_key {
             state.serialize_field("s3_key", key)?;
         }
+        if let Some(ref audit_log) = self.audit_log {
+            state.serialize_field("audit_log", audit_log)?;
+        }
         if self.extension {
             state.serialize_field("extension", &self.extension)?;
         }
@@ -230,29 +547,419 @@ impl Serialize for Deploy {
         if let Some(ref runtimes) = self.compatible_runtimes {
             state.serialize_field("compatible_runtimes", runtimes)?;
         }
+        if let Some(ref architectures) = self.compatible_architectures {
+            state.serialize_field("compatible_architectures", architectures)?;
+        }
         if let Some(ref format) = self.output_format {
             state.serialize_field("output_format", format)?;
         }
         if let Some(ref tag) = self.tag {
             state.serialize_field("tag", tag)?;
         }
+        if self.git_tags {
+            state.serialize_field("git_tags", &self.git_tags)?;
+        }
+        if self.git_description {
+            state.serialize_field("git_description", &self.git_description)?;
+        }
+        if self.prune_tags {
+            state.serialize_field("prune_tags", &self.prune_tags)?;
+        }
+        if let Some(ref outputs) = self.resolve_output {
+            state.serialize_field("resolve_output", outputs)?;
+        }
+        if self.update_architecture {
+            state.serialize_field("update_architecture", &self.update_architecture)?;
+        }
+        if self.env_from_metadata_only {
+            state.serialize_field("env_from_metadata_only", &self.env_from_metadata_only)?;
+        }
+        if self.strict_env {
+            state.serialize_field("strict_env", &self.strict_env)?;
+        }
+        if let Some(ref accounts) = self.allowed_accounts {
+            state.serialize_field("allowed_accounts", accounts)?;
+        }
+        if let Some(ref regions) = self.allowed_regions {
+            state.serialize_field("allowed_regions", regions)?;
+        }
         if let Some(ref include) = self.include {
             state.serialize_field("include", include)?;
         }
+        if let Some(ref script) = self.bootstrap_script {
+            state.serialize_field("bootstrap_script", script)?;
+        }
+        if self.incremental_zip {
+            state.serialize_field("incremental_zip", &true)?;
+        }
+        if self.fast_package {
+            state.serialize_field("fast_package", &true)?;
+        }
+        if let Some(ref dir) = self.split_debuginfo_out {
+            state.serialize_field("split_debuginfo_out", dir)?;
+        }
+        if self.verify_checksum {
+            state.serialize_field("verify_checksum", &true)?;
+        }
+        if let Some(ref payload) = self.smoke_test {
+            state.serialize_field("smoke_test", payload)?;
+        }
+        if let Some(ref canary) = self.canary {
+            state.serialize_field("canary", canary)?;
+        }
+        if let Some(ref alarms) = self.gate_alarm {
+            state.serialize_field("gate_alarm", alarms)?;
+        }
+        if let Some(ref allow_invoke_from) = self.allow_invoke_from {
+            state.serialize_field("allow_invoke_from", allow_invoke_from)?;
+        }
+        if let Some(ref trigger) = self.trigger {
+            state.serialize_field("trigger", trigger)?;
+        }
+        if let Some(ref custom_domain) = self.custom_domain {
+            state.serialize_field("custom_domain", custom_domain)?;
+        }
+        if let Some(ref instances) = self.instances {
+            state.serialize_field("instances", instances)?;
+        }
         if self.dry {
             state.serialize_field("dry", &self.dry)?;
         }
+        if self.show_api_calls {
+            state.serialize_field("show_api_calls", &true)?;
+        }
         if let Some(ref name) = self.name {
             state.serialize_field("name", name)?;
         }
 
         self.remote_config.serialize_fields::<S>(&mut state)?;
         self.function_config.serialize_fields::<S>(&mut state)?;
+        self.size_limits.serialize_fields::<S>(&mut state)?;
 
         state.end()
     }
 }
 
+fn parse_custom_domain(value: &str) -> Result<String, MetadataError> {
+    let labels = value.split('.').collect::<Vec<_>>();
+    let valid = labels.len() > 1
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if !valid {
+        return Err(MetadataError::InvalidCustomDomain(value.to_string()));
+    }
+    Ok(value.to_ascii_lowercase())
+}
+
+/// Function deployed from the same binary as other functions,
+/// with its own name and environment variables.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Instance {
+    pub name: String,
+    /// Environment variables in `KEY=VALUE` format
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<String>,
+}
+
+impl FromStr for Instance {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(MetadataError::InvalidInstance(s.to_string()));
+        }
+
+        Ok(Instance {
+            name: name.to_string(),
+            env: Vec::new(),
+        })
+    }
+}
+
+fn parse_instance(value: &str) -> Result<Instance, MetadataError> {
+    Instance::from_str(value)
+}
+
+/// Instances can be a list of names, a list of tables with a `name` and an `env`,
+/// or a table with the names as keys and the `env` of each instance as values.
+fn deserialize_instances<'de, D>(deserializer: D) -> Result<Option<Vec<Instance>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+    use serde_json::Value;
+
+    fn instance<E: Error>(name: &str, config: Option<Value>) -> Result<Instance, E> {
+        let mut instance = Instance::from_str(name).map_err(E::custom)?;
+        if let Some(env) = config.and_then(|mut config| config.get_mut("env").map(Value::take)) {
+            instance.env = deserialize_vec_or_map(env)
+                .map_err(E::custom)?
+                .unwrap_or_default();
+        }
+        Ok(instance)
+    }
+
+    match Value::deserialize(deserializer)? {
+        Value::Array(arr) => arr
+            .into_iter()
+            .map(|value| match value {
+                Value::String(name) => instance(&name, None),
+                Value::Object(mut config) => match config.remove("name") {
+                    Some(Value::String(name)) => instance(&name, Some(Value::Object(config))),
+                    _ => Err(D::Error::custom("missing the name of the instance")),
+                },
+                value => Err(D::Error::custom(format!("invalid instance `{value}`"))),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some),
+        Value::Object(map) => map
+            .into_iter()
+            .map(|(name, config)| instance(&name, Some(config)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Location in S3 where the deploy records are written.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditLog {
+    pub bucket: String,
+    pub prefix: String,
+}
+
+impl FromStr for AuditLog {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MetadataError::InvalidAuditLog(s.to_string());
+
+        let location = s.strip_prefix("s3://").ok_or_else(invalid)?;
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        if bucket.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(AuditLog {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+fn parse_audit_log(value: &str) -> Result<String, MetadataError> {
+    AuditLog::from_str(value)?;
+    Ok(value.to_string())
+}
+
+/// CloudFormation stack output to set as an environment variable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackOutput {
+    pub stack_name: String,
+    pub output_key: String,
+    pub env_var: String,
+}
+
+impl FromStr for StackOutput {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MetadataError::InvalidStackOutput(s.to_string());
+
+        let (output, env_var) = s.split_once('=').ok_or_else(invalid)?;
+        let (stack_name, output_key) = output.split_once(':').ok_or_else(invalid)?;
+        if stack_name.is_empty() || output_key.is_empty() || env_var.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(StackOutput {
+            stack_name: stack_name.to_string(),
+            output_key: output_key.to_string(),
+            env_var: env_var.to_string(),
+        })
+    }
+}
+
+fn parse_stack_output(value: &str) -> Result<String, MetadataError> {
+    StackOutput::from_str(value)?;
+    Ok(value.to_string())
+}
+
+/// Statement in the function's resource-based policy that allows a principal to invoke it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvokePermission {
+    /// Account id, service principal, or IAM principal ARN
+    pub principal: String,
+    /// ARN of the resource that invokes the function, for service principals
+    pub source_arn: Option<String>,
+    /// Account that owns the resource, for service principals
+    pub source_account: Option<String>,
+}
+
+impl InvokePermission {
+    /// Id for the policy statement, derived from the principal and source,
+    /// so deploying the same permission again doesn't add another statement.
+    pub fn statement_id(&self) -> String {
+        let source = self.source_arn.as_deref().unwrap_or(&self.principal);
+        let id = source
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect::<String>();
+        // Statement ids can't be longer than 100 characters.
+        let id = format!("cargo-lambda-{id}");
+        id.chars().take(100).collect()
+    }
+}
+
+impl FromStr for InvokePermission {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MetadataError::InvalidInvokePermission(s.to_string());
+        let s = s.trim();
+
+        if s.len() == 12 && s.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(InvokePermission {
+                principal: s.to_string(),
+                source_arn: None,
+                source_account: None,
+            });
+        }
+
+        if s.ends_with(".amazonaws.com") && !s.contains(':') {
+            return Ok(InvokePermission {
+                principal: s.to_string(),
+                source_arn: None,
+                source_account: None,
+            });
+        }
+
+        // arn:partition:service:region:account:resource
+        let parts = s.splitn(6, ':').collect::<Vec<_>>();
+        let [arn, _partition, service, _region, account, resource] = parts[..] else {
+            return Err(invalid());
+        };
+        if arn != "arn" || service.is_empty() || resource.is_empty() {
+            return Err(invalid());
+        }
+
+        if service == "iam" || service == "sts" {
+            return Ok(InvokePermission {
+                principal: s.to_string(),
+                source_arn: None,
+                source_account: None,
+            });
+        }
+
+        let principal = match service {
+            "execute-api" => "apigateway.amazonaws.com".to_string(),
+            service => format!("{service}.amazonaws.com"),
+        };
+        Ok(InvokePermission {
+            principal,
+            source_arn: Some(s.to_string()),
+            source_account: (!account.is_empty()).then(|| account.to_string()),
+        })
+    }
+}
+
+fn parse_invoke_permission(value: &str) -> Result<String, MetadataError> {
+    InvokePermission::from_str(value)?;
+    Ok(value.to_string())
+}
+
+/// Service that invokes the function, created after the deploy.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Trigger {
+    /// EventBridge rule with a schedule expression, like `rate(5 minutes)`
+    Schedule(String),
+    /// API Gateway HTTP API that sends all its requests to the function
+    HttpApi,
+}
+
+impl FromStr for Trigger {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MetadataError::InvalidTrigger(s.to_string());
+
+        let (kind, config) = s.split_once(':').ok_or_else(invalid)?;
+        match kind.trim() {
+            "eventbridge" => {
+                let schedule = config.trim();
+                let is_schedule = (schedule.starts_with("rate(") || schedule.starts_with("cron("))
+                    && schedule.ends_with(')');
+                if !is_schedule {
+                    return Err(invalid());
+                }
+                Ok(Trigger::Schedule(schedule.to_string()))
+            }
+            "api" if config.trim().eq_ignore_ascii_case("http") => Ok(Trigger::HttpApi),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn parse_trigger(value: &str) -> Result<String, MetadataError> {
+    Trigger::from_str(value)?;
+    Ok(value.to_string())
+}
+
+/// Traffic shifting for deploys with an alias.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Canary {
+    /// Percentage of the alias traffic that the new version receives, between 0 and 100.
+    pub percentage: f64,
+    /// Time to monitor the new version before promoting it.
+    pub interval: Duration,
+}
+
+impl Canary {
+    /// Weight of the new version in the routing configuration of the alias, between 0 and 1.
+    pub fn weight(&self) -> f64 {
+        self.percentage / 100.0
+    }
+}
+
+impl FromStr for Canary {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MetadataError::InvalidCanary(s.to_string());
+
+        let (percentage, interval) = s.split_once(':').ok_or_else(invalid)?;
+        let percentage = percentage
+            .trim()
+            .strip_suffix('%')
+            .and_then(|p| f64::from_str(p).ok())
+            .filter(|p| *p > 0.0 && *p < 100.0)
+            .ok_or_else(invalid)?;
+        let interval = parse_long_duration(interval)
+            .filter(|i| !i.is_zero())
+            .ok_or_else(invalid)?;
+
+        Ok(Canary {
+            percentage,
+            interval,
+        })
+    }
+}
+
+fn parse_canary(value: &str) -> Result<String, MetadataError> {
+    Canary::from_str(value)?;
+    Ok(value.to_string())
+}
+
 fn default_manifest_path() -> PathBuf {
     PathBuf::from(DEFAULT_MANIFEST_PATH)
 }
@@ -322,12 +1029,24 @@ pub struct FunctionDeployConfig {
     #[serde(flatten)]
     pub vpc: Option<VpcConfig>,
 
-    /// Choose a different Lambda runtime to deploy with.
-    /// The only other option that might work is `provided.al2`.
-    #[arg(long, default_value = DEFAULT_RUNTIME)]
+    /// Choose a different Lambda runtime to deploy with [default: provided.al2023].
+    /// The only other option for Rust binaries is `provided.al2`, for binaries linked against older versions of glibc.
+    /// Functions deployed with `--handler` can use any runtime that Lambda supports, like `nodejs20.x`
+    #[arg(long, value_parser = parse_runtime)]
     #[serde(default)]
     pub runtime: Option<String>,
 
+    /// Handler of the function, like `index.handler`, for runtimes other than `provided`.
+    /// The function is deployed from the zip file in `--binary-path` as it is,
+    /// without looking for a `bootstrap` binary inside it
+    #[arg(
+        long,
+        requires = "binary_path",
+        conflicts_with_all = ["extension", "bootstrap_script", "include"]
+    )]
+    #[serde(default)]
+    pub handler: Option<String>,
+
     /// A description for the new function version.
     #[arg(long)]
     #[serde(default)]
@@ -335,13 +1054,55 @@ pub struct FunctionDeployConfig {
 
     /// Retention policy for the function's log group.
     /// The value is the number of days to keep the logs.
-    #[arg(long)]
-    #[serde(default)]
+    /// The log group is created if it doesn't exist.
+    #[arg(long, visible_alias = "log-retention-days", value_parser = parse_log_retention)]
+    #[serde(default, alias = "log_retention_days")]
     pub log_retention: Option<i32>,
 }
 
+/// Number of days that CloudWatch Logs accepts as retention policy.
+const LOG_RETENTION_DAYS: [i32; 22] = [
+    1, 3, 5, 7, 14, 30, 60, 90, 120, 150, 180, 365, 400, 545, 731, 1096, 1827, 2192, 2557, 2922,
+    3288, 3653,
+];
+
+pub fn validate_log_retention(days: i32) -> Result<i32, MetadataError> {
+    if LOG_RETENTION_DAYS.contains(&days) {
+        Ok(days)
+    } else {
+        Err(MetadataError::InvalidLogRetention(days.to_string()))
+    }
+}
+
+fn parse_log_retention(value: &str) -> Result<i32, MetadataError> {
+    let days = value
+        .parse::<i32>()
+        .map_err(|_| MetadataError::InvalidLogRetention(value.to_string()))?;
+    validate_log_retention(days)
+}
+
+/// Validate the runtime to deploy a function with. Functions with a handler are deployed
+/// from zip files prepared for their runtime, so they can use any runtime that Lambda supports.
+pub fn validate_runtime(runtime: &str, handler: Option<&str>) -> Result<(), MetadataError> {
+    if is_provided_runtime(runtime) || (handler.is_some() && is_lambda_runtime(runtime)) {
+        Ok(())
+    } else {
+        Err(MetadataError::InvalidRuntime(runtime.to_string()))
+    }
+}
+
+/// Parse the runtime flag. Whether the runtime can be used without
+/// a handler is checked with `validate_runtime` before deploying.
+fn parse_runtime(value: &str) -> Result<String, MetadataError> {
+    if is_provided_runtime(value) || is_lambda_runtime(value) {
+        Ok(value.to_string())
+    } else {
+        Err(MetadataError::InvalidRuntime(value.to_string()))
+    }
+}
+
 fn default_runtime() -> String {
-    DEFAULT_RUNTIME.to_string()
+    latest_provided_runtime().to_string()
 }
 
 impl FunctionDeployConfig {
@@ -349,6 +1110,12 @@ impl FunctionDeployConfig {
         self.runtime.clone().unwrap_or_else(default_runtime)
     }
 
+    /// Handler of the function. Functions that run on the `provided`
+    /// runtimes ignore it, Lambda executes their `bootstrap` file.
+    pub fn handler(&self) -> &str {
+        self.handler.as_deref().unwrap_or(DEFAULT_HANDLER)
+    }
+
     pub fn should_update(&self) -> bool {
         let Ok(val) = serde_json::to_value(self) else {
             return false;
@@ -368,7 +1135,9 @@ impl FunctionDeployConfig {
             + self.memory.is_some() as usize
             + self.timeout.is_some() as usize
             + self.runtime.is_some() as usize
+            + self.handler.is_some() as usize
             + self.description.is_some() as usize
+            + self.log_retention.is_some() as usize
             + self.vpc.as_ref().map_or(0, |vpc| vpc.count_fields())
             + self
                 .env_options
@@ -403,6 +1172,10 @@ impl FunctionDeployConfig {
             state.serialize_field("runtime", &runtime)?;
         }
 
+        if let Some(handler) = &self.handler {
+            state.serialize_field("handler", &handler)?;
+        }
+
         if let Some(tracing) = &self.tracing {
             state.serialize_field("tracing", &tracing)?;
         }
@@ -421,6 +1194,10 @@ impl FunctionDeployConfig {
             state.serialize_field("description", &description)?;
         }
 
+        if let Some(log_retention) = &self.log_retention {
+            state.serialize_field("log_retention", &log_retention)?;
+        }
+
         if let Some(vpc) = &self.vpc {
             vpc.serialize_fields::<S>(state)?;
         }
@@ -521,6 +1298,69 @@ mod tests {
         assert_eq!(map.get("team"), Some(&"lambda".to_string()));
     }
 
+    #[test]
+    fn test_append_tags() {
+        let mut deploy = Deploy::default();
+        deploy.append_tags(vec!["git:dirty=false".to_string()]);
+        assert_eq!(
+            deploy.lambda_tags(),
+            Some(HashMap::from([(
+                "git:dirty".to_string(),
+                "false".to_string()
+            )]))
+        );
+
+        let mut deploy = Deploy {
+            tag: Some(vec!["team=lambda".to_string()]),
+            ..Default::default()
+        };
+        deploy.append_tags(vec!["git:commit=a1b2c3d".to_string()]);
+        assert_eq!(
+            deploy.s3_tags(),
+            Some("team=lambda&git:commit=a1b2c3d".to_string())
+        );
+    }
+
+    #[test]
+    fn test_lambda_environment_interpolation() {
+        let mut deploy = Deploy {
+            base_env: HashMap::from([
+                ("STAGE".to_string(), "dev".to_string()),
+                (
+                    "BASE_URL".to_string(),
+                    "https://${STAGE}.example.com".to_string(),
+                ),
+            ]),
+            ..Default::default()
+        };
+        let env = deploy.lambda_environment().unwrap().unwrap();
+        assert_eq!(
+            env.variables().unwrap().get("BASE_URL"),
+            Some(&"https://${STAGE}.example.com".to_string())
+        );
+
+        deploy.function_config.env_options = Some(EnvOptions {
+            interpolate_env: true,
+            ..Default::default()
+        });
+        let env = deploy.lambda_environment().unwrap().unwrap();
+        assert_eq!(
+            env.variables().unwrap().get("BASE_URL"),
+            Some(&"https://dev.example.com".to_string())
+        );
+
+        deploy.function_config.env_options = Some(EnvOptions {
+            env_var: Some(vec!["STAGE=prod".to_string()]),
+            interpolate_env: true,
+            ..Default::default()
+        });
+        let env = deploy.lambda_environment().unwrap().unwrap();
+        assert_eq!(
+            env.variables().unwrap().get("BASE_URL"),
+            Some(&"https://prod.example.com".to_string())
+        );
+    }
+
     #[test]
     fn test_lambda_environment() {
         let deploy = Deploy::default();
@@ -639,4 +1479,336 @@ mod tests {
             Some(vec!["APP_ENV=production".to_string()])
         );
     }
+
+    #[test]
+    fn test_log_retention() {
+        assert_eq!(parse_log_retention("14").unwrap(), 14);
+        assert!(parse_log_retention("15").is_err());
+        assert!(parse_log_retention("forever").is_err());
+
+        let config = FunctionDeployConfig {
+            log_retention: Some(30),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(Deploy {
+            function_config: config,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(json["log_retention"], 30);
+
+        let deploy: Deploy =
+            serde_json::from_value(serde_json::json!({ "log_retention_days": 7 })).unwrap();
+        assert_eq!(deploy.function_config.log_retention, Some(7));
+    }
+
+    #[test]
+    fn test_runtime() {
+        assert_eq!(parse_runtime("provided.al2").unwrap(), "provided.al2");
+        assert_eq!(parse_runtime("nodejs20.x").unwrap(), "nodejs20.x");
+        assert!(parse_runtime("cobol1.x").is_err());
+
+        assert!(validate_runtime("provided.al2023", None).is_ok());
+        assert!(validate_runtime("nodejs20.x", None).is_err());
+        assert!(validate_runtime("nodejs20.x", Some("index.handler")).is_ok());
+        assert!(validate_runtime("cobol1.x", Some("index.handler")).is_err());
+
+        let deploy = Deploy::default();
+        assert_eq!(deploy.function_config.runtime(), "provided.al2023");
+        assert_eq!(deploy.function_config.handler(), "bootstrap");
+
+        let deploy: Deploy =
+            serde_json::from_value(serde_json::json!({ "runtime": "provided.al2" })).unwrap();
+        assert_eq!(deploy.function_config.runtime(), "provided.al2");
+
+        let deploy: Deploy = serde_json::from_value(serde_json::json!({
+            "runtime": "python3.12",
+            "handler": "app.handler",
+        }))
+        .unwrap();
+        assert_eq!(deploy.function_config.handler(), "app.handler");
+
+        let json = serde_json::to_value(&deploy).unwrap();
+        assert_eq!(json["handler"], "app.handler");
+    }
+
+    #[test]
+    fn test_audit_log() {
+        assert_eq!(
+            AuditLog {
+                bucket: "deploys".into(),
+                prefix: "lambda/audit".into(),
+            },
+            AuditLog::from_str("s3://deploys/lambda/audit/").unwrap()
+        );
+        assert_eq!(
+            AuditLog {
+                bucket: "deploys".into(),
+                prefix: "".into(),
+            },
+            AuditLog::from_str("s3://deploys").unwrap()
+        );
+        assert!(AuditLog::from_str("deploys/audit").is_err());
+        assert!(AuditLog::from_str("s3:///audit").is_err());
+
+        let deploy: Deploy =
+            serde_json::from_value(serde_json::json!({ "audit_log": "s3://deploys/audit" }))
+                .unwrap();
+        assert_eq!(deploy.audit_log().unwrap().unwrap().prefix, "audit");
+
+        let json = serde_json::to_value(&deploy).unwrap();
+        assert_eq!(json["audit_log"], "s3://deploys/audit");
+    }
+
+    #[test]
+    fn test_stack_outputs() {
+        assert_eq!(
+            StackOutput {
+                stack_name: "orders".into(),
+                output_key: "QueueUrl".into(),
+                env_var: "QUEUE_URL".into(),
+            },
+            StackOutput::from_str("orders:QueueUrl=QUEUE_URL").unwrap()
+        );
+        assert!(StackOutput::from_str("orders:QueueUrl").is_err());
+        assert!(StackOutput::from_str("QueueUrl=QUEUE_URL").is_err());
+        assert!(StackOutput::from_str("orders:=QUEUE_URL").is_err());
+
+        let deploy: Deploy = serde_json::from_value(serde_json::json!({
+            "resolve_output": ["orders:QueueUrl=QUEUE_URL", "orders:TableName=TABLE_NAME"]
+        }))
+        .unwrap();
+        let outputs = deploy.stack_outputs().unwrap();
+        assert_eq!(2, outputs.len());
+        assert_eq!("TABLE_NAME", outputs[1].env_var);
+
+        let json = serde_json::to_value(&deploy).unwrap();
+        assert_eq!(json["resolve_output"][0], "orders:QueueUrl=QUEUE_URL");
+
+        assert!(Deploy::default().stack_outputs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_canary() {
+        assert_eq!(
+            Canary {
+                percentage: 10.0,
+                interval: Duration::from_secs(300),
+            },
+            Canary::from_str("10%:5m").unwrap()
+        );
+        assert_eq!(0.025, Canary::from_str("2.5%:30s").unwrap().weight());
+        for invalid in [
+            "10%",
+            "10:5m",
+            "0%:5m",
+            "100%:5m",
+            "10%:0s",
+            "10%:soon",
+            "10%:99999999999999999h",
+        ] {
+            assert!(Canary::from_str(invalid).is_err(), "{invalid} should fail");
+        }
+
+        let deploy: Deploy =
+            serde_json::from_value(serde_json::json!({ "canary": "25%:10m" })).unwrap();
+        assert_eq!(25.0, deploy.canary().unwrap().unwrap().percentage);
+
+        let json = serde_json::to_value(&deploy).unwrap();
+        assert_eq!(json["canary"], "25%:10m");
+
+        assert_eq!(None, Deploy::default().canary().unwrap());
+    }
+
+    #[test]
+    fn test_invoke_permissions() {
+        let deploy: Deploy = serde_json::from_value(serde_json::json!({
+            "allow_invoke_from": [
+                "123456789012",
+                "events.amazonaws.com",
+                "arn:aws:iam::210987654321:role/orders",
+                "arn:aws:execute-api:us-east-1:123456789012:a1b2c3/*/GET/orders",
+                "arn:aws:s3:::orders-bucket",
+            ]
+        }))
+        .unwrap();
+        let permissions = deploy.invoke_permissions().unwrap();
+
+        assert_eq!("123456789012", permissions[0].principal);
+        assert_eq!(None, permissions[0].source_arn);
+        assert_eq!("events.amazonaws.com", permissions[1].principal);
+        assert_eq!(
+            "arn:aws:iam::210987654321:role/orders",
+            permissions[2].principal
+        );
+        assert_eq!(None, permissions[2].source_arn);
+        assert_eq!(
+            InvokePermission {
+                principal: "apigateway.amazonaws.com".into(),
+                source_arn: Some(
+                    "arn:aws:execute-api:us-east-1:123456789012:a1b2c3/*/GET/orders".into()
+                ),
+                source_account: Some("123456789012".into()),
+            },
+            permissions[3]
+        );
+        assert_eq!("s3.amazonaws.com", permissions[4].principal);
+        assert_eq!(None, permissions[4].source_account);
+
+        assert_eq!(
+            "cargo-lambda-arn-aws-s3---orders-bucket",
+            permissions[4].statement_id()
+        );
+        assert!(permissions[3].statement_id().len() <= 100);
+
+        let json = serde_json::to_value(&deploy).unwrap();
+        assert_eq!(json["allow_invoke_from"][0], "123456789012");
+
+        assert!(InvokePermission::from_str("12345").is_err());
+        assert!(InvokePermission::from_str("arn:aws:lambda").is_err());
+        assert!(Deploy::default().invoke_permissions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_triggers() {
+        let deploy: Deploy = serde_json::from_value(serde_json::json!({
+            "trigger": ["eventbridge:rate(5 minutes)", "eventbridge:cron(0 12 * * ? *)", "api:HTTP"]
+        }))
+        .unwrap();
+        assert_eq!(
+            vec![
+                Trigger::Schedule("rate(5 minutes)".into()),
+                Trigger::Schedule("cron(0 12 * * ? *)".into()),
+                Trigger::HttpApi,
+            ],
+            deploy.triggers().unwrap()
+        );
+
+        let json = serde_json::to_value(&deploy).unwrap();
+        assert_eq!(json["trigger"][2], "api:HTTP");
+
+        assert!(Trigger::from_str("eventbridge:every 5 minutes").is_err());
+        assert!(Trigger::from_str("api:REST").is_err());
+        assert!(Trigger::from_str("sqs").is_err());
+        assert!(Deploy::default().triggers().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_instances() {
+        let deploy: Deploy = serde_json::from_value(serde_json::json!({
+            "instances": ["orders-acme", "orders-globex"]
+        }))
+        .unwrap();
+        let names: Vec<_> = deploy.instances.iter().flatten().map(|i| &i.name).collect();
+        assert_eq!(vec!["orders-acme", "orders-globex"], names);
+
+        let deploy: Deploy = serde_json::from_value(serde_json::json!({
+            "env_var": ["TENANT=default", "REGION=eu"],
+            "instances": {
+                "orders-acme": { "env": { "TENANT": "acme" } },
+                "orders-globex": {}
+            }
+        }))
+        .unwrap();
+        let instances = deploy.instances.clone().unwrap();
+        assert_eq!(vec!["TENANT=acme".to_string()], instances[0].env);
+        assert!(instances[1].env.is_empty());
+
+        let json = serde_json::to_value(&deploy).unwrap();
+        assert_eq!(
+            serde_json::json!([
+                { "name": "orders-acme", "env": ["TENANT=acme"] },
+                { "name": "orders-globex" }
+            ]),
+            json["instances"]
+        );
+        let roundtrip: Deploy = serde_json::from_value(json).unwrap();
+        assert_eq!(Some(instances.clone()), roundtrip.instances);
+
+        let config = deploy.instance_config(&instances[0]);
+        assert_eq!(Some("orders-acme".to_string()), config.name);
+        assert_eq!(None, config.instances);
+        let env = config.lambda_environment().unwrap().unwrap();
+        let variables = env.variables().unwrap();
+        assert_eq!(Some(&"acme".to_string()), variables.get("TENANT"));
+        assert_eq!(Some(&"eu".to_string()), variables.get("REGION"));
+
+        let config = deploy.instance_config(&instances[1]);
+        let env = config.lambda_environment().unwrap().unwrap();
+        assert_eq!(
+            Some(&"default".to_string()),
+            env.variables().unwrap().get("TENANT")
+        );
+
+        assert!(Instance::from_str("orders acme").is_err());
+        assert!(Instance::from_str("").is_err());
+        assert!(
+            serde_json::from_value::<Deploy>(serde_json::json!({ "instances": [{ "env": {} }] }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_custom_domain() {
+        assert_eq!(
+            "api.example.com",
+            parse_custom_domain("API.example.com").unwrap()
+        );
+        assert_eq!(
+            "orders-api.eu.example.co.uk",
+            parse_custom_domain("orders-api.eu.example.co.uk").unwrap()
+        );
+        assert!(parse_custom_domain("localhost").is_err());
+        assert!(parse_custom_domain("https://api.example.com").is_err());
+        assert!(parse_custom_domain("*.example.com").is_err());
+        assert!(parse_custom_domain("-api.example.com").is_err());
+        assert!(parse_custom_domain("api..example.com").is_err());
+
+        let deploy: Deploy = serde_json::from_value(serde_json::json!({
+            "custom_domain": "Orders.example.com"
+        }))
+        .unwrap();
+        assert_eq!(
+            Some("orders.example.com".to_string()),
+            deploy.custom_domain().unwrap()
+        );
+        assert_eq!(None, Deploy::default().custom_domain().unwrap());
+    }
+
+    #[test]
+    fn test_gate_alarms() {
+        let deploy: Deploy = serde_json::from_value(serde_json::json!({
+            "gate_alarm": ["orders-api-errors", "orders-api-latency"]
+        }))
+        .unwrap();
+        assert_eq!(
+            &["orders-api-errors", "orders-api-latency"],
+            deploy.gate_alarms()
+        );
+
+        let json = serde_json::to_value(&deploy).unwrap();
+        assert_eq!(json["gate_alarm"][1], "orders-api-latency");
+
+        assert!(Deploy::default().gate_alarms().is_empty());
+    }
+
+    #[test]
+    fn test_binary_selection() {
+        let deploy: Deploy = serde_json::from_value(serde_json::json!({
+            "bin": "basic-lambda-1",
+            "packages": ["basic-lambda-1"],
+            "exclude": ["crate-3"],
+        }))
+        .unwrap();
+
+        let selection = deploy.binary_selection();
+        assert_eq!(vec!["basic-lambda-1".to_string()], selection.bins);
+        assert_eq!(vec!["basic-lambda-1".to_string()], selection.packages);
+        assert_eq!(vec!["crate-3".to_string()], selection.exclude);
+
+        let json = serde_json::to_value(&deploy).unwrap();
+        assert_eq!(json["binary_name"], "basic-lambda-1");
+        assert_eq!(json["packages"], serde_json::json!(["basic-lambda-1"]));
+        assert_eq!(json["exclude"], serde_json::json!(["crate-3"]));
+    }
 }
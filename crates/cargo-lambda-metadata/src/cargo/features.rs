@@ -0,0 +1,167 @@
+use cargo_options::CommonOptions;
+use std::collections::HashSet;
+
+use super::{CargoMetadata, CargoPackage};
+
+/// Features that a binary target requires with `required-features`,
+/// and that the build doesn't enable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissingFeatures {
+    pub binary: String,
+    pub package: String,
+    pub features: Vec<String>,
+}
+
+impl MissingFeatures {
+    /// Features qualified with the name of their package, like `basic-lambda/feature`,
+    /// so Cargo enables them in the right package in workspaces.
+    pub fn qualified_features(&self) -> Vec<String> {
+        self.features
+            .iter()
+            .map(|feature| format!("{}/{feature}", self.package))
+            .collect()
+    }
+}
+
+/// Find the binaries that require features that the build doesn't enable.
+/// Cargo skips those binaries when it builds all the targets,
+/// and it fails when they are selected with `--bin`.
+pub fn missing_required_features(
+    metadata: &CargoMetadata,
+    binaries: &HashSet<String>,
+    options: &CommonOptions,
+) -> Vec<MissingFeatures> {
+    if options.all_features {
+        return Vec::new();
+    }
+
+    let mut missing = Vec::new();
+    for package in &metadata.packages {
+        let enabled = enabled_features(package, options);
+
+        for target in &package.targets {
+            if target.required_features.is_empty() || !binaries.contains(&target.name) {
+                continue;
+            }
+
+            let features = target
+                .required_features
+                .iter()
+                .filter(|feature| !enabled.contains(*feature))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !features.is_empty() {
+                missing.push(MissingFeatures {
+                    binary: target.name.clone(),
+                    package: package.name.clone(),
+                    features,
+                });
+            }
+        }
+    }
+
+    missing.sort_by(|a, b| a.binary.cmp(&b.binary));
+    missing
+}
+
+/// Features enabled in a package by the `--features` and `--no-default-features` flags,
+/// including the features that those features enable.
+fn enabled_features(package: &CargoPackage, options: &CommonOptions) -> HashSet<String> {
+    let mut pending = options
+        .features
+        .iter()
+        .flat_map(|f| f.split([',', ' ']))
+        .filter(|f| !f.is_empty())
+        .filter_map(|f| match f.split_once('/') {
+            Some((name, feature)) if name == package.name => Some(feature.to_string()),
+            Some(_) => None,
+            None => Some(f.to_string()),
+        })
+        .collect::<Vec<_>>();
+
+    if !options.no_default_features {
+        pending.push("default".to_string());
+    }
+
+    let mut enabled = HashSet::new();
+    while let Some(feature) = pending.pop() {
+        if !enabled.insert(feature.clone()) {
+            continue;
+        }
+
+        // Features of dependencies, like `dep:serde` or `serde/std`,
+        // don't enable features in this package.
+        pending.extend(
+            package
+                .features
+                .get(&feature)
+                .into_iter()
+                .flatten()
+                .filter(|f| !f.starts_with("dep:") && !f.contains('/'))
+                .cloned(),
+        );
+    }
+    enabled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cargo::load_metadata, tests::fixture_metadata};
+
+    fn binaries() -> HashSet<String> {
+        HashSet::from([
+            "basic-lambda".to_string(),
+            "featured-lambda".to_string(),
+            "default-lambda".to_string(),
+        ])
+    }
+
+    #[test]
+    fn test_missing_required_features() {
+        let metadata = load_metadata(fixture_metadata("required-features-package")).unwrap();
+
+        let missing = missing_required_features(&metadata, &binaries(), &CommonOptions::default());
+        assert_eq!(
+            vec![MissingFeatures {
+                binary: "featured-lambda".into(),
+                package: "required-features-package".into(),
+                features: vec!["dynamodb".into()],
+            }],
+            missing
+        );
+        assert_eq!(
+            vec!["required-features-package/dynamodb".to_string()],
+            missing[0].qualified_features()
+        );
+
+        let options = CommonOptions {
+            features: vec!["aws".into()],
+            ..Default::default()
+        };
+        assert!(missing_required_features(&metadata, &binaries(), &options).is_empty());
+
+        let options = CommonOptions {
+            all_features: true,
+            ..Default::default()
+        };
+        assert!(missing_required_features(&metadata, &binaries(), &options).is_empty());
+
+        let options = CommonOptions {
+            features: vec!["required-features-package/dynamodb".into()],
+            no_default_features: true,
+            ..Default::default()
+        };
+        let missing = missing_required_features(&metadata, &binaries(), &options);
+        assert_eq!(1, missing.len());
+        assert_eq!("default-lambda", missing[0].binary);
+        assert_eq!(vec!["tracing".to_string()], missing[0].features);
+
+        let missing = missing_required_features(
+            &metadata,
+            &HashSet::from(["basic-lambda".to_string()]),
+            &CommonOptions::default(),
+        );
+        assert!(missing.is_empty());
+    }
+}
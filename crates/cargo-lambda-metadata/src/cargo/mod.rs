@@ -18,15 +18,28 @@ use crate::error::MetadataError;
 pub mod build;
 use build::Build;
 
+pub mod cargo_config;
+
 pub mod deploy;
 use deploy::Deploy;
 
+pub mod features;
+
 pub mod profile;
 use profile::CargoProfile;
 
+pub mod schema;
+
+pub mod selection;
+use selection::BinarySelection;
+
+pub mod validation;
+
 pub mod watch;
 use watch::Watch;
-const STRIP_CONFIG: &str = "profile.release.strip=\"symbols\"";
+
+/// Release optimization that removes the symbols from the binaries.
+pub const STRIP_CONFIG: &str = "profile.release.strip=\"symbols\"";
 const LTO_CONFIG: &str = "profile.release.lto=\"thin\"";
 const CODEGEN_CONFIG: &str = "profile.release.codegen-units=1";
 const PANIC_CONFIG: &str = "profile.release.panic=\"abort\"";
@@ -155,45 +168,55 @@ pub fn target_dir_from_metadata(metadata: &CargoMetadata) -> Result<PathBuf> {
     Ok(metadata.target_directory.clone().into_std_path_buf())
 }
 
-/// Attempt to read the release profile section in the Cargo manifest.
+/// Attempt to read the profile sections in the Cargo manifest.
 /// Cargo metadata doesn't expose profile information, so we try
 /// to read it from the Cargo.toml file directly.
+///
+/// It returns `None` when the profile doesn't inherit from `release`,
+/// like `dev`, or custom profiles that inherit from `dev`.
 pub fn cargo_release_profile_config<'a>(
     metadata: &CargoMetadata,
-) -> Result<HashSet<&'a str>, MetadataError> {
+    profile: &str,
+) -> Result<Option<HashSet<&'a str>>, MetadataError> {
     let path = metadata.workspace_root.join("Cargo.toml");
     let file =
         read_to_string(&path).map_err(|e| MetadataError::InvalidManifestFile(path.into(), e))?;
 
     let metadata: Metadata = toml::from_str(&file).map_err(MetadataError::InvalidTomlManifest)?;
 
-    Ok(cargo_release_profile_config_from_metadata(metadata))
+    Ok(cargo_release_profile_config_from_metadata(
+        metadata, profile,
+    ))
 }
 
-fn cargo_release_profile_config_from_metadata(metadata: Metadata) -> HashSet<&'static str> {
+fn cargo_release_profile_config_from_metadata(
+    metadata: Metadata,
+    profile: &str,
+) -> Option<HashSet<&'static str>> {
     let mut config = HashSet::from([STRIP_CONFIG, LTO_CONFIG, CODEGEN_CONFIG, PANIC_CONFIG]);
 
-    let Some(profile) = &metadata.profile else {
-        return config;
-    };
-    let Some(release) = &profile.release else {
-        return config;
+    let Some(profiles) = &metadata.profile else {
+        return matches!(profile, "release" | "bench").then_some(config);
     };
 
-    if release.strip.is_some() || release.debug_enabled() {
-        config.remove(STRIP_CONFIG);
-    }
-    if release.lto.is_some() {
-        config.remove(LTO_CONFIG);
-    }
-    if release.codegen_units.is_some() {
-        config.remove(CODEGEN_CONFIG);
-    }
-    if release.panic.is_some() {
-        config.remove(PANIC_CONFIG);
+    // Custom profiles inherit the optimizations that we add to the release profile,
+    // unless they, or any profile between them and `release`, set those options.
+    for release in profiles.release_chain(profile)? {
+        if release.strip.is_some() || release.debug_enabled() {
+            config.remove(STRIP_CONFIG);
+        }
+        if release.lto.is_some() {
+            config.remove(LTO_CONFIG);
+        }
+        if release.codegen_units.is_some() {
+            config.remove(CODEGEN_CONFIG);
+        }
+        if release.panic.is_some() {
+            config.remove(PANIC_CONFIG);
+        }
     }
 
-    config
+    Some(config)
 }
 
 /// Create metadata about the root package in the Cargo manifest, without any dependencies.
@@ -236,19 +259,7 @@ pub fn load_metadata<P: AsRef<Path> + Debug>(
 /// Use this function when the user didn't provide any funcion name
 /// assuming that there is only one binary in the project
 pub fn main_binary_from_metadata(metadata: &CargoMetadata) -> Result<String, MetadataError> {
-    let targets = binary_targets_from_metadata(metadata, false);
-    if targets.len() > 1 {
-        let mut vec = targets.into_iter().collect::<Vec<_>>();
-        vec.sort();
-        Err(MetadataError::MultipleBinariesInProject(vec.join(", ")))
-    } else if targets.is_empty() {
-        Err(MetadataError::MissingBinaryInProject)
-    } else {
-        targets
-            .into_iter()
-            .next()
-            .ok_or(MetadataError::MissingBinaryInProject)
-    }
+    BinarySelection::default().single(metadata)
 }
 
 fn is_project_metadata_ok(path: &Path) -> bool {
@@ -470,7 +481,8 @@ mod tests {
 
     #[test]
     fn test_release_config() {
-        let config = cargo_release_profile_config_from_metadata(Metadata::default());
+        let config =
+            cargo_release_profile_config_from_metadata(Metadata::default(), "release").unwrap();
         assert!(config.contains(STRIP_CONFIG));
         assert!(config.contains(LTO_CONFIG));
         assert!(config.contains(CODEGEN_CONFIG));
@@ -480,7 +492,9 @@ mod tests {
     #[test]
     fn test_release_config_with_workspace() {
         let metadata = load_metadata(fixture_metadata("workspace-package")).unwrap();
-        let config = cargo_release_profile_config(&metadata).unwrap();
+        let config = cargo_release_profile_config(&metadata, "release")
+            .unwrap()
+            .unwrap();
         assert!(config.contains(STRIP_CONFIG));
         assert!(!config.contains(LTO_CONFIG));
     }
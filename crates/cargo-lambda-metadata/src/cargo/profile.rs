@@ -1,12 +1,48 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Maximum number of profiles to follow in an inheritance chain.
+/// Cargo rejects cycles, this only prevents loops in invalid manifests.
+const MAX_INHERITANCE_DEPTH: usize = 16;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct CargoProfile {
     pub release: Option<CargoProfileRelease>,
+    /// Custom profiles, like `[profile.lambda-release]`.
+    #[serde(flatten)]
+    pub custom: HashMap<String, CargoProfileRelease>,
+}
+
+impl CargoProfile {
+    /// Profiles that a build with `name` uses, from the most specific to
+    /// the built-in profile at the root of the inheritance chain.
+    /// It returns `None` when the profile doesn't inherit from `release`.
+    pub fn release_chain<'a>(&'a self, name: &'a str) -> Option<Vec<&'a CargoProfileRelease>> {
+        let mut chain = Vec::new();
+        let mut current = name;
+
+        for _ in 0..MAX_INHERITANCE_DEPTH {
+            match current {
+                "release" | "bench" => {
+                    chain.extend(self.release.as_ref());
+                    return Some(chain);
+                }
+                "dev" | "test" => return None,
+                _ => {}
+            }
+
+            let profile = self.custom.get(current)?;
+            chain.push(profile);
+            current = profile.inherits.as_deref()?;
+        }
+
+        None
+    }
 }
 
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct CargoProfileRelease {
+    pub inherits: Option<String>,
     pub strip: Option<toml::Value>,
     pub lto: Option<toml::Value>,
     #[serde(rename = "codegen-units")]
@@ -55,11 +91,12 @@ mod tests {
                     strip: Some(toml::Value::String("none".into())),
                     ..Default::default()
                 }),
+                ..Default::default()
             }),
             ..Default::default()
         };
 
-        let config = cargo_release_profile_config_from_metadata(meta);
+        let config = cargo_release_profile_config_from_metadata(meta, "release").unwrap();
         assert!(!config.contains(STRIP_CONFIG));
 
         let meta = Metadata {
@@ -68,11 +105,12 @@ mod tests {
                     debug: CargoBool::Bool(true),
                     ..Default::default()
                 }),
+                ..Default::default()
             }),
             ..Default::default()
         };
 
-        let config = cargo_release_profile_config_from_metadata(meta);
+        let config = cargo_release_profile_config_from_metadata(meta, "release").unwrap();
         assert!(!config.contains(STRIP_CONFIG));
     }
 
@@ -84,11 +122,12 @@ mod tests {
                     lto: Some(toml::Value::String("none".into())),
                     ..Default::default()
                 }),
+                ..Default::default()
             }),
             ..Default::default()
         };
 
-        let config = cargo_release_profile_config_from_metadata(meta);
+        let config = cargo_release_profile_config_from_metadata(meta, "release").unwrap();
         assert!(!config.contains(LTO_CONFIG));
     }
 
@@ -100,11 +139,12 @@ mod tests {
                     codegen_units: Some(toml::Value::Integer(2)),
                     ..Default::default()
                 }),
+                ..Default::default()
             }),
             ..Default::default()
         };
 
-        let config = cargo_release_profile_config_from_metadata(meta);
+        let config = cargo_release_profile_config_from_metadata(meta, "release").unwrap();
         assert!(!config.contains(CODEGEN_CONFIG));
     }
 
@@ -116,11 +156,12 @@ mod tests {
                     panic: Some(toml::Value::String("none".into())),
                     ..Default::default()
                 }),
+                ..Default::default()
             }),
             ..Default::default()
         };
 
-        let config = cargo_release_profile_config_from_metadata(meta);
+        let config = cargo_release_profile_config_from_metadata(meta, "release").unwrap();
         assert!(!config.contains(PANIC_CONFIG));
     }
 
@@ -138,4 +179,55 @@ mod tests {
         let profile = metadata.profile.unwrap().release.unwrap();
         assert!(profile.debug_enabled());
     }
+
+    #[test]
+    fn test_custom_profile_release_chain() {
+        let data = r#"
+        [profile.release]
+        lto = true
+
+        [profile.lambda-release]
+        inherits = "release"
+        panic = "unwind"
+
+        [profile.lambda-small]
+        inherits = "lambda-release"
+        debug = true
+
+        [profile.lambda-dev]
+        inherits = "dev"
+
+        [profile.loop-a]
+        inherits = "loop-b"
+
+        [profile.loop-b]
+        inherits = "loop-a"
+        "#;
+        let metadata: Metadata = toml::from_str(data).unwrap();
+        let profile = metadata.profile.as_ref().unwrap();
+
+        assert_eq!(1, profile.release_chain("release").unwrap().len());
+        assert_eq!(2, profile.release_chain("lambda-release").unwrap().len());
+        assert_eq!(3, profile.release_chain("lambda-small").unwrap().len());
+        assert!(profile.release_chain("dev").is_none());
+        assert!(profile.release_chain("lambda-dev").is_none());
+        assert!(profile.release_chain("missing").is_none());
+        assert!(profile.release_chain("loop-a").is_none());
+
+        let config = cargo_release_profile_config_from_metadata(metadata, "lambda-small").unwrap();
+        assert!(!config.contains(STRIP_CONFIG));
+        assert!(!config.contains(LTO_CONFIG));
+        assert!(config.contains(CODEGEN_CONFIG));
+        assert!(!config.contains(PANIC_CONFIG));
+    }
+
+    #[test]
+    fn test_release_config_without_profiles() {
+        let config = cargo_release_profile_config_from_metadata(Metadata::default(), "bench");
+        assert!(config.is_some());
+
+        let config =
+            cargo_release_profile_config_from_metadata(Metadata::default(), "lambda-release");
+        assert!(config.is_none());
+    }
 }
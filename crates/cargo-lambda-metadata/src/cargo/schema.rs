@@ -0,0 +1,535 @@
+use clap::{Arg, ArgAction, Args, Command};
+use serde_json::{Map, Value, json};
+use std::{any::TypeId, fmt, ops::Range, path::PathBuf};
+use toml_edit::{ImDocument, Item};
+
+use crate::{
+    cargo::{
+        build::Build,
+        deploy::Deploy,
+        validation::{
+            BUILD_EXTRA_KEYS, CLI_ONLY_KEYS, DEPLOY_EXTRA_KEYS, WATCH_EXTRA_KEYS, closest_match,
+        },
+        watch::Watch,
+    },
+    error::MetadataError,
+    lambda::Memory,
+};
+
+/// Keys that serde accepts as aliases of the ids of the arguments.
+const ALIASES: &[(&str, &str)] = &[
+    ("bin", "binary_name"),
+    ("env", "env_var"),
+    ("iam_role", "role"),
+    ("layers", "layer"),
+    ("lazy", "wait"),
+    ("log_retention_days", "log_retention"),
+    ("tags", "tag"),
+];
+
+/// Arguments that accept a list in the command line, like `KEY=VALUE` strings,
+/// and a list or a table in the metadata.
+const VEC_OR_MAP_KEYS: &[&str] = &["env_var", "instances", "tag"];
+
+/// Arguments parsed from strings in the command line that are tables in the metadata.
+const TABLE_KEYS: &[&str] = &["compiler"];
+
+/// JSON Schema of the `lambda` tables in the workspace and package metadata of a Cargo.toml file.
+/// The schema for each command is generated from its command line arguments,
+/// since their ids are the keys in the metadata.
+pub fn metadata_schema() -> Value {
+    let metadata = json!({
+        "type": "object",
+        "properties": {
+            "metadata": {
+                "type": "object",
+                "properties": {
+                    "lambda": { "$ref": "#/definitions/lambda" }
+                }
+            }
+        }
+    });
+
+    let mut lambda = section_schema();
+    lambda["properties"]["bin"] = json!({
+        "description": "Configuration for each binary in the package, merged over the package configuration",
+        "type": "object",
+        "additionalProperties": { "$ref": "#/definitions/section" }
+    });
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "Cargo Lambda metadata",
+        "description": "Configuration for Cargo Lambda in the `[package.metadata.lambda]` and `[workspace.metadata.lambda]` tables of Cargo.toml",
+        "type": "object",
+        "properties": {
+            "package": metadata,
+            "workspace": metadata,
+        },
+        "definitions": {
+            "lambda": lambda,
+            "section": section_schema(),
+            "env": {
+                "description": "Environment variables for the functions",
+                "type": "object",
+                "additionalProperties": { "type": "string" }
+            },
+            "build": command_schema::<Build>(BUILD_EXTRA_KEYS),
+            "deploy": command_schema::<Deploy>(DEPLOY_EXTRA_KEYS),
+            "watch": command_schema::<Watch>(WATCH_EXTRA_KEYS),
+        }
+    })
+}
+
+fn section_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "env": { "$ref": "#/definitions/env" },
+            "build": { "$ref": "#/definitions/build" },
+            "deploy": { "$ref": "#/definitions/deploy" },
+            "watch": { "$ref": "#/definitions/watch" },
+        },
+        "additionalProperties": false
+    })
+}
+
+fn command_schema<T: Args>(extra: &[&str]) -> Value {
+    let command = T::augment_args(Command::new("cargo-lambda"));
+
+    let mut properties = Map::new();
+    for arg in command.get_arguments() {
+        if CLI_ONLY_KEYS.contains(&arg.get_id().as_str()) {
+            continue;
+        }
+        properties.insert(arg.get_id().to_string(), arg_schema(arg));
+    }
+
+    for key in extra {
+        let schema = match ALIASES.iter().find(|(alias, _)| alias == key) {
+            Some((_, id)) => properties.get(*id).cloned().unwrap_or_else(|| json!({})),
+            None if *key == "env" => json!({ "$ref": "#/definitions/env" }),
+            None if *key == "route_prefix" => json!({ "type": "string" }),
+            None => json!({ "type": "object" }),
+        };
+        properties.insert(key.to_string(), schema);
+    }
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false
+    })
+}
+
+/// Schema for the value of an argument. Values parsed with custom parsers
+/// are not restricted, because serde can accept other types for them,
+/// like numbers for the timeout.
+fn arg_schema(arg: &Arg) -> Value {
+    let mut schema = Map::new();
+    if let Some(help) = arg.get_long_help().or_else(|| arg.get_help()) {
+        schema.insert("description".into(), help.to_string().into());
+    }
+
+    let id = arg.get_id().as_str();
+    let multiple = matches!(arg.get_action(), ArgAction::Append)
+        || arg.get_num_args().is_some_and(|n| n.max_values() > 1);
+
+    if VEC_OR_MAP_KEYS.contains(&id) {
+        schema.insert("type".into(), json!(["array", "object"]));
+    } else if TABLE_KEYS.contains(&id) {
+        schema.insert("type".into(), json!(["string", "object"]));
+    } else if matches!(arg.get_action(), ArgAction::SetTrue | ArgAction::SetFalse) {
+        schema.insert("type".into(), "boolean".into());
+    } else if matches!(arg.get_action(), ArgAction::Count) {
+        schema.insert("type".into(), "integer".into());
+    } else if multiple {
+        schema.insert("type".into(), "array".into());
+        schema.insert("items".into(), Value::Object(value_schema(arg)));
+    } else {
+        schema.extend(value_schema(arg));
+    }
+
+    Value::Object(schema)
+}
+
+fn value_schema(arg: &Arg) -> Map<String, Value> {
+    let type_id = arg.get_value_parser().type_id();
+    let mut schema = Map::new();
+
+    if type_id == TypeId::of::<String>() || type_id == TypeId::of::<PathBuf>() {
+        schema.insert("type".into(), "string".into());
+
+        let values = arg
+            .get_possible_values()
+            .iter()
+            .filter(|v| !v.is_hide_set())
+            .map(|v| Value::from(v.get_name()))
+            .collect::<Vec<_>>();
+        if !values.is_empty() {
+            schema.insert("enum".into(), values.into());
+        }
+    } else if type_id == TypeId::of::<bool>() {
+        schema.insert("type".into(), "boolean".into());
+    } else if [
+        TypeId::of::<u8>(),
+        TypeId::of::<u16>(),
+        TypeId::of::<u32>(),
+        TypeId::of::<u64>(),
+        TypeId::of::<usize>(),
+        TypeId::of::<i32>(),
+        TypeId::of::<i64>(),
+        TypeId::of::<Memory>(),
+    ]
+    .iter()
+    .any(|id| type_id == *id)
+    {
+        schema.insert("type".into(), "integer".into());
+    } else {
+        schema.insert("type".into(), json!(["string", "integer", "boolean"]));
+    }
+
+    schema
+}
+
+/// A value in the Cargo.toml file that doesn't match the metadata schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaViolation {
+    /// Dotted path of the key, like `package.metadata.lambda.deploy.memory`
+    pub key: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: `{}` {}",
+            self.line, self.column, self.key, self.message
+        )
+    }
+}
+
+/// Check the `lambda` metadata tables in the content of a Cargo.toml file against the metadata schema.
+pub fn validate_manifest(source: &str) -> Result<Vec<SchemaViolation>, MetadataError> {
+    let document = ImDocument::parse(source)?;
+    let schema = metadata_schema();
+
+    let mut validator = Validator {
+        schema: &schema,
+        source,
+        violations: Vec::new(),
+    };
+    validator.check(&schema, document.as_item(), "", None);
+
+    Ok(validator.violations)
+}
+
+struct Validator<'a> {
+    schema: &'a Value,
+    source: &'a str,
+    violations: Vec<SchemaViolation>,
+}
+
+impl Validator<'_> {
+    fn check(&mut self, schema: &Value, item: &Item, key: &str, span: Option<Range<usize>>) {
+        let schema = resolve(self.schema, schema);
+        let span = item.span().or(span);
+
+        if let Some(expected) = schema.get("type") {
+            if !matches_type(expected, item) {
+                let message = format!(
+                    "expected {}, found {}",
+                    describe_type(expected),
+                    describe_item(item)
+                );
+                self.report(key, span, message);
+                return;
+            }
+        }
+
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            let value = item.as_str().unwrap_or_default();
+            if !values.iter().any(|v| v.as_str() == Some(value)) {
+                let values = values
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let message = format!("has an invalid value `{value}`, expected one of: {values}");
+                self.report(key, span.clone(), message);
+            }
+        }
+
+        if let Some(table) = item.as_table_like() {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            let additional = schema.get("additionalProperties");
+
+            for (name, value) in table.iter() {
+                let child_key = match key {
+                    "" => name.to_string(),
+                    key => format!("{key}.{name}"),
+                };
+                let key_span = table.key(name).and_then(|k| k.span());
+
+                match (properties.and_then(|p| p.get(name)), additional) {
+                    (Some(schema), _) => self.check(schema, value, &child_key, key_span),
+                    (None, Some(Value::Bool(false))) => {
+                        let valid = properties
+                            .map(|p| p.keys().map(String::as_str).collect::<Vec<_>>())
+                            .unwrap_or_default();
+                        let message = match closest_match(name, &valid) {
+                            Some(suggestion) => {
+                                format!("is not a valid key, did you mean `{suggestion}`?")
+                            }
+                            None => "is not a valid key".to_string(),
+                        };
+                        self.report(&child_key, key_span, message);
+                    }
+                    (None, Some(schema @ Value::Object(_))) => {
+                        self.check(schema, value, &child_key, key_span)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(items) = schema.get("items") {
+            let values = match item {
+                Item::Value(toml_edit::Value::Array(array)) => {
+                    array.iter().cloned().map(Item::Value).collect()
+                }
+                Item::ArrayOfTables(array) => array.iter().cloned().map(Item::Table).collect(),
+                _ => Vec::new(),
+            };
+            for (index, value) in values.iter().enumerate() {
+                self.check(items, value, &format!("{key}[{index}]"), span.clone());
+            }
+        }
+    }
+
+    fn report(&mut self, key: &str, span: Option<Range<usize>>, message: String) {
+        let (line, column) = span
+            .map(|span| line_column(self.source, span.start))
+            .unwrap_or((0, 0));
+        self.violations.push(SchemaViolation {
+            key: key.to_string(),
+            line,
+            column,
+            message,
+        });
+    }
+}
+
+/// Follow the references to the definitions of the schema.
+fn resolve<'s>(root: &'s Value, schema: &'s Value) -> &'s Value {
+    schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|r| r.strip_prefix('#'))
+        .and_then(|pointer| root.pointer(pointer))
+        .unwrap_or(schema)
+}
+
+fn matches_type(expected: &Value, item: &Item) -> bool {
+    match expected {
+        Value::Array(types) => types.iter().any(|t| matches_type(t, item)),
+        Value::String(t) => match t.as_str() {
+            "object" => item.is_table_like(),
+            "array" => item.is_array() || item.is_array_of_tables(),
+            "string" => item.is_str(),
+            "boolean" => item.is_bool(),
+            "integer" => item.is_integer(),
+            _ => true,
+        },
+        _ => true,
+    }
+}
+
+fn describe_type(expected: &Value) -> String {
+    match expected {
+        Value::Array(types) => types
+            .iter()
+            .map(describe_type)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        Value::String(t) if t == "object" => "a table".to_string(),
+        Value::String(t) if t == "array" => "an array".to_string(),
+        Value::String(t) if t == "integer" => "an integer".to_string(),
+        Value::String(t) => format!("a {t}"),
+        _ => "a value".to_string(),
+    }
+}
+
+fn describe_item(item: &Item) -> &'static str {
+    if item.is_table_like() {
+        "a table"
+    } else if item.is_array() || item.is_array_of_tables() {
+        "an array"
+    } else if item.is_str() {
+        "a string"
+    } else if item.is_bool() {
+        "a boolean"
+    } else if item.is_integer() {
+        "an integer"
+    } else if item.is_float() {
+        "a float"
+    } else {
+        "a date"
+    }
+}
+
+/// Line and column, starting at 1, of a byte offset in the source.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_schema() {
+        let schema = metadata_schema();
+        let definitions = &schema["definitions"];
+
+        assert_eq!(
+            "boolean",
+            definitions["build"]["properties"]["release"]["type"]
+        );
+        assert_eq!(
+            "array",
+            definitions["build"]["properties"]["features"]["type"]
+        );
+        assert_eq!(
+            "integer",
+            definitions["watch"]["properties"]["invoke_port"]["type"]
+        );
+        assert_eq!(
+            json!(["array", "object"]),
+            definitions["deploy"]["properties"]["tags"]["type"]
+        );
+        assert_eq!(
+            definitions["deploy"]["properties"]["role"],
+            definitions["deploy"]["properties"]["iam_role"]
+        );
+        assert_eq!(
+            "integer",
+            definitions["deploy"]["properties"]["memory"]["type"]
+        );
+        assert_eq!(
+            json!(["string", "integer", "boolean"]),
+            definitions["deploy"]["properties"]["timeout"]["type"]
+        );
+        assert_eq!(
+            json!(["string", "object"]),
+            definitions["build"]["properties"]["compiler"]["type"]
+        );
+        assert_eq!(
+            definitions["watch"]["properties"]["wait"],
+            definitions["watch"]["properties"]["lazy"]
+        );
+    }
+
+    #[test]
+    fn test_validate_manifest() {
+        let source = r#"[package]
+name = "basic-lambda"
+
+[package.metadata.lambda.build]
+release = "yes"
+
+[package.metadata.lambda.deploy]
+memroy = 512
+timeout = 60
+tracing = ["active"]
+tags = { team = "lambda" }
+layers = ["arn:aws:lambda:us-east-1:123456789012:layer:layer1:1"]
+
+[package.metadata.lambda.bin.basic-lambda.watch]
+invoke_port = "9000"
+
+[package.metadata.other]
+anything = true
+"#;
+
+        let violations = validate_manifest(source).unwrap();
+        assert_eq!(
+            vec![
+                SchemaViolation {
+                    key: "package.metadata.lambda.build.release".into(),
+                    line: 5,
+                    column: 11,
+                    message: "expected a boolean, found a string".into(),
+                },
+                SchemaViolation {
+                    key: "package.metadata.lambda.deploy.memroy".into(),
+                    line: 8,
+                    column: 1,
+                    message: "is not a valid key, did you mean `memory`?".into(),
+                },
+                SchemaViolation {
+                    key: "package.metadata.lambda.deploy.tracing".into(),
+                    line: 10,
+                    column: 11,
+                    message: "expected a string or an integer or a boolean, found an array".into(),
+                },
+                SchemaViolation {
+                    key: "package.metadata.lambda.bin.basic-lambda.watch.invoke_port".into(),
+                    line: 15,
+                    column: 15,
+                    message: "expected an integer, found a string".into(),
+                },
+            ],
+            violations
+        );
+        assert_eq!(
+            "8:1: `package.metadata.lambda.deploy.memroy` is not a valid key, did you mean `memory`?",
+            violations[1].to_string()
+        );
+
+        assert!(validate_manifest("[package\nname = 1").is_err());
+    }
+
+    #[test]
+    fn test_fixtures_match_schema() {
+        for fixture in [
+            "single-binary-package",
+            "multi-binary-package",
+            "workspace-package",
+            "config-with-context",
+            "package-with-global-config",
+            "watch-multi-binary",
+        ] {
+            let path = format!("../../tests/fixtures/{fixture}/Cargo.toml");
+            let source = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(
+                Vec::<SchemaViolation>::new(),
+                validate_manifest(&source).unwrap(),
+                "unexpected schema violations in fixture {fixture}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_published_schema_is_up_to_date() {
+        let published = std::fs::read_to_string("../../docs/public/schemas/cargo-lambda.json")
+            .unwrap_or_default();
+        let published: Value = serde_json::from_str(&published).unwrap_or_default();
+        assert!(
+            published == metadata_schema(),
+            "the published schema is outdated, update it with `cargo lambda validate --print-schema > docs/public/schemas/cargo-lambda.json`"
+        );
+    }
+}
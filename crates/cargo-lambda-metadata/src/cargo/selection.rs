@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+
+use super::{CargoMetadata, CargoPackage, kind_bin_filter, kind_example_filter};
+use crate::error::MetadataError;
+
+/// Binary targets selected with the `--package`, `--exclude`, and `--bin` flags.
+/// The build, watch, and deploy commands select the binaries in the same way,
+/// so workspaces can target the same subset of binaries with all of them.
+#[derive(Clone, Debug, Default)]
+pub struct BinarySelection {
+    /// Packages to take the binaries from, all the packages in the project when it's empty
+    pub packages: Vec<String>,
+    /// Packages to skip the binaries from
+    pub exclude: Vec<String>,
+    /// Binaries to select, all the binaries in the packages when it's empty
+    pub bins: Vec<String>,
+    /// Whether to select examples instead of binaries
+    pub examples: bool,
+}
+
+impl BinarySelection {
+    /// Names of the binary targets selected in the project.
+    /// It fails when a selected package is not a member of the project,
+    /// or when a selected binary is not in the selected packages.
+    pub fn select(&self, metadata: &CargoMetadata) -> Result<HashSet<String>, MetadataError> {
+        for name in &self.packages {
+            if !metadata.packages.iter().any(|p| p.name == spec_name(name)) {
+                return Err(MetadataError::MissingPackage(name.clone()));
+            }
+        }
+
+        let target_filter = if self.examples {
+            kind_example_filter
+        } else {
+            kind_bin_filter
+        };
+
+        let targets = metadata
+            .packages
+            .iter()
+            .filter(|p| self.includes_package(p))
+            .flat_map(|p| p.targets.iter())
+            .filter(|t| target_filter(t))
+            .map(|t| t.name.clone())
+            .collect::<HashSet<_>>();
+
+        if self.bins.is_empty() {
+            return Ok(targets);
+        }
+
+        let mut selected = HashSet::with_capacity(self.bins.len());
+        for name in &self.bins {
+            if !targets.contains(name) {
+                return Err(MetadataError::MissingSelectedBinary(name.clone()));
+            }
+            selected.insert(name.clone());
+        }
+        Ok(selected)
+    }
+
+    /// Name of the only binary target selected in the project.
+    pub fn single(&self, metadata: &CargoMetadata) -> Result<String, MetadataError> {
+        let targets = self.select(metadata)?;
+        if targets.len() > 1 {
+            let mut vec = targets.into_iter().collect::<Vec<_>>();
+            vec.sort();
+            Err(MetadataError::MultipleBinariesInProject(vec.join(", ")))
+        } else {
+            targets
+                .into_iter()
+                .next()
+                .ok_or(MetadataError::MissingBinaryInProject)
+        }
+    }
+
+    fn includes_package(&self, package: &CargoPackage) -> bool {
+        let name = package.name.as_str();
+        (self.packages.is_empty() || self.packages.iter().any(|p| spec_name(p) == name))
+            && !self.exclude.iter().any(|p| spec_name(p) == name)
+    }
+}
+
+/// Name of the package in a package spec, like `basic-lambda@0.1.0`.
+fn spec_name(spec: &str) -> &str {
+    spec.split_once('@').map(|(name, _)| name).unwrap_or(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cargo::load_metadata, tests::fixture_metadata};
+
+    fn names(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_select_binaries_in_workspace() {
+        let metadata = load_metadata(fixture_metadata("workspace-package")).unwrap();
+
+        let selection = BinarySelection::default();
+        assert_eq!(
+            names(&["basic-lambda-1", "basic-lambda-2", "crate-3"]),
+            selection.select(&metadata).unwrap()
+        );
+
+        let selection = BinarySelection {
+            packages: vec!["basic-lambda-1".into(), "crate-3@0.1.0".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&["basic-lambda-1", "crate-3"]),
+            selection.select(&metadata).unwrap()
+        );
+
+        let selection = BinarySelection {
+            exclude: vec!["basic-lambda-1".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&["basic-lambda-2", "crate-3"]),
+            selection.select(&metadata).unwrap()
+        );
+
+        let selection = BinarySelection {
+            bins: vec!["crate-3".into()],
+            ..Default::default()
+        };
+        assert_eq!(names(&["crate-3"]), selection.select(&metadata).unwrap());
+        assert_eq!("crate-3", selection.single(&metadata).unwrap());
+    }
+
+    #[test]
+    fn test_select_binaries_errors() {
+        let metadata = load_metadata(fixture_metadata("workspace-package")).unwrap();
+
+        let selection = BinarySelection {
+            packages: vec!["unknown".into()],
+            ..Default::default()
+        };
+        let err = selection.select(&metadata).unwrap_err();
+        assert!(matches!(err, MetadataError::MissingPackage(name) if name == "unknown"));
+
+        let selection = BinarySelection {
+            bins: vec!["crate-3".into()],
+            exclude: vec!["crate-3".into()],
+            ..Default::default()
+        };
+        let err = selection.select(&metadata).unwrap_err();
+        assert!(matches!(err, MetadataError::MissingSelectedBinary(name) if name == "crate-3"));
+
+        let err = BinarySelection::default().single(&metadata).unwrap_err();
+        assert!(matches!(err, MetadataError::MultipleBinariesInProject(_)));
+
+        let selection = BinarySelection {
+            packages: vec!["basic-lambda-2".into()],
+            exclude: vec!["basic-lambda-2".into()],
+            ..Default::default()
+        };
+        let err = selection.single(&metadata).unwrap_err();
+        assert!(matches!(err, MetadataError::MissingBinaryInProject));
+    }
+}
@@ -0,0 +1,240 @@
+use clap::{Args, Command};
+use serde_json::Value;
+use std::fmt;
+
+use crate::cargo::{CargoMetadata, build::Build, deploy::Deploy, watch::Watch};
+
+/// Keys that serde accepts in the metadata, but that are not CLI flags.
+pub(crate) const BUILD_EXTRA_KEYS: &[&str] = &[];
+pub(crate) const DEPLOY_EXTRA_KEYS: &[&str] = &[
+    "bin",
+    "env",
+    "iam_role",
+    "layers",
+    "log_retention_days",
+    "tags",
+];
+/// Arguments that only make sense in the command line, they're not keys in the metadata.
+pub(crate) const CLI_ONLY_KEYS: &[&str] = &["listen_fd"];
+pub(crate) const WATCH_EXTRA_KEYS: &[&str] = &["env", "lazy", "route_prefix", "router"];
+
+/// Sections allowed in `[package.metadata.lambda]`, and in each binary section.
+const SECTION_KEYS: &[&str] = &["build", "deploy", "env", "watch"];
+
+/// A key in the metadata that Cargo Lambda doesn't know about.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnknownKey {
+    /// The package where the key was found, None for workspace metadata
+    pub package: Option<String>,
+    /// The table where the key was found, like `package.metadata.lambda.deploy`
+    pub table: String,
+    /// The unknown key
+    pub key: String,
+    /// The valid key that is the closest match to the unknown key
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown key `{}` in `[{}]`", self.key, self.table)?;
+        if let Some(package) = &self.package {
+            write!(f, " of package `{package}`")?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the keys in the workspace and package metadata
+/// that Cargo Lambda would ignore silently.
+pub fn unknown_metadata_keys(metadata: &CargoMetadata) -> Vec<UnknownKey> {
+    let mut unknown = Vec::new();
+
+    if let Some(lambda) = metadata.workspace_metadata.get("lambda") {
+        unknown.extend(unknown_lambda_keys("workspace.metadata.lambda", lambda));
+    }
+
+    for pkg in metadata.workspace_packages() {
+        if let Some(lambda) = pkg.metadata.get("lambda") {
+            let keys = unknown_lambda_keys("package.metadata.lambda", lambda);
+            unknown.extend(keys.into_iter().map(|key| UnknownKey {
+                package: Some(pkg.name.clone()),
+                ..key
+            }));
+        }
+    }
+
+    unknown
+}
+
+/// Find the unknown keys in a `lambda` metadata table.
+pub(crate) fn unknown_lambda_keys(table: &str, lambda: &Value) -> Vec<UnknownKey> {
+    let mut valid = SECTION_KEYS.to_vec();
+    valid.push("bin");
+
+    let mut unknown = unknown_section_keys(table, lambda, &valid);
+
+    if let Some(bins) = lambda.get("bin").and_then(Value::as_object) {
+        for (name, bin) in bins {
+            let table = format!("{table}.bin.{name}");
+            unknown.extend(unknown_section_keys(&table, bin, SECTION_KEYS));
+        }
+    }
+
+    unknown
+}
+
+fn unknown_section_keys(table: &str, value: &Value, valid: &[&str]) -> Vec<UnknownKey> {
+    let Some(sections) = value.as_object() else {
+        return Vec::new();
+    };
+
+    let mut unknown = unknown_keys(table, value, valid);
+    for (section, value) in sections {
+        let keys = match section.as_str() {
+            "build" => command_keys::<Build>(BUILD_EXTRA_KEYS),
+            "deploy" => command_keys::<Deploy>(DEPLOY_EXTRA_KEYS),
+            "watch" => command_keys::<Watch>(WATCH_EXTRA_KEYS),
+            _ => continue,
+        };
+        let keys = keys.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let table = format!("{table}.{section}");
+        unknown.extend(unknown_keys(&table, value, &keys));
+    }
+
+    unknown
+}
+
+fn unknown_keys(table: &str, value: &Value, valid: &[&str]) -> Vec<UnknownKey> {
+    let Some(object) = value.as_object() else {
+        return Vec::new();
+    };
+
+    object
+        .keys()
+        .filter(|key| !valid.contains(&key.as_str()))
+        .map(|key| UnknownKey {
+            package: None,
+            table: table.to_string(),
+            key: key.clone(),
+            suggestion: closest_match(key, valid),
+        })
+        .collect()
+}
+
+/// The keys for a command are the ids of its arguments,
+/// which match the names of the fields in the metadata.
+fn command_keys<T: Args>(extra: &[&str]) -> Vec<String> {
+    let command = T::augment_args(Command::new("cargo-lambda"));
+    command
+        .get_arguments()
+        .map(|arg| arg.get_id().to_string())
+        .filter(|id| !CLI_ONLY_KEYS.contains(&id.as_str()))
+        .chain(extra.iter().map(|key| key.to_string()))
+        .collect()
+}
+
+pub(crate) fn closest_match(key: &str, valid: &[&str]) -> Option<String> {
+    valid
+        .iter()
+        .map(|candidate| (strsim::jaro_winkler(key, candidate), candidate))
+        .filter(|(score, _)| *score > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{cargo::load_metadata, tests::fixture_metadata};
+
+    #[test]
+    fn test_unknown_lambda_keys() {
+        let lambda = json!({
+            "deploy": {
+                "memroy": 512,
+                "timeout": 60,
+                "tags": {"team": "lambda"},
+            },
+            "watch": {
+                "invoke_port": 9001,
+                "something_else": true,
+            },
+            "buld": {},
+            "bin": {
+                "basic-lambda": {
+                    "build": {"relase": true},
+                },
+            },
+        });
+
+        let unknown = unknown_lambda_keys("package.metadata.lambda", &lambda);
+        assert_eq!(
+            vec![
+                UnknownKey {
+                    package: None,
+                    table: "package.metadata.lambda".into(),
+                    key: "buld".into(),
+                    suggestion: Some("build".into()),
+                },
+                UnknownKey {
+                    package: None,
+                    table: "package.metadata.lambda.deploy".into(),
+                    key: "memroy".into(),
+                    suggestion: Some("memory".into()),
+                },
+                UnknownKey {
+                    package: None,
+                    table: "package.metadata.lambda.watch".into(),
+                    key: "something_else".into(),
+                    suggestion: None,
+                },
+                UnknownKey {
+                    package: None,
+                    table: "package.metadata.lambda.bin.basic-lambda.build".into(),
+                    key: "relase".into(),
+                    suggestion: Some("release".into()),
+                },
+            ],
+            unknown
+        );
+
+        assert_eq!(
+            "unknown key `memroy` in `[package.metadata.lambda.deploy]`, did you mean `memory`?",
+            unknown[1].to_string()
+        );
+
+        let key = UnknownKey {
+            package: Some("basic-lambda".into()),
+            ..unknown[2].clone()
+        };
+        assert_eq!(
+            "unknown key `something_else` in `[package.metadata.lambda.watch]` of package `basic-lambda`",
+            key.to_string()
+        );
+    }
+
+    #[test]
+    fn test_fixtures_have_no_unknown_keys() {
+        for fixture in [
+            "single-binary-package",
+            "multi-binary-package",
+            "workspace-package",
+            "config-with-context",
+            "package-with-global-config",
+            "watch-multi-binary",
+        ] {
+            let metadata = load_metadata(fixture_metadata(fixture)).unwrap();
+            assert_eq!(
+                Vec::<UnknownKey>::new(),
+                unknown_metadata_keys(&metadata),
+                "unexpected unknown keys in fixture {fixture}"
+            );
+        }
+    }
+}
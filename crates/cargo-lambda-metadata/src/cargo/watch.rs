@@ -1,5 +1,5 @@
 use cargo_options::Run;
-use clap::Args;
+use clap::{Args, ValueHint, builder::TypedValueParser};
 use matchit::{InsertError, MatchError, Router};
 use serde::{
     Deserialize, Serialize,
@@ -7,13 +7,20 @@ use serde::{
     ser::SerializeSeq,
 };
 use serde_json::{Value, json};
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use strum_macros::{Display, EnumString};
 
 use crate::{
-    cargo::{count_common_options, serialize_common_options},
+    cargo::{count_common_options, selection::BinarySelection, serialize_common_options},
     env::{EnvOptions, Environment},
     error::MetadataError,
-    lambda::Timeout,
+    lambda::{Memory, Timeout},
 };
 
 use cargo_lambda_remote::tls::TlsOptions;
@@ -25,6 +32,7 @@ const DEFAULT_INVOKE_ADDRESS: &str = "127.0.0.1";
 const DEFAULT_INVOKE_ADDRESS: &str = "::";
 
 const DEFAULT_INVOKE_PORT: u16 = 9000;
+const DEFAULT_INVOKE_QUEUE_SIZE: usize = 100;
 
 #[derive(Args, Clone, Debug, Default, Deserialize)]
 #[command(
@@ -44,24 +52,64 @@ pub struct Watch {
     #[serde(default)]
     pub only_lambda_apis: bool,
 
+    /// Answer invoke requests like the AWS Lambda Runtime Interface Emulator does,
+    /// so scripts and SDK clients written for it work without changes
+    #[arg(long)]
+    #[serde(default)]
+    pub rie_compatible: bool,
+
     #[arg(short = 'a', long, default_value = DEFAULT_INVOKE_ADDRESS)]
     #[serde(default = "default_invoke_address")]
     /// Address where users send invoke requests
     pub invoke_address: String,
 
-    /// Address port where users send invoke requests
-    #[arg(short = 'P', long, default_value_t = DEFAULT_INVOKE_PORT)]
-    #[serde(default = "default_invoke_port")]
-    pub invoke_port: u16,
+    /// Address port where users send invoke requests [default: 9000].
+    /// Use `0` to let the system pick a port that's available
+    #[arg(short = 'P', long)]
+    #[serde(default)]
+    pub invoke_port: Option<u16>,
+
+    /// Fail when the invoke port is in use, instead of
+    /// listening on the next port that's available
+    #[arg(long)]
+    #[serde(default)]
+    pub strict_port: bool,
+
+    /// Write the port where users send invoke requests to this file
+    /// when the server starts. The file is removed when the server stops
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    pub port_file: Option<PathBuf>,
+
+    /// Accept connections on a socket that's already listening, passed as this file descriptor,
+    /// instead of binding the invoke address and port. Sockets passed with systemd's
+    /// socket activation, in the LISTEN_FDS variable, are used automatically
+    #[arg(long, value_name = "FD", conflicts_with_all = ["invoke_port", "strict_port"])]
+    #[serde(skip)]
+    pub listen_fd: Option<i32>,
+
+    /// Host that functions use to reach the runtime API, like `host.docker.internal`,
+    /// when they run in a different container or network than the server.
+    /// It can include a port, like `lambda-dev:9001`, if the port is mapped to a different one
+    #[arg(long, value_name = "HOST", value_parser = parse_runtime_api_host)]
+    #[serde(default)]
+    pub runtime_api_host: Option<String>,
+
+    /// Stop the processes left running by a previous session that didn't exit cleanly,
+    /// without asking for confirmation
+    #[arg(long)]
+    #[serde(default)]
+    pub kill_orphans: bool,
 
     /// Print OpenTelemetry traces after each function invocation
     #[arg(long)]
     #[serde(default)]
     pub print_traces: bool,
 
-    /// Wait for the first invocation to compile the function
-    #[arg(long, short)]
-    #[serde(default)]
+    /// Wait for the first invocation to compile the functions, instead of
+    /// compiling them when the server starts
+    #[arg(long, short, visible_alias = "lazy")]
+    #[serde(default, alias = "lazy")]
     pub wait: bool,
 
     /// Disable the default CORS configuration
@@ -74,6 +122,119 @@ pub struct Watch {
     #[serde(default)]
     pub timeout: Option<Timeout>,
 
+    /// How long the server waits for in-flight invocations to finish
+    /// when it shuts down, in seconds [default: 10]
+    #[arg(long)]
+    #[serde(default)]
+    pub grace_period: Option<Timeout>,
+
+    /// Maximum number of invocations waiting for each function [default: 100].
+    /// New invocations are rejected with a `429 Too Many Requests` status when the queue is full
+    #[arg(long)]
+    #[serde(default)]
+    pub invoke_queue_size: Option<usize>,
+
+    /// Maximum number of invocations in flight for each function, like the reserved
+    /// concurrency in Lambda. Invocations over the limit are rejected with a
+    /// `429 Too Many Requests` status, instead of waiting in the function's queue
+    #[arg(long, value_name = "N")]
+    #[serde(default)]
+    pub reserved_concurrency: Option<usize>,
+
+    /// Write the output of each function to `<name>.log` files in this directory,
+    /// in addition to the console [default: target/lambda/logs]
+    #[arg(long, num_args = 0..=1, default_missing_value = "")]
+    #[serde(default)]
+    pub log_dir: Option<PathBuf>,
+
+    /// Prefix each line of the functions' output with the function name,
+    /// acceptable values are [auto, always, never]. In auto mode, the output
+    /// is prefixed when the project has more than one function
+    #[arg(long)]
+    #[serde(default)]
+    pub log_prefix: Option<LogPrefix>,
+
+    /// Format of the events sent to the functions for HTTP requests,
+    /// acceptable values are [function-url, apigw-http, apigw-rest, alb] [default: function-url]
+    #[arg(long)]
+    #[serde(default)]
+    pub event_format: Option<EventFormat>,
+
+    /// JSON file with a template to build the events sent to the functions for HTTP requests,
+    /// for proxy integrations that don't match any of the event formats
+    #[arg(long, value_hint = ValueHint::FilePath, conflicts_with = "event_format")]
+    #[serde(default)]
+    pub event_mapping: Option<PathBuf>,
+
+    /// OpenAPI document, in JSON or YAML, with `x-amazon-apigateway-integration` extensions.
+    /// Requests for its operations are sent to the functions in the integrations
+    /// with the events that API Gateway sends for each route
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    pub openapi: Option<PathBuf>,
+
+    /// Record the HTTP requests that the functions receive, and their responses,
+    /// in an HTTP Archive (HAR) file
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    pub har_out: Option<PathBuf>,
+
+    /// JSON file with fields to merge into the `requestContext` of the events
+    /// sent to the functions for HTTP requests, like authorizer claims, or the source IP
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    pub request_context: Option<PathBuf>,
+
+    /// Decode the bearer token in the Authorization header of HTTP requests, and add its claims
+    /// to the `requestContext` of the events, like the JWT authorizers of API Gateway HTTP APIs.
+    /// Requests without a valid token are rejected with a `401 Unauthorized` status
+    #[arg(long)]
+    #[serde(default)]
+    pub jwt_authorizer: bool,
+
+    /// JSON Web Key Set file with the keys to verify the signature of the bearer tokens,
+    /// and their expiration. It implies `--jwt-authorizer`
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    pub jwks: Option<PathBuf>,
+
+    /// Shell command that runs before each invocation is sent to a function,
+    /// with the invocation payload in its standard input
+    #[arg(long, value_name = "COMMAND")]
+    #[serde(default)]
+    pub pre_invoke: Option<String>,
+
+    /// Shell command that runs after a function responds to each invocation,
+    /// with the invocation payload in its standard input
+    #[arg(long, value_name = "COMMAND")]
+    #[serde(default)]
+    pub post_invoke: Option<String>,
+
+    /// Package to exclude from the functions that the server runs (see `cargo help pkgid`)
+    #[arg(long, value_name = "SPEC")]
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Glob of files that don't reload the functions when they change, in the same format as `.gitignore` files.
+    /// It allows multiple repetitions (--ignore '*.md' --ignore 'fixtures/**')
+    #[arg(long, value_name = "GLOB")]
+    #[serde(default)]
+    pub ignore: Vec<String>,
+
+    /// Route that sends the HTTP requests for a method and path to a function, like `GET /users/{id}=get-user`.
+    /// The method is optional, and `*` at the end of the path matches any subpath.
+    /// It allows multiple repetitions (--route 'GET /users/*=get-users' --route 'POST /users=create-user')
+    #[arg(long, value_name = "ROUTE", value_parser = parse_route)]
+    #[serde(default)]
+    pub route: Vec<String>,
+
+    /// Run a binary that's already built instead of compiling the function with `cargo run`,
+    /// like `target/lambda/orders/bootstrap`. The function restarts when the binary changes,
+    /// so it can be built in another terminal, in a container, or with a different build tool
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    pub bin_path: Option<PathBuf>,
+
     #[command(flatten)]
     #[serde(flatten)]
     pub cargo_opts: Run,
@@ -86,9 +247,27 @@ pub struct Watch {
     #[serde(flatten)]
     pub tls_options: TlsOptions,
 
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub alb_options: AlbOptions,
+
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub fault_options: FaultOptions,
+
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub sandbox_options: SandboxOptions,
+
     #[arg(skip)]
     #[serde(default)]
     pub router: Option<FunctionRouter>,
+
+    /// Path prefix of the HTTP requests sent to a function,
+    /// when the requests don't match any route in the router
+    #[arg(skip)]
+    #[serde(default)]
+    pub route_prefix: Option<String>,
 }
 
 impl Watch {
@@ -99,6 +278,18 @@ impl Watch {
             .unwrap_or_else(|| "Cargo.toml".into())
     }
 
+    /// Port where users send invoke requests, `0` means
+    /// that the system picks a port that's available.
+    pub fn invoke_port(&self) -> u16 {
+        self.invoke_port.unwrap_or(DEFAULT_INVOKE_PORT)
+    }
+
+    pub fn invoke_queue_size(&self) -> usize {
+        self.invoke_queue_size
+            .unwrap_or(DEFAULT_INVOKE_QUEUE_SIZE)
+            .max(1)
+    }
+
     /// Returns the package name if there is only one package in the list of `packages`,
     /// otherwise None.
     pub fn package(&self) -> Option<String> {
@@ -108,12 +299,121 @@ impl Watch {
         self.cargo_opts.packages.first().map(|s| s.to_string())
     }
 
+    /// Name of the function that runs the binary in `bin_path`. Binaries named
+    /// `bootstrap`, like the ones in `target/lambda`, take the name of their directory.
+    pub fn bin_path_function_name(&self) -> Option<String> {
+        let path = self.bin_path.as_deref()?;
+        let name = path.file_stem()?.to_str()?;
+        let dir = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str());
+        match (name, dir) {
+            ("bootstrap", Some(dir)) => Some(dir.to_string()),
+            (name, _) => Some(name.to_string()),
+        }
+    }
+
+    /// Binary targets that the server runs as functions.
+    pub fn binary_selection(&self) -> BinarySelection {
+        BinarySelection {
+            packages: self.cargo_opts.packages.clone(),
+            exclude: self.exclude.clone(),
+            bins: self.cargo_opts.bin.clone(),
+            examples: false,
+        }
+    }
+
     pub fn lambda_environment(
         &self,
         base: &HashMap<String, String>,
     ) -> Result<Environment, MetadataError> {
         self.env_options.lambda_environment(base)
     }
+
+    /// Add the routes from the `--route` flags to the router.
+    /// They replace the existing routes for the same method and path.
+    pub fn insert_routes(&self, router: &mut FunctionRouter) -> Result<(), MetadataError> {
+        for route in &self.route {
+            let route = RouteSpec::from_str(route)?;
+            router.insert_route(route.method.as_deref(), &route.path, &route.function)?;
+        }
+        Ok(())
+    }
+
+    /// Format of the events sent to the functions for HTTP requests.
+    /// The flag `--alb` is a shortcut for the `alb` format.
+    pub fn event_format(&self) -> EventFormat {
+        if self.alb_options.alb {
+            EventFormat::Alb
+        } else {
+            self.event_format.unwrap_or_default()
+        }
+    }
+
+    /// Host that functions use to reach the runtime API, instead of the server's address.
+    pub fn runtime_api_host(&self) -> Result<Option<RuntimeApiHost>, MetadataError> {
+        self.runtime_api_host
+            .as_deref()
+            .map(RuntimeApiHost::from_str)
+            .transpose()
+    }
+
+    /// Whether the server emulates the JWT authorizers of HTTP APIs.
+    /// The flag `--jwks` enables it too.
+    pub fn jwt_authorizer(&self) -> bool {
+        self.jwt_authorizer || self.jwks.is_some()
+    }
+
+    /// Returns whether the functions' output is prefixed with their names,
+    /// given the number of functions in the project.
+    pub fn use_log_prefix(&self, functions: usize) -> bool {
+        match self.log_prefix.as_ref().unwrap_or(&LogPrefix::Auto) {
+            LogPrefix::Auto => functions > 1,
+            LogPrefix::Always => true,
+            LogPrefix::Never => false,
+        }
+    }
+
+    /// Returns the directory where the functions' logs are written.
+    /// The default directory is `lambda/logs` inside the target directory.
+    pub fn log_dir(&self, target_dir: &Path) -> Option<PathBuf> {
+        self.log_dir.as_ref().map(|dir| {
+            if dir.as_os_str().is_empty() {
+                target_dir.join("lambda").join("logs")
+            } else {
+                dir.clone()
+            }
+        })
+    }
+}
+
+/// Format of the events sent to the functions for HTTP requests.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Display, EnumString, Eq, PartialEq, Serialize,
+)]
+#[strum(ascii_case_insensitive, serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum EventFormat {
+    /// Lambda function URL events
+    #[default]
+    FunctionUrl,
+    /// API Gateway HTTP API events, payload format version 2.0
+    ApigwHttp,
+    /// API Gateway REST API events, payload format version 1.0
+    ApigwRest,
+    /// Application Load Balancer target group events
+    Alb,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Display, EnumString, Eq, PartialEq, Serialize)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum LogPrefix {
+    #[default]
+    Auto,
+    Always,
+    Never,
 }
 
 impl Serialize for Watch {
@@ -126,13 +426,37 @@ impl Serialize for Watch {
         // Count non-empty fields
         let field_count = self.ignore_changes as usize
             + self.only_lambda_apis as usize
+            + self.rie_compatible as usize
             + !self.invoke_address.is_empty() as usize
-            + (self.invoke_port != 0) as usize
+            + self.invoke_port.is_some() as usize
+            + self.strict_port as usize
+            + self.port_file.is_some() as usize
+            + self.runtime_api_host.is_some() as usize
+            + self.kill_orphans as usize
             + self.print_traces as usize
             + self.wait as usize
             + self.disable_cors as usize
             + self.timeout.is_some() as usize
+            + self.grace_period.is_some() as usize
+            + self.invoke_queue_size.is_some() as usize
+            + self.reserved_concurrency.is_some() as usize
+            + self.log_dir.is_some() as usize
+            + self.log_prefix.is_some() as usize
+            + self.event_format.is_some() as usize
+            + self.event_mapping.is_some() as usize
+            + self.openapi.is_some() as usize
+            + self.har_out.is_some() as usize
+            + self.request_context.is_some() as usize
+            + self.jwt_authorizer as usize
+            + self.jwks.is_some() as usize
+            + self.pre_invoke.is_some() as usize
+            + self.post_invoke.is_some() as usize
+            + !self.exclude.is_empty() as usize
+            + !self.ignore.is_empty() as usize
+            + !self.route.is_empty() as usize
+            + self.bin_path.is_some() as usize
             + self.router.is_some() as usize
+            + self.route_prefix.is_some() as usize
             + self.cargo_opts.manifest_path.is_some() as usize
             + self.cargo_opts.release as usize
             + self.cargo_opts.ignore_rust_version as usize
@@ -143,7 +467,10 @@ impl Serialize for Watch {
             + !self.cargo_opts.args.is_empty() as usize
             + count_common_options(&self.cargo_opts.common)
             + self.env_options.count_fields()
-            + self.tls_options.count_fields();
+            + self.tls_options.count_fields()
+            + self.alb_options.count_fields()
+            + self.fault_options.count_fields()
+            + self.sandbox_options.count_fields();
 
         let mut state = serializer.serialize_struct("Watch", field_count)?;
 
@@ -154,11 +481,26 @@ impl Serialize for Watch {
         if self.only_lambda_apis {
             state.serialize_field("only_lambda_apis", &true)?;
         }
+        if self.rie_compatible {
+            state.serialize_field("rie_compatible", &true)?;
+        }
         if !self.invoke_address.is_empty() {
             state.serialize_field("invoke_address", &self.invoke_address)?;
         }
-        if self.invoke_port != 0 {
-            state.serialize_field("invoke_port", &self.invoke_port)?;
+        if let Some(invoke_port) = &self.invoke_port {
+            state.serialize_field("invoke_port", invoke_port)?;
+        }
+        if self.strict_port {
+            state.serialize_field("strict_port", &true)?;
+        }
+        if let Some(port_file) = &self.port_file {
+            state.serialize_field("port_file", port_file)?;
+        }
+        if let Some(runtime_api_host) = &self.runtime_api_host {
+            state.serialize_field("runtime_api_host", runtime_api_host)?;
+        }
+        if self.kill_orphans {
+            state.serialize_field("kill_orphans", &true)?;
         }
         if self.print_traces {
             state.serialize_field("print_traces", &true)?;
@@ -174,13 +516,73 @@ impl Serialize for Watch {
         if let Some(timeout) = &self.timeout {
             state.serialize_field("timeout", timeout)?;
         }
+        if let Some(grace_period) = &self.grace_period {
+            state.serialize_field("grace_period", grace_period)?;
+        }
+        if let Some(invoke_queue_size) = &self.invoke_queue_size {
+            state.serialize_field("invoke_queue_size", invoke_queue_size)?;
+        }
+        if let Some(reserved_concurrency) = &self.reserved_concurrency {
+            state.serialize_field("reserved_concurrency", reserved_concurrency)?;
+        }
+        if let Some(log_dir) = &self.log_dir {
+            state.serialize_field("log_dir", log_dir)?;
+        }
+        if let Some(log_prefix) = &self.log_prefix {
+            state.serialize_field("log_prefix", log_prefix)?;
+        }
+        if let Some(event_format) = &self.event_format {
+            state.serialize_field("event_format", event_format)?;
+        }
+        if let Some(event_mapping) = &self.event_mapping {
+            state.serialize_field("event_mapping", event_mapping)?;
+        }
+        if let Some(openapi) = &self.openapi {
+            state.serialize_field("openapi", openapi)?;
+        }
+        if let Some(har_out) = &self.har_out {
+            state.serialize_field("har_out", har_out)?;
+        }
+        if let Some(request_context) = &self.request_context {
+            state.serialize_field("request_context", request_context)?;
+        }
+        if self.jwt_authorizer {
+            state.serialize_field("jwt_authorizer", &true)?;
+        }
+        if let Some(jwks) = &self.jwks {
+            state.serialize_field("jwks", jwks)?;
+        }
+        if let Some(pre_invoke) = &self.pre_invoke {
+            state.serialize_field("pre_invoke", pre_invoke)?;
+        }
+        if let Some(post_invoke) = &self.post_invoke {
+            state.serialize_field("post_invoke", post_invoke)?;
+        }
+        if !self.exclude.is_empty() {
+            state.serialize_field("exclude", &self.exclude)?;
+        }
+        if !self.ignore.is_empty() {
+            state.serialize_field("ignore", &self.ignore)?;
+        }
+        if !self.route.is_empty() {
+            state.serialize_field("route", &self.route)?;
+        }
+        if let Some(bin_path) = &self.bin_path {
+            state.serialize_field("bin_path", bin_path)?;
+        }
         if let Some(router) = &self.router {
             state.serialize_field("router", router)?;
         }
+        if let Some(route_prefix) = &self.route_prefix {
+            state.serialize_field("route_prefix", route_prefix)?;
+        }
 
         // Flatten the fields from cargo_opts and env_options
         self.env_options.serialize_fields::<S>(&mut state)?;
         self.tls_options.serialize_fields::<S>(&mut state)?;
+        self.alb_options.serialize_fields::<S>(&mut state)?;
+        self.fault_options.serialize_fields::<S>(&mut state)?;
+        self.sandbox_options.serialize_fields::<S>(&mut state)?;
 
         if let Some(manifest_path) = &self.cargo_opts.manifest_path {
             state.serialize_field("manifest_path", manifest_path)?;
@@ -212,48 +614,578 @@ impl Serialize for Watch {
     }
 }
 
+#[derive(Args, Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AlbOptions {
+    /// Send Application Load Balancer events to the function instead of Function URL events
+    #[arg(long)]
+    #[serde(default)]
+    pub alb: bool,
+
+    /// Encode headers and query strings as multi-value fields, like target groups with multi-value headers enabled
+    #[arg(long, requires = "alb")]
+    #[serde(default)]
+    pub alb_multi_value_headers: bool,
+
+    /// Path that answers load balancer health checks without invoking the function
+    #[arg(long = "alb-health-check-path", requires = "alb")]
+    #[serde(default)]
+    pub alb_health_check_paths: Option<Vec<String>>,
+}
+
+impl AlbOptions {
+    pub fn is_health_check(&self, path: &str) -> bool {
+        self.alb
+            && self
+                .alb_health_check_paths
+                .as_ref()
+                .is_some_and(|paths| paths.iter().any(|p| p == path))
+    }
+
+    pub fn count_fields(&self) -> usize {
+        self.alb as usize
+            + self.alb_multi_value_headers as usize
+            + self.alb_health_check_paths.is_some() as usize
+    }
+
+    pub fn serialize_fields<S>(
+        &self,
+        state: &mut <S as serde::Serializer>::SerializeStruct,
+    ) -> Result<(), S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        if self.alb {
+            state.serialize_field("alb", &true)?;
+        }
+        if self.alb_multi_value_headers {
+            state.serialize_field("alb_multi_value_headers", &true)?;
+        }
+        if let Some(paths) = &self.alb_health_check_paths {
+            state.serialize_field("alb_health_check_paths", paths)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FaultOptions {
+    /// Simulate a failure in the functions' initialization.
+    /// Invocations are answered with a `Runtime.InitError` error without reaching the functions
+    #[arg(long)]
+    #[serde(default)]
+    pub fail_init: bool,
+
+    /// Simulate a slow initialization, delaying the first invocation
+    /// after each function starts, like `5s`, or `500ms`
+    #[arg(long, value_parser = parse_init_delay)]
+    #[serde(default)]
+    pub delay_init: Option<String>,
+
+    /// Kill the functions with a `Runtime.OutOfMemory` error when their memory usage reaches
+    /// this threshold, as a percentage of the function's memory like `80%`, or in megabytes like `512MB`.
+    /// Only available on Linux
+    #[arg(long, value_parser = parse_memory_threshold)]
+    #[serde(default)]
+    pub oom_at: Option<String>,
+}
+
+impl FaultOptions {
+    /// Time to delay the first invocation after a function starts.
+    pub fn init_delay(&self) -> Result<Option<Duration>, MetadataError> {
+        self.delay_init.as_deref().map(init_delay).transpose()
+    }
+
+    /// Memory usage that triggers an out of memory error.
+    pub fn memory_threshold(&self) -> Result<Option<MemoryThreshold>, MetadataError> {
+        self.oom_at
+            .as_deref()
+            .map(MemoryThreshold::from_str)
+            .transpose()
+    }
+
+    pub fn count_fields(&self) -> usize {
+        self.fail_init as usize
+            + self.delay_init.is_some() as usize
+            + self.oom_at.is_some() as usize
+    }
+
+    pub fn serialize_fields<S>(
+        &self,
+        state: &mut <S as serde::Serializer>::SerializeStruct,
+    ) -> Result<(), S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        if self.fail_init {
+            state.serialize_field("fail_init", &true)?;
+        }
+        if let Some(delay_init) = &self.delay_init {
+            state.serialize_field("delay_init", delay_init)?;
+        }
+        if let Some(oom_at) = &self.oom_at {
+            state.serialize_field("oom_at", oom_at)?;
+        }
+        Ok(())
+    }
+}
+
+/// Memory usage that triggers a simulated out of memory error.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MemoryThreshold {
+    /// Percentage of the function's memory
+    Percentage(u8),
+    /// Absolute amount of memory, in megabytes
+    Megabytes(u64),
+}
+
+impl MemoryThreshold {
+    /// Number of bytes that trigger the error, for a function with `memory_size` megabytes.
+    pub fn bytes(&self, memory_size: u64) -> u64 {
+        let megabytes = match self {
+            MemoryThreshold::Percentage(p) => memory_size * *p as u64 / 100,
+            MemoryThreshold::Megabytes(mb) => *mb,
+        };
+        megabytes * 1024 * 1024
+    }
+}
+
+impl FromStr for MemoryThreshold {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MetadataError::InvalidMemoryThreshold(s.to_string());
+        let value = s.trim();
+
+        if let Some(percentage) = value.strip_suffix('%') {
+            let percentage = u8::from_str(percentage.trim()).map_err(|_| invalid())?;
+            if !(1..=100).contains(&percentage) {
+                return Err(invalid());
+            }
+            return Ok(MemoryThreshold::Percentage(percentage));
+        }
+
+        let megabytes = value
+            .strip_suffix("MB")
+            .or_else(|| value.strip_suffix("mb"))
+            .ok_or_else(invalid)?;
+        match u64::from_str(megabytes.trim()) {
+            Ok(mb) if mb > 0 => Ok(MemoryThreshold::Megabytes(mb)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug, Default, Deserialize)]
+pub struct SandboxOptions {
+    /// Run the functions inside a cgroup that limits their memory, so the system kills them
+    /// when they run out of memory, like Lambda does. It uses `systemd-run`, only available on Linux
+    #[arg(long)]
+    #[serde(default)]
+    pub sandbox: bool,
+
+    /// Memory allocated for the functions, in megabytes [default: 4096]
+    #[arg(long, alias = "memory-size", value_parser = clap::value_parser!(i32).range(128..=10240).map(Memory))]
+    #[serde(default)]
+    pub memory: Option<Memory>,
+
+    /// Resource limit of the functions in the sandbox, like `nofile=1024`, or `nproc=1024`.
+    /// It allows multiple repetitions (--ulimit nofile=1024 --ulimit nproc=1024)
+    #[arg(long, value_name = "LIMIT", value_parser = parse_resource_limit)]
+    #[serde(default)]
+    pub ulimit: Vec<String>,
+}
+
+impl SandboxOptions {
+    /// Resource limits applied to the functions in the sandbox.
+    pub fn resource_limits(&self) -> Result<Vec<ResourceLimit>, MetadataError> {
+        self.ulimit
+            .iter()
+            .map(|limit| ResourceLimit::from_str(limit))
+            .collect()
+    }
+
+    pub fn count_fields(&self) -> usize {
+        self.sandbox as usize + self.memory.is_some() as usize + !self.ulimit.is_empty() as usize
+    }
+
+    pub fn serialize_fields<S>(
+        &self,
+        state: &mut <S as serde::Serializer>::SerializeStruct,
+    ) -> Result<(), S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        if self.sandbox {
+            state.serialize_field("sandbox", &true)?;
+        }
+        if let Some(memory) = &self.memory {
+            state.serialize_field("memory", memory)?;
+        }
+        if !self.ulimit.is_empty() {
+            state.serialize_field("ulimit", &self.ulimit)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resource limit of the processes in the sandbox.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ResourceLimit {
+    /// Maximum number of open file descriptors
+    OpenFiles(u64),
+    /// Maximum number of processes and threads
+    Processes(u64),
+}
+
+impl FromStr for ResourceLimit {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MetadataError::InvalidResourceLimit(s.to_string());
+
+        let (name, value) = s.split_once('=').ok_or_else(invalid)?;
+        let value = match u64::from_str(value.trim()) {
+            Ok(value) if value > 0 => value,
+            _ => return Err(invalid()),
+        };
+        match name.trim() {
+            "nofile" => Ok(ResourceLimit::OpenFiles(value)),
+            "nproc" => Ok(ResourceLimit::Processes(value)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Host and port that functions use to reach the runtime API,
+/// when they cannot reach it in the address where the server listens.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RuntimeApiHost {
+    /// Host name or IP address, IPv6 addresses are wrapped in brackets
+    pub host: String,
+    /// Port, when it's not the same port where the runtime listens
+    pub port: Option<u16>,
+}
+
+impl RuntimeApiHost {
+    /// Address for `AWS_LAMBDA_RUNTIME_API`, with the port where the runtime
+    /// listens if the host doesn't include one.
+    pub fn address(&self, runtime_port: u16) -> String {
+        format!("{}:{}", self.host, self.port.unwrap_or(runtime_port))
+    }
+}
+
+impl FromStr for RuntimeApiHost {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim();
+
+        if let Ok(ip) = IpAddr::from_str(value) {
+            return Ok(RuntimeApiHost {
+                host: ip_host(ip),
+                port: None,
+            });
+        }
+
+        if let Ok(addr) = SocketAddr::from_str(value) {
+            return Ok(RuntimeApiHost {
+                host: ip_host(addr.ip()),
+                port: Some(addr.port()),
+            });
+        }
+
+        let (host, port) = match value.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = u16::from_str(port)
+                    .map_err(|_| MetadataError::InvalidRuntimeApiHost(s.to_string()))?;
+                (host, Some(port))
+            }
+            None => (value, None),
+        };
+
+        let valid = !host.is_empty()
+            && host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_'));
+        if !valid {
+            return Err(MetadataError::InvalidRuntimeApiHost(s.to_string()));
+        }
+
+        Ok(RuntimeApiHost {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+fn ip_host(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{ip}]"),
+    }
+}
+
+/// Parse durations like `5s`, `500ms`, or `1m`. Values without units are seconds.
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit) = split_duration(value)?;
+
+    match unit {
+        "ms" => Some(Duration::from_millis(number)),
+        "s" => Some(Duration::from_secs(number)),
+        "m" => number.checked_mul(60).map(Duration::from_secs),
+        _ => None,
+    }
+}
+
+/// Parse durations like [`parse_duration`], but also in hours, like `1h`.
+pub(crate) fn parse_long_duration(value: &str) -> Option<Duration> {
+    match split_duration(value)? {
+        (number, "h") => number.checked_mul(3600).map(Duration::from_secs),
+        _ => parse_duration(value),
+    }
+}
+
+fn split_duration(value: &str) -> Option<(u64, &str)> {
+    let value = value.trim();
+
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => value.split_at(idx),
+        None => (value, "s"),
+    };
+    let number = u64::from_str(number).ok()?;
+    Some((number, unit.trim()))
+}
+
+fn init_delay(value: &str) -> Result<Duration, MetadataError> {
+    parse_duration(value).ok_or_else(|| MetadataError::InvalidInitDelay(value.to_string()))
+}
+
+fn parse_init_delay(value: &str) -> Result<String, MetadataError> {
+    init_delay(value)?;
+    Ok(value.to_string())
+}
+
+fn parse_route(value: &str) -> Result<String, MetadataError> {
+    RouteSpec::from_str(value)?;
+    Ok(value.to_string())
+}
+
+fn parse_runtime_api_host(value: &str) -> Result<String, MetadataError> {
+    RuntimeApiHost::from_str(value)?;
+    Ok(value.to_string())
+}
+
+fn parse_memory_threshold(value: &str) -> Result<String, MetadataError> {
+    MemoryThreshold::from_str(value)?;
+    Ok(value.to_string())
+}
+
+fn parse_resource_limit(value: &str) -> Result<String, MetadataError> {
+    ResourceLimit::from_str(value)?;
+    Ok(value.to_string())
+}
+
 fn default_invoke_address() -> String {
     DEFAULT_INVOKE_ADDRESS.to_string()
 }
 
-fn default_invoke_port() -> u16 {
-    DEFAULT_INVOKE_PORT
+/// Watch settings of a binary, from the `watch` section in the metadata of the binary.
+/// They only apply to that function when `cargo lambda watch` runs several functions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WatchConfig {
+    /// Features to enable when the function runs
+    pub features: Vec<String>,
+    /// Globs of files that don't reload the function when they change
+    pub ignore: Vec<String>,
+    /// Path prefix of the HTTP requests sent to the function
+    pub route_prefix: Option<String>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct WatchConfig {
-    pub router: Option<FunctionRouter>,
+impl From<Watch> for WatchConfig {
+    fn from(watch: Watch) -> Self {
+        WatchConfig {
+            features: watch.cargo_opts.features.clone(),
+            ignore: watch.ignore,
+            route_prefix: watch.route_prefix,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct FunctionRouter {
     inner: Router<FunctionRoutes>,
     pub(crate) raw: Vec<(String, FunctionRoutes)>,
+    prefixes: Vec<(String, String)>,
 }
 
 impl FunctionRouter {
+    /// Find the function for a request. Routes are checked before
+    /// the route prefixes, and longer prefixes are checked first.
     pub fn at(
         &self,
         path: &str,
         method: &str,
     ) -> Result<(String, HashMap<String, String>), MatchError> {
-        let matched = self.inner.at(path)?;
-        let function = matched.value.at(method).ok_or(MatchError::NotFound)?;
+        if let Ok(matched) = self.inner.at(path) {
+            if let Some(function) = matched.value.at(method) {
+                let params = matched
+                    .params
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect();
+
+                return Ok((function.to_string(), params));
+            }
+        }
 
-        let params = matched
-            .params
+        self.prefixes
             .iter()
-            .map(|(k, v)| (k.to_string(), v.to_string()))
-            .collect();
-
-        Ok((function.to_string(), params))
+            .find(|(prefix, _)| {
+                path.strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .map(|(_, function)| (function.clone(), HashMap::new()))
+            .ok_or(MatchError::NotFound)
     }
 
     pub fn insert(&mut self, path: &str, routes: FunctionRoutes) -> Result<(), InsertError> {
         self.inner.insert(path, routes)
     }
+
+    /// Send the requests for the method and path to the function. Routes without a method
+    /// receive the requests for all methods, and replace any existing route for the path.
+    /// Routes with a method take precedence over the existing route for all methods.
+    pub fn insert_route(
+        &mut self,
+        method: Option<&str>,
+        path: &str,
+        function: &str,
+    ) -> Result<(), MetadataError> {
+        let existing = self.raw.iter_mut().find(|(p, _)| p == path);
+        match (existing, method) {
+            (Some((_, routes)), None) => *routes = FunctionRoutes::Single(function.to_string()),
+            (Some((_, routes)), Some(method)) => {
+                if let FunctionRoutes::Single(existing) = routes {
+                    let any = HashMap::from([(ANY_METHOD.to_string(), existing.clone())]);
+                    *routes = FunctionRoutes::Multiple(any);
+                }
+                if let FunctionRoutes::Multiple(routes) = routes {
+                    routes.insert(method.to_string(), function.to_string());
+                }
+            }
+            (None, method) => {
+                let routes = match method {
+                    Some(method) => FunctionRoutes::Multiple(HashMap::from([(
+                        method.to_string(),
+                        function.to_string(),
+                    )])),
+                    None => FunctionRoutes::Single(function.to_string()),
+                };
+                self.raw.push((path.to_string(), routes));
+            }
+        }
+
+        let mut inner = Router::new();
+        for (path, routes) in &self.raw {
+            inner
+                .insert(path, routes.clone())
+                .map_err(|e| MetadataError::InvalidRouterPath(path.clone(), e))?;
+        }
+        self.inner = inner;
+        Ok(())
+    }
+
+    /// Send the requests with paths under the prefix to the function.
+    pub fn insert_prefix(&mut self, prefix: &str, function: &str) -> Result<(), MetadataError> {
+        let prefix = prefix.trim_end_matches('/');
+        let prefix = if prefix.is_empty() || prefix.starts_with('/') {
+            prefix.to_string()
+        } else {
+            format!("/{prefix}")
+        };
+
+        if let Some((_, existing)) = self.prefixes.iter().find(|(p, _)| *p == prefix) {
+            let display = if prefix.is_empty() { "/" } else { &prefix };
+            return Err(MetadataError::DuplicatedRoutePrefix(
+                display.to_string(),
+                existing.clone(),
+                function.to_string(),
+            ));
+        }
+
+        self.prefixes.push((prefix, function.to_string()));
+        self.prefixes
+            .sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        Ok(())
+    }
 }
 
+/// Route declared with the `--route` flag, like `GET /users/{id}=get-user`.
+#[derive(Clone, Debug, PartialEq)]
+struct RouteSpec {
+    method: Option<String>,
+    path: String,
+    function: String,
+}
+
+impl FromStr for RouteSpec {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MetadataError::InvalidRoute(s.to_string());
+
+        let (target, function) = s.rsplit_once('=').ok_or_else(invalid)?;
+        let function = function.trim();
+        if function.is_empty() {
+            return Err(invalid());
+        }
+
+        let target = target.trim();
+        let (method, path) = match target.split_once(char::is_whitespace) {
+            Some((method, path)) => (Some(method), path.trim()),
+            None => (None, target),
+        };
+        if !path.starts_with('/') {
+            return Err(invalid());
+        }
+
+        let method = match method {
+            Some(m) if m.chars().all(|c| c.is_ascii_alphabetic()) => {
+                Some(m.to_ascii_uppercase()).filter(|m| m != ANY_METHOD)
+            }
+            Some(_) => return Err(invalid()),
+            None => None,
+        };
+
+        Ok(RouteSpec {
+            method,
+            path: router_path(path),
+            function: function.to_string(),
+        })
+    }
+}
+
+/// Convert a route path to the syntax of the router. `/users/*`,
+/// and API Gateway's `/users/{proxy+}`, match any subpath.
+pub fn router_path(path: &str) -> String {
+    match path.rsplit_once('/') {
+        Some((base, "*")) => format!("{base}/{{*proxy}}"),
+        Some((base, last)) if last.starts_with('{') && last.ends_with("+}") => {
+            format!("{base}/{{*{}}}", &last[1..last.len() - 2])
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Method of the routes that match any method.
+const ANY_METHOD: &str = "ANY";
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum FunctionRoutes {
     Single(String),
@@ -261,10 +1193,14 @@ pub enum FunctionRoutes {
 }
 
 impl FunctionRoutes {
+    /// Function for the method. The method `ANY` matches the methods without their own route.
     pub fn at(&self, method: &str) -> Option<&str> {
         match self {
             FunctionRoutes::Single(function) => Some(function),
-            FunctionRoutes::Multiple(routes) => routes.get(method).map(|s| s.as_str()),
+            FunctionRoutes::Multiple(routes) => routes
+                .get(method)
+                .or_else(|| routes.get(ANY_METHOD))
+                .map(|s| s.as_str()),
         }
     }
 }
@@ -293,7 +1229,11 @@ impl<'de> Visitor<'de> for FunctionRouterVisitor {
         }
 
         let raw: Vec<(String, FunctionRoutes)> = routes.into_iter().collect();
-        Ok(FunctionRouter { inner, raw })
+        Ok(FunctionRouter {
+            inner,
+            raw,
+            ..Default::default()
+        })
     }
 
     fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
@@ -310,7 +1250,11 @@ impl<'de> Visitor<'de> for FunctionRouterVisitor {
             })?;
         }
 
-        Ok(FunctionRouter { inner, raw })
+        Ok(FunctionRouter {
+            inner,
+            raw,
+            ..Default::default()
+        })
     }
 }
 
@@ -491,6 +1435,124 @@ mod tests {
         assert_eq!(params, HashMap::from([("id".to_string(), "1".to_string())]));
     }
 
+    #[test]
+    fn test_parse_route() {
+        let route = RouteSpec::from_str("get /users/*=get-users").unwrap();
+        assert_eq!(route.method, Some("GET".to_string()));
+        assert_eq!(route.path, "/users/{*proxy}");
+        assert_eq!(route.function, "get-users");
+
+        let route = RouteSpec::from_str("/users/{id}=get-user").unwrap();
+        assert_eq!(route.method, None);
+        assert_eq!(route.path, "/users/{id}");
+
+        let route = RouteSpec::from_str("ANY /files/{path+} = files").unwrap();
+        assert_eq!(route.method, None);
+        assert_eq!(route.path, "/files/{*path}");
+        assert_eq!(route.function, "files");
+
+        for invalid in [
+            "GET /users",
+            "GET users=get-users",
+            "/users=",
+            "G3T /users=users",
+        ] {
+            assert!(
+                matches!(
+                    RouteSpec::from_str(invalid),
+                    Err(MetadataError::InvalidRoute(_))
+                ),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_insert_routes_from_flags() {
+        let mut watch: Watch = toml::from_str(
+            r#"
+            [router]
+            "/users" = [{ function = "list-users", method = "GET" }]
+            "/orders" = "orders"
+        "#,
+        )
+        .unwrap();
+        watch.route = vec![
+            "POST /users=create-user".to_string(),
+            "GET /users/*=get-users".to_string(),
+            "/orders=new-orders".to_string(),
+        ];
+
+        let mut router = watch.router.clone().unwrap();
+        watch.insert_routes(&mut router).unwrap();
+        let function = |path: &str, method: &str| router.at(path, method).map(|(f, _)| f);
+        assert_eq!(function("/users", "GET"), Ok("list-users".to_string()));
+        assert_eq!(function("/users", "POST"), Ok("create-user".to_string()));
+        assert_eq!(
+            function("/users/1/orders", "GET"),
+            Ok("get-users".to_string())
+        );
+        assert_eq!(function("/orders", "DELETE"), Ok("new-orders".to_string()));
+
+        watch.route = vec!["GET /orders=get-orders".to_string()];
+        let mut router = watch.router.clone().unwrap();
+        watch.insert_routes(&mut router).unwrap();
+        let function = |path: &str, method: &str| router.at(path, method).map(|(f, _)| f);
+        assert_eq!(function("/orders", "GET"), Ok("get-orders".to_string()));
+        assert_eq!(function("/orders", "POST"), Ok("orders".to_string()));
+    }
+
+    #[test]
+    fn test_router_prefixes() {
+        let mut router: FunctionRouter = toml::from_str(
+            r#"
+            "/users/admin" = [{ function = "admin", method = "GET" }]
+        "#,
+        )
+        .unwrap();
+        router.insert_prefix("/users/", "users").unwrap();
+        router.insert_prefix("users/orders", "orders").unwrap();
+
+        let function = |router: &FunctionRouter, path: &str, method: &str| {
+            router.at(path, method).map(|(f, _)| f)
+        };
+        assert_eq!(
+            function(&router, "/users/admin", "GET"),
+            Ok("admin".to_string())
+        );
+        assert_eq!(
+            function(&router, "/users/admin", "POST"),
+            Ok("users".to_string())
+        );
+        assert_eq!(function(&router, "/users", "GET"), Ok("users".to_string()));
+        assert_eq!(
+            function(&router, "/users/1", "GET"),
+            Ok("users".to_string())
+        );
+        assert_eq!(
+            function(&router, "/users/orders/1", "GET"),
+            Ok("orders".to_string())
+        );
+        assert_eq!(
+            function(&router, "/usersettings", "GET"),
+            Err(MatchError::NotFound)
+        );
+
+        let err = router.insert_prefix("/users", "accounts").unwrap_err();
+        assert!(matches!(
+            err,
+            MetadataError::DuplicatedRoutePrefix(ref prefix, ref a, ref b)
+                if prefix == "/users" && a == "users" && b == "accounts"
+        ));
+
+        router.insert_prefix("/", "fallback").unwrap();
+        assert_eq!(
+            function(&router, "/other", "GET"),
+            Ok("fallback".to_string())
+        );
+        assert_eq!(function(&router, "/", "GET"), Ok("fallback".to_string()));
+    }
+
     #[test]
     fn test_router_serialize() {
         let config = r#"
@@ -525,10 +1587,23 @@ mod tests {
     fn test_watch_serialization() {
         let watch = Watch {
             invoke_address: "127.0.0.1".to_string(),
-            invoke_port: 9000,
+            invoke_port: Some(0),
+            strict_port: true,
+            rie_compatible: true,
+            reserved_concurrency: Some(2),
+            port_file: Some(PathBuf::from("/tmp/lambda.port")),
+            listen_fd: Some(3),
+            runtime_api_host: Some("host.docker.internal".to_string()),
+            request_context: Some(PathBuf::from("/tmp/context.json")),
+            pre_invoke: Some("./scripts/seed.sh".to_string()),
+            jwks: Some(PathBuf::from("/tmp/jwks.json")),
+            exclude: vec!["basic-lambda".to_string()],
+            ignore: vec!["*.md".to_string()],
+            route_prefix: Some("/users".to_string()),
             env_options: EnvOptions {
                 env_file: Some(PathBuf::from("/tmp/env")),
                 env_var: Some(vec!["FOO=BAR".to_string()]),
+                interpolate_env: true,
             },
             tls_options: TlsOptions::new(
                 Some(PathBuf::from("/tmp/cert.pem")),
@@ -570,9 +1645,23 @@ mod tests {
 
         let json = serde_json::to_value(&watch).unwrap();
         assert_eq!(json["invoke_address"], "127.0.0.1");
-        assert_eq!(json["invoke_port"], 9000);
+        assert_eq!(json["invoke_port"], 0);
+        assert_eq!(json["strict_port"], true);
+        assert_eq!(json["rie_compatible"], true);
+        assert_eq!(json["reserved_concurrency"], 2);
+        assert_eq!(json["port_file"], "/tmp/lambda.port");
+        // File descriptors only make sense in the command line.
+        assert!(json.get("listen_fd").is_none());
+        assert_eq!(json["runtime_api_host"], "host.docker.internal");
+        assert_eq!(json["request_context"], "/tmp/context.json");
+        assert_eq!(json["pre_invoke"], "./scripts/seed.sh");
+        assert_eq!(json["jwks"], "/tmp/jwks.json");
+        assert_eq!(json["exclude"], json!(["basic-lambda"]));
+        assert_eq!(json["ignore"], json!(["*.md"]));
+        assert_eq!(json["route_prefix"], "/users");
         assert_eq!(json["env_file"], "/tmp/env");
         assert_eq!(json["env_var"], json!(["FOO=BAR"]));
+        assert_eq!(json["interpolate_env"], true);
         assert_eq!(json["tls_cert"], "/tmp/cert.pem");
         assert_eq!(json["tls_key"], "/tmp/key.pem");
         assert_eq!(json["tls_ca"], "/tmp/ca.pem");
@@ -594,11 +1683,27 @@ mod tests {
 
         assert_eq!(deserialized.invoke_address, watch.invoke_address);
         assert_eq!(deserialized.invoke_port, watch.invoke_port);
+        assert_eq!(deserialized.strict_port, watch.strict_port);
+        assert_eq!(deserialized.rie_compatible, watch.rie_compatible);
+        assert_eq!(
+            deserialized.reserved_concurrency,
+            watch.reserved_concurrency
+        );
+        assert_eq!(deserialized.port_file, watch.port_file);
+        assert_eq!(None, deserialized.listen_fd);
+        assert_eq!(deserialized.runtime_api_host, watch.runtime_api_host);
+        assert_eq!(deserialized.request_context, watch.request_context);
+        assert_eq!(deserialized.pre_invoke, watch.pre_invoke);
+        assert_eq!(deserialized.jwks, watch.jwks);
+        assert_eq!(deserialized.exclude, watch.exclude);
+        assert_eq!(deserialized.ignore, watch.ignore);
+        assert_eq!(deserialized.route_prefix, watch.route_prefix);
         assert_eq!(
             deserialized.env_options.env_file,
             watch.env_options.env_file
         );
         assert_eq!(deserialized.env_options.env_var, watch.env_options.env_var);
+        assert!(deserialized.env_options.interpolate_env);
         assert_eq!(
             deserialized.tls_options.tls_cert,
             watch.tls_options.tls_cert
@@ -658,4 +1763,288 @@ mod tests {
             watch.cargo_opts.common.timings
         );
     }
+
+    #[test]
+    fn test_alb_options_serialization() {
+        let watch = Watch {
+            alb_options: AlbOptions {
+                alb: true,
+                alb_multi_value_headers: true,
+                alb_health_check_paths: Some(vec!["/health".to_string()]),
+            },
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&watch).unwrap();
+        assert_eq!(json["alb"], true);
+        assert_eq!(json["alb_multi_value_headers"], true);
+        assert_eq!(json["alb_health_check_paths"], json!(["/health"]));
+
+        let deserialized: Watch = serde_json::from_value(json).unwrap();
+        assert!(deserialized.alb_options.alb);
+        assert!(deserialized.alb_options.alb_multi_value_headers);
+        assert!(deserialized.alb_options.is_health_check("/health"));
+        assert!(!deserialized.alb_options.is_health_check("/healthz"));
+
+        let disabled = AlbOptions {
+            alb: false,
+            ..deserialized.alb_options
+        };
+        assert!(!disabled.is_health_check("/health"));
+    }
+
+    #[test]
+    fn test_fault_options() {
+        let watch = Watch {
+            fault_options: FaultOptions {
+                fail_init: true,
+                delay_init: Some("500ms".into()),
+                oom_at: Some("80%".into()),
+            },
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&watch).unwrap();
+        assert_eq!(json["fail_init"], true);
+        assert_eq!(json["delay_init"], "500ms");
+        assert_eq!(json["oom_at"], "80%");
+
+        let deserialized: Watch = serde_json::from_value(json).unwrap();
+        let faults = deserialized.fault_options;
+        assert!(faults.fail_init);
+        assert_eq!(
+            Some(Duration::from_millis(500)),
+            faults.init_delay().unwrap()
+        );
+        assert_eq!(
+            Some(MemoryThreshold::Percentage(80)),
+            faults.memory_threshold().unwrap()
+        );
+
+        let faults = FaultOptions::default();
+        assert_eq!(None, faults.init_delay().unwrap());
+        assert_eq!(None, faults.memory_threshold().unwrap());
+    }
+
+    #[test]
+    fn test_sandbox_options() {
+        let watch = Watch {
+            sandbox_options: SandboxOptions {
+                sandbox: true,
+                memory: Some(Memory(512)),
+                ulimit: vec!["nofile=1024".into(), "nproc=64".into()],
+            },
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&watch).unwrap();
+        assert_eq!(json["sandbox"], true);
+        assert_eq!(json["memory"], 512);
+        assert_eq!(json["ulimit"], json!(["nofile=1024", "nproc=64"]));
+
+        let deserialized: Watch = serde_json::from_value(json).unwrap();
+        let sandbox = deserialized.sandbox_options;
+        assert!(sandbox.sandbox);
+        assert_eq!(Some(Memory(512)), sandbox.memory);
+        assert_eq!(
+            vec![ResourceLimit::OpenFiles(1024), ResourceLimit::Processes(64)],
+            sandbox.resource_limits().unwrap()
+        );
+
+        for invalid in ["nofile", "nofile=0", "nofile=lots", "cpu=10"] {
+            assert!(
+                matches!(
+                    ResourceLimit::from_str(invalid),
+                    Err(MetadataError::InvalidResourceLimit(_))
+                ),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_runtime_api_host() {
+        let host = |value: &str| RuntimeApiHost::from_str(value).map(|h| h.address(9001));
+
+        assert_eq!(
+            "host.docker.internal:9001",
+            host("host.docker.internal").unwrap()
+        );
+        assert_eq!("lambda-dev:9010", host("lambda-dev:9010").unwrap());
+        assert_eq!("172.17.0.1:9001", host("172.17.0.1").unwrap());
+        assert_eq!("172.17.0.1:9010", host("172.17.0.1:9010").unwrap());
+        assert_eq!("[fd00::1]:9001", host("fd00::1").unwrap());
+        assert_eq!("[fd00::1]:9010", host("[fd00::1]:9010").unwrap());
+
+        for invalid in ["", "http://host", "host:port", "host name", "host:99999"] {
+            assert!(
+                matches!(
+                    RuntimeApiHost::from_str(invalid),
+                    Err(MetadataError::InvalidRuntimeApiHost(_))
+                ),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Duration::from_secs(5), parse_duration("5s").unwrap());
+        assert_eq!(Duration::from_secs(5), parse_duration("5").unwrap());
+        assert_eq!(Duration::from_millis(250), parse_duration("250ms").unwrap());
+        assert_eq!(Duration::from_secs(120), parse_duration("2m").unwrap());
+
+        for invalid in ["", "s", "5h", "-1s", "1.5s", "999999999999999999m"] {
+            assert!(parse_duration(invalid).is_none(), "{invalid} should fail");
+        }
+
+        assert_eq!(
+            Duration::from_secs(7200),
+            parse_long_duration("2h").unwrap()
+        );
+        assert_eq!(Duration::from_secs(120), parse_long_duration("2m").unwrap());
+        for invalid in ["5d", "99999999999999999h"] {
+            assert!(
+                parse_long_duration(invalid).is_none(),
+                "{invalid} should fail"
+            );
+        }
+    }
+
+    #[test]
+    fn test_memory_threshold() {
+        let threshold = MemoryThreshold::from_str("80%").unwrap();
+        assert_eq!(MemoryThreshold::Percentage(80), threshold);
+        assert_eq!(3276 * 1024 * 1024, threshold.bytes(4096));
+
+        let threshold = MemoryThreshold::from_str("512MB").unwrap();
+        assert_eq!(MemoryThreshold::Megabytes(512), threshold);
+        assert_eq!(512 * 1024 * 1024, threshold.bytes(4096));
+
+        for invalid in ["80", "0%", "101%", "0MB", "lots"] {
+            assert!(
+                MemoryThreshold::from_str(invalid).is_err(),
+                "{invalid} should fail"
+            );
+        }
+    }
+
+    #[test]
+    fn test_log_dir() {
+        let target_dir = Path::new("/tmp/project/target");
+
+        let watch = Watch::default();
+        assert_eq!(None, watch.log_dir(target_dir));
+
+        let watch = Watch {
+            log_dir: Some(PathBuf::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(PathBuf::from("/tmp/project/target/lambda/logs")),
+            watch.log_dir(target_dir)
+        );
+
+        let watch = Watch {
+            log_dir: Some(PathBuf::from("logs")),
+            ..Default::default()
+        };
+        assert_eq!(Some(PathBuf::from("logs")), watch.log_dir(target_dir));
+    }
+
+    #[test]
+    fn test_bin_path_function_name() {
+        assert_eq!(None, Watch::default().bin_path_function_name());
+
+        let watch: Watch =
+            serde_json::from_value(json!({"bin_path": "target/lambda/orders/bootstrap"})).unwrap();
+        assert_eq!(Some("orders".into()), watch.bin_path_function_name());
+        assert_eq!(
+            json!("target/lambda/orders/bootstrap"),
+            serde_json::to_value(&watch).unwrap()["bin_path"]
+        );
+
+        let watch = Watch {
+            bin_path: Some(PathBuf::from("bazel-bin/orders/orders-api")),
+            ..Default::default()
+        };
+        assert_eq!(Some("orders-api".into()), watch.bin_path_function_name());
+
+        let watch = Watch {
+            bin_path: Some(PathBuf::from("bootstrap")),
+            ..Default::default()
+        };
+        assert_eq!(Some("bootstrap".into()), watch.bin_path_function_name());
+    }
+
+    #[test]
+    fn test_invoke_port() {
+        assert_eq!(DEFAULT_INVOKE_PORT, Watch::default().invoke_port());
+
+        let watch: Watch = serde_json::from_value(json!({"invoke_port": 0})).unwrap();
+        assert_eq!(0, watch.invoke_port());
+    }
+
+    #[test]
+    fn test_invoke_queue_size() {
+        assert_eq!(
+            DEFAULT_INVOKE_QUEUE_SIZE,
+            Watch::default().invoke_queue_size()
+        );
+
+        let watch: Watch = serde_json::from_value(json!({"invoke_queue_size": 5})).unwrap();
+        assert_eq!(5, watch.invoke_queue_size());
+
+        let watch: Watch = serde_json::from_value(json!({"invoke_queue_size": 0})).unwrap();
+        assert_eq!(1, watch.invoke_queue_size());
+    }
+
+    #[test]
+    fn test_lazy_alias() {
+        let watch: Watch = serde_json::from_value(json!({"lazy": true})).unwrap();
+        assert!(watch.wait);
+    }
+
+    #[test]
+    fn test_event_format() {
+        assert_eq!(EventFormat::FunctionUrl, Watch::default().event_format());
+
+        let watch: Watch = serde_json::from_value(json!({"event_format": "apigw-rest"})).unwrap();
+        assert_eq!(EventFormat::ApigwRest, watch.event_format());
+
+        let json = serde_json::to_value(&watch).unwrap();
+        assert_eq!(json["event_format"], "apigw-rest");
+
+        let watch = Watch {
+            alb_options: AlbOptions {
+                alb: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(EventFormat::Alb, watch.event_format());
+        assert_eq!(Ok(EventFormat::ApigwHttp), "apigw-http".parse());
+    }
+
+    #[test]
+    fn test_use_log_prefix() {
+        let watch = Watch::default();
+        assert!(!watch.use_log_prefix(1));
+        assert!(watch.use_log_prefix(2));
+
+        let watch = Watch {
+            log_prefix: Some(LogPrefix::Always),
+            ..Default::default()
+        };
+        assert!(watch.use_log_prefix(1));
+
+        let watch = Watch {
+            log_prefix: Some("never".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(!watch.use_log_prefix(2));
+
+        let json = serde_json::to_value(&watch).unwrap();
+        assert_eq!(json["log_prefix"], "never");
+    }
 }
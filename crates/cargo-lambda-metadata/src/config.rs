@@ -2,9 +2,13 @@ use std::{collections::HashMap, path::PathBuf};
 
 use crate::{
     cargo::{
-        CargoMetadata, Metadata, PackageMetadata, binary_targets_from_metadata, build::Build,
-        deploy::Deploy, watch::Watch,
+        CargoMetadata, Metadata, PackageMetadata, binary_targets_from_metadata,
+        build::Build,
+        deploy::Deploy,
+        validation::unknown_metadata_keys,
+        watch::{Watch, WatchConfig},
     },
+    env::lambda_environment,
     error::MetadataError,
 };
 use cargo_metadata::{Package, Target};
@@ -14,7 +18,7 @@ use figment::{
 };
 use miette::{IntoDiagnostic, Result};
 use serde::{Deserialize, Serialize};
-use tracing::trace;
+use tracing::{trace, warn};
 
 #[derive(Debug, Default)]
 pub struct ConfigOptions {
@@ -49,6 +53,10 @@ pub fn load_config(
     metadata: &CargoMetadata,
     options: &ConfigOptions,
 ) -> Result<Config> {
+    for key in unknown_metadata_keys(metadata) {
+        warn!("{key}");
+    }
+
     let mut figment = figment_from_metadata(metadata, options)?;
 
     let mut args_serialized = Serialized::defaults(args_config);
@@ -62,7 +70,12 @@ pub fn load_config(
         figment.merge(args_serialized)
     };
 
-    figment.extract().into_diagnostic()
+    let mut config: Config = figment.extract().into_diagnostic()?;
+    if args_config.build.has_architecture() {
+        config.build.override_architecture(&args_config.build);
+    }
+
+    Ok(config)
 }
 
 pub fn load_config_without_cli_flags(
@@ -74,9 +87,6 @@ pub fn load_config_without_cli_flags(
 }
 
 fn figment_from_metadata(metadata: &CargoMetadata, options: &ConfigOptions) -> Result<Figment> {
-    let (ws_metadata, bin_metadata) = workspace_metadata(metadata, options.name.as_deref())?;
-    let package_metadata = package_metadata(metadata, options.name.as_deref())?;
-
     let mut config_file = options
         .global
         .as_ref()
@@ -103,6 +113,67 @@ fn figment_from_metadata(metadata: &CargoMetadata, options: &ConfigOptions) -> R
         figment.merge(config_file)
     };
 
+    merge_package_metadata(figment, metadata, options)
+}
+
+/// Load the environment variables declared in the Cargo metadata,
+/// ignoring the global config file, the `CARGO_LAMBDA_*` variables,
+/// env files, and the command line flags.
+pub fn load_metadata_environment(
+    metadata: &CargoMetadata,
+    options: &ConfigOptions,
+) -> Result<HashMap<String, String>> {
+    let mut figment = Figment::new();
+    if let Some(context) = &options.context {
+        figment = figment.select(context)
+    }
+
+    let config: Config = merge_package_metadata(figment, metadata, options)?
+        .extract()
+        .into_diagnostic()?;
+
+    let env_var = config
+        .deploy
+        .function_config
+        .env_options
+        .and_then(|opts| opts.env_var);
+
+    lambda_environment(Some(&config.env), &None, env_var.as_ref()).map_err(Into::into)
+}
+
+/// Load the watch settings declared for each binary in the workspace metadata,
+/// and in the metadata of each package. The package metadata takes precedence.
+pub fn load_binary_watch_configs(metadata: &CargoMetadata) -> Result<HashMap<String, WatchConfig>> {
+    let mut sources = Vec::with_capacity(metadata.packages.len() + 1);
+    sources.push(&metadata.workspace_metadata);
+    sources.extend(metadata.packages.iter().map(|pkg| &pkg.metadata));
+
+    let mut configs = HashMap::new();
+    for source in sources {
+        if !source.is_object() {
+            continue;
+        }
+
+        let meta: Metadata = serde_json::from_value(source.clone()).into_diagnostic()?;
+        for (name, bin) in meta.lambda.bin {
+            if let Some(watch) = bin.watch {
+                configs.insert(name, watch.into());
+            }
+        }
+    }
+
+    Ok(configs)
+}
+
+/// Merge the workspace, binary, and package metadata into the figment, in that order.
+fn merge_package_metadata(
+    mut figment: Figment,
+    metadata: &CargoMetadata,
+    options: &ConfigOptions,
+) -> Result<Figment> {
+    let (ws_metadata, bin_metadata) = workspace_metadata(metadata, options.name.as_deref())?;
+    let package_metadata = package_metadata(metadata, options.name.as_deref())?;
+
     let mut ws_serialized = Serialized::defaults(ws_metadata);
     if let Some(context) = &options.context {
         ws_serialized = ws_serialized.profile(context);
@@ -417,4 +488,56 @@ mod tests {
         let config = load_config(&args_config, &metadata, &options).unwrap();
         assert_eq!(config.deploy.function_config.memory, Some(Memory(2048)));
     }
+
+    #[test]
+    fn test_build_architecture_from_metadata() {
+        let metadata =
+            load_metadata(fixture_metadata("single-binary-package-build-arm64")).unwrap();
+
+        let config = load_config(&Config::default(), &metadata, &ConfigOptions::default()).unwrap();
+        assert!(config.build.arm64);
+        assert!(!config.build.x86_64);
+
+        let args_config = Config {
+            build: Build {
+                x86_64: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let config = load_config(&args_config, &metadata, &ConfigOptions::default()).unwrap();
+        assert!(!config.build.arm64);
+        assert!(config.build.x86_64);
+
+        let mut build = Build::default();
+        build.cargo_opts.target = vec!["x86_64-unknown-linux-musl".into()];
+        let args_config = Config {
+            build,
+            ..Default::default()
+        };
+        let options = ConfigOptions {
+            admerge: true,
+            ..Default::default()
+        };
+        let config = load_config(&args_config, &metadata, &options).unwrap();
+        assert!(!config.build.arm64);
+        assert_eq!(
+            vec!["x86_64-unknown-linux-musl".to_string()],
+            config.build.cargo_opts.target
+        );
+    }
+
+    #[test]
+    fn test_load_binary_watch_configs() {
+        let metadata = load_metadata(fixture_metadata("multi-binary-package")).unwrap();
+        let configs = load_binary_watch_configs(&metadata).unwrap();
+
+        let get_product = configs.get("get-product").unwrap();
+        assert_eq!(get_product.features, vec!["lambda".to_string()]);
+        assert_eq!(get_product.ignore, vec!["fixtures/**".to_string()]);
+        assert_eq!(get_product.route_prefix, Some("/products".to_string()));
+
+        assert!(!configs.contains_key("delete-product"));
+        assert!(!configs.contains_key("get-products"));
+    }
 }
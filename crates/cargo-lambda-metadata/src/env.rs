@@ -23,6 +23,12 @@ pub struct EnvOptions {
     #[arg(long, value_hint = ValueHint::FilePath)]
     #[serde(default)]
     pub env_file: Option<PathBuf>,
+
+    /// Resolve references to other variables in the values of the environment,
+    /// like `${STAGE}`, with the other variables and the variables in your shell
+    #[arg(long)]
+    #[serde(default)]
+    pub interpolate_env: bool,
 }
 
 impl EnvOptions {
@@ -30,11 +36,18 @@ impl EnvOptions {
         &self,
         base: &HashMap<String, String>,
     ) -> Result<Environment, MetadataError> {
-        lambda_environment(Some(base), &self.env_file, self.env_var.as_ref())
+        let env = lambda_environment(Some(base), &self.env_file, self.env_var.as_ref())?;
+        if self.interpolate_env {
+            interpolate_environment(&env)
+        } else {
+            Ok(env)
+        }
     }
 
     pub fn count_fields(&self) -> usize {
-        self.env_var.is_some() as usize + self.env_file.is_some() as usize
+        self.env_var.is_some() as usize
+            + self.env_file.is_some() as usize
+            + self.interpolate_env as usize
     }
 
     pub fn serialize_fields<S>(
@@ -50,6 +63,9 @@ impl EnvOptions {
         if let Some(env_file) = &self.env_file {
             state.serialize_field("env_file", env_file)?;
         }
+        if self.interpolate_env {
+            state.serialize_field("interpolate_env", &true)?;
+        }
         Ok(())
     }
 }
@@ -85,6 +101,85 @@ pub(crate) fn lambda_environment(
     Ok(env)
 }
 
+/// Replace the references to other variables in the values of the environment,
+/// like `${STAGE}`, or `${STAGE:-dev}` with a default value. References are resolved
+/// with the other variables in the environment first, and the process environment after.
+pub fn interpolate_environment(env: &Environment) -> Result<Environment, MetadataError> {
+    let mut resolved = HashMap::new();
+    for key in env.keys() {
+        resolve_var(key, env, &mut resolved, &mut Vec::new())?;
+    }
+    Ok(resolved)
+}
+
+fn resolve_var(
+    key: &str,
+    env: &Environment,
+    resolved: &mut Environment,
+    stack: &mut Vec<String>,
+) -> Result<String, MetadataError> {
+    if let Some(value) = resolved.get(key) {
+        return Ok(value.clone());
+    }
+    if stack.iter().any(|k| k == key) {
+        stack.push(key.to_string());
+        return Err(MetadataError::CircularEnvReference(stack.join(" -> ")));
+    }
+
+    stack.push(key.to_string());
+    let value = &env[key];
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value.as_str();
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        if let Some(after) = after.strip_prefix("${") {
+            output.push_str("${");
+            rest = after;
+            continue;
+        }
+        let Some(after) = after.strip_prefix('{') else {
+            output.push('$');
+            rest = after;
+            continue;
+        };
+
+        let invalid = || MetadataError::InvalidEnvReference(key.to_string(), value.clone());
+        let end = after.find('}').ok_or_else(invalid)?;
+        let reference = &after[..end];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(invalid());
+        }
+
+        let replacement = if env.contains_key(name) {
+            Some(resolve_var(name, env, resolved, stack)?)
+        } else {
+            std::env::var(name).ok()
+        };
+        match replacement.or_else(|| default.map(String::from)) {
+            Some(replacement) => output.push_str(&replacement),
+            None => {
+                return Err(MetadataError::UnresolvedEnvReference(
+                    key.to_string(),
+                    name.to_string(),
+                ));
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    output.push_str(rest);
+    stack.pop();
+
+    resolved.insert(key.to_string(), output.clone());
+    Ok(output)
+}
+
 fn extract_var(line: &str) -> Result<(&str, &str), MetadataError> {
     let mut iter = line.trim().splitn(2, '=');
 
@@ -155,6 +250,56 @@ mod test {
         assert_eq!("QUUX".to_string(), env["BAZ"]);
     }
 
+    #[test]
+    fn test_interpolate_environment() {
+        let env = Environment::from([
+            ("STAGE".into(), "dev".into()),
+            ("BASE_URL".into(), "https://${STAGE}.example.com".into()),
+            ("API_URL".into(), "${BASE_URL}/api".into()),
+            (
+                "REGION".into(),
+                "${CARGO_LAMBDA_TEST_UNDEFINED_REGION:-us-east-1}".into(),
+            ),
+            ("PRICE".into(), "$5 or $${LITERAL}".into()),
+            ("HOME_DIR".into(), "${CARGO_PKG_NAME}".into()),
+        ]);
+
+        let env = interpolate_environment(&env).unwrap();
+        assert_eq!("dev", env["STAGE"]);
+        assert_eq!("https://dev.example.com", env["BASE_URL"]);
+        assert_eq!("https://dev.example.com/api", env["API_URL"]);
+        assert_eq!("us-east-1", env["REGION"]);
+        assert_eq!("$5 or ${LITERAL}", env["PRICE"]);
+        assert_eq!(env!("CARGO_PKG_NAME"), env["HOME_DIR"]);
+    }
+
+    #[test]
+    fn test_interpolate_environment_errors() {
+        let env = Environment::from([(
+            "BASE_URL".into(),
+            "https://${CARGO_LAMBDA_TEST_UNDEFINED_STAGE}.example.com".into(),
+        )]);
+        let err = interpolate_environment(&env).unwrap_err();
+        assert!(matches!(
+            err,
+            MetadataError::UnresolvedEnvReference(ref key, ref name)
+                if key == "BASE_URL" && name == "CARGO_LAMBDA_TEST_UNDEFINED_STAGE"
+        ));
+
+        for value in ["${STAGE", "${}", "${STAGE NAME}"] {
+            let env = Environment::from([("BASE_URL".into(), value.into())]);
+            let err = interpolate_environment(&env).unwrap_err();
+            assert!(
+                matches!(err, MetadataError::InvalidEnvReference(..)),
+                "{value}"
+            );
+        }
+
+        let env = Environment::from([("A".into(), "${B}".into()), ("B".into(), "${A}".into())]);
+        let err = interpolate_environment(&env).unwrap_err();
+        assert!(matches!(err, MetadataError::CircularEnvReference(_)));
+    }
+
     #[test]
     fn test_environment_with_file() {
         let file = temp_dir().join(".env");
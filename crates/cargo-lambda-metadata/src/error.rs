@@ -1,6 +1,6 @@
 use std::{num::ParseIntError, path::PathBuf};
 
-use matchit::MergeError;
+use matchit::{InsertError, MergeError};
 use miette::Diagnostic;
 use thiserror::Error;
 
@@ -15,6 +15,65 @@ pub enum MetadataError {
     #[error("invalid timeout value")]
     #[diagnostic()]
     InvalidTimeout(#[from] ParseIntError),
+    #[error(
+        "invalid log retention value `{0}`, valid values are: 1, 3, 5, 7, 14, 30, 60, 90, 120, 150, 180, 365, 400, 545, 731, 1096, 1827, 2192, 2557, 2922, 3288, and 3653"
+    )]
+    #[diagnostic()]
+    InvalidLogRetention(String),
+    #[error("invalid audit log location `{0}`, use an S3 URI like `s3://bucket/prefix`")]
+    #[diagnostic()]
+    InvalidAuditLog(String),
+    #[error("invalid stack output `{0}`, use the format `StackName:OutputKey=ENV_VAR`")]
+    #[diagnostic()]
+    InvalidStackOutput(String),
+    #[error("invalid canary `{0}`, use a percentage of traffic and an interval like `10%:5m`")]
+    #[diagnostic()]
+    InvalidCanary(String),
+    #[error(
+        "invalid invoke permission `{0}`, use an AWS account id, a service principal like `events.amazonaws.com`, or the ARN of a resource or an IAM principal"
+    )]
+    #[diagnostic()]
+    InvalidInvokePermission(String),
+    #[error(
+        "invalid trigger `{0}`, use a schedule like `eventbridge:rate(5 minutes)`, or `api:HTTP`"
+    )]
+    #[diagnostic()]
+    InvalidTrigger(String),
+    #[error(
+        "invalid instance `{0}`, use a function name with letters, numbers, hyphens, and underscores"
+    )]
+    #[diagnostic()]
+    InvalidInstance(String),
+    #[error("invalid custom domain `{0}`, use a domain name like `api.example.com`")]
+    #[diagnostic()]
+    InvalidCustomDomain(String),
+    #[error(
+        "invalid runtime API host `{0}`, use a host name or an IP address, with an optional port, like `host.docker.internal` or `172.17.0.1:9001`"
+    )]
+    #[diagnostic()]
+    InvalidRuntimeApiHost(String),
+    #[error("invalid init delay `{0}`, use a duration like `5s`, or `500ms`")]
+    #[diagnostic()]
+    InvalidInitDelay(String),
+    #[error(
+        "invalid memory threshold `{0}`, use a percentage like `80%`, or megabytes like `512MB`"
+    )]
+    #[diagnostic()]
+    InvalidMemoryThreshold(String),
+    #[error("invalid resource limit `{0}`, use a limit like `nofile=1024`, or `nproc=1024`")]
+    #[diagnostic()]
+    InvalidResourceLimit(String),
+    #[error("invalid package size `{0}`, use a size like `50MB`, `512KB`, or `1GB`")]
+    #[diagnostic()]
+    InvalidPackageSize(String),
+    #[error(
+        "invalid runtime `{0}`, valid values are: {}",
+        crate::runtime::provided_runtime_names().join(", ")
+    )]
+    #[diagnostic(help(
+        "functions for other runtimes can be deployed from a zip file with `--binary-path` and `--handler`"
+    ))]
+    InvalidRuntime(String),
     #[error("invalid tracing option `{0}`")]
     #[diagnostic()]
     InvalidTracing(String),
@@ -26,12 +85,50 @@ pub enum MetadataError {
     #[error("there are no binaries in this project")]
     #[diagnostic()]
     MissingBinaryInProject,
+    #[error("package `{0}` is not a member of this project")]
+    #[diagnostic()]
+    MissingPackage(String),
+    #[error("binary target is missing from the selected packages: {0}")]
+    #[diagnostic()]
+    MissingSelectedBinary(String),
     #[error("invalid environment variable `{0}`")]
     #[diagnostic()]
     InvalidEnvVar(String),
+    #[error("the environment variable `{0}` references `${{{1}}}`, which is not defined")]
+    #[diagnostic(help(
+        "define `{1}` in the metadata, an env file, with `--env-var`, or in your shell, or give it a default value with `${{{1}:-default}}`"
+    ))]
+    UnresolvedEnvReference(String, String),
+    #[error("the environment variable `{0}` has an invalid reference in its value: `{1}`")]
+    #[diagnostic(help(
+        "references use the format `${{NAME}}`, or `${{NAME:-default}}`, use `$${{` to write `${{` literally"
+    ))]
+    InvalidEnvReference(String, String),
+    #[error("the environment variables reference each other in a loop: {0}")]
+    #[diagnostic()]
+    CircularEnvReference(String),
     #[error("invalid environment file `{0}`: {1}")]
     #[diagnostic()]
     InvalidEnvFile(PathBuf, std::io::Error),
+    #[error("the route prefix `{0}` is used by the functions `{1}` and `{2}`")]
+    #[diagnostic(help(
+        "set a different `route_prefix` in the watch metadata of each binary, or use the router to send requests to both functions"
+    ))]
+    DuplicatedRoutePrefix(String, String, String),
+    #[error("invalid route `{0}`")]
+    #[diagnostic(help(
+        "use the format `METHOD /path=function`, like `GET /users/{{id}}=get-user`, the method is optional"
+    ))]
+    InvalidRoute(String),
+    #[error("invalid router path `{0}`: {1}")]
+    #[diagnostic()]
+    InvalidRouterPath(String, InsertError),
+    #[error("invalid request context file `{0}`: {1}")]
+    #[diagnostic()]
+    InvalidRequestContextFile(PathBuf, std::io::Error),
+    #[error("invalid request context in `{0}`: {1}")]
+    #[diagnostic(help("the request context must be a JSON object"))]
+    InvalidRequestContext(PathBuf, String),
     #[error(transparent)]
     #[diagnostic()]
     FailedCmdExecution(#[from] cargo_metadata::Error),
@@ -43,5 +140,8 @@ pub enum MetadataError {
     InvalidTomlManifest(toml::de::Error),
     #[error(transparent)]
     #[diagnostic()]
+    InvalidTomlDocument(#[from] toml_edit::TomlError),
+    #[error(transparent)]
+    #[diagnostic()]
     MergeError(#[from] MergeError),
 }
@@ -2,7 +2,8 @@ use serde::{
     Deserialize, Serialize, Serializer,
     de::{Deserializer, Error, Visitor},
 };
-use std::{fmt, str::FromStr, time::Duration};
+use serde_json::{Map, Value};
+use std::{fmt, path::Path, str::FromStr, time::Duration};
 use strum_macros::{Display, EnumString};
 
 use crate::error::MetadataError;
@@ -187,3 +188,127 @@ impl<'de> Deserialize<'de> for Tracing {
         deserializer.deserialize_string(TracingVisitor)
     }
 }
+
+/// Fields to merge into the `requestContext` of the events sent to functions,
+/// like authorizer claims, or the caller's source IP.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestContext(Map<String, Value>);
+
+impl RequestContext {
+    /// Load the request context from a JSON file.
+    pub fn load(path: &Path) -> Result<RequestContext, MetadataError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| MetadataError::InvalidRequestContextFile(path.to_path_buf(), e))?;
+
+        match serde_json::from_str(&content) {
+            Ok(Value::Object(fields)) => Ok(RequestContext(fields)),
+            Ok(_) => Err(MetadataError::InvalidRequestContext(
+                path.to_path_buf(),
+                "the file doesn't include a JSON object".into(),
+            )),
+            Err(e) => Err(MetadataError::InvalidRequestContext(
+                path.to_path_buf(),
+                e.to_string(),
+            )),
+        }
+    }
+
+    /// Merge the fields into the `requestContext` of an event.
+    /// Nested objects are merged recursively, any other value replaces
+    /// the value in the event. It returns false if the event is not an object.
+    pub fn merge_into(&self, event: &mut Value) -> bool {
+        let Some(event) = event.as_object_mut() else {
+            return false;
+        };
+
+        let context = event
+            .entry("requestContext")
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !context.is_object() {
+            *context = Value::Object(Map::new());
+        }
+
+        merge_objects(context, &self.0);
+        true
+    }
+}
+
+fn merge_objects(target: &mut Value, fields: &Map<String, Value>) {
+    let Some(target) = target.as_object_mut() else {
+        return;
+    };
+
+    for (key, value) in fields {
+        match (target.get_mut(key), value) {
+            (Some(existing @ Value::Object(_)), Value::Object(nested)) => {
+                merge_objects(existing, nested)
+            }
+            _ => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_request_context() {
+        let context = RequestContext(
+            json!({
+                "authorizer": {"jwt": {"claims": {"sub": "user-1"}}},
+                "http": {"sourceIp": "10.0.0.1"}
+            })
+            .as_object()
+            .cloned()
+            .unwrap(),
+        );
+
+        let mut event = json!({
+            "rawPath": "/",
+            "requestContext": {
+                "requestId": "req-id",
+                "http": {"method": "GET", "sourceIp": "127.0.0.1"}
+            }
+        });
+        assert!(context.merge_into(&mut event));
+        assert_eq!(
+            json!({
+                "rawPath": "/",
+                "requestContext": {
+                    "requestId": "req-id",
+                    "http": {"method": "GET", "sourceIp": "10.0.0.1"},
+                    "authorizer": {"jwt": {"claims": {"sub": "user-1"}}}
+                }
+            }),
+            event
+        );
+
+        let mut event = json!({"Records": []});
+        assert!(context.merge_into(&mut event));
+        assert_eq!(event["requestContext"]["http"]["sourceIp"], "10.0.0.1");
+
+        let mut event = json!("hello");
+        assert!(!context.merge_into(&mut event));
+    }
+
+    #[test]
+    fn test_load_request_context() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let path = dir.path().join("context.json");
+        std::fs::write(&path, r#"{"identity": {"sourceIp": "10.0.0.1"}}"#).unwrap();
+        let context = RequestContext::load(&path).unwrap();
+        assert_eq!(context.0["identity"]["sourceIp"], "10.0.0.1");
+
+        std::fs::write(&path, "[1, 2]").unwrap();
+        let err = RequestContext::load(&path).unwrap_err();
+        assert!(matches!(err, MetadataError::InvalidRequestContext(..)));
+
+        let err = RequestContext::load(&dir.path().join("missing.json")).unwrap_err();
+        assert!(matches!(err, MetadataError::InvalidRequestContextFile(..)));
+    }
+}
@@ -4,6 +4,9 @@ pub mod env;
 pub mod error;
 pub mod fs;
 pub mod lambda;
+pub mod net;
+pub mod runtime;
+pub mod size;
 
 /// Name for the function when no name is provided.
 /// This will make the watch command to compile
@@ -0,0 +1,121 @@
+use miette::{IntoDiagnostic, Result, WrapErr};
+use reqwest::{Certificate, Client, NoProxy, Proxy};
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// User agent for the requests that Cargo Lambda sends.
+const USER_AGENT: &str = concat!("cargo-lambda/", env!("CARGO_PKG_VERSION"));
+
+static OPTIONS: OnceLock<HttpOptions> = OnceLock::new();
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Options to download templates, Zig releases, and other artifacts
+/// through proxies and TLS interception in corporate networks.
+#[derive(Clone, Debug, Default)]
+pub struct HttpOptions {
+    /// Proxy to send all the requests through. When it's not set,
+    /// the client uses the proxies in `HTTP_PROXY` and `HTTPS_PROXY`.
+    pub proxy: Option<String>,
+    /// PEM file with certificate authorities to trust,
+    /// besides the ones in the system's certificate store.
+    pub cacert: Option<PathBuf>,
+}
+
+/// Set the options for the HTTP client that all the subcommands share.
+/// The client is created on the first request, so commands that don't
+/// download anything don't fail because of invalid options.
+pub fn configure(options: HttpOptions) {
+    if OPTIONS.set(options).is_err() {
+        tracing::debug!("the HTTP client is already configured");
+    }
+}
+
+/// HTTP client shared by all the subcommands. It uses the default
+/// options if the CLI didn't configure it.
+pub fn client() -> Result<&'static Client> {
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+
+    let client = build_client(OPTIONS.get_or_init(HttpOptions::default))?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+fn build_client(options: &HttpOptions) -> Result<Client> {
+    let mut builder = Client::builder().user_agent(USER_AGENT);
+
+    if let Some(proxy) = &options.proxy {
+        let proxy = Proxy::all(proxy)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("invalid proxy URL `{proxy}`"))?;
+        // Keep honoring NO_PROXY, so requests to internal hosts skip the proxy.
+        builder = builder.proxy(proxy.no_proxy(NoProxy::from_env()));
+    }
+
+    if let Some(path) = &options.cacert {
+        for cert in load_certificates(path)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder
+        .build()
+        .into_diagnostic()
+        .wrap_err("failed to create the HTTP client")
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<Certificate>> {
+    let bundle = std::fs::read(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read the CA certificates {path:?}"))?;
+    let certs = Certificate::from_pem_bundle(&bundle)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("invalid CA certificates {path:?}"))?;
+
+    if certs.is_empty() {
+        return Err(miette::miette!(
+            "the file {path:?} doesn't include any PEM certificate"
+        ));
+    }
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_client_with_options() {
+        let options = HttpOptions {
+            proxy: Some("http://proxy.internal:3128".into()),
+            cacert: Some("../../tests/certs/ca.pem".into()),
+        };
+        build_client(&options).unwrap();
+    }
+
+    #[test]
+    fn test_build_client_with_invalid_options() {
+        let options = HttpOptions {
+            proxy: Some("not a url".into()),
+            ..Default::default()
+        };
+        let err = build_client(&options).unwrap_err();
+        assert!(err.to_string().contains("invalid proxy URL"), "{err}");
+
+        let options = HttpOptions {
+            cacert: Some("../../tests/certs/missing.pem".into()),
+            ..Default::default()
+        };
+        let err = build_client(&options).unwrap_err();
+        assert!(err.to_string().contains("failed to read"), "{err}");
+
+        let options = HttpOptions {
+            cacert: Some("Cargo.toml".into()),
+            ..Default::default()
+        };
+        let err = build_client(&options).unwrap_err();
+        assert!(err.to_string().contains("doesn't include"), "{err}");
+    }
+}
@@ -0,0 +1,84 @@
+use cargo_lambda_remote::aws_sdk_lambda::types::Runtime;
+
+/// OS-only runtime that can execute Rust binaries.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProvidedRuntime {
+    pub name: &'static str,
+    /// Version of glibc that the runtime includes, as (major, minor)
+    pub glibc_version: (u32, u32),
+}
+
+/// Runtimes that can execute Rust binaries, from the newest to the oldest.
+/// Supporting a new runtime only requires adding it to this list,
+/// and to `DEFAULT_COMPATIBLE_RUNTIMES`.
+pub const PROVIDED_RUNTIMES: [ProvidedRuntime; 2] = [
+    ProvidedRuntime {
+        name: "provided.al2023",
+        glibc_version: (2, 34),
+    },
+    ProvidedRuntime {
+        name: "provided.al2",
+        glibc_version: (2, 26),
+    },
+];
+
+/// Runtimes that extensions are compatible with by default, all the runtimes in `PROVIDED_RUNTIMES`.
+pub const DEFAULT_COMPATIBLE_RUNTIMES: &str = "provided.al2,provided.al2023";
+
+const PROVIDED_RUNTIME_PREFIX: &str = "provided.";
+
+/// The newest runtime that can execute Rust binaries, used by default.
+pub fn latest_provided_runtime() -> &'static str {
+    PROVIDED_RUNTIMES[0].name
+}
+
+/// Names of the runtimes that can execute Rust binaries, from the newest to the oldest.
+pub fn provided_runtime_names() -> Vec<&'static str> {
+    PROVIDED_RUNTIMES.iter().map(|r| r.name).collect()
+}
+
+/// Information about a runtime that can execute Rust binaries,
+/// None if the runtime is not in `PROVIDED_RUNTIMES`.
+pub fn provided_runtime(name: &str) -> Option<&'static ProvidedRuntime> {
+    PROVIDED_RUNTIMES.iter().find(|r| r.name == name)
+}
+
+/// Whether the runtime can execute Rust binaries. Besides the runtimes in `PROVIDED_RUNTIMES`,
+/// it accepts the OS-only runtimes that the AWS SDK knows about, so new runtimes can be used
+/// before Cargo Lambda knows about them.
+pub fn is_provided_runtime(name: &str) -> bool {
+    provided_runtime(name).is_some()
+        || (name.starts_with(PROVIDED_RUNTIME_PREFIX) && is_lambda_runtime(name))
+}
+
+/// Whether Lambda supports the runtime.
+pub fn is_lambda_runtime(name: &str) -> bool {
+    Runtime::values().contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provided_runtimes() {
+        assert_eq!("provided.al2023", latest_provided_runtime());
+
+        let mut names = provided_runtime_names();
+        names.sort();
+        let mut compatible = DEFAULT_COMPATIBLE_RUNTIMES.split(',').collect::<Vec<_>>();
+        compatible.sort();
+        assert_eq!(names, compatible);
+
+        assert_eq!(
+            Some((2, 26)),
+            provided_runtime("provided.al2").map(|r| r.glibc_version)
+        );
+        assert_eq!(None, provided_runtime("nodejs20.x"));
+
+        assert!(is_provided_runtime("provided.al2023"));
+        assert!(!is_provided_runtime("provided"));
+        assert!(!is_provided_runtime("provided.cobol"));
+        assert!(!is_provided_runtime("nodejs20.x"));
+    }
+}
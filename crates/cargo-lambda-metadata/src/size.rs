@@ -0,0 +1,171 @@
+use clap::Args;
+use serde::{Deserialize, Serialize, ser::SerializeStruct};
+use std::str::FromStr;
+
+use crate::error::MetadataError;
+
+/// Size limit for zip files uploaded directly to Lambda.
+pub const ZIP_UPLOAD_LIMIT: u64 = 50 * 1024 * 1024;
+
+/// Size limit for the unzipped deployment package, including layers.
+pub const UNZIPPED_LIMIT: u64 = 250 * 1024 * 1024;
+
+#[derive(Args, Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SizeLimitOptions {
+    /// Maximum size of the zip file, like `50MB` [default: 50MB on deploys without `--s3-bucket`].
+    /// Packages over the limit fail the build, or the deploy before uploading them
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    #[serde(default)]
+    pub max_zip_size: Option<String>,
+
+    /// Maximum size of the uncompressed package, like `250MB` [default: 250MB].
+    /// Packages over the limit fail the build, or the deploy before uploading them
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    #[serde(default)]
+    pub max_unzipped_size: Option<String>,
+
+    /// Warn when a package is over the size limits, instead of failing
+    #[arg(long)]
+    #[serde(default)]
+    pub warn_size_limits: bool,
+}
+
+/// Size limits that the packages are checked against, in bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SizeLimits {
+    /// Limit for the zip file, None when the zip file is not checked
+    pub zip: Option<u64>,
+    pub unzipped: u64,
+    pub warn_only: bool,
+}
+
+impl SizeLimitOptions {
+    /// Size limits for a package. Zip files are checked against
+    /// Lambda's limit only when they are uploaded directly to Lambda,
+    /// zip files uploaded to S3 are only checked against `--max-zip-size`.
+    pub fn limits(&self, direct_upload: bool) -> Result<SizeLimits, MetadataError> {
+        let zip = match &self.max_zip_size {
+            Some(size) => Some(PackageSize::from_str(size)?.0),
+            None => direct_upload.then_some(ZIP_UPLOAD_LIMIT),
+        };
+
+        let unzipped = match &self.max_unzipped_size {
+            Some(size) => PackageSize::from_str(size)?.0,
+            None => UNZIPPED_LIMIT,
+        };
+
+        Ok(SizeLimits {
+            zip,
+            unzipped,
+            warn_only: self.warn_size_limits,
+        })
+    }
+
+    pub fn count_fields(&self) -> usize {
+        self.max_zip_size.is_some() as usize
+            + self.max_unzipped_size.is_some() as usize
+            + self.warn_size_limits as usize
+    }
+
+    pub fn serialize_fields<S>(
+        &self,
+        state: &mut <S as serde::Serializer>::SerializeStruct,
+    ) -> Result<(), S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if let Some(max_zip_size) = &self.max_zip_size {
+            state.serialize_field("max_zip_size", max_zip_size)?;
+        }
+        if let Some(max_unzipped_size) = &self.max_unzipped_size {
+            state.serialize_field("max_unzipped_size", max_unzipped_size)?;
+        }
+        if self.warn_size_limits {
+            state.serialize_field("warn_size_limits", &true)?;
+        }
+        Ok(())
+    }
+}
+
+/// Size of a package in bytes, parsed from values like `50MB`, `512KB`, or `1GB`.
+/// Units are multiples of 1024, like the limits in Lambda's documentation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PackageSize(pub u64);
+
+impl FromStr for PackageSize {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MetadataError::InvalidPackageSize(s.to_string());
+
+        let value = s.trim();
+        let (number, multiplier) = [
+            ("GB", 1024 * 1024 * 1024),
+            ("MB", 1024 * 1024),
+            ("KB", 1024),
+        ]
+        .into_iter()
+        .find_map(|(unit, multiplier)| {
+            value
+                .strip_suffix(unit)
+                .or_else(|| value.strip_suffix(&unit.replace('B', "iB")))
+                .map(|number| (number, multiplier))
+        })
+        .unwrap_or((value, 1));
+
+        match number.trim().parse::<u64>() {
+            Ok(size) if size > 0 => size
+                .checked_mul(multiplier)
+                .map(PackageSize)
+                .ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn parse_size(value: &str) -> Result<String, MetadataError> {
+    PackageSize::from_str(value)?;
+    Ok(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_size() {
+        assert_eq!(PackageSize(ZIP_UPLOAD_LIMIT), "50MB".parse().unwrap());
+        assert_eq!(PackageSize(UNZIPPED_LIMIT), "250MiB".parse().unwrap());
+        assert_eq!(PackageSize(512 * 1024), "512 KB".parse().unwrap());
+        assert_eq!(PackageSize(1024 * 1024 * 1024), "1GB".parse().unwrap());
+        assert_eq!(PackageSize(1000), "1000".parse().unwrap());
+
+        for invalid in ["", "0MB", "-1MB", "lots", "50TB"] {
+            assert!(PackageSize::from_str(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let options = SizeLimitOptions::default();
+        assert_eq!(
+            SizeLimits {
+                zip: Some(ZIP_UPLOAD_LIMIT),
+                unzipped: UNZIPPED_LIMIT,
+                warn_only: false,
+            },
+            options.limits(true).unwrap()
+        );
+        assert_eq!(None, options.limits(false).unwrap().zip);
+
+        let options = SizeLimitOptions {
+            max_zip_size: Some("10MB".into()),
+            max_unzipped_size: Some("20MB".into()),
+            warn_size_limits: true,
+        };
+        let limits = options.limits(false).unwrap();
+        assert_eq!(Some(10 * 1024 * 1024), limits.zip);
+        assert_eq!(20 * 1024 * 1024, limits.unzipped);
+        assert!(limits.warn_only);
+    }
+}
@@ -6,7 +6,7 @@ use figment::Jail;
 
 use cargo_lambda_metadata::{
     cargo::load_metadata,
-    config::{ConfigOptions, load_config_without_cli_flags},
+    config::{ConfigOptions, load_config_without_cli_flags, load_metadata_environment},
     lambda::Memory,
 };
 
@@ -109,3 +109,48 @@ fn test_env_with_arrays() {
         Ok(())
     });
 }
+
+#[test]
+fn test_metadata_environment() {
+    Jail::expect_with(|jail| {
+        jail.set_env("CARGO_LAMBDA_DEPLOY.ENV_VAR", "[LOCAL=true]");
+
+        jail.create_file(
+            "Cargo.toml",
+            r#"
+            [package]
+            name = "test"
+
+            [[bin]]
+            name = "test"
+            path = "src/main.rs"
+
+            [package.metadata.lambda.env]
+            FOO = "BAR"
+
+            [package.metadata.lambda.deploy]
+            env = { "APP_ENV" = "production" }
+        "#,
+        )?;
+
+        jail.create_file(
+            "CargoLambda.toml",
+            r#"
+            [env]
+            HOME_DIR = "/home/david"
+        "#,
+        )?;
+
+        let metadata = load_metadata("Cargo.toml").unwrap();
+
+        let config = load_config_without_cli_flags(&metadata, &ConfigOptions::default()).unwrap();
+        assert_eq!(config.env.get("HOME_DIR"), Some(&"/home/david".to_string()));
+
+        let env = load_metadata_environment(&metadata, &ConfigOptions::default()).unwrap();
+        assert_eq!(2, env.len(), "unexpected environment: {env:?}");
+        assert_eq!(env.get("FOO"), Some(&"BAR".to_string()));
+        assert_eq!(env.get("APP_ENV"), Some(&"production".to_string()));
+
+        Ok(())
+    });
+}
@@ -0,0 +1,252 @@
+use indexmap::IndexMap;
+use liquid::Object;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::{
+    fs::{read_to_string, write},
+    path::Path,
+};
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Value};
+
+use crate::template::config::TemplateDependency;
+
+/// Add the template's dependencies whose conditions match the prompt variables
+/// to the rendered Cargo.toml. Editing the manifest keeps its formatting,
+/// instead of toggling the dependencies with Liquid blocks in the template.
+pub(crate) fn add_template_dependencies(
+    manifest_path: &Path,
+    dependencies: &IndexMap<String, TemplateDependency>,
+    variables: &Object,
+) -> Result<()> {
+    if dependencies.is_empty() || !manifest_path.exists() {
+        return Ok(());
+    }
+
+    let manifest = read_to_string(manifest_path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to read file: {manifest_path:?}"))?;
+    let mut manifest = manifest
+        .parse::<DocumentMut>()
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            format!("invalid Cargo.toml rendered by the template: {manifest_path:?}")
+        })?;
+
+    add_dependencies(&mut manifest, dependencies, variables)?;
+
+    write(manifest_path, manifest.to_string())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to update file: {manifest_path:?}"))
+}
+
+fn add_dependencies(
+    manifest: &mut DocumentMut,
+    dependencies: &IndexMap<String, TemplateDependency>,
+    variables: &Object,
+) -> Result<()> {
+    let Some(deps) = manifest
+        .entry("dependencies")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+    else {
+        return Ok(());
+    };
+
+    for (name, dependency) in dependencies {
+        if !dependency.condition.matches(variables) {
+            tracing::trace!(name, "skipping template dependency");
+            continue;
+        }
+
+        match deps.get_mut(name) {
+            Some(existing) => merge_dependency(existing, dependency),
+            None => {
+                let Some(version) = &dependency.version else {
+                    return Err(miette::miette!(
+                        "the dependency `{name}` in CargoLambda.toml doesn't have a version, and the template's Cargo.toml doesn't include it"
+                    ));
+                };
+                deps.insert(name, new_dependency(version, dependency));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn new_dependency(version: &str, dependency: &TemplateDependency) -> Item {
+    if dependency.features.is_empty() && dependency.default_features.is_none() {
+        return toml_edit::value(version);
+    }
+
+    let mut table = InlineTable::new();
+    table.insert("version", version.into());
+    if let Some(default_features) = dependency.default_features {
+        table.insert("default-features", default_features.into());
+    }
+    if !dependency.features.is_empty() {
+        table.insert(
+            "features",
+            Value::Array(Array::from_iter(&dependency.features)),
+        );
+    }
+    Item::Value(Value::InlineTable(table))
+}
+
+/// Add the features to a dependency that the template already declares.
+fn merge_dependency(existing: &mut Item, dependency: &TemplateDependency) {
+    if dependency.features.is_empty() && dependency.default_features.is_none() {
+        return;
+    }
+
+    // Convert `serde = "1"` into `serde = { version = "1" }`
+    // before adding the features.
+    if let Some(version) = existing.as_str().map(String::from) {
+        let mut table = InlineTable::new();
+        table.insert("version", version.into());
+        *existing = Item::Value(Value::InlineTable(table));
+    }
+
+    let Some(table) = existing.as_table_like_mut() else {
+        return;
+    };
+
+    if let Some(default_features) = dependency.default_features {
+        table.insert("default-features", toml_edit::value(default_features));
+    }
+
+    let features = table
+        .entry("features")
+        .or_insert(Item::Value(Value::Array(Array::new())));
+    if let Some(features) = features.as_array_mut() {
+        for feature in &dependency.features {
+            if !features.iter().any(|f| f.as_str() == Some(feature)) {
+                features.push(feature);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::config::{PromptValue, RenderCondition};
+
+    fn dependency(
+        var: &str,
+        value: PromptValue,
+        version: Option<&str>,
+        features: &[&str],
+    ) -> TemplateDependency {
+        TemplateDependency {
+            condition: RenderCondition {
+                var: var.into(),
+                r#match: Some(value),
+                not_match: None,
+            },
+            version: version.map(String::from),
+            features: features.iter().map(|f| f.to_string()).collect(),
+            default_features: None,
+        }
+    }
+
+    #[test]
+    fn test_add_dependencies() {
+        let mut manifest = r#"[package]
+name = "basic-lambda"
+
+[dependencies]
+lambda_runtime = "0.13"
+serde = "1"
+tracing-subscriber = { version = "0.3", default-features = false, features = ["fmt"] }
+"#
+        .parse::<DocumentMut>()
+        .unwrap();
+
+        let mut dependencies = IndexMap::new();
+        dependencies.insert(
+            "serde".to_string(),
+            dependency(
+                "serde_derive",
+                PromptValue::Boolean(true),
+                None,
+                &["derive"],
+            ),
+        );
+        dependencies.insert(
+            "tracing-subscriber".to_string(),
+            dependency(
+                "json_logs",
+                PromptValue::Boolean(true),
+                None,
+                &["fmt", "json"],
+            ),
+        );
+        dependencies.insert(
+            "aws-sdk-s3".to_string(),
+            dependency(
+                "aws_service",
+                PromptValue::String("s3".into()),
+                Some("1"),
+                &[],
+            ),
+        );
+        dependencies.insert(
+            "aws-sdk-dynamodb".to_string(),
+            dependency(
+                "aws_service",
+                PromptValue::String("dynamodb".into()),
+                Some("1"),
+                &[],
+            ),
+        );
+
+        let variables = liquid::object!({
+            "serde_derive": true,
+            "json_logs": true,
+            "aws_service": "s3",
+        });
+        add_dependencies(&mut manifest, &dependencies, &variables).unwrap();
+
+        assert_eq!(
+            r#"[package]
+name = "basic-lambda"
+
+[dependencies]
+lambda_runtime = "0.13"
+serde = { version = "1", features = ["derive"] }
+tracing-subscriber = { version = "0.3", default-features = false, features = ["fmt", "json"] }
+aws-sdk-s3 = "1"
+"#,
+            manifest.to_string()
+        );
+    }
+
+    #[test]
+    fn test_add_dependency_without_version() {
+        let mut manifest = "[dependencies]\n".parse::<DocumentMut>().unwrap();
+        let mut dependencies = IndexMap::new();
+        dependencies.insert(
+            "serde".to_string(),
+            dependency(
+                "serde_derive",
+                PromptValue::Boolean(true),
+                None,
+                &["derive"],
+            ),
+        );
+
+        let variables = liquid::object!({"serde_derive": true});
+        let err = add_dependencies(&mut manifest, &dependencies, &variables).unwrap_err();
+        assert!(err.to_string().contains("`serde`"));
+    }
+
+    #[test]
+    fn test_new_dependency_with_features() {
+        let mut dep = dependency("x", PromptValue::Boolean(true), None, &["rt-tokio"]);
+        dep.default_features = Some(false);
+        assert_eq!(
+            r#"{ version = "1", default-features = false, features = ["rt-tokio"] }"#,
+            new_dependency("1", &dep).to_string()
+        );
+    }
+}
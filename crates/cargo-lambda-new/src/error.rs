@@ -14,14 +14,35 @@ pub(crate) enum CreateError {
     UnexpectedInput(#[from] InquireError),
     #[error("invalid file path in template {0:?}")]
     InvalidTemplateEntry(PathBuf),
-    #[error("project created in {0}, but the EDITOR variable is missing")]
-    InvalidEditor(String),
-    #[error("invalid package name: {0}")]
-    InvalidPackageName(String),
+    #[error("project created in {0:?}, but the EDITOR variable is missing")]
+    InvalidEditor(PathBuf),
+    #[error("invalid package name `{name}`: {reason}")]
+    InvalidPackageName {
+        name: String,
+        reason: String,
+        #[help]
+        help: Option<String>,
+    },
     #[error("the path is not a directory: {0}")]
     NotADirectoryPath(PathBuf),
     #[error(transparent)]
     InvalidPath(#[from] io::Error),
     #[error("`cargo lambda init` cannot be run on existing Cargo packages")]
     InvalidPackageRoot,
+    #[error("the handler file doesn't exist: {0:?}")]
+    MissingHandler(PathBuf),
+    #[error(
+        "the SHA256 checksum of the template {template} is {actual}, but {expected} was expected"
+    )]
+    #[diagnostic(help(
+        "the template changed since its checksum was pinned, or the download was modified. Check the template's source before updating the checksum"
+    ))]
+    TemplateChecksumMismatch {
+        template: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("only ZIP templates can be verified with --template-sha256")]
+    #[diagnostic(help("use a ZIP archive of the template to pin it to a checksum"))]
+    UnverifiableTemplate,
 }
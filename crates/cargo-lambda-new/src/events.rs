@@ -1,52 +1,110 @@
-pub(crate) const WELL_KNOWN_EVENTS: [&str; 50] = [
-    "activemq::ActiveMqEvent",
-    "autoscaling::AutoScalingEvent",
-    "bedrock_agent_runtime::AgentEvent",
-    "chime_bot::ChimeBotEvent",
-    "cloudformation::CloudFormationCustomResourceRequest",
-    "cloudformation::CloudFormationCustomResourceResponse",
-    "cloudformation::provider::CloudFormationCustomResourceRequest",
-    "cloudformation::provider::CloudFormationCustomResourceResponse",
-    "cloudwatch_alarms::CloudWatchAlarm",
-    "cloudwatch_events::CloudWatchEvent",
-    "cloudwatch_logs::CloudwatchLogsEvent",
-    "cloudwatch_logs::CloudwatchLogsLogEvent",
-    "codebuild::CodeBuildEvent",
-    "code_commit::CodeCommitEvent",
-    "codedeploy::CodeDeployDeploymentEvent",
-    "codedeploy::CodeDeployEvent",
-    "codedeploy::CodeDeployInstanceEvent",
-    "codedeploy::CodeDeployLifecycleEvent",
-    "codepipeline_cloudwatch::CodePipelineCloudWatchEvent",
-    "codepipeline_cloudwatch::CodePipelineDeploymentEvent",
-    "codepipeline_cloudwatch::CodePipelineEvent",
-    "codepipeline_cloudwatch::CodePipelineInstanceEvent",
-    "codepipeline_job::CodePipelineJobEvent",
-    "cognito::CognitoEvent",
-    "cognito::CognitoEventUserPoolsPreTokenGenV2",
-    "config::ConfigEvent",
-    "connect::ConnectEvent",
-    "documentdb::DocumentDbEvent",
-    "dynamodb::Event",
-    "ecr_scan::EcrScanEvent",
-    "eventbridge::EventBridgeEvent",
-    "firehose::KinesisFirehoseEvent",
-    "iot_1_click::IoTOneClickDeviceEvent",
-    "iot_1_click::IoTOneClickEvent",
-    "iot_button::IoTButtonEvent",
-    "kafka::KafkaEvent",
-    "kinesis_analytics::KinesisAnalyticsOutputDeliveryEvent",
-    "kinesis::KinesisEvent",
-    "lex::LexEvent",
-    "rabbitmq::RabbitMqEvent",
-    "s3_batch_job::S3BatchJobEvent",
-    "s3::S3Event",
-    "secretsmanager::SecretsManagerSecretRotationEvent",
-    "serde_json::Value", // this type is a special case not included in the events crate
-    "ses::SimpleEmailEvent",
-    "sns::CloudWatchAlarmPayload",
-    "sns::SnsEvent",
-    "sqs::SqsEvent",
-    "sqs::SqsApiEvent",
-    "sqs::SqsApiEventObj",
-];
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+/// Catalog of well known events, embedded in the binary.
+const WELL_KNOWN_EVENTS: &str = include_str!("events.toml");
+
+/// Events indexed by their type, for example `s3::S3Event`.
+pub(crate) type Events = IndexMap<String, EventDefinition>;
+
+/// Event that a function can receive.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub(crate) struct EventDefinition {
+    /// Feature of the aws_lambda_events crate that includes the event
+    pub feature: String,
+    /// Path to import the event type, defaults to `aws_lambda_events::event::TYPE`
+    #[serde(default)]
+    pub import: Option<String>,
+    /// Code to process the event inside the function handler
+    #[serde(default)]
+    pub snippet: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Catalog {
+    events: Events,
+}
+
+/// Events that Cargo Lambda knows how to scaffold.
+pub(crate) fn well_known_events() -> &'static Events {
+    static EVENTS: OnceLock<Events> = OnceLock::new();
+    EVENTS.get_or_init(|| {
+        toml::from_str::<Catalog>(WELL_KNOWN_EVENTS)
+            .expect("invalid events catalog")
+            .events
+    })
+}
+
+/// Well known events, extended with the events that a template defines.
+/// Template events replace well known events with the same type.
+pub(crate) fn supported_events(template_events: &Events) -> Events {
+    let mut events = well_known_events().clone();
+    events.extend(template_events.clone());
+    events
+}
+
+/// Print the supported events with the feature that they require.
+pub(crate) fn print_events(events: &Events) {
+    let width = events.keys().map(|k| k.len()).max().unwrap_or_default();
+
+    println!("{:width$}  FEATURE", "EVENT TYPE");
+    for (event_type, event) in events {
+        println!("{event_type:width$}  {}", event.feature);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_well_known_events() {
+        let events = well_known_events();
+        assert_eq!(50, events.len());
+
+        let s3 = &events["s3::S3Event"];
+        assert_eq!("s3", s3.feature);
+        assert_eq!(None, s3.import);
+        assert!(
+            s3.snippet
+                .as_ref()
+                .unwrap()
+                .contains("record.s3.bucket.name")
+        );
+
+        let json = &events["serde_json::Value"];
+        assert_eq!("serde_json", json.feature);
+        assert_eq!(Some("serde_json::Value".to_string()), json.import);
+
+        let provider = &events["cloudformation::provider::CloudFormationCustomResourceRequest"];
+        assert_eq!("cloudformation", provider.feature);
+    }
+
+    #[test]
+    fn test_supported_events() {
+        let template_events = Events::from([
+            (
+                "s3::S3Event".to_string(),
+                EventDefinition {
+                    feature: "s3".into(),
+                    snippet: Some("todo!()".into()),
+                    ..Default::default()
+                },
+            ),
+            (
+                "my_events::OrderCreated".to_string(),
+                EventDefinition {
+                    feature: "orders".into(),
+                    import: Some("my_events::OrderCreated".into()),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let events = supported_events(&template_events);
+        assert_eq!(51, events.len());
+        assert_eq!(Some("todo!()".to_string()), events["s3::S3Event"].snippet);
+        assert_eq!("orders", events["my_events::OrderCreated"].feature);
+    }
+}
@@ -0,0 +1,388 @@
+use liquid::{Object, model::Value as LiquidValue};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use regex::Regex;
+use std::{
+    collections::BTreeSet,
+    fs::{copy, create_dir_all, read_to_string, write},
+    path::Path,
+};
+use toml_edit::{Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table, Value, value};
+
+use crate::{error::CreateError, events::well_known_events, integration_tests};
+
+/// Path of the handler in the new project.
+const HANDLER_PATH: &str = "src/main.rs";
+
+/// Crates that handlers usually depend on, with the version that new projects use.
+/// The first value is the name that the code uses to import the crate.
+const KNOWN_CRATES: &[(&str, &str, &str, &[&str])] = &[
+    ("anyhow", "anyhow", "1", &[]),
+    (
+        "aws_config",
+        "aws-config",
+        "1",
+        &["behavior-version-latest"],
+    ),
+    ("lambda_http", "lambda_http", "0.14", &[]),
+    ("lambda_runtime", "lambda_runtime", "0.13", &[]),
+    ("serde", "serde", "1", &["derive"]),
+    ("serde_json", "serde_json", "1", &[]),
+    ("tokio", "tokio", "1", &["macros"]),
+    ("tracing", "tracing", "0.1", &[]),
+    ("tracing_subscriber", "tracing-subscriber", "0.3", &[]),
+];
+
+/// Version of aws_lambda_events for handlers that receive AWS events.
+const AWS_LAMBDA_EVENTS_VERSION: &str = "0.16";
+
+/// What the handler's source tells about the project around it.
+#[derive(Debug, Default, PartialEq)]
+struct HandlerInfo {
+    /// Crates that the handler imports, by the name that the code uses
+    crates: BTreeSet<String>,
+    /// Features of aws_lambda_events that include the events that the handler uses
+    event_features: BTreeSet<String>,
+}
+
+impl HandlerInfo {
+    fn inspect(source: &str) -> HandlerInfo {
+        let path_regex = Regex::new(r"\b([a-z][a-z0-9_]*)::").expect("invalid crate path regex");
+        let use_regex =
+            Regex::new(r"\b(?:use|extern crate)\s+([a-z][a-z0-9_]*)\b").expect("invalid use regex");
+        let events_regex = Regex::new(r"\baws_lambda_events::(?:event::)?([a-z0-9_]+)")
+            .expect("invalid events regex");
+
+        let crates = path_regex
+            .captures_iter(source)
+            .chain(use_regex.captures_iter(source))
+            .map(|caps| caps[1].to_string())
+            .filter(|name| is_known_crate(name))
+            .collect();
+
+        let events = well_known_events();
+        let event_features = events_regex
+            .captures_iter(source)
+            .filter_map(|caps| {
+                let prefix = format!("{}::", &caps[1]);
+                events
+                    .iter()
+                    .find(|(event_type, _)| event_type.starts_with(&prefix))
+                    .map(|(_, event)| event.feature.clone())
+            })
+            .collect();
+
+        HandlerInfo {
+            crates,
+            event_features,
+        }
+    }
+
+    fn http_function(&self) -> bool {
+        self.crates.contains("lambda_http")
+    }
+
+    fn uses_runtime(&self) -> bool {
+        self.http_function() || self.crates.contains("lambda_runtime")
+    }
+}
+
+fn is_known_crate(name: &str) -> bool {
+    name == "aws_lambda_events"
+        || name.starts_with("aws_sdk_")
+        || KNOWN_CRATES.iter().any(|(import, ..)| *import == name)
+}
+
+/// Create a project around an existing handler. The handler is copied into the project,
+/// and the manifest depends on the crates that the handler uses.
+/// It returns the variables to render the integration tests with.
+pub(crate) fn create_project(
+    path: &Path,
+    package_name: &str,
+    handler: &Path,
+    binary_name: Option<&str>,
+) -> Result<Object> {
+    if !handler.is_file() {
+        return Err(CreateError::MissingHandler(handler.to_path_buf()).into());
+    }
+
+    let source = read_to_string(handler)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to read file: {handler:?}"))?;
+    let info = HandlerInfo::inspect(&source);
+    if !info.uses_runtime() {
+        return Err(miette::miette!(
+            "the handler {handler:?} doesn't use lambda_runtime or lambda_http"
+        ));
+    }
+
+    // Binaries are named after the handler file, unless the file is already a main file.
+    let binary_name = binary_name.map(String::from).or_else(|| {
+        handler
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .filter(|stem| *stem != "main")
+            .map(String::from)
+    });
+    let binary_name = binary_name.filter(|name| name != package_name);
+
+    let manifest_path = path.join("Cargo.toml");
+    if manifest_path.exists() {
+        return Err(CreateError::InvalidPackageRoot.into());
+    }
+
+    copy_handler(handler, &path.join(HANDLER_PATH))?;
+
+    let manifest = render_manifest(package_name, binary_name.as_deref(), &info);
+    write(&manifest_path, manifest)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to create file: {manifest_path:?}"))?;
+
+    let gitignore_path = path.join(".gitignore");
+    if !gitignore_path.exists() {
+        write(&gitignore_path, "/target\n")
+            .into_diagnostic()
+            .wrap_err_with(|| format!("unable to create file: {gitignore_path:?}"))?;
+    }
+
+    let globals = liquid::object!({
+        "binary_name": binary_name.map(LiquidValue::scalar).unwrap_or(LiquidValue::Nil),
+        "http_function": info.http_function(),
+        "basic_example": false,
+    });
+    integration_tests::create_integration_tests(path, package_name, &globals)?;
+
+    Ok(globals)
+}
+
+fn copy_handler(handler: &Path, destination: &Path) -> Result<()> {
+    // `cargo lambda init` can run in the directory that already has the handler.
+    let same_file = dunce::canonicalize(handler).ok() == dunce::canonicalize(destination).ok();
+    if same_file {
+        return Ok(());
+    }
+
+    if destination.exists() {
+        return Err(miette::miette!(
+            "unable to copy the handler, the file {destination:?} already exists"
+        ));
+    }
+
+    if let Some(parent) = destination.parent() {
+        create_dir_all(parent)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("unable to create directory: {parent:?}"))?;
+    }
+
+    copy(handler, destination)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to copy file: from {handler:?} to {destination:?}"))?;
+    Ok(())
+}
+
+fn render_manifest(package_name: &str, binary_name: Option<&str>, info: &HandlerInfo) -> String {
+    let mut manifest = DocumentMut::new();
+
+    let mut package = Table::new();
+    package.insert("name", value(package_name));
+    package.insert("version", value("0.1.0"));
+    package.insert("edition", value("2024"));
+    manifest.insert("package", Item::Table(package));
+
+    let mut deps = Table::new();
+    for name in &info.crates {
+        if name == "aws_lambda_events" {
+            continue;
+        }
+        match KNOWN_CRATES.iter().find(|(import, ..)| import == name) {
+            Some((_, crate_name, version, features)) => {
+                deps.insert(crate_name, dependency(version, None, features));
+            }
+            // Clients of the AWS SDK, like `aws_sdk_s3`.
+            None => {
+                deps.insert(&name.replace('_', "-"), value("1"));
+            }
+        }
+    }
+    if info.crates.contains("aws_lambda_events") || !info.event_features.is_empty() {
+        let features = info
+            .event_features
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let default_features = if features.is_empty() {
+            None
+        } else {
+            Some(false)
+        };
+        deps.insert(
+            "aws_lambda_events",
+            dependency(AWS_LAMBDA_EVENTS_VERSION, default_features, &features),
+        );
+    }
+    deps.sort_values();
+    manifest.insert("dependencies", Item::Table(deps));
+
+    if let Some(binary_name) = binary_name {
+        let mut bin = Table::new();
+        bin.insert("name", value(binary_name));
+        bin.insert("path", value(HANDLER_PATH));
+        let mut bins = ArrayOfTables::new();
+        bins.push(bin);
+        manifest.insert("bin", Item::ArrayOfTables(bins));
+    }
+
+    // Settings that `cargo lambda deploy` reads, so they're easy to find and change.
+    let mut deploy = Table::new();
+    deploy.insert("memory", value(128));
+    deploy.insert("timeout", value(30));
+    let mut lambda = Table::new();
+    lambda.set_implicit(true);
+    lambda.insert("deploy", Item::Table(deploy));
+    let mut metadata = Table::new();
+    metadata.set_implicit(true);
+    metadata.insert("lambda", Item::Table(lambda));
+    if let Some(package) = manifest["package"].as_table_mut() {
+        package.insert("metadata", Item::Table(metadata));
+    }
+
+    manifest.to_string()
+}
+
+fn dependency(version: &str, default_features: Option<bool>, features: &[&str]) -> Item {
+    if features.is_empty() && default_features.is_none() {
+        return value(version);
+    }
+
+    let mut table = InlineTable::new();
+    table.insert("version", version.into());
+    if let Some(default_features) = default_features {
+        table.insert("default-features", default_features.into());
+    }
+    if !features.is_empty() {
+        table.insert(
+            "features",
+            Value::Array(features.iter().copied().collect::<Array>()),
+        );
+    }
+    Item::Value(Value::InlineTable(table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const S3_HANDLER: &str = r#"use aws_lambda_events::event::s3::S3Event;
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+
+async fn function_handler(event: LambdaEvent<S3Event>) -> Result<(), Error> {
+    let client = aws_sdk_s3::Client::new(&aws_config::load_from_env().await);
+    tracing::info!(records = event.payload.records.len(), "processing objects");
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt().json().init();
+    run(service_fn(function_handler)).await
+}
+"#;
+
+    #[test]
+    fn test_inspect_handler() {
+        let info = HandlerInfo::inspect(S3_HANDLER);
+        assert_eq!(
+            vec![
+                "aws_config",
+                "aws_lambda_events",
+                "aws_sdk_s3",
+                "lambda_runtime",
+                "tokio",
+                "tracing",
+                "tracing_subscriber"
+            ],
+            info.crates.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["s3"], info.event_features.iter().collect::<Vec<_>>());
+        assert!(info.uses_runtime());
+        assert!(!info.http_function());
+
+        let info =
+            HandlerInfo::inspect("use lambda_http::{run, service_fn, Body, Error, Request};");
+        assert!(info.http_function());
+        assert!(info.event_features.is_empty());
+
+        assert!(!HandlerInfo::inspect("fn main() {}").uses_runtime());
+    }
+
+    #[test]
+    fn test_render_manifest() {
+        let info = HandlerInfo::inspect(S3_HANDLER);
+        let manifest = render_manifest("uploads", Some("process-uploads"), &info);
+
+        assert_eq!(
+            r#"[package]
+name = "uploads"
+version = "0.1.0"
+edition = "2024"
+
+[package.metadata.lambda.deploy]
+memory = 128
+timeout = 30
+
+[dependencies]
+aws-config = { version = "1", features = ["behavior-version-latest"] }
+aws-sdk-s3 = "1"
+aws_lambda_events = { version = "0.16", default-features = false, features = ["s3"] }
+lambda_runtime = "0.13"
+tokio = { version = "1", features = ["macros"] }
+tracing = "0.1"
+tracing-subscriber = "0.3"
+
+[[bin]]
+name = "process-uploads"
+path = "src/main.rs"
+"#,
+            manifest
+        );
+    }
+
+    #[test]
+    fn test_create_project() {
+        let tmp = tempfile::tempdir().unwrap();
+        let handler = tmp.path().join("process_uploads.rs");
+        write(&handler, S3_HANDLER).unwrap();
+
+        let project = tmp.path().join("uploads");
+        let globals = create_project(&project, "uploads", &handler, None).unwrap();
+        assert_eq!(globals["binary_name"], "process_uploads");
+
+        assert_eq!(
+            S3_HANDLER,
+            read_to_string(project.join(HANDLER_PATH)).unwrap()
+        );
+        assert!(project.join("Cargo.toml").is_file());
+        assert!(
+            project
+                .join(integration_tests::INTEGRATION_TESTS_PATH)
+                .is_file()
+        );
+
+        let err = create_project(&project, "uploads", &handler, None).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CreateError>(),
+            Some(CreateError::InvalidPackageRoot)
+        ));
+    }
+
+    #[test]
+    fn test_create_project_in_handler_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let handler = tmp.path().join(HANDLER_PATH);
+        create_dir_all(handler.parent().unwrap()).unwrap();
+        write(&handler, S3_HANDLER).unwrap();
+
+        let globals = create_project(tmp.path(), "uploads", &handler, None).unwrap();
+        assert_eq!(globals["binary_name"], LiquidValue::Nil);
+        assert_eq!(S3_HANDLER, read_to_string(&handler).unwrap());
+    }
+}
@@ -1,7 +1,10 @@
+use cargo_lambda_interactive::{Confirm, error::InquireError, is_stdin_tty};
 use clap::Args;
 use liquid::{Object, model::Value};
 use miette::Result;
 
+use crate::error::CreateError;
+
 pub(crate) const DEFAULT_TEMPLATE_URL: &str =
     "https://github.com/cargo-lambda/new-extensions-template/archive/refs/heads/main.zip";
 
@@ -17,10 +20,39 @@ pub(crate) struct Options {
     /// Whether the extension includes an Events processor
     #[arg(long)]
     events: bool,
+    /// Whether the extension is internal, and runs in the same process as the function
+    #[arg(long)]
+    internal: bool,
 }
 
 impl Options {
-    pub(crate) fn validate_options(&mut self) -> Result<()> {
+    pub(crate) fn validate_options(&mut self, no_interactive: bool) -> Result<(), CreateError> {
+        if no_interactive || !self.missing_options() || !is_stdin_tty() {
+            return Ok(());
+        }
+
+        self.ask_template_options()?;
+        Ok(())
+    }
+
+    pub(crate) fn ask_template_options(&mut self) -> Result<(), InquireError> {
+        self.telemetry = Confirm::new("Does this extension subscribe to the Telemetry API?")
+            .with_help_message("type `yes` to receive the function logs, platform events, and metrics that Lambda sends to extensions")
+            .with_default(false)
+            .prompt()?;
+
+        if !self.telemetry {
+            self.logs = Confirm::new("Does this extension subscribe to the Logs API?")
+                .with_help_message("type `yes` to receive the function logs. New extensions should use the Telemetry API instead")
+                .with_default(false)
+                .prompt()?;
+        }
+
+        self.internal = Confirm::new("Is this an internal extension?")
+            .with_help_message("internal extensions run in the same process as the function, external extensions run in their own process")
+            .with_default(false)
+            .prompt()?;
+
         Ok(())
     }
 
@@ -33,6 +65,7 @@ impl Options {
             "logs": self.logs,
             "telemetry": self.telemetry,
             "events": self.add_events_extension(),
+            "internal": self.internal,
             "lambda_extension_version": lv,
         }))
     }
@@ -40,6 +73,10 @@ impl Options {
     fn add_events_extension(&self) -> bool {
         self.events || (!self.logs && !self.telemetry)
     }
+
+    fn missing_options(&self) -> bool {
+        !self.logs && !self.telemetry && !self.events && !self.internal
+    }
 }
 
 #[cfg(test)]
@@ -86,4 +123,22 @@ mod test {
             assert_eq!(exp, opt.add_events_extension(), "options: {:?}", opt);
         }
     }
+
+    #[test]
+    fn test_extension_variables() {
+        let opts = Options {
+            telemetry: true,
+            internal: true,
+            ..Default::default()
+        };
+        assert!(!opts.missing_options());
+
+        let variables = opts.variables().unwrap();
+        assert_eq!(variables["telemetry"], Value::scalar(true));
+        assert_eq!(variables["logs"], Value::scalar(false));
+        assert_eq!(variables["events"], Value::scalar(false));
+        assert_eq!(variables["internal"], Value::scalar(true));
+
+        assert!(Options::default().missing_options());
+    }
 }
@@ -0,0 +1,444 @@
+use liquid::{Object, model::ValueView};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::{
+    fs::{read_to_string, write},
+    path::{Path, PathBuf},
+};
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Value};
+
+use crate::functions::HttpFramework;
+
+/// Function that runs an Axum router. `Router` implements the tower
+/// `Service` trait, so `lambda_http` can run it directly.
+const AXUM_TEMPLATE: &str = r#"use axum::{Json, Router, routing::get};
+use lambda_http::{Error, run};
+use serde_json::{Value, json};
+
+async fn index() -> &'static str {
+    "Hello from Axum on AWS Lambda"
+}
+
+/// Health route, check it with `curl http://localhost:9000/health`
+/// while `cargo lambda watch` is running.
+async fn health() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+fn app() -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/health", get(health))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        // disable printing the name of the module in every log line.
+        .with_target(false)
+        // disabling time is handy because CloudWatch will add the ingestion time.
+        .without_time()
+        .init();
+
+    run(app()).await
+}
+"#;
+
+/// Function that runs an Actix Web application. Actix services are not `Send`,
+/// so the application runs in its own thread, and the Lambda handler forwards
+/// every request to it through a channel.
+const ACTIX_TEMPLATE: &str = r#"use actix_web::{App, HttpResponse, Responder, get, http::Method, rt::System, test, web};
+use lambda_http::{Body, Error, Request, Response, run, service_fn};
+use serde_json::json;
+use tokio::sync::{mpsc, oneshot};
+
+/// Request forwarded to the Actix application, with the channel to send the response back.
+type Invocation = (Request, oneshot::Sender<Result<Response<Body>, Error>>);
+
+#[get("/")]
+async fn index() -> impl Responder {
+    HttpResponse::Ok().body("Hello from Actix Web on AWS Lambda")
+}
+
+/// Health route, check it with `curl http://localhost:9000/health`
+/// while `cargo lambda watch` is running.
+#[get("/health")]
+async fn health() -> impl Responder {
+    HttpResponse::Ok().json(json!({ "status": "ok" }))
+}
+
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(index).service(health);
+}
+
+/// Start the Actix application in its own thread.
+fn start_app() -> mpsc::Sender<Invocation> {
+    let (tx, mut rx) = mpsc::channel::<Invocation>(1);
+
+    std::thread::spawn(move || {
+        System::new().block_on(async move {
+            let app = test::init_service(App::new().configure(routes)).await;
+
+            while let Some((event, resp_tx)) = rx.recv().await {
+                let (parts, body) = event.into_parts();
+                let path = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+                let method = Method::from_bytes(parts.method.as_str().as_bytes()).unwrap_or(Method::GET);
+
+                let mut request = test::TestRequest::default()
+                    .method(method)
+                    .uri(path)
+                    .set_payload(body.to_vec());
+                for (name, value) in &parts.headers {
+                    if let Ok(value) = value.to_str() {
+                        request = request.insert_header((name.as_str(), value));
+                    }
+                }
+
+                let response = test::call_service(&app, request.to_request()).await;
+
+                let mut builder = Response::builder().status(response.status().as_u16());
+                for (name, value) in response.headers() {
+                    builder = builder.header(name.as_str(), value.as_bytes());
+                }
+                let body = test::read_body(response).await;
+
+                let _ = resp_tx.send(builder.body(Body::from(body.to_vec())).map_err(Error::from));
+            }
+        });
+    });
+
+    tx
+}
+
+async fn function_handler(app: &mpsc::Sender<Invocation>, event: Request) -> Result<Response<Body>, Error> {
+    let (tx, rx) = oneshot::channel();
+    app.send((event, tx))
+        .await
+        .map_err(|_| "the Actix application stopped")?;
+    rx.await?
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        // disable printing the name of the module in every log line.
+        .with_target(false)
+        // disabling time is handy because CloudWatch will add the ingestion time.
+        .without_time()
+        .init();
+
+    let app = start_app();
+    run(service_fn(|event| function_handler(&app, event))).await
+}
+"#;
+
+/// Function that runs Warp filters. The handler replies to every
+/// request with the filters, and converts the reply into a Lambda response.
+const WARP_TEMPLATE: &str = r#"use lambda_http::{Body, Error, Request, Response, run, service_fn};
+use serde_json::json;
+use warp::{Filter, Rejection, Reply};
+
+fn routes() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let index = warp::path::end()
+        .and(warp::get())
+        .map(|| "Hello from Warp on AWS Lambda");
+
+    // Health route, check it with `curl http://localhost:9000/health`
+    // while `cargo lambda watch` is running.
+    let health = warp::path("health")
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(|| warp::reply::json(&json!({ "status": "ok" })));
+
+    index.or(health)
+}
+
+async fn function_handler(event: Request) -> Result<Response<Body>, Error> {
+    let (parts, body) = event.into_parts();
+    let path = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+    let mut request = warp::test::request()
+        .method(parts.method.as_str())
+        .path(path)
+        .body(body.to_vec());
+    for (name, value) in &parts.headers {
+        request = request.header(name.as_str(), value.as_bytes());
+    }
+
+    let response = request.reply(&routes()).await;
+
+    let mut builder = Response::builder().status(response.status().as_u16());
+    for (name, value) in response.headers() {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    Ok(builder.body(Body::from(response.body().to_vec()))?)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        // disable printing the name of the module in every log line.
+        .with_target(false)
+        // disabling time is handy because CloudWatch will add the ingestion time.
+        .without_time()
+        .init();
+
+    run(service_fn(function_handler)).await
+}
+"#;
+
+/// Instructions to run the function locally, appended to the project's README.
+const README_TEMPLATE: &str = r#"
+## Running locally
+
+This function uses [{{ framework_name }}]({{ framework_url }}) with [lambda_http](https://crates.io/crates/lambda_http).
+Start the function with Cargo Lambda's emulator:
+
+```sh
+cargo lambda watch
+```
+
+Then, send requests to the function's routes:
+
+```sh
+curl http://localhost:9000/
+curl http://localhost:9000/health
+```
+"#;
+
+impl HttpFramework {
+    fn source(&self) -> &'static str {
+        match self {
+            HttpFramework::Axum => AXUM_TEMPLATE,
+            HttpFramework::Actix => ACTIX_TEMPLATE,
+            HttpFramework::Warp => WARP_TEMPLATE,
+        }
+    }
+
+    /// Dependencies to add to the project, besides `lambda_http` and `tokio`.
+    fn dependencies(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            HttpFramework::Axum => &[("axum", "0.8"), ("serde_json", "1")],
+            HttpFramework::Actix => &[("actix-web", "4"), ("serde_json", "1")],
+            HttpFramework::Warp => &[("warp", "0.3"), ("serde_json", "1")],
+        }
+    }
+
+    /// Tokio features that the generated code needs, besides `macros`.
+    fn tokio_features(&self) -> &'static [&'static str] {
+        match self {
+            HttpFramework::Actix => &["sync"],
+            HttpFramework::Axum | HttpFramework::Warp => &[],
+        }
+    }
+
+    fn crate_name(&self) -> &'static str {
+        self.dependencies()[0].0
+    }
+
+    fn display_name(&self) -> &'static str {
+        match self {
+            HttpFramework::Axum => "Axum",
+            HttpFramework::Actix => "Actix Web",
+            HttpFramework::Warp => "Warp",
+        }
+    }
+}
+
+/// Replace the generic HTTP handler in the project with one that uses the web framework,
+/// unless the template already depends on the framework.
+pub(crate) fn create_http_scaffold(
+    path: &Path,
+    framework: &HttpFramework,
+    globals: &Object,
+) -> Result<()> {
+    let manifest_path = path.join("Cargo.toml");
+    let manifest = read_to_string(&manifest_path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to read file: {manifest_path:?}"))?;
+    let mut manifest = manifest.parse::<DocumentMut>().into_diagnostic()?;
+
+    if manifest
+        .get("dependencies")
+        .and_then(|deps| deps.get(framework.crate_name()))
+        .is_some()
+    {
+        tracing::debug!(?framework, "template already includes the web framework");
+        return Ok(());
+    }
+
+    let binary_name = globals
+        .get("binary_name")
+        .and_then(|v| v.as_scalar())
+        .map(|s| s.into_string().to_string());
+    let source_path = path.join(function_source_path(&manifest, binary_name.as_deref()));
+
+    add_dependencies(&mut manifest, framework);
+    write(&manifest_path, manifest.to_string())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to update file: {manifest_path:?}"))?;
+
+    write(&source_path, framework.source())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to create file: {source_path:?}"))?;
+
+    let readme_path = path.join("README.md");
+    let mut readme = read_to_string(&readme_path).unwrap_or_default();
+    readme.push_str(&render_readme(framework)?);
+    write(&readme_path, readme)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to update file: {readme_path:?}"))
+}
+
+/// Path to the source file of the function's binary. Templates that set a
+/// binary name declare it in a `[[bin]]` section, otherwise it's `src/main.rs`.
+fn function_source_path(manifest: &DocumentMut, binary_name: Option<&str>) -> PathBuf {
+    let bin_path = binary_name.and_then(|name| {
+        manifest
+            .get("bin")
+            .and_then(|bins| bins.as_array_of_tables())
+            .and_then(|bins| {
+                bins.iter()
+                    .find(|bin| bin.get("name").and_then(|n| n.as_str()) == Some(name))
+            })
+            .and_then(|bin| bin.get("path"))
+            .and_then(|path| path.as_str())
+    });
+
+    PathBuf::from(bin_path.unwrap_or("src/main.rs"))
+}
+
+fn add_dependencies(manifest: &mut DocumentMut, framework: &HttpFramework) {
+    let Some(deps) = manifest
+        .entry("dependencies")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+    else {
+        return;
+    };
+
+    for (name, version) in framework.dependencies() {
+        if !deps.contains_key(name) {
+            deps.insert(name, toml_edit::value(*version));
+        }
+    }
+
+    if framework.tokio_features().is_empty() {
+        return;
+    }
+
+    let Some(tokio) = deps.get_mut("tokio") else {
+        return;
+    };
+
+    // Convert `tokio = "1"` into `tokio = { version = "1", features = [] }`
+    // before adding the features.
+    if let Some(version) = tokio.as_str().map(String::from) {
+        let mut table = InlineTable::new();
+        table.insert("version", version.into());
+        *tokio = Item::Value(Value::InlineTable(table));
+    }
+
+    let Some(table) = tokio.as_table_like_mut() else {
+        return;
+    };
+
+    let features = table
+        .entry("features")
+        .or_insert(Item::Value(Value::Array(Array::new())));
+    if let Some(features) = features.as_array_mut() {
+        for feature in framework.tokio_features() {
+            if !features.iter().any(|f| f.as_str() == Some(*feature)) {
+                features.push(*feature);
+            }
+        }
+    }
+}
+
+fn render_readme(framework: &HttpFramework) -> Result<String> {
+    let variables = liquid::object!({
+        "framework_name": framework.display_name(),
+        "framework_url": format!("https://crates.io/crates/{}", framework.crate_name()),
+    });
+
+    let parser = liquid::ParserBuilder::with_stdlib()
+        .build()
+        .into_diagnostic()?;
+    let template = parser.parse(README_TEMPLATE).into_diagnostic()?;
+    template.render(&variables).into_diagnostic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"[package]
+name = "http-function"
+
+[dependencies]
+lambda_http = "0.14"
+tokio = { version = "1", features = ["macros"] }
+
+[[bin]]
+name = "http-bin"
+path = "src/bin/http.rs"
+"#;
+
+    #[test]
+    fn test_add_framework_dependencies() {
+        let mut manifest = MANIFEST.parse::<DocumentMut>().unwrap();
+        add_dependencies(&mut manifest, &HttpFramework::Actix);
+
+        let deps = &manifest["dependencies"];
+        assert_eq!(Some("4"), deps["actix-web"].as_str());
+        assert_eq!(Some("1"), deps["serde_json"].as_str());
+
+        let features = deps["tokio"]["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|f| f.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["macros", "sync"], features);
+    }
+
+    #[test]
+    fn test_add_tokio_features_to_version_string() {
+        let mut manifest = "[dependencies]\ntokio = \"1\"\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+        add_dependencies(&mut manifest, &HttpFramework::Actix);
+
+        assert_eq!(
+            "[dependencies]\ntokio = { version = \"1\", features = [\"sync\"] }\nactix-web = \"4\"\nserde_json = \"1\"\n",
+            manifest.to_string()
+        );
+    }
+
+    #[test]
+    fn test_function_source_path() {
+        let manifest = MANIFEST.parse::<DocumentMut>().unwrap();
+        assert_eq!(
+            PathBuf::from("src/bin/http.rs"),
+            function_source_path(&manifest, Some("http-bin"))
+        );
+        assert_eq!(
+            PathBuf::from("src/main.rs"),
+            function_source_path(&manifest, Some("other-bin"))
+        );
+        assert_eq!(
+            PathBuf::from("src/main.rs"),
+            function_source_path(&manifest, None)
+        );
+    }
+
+    #[test]
+    fn test_render_readme() {
+        let readme = render_readme(&HttpFramework::Axum).unwrap();
+        assert!(readme.contains("[Axum](https://crates.io/crates/axum)"));
+        assert!(readme.contains("cargo lambda watch"));
+        assert!(readme.contains("curl http://localhost:9000/health"));
+    }
+}
@@ -1,5 +1,5 @@
 use cargo_lambda_interactive::{
-    Confirm, Text,
+    Confirm, Text, choose_option,
     error::{CustomUserError, InquireError},
     is_stdin_tty,
     validator::{ErrorMessage, Validation},
@@ -8,7 +8,7 @@ use clap::Args;
 use liquid::{Object, model::Value};
 use miette::Result;
 
-use crate::{error::CreateError, template::PROMPT_WITH_OPTIONS_HELP_MESSAGE};
+use crate::{error::CreateError, events::Events, template::PROMPT_WITH_OPTIONS_HELP_MESSAGE};
 
 pub(crate) const DEFAULT_TEMPLATE_URL: &str =
     "https://github.com/cargo-lambda/new-functions-template/archive/refs/heads/main.zip";
@@ -24,8 +24,12 @@ pub(crate) struct Options {
     #[arg(long, conflicts_with = "http")]
     http_feature: Option<HttpFeature>,
 
+    /// Web framework to route the HTTP requests with: axum, actix, or warp
+    #[arg(long)]
+    http_framework: Option<HttpFramework>,
+
     /// Type of AWS event that this function is going to receive, from the aws_lambda_events crate, for example s3::S3Event
-    #[arg(long, conflicts_with_all = ["http", "http_feature"])]
+    #[arg(long, conflicts_with_all = ["http", "http_feature", "http_framework"])]
     event_type: Option<String>,
 }
 
@@ -38,13 +42,28 @@ pub(crate) enum HttpFeature {
     ApigwWebsockets,
 }
 
+#[derive(Clone, Debug, PartialEq, strum_macros::Display, strum_macros::EnumString)]
+#[strum(ascii_case_insensitive, serialize_all = "snake_case")]
+pub(crate) enum HttpFramework {
+    Axum,
+    Actix,
+    Warp,
+}
+
+/// Option to use `lambda_http` without a web framework in the framework prompt.
+const NO_HTTP_FRAMEWORK: &str = "none";
+
 impl Options {
-    pub(crate) fn validate_options(&mut self, no_interactive: bool) -> Result<(), CreateError> {
+    pub(crate) fn validate_options(
+        &mut self,
+        no_interactive: bool,
+        events: &Events,
+    ) -> Result<(), CreateError> {
         if no_interactive {
             return Ok(());
         }
 
-        if self.http_feature.is_some() && !self.http {
+        if (self.http_feature.is_some() || self.http_framework.is_some()) && !self.http {
             self.http = true;
         }
 
@@ -53,7 +72,7 @@ impl Options {
                 return Err(CreateError::MissingFunctionOptions);
             }
 
-            self.ask_template_options()?;
+            self.ask_template_options(events)?;
 
             if self.missing_options() {
                 return Err(CreateError::MissingFunctionOptions);
@@ -67,21 +86,34 @@ impl Options {
         Ok(())
     }
 
-    pub(crate) fn ask_template_options(&mut self) -> Result<(), InquireError> {
+    pub(crate) fn ask_template_options(&mut self, events: &Events) -> Result<(), InquireError> {
         if !self.http {
             self.http = Confirm::new("Is this function an HTTP function?")
                 .with_help_message("type `yes` if the Lambda function is triggered by an API Gateway, Amazon Load Balancer(ALB), or a Lambda URL")
                 .with_default(false)
                 .prompt()?;
+
+            if self.http && self.http_framework.is_none() {
+                let options = vec![NO_HTTP_FRAMEWORK, "axum", "actix", "warp"];
+                let framework = choose_option("Which web framework do you want to use?", options)?;
+                self.http_framework = framework.parse().ok();
+            }
         }
 
         if !self.http {
             let help = format!(
                 "{PROMPT_WITH_OPTIONS_HELP_MESSAGE}.\nLeave this input empty if you want to use a predefined example"
             );
+            let event_types = events.keys().cloned().collect::<Vec<_>>();
+            let event_types_for_validator = event_types.clone();
+
+            let autocomplete = move |input: &str| suggest_event_type(input, &event_types);
+            let validator =
+                move |input: &str| validate_event_type(input, &event_types_for_validator);
+
             let event_type = Text::new("Event type that this function receives")
-                .with_autocomplete(suggest_event_type)
-                .with_validator(validate_event_type)
+                .with_autocomplete(autocomplete)
+                .with_validator(validator)
                 .with_help_message(&help)
                 .prompt()?;
             self.event_type = Some(event_type);
@@ -94,10 +126,18 @@ impl Options {
         &self,
         package_name: &str,
         binary_name: &Option<String>,
+        events: &Events,
     ) -> Result<Object> {
         let use_basic_example = !self.http && !self.has_event_type();
 
-        let (ev_import, ev_feat, ev_type) = self.event_type_triple()?;
+        let (ev_import, ev_feat, ev_type) = self.event_type_triple(events)?;
+        let ev_snippet = self
+            .event_type
+            .as_ref()
+            .and_then(|s| events.get(s))
+            .and_then(|e| e.snippet.clone())
+            .map(Value::scalar)
+            .unwrap_or(Value::Nil);
 
         let fn_name = match binary_name {
             Some(name) if name != package_name => Value::scalar(name.clone()),
@@ -122,20 +162,32 @@ impl Options {
             .map(|v| Value::scalar(v.to_string()))
             .unwrap_or(Value::Nil);
 
+        let http_framework = self
+            .http_framework
+            .as_ref()
+            .map(|v| Value::scalar(v.to_string()))
+            .unwrap_or(Value::Nil);
+
         Ok(liquid::object!({
             "function_name": fn_name,
             "basic_example": use_basic_example,
             "http_function": self.http,
             "http_feature": http_feature,
+            "http_framework": http_framework,
             "event_type": ev_type,
             "event_type_feature": ev_feat,
             "event_type_import": ev_import,
+            "event_type_snippet": ev_snippet,
             "lambda_http_version": lhv,
             "lambda_runtime_version": lrv,
             "aws_lambda_events_version": lev,
         }))
     }
 
+    pub(crate) fn http_framework(&self) -> Option<&HttpFramework> {
+        self.http_framework.as_ref()
+    }
+
     fn missing_options(&self) -> bool {
         !self.http && self.event_type.is_none()
     }
@@ -144,13 +196,24 @@ impl Options {
         matches!(&self.event_type, Some(s) if !s.is_empty())
     }
 
-    fn event_type_triple(&self) -> Result<(Value, Value, Value)> {
+    /// Import path, crate feature, and name of the event type.
+    /// Events that are not in the catalog are expected to be in the aws_lambda_events crate,
+    /// under a feature with the same name as the event's module.
+    fn event_type_triple(&self, events: &Events) -> Result<(Value, Value, Value)> {
         match &self.event_type {
-            Some(s) if s == "serde_json::Value" => Ok((
-                Value::scalar(s.clone()),
-                Value::scalar("serde_json"),
-                Value::scalar("Value"),
-            )),
+            Some(s) if events.contains_key(s) => {
+                let event = &events[s];
+                let import = event
+                    .import
+                    .clone()
+                    .unwrap_or_else(|| format!("aws_lambda_events::event::{s}"));
+                let ev_type = s.rsplit("::").next().unwrap_or(s);
+                Ok((
+                    Value::scalar(import),
+                    Value::scalar(event.feature.clone()),
+                    Value::scalar(ev_type.to_string()),
+                ))
+            }
             Some(s) if !s.is_empty() => {
                 let import = Value::scalar(format!("aws_lambda_events::event::{s}"));
                 match s.rsplitn(2, "::").collect::<Vec<_>>()[..] {
@@ -167,8 +230,8 @@ impl Options {
     }
 }
 
-fn validate_event_type(name: &str) -> Result<Validation, CustomUserError> {
-    match name.is_empty() || crate::events::WELL_KNOWN_EVENTS.contains(&name) {
+fn validate_event_type(name: &str, event_types: &[String]) -> Result<Validation, CustomUserError> {
+    match name.is_empty() || event_types.iter().any(|e| e == name) {
         true => Ok(Validation::Valid),
         false => Ok(Validation::Invalid(ErrorMessage::Custom(format!(
             "invalid event type: {name}"
@@ -176,8 +239,8 @@ fn validate_event_type(name: &str) -> Result<Validation, CustomUserError> {
     }
 }
 
-fn suggest_event_type(text: &str) -> Result<Vec<String>, CustomUserError> {
-    Ok(crate::events::WELL_KNOWN_EVENTS
+fn suggest_event_type(text: &str, event_types: &[String]) -> Result<Vec<String>, CustomUserError> {
+    Ok(event_types
         .iter()
         .filter_map(|s| {
             if s.starts_with(text) {
@@ -192,21 +255,45 @@ fn suggest_event_type(text: &str) -> Result<Vec<String>, CustomUserError> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use liquid::model::ValueView;
 
     #[test]
     fn test_http_features_to_string() {
         assert_eq!("apigw_http", HttpFeature::ApigwHttp.to_string().as_str());
     }
 
+    #[test]
+    fn test_http_framework_implies_http() {
+        let mut opt = Options {
+            http: false,
+            http_feature: None,
+            http_framework: Some("Axum".parse().unwrap()),
+            event_type: None,
+        };
+
+        let events = Events::default();
+        opt.validate_options(false, &events).unwrap();
+        assert!(opt.http);
+
+        let variables = opt.variables("http-function", &None, &events).unwrap();
+        assert_eq!(
+            Some(&Value::scalar("axum")),
+            variables.get("http_framework")
+        );
+    }
+
     #[test]
     fn test_json_value_event_type() {
         let opt = Options {
             http: false,
             http_feature: None,
+            http_framework: None,
             event_type: Some("serde_json::Value".to_string()),
         };
 
-        let (imp, module, kind) = opt.event_type_triple().unwrap();
+        let (imp, module, kind) = opt
+            .event_type_triple(crate::events::well_known_events())
+            .unwrap();
         assert_eq!(Value::scalar("serde_json::Value"), imp);
         assert_eq!(Value::scalar("serde_json"), module);
         assert_eq!(Value::scalar("Value"), kind);
@@ -217,10 +304,13 @@ mod test {
         let opt = Options {
             http: false,
             http_feature: None,
+            http_framework: None,
             event_type: Some("sns::SnsEvent".to_string()),
         };
 
-        let (imp, module, kind) = opt.event_type_triple().unwrap();
+        let (imp, module, kind) = opt
+            .event_type_triple(crate::events::well_known_events())
+            .unwrap();
         assert_eq!(
             Value::scalar("aws_lambda_events::event::sns::SnsEvent"),
             imp
@@ -234,19 +324,56 @@ mod test {
         let opt = Options {
             http: false,
             http_feature: None,
+            http_framework: None,
             event_type: Some(
                 "cloudformation::provider::CloudFormationCustomResourceRequest".to_string(),
             ),
         };
 
-        let (imp, module, kind) = opt.event_type_triple().unwrap();
+        let (imp, module, kind) = opt
+            .event_type_triple(crate::events::well_known_events())
+            .unwrap();
         assert_eq!(
             Value::scalar(
                 "aws_lambda_events::event::cloudformation::provider::CloudFormationCustomResourceRequest"
             ),
             imp
         );
-        assert_eq!(Value::scalar("cloudformation::provider"), module);
+        assert_eq!(Value::scalar("cloudformation"), module);
         assert_eq!(Value::scalar("CloudFormationCustomResourceRequest"), kind);
     }
+
+    #[test]
+    fn test_unknown_event_type() {
+        let opt = Options {
+            http: false,
+            http_feature: None,
+            http_framework: None,
+            event_type: Some("new_service::NewServiceEvent".to_string()),
+        };
+
+        let (imp, module, kind) = opt.event_type_triple(&Events::default()).unwrap();
+        assert_eq!(
+            Value::scalar("aws_lambda_events::event::new_service::NewServiceEvent"),
+            imp
+        );
+        assert_eq!(Value::scalar("new_service"), module);
+        assert_eq!(Value::scalar("NewServiceEvent"), kind);
+    }
+
+    #[test]
+    fn test_event_type_snippet() {
+        let opt = Options {
+            http: false,
+            http_feature: None,
+            http_framework: None,
+            event_type: Some("sqs::SqsEvent".to_string()),
+        };
+
+        let variables = opt
+            .variables("sqs-function", &None, crate::events::well_known_events())
+            .unwrap();
+        let snippet = variables.get("event_type_snippet").unwrap().to_kstr();
+        assert!(snippet.contains("record.message_id"));
+    }
 }
@@ -0,0 +1,213 @@
+use liquid::{Object, ParserBuilder, model::ValueView};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::{
+    fs::{create_dir_all, read_to_string, write},
+    path::Path,
+};
+use toml_edit::{Array, DocumentMut, InlineTable, Value};
+
+pub(crate) const INTEGRATION_TESTS_PATH: &str = "tests/integration.rs";
+
+/// Template for the integration tests. The tests start the function with
+/// the emulator in `cargo-lambda-emulator`, invoke it with a sample event,
+/// and assert that the invocation returns a response.
+const INTEGRATION_TESTS_TEMPLATE: &str = r##"//! Integration tests that run the function locally with the Cargo Lambda emulator.
+//!
+//! Run them with `cargo test --test integration`.
+
+use cargo_lambda_emulator::Emulator;
+use serde_json::Value;
+
+const FUNCTION_NAME: &str = "{{ function_name }}";
+const SAMPLE_EVENT: &str = r#"{{ sample_event }}"#;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invoke_function() {
+    let emulator = Emulator::builder()
+        .bin(FUNCTION_NAME)
+        .spawn()
+        .await
+        .expect("failed to start the emulator");
+
+    let event: Value = serde_json::from_str(SAMPLE_EVENT).expect("invalid sample event");
+    let response = emulator.invoke(FUNCTION_NAME, &event).await;
+    emulator
+        .shutdown()
+        .await
+        .expect("failed to stop the emulator");
+
+    let response = response.expect("invocation failed");
+{% if http_function %}    assert_eq!(response["statusCode"], 200, "unexpected response: {response}");
+{% else %}    assert!(!response.is_null(), "the function returned an empty response");
+{% endif %}}
+"##;
+
+/// Crates that the integration tests use, with the features that they need.
+const DEV_DEPENDENCIES: &[(&str, &str, &[&str])] = &[
+    ("cargo-lambda-emulator", env!("CARGO_PKG_VERSION"), &[]),
+    ("serde_json", "1", &[]),
+    ("tokio", "1", &["macros", "rt-multi-thread"]),
+];
+
+/// Write the integration tests in the project, and add the crates that they use
+/// to the project's dev-dependencies, unless the template already includes them.
+pub(crate) fn create_integration_tests(
+    path: &Path,
+    package_name: &str,
+    globals: &Object,
+) -> Result<()> {
+    let tests_path = path.join(INTEGRATION_TESTS_PATH);
+    if tests_path.exists() {
+        tracing::debug!(?tests_path, "template already includes integration tests");
+        return Ok(());
+    }
+
+    let content = render_integration_tests(package_name, globals)?;
+
+    if let Some(parent) = tests_path.parent() {
+        create_dir_all(parent).into_diagnostic()?;
+    }
+    write(&tests_path, content)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to create file: {tests_path:?}"))?;
+
+    let manifest_path = path.join("Cargo.toml");
+    let manifest = read_to_string(&manifest_path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to read file: {manifest_path:?}"))?;
+    let mut manifest = manifest.parse::<DocumentMut>().into_diagnostic()?;
+
+    add_dev_dependencies(&mut manifest);
+    write(&manifest_path, manifest.to_string())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to update file: {manifest_path:?}"))
+}
+
+fn add_dev_dependencies(manifest: &mut DocumentMut) {
+    let Some(deps) = manifest
+        .entry("dev-dependencies")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+    else {
+        return;
+    };
+
+    for (name, version, features) in DEV_DEPENDENCIES {
+        if deps.contains_key(name) {
+            continue;
+        }
+
+        if features.is_empty() {
+            deps.insert(name, toml_edit::value(*version));
+        } else {
+            let mut table = InlineTable::new();
+            table.insert("version", (*version).into());
+            table.insert(
+                "features",
+                Value::Array(Array::from_iter(features.iter().copied())),
+            );
+            deps.insert(name, toml_edit::value(table));
+        }
+    }
+}
+
+fn render_integration_tests(package_name: &str, globals: &Object) -> Result<String> {
+    let function_name = globals
+        .get("binary_name")
+        .and_then(|v| v.as_scalar())
+        .map(|s| s.into_string().to_string())
+        .unwrap_or_else(|| package_name.to_string());
+
+    let http_function = globals
+        .get("http_function")
+        .and_then(|v| v.as_scalar())
+        .and_then(|s| s.to_bool())
+        .unwrap_or_default();
+
+    let basic_example = globals
+        .get("basic_example")
+        .and_then(|v| v.as_scalar())
+        .and_then(|s| s.to_bool())
+        .unwrap_or_default();
+
+    let sample_event = sample_event(http_function, basic_example);
+
+    let variables = liquid::object!({
+        "function_name": function_name,
+        "http_function": http_function,
+        "sample_event": sample_event,
+    });
+
+    let parser = ParserBuilder::with_stdlib().build().into_diagnostic()?;
+    let template = parser.parse(INTEGRATION_TESTS_TEMPLATE).into_diagnostic()?;
+    template.render(&variables).into_diagnostic()
+}
+
+/// Event to send in the integration tests.
+/// Functions with custom event types receive an empty object,
+/// users are expected to replace it with an event that makes sense for them.
+fn sample_event(http_function: bool, basic_example: bool) -> &'static str {
+    if http_function {
+        r#"{"version":"2.0","rawPath":"/","rawQueryString":"","headers":{},"requestContext":{"http":{"method":"GET","path":"/","protocol":"HTTP/1.1","sourceIp":"127.0.0.1","userAgent":"cargo-lambda"}},"isBase64Encoded":false}"#
+    } else if basic_example {
+        r#"{"command":"hello"}"#
+    } else {
+        "{}"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liquid::model::Value;
+
+    #[test]
+    fn test_render_http_integration_tests() {
+        let globals = liquid::object!({
+            "binary_name": Value::Nil,
+            "http_function": true,
+            "basic_example": false,
+        });
+
+        let content = render_integration_tests("http-function", &globals).unwrap();
+        assert!(content.contains(r#"const FUNCTION_NAME: &str = "http-function";"#));
+        assert!(content.contains(r#""rawPath":"/""#));
+        assert!(content.contains(r#"assert_eq!(response["statusCode"], 200"#));
+    }
+
+    #[test]
+    fn test_render_basic_integration_tests() {
+        let globals = liquid::object!({
+            "binary_name": "basic-bin",
+            "http_function": false,
+            "basic_example": true,
+        });
+
+        let content = render_integration_tests("basic-function", &globals).unwrap();
+        assert!(content.contains(r#"const FUNCTION_NAME: &str = "basic-bin";"#));
+        assert!(content.contains(r##"const SAMPLE_EVENT: &str = r#"{"command":"hello"}"#;"##));
+        assert!(!content.contains("statusCode"));
+    }
+
+    #[test]
+    fn test_add_dev_dependencies() {
+        let mut manifest = r#"[package]
+name = "basic-function"
+
+[dev-dependencies]
+tokio = "1.40"
+"#
+        .parse::<DocumentMut>()
+        .unwrap();
+
+        add_dev_dependencies(&mut manifest);
+
+        let deps = &manifest["dev-dependencies"];
+        assert_eq!(
+            Some(env!("CARGO_PKG_VERSION")),
+            deps["cargo-lambda-emulator"].as_str()
+        );
+        assert_eq!(Some("1"), deps["serde_json"].as_str());
+        assert_eq!(Some("1.40"), deps["tokio"].as_str());
+    }
+}
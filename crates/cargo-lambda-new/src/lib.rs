@@ -1,11 +1,13 @@
 use cargo_lambda_interactive::{
     command::new_command, is_user_cancellation_error, progress::Progress,
 };
-use cargo_lambda_metadata::fs::{copy_and_replace, copy_without_replace};
+use cargo_lambda_metadata::{
+    fs::{copy_and_replace, copy_without_replace},
+    runtime::{latest_provided_runtime, provided_runtime_names},
+};
 use clap::Args;
 use liquid::{Object, Parser, ParserBuilder, model::Value};
 use miette::{IntoDiagnostic, Result, WrapErr};
-use regex::Regex;
 use std::{
     collections::HashMap,
     env,
@@ -16,16 +18,26 @@ use std::{
 use template::{TemplateRoot, config::TemplateConfig};
 use walkdir::WalkDir;
 
-use crate::template::TemplateSource;
+use crate::{events::Events, template::TemplateSource};
 
+mod dependencies;
 mod error;
 use error::CreateError;
 
 mod events;
+mod existing_handler;
 mod extensions;
+mod frameworks;
 mod functions;
+mod integration_tests;
+mod names;
 mod template;
 
+/// Types of the well known events that new functions can receive, like `s3::S3Event`.
+pub fn well_known_event_types() -> impl Iterator<Item = &'static str> {
+    events::well_known_events().keys().map(String::as_str)
+}
+
 #[derive(Args, Clone, Debug)]
 #[group(skip)]
 struct Config {
@@ -33,6 +45,10 @@ struct Config {
     #[arg(long)]
     template: Option<String>,
 
+    /// SHA256 checksum that ZIP templates must match before they're extracted
+    #[arg(long, value_name = "CHECKSUM", requires = "template")]
+    template_sha256: Option<String>,
+
     /// Start a project for a Lambda Extension
     #[arg(long)]
     extension: bool,
@@ -68,6 +84,19 @@ struct Config {
     /// List of files to ignore from the template
     #[arg(long)]
     ignore_file: Option<Vec<PathBuf>>,
+
+    /// Generate integration tests that invoke the function with `cargo lambda watch`
+    #[arg(long, conflicts_with = "extension")]
+    with_integration_tests: bool,
+
+    /// Print the event types that functions can receive, and the crate feature that they require
+    #[arg(long, conflicts_with = "extension")]
+    list_events: bool,
+
+    /// Create the project around an existing function handler, instead of a template.
+    /// The handler is copied into the project, and its dependencies are inferred from the crates that it uses
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["extension", "template", "list_events", "function-opts"])]
+    from_existing_handler: Option<PathBuf>,
 }
 
 #[derive(Args, Clone, Debug)]
@@ -90,6 +119,10 @@ pub struct Init {
 impl Init {
     #[tracing::instrument(skip(self), target = "cargo_lambda")]
     pub async fn run(&mut self) -> Result<()> {
+        if self.config.list_events {
+            return list_events(&self.config).await;
+        }
+
         if !self.path.is_dir() {
             Err(CreateError::NotADirectoryPath(self.path.to_path_buf()))?;
         }
@@ -105,8 +138,9 @@ impl Init {
             .as_deref()
             .or_else(|| path.file_name().and_then(|s| s.to_str()))
             .ok_or_else(|| miette::miette!("invalid package name"))?;
+        let name = names::checked_name(name, self.config.no_interactive)?;
 
-        new_project(name, &path, &mut self.config, false).await
+        new_project(&name, &path, &mut self.config, false).await
     }
 }
 
@@ -120,14 +154,23 @@ pub struct New {
     config: Config,
 
     /// Name of the Rust package to create
-    #[arg()]
-    name: String,
+    #[arg(required_unless_present = "list_events")]
+    name: Option<String>,
 }
 
 impl New {
     #[tracing::instrument(skip(self), target = "cargo_lambda")]
     pub async fn run(&mut self) -> Result<()> {
-        new_project(&self.name, &self.name, &mut self.config, true).await
+        if self.config.list_events {
+            return list_events(&self.config).await;
+        }
+
+        let name = self
+            .name
+            .as_deref()
+            .ok_or_else(|| miette::miette!("missing package name"))?;
+        let name = names::checked_name(name, self.config.no_interactive)?;
+        new_project(&name, &name, &mut self.config, true).await
     }
 }
 
@@ -140,9 +183,13 @@ async fn new_project<T: AsRef<Path> + Debug>(
 ) -> Result<()> {
     tracing::trace!(name, ?path, ?config, "creating new project");
 
-    validate_name(name)?;
-    if let Some(name) = &config.bin_name {
-        validate_name(name)?;
+    if let Some(bin_name) = &config.bin_name {
+        config.bin_name = Some(names::checked_name(bin_name, config.no_interactive)?);
+    }
+
+    if let Some(handler) = &config.from_existing_handler {
+        existing_handler::create_project(path.as_ref(), name, handler, config.bin_name.as_deref())?;
+        return open_project(path.as_ref(), config).await;
     }
 
     let template = get_template(config).await?;
@@ -150,23 +197,26 @@ async fn new_project<T: AsRef<Path> + Debug>(
 
     let template_config = template::config::parse_template_config(template.config_path())?;
     let ignore_default_prompts = template_config.disable_default_prompts || config.no_interactive;
+    let events = events::supported_events(&template_config.events);
 
-    if config.extension {
-        config.extension_options.validate_options()?;
+    let validation = if config.extension {
+        config
+            .extension_options
+            .validate_options(ignore_default_prompts)
     } else {
-        match config
+        config
             .function_options
-            .validate_options(ignore_default_prompts)
-        {
-            Err(CreateError::UnexpectedInput(err)) if is_user_cancellation_error(&err) => {
-                return Ok(());
-            }
-            Err(err) => return Err(err.into()),
-            Ok(()) => {}
+            .validate_options(ignore_default_prompts, &events)
+    };
+    match validation {
+        Err(CreateError::UnexpectedInput(err)) if is_user_cancellation_error(&err) => {
+            return Ok(());
         }
+        Err(err) => return Err(err.into()),
+        Ok(()) => {}
     }
 
-    let globals = build_template_variables(config, &template_config, name)?;
+    let globals = build_template_variables(config, &template_config, &events, name)?;
     let render_files = build_render_files(config, &template_config);
     let ignore_files = build_ignore_files(config, &template_config);
 
@@ -180,17 +230,46 @@ async fn new_project<T: AsRef<Path> + Debug>(
         replace,
     )
     .await?;
+
+    let http_framework = config
+        .function_options
+        .http_framework()
+        .filter(|_| !config.extension);
+    if let Some(framework) = http_framework {
+        frameworks::create_http_scaffold(path.as_ref(), framework, &globals)?;
+    }
+
+    if config.with_integration_tests {
+        integration_tests::create_integration_tests(path.as_ref(), name, &globals)?;
+    }
+
+    open_project(path.as_ref(), config).await
+}
+
+async fn open_project(path: &Path, config: &Config) -> Result<()> {
     if config.open {
-        let path_ref = path.as_ref();
-        let path_str = path_ref
-            .to_str()
-            .ok_or_else(|| CreateError::NotADirectoryPath(path_ref.to_path_buf()))?;
-        open_code_editor(path_str).await
+        open_code_editor(path).await
     } else {
         Ok(())
     }
 }
 
+/// Print the events that function templates support. When the command
+/// uses a custom template, the list includes the events that the template defines.
+async fn list_events(config: &Config) -> Result<()> {
+    let template_events = match &config.template {
+        Some(_) => {
+            let template = get_template(config).await?;
+            template.cleanup();
+            template::config::parse_template_config(template.config_path())?.events
+        }
+        None => Events::default(),
+    };
+
+    events::print_events(&events::supported_events(&template_events));
+    Ok(())
+}
+
 async fn get_template(config: &Config) -> Result<TemplateRoot> {
     let progress = Progress::start("downloading template");
 
@@ -203,7 +282,7 @@ async fn get_template(config: &Config) -> Result<TemplateRoot> {
     let template_source = TemplateSource::try_from(template_option);
     match template_source {
         Ok(ts) => {
-            let result = ts.expand().await;
+            let result = ts.expand(config.template_sha256.as_deref()).await;
             progress.finish_and_clear();
             result
         }
@@ -297,6 +376,12 @@ async fn create_project<T: AsRef<Path> + Debug>(
         }
     }
 
+    dependencies::add_template_dependencies(
+        &render_path.join("Cargo.toml"),
+        &template_config.dependencies,
+        globals,
+    )?;
+
     let res = if replace {
         copy_and_replace(render_path, &path)
     } else {
@@ -307,29 +392,18 @@ async fn create_project<T: AsRef<Path> + Debug>(
         .wrap_err_with(|| format!("failed to create package: template {render_path:?} to {path:?}"))
 }
 
-pub(crate) fn validate_name(name: &str) -> Result<()> {
-    // TODO(david): use a more extensive verification.
-    // See what Cargo does in https://github.com/rust-lang/cargo/blob/42696ae234dfb7b23c9638ad118373826c784c60/src/cargo/util/restricted_names.rs
-    let valid_ident = Regex::new(r"^([a-zA-Z][a-zA-Z0-9_-]+)$").into_diagnostic()?;
-
-    match valid_ident.is_match(name) {
-        true => Ok(()),
-        false => Err(CreateError::InvalidPackageName(name.to_string()).into()),
-    }
-}
-
-async fn open_code_editor(path: &str) -> Result<()> {
+async fn open_code_editor(path: &Path) -> Result<()> {
     let editor = env::var("EDITOR").unwrap_or_default();
     let editor = editor.trim();
     if editor.is_empty() {
-        return Err(CreateError::InvalidEditor(path.into()).into());
+        return Err(CreateError::InvalidEditor(path.to_path_buf()).into());
     }
 
     let mut child = new_command(editor)
-        .args([path])
+        .arg(path)
         .spawn()
         .into_diagnostic()
-        .wrap_err_with(|| format!("Failed to run `{editor} {path}`"))?;
+        .wrap_err_with(|| format!("Failed to run `{editor} {}`", path.display()))?;
 
     child
         .wait()
@@ -361,17 +435,32 @@ fn render_variables(config: &Config) -> Object {
 fn build_template_variables(
     config: &Config,
     template_config: &TemplateConfig,
+    events: &Events,
     name: &str,
 ) -> Result<Object> {
     let mut variables = liquid::object!({
         "project_name": name,
         "binary_name": config.bin_name,
+        "runtime": latest_provided_runtime(),
     });
+    variables.insert(
+        "provided_runtimes".into(),
+        Value::Array(
+            provided_runtime_names()
+                .into_iter()
+                .map(Value::scalar)
+                .collect(),
+        ),
+    );
 
     if config.extension {
         variables.extend(config.extension_options.variables()?);
     } else {
-        variables.extend(config.function_options.variables(name, &config.bin_name)?);
+        variables.extend(
+            config
+                .function_options
+                .variables(name, &config.bin_name, events)?,
+        );
     };
 
     if !template_config.prompts.is_empty() {
@@ -428,25 +517,7 @@ fn should_render_file(
                 .and_then(|s| template_config.render_conditional_files.get(s))
         });
 
-    if let Some(condition) = condition {
-        let Some(variable) = variables.get::<str>(&condition.var) else {
-            return false;
-        };
-
-        if let Some(condition_value) = &condition.r#match {
-            if condition_value.to_value() == *variable {
-                return true;
-            }
-        }
-
-        if let Some(condition_value) = &condition.not_match {
-            if condition_value.to_value() != *variable {
-                return true;
-            }
-        }
-    }
-
-    false
+    condition.is_some_and(|condition| condition.matches(variables))
 }
 
 fn should_ignore_file(
@@ -476,39 +547,29 @@ fn should_ignore_file(
                 .and_then(|s| template_config.ignore_conditional_files.get(s))
         });
 
-    if let Some(condition) = condition {
-        let Some(variable) = variables.get::<str>(&condition.var) else {
-            return false;
-        };
-
-        if let Some(condition_value) = &condition.r#match {
-            if condition_value.to_value() == *variable {
-                return true;
-            }
-        }
-
-        if let Some(condition_value) = &condition.not_match {
-            if condition_value.to_value() != *variable {
-                return true;
-            }
-        }
-    }
-
-    false
+    condition.is_some_and(|condition| condition.matches(variables))
 }
 
+/// Render the variables in the names of the path's components. Components that
+/// are not valid UTF-8, like the ones in the user's directories, are kept as they are.
 fn render_path_with_variables(path: &Path, parser: &Parser, variables: &Object) -> Option<PathBuf> {
     let re = regex::Regex::new(r"\{\{[^/]*\}\}").ok()?;
 
-    let path_str = path.to_string_lossy();
-    if !re.is_match(&path_str) {
-        return None;
+    let mut rendered = false;
+    let mut new_path = PathBuf::new();
+    for component in path.components() {
+        let name = component.as_os_str();
+        match name.to_str() {
+            Some(name) if re.is_match(name) => {
+                let template = parser.parse(name).ok()?;
+                new_path.push(template.render(&variables).ok()?);
+                rendered = true;
+            }
+            _ => new_path.push(name),
+        }
     }
 
-    let template = parser.parse(&path_str).ok()?;
-    let path_str = template.render(&variables).ok()?;
-
-    Some(PathBuf::from(path_str))
+    rendered.then_some(new_path)
 }
 
 #[cfg(target_os = "windows")]
@@ -644,6 +705,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_render_path_with_variables_in_unicode_directory() {
+        let parser = ParserBuilder::with_stdlib().build().unwrap();
+        let variables = liquid::object!({"project_name": "basic-lambda"});
+
+        let dir = Path::new("home").join("José Pérez").join("proyectos");
+        assert_eq!(
+            Some(dir.join("basic-lambda").join("src")),
+            render_path_with_variables(
+                &dir.join("{{project_name}}").join("src"),
+                &parser,
+                &variables
+            )
+        );
+        assert_eq!(None, render_path_with_variables(&dir, &parser, &variables));
+
+        #[cfg(unix)]
+        {
+            use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+            let dir = Path::new(OsStr::from_bytes(b"/home/jos\xe9"));
+            assert_eq!(
+                Some(dir.join("basic-lambda")),
+                render_path_with_variables(&dir.join("{{project_name}}"), &parser, &variables)
+            );
+        }
+    }
+
     #[test]
     fn test_should_ignore_file() {
         #[cfg(not(target_os = "windows"))]
@@ -0,0 +1,199 @@
+use cargo_lambda_interactive::{Confirm, is_stdin_tty};
+use miette::Result;
+
+use crate::error::CreateError;
+
+/// Maximum length of package names that crates.io accepts.
+const MAX_NAME_LEN: usize = 64;
+
+/// Rust keywords, including the ones reserved for future use.
+/// Cargo doesn't allow packages named after them.
+const KEYWORDS: &[&str] = &[
+    "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+    "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Directories that Cargo creates in the target directory.
+const ARTIFACT_NAMES: &[&str] = &["build", "deps", "examples", "incremental"];
+
+/// File names that Windows reserves for devices, in any case.
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "aux", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9", "con", "lpt1",
+    "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9", "nul", "prn",
+];
+
+/// Check that Cargo accepts the name for a package or a binary.
+/// It returns the reason why the name is not valid.
+pub(crate) fn name_error(name: &str) -> Option<String> {
+    let Some(first) = name.chars().next() else {
+        return Some("the name cannot be empty".into());
+    };
+
+    if first.is_ascii_digit() {
+        return Some("the name cannot start with a digit".into());
+    }
+
+    if !(first.is_ascii_alphabetic() || first == '_') {
+        return Some(format!(
+            "the first character must be a letter or `_`, found `{first}`"
+        ));
+    }
+
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        return Some(format!(
+            "the name can only contain letters, numbers, `-`, and `_`, found `{c}`"
+        ));
+    }
+
+    if name.len() > MAX_NAME_LEN {
+        return Some(format!(
+            "the name cannot be longer than {MAX_NAME_LEN} characters"
+        ));
+    }
+
+    if KEYWORDS.contains(&name) {
+        return Some("the name is a Rust keyword".into());
+    }
+
+    if name == "test" {
+        return Some("the name conflicts with Rust's built-in test library".into());
+    }
+
+    if ARTIFACT_NAMES.contains(&name) {
+        return Some("the name conflicts with Cargo's build directories".into());
+    }
+
+    if WINDOWS_RESERVED_NAMES.contains(&name.to_ascii_lowercase().as_str()) {
+        return Some("the name is reserved by Windows".into());
+    }
+
+    None
+}
+
+/// Valid name similar to the one given, for names that Cargo doesn't accept.
+pub(crate) fn suggest_name(name: &str) -> Option<String> {
+    let mut suggestion = String::with_capacity(name.len());
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            suggestion.push(c);
+        } else if !suggestion.is_empty() && !suggestion.ends_with('-') {
+            suggestion.push('-');
+        }
+    }
+
+    let mut suggestion = suggestion.trim_end_matches('-').to_string();
+    if suggestion.is_empty() {
+        return None;
+    }
+
+    if suggestion.starts_with(|c: char| c.is_ascii_digit()) {
+        suggestion.insert_str(0, "lambda-");
+    }
+
+    let reserved = |name: &str| {
+        KEYWORDS.contains(&name)
+            || name == "test"
+            || ARTIFACT_NAMES.contains(&name)
+            || WINDOWS_RESERVED_NAMES.contains(&name.to_ascii_lowercase().as_str())
+    };
+    if reserved(&suggestion) {
+        suggestion.push_str("-lambda");
+    }
+
+    suggestion.truncate(MAX_NAME_LEN);
+    let suggestion = suggestion.trim_end_matches('-').to_string();
+
+    (name_error(&suggestion).is_none()).then_some(suggestion)
+}
+
+/// Check that the name is valid. When it's not, ask whether to use
+/// a valid name instead, if the command runs interactively.
+pub(crate) fn checked_name(name: &str, no_interactive: bool) -> Result<String> {
+    let Some(reason) = name_error(name) else {
+        return Ok(name.to_string());
+    };
+
+    let suggestion = suggest_name(name);
+    if let Some(suggestion) = &suggestion
+        && !no_interactive
+        && is_stdin_tty()
+    {
+        let accept = Confirm::new(&format!(
+            "`{name}` is not a valid name, {reason}. Do you want to use `{suggestion}` instead?"
+        ))
+        .with_default(true)
+        .prompt()
+        .unwrap_or_default();
+        if accept {
+            return Ok(suggestion.clone());
+        }
+    }
+
+    Err(CreateError::InvalidPackageName {
+        name: name.to_string(),
+        reason,
+        help: suggestion.map(|s| format!("try `{s}` instead")),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_error() {
+        for name in ["basic-lambda", "a", "_private", "Uploads_v2", "fn-handler"] {
+            assert_eq!(None, name_error(name), "{name} should be valid");
+        }
+
+        for (name, reason) in [
+            ("", "empty"),
+            ("1password", "start with a digit"),
+            ("-lambda", "first character"),
+            ("my lambda", "found ` `"),
+            ("función", "found `ó`"),
+            ("fn", "keyword"),
+            ("Self", "keyword"),
+            ("test", "test library"),
+            ("deps", "build directories"),
+            ("CON", "Windows"),
+            ("lpt1", "Windows"),
+        ] {
+            let error = name_error(name).unwrap_or_default();
+            assert!(error.contains(reason), "{name}: {error}");
+        }
+
+        assert!(name_error(&"a".repeat(65)).is_some());
+    }
+
+    #[test]
+    fn test_suggest_name() {
+        assert_eq!(Some("my-lambda".into()), suggest_name("my lambda"));
+        assert_eq!(Some("my-lambda".into()), suggest_name("my.lambda!"));
+        assert_eq!(Some("lambda-1password".into()), suggest_name("1password"));
+        assert_eq!(Some("lambda".into()), suggest_name("-lambda"));
+        assert_eq!(Some("fn-lambda".into()), suggest_name("fn"));
+        assert_eq!(Some("con-lambda".into()), suggest_name("con"));
+        assert_eq!(Some("a".repeat(64)), suggest_name(&"a".repeat(80)));
+        assert_eq!(None, suggest_name("!!!"));
+    }
+
+    #[test]
+    fn test_checked_name() {
+        assert_eq!("basic-lambda", checked_name("basic-lambda", true).unwrap());
+
+        let error = checked_name("fn", true).unwrap_err();
+        let diagnostic = error.downcast_ref::<CreateError>().unwrap();
+        assert!(matches!(
+            diagnostic,
+            CreateError::InvalidPackageName { help: Some(help), .. } if help == "try `fn-lambda` instead"
+        ));
+    }
+}
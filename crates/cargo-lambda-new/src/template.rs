@@ -1,15 +1,19 @@
 use std::{
     fmt,
-    fs::{File, remove_dir_all, remove_file},
+    fs::{File, read, remove_dir_all, remove_file},
     io::{Cursor, copy},
     path::{Path, PathBuf},
 };
 
+use cargo_lambda_metadata::net;
 use gix::refs::PartialName;
 use miette::{Context, IntoDiagnostic, Result};
+use sha2::{Digest, Sha256};
 use tempfile::{TempDir, tempdir};
 use zip::ZipArchive;
 
+use crate::error::CreateError;
+
 pub(crate) const PROMPT_WITH_OPTIONS_HELP_MESSAGE: &str =
     "use arrows (↑↓) to move, tab to auto-complete, enter to submit";
 
@@ -62,15 +66,24 @@ pub(crate) enum TemplateSource {
 }
 
 impl TemplateSource {
+    /// Expand the template into a directory. When a checksum is given,
+    /// ZIP templates are verified before they're extracted.
     #[tracing::instrument(target = "cargo_lambda")]
-    pub(crate) async fn expand(&self) -> Result<TemplateRoot> {
+    pub(crate) async fn expand(&self, checksum: Option<&str>) -> Result<TemplateRoot> {
         tracing::debug!("expanding template");
 
+        if checksum.is_some() && !matches!(self, Self::RemoteZip(_) | Self::LocalZip(_)) {
+            return Err(CreateError::UnverifiableTemplate.into());
+        }
+
         let base = match self {
             Self::RemoteZip(url) => {
                 let tmp_dir = tempdir().into_diagnostic()?;
 
                 let local_zip = download_zip_template(url, tmp_dir.path()).await?;
+                if let Some(checksum) = checksum {
+                    verify_checksum(&local_zip, url, checksum)?;
+                }
                 unzip_template(&local_zip, tmp_dir.path())?;
 
                 let base = adjust_remote_zip_base(url, tmp_dir.path());
@@ -80,6 +93,10 @@ impl TemplateSource {
             Self::LocalZip(path) => {
                 let tmp_dir = tempdir().into_diagnostic()?;
 
+                if let Some(checksum) = checksum {
+                    verify_checksum(path, &path.to_string_lossy(), checksum)?;
+                }
+
                 unzip_template(path, tmp_dir.path())?;
                 TemplateRoot::TempDir((tmp_dir, None))
             }
@@ -183,7 +200,7 @@ impl TemplateRoot {
 async fn download_zip_template(url: &str, template_root: &Path) -> Result<PathBuf> {
     tracing::debug!("downloading template");
 
-    let response = reqwest::get(url).await.into_diagnostic()?;
+    let response = net::client()?.get(url).send().await.into_diagnostic()?;
     if response.status() != reqwest::StatusCode::OK {
         return Err(miette::miette!(
             "error downloading template from {} - {}",
@@ -203,6 +220,27 @@ async fn download_zip_template(url: &str, template_root: &Path) -> Result<PathBu
     Ok(tmp_file)
 }
 
+/// Check that the SHA256 checksum of a ZIP template matches the one that the user pinned.
+fn verify_checksum(file: &Path, template: &str, expected: &str) -> Result<()> {
+    let data = read(file)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to read ZIP file: {file:?}"))?;
+    let actual = format!("{:x}", Sha256::digest(&data));
+
+    let expected = expected.trim();
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(CreateError::TemplateChecksumMismatch {
+            template: template.to_string(),
+            expected: expected.to_string(),
+            actual,
+        }
+        .into());
+    }
+
+    tracing::debug!(checksum = actual, "verified template checksum");
+    Ok(())
+}
+
 #[tracing::instrument(target = "cargo_lambda")]
 fn unzip_template(file: &Path, path: &Path) -> Result<PathBuf> {
     tracing::debug!("extracting template from ZIP file");
@@ -396,6 +434,34 @@ mod test {
         assert!(!is_remote_zip_file("c:\\path\\to\\file.zip"));
     }
 
+    #[tokio::test]
+    async fn test_expand_with_checksum() {
+        let zip = "../../tests/templates/function-template.zip";
+        let data = std::fs::read(zip).unwrap();
+        let checksum = format!("{:x}", Sha256::digest(&data));
+
+        let source = TemplateSource::LocalZip(zip.into());
+        let root = source.expand(Some(&checksum)).await.unwrap();
+        assert!(root.config_path().exists());
+        root.cleanup();
+
+        let Err(err) = source.expand(Some(&"0".repeat(64))).await else {
+            panic!("expected a checksum mismatch");
+        };
+        let err = err.downcast_ref::<CreateError>().unwrap();
+        assert!(matches!(
+            err,
+            CreateError::TemplateChecksumMismatch { actual, .. } if actual == &checksum
+        ));
+
+        let source = TemplateSource::LocalDir("../../tests/templates/function-template".into());
+        let Err(err) = source.expand(Some(&checksum)).await else {
+            panic!("expected directory templates to be rejected");
+        };
+        let err = err.downcast_ref::<CreateError>().unwrap();
+        assert!(matches!(err, CreateError::UnverifiableTemplate));
+    }
+
     #[test]
     fn test_find_local_zip_file() {
         let tmp_dir = tempdir().unwrap();
@@ -13,7 +13,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::template::PROMPT_WITH_OPTIONS_HELP_MESSAGE;
+use crate::{events::Events, template::PROMPT_WITH_OPTIONS_HELP_MESSAGE};
 
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 #[serde(untagged)]
@@ -50,6 +50,45 @@ pub(crate) struct RenderCondition {
     pub not_match: Option<PromptValue>,
 }
 
+impl RenderCondition {
+    /// Whether the variable matches the condition's value, or doesn't match its `not_match` value.
+    /// The condition never matches when the variable doesn't exist.
+    pub(crate) fn matches(&self, variables: &Object) -> bool {
+        let Some(variable) = variables.get::<str>(&self.var) else {
+            return false;
+        };
+
+        if let Some(condition_value) = &self.r#match
+            && condition_value.to_value() == *variable
+        {
+            return true;
+        }
+
+        if let Some(condition_value) = &self.not_match
+            && condition_value.to_value() != *variable
+        {
+            return true;
+        }
+
+        false
+    }
+}
+
+/// Dependency that the template adds to the project's Cargo.toml when a prompt variable
+/// matches the condition. If the template already declares the dependency,
+/// only its features are added.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct TemplateDependency {
+    #[serde(flatten)]
+    pub condition: RenderCondition,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default, alias = "default-features")]
+    pub default_features: Option<bool>,
+}
+
 #[derive(Debug, Default, Deserialize)]
 pub(crate) struct TemplatePrompt {
     pub message: String,
@@ -77,6 +116,10 @@ pub(crate) struct TemplateConfig {
     pub render_conditional_files: HashMap<String, RenderCondition>,
     #[serde(default)]
     pub ignore_conditional_files: HashMap<String, RenderCondition>,
+    #[serde(default)]
+    pub dependencies: IndexMap<String, TemplateDependency>,
+    #[serde(default)]
+    pub events: Events,
 }
 
 #[derive(Debug, Deserialize)]
@@ -259,6 +302,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_template_config_events() {
+        let config = parse_template_config("../../tests/templates/config-template").unwrap();
+        let event = &config.events["orders::OrderCreated"];
+        assert_eq!("orders", event.feature);
+        assert_eq!(
+            Some("orders_events::OrderCreated".to_string()),
+            event.import
+        );
+        assert!(
+            event
+                .snippet
+                .as_ref()
+                .unwrap()
+                .contains("event.payload.order_id")
+        );
+    }
+
+    #[test]
+    fn test_parse_template_config_dependencies() {
+        let config = parse_template_config("../../tests/templates/config-template").unwrap();
+        assert_eq!(
+            vec!["serde", "aws-sdk-s3", "tracing-subscriber"],
+            config.dependencies.keys().collect::<Vec<_>>()
+        );
+
+        let serde = &config.dependencies["serde"];
+        assert_eq!("serde_derive", serde.condition.var);
+        assert_eq!(Some(PromptValue::Boolean(true)), serde.condition.r#match);
+        assert_eq!(vec!["derive".to_string()], serde.features);
+        assert_eq!(None, serde.version);
+
+        let s3 = &config.dependencies["aws-sdk-s3"];
+        assert_eq!(Some("1".to_string()), s3.version);
+        assert_eq!(Some(false), s3.default_features);
+    }
+
+    #[test]
+    fn test_render_condition_matches() {
+        let variables = liquid::object!({"license": "MIT", "github_actions": true});
+        let condition = |var: &str, r#match, not_match| RenderCondition {
+            var: var.into(),
+            r#match,
+            not_match,
+        };
+
+        assert!(
+            condition("github_actions", Some(PromptValue::Boolean(true)), None).matches(&variables)
+        );
+        assert!(
+            !condition("github_actions", Some(PromptValue::Boolean(false)), None)
+                .matches(&variables)
+        );
+        assert!(
+            condition("license", None, Some(PromptValue::String("APACHE".into())))
+                .matches(&variables)
+        );
+        assert!(
+            !condition("license", None, Some(PromptValue::String("MIT".into())))
+                .matches(&variables)
+        );
+        assert!(!condition("missing", Some(PromptValue::Boolean(true)), None).matches(&variables));
+    }
+
     #[test]
     fn test_parse_template_config_render_files() {
         let config = parse_template_config("../../tests/templates/config-template").unwrap();
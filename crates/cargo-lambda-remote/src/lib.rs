@@ -8,6 +8,8 @@ use aws_config::{
 use aws_types::{SdkConfig, region::Region};
 use clap::Args;
 use serde::{Deserialize, Serialize, ser::SerializeStruct};
+pub mod profiles;
+pub mod sso;
 pub mod tls;
 
 const DEFAULT_REGION: &str = "us-east-1";
@@ -260,6 +262,34 @@ mod tests {
         assert_eq!(creds.access_key_id(), "BBBBBBBBBBBBBBBBBBBB");
     }
 
+    /// Specify a profile which gets its credentials from AWS SSO, without a session
+    /// Expectations:
+    /// - The profile is reported as an expired SSO profile
+    /// - Profiles with static credentials are not reported
+    #[tokio::test]
+    async fn expired_sso_profile() {
+        setup();
+
+        let args = RemoteConfig {
+            profile: Some("elderberry".to_owned()),
+            retry_attempts: Some(1),
+            ..Default::default()
+        };
+        let config = args.sdk_config(None).await;
+        assert_eq!(
+            args.expired_sso_profile(&config).await,
+            Some("elderberry".to_string())
+        );
+
+        let args = RemoteConfig {
+            profile: Some("apple".to_owned()),
+            retry_attempts: Some(1),
+            ..Default::default()
+        };
+        let config = args.sdk_config(None).await;
+        assert_eq!(args.expired_sso_profile(&config).await, None);
+    }
+
     /// Use the default profile which has a region associated to it
     /// Expectations:
     /// - Region is used from the profile
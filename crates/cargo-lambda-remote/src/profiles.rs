@@ -0,0 +1,136 @@
+use aws_config::profile::{ProfileSet, load};
+use aws_runtime::env_config::file::EnvConfigFiles;
+use aws_types::os_shim_internal::{Env, Fs};
+use std::fmt;
+
+/// Regions where AWS Lambda is available, to choose from when the profile doesn't set one.
+pub const LAMBDA_REGIONS: &[&str] = &[
+    "us-east-1",
+    "us-east-2",
+    "us-west-1",
+    "us-west-2",
+    "af-south-1",
+    "ap-east-1",
+    "ap-south-1",
+    "ap-south-2",
+    "ap-southeast-1",
+    "ap-southeast-2",
+    "ap-southeast-3",
+    "ap-southeast-4",
+    "ap-northeast-1",
+    "ap-northeast-2",
+    "ap-northeast-3",
+    "ca-central-1",
+    "ca-west-1",
+    "eu-central-1",
+    "eu-central-2",
+    "eu-west-1",
+    "eu-west-2",
+    "eu-west-3",
+    "eu-south-1",
+    "eu-south-2",
+    "eu-north-1",
+    "il-central-1",
+    "me-south-1",
+    "me-central-1",
+    "sa-east-1",
+];
+
+/// Profile defined in the AWS shared configuration and credentials files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileInfo {
+    pub name: String,
+    pub region: Option<String>,
+}
+
+impl fmt::Display for ProfileInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.region {
+            Some(region) => write!(f, "{} ({region})", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// Load the profiles in the AWS shared configuration and credentials files.
+/// The selected profile is `profile`, or the default profile if it's None.
+pub(crate) async fn load_profile_set(profile: Option<String>) -> Option<ProfileSet> {
+    load(
+        &Fs::real(),
+        &Env::real(),
+        &EnvConfigFiles::default(),
+        profile.map(Into::into),
+    )
+    .await
+    .ok()
+}
+
+/// List the profiles in the AWS shared configuration and credentials files, sorted by name.
+pub async fn list_profiles() -> Vec<ProfileInfo> {
+    load_profile_set(None)
+        .await
+        .map(|set| profiles(&set))
+        .unwrap_or_default()
+}
+
+fn profiles(profile_set: &ProfileSet) -> Vec<ProfileInfo> {
+    let mut profiles = profile_set
+        .profiles()
+        .map(|name| ProfileInfo {
+            name: name.to_string(),
+            region: profile_set
+                .get_profile(name)
+                .and_then(|p| p.get("region"))
+                .map(String::from),
+        })
+        .collect::<Vec<_>>();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    profiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_profiles() {
+        let fs = Fs::from_slice(&[
+            (
+                "config",
+                "[default]\nregion = us-east-1\n\n[profile staging]\nregion = eu-west-1\n",
+            ),
+            (
+                "credentials",
+                "[production]\naws_access_key_id = AAAA\naws_secret_access_key = BBBB\n",
+            ),
+        ]);
+        let env = Env::from_slice(&[
+            ("AWS_CONFIG_FILE", "config"),
+            ("AWS_SHARED_CREDENTIALS_FILE", "credentials"),
+        ]);
+        let profile_set = load(&fs, &env, &EnvConfigFiles::default(), None)
+            .await
+            .unwrap();
+
+        let profiles = profiles(&profile_set);
+        assert_eq!(
+            vec![
+                ProfileInfo {
+                    name: "default".into(),
+                    region: Some("us-east-1".into())
+                },
+                ProfileInfo {
+                    name: "production".into(),
+                    region: None
+                },
+                ProfileInfo {
+                    name: "staging".into(),
+                    region: Some("eu-west-1".into())
+                },
+            ],
+            profiles
+        );
+        assert_eq!("staging (eu-west-1)", profiles[2].to_string());
+        assert_eq!("production", profiles[1].to_string());
+    }
+}
@@ -0,0 +1,121 @@
+use aws_config::profile::ProfileSet;
+use aws_sdk_lambda::config::ProvideCredentials;
+use aws_types::SdkConfig;
+use miette::Diagnostic;
+use std::{
+    io::ErrorKind,
+    process::{Command, ExitStatus},
+};
+use thiserror::Error;
+
+use crate::{RemoteConfig, profiles::load_profile_set};
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum SsoError {
+    #[error("the AWS CLI is not installed, it's necessary to log in with AWS SSO")]
+    #[diagnostic(help(
+        "install the AWS CLI, or log in with `aws sso login --profile {0}` in another machine"
+    ))]
+    MissingAwsCli(String),
+
+    #[error("failed to run `aws sso login`: {0}")]
+    #[diagnostic()]
+    LoginCommand(#[source] std::io::Error),
+
+    #[error("`aws sso login` exited with {0}")]
+    #[diagnostic()]
+    LoginFailed(ExitStatus),
+}
+
+impl RemoteConfig {
+    /// Check whether the credentials fail to load because the profile gets them from
+    /// AWS SSO (IAM Identity Center), and its session expired, or the user never logged in.
+    /// It returns the name of the profile to log in with.
+    pub async fn expired_sso_profile(&self, sdk_config: &SdkConfig) -> Option<String> {
+        let profile_set = load_profile_set(self.profile.clone()).await?;
+
+        let profile = sso_profile(&profile_set)?;
+
+        let provider = sdk_config.credentials_provider()?;
+        match provider.provide_credentials().await {
+            Ok(_) => None,
+            Err(error) => {
+                tracing::debug!(?error, profile, "failed to load SSO credentials");
+                Some(profile)
+            }
+        }
+    }
+}
+
+/// Name of the selected profile, if it gets its credentials from AWS SSO.
+fn sso_profile(profile_set: &ProfileSet) -> Option<String> {
+    let name = profile_set.selected_profile();
+    let profile = profile_set.get_profile(name)?;
+
+    if profile.get("sso_session").is_some() || profile.get("sso_start_url").is_some() {
+        Some(name.to_string())
+    } else {
+        None
+    }
+}
+
+/// Log in with AWS SSO using the AWS CLI. The CLI opens a browser
+/// to authorize the device, and it stores the new token in its cache,
+/// where the SDK finds it.
+pub fn sso_login(profile: &str) -> Result<(), SsoError> {
+    let status = Command::new("aws")
+        .args(["sso", "login", "--profile", profile])
+        .status()
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => SsoError::MissingAwsCli(profile.to_string()),
+            _ => SsoError::LoginCommand(err),
+        })?;
+
+    if !status.success() {
+        return Err(SsoError::LoginFailed(status));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_config::profile::load;
+    use aws_runtime::env_config::file::EnvConfigFiles;
+    use aws_types::os_shim_internal::{Env, Fs};
+
+    async fn profile_set(profile: Option<&'static str>) -> ProfileSet {
+        let fs = Fs::from_slice(&[(
+            "config",
+            "[default]\nregion = us-east-1\n\n\
+             [profile legacy]\nsso_start_url = https://example.awsapps.com/start\nsso_region = us-east-1\n\n\
+             [profile session]\nsso_session = my-sso\nsso_account_id = 123456789012\nsso_role_name = Admin\n\n\
+             [sso-session my-sso]\nsso_start_url = https://example.awsapps.com/start\nsso_region = us-east-1\n",
+        )]);
+        let env = Env::from_slice(&[("AWS_CONFIG_FILE", "config")]);
+
+        load(
+            &fs,
+            &env,
+            &EnvConfigFiles::default(),
+            profile.map(Into::into),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sso_profile() {
+        assert_eq!(None, sso_profile(&profile_set(None).await));
+        assert_eq!(None, sso_profile(&profile_set(Some("missing")).await));
+        assert_eq!(
+            Some("legacy".to_string()),
+            sso_profile(&profile_set(Some("legacy")).await)
+        );
+        assert_eq!(
+            Some("session".to_string()),
+            sso_profile(&profile_set(Some("session")).await)
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use addr2line::{
+    Context,
+    gimli::{self, EndianSlice, RunTimeEndian},
+};
+use clap::{Args, ValueHint};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use object::{Object, ObjectSection, SymbolMap, SymbolMapName};
+use rustc_demangle::demangle;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::{File, read},
+    io::{BufRead, BufReader, Write, stdin, stdout},
+    path::PathBuf,
+};
+use tracing::trace;
+
+#[derive(Args, Clone, Debug)]
+#[command(
+    name = "symbolicate",
+    after_help = "Full command documentation: https://www.cargo-lambda.info/commands/symbolicate.html"
+)]
+pub struct Symbolicate {
+    /// File with the debug information of the binary that printed the backtrace,
+    /// like the files that `cargo lambda build --split-debuginfo-out` creates
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    debug_file: PathBuf,
+
+    /// Address where the binary was loaded in memory, like `0x55d5c8a00000`.
+    /// Position independent binaries are loaded in a different address every time
+    /// they start, and the addresses in their backtraces include it
+    #[arg(long, value_parser = parse_address)]
+    load_address: Option<u64>,
+
+    /// File with the backtrace to symbolicate, it's read from stdin when it's not set
+    #[arg(value_hint = ValueHint::FilePath)]
+    backtrace: Option<PathBuf>,
+}
+
+impl Symbolicate {
+    #[tracing::instrument(skip(self), target = "cargo_lambda")]
+    pub fn run(&self) -> Result<()> {
+        trace!(options = ?self, "symbolicating backtrace");
+
+        let data = read(&self.debug_file)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read debug file `{:?}`", self.debug_file))?;
+        let object = object::File::parse(&*data)
+            .into_diagnostic()
+            .wrap_err("the debug file is not a valid Linux binary")?;
+
+        let input: Box<dyn BufRead> = match &self.backtrace {
+            Some(path) => Box::new(BufReader::new(
+                File::open(path)
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("failed to open backtrace file `{path:?}`"))?,
+            )),
+            None => Box::new(stdin().lock()),
+        };
+
+        symbolicate(
+            &object,
+            self.load_address.unwrap_or_default(),
+            input,
+            stdout().lock(),
+        )
+    }
+}
+
+/// Replace the addresses in a backtrace with the functions, files, and lines that they point to.
+/// Lines without addresses, or with addresses that the debug information doesn't know about,
+/// are printed without changes.
+fn symbolicate<'data, R: BufRead, W: Write>(
+    object: &object::File<'data>,
+    load_address: u64,
+    input: R,
+    mut output: W,
+) -> Result<()> {
+    let sections = debug_sections(object)?;
+    let endian = if object.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let dwarf = gimli::Dwarf::load(|id| -> Result<_, gimli::Error> {
+        let data = sections.get(id.name()).map(|s| &**s).unwrap_or(&[]);
+        Ok(EndianSlice::new(data, endian))
+    })
+    .into_diagnostic()?;
+    let context = Context::from_dwarf(dwarf)
+        .into_diagnostic()
+        .wrap_err("failed to read the debug information")?;
+    let symbols = object.symbol_map();
+
+    for line in input.lines() {
+        let line = line
+            .into_diagnostic()
+            .wrap_err("failed to read backtrace")?;
+
+        let frames = parse_frame(&line)
+            .and_then(|(index, address)| {
+                let address = address.checked_sub(load_address)?;
+                // Return addresses point to the instruction after the call,
+                // except in the first frame, which is where the program stopped.
+                let probe = if index == Some(0) {
+                    address
+                } else {
+                    address.saturating_sub(1)
+                };
+                Some((index, address, find_frames(&context, &symbols, probe)))
+            })
+            .filter(|(_, _, frames)| !frames.is_empty());
+
+        match frames {
+            Some((index, address, frames)) => {
+                write_frames(&mut output, index, address, &frames).into_diagnostic()?
+            }
+            None => writeln!(output, "{line}").into_diagnostic()?,
+        }
+    }
+
+    Ok(())
+}
+
+/// A frame resolved from an address. Inlined functions
+/// resolve to several frames for the same address.
+#[derive(Debug, PartialEq)]
+struct Frame {
+    function: String,
+    location: Option<String>,
+}
+
+fn find_frames(
+    context: &Context<EndianSlice<'_, RunTimeEndian>>,
+    symbols: &SymbolMap<SymbolMapName<'_>>,
+    probe: u64,
+) -> Vec<Frame> {
+    let mut frames = Vec::new();
+
+    if let Ok(mut iter) = context.find_frames(probe).skip_all_loads() {
+        while let Ok(Some(frame)) = iter.next() {
+            let function = frame
+                .function
+                .as_ref()
+                .and_then(|f| f.raw_name().ok())
+                .map(|name| format!("{:#}", demangle(&name)))
+                .or_else(|| symbol_name(symbols, probe))
+                .unwrap_or_else(|| "<unknown>".to_string());
+
+            let location = frame.location.and_then(|l| {
+                let file = l.file?;
+                Some(match (l.line, l.column) {
+                    (Some(line), Some(column)) => format!("{file}:{line}:{column}"),
+                    (Some(line), None) => format!("{file}:{line}"),
+                    _ => file.to_string(),
+                })
+            });
+
+            frames.push(Frame { function, location });
+        }
+    }
+
+    // Functions without debug information, like functions from C libraries,
+    // can still be found in the symbol table.
+    if frames.is_empty() {
+        frames.extend(symbol_name(symbols, probe).map(|function| Frame {
+            function,
+            location: None,
+        }));
+    }
+
+    frames
+}
+
+fn symbol_name(symbols: &SymbolMap<SymbolMapName<'_>>, probe: u64) -> Option<String> {
+    symbols
+        .get(probe)
+        .map(|symbol| format!("{:#}", demangle(symbol.name())))
+}
+
+/// Print the frames in the same format that Rust uses for backtraces.
+fn write_frames<W: Write>(
+    output: &mut W,
+    index: Option<usize>,
+    address: u64,
+    frames: &[Frame],
+) -> std::io::Result<()> {
+    for (i, frame) in frames.iter().enumerate() {
+        match (i, index) {
+            (0, Some(index)) => writeln!(output, "{index:>4}: {address:#x} - {}", frame.function)?,
+            (0, None) => writeln!(output, "{address:#x} - {}", frame.function)?,
+            _ => writeln!(output, "      {}", frame.function)?,
+        }
+        if let Some(location) = &frame.location {
+            writeln!(output, "             at {location}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Find the frame index and the address in a line of a backtrace, like
+/// `  3:     0x55d5c8b1a2b3 - <unknown>`, or a line with only an address.
+fn parse_frame(line: &str) -> Option<(Option<usize>, u64)> {
+    let line = line.trim();
+    let (index, rest) = match line.split_once(':') {
+        Some((index, rest)) if index.parse::<usize>().is_ok() => (index.parse().ok(), rest),
+        _ => (None, line),
+    };
+
+    let address = rest
+        .split_whitespace()
+        .find_map(|token| parse_address(token).ok())?;
+    Some((index, address))
+}
+
+fn parse_address(value: &str) -> Result<u64, String> {
+    let hex = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .ok_or_else(|| format!("invalid address `{value}`, it must start with `0x`"))?;
+    u64::from_str_radix(hex, 16).map_err(|e| format!("invalid address `{value}`: {e}"))
+}
+
+/// Sections with debug information, decompressed when they're compressed in the file.
+fn debug_sections<'data>(
+    object: &object::File<'data>,
+) -> Result<HashMap<String, Cow<'data, [u8]>>> {
+    let mut sections = HashMap::new();
+    for section in object.sections() {
+        let Ok(name) = section.name() else {
+            continue;
+        };
+        if !name.starts_with(".debug_") {
+            continue;
+        }
+
+        let data = section
+            .uncompressed_data()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read section `{name}`"))?;
+        sections.insert(name.to_string(), data);
+    }
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::ObjectSymbol;
+
+    #[inline(never)]
+    fn symbolicate_fixture() -> u64 {
+        std::hint::black_box(42)
+    }
+
+    #[test]
+    fn test_parse_frame() {
+        assert_eq!(
+            Some((Some(3), 0x55d5c8b1a2b3)),
+            parse_frame("   3:     0x55d5c8b1a2b3 - <unknown>")
+        );
+        assert_eq!(Some((None, 0x1234)), parse_frame("0x1234"));
+        assert_eq!(
+            Some((None, 0x1234)),
+            parse_frame("2024-01-01T00:00:00Z  0x1234 - <unknown>")
+        );
+        assert_eq!(
+            None,
+            parse_frame("thread 'main' panicked at src/main.rs:5:5:")
+        );
+        assert_eq!(None, parse_frame("stack backtrace:"));
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(Ok(0x55d5c8a00000), parse_address("0x55d5c8a00000"));
+        assert!(parse_address("55d5c8a00000").is_err());
+        assert!(parse_address("0xzz").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_symbolicate() {
+        assert_eq!(42, symbolicate_fixture());
+
+        // The test binary is built with debug information, so it can symbolicate itself.
+        let data = read(std::env::current_exe().unwrap()).unwrap();
+        let object = object::File::parse(&*data).unwrap();
+        let address = object
+            .symbols()
+            .find(|s| {
+                s.name().is_ok_and(|name| {
+                    format!("{:#}", demangle(name)).ends_with("symbolicate_fixture")
+                })
+            })
+            .expect("missing fixture symbol")
+            .address();
+
+        let load_address = 0x5500_0000_0000;
+        let input = format!(
+            "thread 'main' panicked at src/main.rs:5:5:\n   0: {:#x} - <unknown>\n",
+            address + load_address
+        );
+        let mut output = Vec::new();
+        symbolicate(&object, load_address, input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let mut lines = output.lines();
+        assert_eq!(
+            Some("thread 'main' panicked at src/main.rs:5:5:"),
+            lines.next()
+        );
+        assert_eq!(
+            Some(
+                format!(
+                    "   0: {address:#x} - cargo_lambda_symbolicate::tests::symbolicate_fixture"
+                )
+                .as_str()
+            ),
+            lines.next()
+        );
+        let location = lines.next().unwrap();
+        assert!(location.contains("src/lib.rs:"), "{location}");
+    }
+}
@@ -1,27 +1,51 @@
-use clap::Args;
+use clap::{Args, Subcommand};
 use miette::Result;
 
-use cargo_lambda_build::{Zig, install_options, install_zig, print_install_options};
+use cargo_lambda_build::{find_zig, install_options, install_zig, print_install_options};
 use cargo_lambda_interactive::is_stdin_tty;
 use tracing::trace;
 
+mod info;
+pub use info::Info;
+
+mod update;
+pub use update::Update;
+
 #[derive(Args, Clone, Debug)]
 #[command(
     name = "system",
-    after_help = "Full command documentation: https://www.cargo-lambda.info/commands/system.html"
+    after_help = "Full command documentation: https://www.cargo-lambda.info/commands/system.html",
+    args_conflicts_with_subcommands = true
 )]
 pub struct System {
     /// Setup and install Zig if it is not already installed.
     #[arg(long, visible_alias = "install")]
     setup: bool,
+
+    #[command(subcommand)]
+    command: Option<SystemCommand>,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum SystemCommand {
+    /// Print information about the system to include in bug reports
+    Info(Info),
+    /// Update Cargo Lambda to the latest release in GitHub
+    Update(Update),
 }
 
 impl System {
     #[tracing::instrument(skip(self), target = "cargo_lambda")]
-    pub async fn run(&self) -> Result<()> {
+    pub async fn run(&self, version: &str) -> Result<()> {
         trace!(options = ?self, "running system command");
 
-        if let Ok((path, _)) = Zig::find_zig() {
+        match &self.command {
+            Some(SystemCommand::Info(info)) => return info.run(version),
+            Some(SystemCommand::Update(update)) => return update.run().await,
+            None => {}
+        }
+
+        if let Ok((path, _)) = find_zig() {
             println!("Zig installation found at:");
             println!("{}", path.display());
         } else {
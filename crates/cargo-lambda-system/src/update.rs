@@ -0,0 +1,466 @@
+use cargo_lambda_interactive::progress::Progress;
+use cargo_lambda_metadata::net;
+use clap::Args;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use reqwest::Client;
+use semver::Version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{copy, rename, write},
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+/// GitHub API endpoint with the latest stable release of Cargo Lambda.
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/cargo-lambda/cargo-lambda/releases/latest";
+
+#[derive(Args, Clone, Debug)]
+pub struct Update {
+    /// Check if there is a new release, without installing it
+    #[arg(long)]
+    check: bool,
+}
+
+impl Update {
+    #[tracing::instrument(skip(self), target = "cargo_lambda")]
+    pub async fn run(&self) -> Result<()> {
+        let current = Version::parse(env!("CARGO_PKG_VERSION")).into_diagnostic()?;
+
+        let client = net::client()?;
+        let release = latest_release(client, LATEST_RELEASE_URL).await?;
+        let latest = release.version()?;
+
+        if latest <= current {
+            println!("Cargo Lambda {current} is up to date");
+            return Ok(());
+        }
+        println!("Cargo Lambda {latest} is available, you're using {current}");
+        if self.check {
+            return Ok(());
+        }
+
+        let exe = std::env::current_exe()
+            .and_then(dunce::canonicalize)
+            .into_diagnostic()
+            .wrap_err("failed to find the path to the Cargo Lambda binary")?;
+
+        if let Some(manager) = PackageManager::detect(&exe) {
+            println!(
+                "Cargo Lambda was installed with {manager}, run `{}` to update it",
+                manager.usage()
+            );
+            return Ok(());
+        }
+
+        let target = release_target(std::env::consts::OS, std::env::consts::ARCH)
+            .ok_or_else(|| miette::miette!("there are no Cargo Lambda releases for this system"))?;
+
+        let pb = Progress::start(format!("Downloading Cargo Lambda {latest}..."));
+        let result = install_release(client, &release, target, &exe).await;
+        let finish = if result.is_ok() {
+            format!("Cargo Lambda updated to {latest}")
+        } else {
+            "Failed to update Cargo Lambda".to_string()
+        };
+        pb.finish(&finish);
+
+        result
+    }
+}
+
+/// Package managers that install Cargo Lambda, and that should update it too.
+#[derive(Debug, PartialEq, strum_macros::Display)]
+enum PackageManager {
+    Homebrew,
+    Nix,
+    #[strum(to_string = "Pip3")]
+    Pip,
+    Scoop,
+}
+
+impl PackageManager {
+    /// Find the package manager that installed a binary from the binary's path.
+    fn detect(exe: &Path) -> Option<PackageManager> {
+        let path = exe.to_string_lossy().to_lowercase().replace('\\', "/");
+
+        if path.contains("/cellar/") || path.contains("/homebrew/") {
+            Some(PackageManager::Homebrew)
+        } else if path.starts_with("/nix/store/") {
+            Some(PackageManager::Nix)
+        } else if path.contains("/scoop/") {
+            Some(PackageManager::Scoop)
+        } else if path.contains("/site-packages/") || is_python_environment(exe) {
+            Some(PackageManager::Pip)
+        } else {
+            None
+        }
+    }
+
+    fn usage(&self) -> &'static str {
+        match self {
+            PackageManager::Homebrew => "brew upgrade cargo-lambda",
+            PackageManager::Nix => "nix profile upgrade cargo-lambda",
+            PackageManager::Pip => "pip3 install --upgrade cargo-lambda",
+            PackageManager::Scoop => "scoop update cargo-lambda",
+        }
+    }
+}
+
+/// Pip installs binaries in the `bin` directory of a virtual environment,
+/// next to its `pyvenv.cfg`, or in the `Scripts` directory of a Python
+/// installation on Windows, next to `python.exe`.
+fn is_python_environment(exe: &Path) -> bool {
+    let Some(prefix) = exe.parent().and_then(Path::parent) else {
+        return false;
+    };
+    prefix.join("pyvenv.cfg").is_file() || prefix.join("python.exe").is_file()
+}
+
+/// A release of Cargo Lambda in GitHub.
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReleaseAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn version(&self) -> Result<Version> {
+        let version = self.tag_name.strip_prefix('v').unwrap_or(&self.tag_name);
+        Version::parse(version)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("invalid release version {}", self.tag_name))
+    }
+
+    fn asset(&self, name: &str) -> Result<&ReleaseAsset> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| miette::miette!("the release {} doesn't include {name}", self.tag_name))
+    }
+}
+
+/// Target in the names of the release archives, like `x86_64-unknown-linux-musl`.
+/// Returns None if there are no releases for the host.
+fn release_target(os: &str, arch: &str) -> Option<&'static str> {
+    match (os, arch) {
+        ("linux", "x86_64") => Some("x86_64-unknown-linux-musl"),
+        ("linux", "aarch64") => Some("aarch64-unknown-linux-musl"),
+        ("macos", "x86_64") => Some("x86_64-apple-darwin"),
+        ("macos", "aarch64") => Some("aarch64-apple-darwin"),
+        ("windows", "x86_64") => Some("windows-x64"),
+        _ => None,
+    }
+}
+
+/// Windows releases are zip files with `cargo-lambda.exe`,
+/// the rest are tarballs with `cargo-lambda`.
+fn is_windows_target(target: &str) -> bool {
+    target.starts_with("windows")
+}
+
+async fn latest_release(client: &Client, url: &str) -> Result<Release> {
+    let data = download(client, url).await?;
+    serde_json::from_slice(&data)
+        .into_diagnostic()
+        .wrap_err("failed to parse the latest release of Cargo Lambda")
+}
+
+async fn download(client: &Client, url: &str) -> Result<Vec<u8>> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to download {url}"))?;
+    let data = response
+        .bytes()
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to download {url}"))?;
+    Ok(data.to_vec())
+}
+
+/// Download the release archive for a target, verify it with the checksum
+/// published next to it, and replace the binary with the one in the archive.
+/// The checksum comes from the same release as the archive, so it only
+/// detects corrupted downloads, it doesn't prove who published the archive.
+async fn install_release(
+    client: &Client,
+    release: &Release,
+    target: &str,
+    exe: &Path,
+) -> Result<()> {
+    let extension = if is_windows_target(target) {
+        "zip"
+    } else {
+        "tar.gz"
+    };
+    let name = format!("cargo-lambda-{}.{target}.{extension}", release.tag_name);
+    let archive = release.asset(&name)?;
+    let checksum = release.asset(&format!("{name}.sha256"))?;
+    debug!(?archive, ?exe, "downloading release");
+
+    let data = download(client, &archive.browser_download_url).await?;
+    let checksum = download(client, &checksum.browser_download_url).await?;
+    verify_checksum(&data, &String::from_utf8_lossy(&checksum), &name)?;
+
+    let dir = tempfile::tempdir().into_diagnostic()?;
+    let binary = if is_windows_target(target) {
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).into_diagnostic()?;
+        archive.extract(dir.path()).into_diagnostic()?;
+        dir.path().join("cargo-lambda.exe")
+    } else {
+        extract_tar_gz(&data, dir.path()).await?;
+        dir.path().join("cargo-lambda")
+    };
+
+    if !binary.is_file() {
+        return Err(miette::miette!(
+            "the Cargo Lambda binary is missing from the downloaded archive {name}"
+        ));
+    }
+
+    replace_executable(&binary, exe)
+}
+
+/// Compare the checksum of an archive with the content of its `.sha256` file,
+/// in the format that `shasum -a 256` prints.
+fn verify_checksum(data: &[u8], checksum_file: &str, name: &str) -> Result<()> {
+    let expected = checksum_file.split_whitespace().next().unwrap_or_default();
+    let shasum = format!("{:x}", Sha256::digest(data));
+    if !shasum.eq_ignore_ascii_case(expected) {
+        return Err(miette::miette!(
+            "the checksum of {name} doesn't match the checksum published with the release"
+        ));
+    }
+    Ok(())
+}
+
+/// Extract a tarball with the system's `tar` command.
+async fn extract_tar_gz(data: &[u8], dir: &Path) -> Result<()> {
+    let tar_path = dir.join("cargo-lambda.tar.gz");
+    write(&tar_path, data).into_diagnostic()?;
+
+    let tar_file = tar_path.to_string_lossy();
+    let target = dir.to_string_lossy();
+    let status = tokio::process::Command::new("tar")
+        .args(["-xzf", &tar_file, "-C", &target])
+        .status()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to run `tar` to extract the Cargo Lambda archive")?;
+
+    if !status.success() {
+        return Err(miette::miette!(
+            "failed to extract the Cargo Lambda archive"
+        ));
+    }
+    Ok(())
+}
+
+/// Replace the running binary with a new one.
+/// The new binary is copied next to the current one first, so the
+/// final rename happens in the same filesystem and cannot be left halfway.
+fn replace_executable(binary: &Path, exe: &Path) -> Result<()> {
+    let staged = sibling_path(exe, "new");
+    copy(binary, &staged)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to copy the new binary to {}", staged.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .into_diagnostic()?;
+    }
+
+    // Windows doesn't allow replacing a running binary, but it allows renaming it.
+    #[cfg(windows)]
+    {
+        let old = sibling_path(exe, "old");
+        let _ = std::fs::remove_file(&old);
+        rename(exe, &old).into_diagnostic()?;
+    }
+
+    rename(&staged, exe)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to replace {}", exe.display()))
+}
+
+/// Path next to a file, with an extra extension, like `cargo-lambda.new`.
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::MockServer;
+    use std::io::Write;
+
+    #[test]
+    fn test_release_target() {
+        assert_eq!(
+            Some("x86_64-unknown-linux-musl"),
+            release_target("linux", "x86_64")
+        );
+        assert_eq!(
+            Some("aarch64-apple-darwin"),
+            release_target("macos", "aarch64")
+        );
+        assert_eq!(Some("windows-x64"), release_target("windows", "x86_64"));
+        assert_eq!(None, release_target("windows", "aarch64"));
+        assert_eq!(None, release_target("freebsd", "x86_64"));
+    }
+
+    #[test]
+    fn test_detect_package_manager() {
+        let cases = [
+            (
+                "/opt/homebrew/Cellar/cargo-lambda/1.7.0/bin/cargo-lambda",
+                Some(PackageManager::Homebrew),
+            ),
+            (
+                "/home/linuxbrew/.linuxbrew/Cellar/cargo-lambda/1.7.0/bin/cargo-lambda",
+                Some(PackageManager::Homebrew),
+            ),
+            (
+                "/nix/store/abc-cargo-lambda-1.7.0/bin/cargo-lambda",
+                Some(PackageManager::Nix),
+            ),
+            (
+                r"C:\Users\me\scoop\apps\cargo-lambda\current\cargo-lambda.exe",
+                Some(PackageManager::Scoop),
+            ),
+            (
+                "/home/me/.local/lib/python3.12/site-packages/cargo_lambda/bin/cargo-lambda",
+                Some(PackageManager::Pip),
+            ),
+            ("/home/me/projects/python-tools/bin/cargo-lambda", None),
+            ("/home/me/.cargo/bin/cargo-lambda", None),
+            ("/usr/local/bin/cargo-lambda", None),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(expected, PackageManager::detect(Path::new(path)), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_detect_pip_environments() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let venv = dir.path().join("venv");
+        std::fs::create_dir_all(venv.join("bin")).unwrap();
+        write(venv.join("pyvenv.cfg"), "home = /usr/bin\n").unwrap();
+        assert_eq!(
+            Some(PackageManager::Pip),
+            PackageManager::detect(&venv.join("bin").join("cargo-lambda"))
+        );
+
+        let python = dir.path().join("Python312");
+        std::fs::create_dir_all(python.join("Scripts")).unwrap();
+        write(python.join("python.exe"), "").unwrap();
+        assert_eq!(
+            Some(PackageManager::Pip),
+            PackageManager::detect(&python.join("Scripts").join("cargo-lambda.exe"))
+        );
+
+        let tools = dir.path().join("python-tools");
+        std::fs::create_dir_all(tools.join("bin")).unwrap();
+        assert_eq!(
+            None,
+            PackageManager::detect(&tools.join("bin").join("cargo-lambda"))
+        );
+    }
+
+    #[test]
+    fn test_release_version() {
+        let release = Release {
+            tag_name: "v1.8.0".into(),
+            assets: Vec::new(),
+        };
+        assert_eq!(Version::new(1, 8, 0), release.version().unwrap());
+
+        let release = Release {
+            tag_name: "latest".into(),
+            assets: Vec::new(),
+        };
+        assert!(release.version().is_err());
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let data = b"cargo-lambda";
+        let shasum = format!("{:x}", Sha256::digest(data));
+        let file = format!("{shasum}  cargo-lambda-v1.8.0.x86_64-unknown-linux-musl.tar.gz\n");
+
+        assert!(verify_checksum(data, &file, "cargo-lambda").is_ok());
+        assert!(verify_checksum(b"tampered", &file, "cargo-lambda").is_err());
+        assert!(verify_checksum(data, "", "cargo-lambda").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_install_release() {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("cargo-lambda.exe", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"new binary").unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+        let shasum = format!("{:x}", Sha256::digest(&archive));
+
+        let server = MockServer::start_async().await;
+        let name = "cargo-lambda-v1.8.0.windows-x64.zip";
+        server.mock(|when, then| {
+            when.path(format!("/{name}"));
+            then.status(200).body(&archive);
+        });
+        let checksum = server.mock(|when, then| {
+            when.path(format!("/{name}.sha256"));
+            then.status(200).body(format!("{shasum}  {name}\n"));
+        });
+
+        let release = Release {
+            tag_name: "v1.8.0".into(),
+            assets: vec![
+                ReleaseAsset {
+                    name: name.into(),
+                    browser_download_url: server.url(format!("/{name}")),
+                },
+                ReleaseAsset {
+                    name: format!("{name}.sha256"),
+                    browser_download_url: server.url(format!("/{name}.sha256")),
+                },
+            ],
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("cargo-lambda.exe");
+        write(&exe, "old binary").unwrap();
+
+        install_release(&Client::new(), &release, "windows-x64", &exe)
+            .await
+            .unwrap();
+        checksum.assert();
+        assert_eq!("new binary", std::fs::read_to_string(&exe).unwrap());
+        assert!(!sibling_path(&exe, "new").exists());
+
+        let err = install_release(&Client::new(), &release, "x86_64-apple-darwin", &exe)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("doesn't include"));
+    }
+}
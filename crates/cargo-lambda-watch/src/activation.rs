@@ -0,0 +1,115 @@
+use miette::Result;
+use tokio::net::TcpListener;
+use tracing::{debug, warn};
+
+/// First file descriptor that systemd passes to services with socket activation.
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Variables that systemd sets for services with socket activation.
+/// The functions run as child processes of the server, they're removed
+/// from their environment so they don't think that the sockets are for them.
+pub(crate) const SD_LISTEN_VARS: [&str; 3] = ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"];
+
+/// Socket inherited from the process that started the server, like systemd,
+/// or an IDE test harness that binds the port in advance. It's the descriptor
+/// in `--listen-fd`, or the first one passed with systemd's socket activation.
+pub(crate) fn inherited_listener(listen_fd: Option<i32>) -> Result<Option<TcpListener>> {
+    if let Some(fd) = listen_fd {
+        return listener_from_fd(fd).map(Some);
+    }
+
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    let Some(fd) = systemd_listen_fd(pid.as_deref(), fds.as_deref(), std::process::id()) else {
+        return Ok(None);
+    };
+
+    debug!(
+        fd,
+        "using the socket passed with systemd's socket activation"
+    );
+    listener_from_fd(fd).map(Some)
+}
+
+/// Descriptor of the socket that systemd passed to this process, if it passed any.
+fn systemd_listen_fd(pid: Option<&str>, fds: Option<&str>, current_pid: u32) -> Option<i32> {
+    let pid = pid?.parse::<u32>().ok()?;
+    let fds = fds?.parse::<i32>().ok()?;
+    if pid != current_pid || fds < 1 {
+        return None;
+    }
+
+    if fds > 1 {
+        warn!(
+            fds,
+            "systemd passed several sockets, the server only listens on the first one"
+        );
+    }
+    Some(SD_LISTEN_FDS_START)
+}
+
+#[cfg(unix)]
+fn listener_from_fd(fd: i32) -> Result<TcpListener> {
+    use miette::{IntoDiagnostic, WrapErr};
+    use std::{mem::ManuallyDrop, os::fd::FromRawFd};
+
+    if fd < 0 {
+        return Err(miette::miette!("invalid file descriptor {fd}"));
+    }
+
+    // SAFETY: the process that started the server passed the descriptor to it,
+    // nothing else in this process uses it. It's not closed if it's not
+    // a TCP socket, because then it's not ours to close.
+    let listener = ManuallyDrop::new(unsafe { std::net::TcpListener::from_raw_fd(fd) });
+    if let Err(err) = listener.local_addr() {
+        return Err(miette::miette!(
+            help = "pass the descriptor of a TCP socket that's listening for connections",
+            "the file descriptor {fd} is not a TCP socket: {err}"
+        ));
+    }
+
+    let listener = ManuallyDrop::into_inner(listener);
+    listener
+        .set_nonblocking(true)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to configure the socket in file descriptor {fd}"))?;
+    TcpListener::from_std(listener).into_diagnostic()
+}
+
+#[cfg(not(unix))]
+fn listener_from_fd(fd: i32) -> Result<TcpListener> {
+    Err(miette::miette!(
+        "the server cannot use the file descriptor {fd}, inherited sockets are only supported on Unix systems"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_systemd_listen_fd() {
+        assert_eq!(Some(3), systemd_listen_fd(Some("42"), Some("1"), 42));
+        assert_eq!(Some(3), systemd_listen_fd(Some("42"), Some("2"), 42));
+        assert_eq!(None, systemd_listen_fd(Some("41"), Some("1"), 42));
+        assert_eq!(None, systemd_listen_fd(Some("42"), Some("0"), 42));
+        assert_eq!(None, systemd_listen_fd(None, Some("1"), 42));
+        assert_eq!(None, systemd_listen_fd(Some("42"), None, 42));
+        assert_eq!(None, systemd_listen_fd(Some("pid"), Some("1"), 42));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_listener_from_fd() {
+        use std::os::fd::{AsRawFd, IntoRawFd};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let inherited = listener_from_fd(listener.into_raw_fd()).unwrap();
+        assert_eq!(addr, inherited.local_addr().unwrap());
+
+        let file = tempfile::tempfile().unwrap();
+        let err = listener_from_fd(file.as_raw_fd()).unwrap_err();
+        assert!(err.to_string().contains("is not a TCP socket"), "{err}");
+    }
+}
@@ -0,0 +1,323 @@
+use crate::{
+    RefRuntimeState,
+    error::ServerError,
+    reload::{FunctionHealth, ReloadEvent},
+    requests::{Action, ControlCommand, ControlRequest, ControlStatus},
+    trigger_router::{respond_with_disabled_default_function, respond_with_missing_function},
+};
+use axum::{
+    Router,
+    body::Body,
+    extract::{Extension, Path, Query, State},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{get, post},
+};
+use cargo_lambda_metadata::DEFAULT_PACKAGE_FUNCTION;
+use hyper::{StatusCode, header};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use tokio::sync::{mpsc::Sender, oneshot};
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+
+pub(crate) const CONTROL_PATH: &str = "/_lambda";
+
+pub(crate) fn routes() -> Router<RefRuntimeState> {
+    Router::new()
+        .route(
+            &format!("{CONTROL_PATH}/functions/:function_name/stop"),
+            post(stop_function),
+        )
+        .route(
+            &format!("{CONTROL_PATH}/functions/:function_name/restart"),
+            post(restart_function),
+        )
+        .route(
+            &format!("{CONTROL_PATH}/functions/:function_name/queue"),
+            get(function_queue),
+        )
+        .route(&format!("{CONTROL_PATH}/events"), get(reload_events))
+        .route(&format!("{CONTROL_PATH}/health"), get(health))
+        .route(&format!("{CONTROL_PATH}/ready"), get(ready))
+}
+
+async fn stop_function(
+    State(state): State<RefRuntimeState>,
+    Extension(cmd_tx): Extension<Sender<Action>>,
+    Path(function_name): Path<String>,
+) -> Result<Response<Body>, ServerError> {
+    control_function(state, cmd_tx, function_name, ControlCommand::Stop).await
+}
+
+async fn restart_function(
+    State(state): State<RefRuntimeState>,
+    Extension(cmd_tx): Extension<Sender<Action>>,
+    Path(function_name): Path<String>,
+) -> Result<Response<Body>, ServerError> {
+    control_function(state, cmd_tx, function_name, ControlCommand::Restart).await
+}
+
+/// Report how many invocations are waiting for a function,
+/// and how many invocations its queue accepts before rejecting them.
+async fn function_queue(
+    State(state): State<RefRuntimeState>,
+    Path(function_name): Path<String>,
+) -> Result<Response<Body>, ServerError> {
+    if let Some(resp) = check_function(&state, &function_name) {
+        return resp;
+    }
+
+    let mut queued = state.req_cache.depth(&function_name).await;
+    // Functions in single binary projects can run under the default function name.
+    if queued.is_none()
+        && state.is_default_function_enabled()
+        && state.initial_functions.contains(&function_name)
+    {
+        queued = state.req_cache.depth(DEFAULT_PACKAGE_FUNCTION).await;
+    }
+
+    let body = serde_json::json!({
+        "function": function_name,
+        "queued": queued.unwrap_or_default(),
+        "capacity": state.invoke_queue_size(),
+    });
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        body.to_string(),
+    )
+        .into_response())
+}
+
+/// Report that the server is alive and accepting requests.
+async fn health() -> Response<Body> {
+    let body = serde_json::json!({ "status": "ok" });
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+/// Report whether the functions are ready to process invocations.
+/// It answers with a `503 Service Unavailable` status while any function is compiling,
+/// or until a function's process asks for invocations, so scripts can wait for
+/// the emulator before they send the first invocation. It answers with a
+/// `500 Internal Server Error` status when a function exited before it was ready.
+async fn ready(State(state): State<RefRuntimeState>) -> Response<Body> {
+    ready_response(state.functions_health())
+}
+
+fn ready_response(functions: BTreeMap<String, FunctionHealth>) -> Response<Body> {
+    let failed = functions.values().any(|h| *h == FunctionHealth::Failed);
+    let starting = functions.values().any(|h| *h == FunctionHealth::Starting);
+    let registered = functions.values().any(|h| *h == FunctionHealth::Ready);
+    let (status_code, status) = if failed {
+        (StatusCode::INTERNAL_SERVER_ERROR, "failed")
+    } else if registered && !starting {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+    };
+
+    let body = serde_json::json!({
+        "status": status,
+        "functions": functions,
+    });
+
+    (
+        status_code,
+        [(header::CONTENT_TYPE, "application/json")],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ReloadFilter {
+    function: Option<String>,
+}
+
+/// Stream server-sent events when the functions are rebuilding and
+/// when they are ready again, so front-end dev servers can reload.
+/// The `function` query parameter limits the events to one function.
+async fn reload_events(
+    State(state): State<RefRuntimeState>,
+    Query(filter): Query<ReloadFilter>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let events = BroadcastStream::new(state.reload.subscribe())
+        // Clients that fall behind skip the events that they missed.
+        .filter_map(move |event| event.ok().filter(|e| filter.matches(e)))
+        .map(|event| Event::default().json_data(event));
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+impl ReloadFilter {
+    fn matches(&self, event: &ReloadEvent) -> bool {
+        self.function
+            .as_ref()
+            .is_none_or(|function| function == &event.function)
+    }
+}
+
+/// Check that the function exists. It returns the error response when it doesn't.
+fn check_function(
+    state: &RefRuntimeState,
+    function_name: &str,
+) -> Option<Result<Response<Body>, ServerError>> {
+    if function_name == DEFAULT_PACKAGE_FUNCTION {
+        if !state.is_default_function_enabled() {
+            return Some(respond_with_disabled_default_function(state, true));
+        }
+    } else if let Err(binaries) = state.is_function_available(function_name) {
+        return Some(respond_with_missing_function(&binaries));
+    }
+    None
+}
+
+async fn control_function(
+    state: RefRuntimeState,
+    cmd_tx: Sender<Action>,
+    function_name: String,
+    command: ControlCommand,
+) -> Result<Response<Body>, ServerError> {
+    tracing::debug!(%function_name, ?command, "control request received");
+
+    if let Some(resp) = check_function(&state, &function_name) {
+        return resp;
+    }
+
+    let (resp_tx, resp_rx) = oneshot::channel::<ControlStatus>();
+    let req = ControlRequest {
+        function_name: function_name.clone(),
+        command,
+        resp_tx,
+    };
+
+    cmd_tx
+        .send(Action::Control(req))
+        .await
+        .map_err(|e| ServerError::SendActionMessage(Box::new(e)))?;
+
+    let status = resp_rx.await.map_err(ServerError::ReceiveControlMessage)?;
+
+    Ok(control_response(&function_name, status))
+}
+
+fn control_response(function_name: &str, status: ControlStatus) -> Response<Body> {
+    let status_code = match status {
+        ControlStatus::Unmanaged => StatusCode::CONFLICT,
+        _ => StatusCode::OK,
+    };
+
+    let body = serde_json::json!({
+        "function": function_name,
+        "status": status,
+    });
+
+    (
+        status_code,
+        [(header::CONTENT_TYPE, "application/json")],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reload::ReloadStatus;
+    use http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn test_control_response() {
+        let resp = control_response("basic-lambda", ControlStatus::Stopped);
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            serde_json::json!({"function": "basic-lambda", "status": "stopped"}),
+            body
+        );
+
+        let resp = control_response("basic-lambda", ControlStatus::Unmanaged);
+        assert_eq!(StatusCode::CONFLICT, resp.status());
+    }
+
+    #[tokio::test]
+    async fn test_health_response() {
+        let resp = health().await;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(serde_json::json!({"status": "ok"}), body);
+    }
+
+    #[tokio::test]
+    async fn test_ready_response() {
+        let resp = ready_response(BTreeMap::from([
+            ("basic-lambda".to_string(), FunctionHealth::Ready),
+            ("other-lambda".to_string(), FunctionHealth::Starting),
+        ]));
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, resp.status());
+
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "status": "not_ready",
+                "functions": {"basic-lambda": "ready", "other-lambda": "starting"},
+            }),
+            body
+        );
+
+        let resp = ready_response(BTreeMap::from([
+            ("basic-lambda".to_string(), FunctionHealth::Ready),
+            ("other-lambda".to_string(), FunctionHealth::Idle),
+        ]));
+        assert_eq!(StatusCode::OK, resp.status());
+
+        // No process has asked for invocations yet.
+        let resp = ready_response(BTreeMap::from([(
+            "basic-lambda".to_string(),
+            FunctionHealth::Idle,
+        )]));
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, resp.status());
+
+        let resp = ready_response(BTreeMap::from([
+            ("basic-lambda".to_string(), FunctionHealth::Ready),
+            ("other-lambda".to_string(), FunctionHealth::Failed),
+        ]));
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, resp.status());
+
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!("failed", body["status"]);
+    }
+
+    #[test]
+    fn test_reload_filter() {
+        let event = ReloadEvent {
+            function: "basic-lambda".into(),
+            status: ReloadStatus::Ready,
+        };
+        assert!(ReloadFilter::default().matches(&event));
+
+        let filter = ReloadFilter {
+            function: Some("basic-lambda".into()),
+        };
+        assert!(filter.matches(&event));
+
+        let filter = ReloadFilter {
+            function: Some("other-lambda".into()),
+        };
+        assert!(!filter.matches(&event));
+    }
+}
@@ -36,10 +36,18 @@ pub enum ServerError {
     #[diagnostic()]
     ReceiveFunctionMessage(#[from] tokio::sync::oneshot::error::RecvError),
 
+    #[error("failed to receive message from scheduler: {0}")]
+    #[diagnostic()]
+    ReceiveControlMessage(tokio::sync::oneshot::error::RecvError),
+
     #[error("failed to start function process")]
     #[diagnostic()]
     SpawnCommand(#[from] std::io::Error),
 
+    #[error("failed to open the function's log file: {0}")]
+    #[diagnostic()]
+    OpenLogFile(std::io::Error),
+
     #[error("invalid request id header: {0}")]
     #[diagnostic()]
     InvalidRequestIdHeader(#[from] axum::http::header::ToStrError),
@@ -0,0 +1,354 @@
+use crate::{
+    requests::{InvokeRequest, function_error_response},
+    session::{self, SessionProcess},
+};
+use cargo_lambda_metadata::{cargo::watch::FaultOptions, error::MetadataError};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+
+/// Error type that Lambda reports when the function fails to initialize.
+const INIT_ERROR: &str = "Runtime.InitError";
+
+/// Error type that Lambda reports when the function runs out of memory.
+pub(crate) const OUT_OF_MEMORY_ERROR: &str = "Runtime.OutOfMemory";
+
+/// Message that Lambda reports when the function runs out of memory.
+pub(crate) const OUT_OF_MEMORY_MESSAGE: &str = "Runtime exited with error: signal: killed";
+
+/// How often the memory usage of the functions is checked.
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Failures injected in the lifecycle of the functions, to exercise
+/// how the functions and their callers handle them without deploying.
+#[derive(Debug, Default)]
+pub(crate) struct FaultInjector {
+    fail_init: bool,
+    init_delay: Option<Duration>,
+    memory_threshold: Option<u64>,
+    /// Functions that have started processing invocations since their process started
+    initialized: Mutex<HashSet<String>>,
+    /// Functions that have asked for an invocation since their process started
+    ready: Mutex<HashSet<String>>,
+    /// Functions whose process was killed because they exceeded the memory threshold
+    out_of_memory: Mutex<HashSet<String>>,
+}
+
+impl FaultInjector {
+    pub(crate) fn new(
+        options: &FaultOptions,
+        memory_size: u64,
+    ) -> Result<FaultInjector, MetadataError> {
+        let memory_threshold = options
+            .memory_threshold()?
+            .map(|threshold| threshold.bytes(memory_size));
+
+        if memory_threshold.is_some() && !cfg!(target_os = "linux") {
+            warn!("--oom-at is only available on Linux, the functions' memory won't be monitored");
+        }
+
+        Ok(FaultInjector {
+            fail_init: options.fail_init,
+            init_delay: options.init_delay()?,
+            memory_threshold,
+            ..Default::default()
+        })
+    }
+
+    /// Reset the state of a function when its process starts again.
+    pub(crate) fn function_started(&self, name: &str) {
+        self.initialized.lock().unwrap().remove(name);
+        self.ready.lock().unwrap().remove(name);
+        self.out_of_memory.lock().unwrap().remove(name);
+    }
+
+    /// Record that the function asked for an invocation, its memory is monitored after that.
+    pub(crate) fn function_ready(&self, name: &str) {
+        if self.memory_threshold.is_some() {
+            self.ready.lock().unwrap().insert(name.to_string());
+        }
+    }
+
+    /// Time to wait before the function receives its first invocation.
+    /// It only returns a delay the first time that the function asks
+    /// for an invocation after its process starts.
+    pub(crate) fn init_delay(&self, name: &str) -> Option<Duration> {
+        let delay = self.init_delay?;
+        if self.initialized.lock().unwrap().insert(name.to_string()) {
+            Some(delay)
+        } else {
+            None
+        }
+    }
+
+    /// Answer an invocation with an init error if the function's initialization must fail.
+    /// It returns the invocation back when the function can process it.
+    pub(crate) fn fail_init(&self, invoke: InvokeRequest) -> Option<InvokeRequest> {
+        if !self.fail_init {
+            return Some(invoke);
+        }
+
+        warn!(function = ?invoke.function_name, "failing the invocation with a simulated init error");
+        let resp = function_error_response(INIT_ERROR, "Simulated init failure (--fail-init)");
+        if invoke.resp_tx.send(resp).is_err() {
+            debug!(function = ?invoke.function_name, "the invocation was cancelled before responding");
+        }
+        None
+    }
+
+    /// Returns true if the function's process was killed because it ran out of memory.
+    pub(crate) fn take_out_of_memory(&self, name: &str) -> bool {
+        self.out_of_memory.lock().unwrap().remove(name)
+    }
+
+    /// Monitor the memory that a function's process uses, and kill it when
+    /// it exceeds the threshold. The monitor stops when the process exits.
+    /// The process is `cargo run`, which compiles the function before starting it,
+    /// so the memory only counts after the function asks for its first invocation.
+    pub(crate) fn watch_memory(self: &Arc<Self>, name: &str, process: SessionProcess) {
+        let Some(threshold) = self.memory_threshold.filter(|_| cfg!(target_os = "linux")) else {
+            return;
+        };
+
+        info!(function = ?name, threshold_mb = threshold / 1024 / 1024, "monitoring the function's memory");
+
+        let faults = self.clone();
+        let name = name.to_string();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MEMORY_SAMPLE_INTERVAL);
+            loop {
+                interval.tick().await;
+
+                let Some(usage) = memory_usage(process.id) else {
+                    return;
+                };
+                if usage < threshold || !faults.ready.lock().unwrap().contains(&name) {
+                    continue;
+                }
+
+                error!(
+                    function = ?name,
+                    usage_mb = usage / 1024 / 1024,
+                    threshold_mb = threshold / 1024 / 1024,
+                    "the function exceeded the memory threshold, killing it"
+                );
+                faults.out_of_memory.lock().unwrap().insert(name.clone());
+                if let Err(error) = session::kill(&process) {
+                    error!(?error, function = ?name, "failed to kill the function");
+                }
+                return;
+            }
+        });
+    }
+}
+
+/// Resident memory used by the processes that a process started, in bytes.
+/// The process itself is `cargo run`, so its memory is not included.
+/// It returns None when the process is not running.
+#[cfg(target_os = "linux")]
+fn memory_usage(pid: u32) -> Option<u64> {
+    use std::{collections::HashMap, fs};
+
+    if !std::path::Path::new(&format!("/proc/{pid}")).exists() {
+        return None;
+    }
+
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(child) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        // The parent pid is the second field after the command name,
+        // which is wrapped in parentheses and it can include spaces.
+        let parent = fs::read_to_string(entry.path().join("stat"))
+            .ok()
+            .and_then(|stat| {
+                let (_, fields) = stat.rsplit_once(')')?;
+                fields.split_whitespace().nth(1)?.parse::<u32>().ok()
+            });
+        if let Some(parent) = parent {
+            children.entry(parent).or_default().push(child);
+        }
+    }
+
+    let mut usage = 0;
+    let mut pending = children.remove(&pid).unwrap_or_default();
+    while let Some(child) = pending.pop() {
+        usage += resident_memory(child).unwrap_or_default();
+        pending.extend(children.remove(&child).unwrap_or_default());
+    }
+
+    Some(usage)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn memory_usage(_pid: u32) -> Option<u64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn resident_memory(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FUNCTION_MEMORY_SIZE;
+    use axum::{body::Body, http::Request};
+    use http::StatusCode;
+    use http_body_util::BodyExt;
+    use tokio::sync::oneshot;
+
+    fn fault_options(fail_init: bool, delay_init: Option<&str>) -> FaultOptions {
+        FaultOptions {
+            fail_init,
+            delay_init: delay_init.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_init_delay() {
+        let faults =
+            FaultInjector::new(&fault_options(false, Some("5s")), FUNCTION_MEMORY_SIZE).unwrap();
+
+        assert_eq!(
+            Some(Duration::from_secs(5)),
+            faults.init_delay("basic-lambda")
+        );
+        assert_eq!(None, faults.init_delay("basic-lambda"));
+        assert_eq!(
+            Some(Duration::from_secs(5)),
+            faults.init_delay("other-lambda")
+        );
+
+        faults.function_started("basic-lambda");
+        assert_eq!(
+            Some(Duration::from_secs(5)),
+            faults.init_delay("basic-lambda")
+        );
+
+        let faults = FaultInjector::default();
+        assert_eq!(None, faults.init_delay("basic-lambda"));
+    }
+
+    #[tokio::test]
+    async fn test_fail_init() {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        let invoke = InvokeRequest {
+            function_name: "basic-lambda".into(),
+            req: Request::new(Body::empty()),
+            resp_tx,
+            attempts: 0,
+        };
+
+        let faults = FaultInjector::default();
+        let invoke = faults.fail_init(invoke).expect("missing invocation");
+
+        let faults = FaultInjector::new(&fault_options(true, None), FUNCTION_MEMORY_SIZE).unwrap();
+        assert!(faults.fail_init(invoke).is_none());
+
+        let resp = resp_rx.await.unwrap();
+        assert_eq!(
+            Some(&StatusCode::INTERNAL_SERVER_ERROR),
+            resp.extensions().get::<StatusCode>()
+        );
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(INIT_ERROR, body["errorType"]);
+    }
+
+    #[test]
+    fn test_out_of_memory() {
+        let faults = FaultInjector::default();
+        faults
+            .out_of_memory
+            .lock()
+            .unwrap()
+            .insert("basic-lambda".into());
+
+        assert!(faults.take_out_of_memory("basic-lambda"));
+        assert!(!faults.take_out_of_memory("basic-lambda"));
+
+        faults
+            .out_of_memory
+            .lock()
+            .unwrap()
+            .insert("basic-lambda".into());
+        faults.function_started("basic-lambda");
+        assert!(!faults.take_out_of_memory("basic-lambda"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_watch_memory_after_the_function_is_ready() {
+        let faults = Arc::new(FaultInjector {
+            memory_threshold: Some(1),
+            ..Default::default()
+        });
+        faults.function_started("basic-lambda");
+
+        // The child process stands for the compiler that `cargo run` starts.
+        let mut parent = std::process::Command::new("sh")
+            .args(["-c", "sleep 5; true"])
+            .spawn()
+            .unwrap();
+        let process = SessionProcess {
+            id: parent.id(),
+            grouped: false,
+        };
+        faults.watch_memory("basic-lambda", process);
+
+        tokio::time::sleep(MEMORY_SAMPLE_INTERVAL * 5).await;
+        assert!(parent.try_wait().unwrap().is_none(), "the build was killed");
+        assert!(!faults.take_out_of_memory("basic-lambda"));
+
+        faults.function_ready("basic-lambda");
+        for _ in 0..50 {
+            if parent.try_wait().unwrap().is_some() {
+                break;
+            }
+            tokio::time::sleep(MEMORY_SAMPLE_INTERVAL).await;
+        }
+        assert!(
+            parent.try_wait().unwrap().is_some(),
+            "the function was not killed"
+        );
+        assert!(faults.take_out_of_memory("basic-lambda"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_memory_usage() {
+        let mut parent = std::process::Command::new("sh")
+            .args(["-c", "sleep 2; true"])
+            .spawn()
+            .unwrap();
+        let pid = parent.id();
+
+        let mut usage = 0;
+        for _ in 0..50 {
+            usage = memory_usage(pid).unwrap();
+            if usage > 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(usage > 0, "the memory of the child process is not included");
+
+        parent.kill().unwrap();
+        parent.wait().unwrap();
+        assert_eq!(None, memory_usage(pid));
+    }
+}
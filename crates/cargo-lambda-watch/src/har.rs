@@ -0,0 +1,448 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, Version, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose as b64};
+use chrono::{DateTime, SecondsFormat, Utc};
+use http_body_util::BodyExt;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde::Serialize;
+use std::{path::Path, time::Instant};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tracing::{debug, error};
+
+const HAR_VERSION: &str = "1.2";
+
+/// Records the HTTP requests that the emulator receives, and the responses
+/// that it sends, in an HTTP Archive (HAR) file. The file is written after
+/// each request, so it's complete even if the server doesn't shut down cleanly.
+#[derive(Clone)]
+pub(crate) struct HarRecorder {
+    scheme: &'static str,
+    entries: UnboundedSender<Entry>,
+}
+
+impl HarRecorder {
+    pub(crate) fn start(path: &Path, secure: bool) -> Result<HarRecorder> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent).into_diagnostic()?;
+            }
+        }
+
+        let mut archive = Archive::default();
+        archive
+            .write(path)
+            .wrap_err_with(|| format!("failed to write the HAR file {}", path.display()))?;
+
+        let (entries, mut rx) = unbounded_channel();
+        let path = path.to_path_buf();
+        tokio::spawn(async move {
+            while let Some(entry) = rx.recv().await {
+                archive.log.entries.push(entry);
+                if let Err(error) = archive.write(&path) {
+                    error!(?error, path = ?path, "failed to write the HAR file");
+                }
+            }
+        });
+
+        Ok(HarRecorder {
+            scheme: if secure { "https" } else { "http" },
+            entries,
+        })
+    }
+}
+
+/// Middleware that records each request and its response.
+/// Response bodies are recorded while they're sent, so streaming
+/// responses reach the client without waiting for the whole body.
+pub(crate) async fn record(
+    State(recorder): State<HarRecorder>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let started = Utc::now();
+    let start = Instant::now();
+
+    let (parts, body) = req.into_parts();
+    let body = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(error) => {
+            debug!(?error, "failed to read the request body to record it");
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+
+    let request = HarRequest::new(&parts, &body, recorder.scheme);
+    let resp = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let wait = start.elapsed();
+
+    let (parts, body) = resp.into_parts();
+    let pending = PendingEntry {
+        recorder,
+        started,
+        start,
+        wait: wait.as_secs_f64() * 1000.0,
+        request,
+        status: parts.status,
+        version: parts.version,
+        headers: parts.headers.clone(),
+    };
+
+    let mut recorded = RecordedBody {
+        content: Vec::new(),
+        pending: Some(pending),
+    };
+    let body = body.map_frame(move |frame| {
+        if let Some(data) = frame.data_ref() {
+            recorded.content.extend_from_slice(data);
+        }
+        frame
+    });
+    Response::from_parts(parts, Body::new(body))
+}
+
+/// Information about a request that's waiting for the response body to be recorded.
+struct PendingEntry {
+    recorder: HarRecorder,
+    started: DateTime<Utc>,
+    start: Instant,
+    wait: f64,
+    request: HarRequest,
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+}
+
+impl PendingEntry {
+    fn finish(self, content: &[u8]) {
+        let time = self.start.elapsed().as_secs_f64() * 1000.0;
+        let mime_type = content_type(&self.headers);
+        let (text, encoding) = body_text(content);
+
+        let entry = Entry {
+            started_date_time: self.started.to_rfc3339_opts(SecondsFormat::Millis, true),
+            time,
+            request: self.request,
+            response: HarResponse {
+                status: self.status.as_u16(),
+                status_text: self.status.canonical_reason().unwrap_or_default().into(),
+                http_version: http_version(self.version),
+                cookies: Vec::new(),
+                headers: headers(&self.headers),
+                content: Content {
+                    size: content.len(),
+                    mime_type,
+                    text,
+                    encoding,
+                },
+                redirect_url: String::new(),
+                headers_size: -1,
+                body_size: content.len() as i64,
+            },
+            cache: Cache {},
+            timings: Timings {
+                send: 0.0,
+                wait: self.wait,
+                receive: time - self.wait,
+            },
+        };
+
+        // The channel is only closed when the server shuts down.
+        let _ = self.recorder.entries.send(entry);
+    }
+}
+
+/// Copy of a response body. The entry is recorded when the body is dropped,
+/// after it's sent to the client, or when the client disconnects.
+struct RecordedBody {
+    content: Vec<u8>,
+    pending: Option<PendingEntry>,
+}
+
+impl Drop for RecordedBody {
+    fn drop(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            pending.finish(&self.content);
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+struct Archive {
+    log: Log,
+}
+
+impl Archive {
+    fn write(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_vec_pretty(self).into_diagnostic()?;
+        std::fs::write(path, content).into_diagnostic()
+    }
+}
+
+#[derive(Serialize)]
+struct Log {
+    version: &'static str,
+    creator: Creator,
+    entries: Vec<Entry>,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Log {
+            version: HAR_VERSION,
+            creator: Creator {
+                name: "cargo-lambda",
+                version: env!("CARGO_PKG_VERSION"),
+            },
+            entries: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Creator {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    started_date_time: String,
+    time: f64,
+    request: HarRequest,
+    response: HarResponse,
+    cache: Cache,
+    timings: Timings,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    http_version: String,
+    cookies: Vec<NameValue>,
+    headers: Vec<NameValue>,
+    query_string: Vec<NameValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_data: Option<PostData>,
+    headers_size: i64,
+    body_size: i64,
+}
+
+impl HarRequest {
+    fn new(parts: &Parts, body: &[u8], scheme: &str) -> HarRequest {
+        let host = parts
+            .headers
+            .get("host")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("localhost");
+        let path = parts
+            .uri
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or("/");
+
+        let query_string = parts.uri.query().map(query_string).unwrap_or_default();
+
+        let post_data = (!body.is_empty()).then(|| {
+            let (text, encoding) = body_text(body);
+            PostData {
+                mime_type: content_type(&parts.headers),
+                text: text.unwrap_or_default(),
+                encoding,
+            }
+        });
+
+        HarRequest {
+            method: parts.method.to_string(),
+            url: format!("{scheme}://{host}{path}"),
+            http_version: http_version(parts.version),
+            cookies: Vec::new(),
+            headers: headers(&parts.headers),
+            query_string,
+            post_data,
+            headers_size: -1,
+            body_size: body.len() as i64,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PostData {
+    mime_type: String,
+    text: String,
+    /// HAR doesn't define encodings for request bodies, custom fields start with `_`
+    #[serde(rename = "_encoding", skip_serializing_if = "Option::is_none")]
+    encoding: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarResponse {
+    status: u16,
+    status_text: String,
+    http_version: String,
+    cookies: Vec<NameValue>,
+    headers: Vec<NameValue>,
+    content: Content,
+    #[serde(rename = "redirectURL")]
+    redirect_url: String,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Content {
+    size: usize,
+    mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct Cache {}
+
+#[derive(Serialize)]
+struct Timings {
+    send: f64,
+    wait: f64,
+    receive: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct NameValue {
+    name: String,
+    value: String,
+}
+
+fn headers(headers: &HeaderMap) -> Vec<NameValue> {
+    headers
+        .iter()
+        .map(|(name, value)| NameValue {
+            name: name.to_string(),
+            value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        })
+        .collect()
+}
+
+fn content_type(headers: &HeaderMap) -> String {
+    headers
+        .get("content-type")
+        .and_then(|c| c.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Text of a body, and its encoding when the body is not valid UTF-8.
+fn body_text(body: &[u8]) -> (Option<String>, Option<&'static str>) {
+    if body.is_empty() {
+        return (None, None);
+    }
+
+    match std::str::from_utf8(body) {
+        Ok(text) => (Some(text.to_string()), None),
+        Err(_) => (Some(b64::STANDARD.encode(body)), Some("base64")),
+    }
+}
+
+fn http_version(version: Version) -> String {
+    format!("{version:?}")
+}
+
+/// Name and value pairs from a query string, without decoding them.
+fn query_string(query: &str) -> Vec<NameValue> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            NameValue {
+                name: name.to_string(),
+                value: value.to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use serde_json::json;
+
+    #[test]
+    fn test_request_entry() {
+        let (parts, _) = Request::builder()
+            .method("POST")
+            .uri("/users?name=a%20b&active")
+            .header("host", "localhost:9000")
+            .header("content-type", "application/json")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let request = HarRequest::new(&parts, br#"{"id":1}"#, "http");
+        let request = serde_json::to_value(request).unwrap();
+        assert_eq!(request["method"], "POST");
+        assert_eq!(
+            request["url"],
+            "http://localhost:9000/users?name=a%20b&active"
+        );
+        assert_eq!(request["httpVersion"], "HTTP/1.1");
+        assert_eq!(
+            request["queryString"],
+            json!([{ "name": "name", "value": "a%20b" }, { "name": "active", "value": "" }])
+        );
+        assert_eq!(request["postData"]["mimeType"], "application/json");
+        assert_eq!(request["postData"]["text"], r#"{"id":1}"#);
+        assert_eq!(request["bodySize"], 8);
+    }
+
+    #[tokio::test]
+    async fn test_record_response_when_body_drops() {
+        let (entries, mut rx) = unbounded_channel();
+        let recorder = HarRecorder {
+            scheme: "http",
+            entries,
+        };
+
+        let (parts, _) = Request::builder().uri("/").body(()).unwrap().into_parts();
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/octet-stream".parse().unwrap());
+
+        let mut recorded = RecordedBody {
+            content: Vec::new(),
+            pending: Some(PendingEntry {
+                recorder,
+                started: Utc::now(),
+                start: Instant::now(),
+                wait: 0.0,
+                request: HarRequest::new(&parts, &[], "http"),
+                status: StatusCode::OK,
+                version: Version::HTTP_11,
+                headers,
+            }),
+        };
+        recorded.content.extend_from_slice(&[0xff, 0x00]);
+        drop(recorded);
+
+        let entry = serde_json::to_value(rx.recv().await.unwrap()).unwrap();
+        assert_eq!(entry["request"]["url"], "http://localhost/");
+        assert!(entry["request"].get("postData").is_none());
+        assert_eq!(entry["response"]["status"], 200);
+        assert_eq!(entry["response"]["statusText"], "OK");
+        assert_eq!(entry["response"]["content"]["size"], 2);
+        assert_eq!(entry["response"]["content"]["text"], "/wA=");
+        assert_eq!(entry["response"]["content"]["encoding"], "base64");
+    }
+}
@@ -0,0 +1,146 @@
+use bytes::Bytes;
+use cargo_lambda_metadata::cargo::watch::Watch;
+use std::process::Stdio;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{debug, error, warn};
+
+/// Environment variable with the name of the function invoked.
+const FUNCTION_NAME_VAR: &str = "LAMBDA_FUNCTION_NAME";
+
+/// Environment variable with the request id of the invocation.
+const REQUEST_ID_VAR: &str = "LAMBDA_REQUEST_ID";
+
+/// Environment variable with the outcome of the invocation, `success` or `error`.
+const INVOKE_STATUS_VAR: &str = "LAMBDA_INVOKE_STATUS";
+
+/// Commands that run before and after each invocation, to prepare
+/// the environment that the functions use, or to inspect their effects.
+/// A failing command is reported, but it doesn't fail the invocation.
+#[derive(Debug, Default)]
+pub(crate) struct InvokeHooks {
+    pre_invoke: Option<String>,
+    post_invoke: Option<String>,
+}
+
+impl InvokeHooks {
+    pub(crate) fn new(config: &Watch) -> InvokeHooks {
+        InvokeHooks {
+            pre_invoke: config.pre_invoke.clone(),
+            post_invoke: config.post_invoke.clone(),
+        }
+    }
+
+    /// Run the pre-invoke command before the function receives the invocation.
+    pub(crate) async fn pre_invoke(&self, function_name: &str, req_id: &str, payload: &Bytes) {
+        if let Some(command) = &self.pre_invoke {
+            let env = [(FUNCTION_NAME_VAR, function_name), (REQUEST_ID_VAR, req_id)];
+            run_hook("pre-invoke", command, &env, payload).await;
+        }
+    }
+
+    /// Run the post-invoke command after the function responds to the invocation.
+    pub(crate) async fn post_invoke(
+        &self,
+        function_name: &str,
+        req_id: &str,
+        payload: &Bytes,
+        success: bool,
+    ) {
+        if let Some(command) = &self.post_invoke {
+            let status = if success { "success" } else { "error" };
+            let env = [
+                (FUNCTION_NAME_VAR, function_name),
+                (REQUEST_ID_VAR, req_id),
+                (INVOKE_STATUS_VAR, status),
+            ];
+            run_hook("post-invoke", command, &env, payload).await;
+        }
+    }
+}
+
+/// Run the command in the system's shell, with the payload in its standard input.
+/// It returns whether the command succeeded.
+async fn run_hook(hook: &str, command: &str, env: &[(&str, &str)], payload: &Bytes) -> bool {
+    debug!(hook, command, ?env, "running invoke hook");
+
+    let mut child = match shell(command)
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(error) => {
+            error!(hook, command, %error, "failed to start invoke hook");
+            return false;
+        }
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // Commands can exit without reading the payload, ignore the broken pipe.
+        if let Err(error) = stdin.write_all(payload).await {
+            debug!(hook, command, %error, "failed to write the payload to the invoke hook");
+        }
+    }
+
+    match child.wait().await {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            warn!(hook, command, %status, "invoke hook failed");
+            false
+        }
+        Err(error) => {
+            error!(hook, command, %error, "failed to wait for invoke hook");
+            false
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let command = format!(
+            "cat > {0} && echo \"$LAMBDA_FUNCTION_NAME $LAMBDA_INVOKE_STATUS\" >> {0}",
+            out.display()
+        );
+        let env = [
+            (FUNCTION_NAME_VAR, "basic-lambda"),
+            (INVOKE_STATUS_VAR, "success"),
+        ];
+
+        assert!(
+            run_hook(
+                "post-invoke",
+                &command,
+                &env,
+                &Bytes::from("{\"command\":\"hi\"}")
+            )
+            .await
+        );
+        assert_eq!(
+            "{\"command\":\"hi\"}basic-lambda success\n",
+            std::fs::read_to_string(&out).unwrap()
+        );
+
+        assert!(!run_hook("pre-invoke", "exit 1", &[], &Bytes::new()).await);
+    }
+}
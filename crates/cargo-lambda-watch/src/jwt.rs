@@ -0,0 +1,331 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use hyper::{HeaderMap, header::AUTHORIZATION};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::path::Path;
+use thiserror::Error;
+
+/// Errors that make API Gateway reject a request before it invokes the function.
+#[derive(Debug, Error, PartialEq)]
+pub(crate) enum JwtError {
+    #[error("the request doesn't include a bearer token in the Authorization header")]
+    MissingToken,
+    #[error("the bearer token is not a valid JWT")]
+    Malformed,
+    #[error("the token's signature algorithm `{0}` is not supported")]
+    UnsupportedAlgorithm(String),
+    #[error("the key set doesn't include a key to verify the token")]
+    MissingKey,
+    #[error("the token's signature is invalid")]
+    InvalidSignature,
+    #[error("the token has expired")]
+    Expired,
+}
+
+/// Emulate the JWT authorizers of API Gateway HTTP APIs. It decodes the bearer token
+/// in the Authorization header of the requests, and verifies its signature and
+/// expiration when it has a JSON Web Key Set. Tokens are only decoded without one.
+#[derive(Debug, Default)]
+pub(crate) struct JwtAuthorizer {
+    keys: Option<Vec<Jwk>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+impl JwtAuthorizer {
+    pub(crate) fn new(jwks: Option<&Path>) -> Result<JwtAuthorizer> {
+        let Some(path) = jwks else {
+            return Ok(JwtAuthorizer::default());
+        };
+
+        let content = std::fs::read_to_string(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read the JSON Web Key Set {path:?}"))?;
+        let set: JwkSet = serde_json::from_str(&content)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("invalid JSON Web Key Set {path:?}"))?;
+
+        Ok(JwtAuthorizer {
+            keys: Some(set.keys),
+        })
+    }
+
+    /// Claims of the bearer token in the request's Authorization header.
+    pub(crate) fn authorize(&self, headers: &HeaderMap) -> Result<Map<String, Value>, JwtError> {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.strip_prefix("Bearer ").unwrap_or(h).trim())
+            .filter(|t| !t.is_empty())
+            .ok_or(JwtError::MissingToken)?;
+
+        let mut segments = token.split('.');
+        let (Some(header), Some(payload), Some(sig), None) = (
+            segments.next(),
+            segments.next(),
+            segments.next(),
+            segments.next(),
+        ) else {
+            return Err(JwtError::Malformed);
+        };
+
+        let claims: Map<String, Value> = decode_segment(payload)?;
+        let Some(keys) = &self.keys else {
+            return Ok(claims);
+        };
+
+        let jwt_header: JwtHeader = decode_segment(header)?;
+        let sig = URL_SAFE_NO_PAD
+            .decode(sig)
+            .map_err(|_| JwtError::Malformed)?;
+        let message = &token[..header.len() + payload.len() + 1];
+        verify_signature(keys, &jwt_header, message.as_bytes(), &sig)?;
+
+        let expired = claims
+            .get("exp")
+            .and_then(Value::as_i64)
+            .is_some_and(|exp| exp <= Utc::now().timestamp());
+        if expired {
+            return Err(JwtError::Expired);
+        }
+
+        Ok(claims)
+    }
+}
+
+/// Add the claims to the `requestContext` of an event, in the format of the
+/// HTTP API's JWT authorizers. API Gateway sends the values of the claims as strings,
+/// and the scopes from the `scope` claim.
+pub(crate) fn add_claims(event: &mut Value, claims: &Map<String, Value>) {
+    let scopes = claims
+        .get("scope")
+        .and_then(Value::as_str)
+        .map(|scope| scope.split_whitespace().collect::<Vec<_>>());
+    let claims = claims
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::String(claim_string(value))))
+        .collect::<Map<_, _>>();
+
+    let Some(event) = event.as_object_mut() else {
+        return;
+    };
+    let context = event.entry("requestContext").or_insert_with(|| json!({}));
+    if let Some(context) = context.as_object_mut() {
+        context.insert(
+            "authorizer".into(),
+            json!({"jwt": {"claims": claims, "scopes": scopes}}),
+        );
+    }
+}
+
+/// API Gateway renders arrays like `[admin editor]`.
+fn claim_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(values) => {
+            let values = values.iter().map(claim_string).collect::<Vec<_>>();
+            format!("[{}]", values.join(" "))
+        }
+        other => other.to_string(),
+    }
+}
+
+fn decode_segment<T: serde::de::DeserializeOwned>(segment: &str) -> Result<T, JwtError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(segment)
+        .map_err(|_| JwtError::Malformed)?;
+    serde_json::from_slice(&bytes).map_err(|_| JwtError::Malformed)
+}
+
+fn verify_signature(
+    keys: &[Jwk],
+    header: &JwtHeader,
+    message: &[u8],
+    sig: &[u8],
+) -> Result<(), JwtError> {
+    let kty = match header.alg.as_str() {
+        "RS256" | "RS384" | "RS512" => "RSA",
+        "ES256" | "ES384" => "EC",
+        alg => return Err(JwtError::UnsupportedAlgorithm(alg.to_string())),
+    };
+
+    let key = keys
+        .iter()
+        .filter(|k| k.kty == kty)
+        .find(|k| header.kid.is_none() || k.kid == header.kid)
+        .ok_or(JwtError::MissingKey)?;
+
+    let decode = |value: &Option<String>| {
+        value
+            .as_deref()
+            .and_then(|v| URL_SAFE_NO_PAD.decode(v).ok())
+            .ok_or(JwtError::MissingKey)
+    };
+
+    let verified = if kty == "RSA" {
+        let params = match header.alg.as_str() {
+            "RS256" => &signature::RSA_PKCS1_2048_8192_SHA256,
+            "RS384" => &signature::RSA_PKCS1_2048_8192_SHA384,
+            _ => &signature::RSA_PKCS1_2048_8192_SHA512,
+        };
+        let (n, e) = (decode(&key.n)?, decode(&key.e)?);
+        RsaPublicKeyComponents { n: &n, e: &e }.verify(params, message, sig)
+    } else {
+        let params = match header.alg.as_str() {
+            "ES256" => &signature::ECDSA_P256_SHA256_FIXED,
+            _ => &signature::ECDSA_P384_SHA384_FIXED,
+        };
+        // Uncompressed point format: 0x04 || x || y
+        let mut point = vec![0x04];
+        point.extend(decode(&key.x)?);
+        point.extend(decode(&key.y)?);
+        UnparsedPublicKey::new(params, point).verify(message, sig)
+    };
+
+    verified.map_err(|_| JwtError::InvalidSignature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::{
+        rand::SystemRandom,
+        signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair},
+    };
+
+    fn encode(value: &Value) -> String {
+        URL_SAFE_NO_PAD.encode(value.to_string())
+    }
+
+    fn headers(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+        headers
+    }
+
+    /// Sign a token with a new key, and return the token with the key set to verify it.
+    fn signed_token(claims: &Value) -> (String, Vec<Jwk>) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+            .unwrap();
+
+        let message = format!(
+            "{}.{}",
+            encode(&json!({"alg": "ES256", "kid": "dev"})),
+            encode(claims)
+        );
+        let sig = pair.sign(&rng, message.as_bytes()).unwrap();
+        let token = format!("{message}.{}", URL_SAFE_NO_PAD.encode(sig.as_ref()));
+
+        let point = pair.public_key().as_ref();
+        let key = Jwk {
+            kty: "EC".into(),
+            kid: Some("dev".into()),
+            n: None,
+            e: None,
+            x: Some(URL_SAFE_NO_PAD.encode(&point[1..33])),
+            y: Some(URL_SAFE_NO_PAD.encode(&point[33..])),
+        };
+        (token, vec![key])
+    }
+
+    #[test]
+    fn test_decode_without_verification() {
+        let token = format!(
+            "{}.{}.signature",
+            encode(&json!({"alg": "RS256"})),
+            encode(&json!({"sub": "user-1", "groups": ["admin", "dev"]}))
+        );
+
+        let authorizer = JwtAuthorizer::default();
+        let claims = authorizer.authorize(&headers(&token)).unwrap();
+        assert_eq!(claims["sub"], "user-1");
+
+        assert_eq!(
+            Err(JwtError::MissingToken),
+            authorizer.authorize(&HeaderMap::new())
+        );
+        assert_eq!(
+            Err(JwtError::Malformed),
+            authorizer.authorize(&headers("not-a-token"))
+        );
+    }
+
+    #[test]
+    fn test_verify_with_key_set() {
+        let exp = Utc::now().timestamp() + 3600;
+        let (token, keys) = signed_token(&json!({"sub": "user-1", "exp": exp}));
+        let authorizer = JwtAuthorizer { keys: Some(keys) };
+        assert_eq!(
+            "user-1",
+            authorizer.authorize(&headers(&token)).unwrap()["sub"]
+        );
+
+        let tampered = format!("{token}x");
+        assert!(authorizer.authorize(&headers(&tampered)).is_err());
+
+        let (other, _) = signed_token(&json!({"sub": "user-1", "exp": exp}));
+        assert_eq!(
+            Err(JwtError::InvalidSignature),
+            authorizer.authorize(&headers(&other))
+        );
+
+        let (expired, keys) = signed_token(&json!({"sub": "user-1", "exp": 1}));
+        let authorizer = JwtAuthorizer { keys: Some(keys) };
+        assert_eq!(
+            Err(JwtError::Expired),
+            authorizer.authorize(&headers(&expired))
+        );
+    }
+
+    #[test]
+    fn test_add_claims() {
+        let claims = json!({
+            "sub": "user-1",
+            "exp": 1700000000,
+            "groups": ["admin", "dev"],
+            "scope": "read write",
+        });
+        let mut event = json!({"requestContext": {"requestId": "req-id"}});
+        add_claims(&mut event, claims.as_object().unwrap());
+
+        assert_eq!(
+            json!({
+                "requestId": "req-id",
+                "authorizer": {"jwt": {
+                    "claims": {
+                        "sub": "user-1",
+                        "exp": "1700000000",
+                        "groups": "[admin dev]",
+                        "scope": "read write",
+                    },
+                    "scopes": ["read", "write"],
+                }},
+            }),
+            event["requestContext"]
+        );
+    }
+}
@@ -1,12 +1,15 @@
-use axum::{Router, extract::Extension, http::header::HeaderName};
+use axum::{Router, extract::Extension, http::header::HeaderName, middleware::from_fn_with_state};
 use bytes::Bytes;
 use cargo_lambda_metadata::{
     DEFAULT_PACKAGE_FUNCTION,
     cargo::{
-        CargoMetadata, CargoPackage, filter_binary_targets_from_metadata, kind_bin_filter,
-        selected_bin_filter, watch::Watch,
+        CargoMetadata,
+        features::missing_required_features,
+        target_dir_from_metadata,
+        watch::{FunctionRouter, Watch, WatchConfig},
     },
-    lambda::Timeout,
+    config::load_binary_watch_configs,
+    lambda::{RequestContext, Timeout},
 };
 use cargo_lambda_remote::tls::TlsOptions;
 use cargo_options::Run as CargoOptions;
@@ -25,19 +28,23 @@ use opentelemetry_aws::trace::XrayPropagator;
 use rustls::ServerConfig;
 use std::{
     collections::{HashMap, HashSet},
+    convert::Infallible,
+    future::IntoFuture,
+    io::{ErrorKind, IsTerminal},
     net::{IpAddr, SocketAddr},
-    path::Path,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
 };
 use tokio::{
     net::{TcpListener, TcpStream},
     pin,
+    task::JoinHandle,
     time::Duration,
 };
 use tokio_graceful_shutdown::{SubsystemBuilder, SubsystemHandle, Toplevel};
 use tokio_rustls::TlsAcceptor;
-use tokio_util::task::TaskTracker;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 use tower_http::{
     catch_panic::CatchPanicLayer,
     cors::CorsLayer,
@@ -45,26 +52,54 @@ use tower_http::{
     timeout::TimeoutLayer,
     trace::TraceLayer,
 };
-use tracing::{Subscriber, error, info};
+use tracing::{Subscriber, debug, error, info, warn};
 use tracing_opentelemetry::OpenTelemetryLayer;
 use tracing_subscriber::registry::LookupSpan;
 
+mod activation;
+mod control_router;
 mod error;
+mod faults;
+use faults::FaultInjector;
+mod har;
+use har::HarRecorder;
+mod hooks;
+use hooks::InvokeHooks;
+mod jwt;
+use jwt::JwtAuthorizer;
+mod openapi;
+use openapi::OpenApiTranslator;
+mod reload;
 mod requests;
 mod runtime;
-
+mod sandbox;
+use sandbox::Sandbox;
 mod scheduler;
 use scheduler::*;
+mod session;
+use session::Session;
 mod state;
 use state::*;
+mod translators;
+use translators::{MappingTranslator, RefEventTranslator, RequestContextTranslator, translator};
 mod trigger_router;
 mod watcher;
-use watcher::WatcherConfig;
+use watcher::{LogPrefixConfig, WatcherConfig};
 
 use crate::{error::ServerError, requests::Action};
 
 pub(crate) const RUNTIME_EMULATOR_PATH: &str = "/.rt";
 
+/// Memory size, in megabytes, that the functions report to the runtime
+/// when the watch configuration doesn't set their memory.
+pub(crate) const FUNCTION_MEMORY_SIZE: u64 = 4096;
+
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Number of ports that the server tries, starting from the invoke port,
+/// when the invoke port is in use.
+const PORT_FALLBACK_ATTEMPTS: u16 = 10;
+
 #[tracing::instrument(target = "cargo_lambda")]
 pub async fn run(
     config: &Watch,
@@ -74,80 +109,317 @@ pub async fn run(
 ) -> Result<()> {
     tracing::trace!("watching project");
 
-    let manifest_path = config.manifest_path();
+    let target_dir = target_dir(config, metadata)?;
+    let session = Arc::new(Session::start(&target_dir, config.kill_orphans)?);
 
-    let mut cargo_options = config.cargo_opts.clone();
-    cargo_options.color = Some(color.into());
-    if cargo_options.manifest_path.is_none() {
-        cargo_options.manifest_path = Some(manifest_path.clone());
+    let server = Server::prepare(config, base_env, metadata, color, Some(session.clone())).await?;
+    session.set_invoke_addr(server.invoke_addr)?;
+
+    if let Some(port_file) = &config.port_file {
+        write_port_file(port_file, server.invoke_addr)?;
     }
 
-    let base = dunce::canonicalize(".").into_diagnostic()?;
-    let ignore_files = watcher::ignore::discover_files(&base).await;
+    server.serve(None).await;
 
-    let env = config.lambda_environment(base_env).into_diagnostic()?;
+    if let Some(port_file) = &config.port_file {
+        let _ = std::fs::remove_file(port_file);
+    }
+    session.finish();
 
-    let package_filter = if !cargo_options.packages.is_empty() {
-        let packages = cargo_options.packages.clone();
-        Some(move |p: &&CargoPackage| packages.contains(&p.name))
-    } else {
-        None
-    };
+    Ok(())
+}
 
-    let binary_filter = if config.cargo_opts.bin.is_empty() {
-        Box::new(kind_bin_filter)
-    } else {
-        selected_bin_filter(config.cargo_opts.bin.clone())
-    };
+/// Server that runs in the background, started with [`spawn`].
+/// The server starts shutting down when the handle is dropped.
+#[derive(Debug)]
+pub struct ServerHandle {
+    invoke_addr: SocketAddr,
+    shutdown: CancellationToken,
+    task: Option<JoinHandle<()>>,
+}
 
-    let binary_packages =
-        filter_binary_targets_from_metadata(metadata, binary_filter, package_filter);
+impl ServerHandle {
+    /// Address where the server receives invoke requests.
+    pub fn invoke_addr(&self) -> SocketAddr {
+        self.invoke_addr
+    }
 
-    if binary_packages.is_empty() {
-        Err(ServerError::NoBinaryPackages)?;
+    /// Stop the server after the invocations in flight complete,
+    /// and wait until the functions' processes terminate.
+    pub async fn shutdown(mut self) -> Result<()> {
+        self.shutdown.cancel();
+        if let Some(task) = self.task.take() {
+            task.await
+                .into_diagnostic()
+                .wrap_err("the server didn't stop cleanly")?;
+        }
+        Ok(())
     }
+}
 
-    let watcher_config = WatcherConfig {
-        base,
-        ignore_files,
-        env,
-        ignore_changes: config.ignore_changes,
-        only_lambda_apis: config.only_lambda_apis,
-        manifest_path: manifest_path.clone(),
-        wait: config.wait,
-        ..Default::default()
-    };
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
 
-    let runtime_state = build_runtime_state(config, &manifest_path, binary_packages)?;
-
-    let disable_cors = config.disable_cors;
-    let timeout = config.timeout.clone();
-    let tls_options = config.tls_options.clone();
-
-    let _ = Toplevel::new(move |s| async move {
-        s.start(SubsystemBuilder::new("Lambda server", move |s| {
-            start_server(
-                s,
-                runtime_state,
-                cargo_options,
-                watcher_config,
-                tls_options,
-                disable_cors,
-                timeout,
-            )
-        }));
+/// Start the server in the background, and return when it accepts invoke requests.
+/// Unlike [`run`], the server doesn't handle the signals sent to the process,
+/// and it doesn't record a session in the target directory, so programs
+/// can run several servers at the same time.
+pub async fn spawn(
+    config: &Watch,
+    base_env: &HashMap<String, String>,
+    metadata: &CargoMetadata,
+) -> Result<ServerHandle> {
+    // Embedded servers don't go through the CLI, which
+    // installs the tracer with its tracing subscriber.
+    xray_tracer(config);
+
+    let server = Server::prepare(config, base_env, metadata, "auto", None).await?;
+    let invoke_addr = server.invoke_addr;
+
+    let shutdown = CancellationToken::new();
+    let task = tokio::spawn(server.serve(Some(shutdown.clone())));
+
+    Ok(ServerHandle {
+        invoke_addr,
+        shutdown,
+        task: Some(task),
     })
-    .catch_signals()
-    .handle_shutdown_requests(Duration::from_secs(1))
-    .await;
+}
 
-    Ok(())
+/// Server with its sockets bound, ready to run the functions.
+struct Server {
+    invoke_addr: SocketAddr,
+    runtime_state: RuntimeState,
+    cargo_options: CargoOptions,
+    watcher_config: WatcherConfig,
+    server_options: ServerOptions,
+    listeners: Listeners,
+}
+
+impl Server {
+    async fn prepare(
+        config: &Watch,
+        base_env: &HashMap<String, String>,
+        metadata: &CargoMetadata,
+        color: &str,
+        session: Option<Arc<Session>>,
+    ) -> Result<Server> {
+        let manifest_path = config.manifest_path();
+
+        let mut cargo_options = config.cargo_opts.clone();
+        cargo_options.color = Some(color.into());
+        if cargo_options.manifest_path.is_none() {
+            cargo_options.manifest_path = Some(manifest_path.clone());
+        }
+
+        let base = dunce::canonicalize(".").into_diagnostic()?;
+        let ignore_files = watcher::ignore::discover_files(&base).await;
+
+        let env = config.lambda_environment(base_env).into_diagnostic()?;
+
+        let bin_path = config
+            .bin_path
+            .as_deref()
+            .map(prebuilt_binary)
+            .transpose()?;
+        let binary_packages = match config.bin_path_function_name() {
+            Some(name) => HashSet::from([name]),
+            None => config.binary_selection().select(metadata)?,
+        };
+
+        if binary_packages.is_empty() {
+            Err(ServerError::NoBinaryPackages)?;
+        }
+
+        // Functions run with `cargo run --bin`, which fails when
+        // the binary requires features that are not enabled.
+        let missing_features = match bin_path {
+            Some(_) => Vec::new(),
+            None => missing_required_features(metadata, &binary_packages, &cargo_options.common),
+        };
+        for missing in missing_features {
+            info!(
+                binary = missing.binary,
+                features = ?missing.features,
+                "enabling the features that the binary requires"
+            );
+            cargo_options.features.extend(missing.qualified_features());
+        }
+
+        let mut binaries = load_binary_watch_configs(metadata)?;
+        binaries.retain(|name, _| binary_packages.contains(name));
+
+        let target_dir = target_dir(config, metadata)?;
+
+        let memory_size = memory_size(config);
+        let sandbox = Sandbox::new(
+            &config.sandbox_options,
+            memory_size,
+            config.cargo_opts.common.target.first().map(String::as_str),
+        )?;
+
+        let watcher_config = WatcherConfig {
+            base,
+            ignore_files,
+            ignore_globs: config.ignore.clone(),
+            env,
+            ignore_changes: config.ignore_changes,
+            only_lambda_apis: config.only_lambda_apis,
+            manifest_path: manifest_path.clone(),
+            wait: config.wait,
+            log_dir: config.log_dir(&target_dir),
+            log_prefix: log_prefix_config(config, &binary_packages, color),
+            session,
+            binaries: Arc::new(binaries.clone()),
+            bin_path,
+            memory_size,
+            sandbox,
+            ..Default::default()
+        };
+
+        let listeners = Listeners::bind(config).await?;
+        let invoke_addr = listeners.invoke_addr()?;
+        info!(%invoke_addr, "server listening, send invoke requests to this address");
+
+        let runtime_state = build_runtime_state(
+            config,
+            &manifest_path,
+            binary_packages,
+            &binaries,
+            &listeners,
+        )?;
+
+        let server_options = ServerOptions {
+            tls_options: config.tls_options.clone(),
+            disable_cors: config.disable_cors,
+            har_out: config.har_out.clone(),
+            timeout: config.timeout.clone(),
+            grace_period: config
+                .grace_period
+                .as_ref()
+                .map(|g| g.duration())
+                .unwrap_or(DEFAULT_GRACE_PERIOD),
+        };
+
+        Ok(Server {
+            invoke_addr,
+            runtime_state,
+            cargo_options,
+            watcher_config,
+            server_options,
+            listeners,
+        })
+    }
+
+    /// Run the server until it receives a shutdown signal, or until
+    /// the `shutdown` token is cancelled, when the server runs embedded.
+    async fn serve(self, shutdown: Option<CancellationToken>) {
+        let Server {
+            runtime_state,
+            cargo_options,
+            watcher_config,
+            server_options,
+            listeners,
+            ..
+        } = self;
+        let shutdown_timeout = server_options.grace_period + Duration::from_secs(5);
+        let catch_signals = shutdown.is_none();
+
+        let toplevel = Toplevel::new(move |s| async move {
+            if let Some(shutdown) = shutdown {
+                s.start(SubsystemBuilder::new(
+                    "shutdown token",
+                    move |s| async move {
+                        tokio::select! {
+                            _ = shutdown.cancelled() => s.request_shutdown(),
+                            _ = s.on_shutdown_requested() => {}
+                        }
+                        Ok::<(), Infallible>(())
+                    },
+                ));
+            }
+
+            s.start(SubsystemBuilder::new("Lambda server", move |s| {
+                start_server(
+                    s,
+                    runtime_state,
+                    cargo_options,
+                    watcher_config,
+                    server_options,
+                    listeners,
+                )
+            }));
+        });
+        let toplevel = if catch_signals {
+            toplevel.catch_signals()
+        } else {
+            toplevel
+        };
+
+        let _ = toplevel.handle_shutdown_requests(shutdown_timeout).await;
+    }
+}
+
+fn target_dir(config: &Watch, metadata: &CargoMetadata) -> Result<PathBuf> {
+    match &config.cargo_opts.common.target_dir {
+        Some(dir) => Ok(dir.clone()),
+        None => Ok(target_dir_from_metadata(metadata)?),
+    }
+}
+
+/// Memory size, in megabytes, that the functions report to the runtime.
+/// The sandbox kills the functions when they use more memory.
+fn memory_size(config: &Watch) -> u64 {
+    config
+        .sandbox_options
+        .memory
+        .as_ref()
+        .map(|memory| i32::from(memory) as u64)
+        .unwrap_or(FUNCTION_MEMORY_SIZE)
+}
+
+/// Absolute path of a binary built outside the server. The binary doesn't need
+/// to exist yet, the function starts when the binary is built.
+fn prebuilt_binary(path: &Path) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| miette::miette!("invalid binary path {path:?}"))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir = dunce::canonicalize(dir)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("the directory of the binary {path:?} doesn't exist"))?;
+
+    let path = dir.join(file_name);
+    if path.is_dir() {
+        return Err(miette::miette!(
+            "invalid binary path {path:?}, it's a directory"
+        ));
+    }
+    if !path.exists() {
+        warn!(
+            ?path,
+            "the binary doesn't exist yet, the function starts when it's built"
+        );
+    }
+    Ok(path)
 }
 
 pub fn xray_layer<S>(config: &Watch) -> OpenTelemetryLayer<S, Tracer>
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
 {
+    tracing_opentelemetry::layer().with_tracer(xray_tracer(config))
+}
+
+/// Install the global propagator and tracer that generate
+/// the X-Ray trace ids that the functions receive.
+fn xray_tracer(config: &Watch) -> Tracer {
     global::set_text_map_propagator(XrayPropagator::default());
 
     let builder = stdout::new_pipeline().with_trace_config(
@@ -155,39 +427,280 @@ where
             .with_sampler(trace::Sampler::AlwaysOn)
             .with_id_generator(trace::XrayIdGenerator::default()),
     );
-    let tracer = if config.print_traces {
+    if config.print_traces {
         builder.install_simple()
     } else {
         builder.with_writer(std::io::sink()).install_simple()
+    }
+}
+
+fn log_prefix_config(
+    config: &Watch,
+    binary_packages: &HashSet<String>,
+    color: &str,
+) -> Option<LogPrefixConfig> {
+    if !config.use_log_prefix(binary_packages.len()) {
+        return None;
+    }
+
+    let color = match color {
+        "always" => true,
+        "never" => false,
+        _ => std::io::stdout().is_terminal(),
     };
-    tracing_opentelemetry::layer().with_tracer(tracer)
+    let width = binary_packages.iter().map(|n| n.len()).max().unwrap_or(0);
+
+    Some(LogPrefixConfig { width, color })
 }
 
 fn build_runtime_state(
     config: &Watch,
     manifest_path: &Path,
     binary_packages: HashSet<String>,
+    binaries: &HashMap<String, WatchConfig>,
+    listeners: &Listeners,
 ) -> Result<RuntimeState> {
-    let ip = IpAddr::from_str(&config.invoke_address)
-        .into_diagnostic()
-        .wrap_err("invalid invoke address")?;
-    let (runtime_port, proxy_addr) = if config.tls_options.is_secure() {
-        (
-            config.invoke_port + 1,
-            Some(SocketAddr::from((ip, config.invoke_port))),
-        )
+    let runtime_addr = listeners.runtime.local_addr().into_diagnostic()?;
+    let proxy_addr = match &listeners.proxy {
+        Some(proxy) => Some(proxy.local_addr().into_diagnostic()?),
+        None => None,
+    };
+
+    let event_translator: RefEventTranslator = match &config.event_mapping {
+        Some(path) => Arc::new(MappingTranslator::load(path)?),
+        None => translator(
+            config.event_format(),
+            config.alb_options.alb_multi_value_headers,
+        ),
+    };
+
+    let openapi = match &config.openapi {
+        Some(path) => Some(Arc::new(OpenApiTranslator::load(
+            path,
+            event_translator.clone(),
+        )?)),
+        None => None,
+    };
+    let router = function_router(config, &binary_packages, binaries, openapi.as_deref())?;
+
+    let event_translator: RefEventTranslator = match openapi {
+        Some(openapi) => openapi,
+        None => event_translator,
+    };
+
+    let event_translator: RefEventTranslator = match &config.request_context {
+        Some(path) => Arc::new(RequestContextTranslator {
+            translator: event_translator,
+            context: RequestContext::load(path)?,
+        }),
+        None => event_translator,
+    };
+
+    let jwt_authorizer = if config.jwt_authorizer() {
+        Some(JwtAuthorizer::new(config.jwks.as_deref())?)
     } else {
-        (config.invoke_port, None)
+        None
     };
-    let runtime_addr = SocketAddr::from((ip, runtime_port));
 
     Ok(RuntimeState::new(
         runtime_addr,
         proxy_addr,
         manifest_path.to_path_buf(),
         binary_packages,
-        config.router.clone(),
-    ))
+        router,
+    )
+    .with_runtime_api_host(config.runtime_api_host()?)
+    .with_alb_options(config.alb_options.clone())
+    .with_rie_compatible(config.rie_compatible)
+    .with_invoke_queue_size(config.invoke_queue_size())
+    .with_reserved_concurrency(config.reserved_concurrency)
+    .with_event_translator(event_translator)
+    .with_invoke_hooks(InvokeHooks::new(config))
+    .with_jwt_authorizer(jwt_authorizer)
+    .with_faults(FaultInjector::new(
+        &config.fault_options,
+        memory_size(config),
+    )?))
+}
+
+/// Add the routes from the OpenAPI document, the routes from the `--route` flags,
+/// and the route prefixes from the watch metadata to the function router.
+/// The prefix in the package metadata applies when there is only one function.
+fn function_router(
+    config: &Watch,
+    binary_packages: &HashSet<String>,
+    binaries: &HashMap<String, WatchConfig>,
+    openapi: Option<&OpenApiTranslator>,
+) -> Result<Option<FunctionRouter>> {
+    let mut prefixes = binaries
+        .iter()
+        .filter_map(|(name, binary)| binary.route_prefix.as_deref().map(|p| (p, name.as_str())))
+        .collect::<Vec<_>>();
+    prefixes.sort();
+
+    if let Some(prefix) = &config.route_prefix {
+        match binary_packages.iter().next() {
+            Some(name) if binary_packages.len() == 1 => {
+                if binaries.get(name).is_none_or(|b| b.route_prefix.is_none()) {
+                    prefixes.push((prefix, name));
+                }
+            }
+            _ => warn!(
+                %prefix,
+                "ignoring the route prefix in the package metadata because there are several functions, set it in the metadata of each binary"
+            ),
+        }
+    }
+
+    let mut router = config.router.clone();
+    if let Some(openapi) = openapi {
+        openapi.insert_routes(router.get_or_insert_default())?;
+    }
+    if !config.route.is_empty() {
+        config.insert_routes(router.get_or_insert_default())?;
+    }
+    for (prefix, name) in prefixes {
+        router.get_or_insert_default().insert_prefix(prefix, name)?;
+    }
+    Ok(router)
+}
+
+/// Sockets where the server accepts connections. They are bound
+/// before the server starts, so the ports are known in advance
+/// when the system picks them.
+struct Listeners {
+    runtime: TcpListener,
+    proxy: Option<TcpListener>,
+}
+
+impl Listeners {
+    /// Bind the sockets to the invoke port. When the port is in use,
+    /// the server listens on the next port that's available,
+    /// unless the port must be the one that the user picked.
+    async fn bind(config: &Watch) -> Result<Listeners> {
+        let secure = config.tls_options.is_secure();
+        if let Some(listener) = activation::inherited_listener(config.listen_fd)? {
+            return Listeners::inherit(listener, secure).await;
+        }
+
+        let ip = IpAddr::from_str(&config.invoke_address)
+            .into_diagnostic()
+            .wrap_err("invalid invoke address")?;
+        let port = config.invoke_port();
+
+        // The system always picks a port that's available for the port `0`.
+        let last = if port == 0 || config.strict_port {
+            port
+        } else {
+            port.saturating_add(PORT_FALLBACK_ATTEMPTS - 1)
+        };
+
+        let mut candidate = port;
+        loop {
+            match Listeners::bind_port(ip, candidate, secure).await {
+                Ok(listeners) => {
+                    if candidate != port {
+                        warn!(
+                            port,
+                            invoke_port = candidate,
+                            "the invoke port is in use, the server is listening on port {candidate} instead"
+                        );
+                    }
+                    return Ok(listeners);
+                }
+                Err(err) if err.kind() == ErrorKind::AddrInUse && candidate < last => {
+                    debug!(port = candidate, "port in use, trying the next one");
+                    candidate += 1;
+                }
+                Err(err) => {
+                    let addr = SocketAddr::from((ip, candidate));
+                    let help = if err.kind() == ErrorKind::AddrInUse {
+                        "use `--invoke-port 0` to let the system pick a port that's available"
+                    } else {
+                        "check that the invoke address belongs to this system"
+                    };
+                    return Err(miette::miette!(
+                        help = help,
+                        "failed to bind the server to {addr}: {err}"
+                    ));
+                }
+            }
+        }
+    }
+
+    async fn bind_port(ip: IpAddr, port: u16, secure: bool) -> std::io::Result<Listeners> {
+        if !secure {
+            let runtime = TcpListener::bind(SocketAddr::from((ip, port))).await?;
+            return Ok(Listeners {
+                runtime,
+                proxy: None,
+            });
+        }
+
+        // The TLS proxy takes the invoke port, and the runtime listens on the next one.
+        // When the system picks the ports, the runtime gets a port of its own.
+        let proxy = TcpListener::bind(SocketAddr::from((ip, port))).await?;
+        let runtime_port = if port == 0 { 0 } else { port.saturating_add(1) };
+        let runtime = TcpListener::bind(SocketAddr::from((ip, runtime_port))).await?;
+
+        Ok(Listeners {
+            runtime,
+            proxy: Some(proxy),
+        })
+    }
+
+    /// Accept the invoke requests in a socket that's already listening.
+    /// With TLS, the runtime listens on a port that the system picks.
+    async fn inherit(listener: TcpListener, secure: bool) -> Result<Listeners> {
+        if !secure {
+            return Ok(Listeners {
+                runtime: listener,
+                proxy: None,
+            });
+        }
+
+        let ip = listener.local_addr().into_diagnostic()?.ip();
+        let runtime = TcpListener::bind(SocketAddr::from((ip, 0)))
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to bind the runtime server to {ip}"))?;
+        Ok(Listeners {
+            runtime,
+            proxy: Some(listener),
+        })
+    }
+
+    /// Address where users send invoke requests.
+    fn invoke_addr(&self) -> Result<SocketAddr> {
+        self.proxy
+            .as_ref()
+            .unwrap_or(&self.runtime)
+            .local_addr()
+            .into_diagnostic()
+    }
+}
+
+/// Write the port where users send invoke requests,
+/// so other tools can find the server.
+fn write_port_file(path: &Path, addr: SocketAddr) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent).into_diagnostic()?;
+        }
+    }
+
+    std::fs::write(path, addr.port().to_string())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write the port file {}", path.display()))
+}
+
+/// Options to configure how the HTTP server handles requests.
+struct ServerOptions {
+    tls_options: TlsOptions,
+    disable_cors: bool,
+    har_out: Option<PathBuf>,
+    timeout: Option<Timeout>,
+    grace_period: Duration,
 }
 
 async fn start_server(
@@ -195,36 +708,59 @@ async fn start_server(
     runtime_state: RuntimeState,
     cargo_options: CargoOptions,
     watcher_config: WatcherConfig,
-    tls_options: TlsOptions,
-    disable_cors: bool,
-    timeout: Option<Timeout>,
+    server_options: ServerOptions,
+    listeners: Listeners,
 ) -> Result<()> {
+    let ServerOptions {
+        tls_options,
+        disable_cors,
+        har_out,
+        timeout,
+        grace_period,
+    } = server_options;
     let only_lambda_apis = watcher_config.only_lambda_apis;
-    let init_default_function =
-        runtime_state.is_default_function_enabled() && watcher_config.send_function_init();
+    let memory_size = watcher_config.memory_size;
+    let init_functions = if watcher_config.send_function_init() {
+        init_function_names(&runtime_state)
+    } else {
+        Vec::new()
+    };
+    // Track the functions as starting before the server accepts
+    // requests, so the health endpoint doesn't report them ready.
+    for name in &init_functions {
+        runtime_state.reload.function_compiling(name);
+    }
 
-    let (runtime_addr, proxy_addr, runtime_url) = runtime_state.addresses();
+    let (runtime_addr, _, runtime_url) = runtime_state.addresses();
 
     let x_request_id = HeaderName::from_static("lambda-runtime-aws-request-id");
-    let req_tx = init_scheduler(
+    let (req_tx, scheduler) = init_scheduler(
         &subsys,
         runtime_state.clone(),
         cargo_options,
         watcher_config,
     );
 
+    let mut trigger_routes = trigger_router::routes();
+    if let Some(path) = &har_out {
+        let recorder = HarRecorder::start(path, tls_options.is_secure())?;
+        info!(?path, "recording the HTTP requests in HAR format");
+        trigger_routes = trigger_routes.layer(from_fn_with_state(recorder, har::record));
+    }
+
     let state_ref = Arc::new(runtime_state);
     let mut app = Router::new()
-        .merge(trigger_router::routes().with_state(state_ref.clone()))
+        .merge(control_router::routes().with_state(state_ref.clone()))
+        .merge(trigger_routes.with_state(state_ref.clone()))
         .nest(
             RUNTIME_EMULATOR_PATH,
             runtime::routes().with_state(state_ref.clone()),
         )
-        .layer(SetRequestIdLayer::new(
-            x_request_id.clone(),
-            MakeRequestUuid,
-        ))
-        .layer(PropagateRequestIdLayer::new(x_request_id))
+        // The request id is propagated to the response, so clients
+        // can correlate their requests with the invocation logs.
+        // This layer must wrap the one that sets the request id.
+        .layer(PropagateRequestIdLayer::new(x_request_id.clone()))
+        .layer(SetRequestIdLayer::new(x_request_id, MakeRequestUuid))
         .layer(Extension(req_tx.clone()))
         .layer(TraceLayer::new_for_http())
         .layer(CatchPanicLayer::new());
@@ -234,7 +770,7 @@ async fn start_server(
     if let Some(timeout) = timeout {
         app = app.layer(TimeoutLayer::new(timeout.duration()));
     }
-    let app = app.with_state(state_ref);
+    let app = app.with_state(state_ref.clone());
 
     if only_lambda_apis {
         info!("");
@@ -246,17 +782,18 @@ async fn start_server(
             "you MUST set these variables in the environment where you're running your function:"
         );
         info!("AWS_LAMBDA_FUNCTION_VERSION=1");
-        info!("AWS_LAMBDA_FUNCTION_MEMORY_SIZE=4096");
+        info!("AWS_LAMBDA_FUNCTION_MEMORY_SIZE={memory_size}");
         info!("AWS_LAMBDA_RUNTIME_API={}", runtime_url);
         info!("AWS_LAMBDA_FUNCTION_NAME={DEFAULT_PACKAGE_FUNCTION}");
     } else {
-        let print_start_info = if init_default_function {
+        let mut print_start_info = init_functions.is_empty();
+        for name in init_functions {
             // This call ignores any error sending the action.
             // The function can still be lazy loaded later if there is any error.
-            req_tx.send(Action::Init).await.is_err()
-        } else {
-            false
-        };
+            if req_tx.send(Action::Init(name)).await.is_err() {
+                print_start_info = true;
+            }
+        }
 
         if print_start_info {
             info!("");
@@ -269,23 +806,49 @@ async fn start_server(
     let tls_config = tls_options.server_config()?;
     let tls_tracker = TaskTracker::new();
 
-    if let (Some(tls_config), Some(proxy_addr)) = (tls_config, proxy_addr) {
+    if let (Some(tls_config), Some(proxy)) = (tls_config, listeners.proxy) {
         let tls_tracker = tls_tracker.clone();
 
         subsys.start(SubsystemBuilder::new("TLS proxy", move |s| async move {
-            start_tls_proxy(s, tls_tracker, tls_config, proxy_addr, runtime_addr).await
+            start_tls_proxy(s, tls_tracker, tls_config, proxy, runtime_addr).await
         }));
     }
 
+    // The runtime APIs must keep working while the invocations in flight
+    // are drained, so the server only stops after the functions are terminated.
+    let server_shutdown = CancellationToken::new();
+    let server_stop = server_shutdown.clone();
+
     info!(?runtime_addr, "starting Runtime server");
-    let out = axum::serve(
-        TcpListener::bind(runtime_addr).await.into_diagnostic()?,
-        app.into_make_service(),
-    )
-    .with_graceful_shutdown(async move {
-        subsys.on_shutdown_requested().await;
-    })
-    .await;
+    let server = axum::serve(listeners.runtime, app.into_make_service())
+        .with_graceful_shutdown(async move {
+            server_stop.cancelled().await;
+        })
+        .into_future();
+    pin!(server);
+
+    let out = tokio::select! {
+        out = &mut server => Some(out),
+        _ = subsys.on_shutdown_requested() => None,
+    };
+
+    let out = match out {
+        Some(out) => out,
+        None => {
+            drain_invocations(&state_ref, grace_period).await;
+
+            scheduler.initiate_shutdown();
+            if let Err(error) = scheduler.join().await {
+                error!(error = ?error, "failed to stop the lambda scheduler");
+            }
+
+            server_shutdown.cancel();
+            match tokio::time::timeout(Duration::from_secs(1), server).await {
+                Ok(out) => out,
+                Err(_) => Ok(()),
+            }
+        }
+    };
 
     if let Err(error) = out {
         error!(error = ?error, "failed to serve HTTP requests");
@@ -297,13 +860,46 @@ async fn start_server(
     Ok(())
 }
 
+/// Functions to compile and start when the server starts, so the first
+/// invocations don't wait for them. Single binary projects start the
+/// default function, multi binary projects start all the selected binaries.
+fn init_function_names(state: &RuntimeState) -> Vec<String> {
+    if state.is_default_function_enabled() {
+        return vec![DEFAULT_PACKAGE_FUNCTION.to_string()];
+    }
+
+    let mut names = state.initial_functions.iter().cloned().collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// Stop accepting new invocations, and wait for the invocations in flight
+/// to complete, up to the grace period.
+async fn drain_invocations(state: &RuntimeState, grace_period: Duration) {
+    info!(
+        ?grace_period,
+        "waiting for invocations in flight to complete before shutting down"
+    );
+
+    if tokio::time::timeout(grace_period, state.drain_invocations())
+        .await
+        .is_err()
+    {
+        warn!(
+            ?grace_period,
+            "the grace period expired before all invocations completed"
+        );
+    }
+}
+
 async fn start_tls_proxy(
     subsys: SubsystemHandle,
     connection_tracker: TaskTracker,
     tls_config: ServerConfig,
-    proxy_addr: SocketAddr,
+    listener: TcpListener,
     runtime_addr: SocketAddr,
 ) -> Result<()> {
+    let proxy_addr = listener.local_addr().into_diagnostic()?;
     info!(
         ?proxy_addr,
         "starting TLS server, use this address to send secure requests to the runtime"
@@ -311,12 +907,13 @@ async fn start_tls_proxy(
 
     let acceptor = TlsAcceptor::from(Arc::new(tls_config));
 
-    let listener = TcpListener::bind(proxy_addr).await.into_diagnostic()?;
-
     let addr = Arc::new(runtime_addr);
 
     loop {
-        let (stream, _) = listener.accept().await.into_diagnostic()?;
+        let (stream, _) = tokio::select! {
+            res = listener.accept() => res.into_diagnostic()?,
+            _ = subsys.on_shutdown_requested() => return Ok(()),
+        };
         let acceptor = acceptor.clone();
 
         let addr = addr.clone();
@@ -384,3 +981,79 @@ async fn proxy(
     let resp = sender.send_request(req).await?;
     Ok(resp.map(|b| b.boxed()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watch_config(port: u16, strict_port: bool) -> Watch {
+        Watch {
+            invoke_address: "127.0.0.1".into(),
+            invoke_port: Some(port),
+            strict_port,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_next_available_port() {
+        let busy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = busy.local_addr().unwrap().port();
+
+        let listeners = Listeners::bind(&watch_config(port, false)).await.unwrap();
+        let invoke_addr = listeners.invoke_addr().unwrap();
+        assert_ne!(port, invoke_addr.port());
+        assert!(invoke_addr.port() > port);
+        assert!(invoke_addr.port() < port.saturating_add(PORT_FALLBACK_ATTEMPTS));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bind_inherited_listener() {
+        use std::os::fd::IntoRawFd;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = Watch {
+            listen_fd: Some(listener.into_raw_fd()),
+            ..watch_config(9000, true)
+        };
+
+        let listeners = Listeners::bind(&config).await.unwrap();
+        assert_eq!(addr, listeners.invoke_addr().unwrap());
+        assert!(listeners.proxy.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bind_strict_port() {
+        let busy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = busy.local_addr().unwrap().port();
+
+        let err = Listeners::bind(&watch_config(port, true))
+            .await
+            .err()
+            .expect("the port is in use");
+        assert!(
+            err.to_string()
+                .starts_with(&format!("failed to bind the server to 127.0.0.1:{port}")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_prebuilt_binary() {
+        let dir = tempfile::tempdir().unwrap();
+        let function_dir = dir.path().join("orders");
+        std::fs::create_dir(&function_dir).unwrap();
+
+        let path = prebuilt_binary(&function_dir.join("bootstrap")).unwrap();
+        assert!(path.is_absolute());
+        assert!(path.ends_with("orders/bootstrap"));
+
+        let err = prebuilt_binary(&function_dir).unwrap_err();
+        assert!(err.to_string().contains("it's a directory"), "{err}");
+
+        let err = prebuilt_binary(&dir.path().join("missing/bootstrap")).unwrap_err();
+        assert!(err.to_string().contains("doesn't exist"), "{err}");
+    }
+}
@@ -0,0 +1,498 @@
+use cargo_lambda_metadata::{
+    cargo::watch::{EventFormat, FunctionRouter, router_path},
+    error::MetadataError,
+};
+use matchit::Router;
+use miette::{IntoDiagnostic, Result, WrapErr, miette};
+use serde_json::{Value, json};
+use std::{collections::HashMap, path::Path};
+use tracing::{debug, warn};
+
+use crate::translators::{EventTranslator, HttpRequest, RefEventTranslator, translator};
+
+/// Extension with the integration of an operation in API Gateway.
+const INTEGRATION_EXTENSION: &str = "x-amazon-apigateway-integration";
+/// Extension with the authorizer of a security scheme in API Gateway.
+const AUTHORIZER_EXTENSION: &str = "x-amazon-apigateway-authorizer";
+/// Extension that marks security schemes that use IAM authorization.
+const AUTHTYPE_EXTENSION: &str = "x-amazon-apigateway-authtype";
+/// Path item key for operations that match any method.
+const ANY_METHOD: &str = "x-amazon-apigateway-any-method";
+
+const METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Principal that the authorizer stubs report as the caller.
+const PRINCIPAL_ID: &str = "cargo-lambda";
+
+/// Authorizers that API Gateway runs before invoking a function.
+/// The events include stubs with the fields that they add to the request context.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Authorizer {
+    Lambda,
+    Jwt,
+    Cognito,
+    Iam,
+}
+
+impl Authorizer {
+    fn from_scheme(scheme: &Value) -> Option<Authorizer> {
+        if let Some(authorizer) = scheme.get(AUTHORIZER_EXTENSION) {
+            let kind = authorizer.get("type").and_then(Value::as_str)?;
+            return match kind.to_ascii_lowercase().as_str() {
+                "token" | "request" => Some(Authorizer::Lambda),
+                "jwt" => Some(Authorizer::Jwt),
+                "cognito_user_pools" => Some(Authorizer::Cognito),
+                _ => None,
+            };
+        }
+
+        let authtype = scheme.get(AUTHTYPE_EXTENSION).and_then(Value::as_str)?;
+        authtype
+            .eq_ignore_ascii_case("awsSigv4")
+            .then_some(Authorizer::Iam)
+    }
+
+    /// Fields that the authorizer adds to events with the payload format version 1.0.
+    fn rest_context(&self) -> Option<Value> {
+        match self {
+            Authorizer::Lambda => Some(json!({
+                "principalId": PRINCIPAL_ID,
+                "integrationLatency": 0,
+            })),
+            Authorizer::Cognito => Some(json!({ "claims": { "sub": PRINCIPAL_ID } })),
+            Authorizer::Jwt | Authorizer::Iam => None,
+        }
+    }
+
+    /// Fields that the authorizer adds to events with the payload format version 2.0.
+    fn http_context(&self) -> Value {
+        match self {
+            Authorizer::Lambda => json!({ "lambda": { "principalId": PRINCIPAL_ID } }),
+            Authorizer::Jwt | Authorizer::Cognito => json!({
+                "jwt": { "claims": { "sub": PRINCIPAL_ID }, "scopes": [] }
+            }),
+            Authorizer::Iam => json!({
+                "iam": { "accountId": "123456789012", "userId": PRINCIPAL_ID }
+            }),
+        }
+    }
+}
+
+/// Operation in an OpenAPI document that API Gateway sends to a function.
+struct OpenApiRoute {
+    /// HTTP method of the operation, `None` matches any method
+    method: Option<String>,
+    /// Path of the operation in the document, like `/users/{id}`
+    resource: String,
+    function: String,
+    format: EventFormat,
+    authorizer: Option<Authorizer>,
+    translator: RefEventTranslator,
+}
+
+impl OpenApiRoute {
+    fn route_key(&self) -> String {
+        let method = self.method.as_deref().unwrap_or("ANY");
+        format!("{method} {}", self.resource)
+    }
+}
+
+/// Routes from an OpenAPI document with API Gateway extensions.
+/// It builds the events that API Gateway sends for each route,
+/// and it uses the fallback translator for other requests.
+pub(crate) struct OpenApiTranslator {
+    /// Routes grouped by their path in the router
+    routes: Vec<(String, Vec<OpenApiRoute>)>,
+    /// Router with the position of the routes for each path
+    router: Router<usize>,
+    stage: String,
+    fallback: RefEventTranslator,
+}
+
+impl OpenApiTranslator {
+    pub(crate) fn load(path: &Path, fallback: RefEventTranslator) -> Result<OpenApiTranslator> {
+        let content = std::fs::read_to_string(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read the OpenAPI document {}", path.display()))?;
+        // YAML is a superset of JSON, so this parses documents in both formats.
+        let document: Value = serde_yaml::from_str(&content)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("invalid OpenAPI document {}", path.display()))?;
+        Self::from_document(&document, fallback)
+            .wrap_err_with(|| format!("invalid OpenAPI document {}", path.display()))
+    }
+
+    fn from_document(document: &Value, fallback: RefEventTranslator) -> Result<OpenApiTranslator> {
+        let paths = document
+            .get("paths")
+            .and_then(Value::as_object)
+            .ok_or_else(|| miette!("the document doesn't have any paths"))?;
+
+        let schemes = security_schemes(document);
+        let default_security = document.get("security");
+
+        let mut grouped: Vec<(String, Vec<OpenApiRoute>)> = Vec::new();
+        for (resource, item) in paths {
+            let Some(item) = item.as_object() else {
+                continue;
+            };
+
+            for (key, operation) in item {
+                let method = if key == ANY_METHOD {
+                    None
+                } else if METHODS.contains(&key.as_str()) {
+                    Some(key.to_ascii_uppercase())
+                } else {
+                    continue;
+                };
+
+                let Some(integration) = operation.get(INTEGRATION_EXTENSION) else {
+                    debug!(%resource, %key, "skipping operation without API Gateway integration");
+                    continue;
+                };
+
+                let kind = integration
+                    .get("type")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                if !kind.eq_ignore_ascii_case("aws_proxy") {
+                    warn!(%resource, %key, integration = %kind, "skipping operation because only Lambda proxy integrations are supported");
+                    continue;
+                }
+
+                let Some(function) = integration.get("uri").and_then(function_from_uri) else {
+                    warn!(%resource, %key, "skipping operation because the function name cannot be found in the integration uri, use the flag `--route` to send its requests to a function");
+                    continue;
+                };
+
+                let format = match integration
+                    .get("payloadFormatVersion")
+                    .and_then(Value::as_str)
+                {
+                    Some("2.0") => EventFormat::ApigwHttp,
+                    _ => EventFormat::ApigwRest,
+                };
+
+                let security = operation.get("security").or(default_security);
+                let authorizer = security.and_then(|s| authorizer(s, &schemes));
+
+                let route = OpenApiRoute {
+                    method,
+                    resource: resource.clone(),
+                    function,
+                    format,
+                    authorizer,
+                    translator: translator(format, false),
+                };
+
+                let path = router_path(resource);
+                match grouped.iter_mut().find(|(p, _)| *p == path) {
+                    Some((_, routes)) => routes.push(route),
+                    None => grouped.push((path, vec![route])),
+                }
+            }
+        }
+
+        let mut router = Router::new();
+        for (idx, (path, _)) in grouped.iter().enumerate() {
+            router
+                .insert(path, idx)
+                .map_err(|e| MetadataError::InvalidRouterPath(path.clone(), e))?;
+        }
+
+        Ok(OpenApiTranslator {
+            routes: grouped,
+            router,
+            stage: stage(document).unwrap_or_else(|| "$default".into()),
+            fallback,
+        })
+    }
+
+    /// Add the routes to the function router, so the requests
+    /// for each operation are sent to its function.
+    pub(crate) fn insert_routes(&self, router: &mut FunctionRouter) -> Result<(), MetadataError> {
+        let mut routes = self
+            .routes
+            .iter()
+            .flat_map(|(path, group)| group.iter().map(move |route| (path.as_str(), route)))
+            .collect::<Vec<_>>();
+        // Operations for any method go first, so they don't replace the ones for a single method.
+        routes.sort_by_key(|(path, route)| (*path, route.method.is_some()));
+
+        for (path, route) in routes {
+            router.insert_route(route.method.as_deref(), path, &route.function)?;
+        }
+        Ok(())
+    }
+
+    fn find(&self, req: &HttpRequest<'_>) -> Option<&OpenApiRoute> {
+        let matched = self.router.at(req.path).ok()?;
+        let method = req.parts.method.as_str();
+
+        let (_, group) = &self.routes[*matched.value];
+        let candidates = group
+            .iter()
+            .filter(|route| route.function == req.function_name);
+        let mut any = None;
+        for route in candidates {
+            match route.method.as_deref() {
+                Some(m) if m == method => return Some(route),
+                None => any = Some(route),
+                _ => {}
+            }
+        }
+        any
+    }
+}
+
+impl EventTranslator for OpenApiTranslator {
+    fn translate(&self, req: &HttpRequest<'_>) -> serde_json::Result<Value> {
+        let Some(route) = self.find(req) else {
+            return self.fallback.translate(req);
+        };
+
+        let mut event = route.translator.translate(req)?;
+        match route.format {
+            EventFormat::ApigwHttp => {
+                let route_key = route.route_key();
+                event["routeKey"] = json!(route_key);
+                event["requestContext"]["routeKey"] = json!(route_key);
+                event["requestContext"]["stage"] = json!(self.stage);
+                if let Some(authorizer) = route.authorizer {
+                    event["requestContext"]["authorizer"] = authorizer.http_context();
+                }
+            }
+            _ => {
+                event["resource"] = json!(route.resource);
+                event["requestContext"]["resourcePath"] = json!(route.resource);
+                event["requestContext"]["stage"] = json!(self.stage);
+                if let Some(context) = route.authorizer.and_then(|a| a.rest_context()) {
+                    event["requestContext"]["authorizer"] = context;
+                }
+            }
+        }
+
+        Ok(event)
+    }
+}
+
+/// Authorizers for the security schemes in OpenAPI 3 and Swagger 2 documents.
+fn security_schemes(document: &Value) -> HashMap<String, Authorizer> {
+    let schemes = document
+        .pointer("/components/securitySchemes")
+        .or_else(|| document.get("securityDefinitions"))
+        .and_then(Value::as_object);
+
+    schemes
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, scheme)| Some((name.clone(), Authorizer::from_scheme(scheme)?)))
+        .collect()
+}
+
+/// Authorizer of the first security requirement that API Gateway handles.
+fn authorizer(security: &Value, schemes: &HashMap<String, Authorizer>) -> Option<Authorizer> {
+    security
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|requirement| requirement.keys())
+        .find_map(|name| schemes.get(name).copied())
+}
+
+/// Stage from the `basePath` variable of the first server in OpenAPI 3
+/// documents exported from API Gateway, or from the `basePath` in Swagger 2 documents.
+fn stage(document: &Value) -> Option<String> {
+    let base_path = document
+        .pointer("/servers/0/variables/basePath/default")
+        .or_else(|| document.get("basePath"))
+        .and_then(Value::as_str)?;
+
+    let stage = base_path.trim_matches('/');
+    (!stage.is_empty()).then(|| stage.to_string())
+}
+
+/// Function name from the uri of a Lambda integration, like
+/// `arn:aws:apigateway:us-east-1:lambda:path/2015-03-31/functions/arn:aws:lambda:us-east-1:123456789012:function:get-user/invocations`.
+/// CloudFormation templates can wrap the uri in a `Fn::Sub` function.
+fn function_from_uri(uri: &Value) -> Option<String> {
+    let uri = match uri {
+        Value::String(uri) => uri.as_str(),
+        Value::Object(function) => function.get("Fn::Sub").and_then(Value::as_str)?,
+        _ => return None,
+    };
+
+    let (_, function) = uri.split_once(":function:")?;
+    let name = function
+        .split(['/', ':'])
+        .next()
+        .filter(|name| !name.is_empty() && !name.contains('$'))?;
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{Method, Request, request::Parts};
+    use chrono::Utc;
+
+    const DOCUMENT: &str = r#"
+openapi: "3.0.1"
+servers:
+  - url: "https://{restapi_id}.execute-api.us-east-1.amazonaws.com/{basePath}"
+    variables:
+      basePath:
+        default: "/prod"
+components:
+  securitySchemes:
+    token:
+      type: apiKey
+      name: Authorization
+      in: header
+      x-amazon-apigateway-authorizer:
+        type: token
+paths:
+  /users/{id}:
+    get:
+      security:
+        - token: []
+      x-amazon-apigateway-integration:
+        type: aws_proxy
+        httpMethod: POST
+        uri: "arn:aws:apigateway:us-east-1:lambda:path/2015-03-31/functions/arn:aws:lambda:us-east-1:123456789012:function:get-user/invocations"
+  /orders/{proxy+}:
+    x-amazon-apigateway-any-method:
+      x-amazon-apigateway-integration:
+        type: AWS_PROXY
+        payloadFormatVersion: "2.0"
+        uri:
+          Fn::Sub: "arn:aws:apigateway:${AWS::Region}:lambda:path/2015-03-31/functions/arn:aws:lambda:${AWS::Region}:${AWS::AccountId}:function:orders/invocations"
+    post:
+      x-amazon-apigateway-integration:
+        type: aws_proxy
+        payloadFormatVersion: "2.0"
+        uri: "arn:aws:lambda:us-east-1:123456789012:function:create-order:live/invocations"
+  /health:
+    get:
+      x-amazon-apigateway-integration:
+        type: mock
+"#;
+
+    fn load() -> OpenApiTranslator {
+        let document: Value = serde_yaml::from_str(DOCUMENT).unwrap();
+        OpenApiTranslator::from_document(&document, translator(EventFormat::FunctionUrl, false))
+            .unwrap()
+    }
+
+    fn request_parts(method: Method, uri: &str) -> Parts {
+        let (parts, _) = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(())
+            .unwrap()
+            .into_parts();
+        parts
+    }
+
+    fn translate(
+        translator: &OpenApiTranslator,
+        function_name: &str,
+        parts: &Parts,
+        params: HashMap<String, String>,
+    ) -> Value {
+        let req = HttpRequest {
+            function_name,
+            parts,
+            path: parts.uri.path(),
+            path_parameters: params,
+            body: None,
+            is_base64_encoded: false,
+            request_id: "request-id",
+            time: Utc::now(),
+        };
+        translator.translate(&req).unwrap()
+    }
+
+    #[test]
+    fn test_function_from_uri() {
+        let uri = json!(
+            "arn:aws:apigateway:us-east-1:lambda:path/2015-03-31/functions/arn:aws:lambda:us-east-1:123456789012:function:get-user/invocations"
+        );
+        assert_eq!(function_from_uri(&uri), Some("get-user".to_string()));
+
+        let uri = json!({ "Fn::Sub": "arn:aws:apigateway:${AWS::Region}:lambda:path/2015-03-31/functions/${GetUser.Arn}/invocations" });
+        assert_eq!(function_from_uri(&uri), None);
+
+        let uri = json!({ "Fn::GetAtt": ["GetUser", "Arn"] });
+        assert_eq!(function_from_uri(&uri), None);
+    }
+
+    #[test]
+    fn test_insert_routes() {
+        let translator = load();
+        let mut router = FunctionRouter::default();
+        translator.insert_routes(&mut router).unwrap();
+
+        let (function, params) = router.at("/users/1", "GET").unwrap();
+        assert_eq!(function, "get-user");
+        assert_eq!(params, HashMap::from([("id".into(), "1".into())]));
+
+        let (function, params) = router.at("/orders/1/items", "GET").unwrap();
+        assert_eq!(function, "orders");
+        assert_eq!(params, HashMap::from([("proxy".into(), "1/items".into())]));
+
+        let (function, _) = router.at("/orders/1", "POST").unwrap();
+        assert_eq!(function, "create-order");
+
+        assert!(router.at("/users/1", "DELETE").is_err());
+        assert!(router.at("/health", "GET").is_err());
+    }
+
+    #[test]
+    fn test_rest_api_event() {
+        let translator = load();
+        let parts = request_parts(Method::GET, "/users/1");
+        let params = HashMap::from([("id".into(), "1".into())]);
+        let event = translate(&translator, "get-user", &parts, params);
+
+        assert_eq!(event["resource"], "/users/{id}");
+        assert_eq!(event["path"], "/users/1");
+        assert_eq!(event["pathParameters"]["id"], "1");
+        assert_eq!(event["requestContext"]["resourcePath"], "/users/{id}");
+        assert_eq!(event["requestContext"]["stage"], "prod");
+        assert_eq!(
+            event["requestContext"]["authorizer"]["principalId"],
+            PRINCIPAL_ID
+        );
+    }
+
+    #[test]
+    fn test_http_api_event() {
+        let translator = load();
+        let parts = request_parts(Method::DELETE, "/orders/1");
+        let params = HashMap::from([("proxy".into(), "1".into())]);
+        let event = translate(&translator, "orders", &parts, params);
+
+        assert_eq!(event["version"], "2.0");
+        assert_eq!(event["routeKey"], "ANY /orders/{proxy+}");
+        assert_eq!(event["requestContext"]["routeKey"], "ANY /orders/{proxy+}");
+        assert_eq!(event["requestContext"]["stage"], "prod");
+        assert_eq!(event["pathParameters"]["proxy"], "1");
+        assert!(event["requestContext"]["authorizer"].is_null());
+
+        let parts = request_parts(Method::POST, "/orders/1");
+        let event = translate(&translator, "create-order", &parts, HashMap::new());
+        assert_eq!(event["routeKey"], "POST /orders/{proxy+}");
+    }
+
+    #[test]
+    fn test_fallback_event() {
+        let translator = load();
+        let parts = request_parts(Method::GET, "/lambda-url/get-user");
+        let event = translate(&translator, "get-user", &parts, HashMap::new());
+        assert_eq!(event["routeKey"], "$default");
+        assert_eq!(event["requestContext"]["stage"], "$default");
+    }
+}
@@ -0,0 +1,233 @@
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use tracing::debug;
+
+/// Number of events kept for clients that fall behind.
+/// Slow clients skip the events that they missed.
+const RELOAD_EVENTS_CAPACITY: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ReloadStatus {
+    /// The function's code changed, and it's being compiled again
+    Rebuilding,
+    /// The function started and it's waiting for invocations
+    Ready,
+}
+
+/// Whether a function can process invocations, reported by the health endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FunctionHealth {
+    /// The function is compiling, or initializing after its process started
+    Starting,
+    /// The function asked for an invocation after its process started
+    Ready,
+    /// The function is not running, it starts with the first invocation
+    Idle,
+    /// The function's process exited before it asked for an invocation,
+    /// like when it fails to compile or to initialize
+    Failed,
+}
+
+/// Event sent to the clients of the reload endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ReloadEvent {
+    pub function: String,
+    pub status: ReloadStatus,
+}
+
+/// Notify clients, like front-end dev servers, when the functions
+/// are rebuilt, so they can reload after the backend starts again.
+#[derive(Debug)]
+pub(crate) struct ReloadNotifier {
+    tx: Sender<ReloadEvent>,
+    /// Functions whose process started, but that haven't asked for an invocation yet
+    starting: Mutex<HashSet<String>>,
+    /// Health of the functions that have been started
+    health: Mutex<HashMap<String, FunctionHealth>>,
+}
+
+impl Default for ReloadNotifier {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(RELOAD_EVENTS_CAPACITY);
+        ReloadNotifier {
+            tx,
+            starting: Mutex::default(),
+            health: Mutex::default(),
+        }
+    }
+}
+
+impl ReloadNotifier {
+    pub(crate) fn subscribe(&self) -> Receiver<ReloadEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Notify that the function's code changed and it's compiling again.
+    pub(crate) fn function_rebuilding(&self, name: &str) {
+        self.set_health(name, FunctionHealth::Starting);
+        self.send(name, ReloadStatus::Rebuilding);
+    }
+
+    /// Track that the function is compiling, before its process starts.
+    pub(crate) fn function_compiling(&self, name: &str) {
+        self.set_health(name, FunctionHealth::Starting);
+    }
+
+    /// Track that the function's process started. The function is ready
+    /// when it asks for its first invocation, after its initialization.
+    pub(crate) fn function_started(&self, name: &str) {
+        self.starting.lock().unwrap().insert(name.to_string());
+        self.set_health(name, FunctionHealth::Starting);
+    }
+
+    /// Notify that the function is ready, the first time that it asks
+    /// for an invocation after its process starts. Functions that Cargo Lambda
+    /// doesn't start, like with `--only-lambda-apis`, are ready when they ask for one.
+    pub(crate) fn function_ready(&self, name: &str) {
+        if self.starting.lock().unwrap().remove(name) {
+            self.set_health(name, FunctionHealth::Ready);
+            self.send(name, ReloadStatus::Ready);
+        } else {
+            self.health
+                .lock()
+                .unwrap()
+                .entry(name.to_string())
+                .or_insert(FunctionHealth::Ready);
+        }
+    }
+
+    /// Track that the function's process exited. It failed when it
+    /// exited before asking for its first invocation.
+    pub(crate) fn function_exited(&self, name: &str) {
+        if self.starting.lock().unwrap().remove(name) {
+            self.set_health(name, FunctionHealth::Failed);
+        }
+    }
+
+    /// Health of the function, Idle when it has not been started.
+    pub(crate) fn function_health(&self, name: &str) -> FunctionHealth {
+        self.health
+            .lock()
+            .unwrap()
+            .get(name)
+            .copied()
+            .unwrap_or(FunctionHealth::Idle)
+    }
+
+    fn set_health(&self, name: &str, health: FunctionHealth) {
+        self.health.lock().unwrap().insert(name.to_string(), health);
+    }
+
+    fn send(&self, name: &str, status: ReloadStatus) {
+        let event = ReloadEvent {
+            function: name.to_string(),
+            status,
+        };
+        // Sending only fails when there are no clients listening.
+        if self.tx.send(event).is_err() {
+            debug!(function = ?name, ?status, "no clients listening for reload events");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload_events() {
+        let notifier = ReloadNotifier::default();
+        // Events without clients are dropped.
+        notifier.function_rebuilding("basic-lambda");
+
+        let mut rx = notifier.subscribe();
+        notifier.function_rebuilding("basic-lambda");
+        notifier.function_ready("basic-lambda");
+        notifier.function_started("basic-lambda");
+        notifier.function_ready("basic-lambda");
+        notifier.function_ready("basic-lambda");
+
+        assert_eq!(
+            ReloadEvent {
+                function: "basic-lambda".into(),
+                status: ReloadStatus::Rebuilding,
+            },
+            rx.try_recv().unwrap()
+        );
+        assert_eq!(
+            ReloadEvent {
+                function: "basic-lambda".into(),
+                status: ReloadStatus::Ready,
+            },
+            rx.try_recv().unwrap()
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_function_health() {
+        let notifier = ReloadNotifier::default();
+        assert_eq!(
+            FunctionHealth::Idle,
+            notifier.function_health("basic-lambda")
+        );
+
+        notifier.function_compiling("basic-lambda");
+        assert_eq!(
+            FunctionHealth::Starting,
+            notifier.function_health("basic-lambda")
+        );
+
+        // The function is not ready until its process starts.
+        notifier.function_ready("basic-lambda");
+        assert_eq!(
+            FunctionHealth::Starting,
+            notifier.function_health("basic-lambda")
+        );
+
+        notifier.function_started("basic-lambda");
+        notifier.function_ready("basic-lambda");
+        assert_eq!(
+            FunctionHealth::Ready,
+            notifier.function_health("basic-lambda")
+        );
+
+        // Exiting after asking for invocations is not a failure.
+        notifier.function_exited("basic-lambda");
+        assert_eq!(
+            FunctionHealth::Ready,
+            notifier.function_health("basic-lambda")
+        );
+
+        notifier.function_rebuilding("basic-lambda");
+        assert_eq!(
+            FunctionHealth::Starting,
+            notifier.function_health("basic-lambda")
+        );
+
+        // The compilation failed, and the process exited before it was ready.
+        notifier.function_started("basic-lambda");
+        notifier.function_exited("basic-lambda");
+        assert_eq!(
+            FunctionHealth::Failed,
+            notifier.function_health("basic-lambda")
+        );
+
+        // Processes that Cargo Lambda doesn't start are ready when they ask for invocations.
+        assert_eq!(
+            FunctionHealth::Idle,
+            notifier.function_health("other-lambda")
+        );
+        notifier.function_ready("other-lambda");
+        assert_eq!(
+            FunctionHealth::Ready,
+            notifier.function_health("other-lambda")
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use axum::{
     body::Body,
-    http::{Request, StatusCode},
+    http::{Request, StatusCode, request::Parts},
 };
+use bytes::Bytes;
 use hyper::HeaderMap;
 use serde::{Deserialize, Serialize};
 use tokio::sync::oneshot::Sender;
@@ -13,10 +14,52 @@ pub(crate) const AWS_XRAY_TRACE_HEADER: &str = "x-amzn-trace-id";
 /// model, this response is represented as a HTTP Request data object.
 pub type LambdaResponse = Request<Body>;
 
+/// Response for invocations rejected because the function's queue is full.
+/// It mirrors the error that Lambda returns when it throttles an invocation.
+pub(crate) fn throttled_response() -> LambdaResponse {
+    throttled(serde_json::json!({
+        "Type": "User",
+        "message": "Rate Exceeded.",
+    }))
+}
+
+/// Response for invocations rejected because the function
+/// has as many invocations in flight as its reserved concurrency.
+pub(crate) fn concurrency_throttled_response() -> LambdaResponse {
+    throttled(serde_json::json!({
+        "Reason": "ReservedFunctionConcurrentInvocationLimitExceeded",
+        "Type": "User",
+        "message": "Rate Exceeded.",
+    }))
+}
+
+fn throttled(body: serde_json::Value) -> LambdaResponse {
+    let mut resp = LambdaResponse::new(Body::from(body.to_string()));
+    resp.extensions_mut().insert(StatusCode::TOO_MANY_REQUESTS);
+    resp
+}
+
+/// Response for invocations that fail before the function can respond,
+/// with the error type and message that Lambda reports.
+pub(crate) fn function_error_response(error_type: &str, message: &str) -> LambdaResponse {
+    let body = serde_json::json!({
+        "errorType": error_type,
+        "errorMessage": message,
+    });
+
+    let mut resp = LambdaResponse::new(Body::from(body.to_string()));
+    resp.extensions_mut()
+        .insert(StatusCode::INTERNAL_SERVER_ERROR);
+    resp
+}
+
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum Action {
     Invoke(InvokeRequest),
-    Init,
+    /// Start a function before it receives invocations
+    Init(String),
+    Control(ControlRequest),
 }
 
 #[derive(Debug)]
@@ -24,6 +67,62 @@ pub struct InvokeRequest {
     pub function_name: String,
     pub req: Request<Body>,
     pub resp_tx: Sender<LambdaResponse>,
+    /// Number of times that the invocation has been sent to the function
+    pub attempts: usize,
+}
+
+/// InFlightInvocation is an invocation that a function is processing.
+/// The request is kept until the function responds, so it can be
+/// sent again if the function's process dies before responding.
+#[derive(Debug)]
+pub struct InFlightInvocation {
+    pub function_name: String,
+    pub parts: Parts,
+    pub body: Bytes,
+    pub resp_tx: Sender<LambdaResponse>,
+    pub attempts: usize,
+}
+
+impl InFlightInvocation {
+    /// Build a new request to send the invocation to the function again.
+    pub fn retry(self) -> InvokeRequest {
+        InvokeRequest {
+            function_name: self.function_name,
+            req: Request::from_parts(self.parts, Body::from(self.body)),
+            resp_tx: self.resp_tx,
+            attempts: self.attempts,
+        }
+    }
+}
+
+/// ControlCommand is an operation that the control
+/// endpoints can request on a single function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlCommand {
+    Stop,
+    Restart,
+}
+
+/// ControlStatus is the result of applying a ControlCommand
+/// in the scheduler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlStatus {
+    /// The function was running, and it's been stopped
+    Stopped,
+    /// The function has been (re)started
+    Restarted,
+    /// The function was not running, there was nothing to stop
+    NotRunning,
+    /// The functions are not managed by Cargo Lambda, see `--only-lambda-apis`
+    Unmanaged,
+}
+
+#[derive(Debug)]
+pub struct ControlRequest {
+    pub function_name: String,
+    pub command: ControlCommand,
+    pub resp_tx: Sender<ControlStatus>,
 }
 
 #[derive(Debug, Deserialize)]
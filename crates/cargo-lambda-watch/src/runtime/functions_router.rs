@@ -1,9 +1,6 @@
 use crate::{
-    RefRuntimeState,
-    error::ServerError,
-    requests::*,
-    runtime::LAMBDA_RUNTIME_XRAY_TRACE_HEADER,
-    state::{RequestCache, ResponseCache},
+    RefRuntimeState, error::ServerError, requests::*, runtime::LAMBDA_RUNTIME_XRAY_TRACE_HEADER,
+    state::RequestCache,
 };
 use axum::{
     body::Body,
@@ -14,7 +11,8 @@ use axum::{
 use base64::{Engine as _, engine::general_purpose as b64};
 use cargo_lambda_metadata::DEFAULT_PACKAGE_FUNCTION;
 use http::request::Parts;
-use tracing::debug;
+use http_body_util::BodyExt;
+use tracing::{debug, info};
 
 use super::LAMBDA_RUNTIME_AWS_REQUEST_ID;
 
@@ -54,12 +52,19 @@ pub(crate) async fn process_next_request(
         .get(LAMBDA_RUNTIME_AWS_REQUEST_ID)
         .expect("missing request id");
 
+    if let Some(delay) = state.faults.init_delay(function_name) {
+        info!(function = ?function_name, ?delay, "delaying the function's initialization");
+        tokio::time::sleep(delay).await;
+    }
+    state.reload.function_ready(function_name);
+    state.faults.function_ready(function_name);
+
     let mut builder = Response::builder()
         .header(LAMBDA_RUNTIME_AWS_REQUEST_ID, req_id)
         .header(LAMBDA_RUNTIME_DEADLINE_MS, 600_000_u32)
         .header(LAMBDA_RUNTIME_FUNCTION_ARN, "function-arn");
 
-    let resp = match state.req_cache.pop(function_name).await {
+    let resp = match next_invocation(state, function_name).await {
         None => builder.status(StatusCode::NO_CONTENT).body(Body::empty()),
         Some(invoke) => {
             let req_id = req_id
@@ -71,9 +76,22 @@ pub(crate) async fn process_next_request(
             state.ext_cache.send_event(next_event).await?;
 
             let (parts, body) = invoke.req.into_parts();
-
-            let resp_tx = invoke.resp_tx;
-            state.res_cache.push(req_id, resp_tx).await;
+            let body = body
+                .collect()
+                .await
+                .map_err(ServerError::DataDeserialization)?
+                .to_bytes();
+
+            state.hooks.pre_invoke(function_name, req_id, &body).await;
+
+            let in_flight = InFlightInvocation {
+                function_name: function_name.to_string(),
+                parts: parts.clone(),
+                body: body.clone(),
+                resp_tx: invoke.resp_tx,
+                attempts: invoke.attempts + 1,
+            };
+            state.res_cache.push(req_id, in_flight).await;
 
             let headers = parts.headers;
             if let Some(h) = headers.get(LAMBDA_RUNTIME_CLIENT_CONTEXT) {
@@ -91,19 +109,31 @@ pub(crate) async fn process_next_request(
                 builder = builder.header(LAMBDA_RUNTIME_XRAY_TRACE_HEADER, h);
             }
 
-            builder.status(StatusCode::OK).body(body)
+            builder.status(StatusCode::OK).body(Body::from(body))
         }
     };
 
     resp.map_err(ServerError::ResponseBuild)
 }
 
+/// Wait for the next invocation that the function can process.
+/// Invocations are answered with an error without reaching
+/// the function when its initialization must fail.
+async fn next_invocation(state: &RefRuntimeState, function_name: &str) -> Option<InvokeRequest> {
+    loop {
+        let invoke = state.req_cache.pop(function_name).await?;
+        if let Some(invoke) = state.faults.fail_init(invoke) {
+            return Some(invoke);
+        }
+    }
+}
+
 pub(crate) async fn next_invocation_response(
     State(state): State<RefRuntimeState>,
     Path((_function_name, req_id)): Path<(String, String)>,
     req: Request<Body>,
 ) -> Result<Response<Body>, ServerError> {
-    respond_to_next_invocation(&state.res_cache, &req_id, req, StatusCode::OK).await
+    respond_to_next_invocation(&state, &req_id, req, StatusCode::OK).await
 }
 
 pub(crate) async fn bare_next_invocation_response(
@@ -111,7 +141,7 @@ pub(crate) async fn bare_next_invocation_response(
     Path(req_id): Path<String>,
     req: Request<Body>,
 ) -> Result<Response<Body>, ServerError> {
-    respond_to_next_invocation(&state.res_cache, &req_id, req, StatusCode::OK).await
+    respond_to_next_invocation(&state, &req_id, req, StatusCode::OK).await
 }
 
 pub(crate) async fn next_invocation_error(
@@ -119,13 +149,7 @@ pub(crate) async fn next_invocation_error(
     Path((_function_name, req_id)): Path<(String, String)>,
     req: Request<Body>,
 ) -> Result<Response<Body>, ServerError> {
-    respond_to_next_invocation(
-        &state.res_cache,
-        &req_id,
-        req,
-        StatusCode::INTERNAL_SERVER_ERROR,
-    )
-    .await
+    respond_to_next_invocation(&state, &req_id, req, StatusCode::INTERNAL_SERVER_ERROR).await
 }
 
 pub(crate) async fn bare_next_invocation_error(
@@ -133,27 +157,30 @@ pub(crate) async fn bare_next_invocation_error(
     Path(req_id): Path<String>,
     req: Request<Body>,
 ) -> Result<Response<Body>, ServerError> {
-    respond_to_next_invocation(
-        &state.res_cache,
-        &req_id,
-        req,
-        StatusCode::INTERNAL_SERVER_ERROR,
-    )
-    .await
+    respond_to_next_invocation(&state, &req_id, req, StatusCode::INTERNAL_SERVER_ERROR).await
 }
 
 async fn respond_to_next_invocation(
-    cache: &ResponseCache,
+    state: &RefRuntimeState,
     req_id: &str,
     mut req: Request<Body>,
     response_status: StatusCode,
 ) -> Result<Response<Body>, ServerError> {
-    if let Some(resp_tx) = cache.pop(req_id).await {
+    if let Some(invocation) = state.res_cache.pop(req_id).await {
         req.extensions_mut().insert(response_status);
 
-        resp_tx
+        invocation
+            .resp_tx
             .send(req)
             .map_err(|_| ServerError::SendFunctionMessage)?;
+
+        // The function waits for the hook before it asks for the next
+        // invocation, so the hooks of different invocations don't overlap.
+        let success = response_status.is_success();
+        state
+            .hooks
+            .post_invoke(&invocation.function_name, req_id, &invocation.body, success)
+            .await;
     }
 
     Ok(Response::new(Body::empty()))
@@ -0,0 +1,212 @@
+use cargo_lambda_metadata::cargo::watch::{ResourceLimit, SandboxOptions};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde_json::json;
+use std::path::Path;
+use tracing::{info, warn};
+use watchexec::command::Command;
+
+/// Program that starts the functions in a transient scope of the user's
+/// systemd instance, which has its own cgroup, without root privileges.
+const SYSTEMD_RUN: &str = "systemd-run";
+
+/// Sandbox that limits the memory and resources of the functions' processes,
+/// so they're killed when they run out of memory, like in Lambda.
+#[derive(Clone, Debug)]
+pub(crate) struct Sandbox {
+    /// Target that `cargo run` compiles the functions for
+    target: String,
+    /// Program and arguments that run a binary inside the sandbox
+    runner: Vec<String>,
+}
+
+impl Sandbox {
+    /// Create the sandbox for functions with `memory_size` megabytes.
+    /// It returns None when the sandbox is not enabled, or when it's not available.
+    pub(crate) fn new(
+        options: &SandboxOptions,
+        memory_size: u64,
+        target: Option<&str>,
+    ) -> Result<Option<Sandbox>> {
+        let limits = options.resource_limits()?;
+        if !options.sandbox {
+            if !limits.is_empty() {
+                warn!(
+                    "--ulimit only applies to functions in the sandbox, enable it with --sandbox"
+                );
+            }
+            return Ok(None);
+        }
+
+        if !cfg!(target_os = "linux") {
+            warn!(
+                "--sandbox is only available on Linux, the functions' resources won't be limited"
+            );
+            return Ok(None);
+        }
+
+        let runner = runner(memory_size, &limits);
+        check_runner(&runner)?;
+
+        let target = match target {
+            Some(target) => target.to_string(),
+            None => {
+                rustc_version::version_meta()
+                    .into_diagnostic()
+                    .wrap_err("failed to detect the host target for the sandbox")?
+                    .host
+            }
+        };
+
+        info!(
+            memory_mb = memory_size,
+            ?limits,
+            "running the functions in a sandbox"
+        );
+        Ok(Some(Sandbox { target, runner }))
+    }
+
+    /// Command that runs a binary inside the sandbox.
+    pub(crate) fn command(&self, binary: &Path) -> Command {
+        let mut args = self.runner[1..].to_vec();
+        args.push(binary.to_string_lossy().to_string());
+        Command::Exec {
+            prog: self.runner[0].clone(),
+            args,
+        }
+    }
+
+    /// Cargo configuration that runs the binaries that `cargo run` compiles
+    /// inside the sandbox. Cargo itself, and the compiler, run outside of it.
+    pub(crate) fn cargo_config(&self) -> String {
+        format!(
+            "target.{}.runner={}",
+            json!(self.target),
+            json!(self.runner)
+        )
+    }
+}
+
+fn runner(memory_size: u64, limits: &[ResourceLimit]) -> Vec<String> {
+    let mut runner: Vec<String> = [SYSTEMD_RUN, "--user", "--scope", "--quiet", "--collect"]
+        .map(String::from)
+        .to_vec();
+    // Lambda doesn't swap, the functions are killed when they reach their memory.
+    runner.extend([
+        "-p".into(),
+        format!("MemoryMax={memory_size}M"),
+        "-p".into(),
+        "MemorySwapMax=0".into(),
+    ]);
+
+    let mut script = Vec::new();
+    for limit in limits {
+        match limit {
+            ResourceLimit::Processes(max) => {
+                runner.extend(["-p".into(), format!("TasksMax={max}")]);
+            }
+            ResourceLimit::OpenFiles(max) => script.push(format!("ulimit -n {max}")),
+        }
+    }
+
+    // Scopes don't support the `Limit*` properties of services,
+    // the limits are set by a shell that replaces itself with the binary.
+    if !script.is_empty() {
+        script.push(r#"exec "$0" "$@""#.into());
+        runner.extend(["sh".into(), "-c".into(), script.join(" && ")]);
+    }
+
+    runner
+}
+
+/// Run a command that does nothing inside the sandbox, to fail
+/// before starting the server if the sandbox doesn't work.
+fn check_runner(runner: &[String]) -> Result<()> {
+    let output = std::process::Command::new(&runner[0])
+        .args(&runner[1..])
+        .arg("true")
+        .output()
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette::miette!(
+                help = "the sandbox uses `systemd-run` to limit the functions' memory, install systemd or run the functions without `--sandbox`",
+                "failed to run `{SYSTEMD_RUN}`"
+            )
+        })?;
+
+    if !output.status.success() {
+        return Err(miette::miette!(
+            help = "the sandbox needs a systemd user session, check that `systemd-run --user --scope true` works",
+            "failed to start the sandbox: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandbox(limits: &[ResourceLimit]) -> Sandbox {
+        Sandbox {
+            target: "x86_64-unknown-linux-gnu".into(),
+            runner: runner(512, limits),
+        }
+    }
+
+    #[test]
+    fn test_runner() {
+        assert_eq!(
+            vec![
+                "systemd-run",
+                "--user",
+                "--scope",
+                "--quiet",
+                "--collect",
+                "-p",
+                "MemoryMax=512M",
+                "-p",
+                "MemorySwapMax=0",
+            ],
+            runner(512, &[])
+        );
+
+        let runner = runner(
+            128,
+            &[ResourceLimit::OpenFiles(1024), ResourceLimit::Processes(64)],
+        );
+        assert_eq!(
+            [
+                "-p",
+                "TasksMax=64",
+                "sh",
+                "-c",
+                r#"ulimit -n 1024 && exec "$0" "$@""#
+            ],
+            runner[9..]
+        );
+    }
+
+    #[test]
+    fn test_command() {
+        let Command::Exec { prog, args } = sandbox(&[]).command(Path::new("/tmp/orders/bootstrap"))
+        else {
+            panic!("unexpected command");
+        };
+        assert_eq!("systemd-run", prog);
+        assert_eq!(
+            Some("/tmp/orders/bootstrap"),
+            args.last().map(String::as_str)
+        );
+    }
+
+    #[test]
+    fn test_cargo_config() {
+        let config = sandbox(&[ResourceLimit::OpenFiles(1024)]).cargo_config();
+        assert_eq!(
+            r#"target."x86_64-unknown-linux-gnu".runner=["systemd-run","--user","--scope","--quiet","--collect","-p","MemoryMax=512M","-p","MemorySwapMax=0","sh","-c","ulimit -n 1024 && exec \"$0\" \"$@\""]"#,
+            config
+        );
+    }
+}
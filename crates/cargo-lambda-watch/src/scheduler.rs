@@ -1,29 +1,41 @@
 use crate::{
     error::ServerError,
-    requests::{Action, NextEvent},
-    state::{ExtensionCache, RuntimeState},
+    requests::{Action, ControlCommand, ControlRequest, ControlStatus, NextEvent},
+    state::RuntimeState,
     watcher::WatcherConfig,
 };
 use cargo_lambda_metadata::DEFAULT_PACKAGE_FUNCTION;
 use cargo_options::Run as CargoOptions;
-use tokio::sync::mpsc::{self, Receiver, Sender};
-use tokio_graceful_shutdown::{SubsystemBuilder, SubsystemHandle};
+use std::collections::HashMap;
+use tokio::sync::{
+    mpsc::{self, Receiver, Sender},
+    oneshot,
+};
+use tokio_graceful_shutdown::{NestedSubsystem, SubsystemBuilder, SubsystemHandle};
 use tracing::{error, info};
 use watchexec::command::Command;
 
+pub(crate) type FunctionHandle = NestedSubsystem<Box<dyn std::error::Error + Send + Sync>>;
+
+/// Start the scheduler in a detached subsystem. The scheduler doesn't
+/// react to the server's shutdown, the server stops it with the returned
+/// handle after it drains the invocations in flight.
 pub(crate) fn init_scheduler(
     subsys: &SubsystemHandle,
     state: RuntimeState,
     cargo_options: CargoOptions,
     watcher_config: WatcherConfig,
-) -> Sender<Action> {
-    let (req_tx, req_rx) = mpsc::channel::<Action>(100);
+) -> (Sender<Action>, FunctionHandle) {
+    let (req_tx, req_rx) = mpsc::channel::<Action>(state.invoke_queue_size());
 
-    subsys.start(SubsystemBuilder::new("lambda scheduler", move |s| {
-        start_scheduler(s, state, cargo_options, watcher_config, req_rx)
-    }));
+    let handle = subsys.start(
+        SubsystemBuilder::new("lambda scheduler", move |s| {
+            start_scheduler(s, state, cargo_options, watcher_config, req_rx)
+        })
+        .detached(),
+    );
 
-    req_tx
+    (req_tx, handle)
 }
 
 async fn start_scheduler(
@@ -34,53 +46,203 @@ async fn start_scheduler(
     mut req_rx: Receiver<Action>,
 ) -> Result<(), ServerError> {
     let (gc_tx, mut gc_rx) = mpsc::channel::<String>(10);
+    let (stopped_tx, mut stopped_rx) = mpsc::channel::<String>(10);
+    let mut running: HashMap<String, FunctionHandle> = HashMap::new();
+    // Functions that are shutting down, and whether to
+    // start them again when their process terminates.
+    let mut stopping: HashMap<String, bool> = HashMap::new();
 
     loop {
-        tokio::select! {
+        let start_function_name = tokio::select! {
             Some(action) = req_rx.recv() => {
                 tracing::trace!(?action, "request action received");
-                let start_function_name = match action {
+                match action {
                     Action::Invoke(req) => {
                         state.req_cache.upsert(req).await?
                     },
-                    Action::Init => {
-                        state.req_cache.init(DEFAULT_PACKAGE_FUNCTION).await;
-                        Some(DEFAULT_PACKAGE_FUNCTION.into())
+                    Action::Init(name) => {
+                        state.req_cache.init(&name).await;
+                        Some(name)
+                    },
+                    Action::Control(req) => {
+                        control_function(&state, &watcher_config, &mut running, &mut stopping, &stopped_tx, req).await
                     },
-                };
-
-                if watcher_config.start_function() {
-                    if let Some(name) = start_function_name {
-                        let runtime_api = state.function_addr(&name);
-                        let gc_tx = gc_tx.clone();
-                        let cargo_options = cargo_options.clone();
-                        let watcher_config = watcher_config.clone();
-                        let ext_cache = state.ext_cache.clone();
-                        subsys.start(SubsystemBuilder::new("lambda runtime", move |s| start_function(s, name, runtime_api, cargo_options, watcher_config, gc_tx, ext_cache)));
-                    }
                 }
             }
             Some(name) = gc_rx.recv() => {
+                running.remove(&name);
                 state.req_cache.clean(&name).await;
+                None
+            }
+            Some(name) = stopped_rx.recv() => {
+                match stopping.remove(&name) {
+                    Some(true) => Some(name),
+                    _ => None,
+                }
             }
             _ = subsys.on_shutdown_requested() => {
                 info!("terminating lambda scheduler");
                 return Ok(());
             }
+        };
+
+        if !watcher_config.start_function() {
+            continue;
+        }
+        let Some(name) = start_function_name else {
+            continue;
+        };
+        // Two processes of the same function would compete for its invocations.
+        if let Some(start_again) = stopping.get_mut(&name) {
+            *start_again = true;
+            continue;
+        }
+
+        let runtime_api = state.function_addr(&name);
+        let gc_tx = gc_tx.clone();
+        let cargo_options = cargo_options.clone();
+        let mut watcher_config = watcher_config.clone();
+        watcher_config.display_name = state.function_display_name(&name);
+        let state = state.clone();
+        let fn_name = name.clone();
+        let handle = subsys.start(SubsystemBuilder::new("lambda runtime", move |s| {
+            start_function(
+                s,
+                fn_name,
+                runtime_api,
+                cargo_options,
+                watcher_config,
+                gc_tx,
+                state,
+            )
+        }));
+        running.insert(name, handle);
+    }
+}
+
+/// Stop a running function, and start it again if the command is a restart.
+/// It returns the name of the function to start, if any. The function
+/// terminates in the background, so the other functions keep processing
+/// invocations. The control request gets its status after it terminates.
+async fn control_function(
+    state: &RuntimeState,
+    watcher_config: &WatcherConfig,
+    running: &mut HashMap<String, FunctionHandle>,
+    stopping: &mut HashMap<String, bool>,
+    stopped_tx: &Sender<String>,
+    req: ControlRequest,
+) -> Option<String> {
+    let ControlRequest {
+        function_name,
+        command,
+        resp_tx,
+    } = req;
+
+    if !watcher_config.start_function() {
+        send_control_status(&function_name, resp_tx, ControlStatus::Unmanaged);
+        return None;
+    }
+
+    let name = running_function_name(state, running, &function_name);
+
+    // Remove the function's queue so the next invocation starts
+    // the function again after a stop. Pending requests are dropped.
+    state.req_cache.clean(&name).await;
+
+    let (status, start) = match command {
+        ControlCommand::Restart => {
+            state.req_cache.init(&name).await;
+            (ControlStatus::Restarted, Some(name.clone()))
+        }
+        ControlCommand::Stop if running.contains_key(&name) => (ControlStatus::Stopped, None),
+        ControlCommand::Stop => {
+            // Don't start it again if it's still terminating.
+            if let Some(start_again) = stopping.get_mut(&name) {
+                *start_again = false;
+            }
+            (ControlStatus::NotRunning, None)
+        }
+    };
+
+    match running.remove(&name) {
+        None => send_control_status(&function_name, resp_tx, status),
+        Some(handle) => {
+            info!(function = ?name, ?command, "stopping lambda function");
+            handle.initiate_shutdown();
+            stopping.insert(name.clone(), false);
+
+            let stopped_tx = stopped_tx.clone();
+            tokio::spawn(async move {
+                if let Err(error) = handle.join().await {
+                    error!(?error, function = ?name, "lambda function didn't stop cleanly");
+                }
+                send_control_status(&function_name, resp_tx, status);
+                if let Err(error) = stopped_tx.send(name.clone()).await {
+                    error!(%error, function = ?name, "failed to notify that the function stopped");
+                }
+            });
         }
     }
+
+    start
+}
+
+fn send_control_status(
+    function_name: &str,
+    resp_tx: oneshot::Sender<ControlStatus>,
+    status: ControlStatus,
+) {
+    if resp_tx.send(status).is_err() {
+        error!(function = ?function_name, "failed to send control response");
+    }
+}
+
+/// Find the key that the scheduler uses for a function.
+/// Functions in single binary projects can run under the default
+/// function name, when they're invoked without a name.
+fn running_function_name(
+    state: &RuntimeState,
+    running: &HashMap<String, FunctionHandle>,
+    function_name: &str,
+) -> String {
+    if !running.contains_key(function_name)
+        && running.contains_key(DEFAULT_PACKAGE_FUNCTION)
+        && state.is_default_function_enabled()
+        && state.initial_functions.contains(function_name)
+    {
+        return DEFAULT_PACKAGE_FUNCTION.into();
+    }
+
+    function_name.into()
 }
 
 async fn start_function(
     subsys: SubsystemHandle,
     name: String,
     runtime_api: String,
-    cargo_options: CargoOptions,
+    mut cargo_options: CargoOptions,
     mut watcher_config: WatcherConfig,
     gc_tx: Sender<String>,
-    ext_cache: ExtensionCache,
+    state: RuntimeState,
 ) -> Result<(), ServerError> {
-    let cmd = cargo_command(&name, &cargo_options)?;
+    let binary = watcher_config
+        .binaries
+        .get(&name)
+        .cloned()
+        .unwrap_or_default();
+    let cmd = match (&watcher_config.bin_path, &watcher_config.sandbox) {
+        (Some(path), Some(sandbox)) => sandbox.command(path),
+        (Some(path), None) => Command::Exec {
+            prog: path.to_string_lossy().to_string(),
+            args: Vec::new(),
+        },
+        (None, sandbox) => {
+            if let Some(sandbox) = sandbox {
+                cargo_options.common.config.push(sandbox.cargo_config());
+            }
+            cargo_command(&name, &cargo_options, &binary.features)?
+        }
+    };
     info!(function = ?name, manifest = ?cargo_options.manifest_path, ?cmd, "starting lambda function");
 
     watcher_config.bin_name = if is_valid_bin_name(&name) {
@@ -90,11 +252,14 @@ async fn start_function(
     };
     watcher_config.name.clone_from(&name);
     watcher_config.runtime_api = runtime_api;
+    watcher_config.ignore_globs.extend(binary.ignore);
 
-    let wx = crate::watcher::new(cmd, watcher_config, ext_cache.clone()).await?;
+    let ext_cache = state.ext_cache.clone();
+    let wx = crate::watcher::new(cmd, watcher_config, state).await?;
+    let mut main = wx.main();
 
     tokio::select! {
-        res = wx.main() => match res {
+        res = &mut main => match res {
             Ok(_) => {},
             Err(error) => {
                 error!(?error, "failed to obtain the watchexec task");
@@ -105,6 +270,24 @@ async fn start_function(
         },
         _ = subsys.on_shutdown_requested() => {
             info!(function = ?name, "terminating lambda function");
+            // Let the extensions know about the shutdown
+            // before the function's process is killed.
+            let event = NextEvent::shutdown(&format!("{name} function shutting down"));
+            if let Err(error) = ext_cache.send_event(event).await {
+                error!(?error, function = ?name, "failed to send shutdown event to extensions");
+            }
+
+            // Dropping the main task doesn't stop the process,
+            // ask watchexec to terminate it before returning.
+            match crate::watcher::terminate(&wx).await {
+                Ok(_) => {
+                    let _ = main.await;
+                }
+                Err(error) => {
+                    error!(?error, function = ?name, "failed to terminate lambda function");
+                }
+            }
+            return Ok(());
         }
     }
 
@@ -119,10 +302,16 @@ fn is_valid_bin_name(name: &str) -> bool {
 fn cargo_command(
     name: &str,
     cargo_options: &CargoOptions,
+    features: &[String],
 ) -> Result<watchexec::command::Command, ServerError> {
     let cmd = if is_valid_bin_name(name) {
         let mut command_opts = cargo_options.clone();
         command_opts.bin.push(name.to_string());
+        for feature in features {
+            if !command_opts.features.contains(feature) {
+                command_opts.features.push(feature.clone());
+            }
+        }
         command_opts.command()
     } else {
         cargo_options.command()
@@ -0,0 +1,405 @@
+use cargo_lambda_interactive::{Confirm, is_stdin_tty, is_user_cancellation_error};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+use tracing::{debug, info, warn};
+
+/// Directory inside `target/lambda` where each session records the processes that it starts.
+const SESSIONS_DIR: &str = "watch-sessions";
+
+/// Process started by a session, usually `cargo run` and the function that it runs.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct SessionProcess {
+    /// Process id, or process group id when the process runs in its own group
+    pub id: u32,
+    /// Whether the process runs in its own process group
+    pub grouped: bool,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+struct SessionState {
+    pid: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    invoke_addr: Option<SocketAddr>,
+    #[serde(default)]
+    functions: HashMap<String, SessionProcess>,
+}
+
+/// Pid file of a `cargo lambda watch` session.
+/// If the session crashes, the next session uses the file
+/// to find the functions that were left running and holding the ports.
+#[derive(Debug)]
+pub(crate) struct Session {
+    path: PathBuf,
+    state: Mutex<SessionState>,
+}
+
+impl Session {
+    /// Stop the processes left by previous sessions that didn't exit cleanly,
+    /// and record the new session in the target directory.
+    pub(crate) fn start(target_dir: &Path, kill_orphans: bool) -> Result<Session> {
+        let dir = target_dir.join("lambda").join(SESSIONS_DIR);
+        stop_orphans(&dir, kill_orphans)?;
+
+        let pid = std::process::id();
+        let session = Session {
+            path: dir.join(format!("{pid}.json")),
+            state: Mutex::new(SessionState {
+                pid,
+                ..Default::default()
+            }),
+        };
+        session.write()?;
+
+        Ok(session)
+    }
+
+    pub(crate) fn set_invoke_addr(&self, addr: SocketAddr) -> Result<()> {
+        self.state.lock().unwrap().invoke_addr = Some(addr);
+        self.write()
+    }
+
+    /// Record the process that runs a function.
+    /// The previous process for the same function is replaced,
+    /// because watchexec stops it before spawning a new one.
+    pub(crate) fn add_process(&self, name: &str, process: SessionProcess) {
+        self.state
+            .lock()
+            .unwrap()
+            .functions
+            .insert(name.to_string(), process);
+
+        if let Err(error) = self.write() {
+            warn!(?error, function = ?name, "failed to record the function's process");
+        }
+    }
+
+    /// Remove the pid file when the session exits cleanly.
+    pub(crate) fn finish(&self) {
+        let _ = fs::remove_file(&self.path);
+    }
+
+    fn write(&self) -> Result<()> {
+        let content = {
+            let state = self.state.lock().unwrap();
+            serde_json::to_vec_pretty(&*state).into_diagnostic()?
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).into_diagnostic()?;
+        }
+
+        fs::write(&self.path, content)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to write the session file {}", self.path.display()))
+    }
+}
+
+/// Session that didn't remove its pid file, and whose functions are still running.
+#[derive(Debug, PartialEq)]
+struct Orphans {
+    path: PathBuf,
+    state: SessionState,
+}
+
+fn stop_orphans(dir: &Path, kill_orphans: bool) -> Result<()> {
+    let orphans = find_orphans(dir)?;
+    if orphans.is_empty() {
+        return Ok(());
+    }
+
+    let summary = summary(&orphans);
+    if !kill_orphans {
+        if !is_stdin_tty() {
+            return Err(miette::miette!(
+                help = "stop those processes, or run the command with `--kill-orphans`",
+                "{summary}"
+            ));
+        }
+
+        println!("{summary}");
+        let answer = Confirm::new("Do you want to stop those processes?")
+            .with_default(true)
+            .prompt();
+
+        match answer {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!("the processes left by previous sessions can keep the ports busy");
+                return Ok(());
+            }
+            Err(err) if is_user_cancellation_error(&err) => {
+                return Err(miette::miette!("watch cancelled"));
+            }
+            Err(err) => return Err(err).into_diagnostic(),
+        }
+    }
+
+    for orphan in orphans {
+        for (name, process) in &orphan.state.functions {
+            if is_running(process) {
+                info!(function = ?name, pid = process.id, "stopping process left by a previous session");
+                kill(process)
+                    .wrap_err_with(|| format!("failed to stop the process {}", process.id))?;
+            }
+        }
+        let _ = fs::remove_file(&orphan.path);
+    }
+
+    Ok(())
+}
+
+/// Find the sessions that exited without removing their pid files,
+/// and still have functions running. Pid files of sessions without
+/// running functions are removed. Sessions still running are left untouched.
+fn find_orphans(dir: &Path) -> Result<Vec<Orphans>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to read the sessions in {}", dir.display()));
+        }
+    };
+
+    let current = std::process::id();
+    let mut orphans = Vec::new();
+
+    for entry in entries {
+        let path = entry.into_diagnostic()?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+
+        let state: SessionState = match fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|c| serde_json::from_slice(&c).map_err(|e| e.to_string()))
+        {
+            Ok(state) => state,
+            Err(error) => {
+                debug!(?error, ?path, "ignoring invalid session file");
+                continue;
+            }
+        };
+
+        if state.pid == current
+            || is_running(&SessionProcess {
+                id: state.pid,
+                grouped: false,
+            })
+        {
+            continue;
+        }
+
+        if state.functions.values().any(is_running) {
+            orphans.push(Orphans { path, state });
+        } else {
+            let _ = fs::remove_file(&path);
+        }
+    }
+
+    Ok(orphans)
+}
+
+fn summary(orphans: &[Orphans]) -> String {
+    let mut lines = Vec::new();
+    for orphan in orphans {
+        let addr = orphan
+            .state
+            .invoke_addr
+            .map(|a| format!(" listening on {a}"))
+            .unwrap_or_default();
+        lines.push(format!(
+            "a previous session{addr} (pid {}) didn't exit cleanly, and left these processes running:",
+            orphan.state.pid
+        ));
+
+        let mut functions = orphan
+            .state
+            .functions
+            .iter()
+            .filter(|(_, p)| is_running(p))
+            .collect::<Vec<_>>();
+        functions.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, process) in functions {
+            lines.push(format!("  - {name} (pid {})", process.id));
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(unix)]
+fn process_target(process: &SessionProcess) -> String {
+    if process.grouped {
+        format!("-{}", process.id)
+    } else {
+        process.id.to_string()
+    }
+}
+
+#[cfg(unix)]
+fn is_running(process: &SessionProcess) -> bool {
+    Command::new("kill")
+        .args(["-0", "--", &process_target(process)])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+pub(crate) fn kill(process: &SessionProcess) -> Result<()> {
+    let status = Command::new("kill")
+        .args(["-KILL", "--", &process_target(process)])
+        .stdout(Stdio::null())
+        .status()
+        .into_diagnostic()?;
+    if !status.success() {
+        return Err(miette::miette!("kill exited with {status}"));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn is_running(process: &SessionProcess) -> bool {
+    Command::new("tasklist")
+        .args(["/NH", "/FI", &format!("PID eq {}", process.id)])
+        .stderr(Stdio::null())
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).contains(&process.id.to_string()))
+        .unwrap_or_default()
+}
+
+#[cfg(windows)]
+pub(crate) fn kill(process: &SessionProcess) -> Result<()> {
+    let status = Command::new("taskkill")
+        .args(["/PID", &process.id.to_string(), "/T", "/F"])
+        .stdout(Stdio::null())
+        .status()
+        .into_diagnostic()?;
+    if !status.success() {
+        return Err(miette::miette!("taskkill exited with {status}"));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn finished_process() -> u32 {
+        let mut child = Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        pid
+    }
+
+    fn write_session(dir: &Path, state: &SessionState) -> PathBuf {
+        let path = dir
+            .join("lambda")
+            .join(SESSIONS_DIR)
+            .join(format!("{}.json", state.pid));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, serde_json::to_vec(state).unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_session_file() {
+        let target_dir = tempfile::tempdir().unwrap();
+
+        let session = Session::start(target_dir.path(), false).unwrap();
+        session
+            .set_invoke_addr("127.0.0.1:9000".parse().unwrap())
+            .unwrap();
+        session.add_process(
+            "basic-lambda",
+            SessionProcess {
+                id: 42,
+                grouped: true,
+            },
+        );
+
+        let content = fs::read(&session.path).unwrap();
+        let state: SessionState = serde_json::from_slice(&content).unwrap();
+        assert_eq!(std::process::id(), state.pid);
+        assert_eq!(Some("127.0.0.1:9000".parse().unwrap()), state.invoke_addr);
+        assert_eq!(
+            Some(&SessionProcess {
+                id: 42,
+                grouped: true
+            }),
+            state.functions.get("basic-lambda")
+        );
+
+        session.finish();
+        assert!(!session.path.exists());
+    }
+
+    #[test]
+    fn test_find_orphans_removes_finished_sessions() {
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = write_session(
+            target_dir.path(),
+            &SessionState {
+                pid: finished_process(),
+                invoke_addr: None,
+                functions: HashMap::from([(
+                    "basic-lambda".to_string(),
+                    SessionProcess {
+                        id: finished_process(),
+                        grouped: false,
+                    },
+                )]),
+            },
+        );
+
+        let orphans = find_orphans(path.parent().unwrap()).unwrap();
+        assert!(orphans.is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_kill_orphans() {
+        let target_dir = tempfile::tempdir().unwrap();
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+
+        let state = SessionState {
+            pid: finished_process(),
+            invoke_addr: Some("127.0.0.1:9000".parse().unwrap()),
+            functions: HashMap::from([(
+                "basic-lambda".to_string(),
+                SessionProcess {
+                    id: child.id(),
+                    grouped: false,
+                },
+            )]),
+        };
+        let path = write_session(target_dir.path(), &state);
+
+        let orphans = find_orphans(path.parent().unwrap()).unwrap();
+        assert_eq!(
+            format!(
+                "a previous session listening on 127.0.0.1:9000 (pid {}) didn't exit cleanly, and left these processes running:\n  - basic-lambda (pid {})",
+                state.pid,
+                child.id()
+            ),
+            summary(&orphans)
+        );
+
+        let session = Session::start(target_dir.path(), true).unwrap();
+        assert!(!child.wait().unwrap().success());
+        assert!(!path.exists());
+
+        session.finish();
+    }
+}
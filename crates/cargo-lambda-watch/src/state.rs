@@ -1,21 +1,51 @@
 use crate::{
     RUNTIME_EMULATOR_PATH,
     error::ServerError,
-    requests::{InvokeRequest, LambdaResponse, NextEvent},
+    faults::FaultInjector,
+    hooks::InvokeHooks,
+    jwt::JwtAuthorizer,
+    reload::{FunctionHealth, ReloadNotifier},
+    requests::{
+        InFlightInvocation, InvokeRequest, NextEvent, function_error_response, throttled_response,
+    },
+    translators::{RefEventTranslator, translator},
+};
+use cargo_lambda_metadata::{
+    DEFAULT_PACKAGE_FUNCTION,
+    cargo::{
+        binary_targets,
+        watch::{AlbOptions, EventFormat, FunctionRouter, RuntimeApiHost},
+    },
 };
-use cargo_lambda_metadata::cargo::{binary_targets, watch::FunctionRouter};
 use miette::Result;
 use mpsc::{Receiver, Sender, channel};
 use std::{
-    collections::{HashMap, HashSet, hash_map::Entry},
+    collections::{BTreeMap, HashMap, HashSet, hash_map::Entry},
     net::SocketAddr,
     path::PathBuf,
     sync::Arc,
 };
-use tokio::sync::{Mutex, RwLock, mpsc, oneshot};
-use tracing::debug;
+use tokio::sync::{
+    Mutex, OwnedSemaphorePermit, RwLock, Semaphore, mpsc,
+    mpsc::error::{SendError, TrySendError},
+};
+use tokio_util::task::{TaskTracker, task_tracker::TaskTrackerToken};
+use tracing::{debug, error, warn};
 use uuid::Uuid;
 
+/// Number of times that an invocation is sent to a function
+/// before answering with an error, when the function's process
+/// dies without responding to it.
+pub(crate) const MAX_INVOKE_ATTEMPTS: usize = 3;
+
+/// Error type that Lambda reports when the runtime exits before responding.
+const EXIT_ERROR: &str = "Runtime.ExitError";
+
+const DEFAULT_QUEUE_SIZE: usize = 100;
+
+/// Name that the Runtime Interface Emulator uses for the function that it runs.
+const RIE_FUNCTION_NAME: &str = "function";
+
 #[derive(Clone)]
 pub(crate) struct RuntimeState {
     runtime_addr: SocketAddr,
@@ -24,9 +54,19 @@ pub(crate) struct RuntimeState {
     manifest_path: PathBuf,
     pub initial_functions: HashSet<String>,
     pub function_router: Option<FunctionRouter>,
+    pub alb_options: AlbOptions,
+    /// Whether invoke requests are answered like the Runtime Interface Emulator does
+    pub rie_compatible: bool,
+    pub event_translator: RefEventTranslator,
     pub req_cache: RequestCache,
     pub res_cache: ResponseCache,
     pub ext_cache: ExtensionCache,
+    pub faults: Arc<FaultInjector>,
+    pub hooks: Arc<InvokeHooks>,
+    pub jwt_authorizer: Option<Arc<JwtAuthorizer>>,
+    pub reload: Arc<ReloadNotifier>,
+    pub concurrency: ConcurrencyLimiter,
+    invocations: TaskTracker,
 }
 
 pub(crate) type RefRuntimeState = Arc<RuntimeState>;
@@ -45,13 +85,77 @@ impl RuntimeState {
             manifest_path,
             initial_functions,
             function_router,
+            alb_options: AlbOptions::default(),
+            rie_compatible: false,
+            event_translator: translator(EventFormat::default(), false),
             runtime_url: format!("http://{runtime_addr}{RUNTIME_EMULATOR_PATH}"),
-            req_cache: RequestCache::new(),
+            req_cache: RequestCache::new(DEFAULT_QUEUE_SIZE),
             res_cache: ResponseCache::new(),
             ext_cache: ExtensionCache::default(),
+            faults: Arc::new(FaultInjector::default()),
+            hooks: Arc::new(InvokeHooks::default()),
+            jwt_authorizer: None,
+            reload: Arc::new(ReloadNotifier::default()),
+            concurrency: ConcurrencyLimiter::default(),
+            invocations: TaskTracker::new(),
         }
     }
 
+    pub(crate) fn with_alb_options(mut self, alb_options: AlbOptions) -> RuntimeState {
+        self.alb_options = alb_options;
+        self
+    }
+
+    /// Advertise the runtime API to the functions in a different host,
+    /// for functions that cannot reach the address where the server listens.
+    pub(crate) fn with_runtime_api_host(mut self, host: Option<RuntimeApiHost>) -> RuntimeState {
+        if let Some(host) = host {
+            let address = host.address(self.runtime_addr.port());
+            self.runtime_url = format!("http://{address}{RUNTIME_EMULATOR_PATH}");
+        }
+        self
+    }
+
+    pub(crate) fn with_rie_compatible(mut self, rie_compatible: bool) -> RuntimeState {
+        self.rie_compatible = rie_compatible;
+        self
+    }
+
+    pub(crate) fn with_faults(mut self, faults: FaultInjector) -> RuntimeState {
+        self.faults = Arc::new(faults);
+        self
+    }
+
+    pub(crate) fn with_invoke_hooks(mut self, hooks: InvokeHooks) -> RuntimeState {
+        self.hooks = Arc::new(hooks);
+        self
+    }
+
+    pub(crate) fn with_jwt_authorizer(mut self, authorizer: Option<JwtAuthorizer>) -> RuntimeState {
+        self.jwt_authorizer = authorizer.map(Arc::new);
+        self
+    }
+
+    pub(crate) fn with_reserved_concurrency(mut self, limit: Option<usize>) -> RuntimeState {
+        self.concurrency = ConcurrencyLimiter::new(limit);
+        self
+    }
+
+    pub(crate) fn with_invoke_queue_size(mut self, size: usize) -> RuntimeState {
+        self.req_cache = RequestCache::new(size);
+        self
+    }
+
+    /// Maximum number of invocations waiting for each function.
+    pub(crate) fn invoke_queue_size(&self) -> usize {
+        self.req_cache.queue_size
+    }
+
+    pub(crate) fn with_event_translator(mut self, translator: RefEventTranslator) -> RuntimeState {
+        self.event_translator = translator;
+        self
+    }
+
     pub(crate) fn addresses(&self) -> (SocketAddr, Option<SocketAddr>, String) {
         (self.runtime_addr, self.proxy_addr, self.runtime_url.clone())
     }
@@ -60,10 +164,122 @@ impl RuntimeState {
         format!("{}/{}", &self.runtime_url, name)
     }
 
+    /// Returns the name to show for a function. Functions invoked
+    /// through the default route use the name of the only binary.
+    pub(crate) fn function_display_name(&self, name: &str) -> String {
+        if name == DEFAULT_PACKAGE_FUNCTION && self.is_default_function_enabled() {
+            if let Some(bin) = self.initial_functions.iter().next() {
+                return bin.clone();
+            }
+        }
+        name.to_string()
+    }
+
+    /// Returns the name of the function to invoke. The Runtime Interface Emulator
+    /// runs a single function that clients invoke as `function`, so that name
+    /// points to the default function, unless a binary in the project has that name.
+    pub(crate) fn invoked_function_name(&self, name: String) -> String {
+        if self.rie_compatible
+            && name == RIE_FUNCTION_NAME
+            && !self.initial_functions.contains(RIE_FUNCTION_NAME)
+        {
+            return DEFAULT_PACKAGE_FUNCTION.to_string();
+        }
+        name
+    }
+
     pub(crate) fn is_default_function_enabled(&self) -> bool {
         self.initial_functions.len() == 1
     }
 
+    /// Register an invocation in flight. The invocation is tracked until the
+    /// token is dropped. It returns None when the server is draining invocations
+    /// to shut down.
+    pub(crate) fn track_invocation(&self) -> Option<TaskTrackerToken> {
+        if self.invocations.is_closed() {
+            return None;
+        }
+        Some(self.invocations.token())
+    }
+
+    /// Stop accepting new invocations, and wait for the invocations
+    /// in flight to complete.
+    pub(crate) async fn drain_invocations(&self) {
+        self.invocations.close();
+        self.invocations.wait().await;
+    }
+
+    /// Recover the invocations that a function was processing when its process ended.
+    /// The invocations are queued again, so the function processes them when it restarts.
+    /// After `MAX_INVOKE_ATTEMPTS`, the invocations are answered with an error, like Lambda does.
+    /// It returns true if any invocation was queued again.
+    pub(crate) async fn recover_invocations(&self, function_name: &str, message: &str) -> bool {
+        let mut requeued = false;
+
+        for invocation in self.res_cache.take_function(function_name).await {
+            if invocation.attempts >= MAX_INVOKE_ATTEMPTS {
+                error!(
+                    function = ?function_name,
+                    attempts = invocation.attempts,
+                    "the function exited before responding to the invocation, giving up"
+                );
+                fail_invocation(invocation, EXIT_ERROR, message);
+                continue;
+            }
+
+            warn!(
+                function = ?function_name,
+                attempts = invocation.attempts,
+                "the function exited before responding to the invocation, sending it again"
+            );
+            match self.req_cache.requeue(invocation.retry()).await {
+                Ok(()) => requeued = true,
+                Err(req) => {
+                    let invocation = InFlightInvocation {
+                        function_name: req.function_name,
+                        parts: req.req.into_parts().0,
+                        body: Default::default(),
+                        resp_tx: req.resp_tx,
+                        attempts: req.attempts,
+                    };
+                    fail_invocation(invocation, EXIT_ERROR, message);
+                }
+            }
+        }
+
+        requeued
+    }
+
+    /// Answer the invocations that a function was processing with an error,
+    /// without sending them again. Lambda doesn't retry invocations that
+    /// fail because the function ran out of memory.
+    pub(crate) async fn fail_invocations(
+        &self,
+        function_name: &str,
+        error_type: &str,
+        message: &str,
+    ) {
+        for invocation in self.res_cache.take_function(function_name).await {
+            error!(function = ?function_name, error_type, "the function failed while processing the invocation");
+            fail_invocation(invocation, error_type, message);
+        }
+    }
+
+    /// Health of the project's functions, by name. Functions in single binary
+    /// projects can run under the default function name.
+    pub(crate) fn functions_health(&self) -> BTreeMap<String, FunctionHealth> {
+        self.initial_functions
+            .iter()
+            .map(|name| {
+                let mut health = self.reload.function_health(name);
+                if health == FunctionHealth::Idle && self.is_default_function_enabled() {
+                    health = self.reload.function_health(DEFAULT_PACKAGE_FUNCTION);
+                }
+                (name.clone(), health)
+            })
+            .collect()
+    }
+
     pub(crate) fn is_function_available(&self, name: &str) -> Result<(), HashSet<String>> {
         if self.initial_functions.contains(name) {
             return Ok(());
@@ -80,6 +296,15 @@ impl RuntimeState {
     }
 }
 
+/// Answer an invocation with the error that Lambda returns
+/// when the runtime exits before responding.
+fn fail_invocation(invocation: InFlightInvocation, error_type: &str, message: &str) {
+    let resp = function_error_response(error_type, message);
+    if invocation.resp_tx.send(resp).is_err() {
+        debug!(function = ?invocation.function_name, "the invocation was cancelled before responding");
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct RequestQueue {
     tx: Arc<Sender<InvokeRequest>>,
@@ -87,8 +312,8 @@ pub(crate) struct RequestQueue {
 }
 
 impl RequestQueue {
-    pub fn new() -> RequestQueue {
-        let (tx, rx) = channel::<InvokeRequest>(100);
+    pub fn new(size: usize) -> RequestQueue {
+        let (tx, rx) = channel::<InvokeRequest>(size.max(1));
 
         RequestQueue {
             tx: Arc::new(tx),
@@ -101,29 +326,45 @@ impl RequestQueue {
         rx.recv().await
     }
 
-    pub async fn push(&self, req: InvokeRequest) -> Result<(), ServerError> {
-        self.tx
-            .send(req)
-            .await
-            .map_err(|e| ServerError::SendInvokeMessage(Box::new(e)))
+    /// Add an invocation to the queue. When the queue is full,
+    /// the invocation is rejected with a throttling error.
+    pub fn push(&self, req: InvokeRequest) -> Result<(), Box<SendError<InvokeRequest>>> {
+        match self.tx.try_send(req) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(req)) => {
+                warn!(function = ?req.function_name, "the function's queue is full, rejecting the invocation");
+                if req.resp_tx.send(throttled_response()).is_err() {
+                    debug!(function = ?req.function_name, "the invocation was cancelled before responding");
+                }
+                Ok(())
+            }
+            Err(TrySendError::Closed(req)) => Err(Box::new(SendError(req))),
+        }
+    }
+
+    /// Number of invocations waiting in the queue.
+    pub fn depth(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
     }
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct RequestCache {
     inner: Arc<RwLock<HashMap<String, RequestQueue>>>,
+    queue_size: usize,
 }
 
 impl RequestCache {
-    pub fn new() -> RequestCache {
+    pub fn new(queue_size: usize) -> RequestCache {
         RequestCache {
             inner: Arc::new(RwLock::new(HashMap::new())),
+            queue_size,
         }
     }
 
     pub async fn init(&self, function_name: &str) {
         let mut inner = self.inner.write().await;
-        inner.insert(function_name.into(), RequestQueue::new());
+        inner.insert(function_name.into(), RequestQueue::new(self.queue_size));
         debug!(
             function_name,
             "request stack initialized before compilation"
@@ -136,8 +377,8 @@ impl RequestCache {
 
         match inner.entry(function_name.clone()) {
             Entry::Vacant(v) => {
-                let stack = RequestQueue::new();
-                stack.push(req).await?;
+                let stack = RequestQueue::new(self.queue_size);
+                stack.push(req)?;
                 v.insert(stack);
 
                 debug!(?function_name, "request stack initialized in first request");
@@ -145,7 +386,7 @@ impl RequestCache {
                 Ok(Some(function_name))
             }
             Entry::Occupied(o) => {
-                o.into_mut().push(req).await?;
+                o.into_mut().push(req)?;
                 debug!(?function_name, "request stack increased");
 
                 Ok(None)
@@ -153,6 +394,16 @@ impl RequestCache {
         }
     }
 
+    /// Queue an invocation again, only if the function's queue still exists,
+    /// and it's not full. The queue doesn't exist when the function has been stopped.
+    pub async fn requeue(&self, req: InvokeRequest) -> Result<(), InvokeRequest> {
+        let inner = self.inner.read().await;
+        match inner.get(&req.function_name) {
+            Some(stack) => stack.tx.try_send(req).map_err(|e| e.into_inner()),
+            None => Err(req),
+        }
+    }
+
     pub async fn pop(&self, function_name: &str) -> Option<InvokeRequest> {
         let inner = self.inner.read().await;
         let stack = match inner.get(function_name) {
@@ -162,7 +413,7 @@ impl RequestCache {
                 let stack = match inner.entry(function_name.to_owned()) {
                     Entry::Occupied(o) => o.into_mut().clone(),
                     Entry::Vacant(v) => {
-                        let stack = v.insert(RequestQueue::new()).clone();
+                        let stack = v.insert(RequestQueue::new(self.queue_size)).clone();
                         debug!(
                             ?function_name,
                             "request stack initialized in first lambda connection"
@@ -189,6 +440,12 @@ impl RequestCache {
         debug!(function_name, "request stack cleaned");
     }
 
+    /// Number of invocations waiting for a function.
+    pub async fn depth(&self, function_name: &str) -> Option<usize> {
+        let inner = self.inner.read().await;
+        inner.get(function_name).map(RequestQueue::depth)
+    }
+
     pub async fn keys(&self) -> Vec<String> {
         let inner = self.inner.read().await;
         inner.keys().cloned().collect()
@@ -197,7 +454,7 @@ impl RequestCache {
 
 #[derive(Clone)]
 pub(crate) struct ResponseCache {
-    inner: Arc<Mutex<HashMap<String, oneshot::Sender<LambdaResponse>>>>,
+    inner: Arc<Mutex<HashMap<String, InFlightInvocation>>>,
 }
 
 impl ResponseCache {
@@ -207,17 +464,72 @@ impl ResponseCache {
         }
     }
 
-    pub async fn pop(&self, req_id: &str) -> Option<oneshot::Sender<LambdaResponse>> {
+    pub async fn pop(&self, req_id: &str) -> Option<InFlightInvocation> {
         let mut cache = self.inner.lock().await;
         cache.remove(req_id)
     }
 
-    pub async fn push(&self, req_id: &str, resp_tx: oneshot::Sender<LambdaResponse>) {
+    pub async fn push(&self, req_id: &str, invocation: InFlightInvocation) {
         let mut cache = self.inner.lock().await;
-        cache.insert(req_id.into(), resp_tx);
+        cache.insert(req_id.into(), invocation);
+    }
+
+    /// Remove all the invocations that a function is processing.
+    pub async fn take_function(&self, function_name: &str) -> Vec<InFlightInvocation> {
+        let mut cache = self.inner.lock().await;
+        let ids = cache
+            .iter()
+            .filter(|(_, i)| i.function_name == function_name)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+
+        ids.into_iter().filter_map(|id| cache.remove(&id)).collect()
     }
 }
 
+/// Limit of invocations in flight for each function, like
+/// the reserved concurrency of functions in Lambda.
+#[derive(Clone, Default)]
+pub(crate) struct ConcurrencyLimiter {
+    limit: Option<usize>,
+    executions: Arc<std::sync::Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(limit: Option<usize>) -> ConcurrencyLimiter {
+        ConcurrencyLimiter {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    /// Reserve an execution for an invocation. It returns None when the function
+    /// has as many invocations in flight as its reserved concurrency,
+    /// and the invocation must be throttled.
+    pub fn acquire(&self, function_name: &str) -> Option<ExecutionPermit> {
+        let Some(limit) = self.limit else {
+            return Some(ExecutionPermit(None));
+        };
+
+        let semaphore = self
+            .executions
+            .lock()
+            .expect("concurrency limiter lock poisoned")
+            .entry(function_name.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+            .clone();
+
+        semaphore
+            .try_acquire_owned()
+            .ok()
+            .map(|permit| ExecutionPermit(Some(permit)))
+    }
+}
+
+/// Execution reserved for an invocation, it's released when the permit is dropped.
+/// Functions without reserved concurrency get permits that don't reserve anything.
+pub(crate) struct ExecutionPermit(#[allow(dead_code)] Option<OwnedSemaphorePermit>);
+
 #[derive(Clone, Default)]
 pub(crate) struct ExtensionCache {
     extensions: Arc<Mutex<HashMap<String, Vec<String>>>>,
@@ -285,3 +597,270 @@ impl ExtensionCache {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::LambdaResponse;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::Duration,
+    };
+    use tokio::sync::oneshot;
+
+    fn test_state() -> RuntimeState {
+        RuntimeState::new(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+            None,
+            PathBuf::new(),
+            HashSet::new(),
+            None,
+        )
+    }
+
+    fn in_flight(
+        function_name: &str,
+        attempts: usize,
+    ) -> (InFlightInvocation, oneshot::Receiver<LambdaResponse>) {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        let (parts, _) = Request::builder()
+            .uri("/2015-03-31/functions/basic-lambda/invocations")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let invocation = InFlightInvocation {
+            function_name: function_name.into(),
+            parts,
+            body: "{\"command\":\"hi\"}".into(),
+            resp_tx,
+            attempts,
+        };
+        (invocation, resp_rx)
+    }
+
+    async fn assert_exit_error(resp_rx: oneshot::Receiver<LambdaResponse>, message: &str) {
+        assert_function_error(resp_rx, EXIT_ERROR, message).await;
+    }
+
+    async fn assert_function_error(
+        resp_rx: oneshot::Receiver<LambdaResponse>,
+        error_type: &str,
+        message: &str,
+    ) {
+        let resp = resp_rx.await.unwrap();
+        assert_eq!(
+            Some(&StatusCode::INTERNAL_SERVER_ERROR),
+            resp.extensions().get::<StatusCode>()
+        );
+
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error_type, body["errorType"]);
+        assert_eq!(message, body["errorMessage"]);
+    }
+
+    #[tokio::test]
+    async fn test_fail_invocations() {
+        let state = test_state();
+        state.req_cache.init("basic-lambda").await;
+
+        let (invocation, resp_rx) = in_flight("basic-lambda", 1);
+        state.res_cache.push("req-1", invocation).await;
+
+        state
+            .fail_invocations(
+                "basic-lambda",
+                "Runtime.OutOfMemory",
+                "Runtime exited with error: signal: killed",
+            )
+            .await;
+
+        assert!(state.res_cache.pop("req-1").await.is_none());
+        assert_eq!(Some(0), state.req_cache.depth("basic-lambda").await);
+        assert_function_error(
+            resp_rx,
+            "Runtime.OutOfMemory",
+            "Runtime exited with error: signal: killed",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_recover_invocations() {
+        let state = test_state();
+        state.req_cache.init("basic-lambda").await;
+
+        let (invocation, _resp_rx) = in_flight("basic-lambda", 1);
+        state.res_cache.push("req-1", invocation).await;
+        let (invocation, _other_rx) = in_flight("other-lambda", 1);
+        state.res_cache.push("req-2", invocation).await;
+
+        assert!(
+            state
+                .recover_invocations("basic-lambda", "Runtime exited with error: exit status 1")
+                .await
+        );
+        assert!(state.res_cache.pop("req-1").await.is_none());
+        assert!(state.res_cache.pop("req-2").await.is_some());
+
+        let req = state.req_cache.pop("basic-lambda").await.unwrap();
+        assert_eq!(1, req.attempts);
+        assert_eq!(
+            "/2015-03-31/functions/basic-lambda/invocations",
+            req.req.uri()
+        );
+        let body = req.req.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!("{\"command\":\"hi\"}", body);
+    }
+
+    #[tokio::test]
+    async fn test_throttle_invocations_when_queue_is_full() {
+        let state = test_state().with_invoke_queue_size(1);
+        assert_eq!(1, state.invoke_queue_size());
+
+        let mut receivers = Vec::new();
+        for _ in 0..2 {
+            let (resp_tx, resp_rx) = oneshot::channel();
+            let req = InvokeRequest {
+                function_name: "basic-lambda".into(),
+                req: Request::new(Body::empty()),
+                resp_tx,
+                attempts: 0,
+            };
+            state.req_cache.upsert(req).await.unwrap();
+            receivers.push(resp_rx);
+        }
+        assert_eq!(Some(1), state.req_cache.depth("basic-lambda").await);
+
+        let resp = receivers.pop().unwrap().await.unwrap();
+        assert_eq!(
+            Some(&StatusCode::TOO_MANY_REQUESTS),
+            resp.extensions().get::<StatusCode>()
+        );
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!("Rate Exceeded.", body["message"]);
+
+        assert!(state.req_cache.pop("basic-lambda").await.is_some());
+        assert_eq!(Some(0), state.req_cache.depth("basic-lambda").await);
+    }
+
+    #[tokio::test]
+    async fn test_recover_invocations_after_max_attempts() {
+        let state = test_state();
+        state.req_cache.init("basic-lambda").await;
+
+        let (invocation, resp_rx) = in_flight("basic-lambda", MAX_INVOKE_ATTEMPTS);
+        state.res_cache.push("req-1", invocation).await;
+
+        let message = "Runtime exited with error: signal: SIGSEGV";
+        assert!(!state.recover_invocations("basic-lambda", message).await);
+        assert_exit_error(resp_rx, message).await;
+    }
+
+    #[tokio::test]
+    async fn test_recover_invocations_of_stopped_function() {
+        let state = test_state();
+
+        let (invocation, resp_rx) = in_flight("basic-lambda", 1);
+        state.res_cache.push("req-1", invocation).await;
+
+        let message = "Runtime exited without providing a reason";
+        assert!(!state.recover_invocations("basic-lambda", message).await);
+        assert_exit_error(resp_rx, message).await;
+    }
+
+    #[tokio::test]
+    async fn test_drain_invocations() {
+        let state = test_state();
+
+        let invocation = state.track_invocation().expect("missing invocation token");
+
+        let drain = state.drain_invocations();
+        tokio::pin!(drain);
+
+        let res = tokio::time::timeout(Duration::from_millis(50), &mut drain).await;
+        assert!(res.is_err(), "drain finished with an invocation in flight");
+        assert!(state.track_invocation().is_none());
+
+        drop(invocation);
+        tokio::time::timeout(Duration::from_millis(50), &mut drain)
+            .await
+            .expect("drain didn't finish after the invocation completed");
+    }
+
+    #[test]
+    fn test_reserved_concurrency() {
+        let state = test_state().with_reserved_concurrency(Some(2));
+
+        let first = state.concurrency.acquire("basic-lambda");
+        let second = state.concurrency.acquire("basic-lambda");
+        assert!(first.is_some());
+        assert!(second.is_some());
+        assert!(state.concurrency.acquire("basic-lambda").is_none());
+
+        // Each function has its own limit.
+        assert!(state.concurrency.acquire("other-lambda").is_some());
+
+        drop(first);
+        assert!(state.concurrency.acquire("basic-lambda").is_some());
+
+        let state = test_state().with_reserved_concurrency(Some(0));
+        assert!(state.concurrency.acquire("basic-lambda").is_none());
+
+        let state = test_state();
+        let permits = (0..10)
+            .map(|_| state.concurrency.acquire("basic-lambda"))
+            .collect::<Vec<_>>();
+        assert!(permits.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn test_functions_health() {
+        let state = RuntimeState::new(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+            None,
+            PathBuf::new(),
+            HashSet::from(["basic-lambda".to_string()]),
+            None,
+        );
+        assert_eq!(
+            BTreeMap::from([("basic-lambda".to_string(), FunctionHealth::Idle)]),
+            state.functions_health()
+        );
+
+        state.reload.function_compiling(DEFAULT_PACKAGE_FUNCTION);
+        assert_eq!(
+            BTreeMap::from([("basic-lambda".to_string(), FunctionHealth::Starting)]),
+            state.functions_health()
+        );
+    }
+
+    #[test]
+    fn test_runtime_api_host() {
+        let state = RuntimeState::new(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 9001),
+            None,
+            PathBuf::new(),
+            HashSet::new(),
+            None,
+        );
+        assert_eq!("http://0.0.0.0:9001/.rt/_", state.function_addr("_"));
+
+        let host = RuntimeApiHost {
+            host: "host.docker.internal".into(),
+            port: None,
+        };
+        let state = state.with_runtime_api_host(Some(host));
+        assert_eq!(
+            "http://host.docker.internal:9001/.rt/_",
+            state.function_addr("_")
+        );
+    }
+}
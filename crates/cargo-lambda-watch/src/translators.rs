@@ -0,0 +1,620 @@
+use aws_lambda_events::apigw::{
+    ApiGatewayProxyRequest, ApiGatewayProxyRequestContext, ApiGatewayRequestIdentity,
+    ApiGatewayV2httpRequest, ApiGatewayV2httpRequestContext,
+    ApiGatewayV2httpRequestContextHttpDescription,
+};
+use axum::http::request::Parts;
+use cargo_lambda_metadata::{cargo::watch::EventFormat, lambda::RequestContext};
+use chrono::{DateTime, Utc};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use query_map::QueryMap;
+use serde_json::{Map, Value, json};
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+const SOURCE_IP: &str = "127.0.0.1";
+const USER_AGENT: &str = "cargo-lambda";
+
+/// HttpRequest includes the information about an HTTP request
+/// that translators use to build the events for the functions.
+pub(crate) struct HttpRequest<'a> {
+    pub function_name: &'a str,
+    pub parts: &'a Parts,
+    pub path: &'a str,
+    pub path_parameters: HashMap<String, String>,
+    pub body: Option<String>,
+    pub is_base64_encoded: bool,
+    pub request_id: &'a str,
+    pub time: DateTime<Utc>,
+}
+
+/// EventTranslator converts HTTP requests into the events
+/// that AWS services send to Lambda functions.
+pub(crate) trait EventTranslator: Send + Sync {
+    fn translate(&self, req: &HttpRequest<'_>) -> serde_json::Result<Value>;
+}
+
+pub(crate) type RefEventTranslator = Arc<dyn EventTranslator>;
+
+/// Returns the translator for an event format.
+pub(crate) fn translator(format: EventFormat, alb_multi_value_headers: bool) -> RefEventTranslator {
+    match format {
+        EventFormat::FunctionUrl => Arc::new(HttpApiTranslator { api_gateway: false }),
+        EventFormat::ApigwHttp => Arc::new(HttpApiTranslator { api_gateway: true }),
+        EventFormat::ApigwRest => Arc::new(RestApiTranslator),
+        EventFormat::Alb => Arc::new(AlbTranslator {
+            multi_value: alb_multi_value_headers,
+        }),
+    }
+}
+
+/// Translator that merges fields into the `requestContext`
+/// of the events that another translator builds.
+pub(crate) struct RequestContextTranslator {
+    pub translator: RefEventTranslator,
+    pub context: RequestContext,
+}
+
+impl EventTranslator for RequestContextTranslator {
+    fn translate(&self, req: &HttpRequest<'_>) -> serde_json::Result<Value> {
+        let mut event = self.translator.translate(req)?;
+        self.context.merge_into(&mut event);
+        Ok(event)
+    }
+}
+
+/// Translator for function URLs, and API Gateway HTTP APIs.
+/// Both services send events with the payload format version 2.0.
+struct HttpApiTranslator {
+    api_gateway: bool,
+}
+
+impl EventTranslator for HttpApiTranslator {
+    fn translate(&self, req: &HttpRequest<'_>) -> serde_json::Result<Value> {
+        let parts = req.parts;
+        let uri = &parts.uri;
+
+        let query_string_parameters = uri
+            .query()
+            .unwrap_or_default()
+            .parse::<QueryMap>()
+            .unwrap_or_default();
+
+        let cookies = parts.headers.get("cookie").map(|c| {
+            c.to_str()
+                .unwrap_or_default()
+                .split("; ")
+                .map(|s| s.trim().to_string())
+                .collect()
+        });
+
+        let (account_id, apiid) = if self.api_gateway {
+            (Some("123456789012".into()), Some(USER_AGENT.into()))
+        } else {
+            (None, None)
+        };
+
+        let request_context = ApiGatewayV2httpRequestContext {
+            stage: Some("$default".into()),
+            route_key: Some("$default".into()),
+            request_id: Some(req.request_id.into()),
+            domain_name: Some("localhost".into()),
+            domain_prefix: Some(req.function_name.into()),
+            http: ApiGatewayV2httpRequestContextHttpDescription {
+                method: parts.method.clone(),
+                path: Some(req.path.into()),
+                protocol: Some("http".into()),
+                source_ip: Some(SOURCE_IP.into()),
+                user_agent: Some(USER_AGENT.into()),
+            },
+            time: Some(req.time.format("%d/%b/%Y:%T %z").to_string()),
+            time_epoch: req.time.timestamp(),
+            account_id,
+            authorizer: None,
+            authentication: None,
+            apiid,
+        };
+
+        let event = ApiGatewayV2httpRequest {
+            version: Some("2.0".into()),
+            route_key: Some("$default".into()),
+            raw_path: Some(req.path.into()),
+            raw_query_string: uri.query().map(String::from),
+            headers: parts.headers.clone(),
+            body: req.body.clone(),
+            request_context,
+            cookies,
+            query_string_parameters,
+            is_base64_encoded: req.is_base64_encoded,
+            path_parameters: req.path_parameters.clone(),
+            ..Default::default()
+        };
+
+        serde_json::to_value(event)
+    }
+}
+
+/// Translator for API Gateway REST APIs,
+/// that send events with the payload format version 1.0.
+struct RestApiTranslator;
+
+impl EventTranslator for RestApiTranslator {
+    fn translate(&self, req: &HttpRequest<'_>) -> serde_json::Result<Value> {
+        let parts = req.parts;
+
+        let query = parts
+            .uri
+            .query()
+            .unwrap_or_default()
+            .parse::<QueryMap>()
+            .unwrap_or_default();
+
+        // The stage `$default` prevents the runtime from adding
+        // the stage name to the path that the function receives.
+        let request_context = ApiGatewayProxyRequestContext {
+            account_id: Some("123456789012".into()),
+            stage: Some("$default".into()),
+            domain_name: Some("localhost".into()),
+            domain_prefix: Some(req.function_name.into()),
+            request_id: Some(req.request_id.into()),
+            protocol: Some("HTTP/1.1".into()),
+            identity: ApiGatewayRequestIdentity {
+                source_ip: Some(SOURCE_IP.into()),
+                user_agent: Some(USER_AGENT.into()),
+                ..Default::default()
+            },
+            resource_path: Some(req.path.into()),
+            path: Some(req.path.into()),
+            http_method: parts.method.clone(),
+            request_time: Some(req.time.format("%d/%b/%Y:%T %z").to_string()),
+            request_time_epoch: req.time.timestamp_millis(),
+            apiid: Some(USER_AGENT.into()),
+            ..Default::default()
+        };
+
+        let event = ApiGatewayProxyRequest {
+            resource: Some(req.path.into()),
+            path: Some(req.path.into()),
+            http_method: parts.method.clone(),
+            headers: parts.headers.clone(),
+            multi_value_headers: parts.headers.clone(),
+            query_string_parameters: query.clone(),
+            multi_value_query_string_parameters: query,
+            path_parameters: req.path_parameters.clone(),
+            stage_variables: HashMap::new(),
+            request_context,
+            body: req.body.clone(),
+            is_base64_encoded: req.is_base64_encoded,
+        };
+
+        serde_json::to_value(event)
+    }
+}
+
+/// Translator for Application Load Balancer target groups.
+/// ALB forwards query string values without decoding them, and when multi-value headers are
+/// disabled, it only keeps the last value for each header and query string parameter.
+struct AlbTranslator {
+    multi_value: bool,
+}
+
+impl EventTranslator for AlbTranslator {
+    fn translate(&self, req: &HttpRequest<'_>) -> serde_json::Result<Value> {
+        let parts = req.parts;
+        let mut event = json!({
+            "requestContext": {
+                "elb": {
+                    "targetGroupArn": format!("arn:aws:elasticloadbalancing:us-east-1:123456789012:targetgroup/{}/cargo-lambda", req.function_name),
+                },
+            },
+            "httpMethod": parts.method.as_str(),
+            "path": req.path,
+            "body": req.body.clone().unwrap_or_default(),
+            "isBase64Encoded": req.is_base64_encoded,
+        });
+
+        let query = raw_query_parameters(parts.uri.query());
+        let headers = multi_value_headers(parts);
+
+        if self.multi_value {
+            event["multiValueQueryStringParameters"] = json!(query);
+            event["multiValueHeaders"] = json!(headers);
+        } else {
+            event["queryStringParameters"] = json!(last_values(&query));
+            event["headers"] = json!(last_values(&headers));
+        }
+
+        Ok(event)
+    }
+}
+
+/// Translator that builds events from a JSON template, for proxy integrations
+/// that don't match any of the AWS services. String values in the template
+/// can reference the request with placeholders, like `${path}`, or `${header.host}`.
+/// A string that only includes a placeholder is replaced with the JSON value,
+/// so `"${headers}"` is replaced with an object with all the headers.
+pub(crate) struct MappingTranslator {
+    template: Value,
+}
+
+impl MappingTranslator {
+    pub(crate) fn load(path: &Path) -> Result<MappingTranslator> {
+        let content = std::fs::read_to_string(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read the event mapping {}", path.display()))?;
+        let template = serde_json::from_str(&content)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("invalid event mapping {}", path.display()))?;
+        Ok(MappingTranslator { template })
+    }
+}
+
+impl EventTranslator for MappingTranslator {
+    fn translate(&self, req: &HttpRequest<'_>) -> serde_json::Result<Value> {
+        let variables = MappingVariables::new(req);
+        Ok(render_template(&self.template, &variables))
+    }
+}
+
+/// Values that placeholders in a mapping template can reference.
+struct MappingVariables {
+    values: HashMap<&'static str, Value>,
+    headers: HashMap<String, Vec<String>>,
+    query: HashMap<String, Vec<String>>,
+    path_parameters: HashMap<String, String>,
+}
+
+impl MappingVariables {
+    fn new(req: &HttpRequest<'_>) -> MappingVariables {
+        let parts = req.parts;
+        let headers = multi_value_headers(parts)
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.into_iter().map(String::from).collect()))
+            .collect::<HashMap<String, Vec<String>>>();
+        let query = raw_query_parameters(parts.uri.query());
+
+        let values = HashMap::from([
+            ("functionName", json!(req.function_name)),
+            ("method", json!(parts.method.as_str())),
+            ("path", json!(req.path)),
+            (
+                "rawQueryString",
+                json!(parts.uri.query().unwrap_or_default()),
+            ),
+            ("headers", json!(last_values(&headers))),
+            ("multiValueHeaders", json!(headers)),
+            ("queryStringParameters", json!(last_values(&query))),
+            ("multiValueQueryStringParameters", json!(query)),
+            ("pathParameters", json!(req.path_parameters)),
+            ("body", json!(req.body)),
+            ("isBase64Encoded", json!(req.is_base64_encoded)),
+            ("requestId", json!(req.request_id)),
+            ("requestTime", json!(req.time.to_rfc3339())),
+            ("requestTimeEpoch", json!(req.time.timestamp_millis())),
+            ("sourceIp", json!(SOURCE_IP)),
+        ]);
+
+        MappingVariables {
+            values,
+            headers,
+            query,
+            path_parameters: req.path_parameters.clone(),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<Value> {
+        if let Some(header) = name.strip_prefix("header.") {
+            let header = header.to_lowercase();
+            return self
+                .headers
+                .get(&header)
+                .and_then(|v| v.last())
+                .map(|v| json!(v));
+        }
+        if let Some(param) = name.strip_prefix("query.") {
+            return self
+                .query
+                .get(param)
+                .and_then(|v| v.last())
+                .map(|v| json!(v));
+        }
+        if let Some(param) = name.strip_prefix("pathParameters.") {
+            return self.path_parameters.get(param).map(|v| json!(v));
+        }
+        self.values.get(name).cloned()
+    }
+}
+
+fn render_template(template: &Value, variables: &MappingVariables) -> Value {
+    match template {
+        Value::String(s) => render_string(s, variables),
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|v| render_template(v, variables))
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), render_template(v, variables)))
+                .collect::<Map<String, Value>>(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn render_string(s: &str, variables: &MappingVariables) -> Value {
+    if let Some(name) = s.strip_prefix("${").and_then(|s| s.strip_suffix('}')) {
+        if !name.contains("${") {
+            return variables.get(name).unwrap_or(Value::Null);
+        }
+    }
+
+    let mut output = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+
+        let name = &rest[start + 2..start + end];
+        match variables.get(name) {
+            Some(Value::String(value)) => output.push_str(&value),
+            Some(Value::Null) | None => {}
+            Some(value) => output.push_str(&value.to_string()),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    output.push_str(rest);
+
+    Value::String(output)
+}
+
+fn multi_value_headers(parts: &Parts) -> HashMap<&str, Vec<&str>> {
+    let mut headers: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (name, value) in &parts.headers {
+        if let Ok(value) = value.to_str() {
+            headers.entry(name.as_str()).or_default().push(value);
+        }
+    }
+    headers
+}
+
+fn last_values<K: AsRef<str>, V: AsRef<str>>(values: &HashMap<K, Vec<V>>) -> HashMap<&str, &str> {
+    values
+        .iter()
+        .filter_map(|(k, v)| v.last().map(|v| (k.as_ref(), v.as_ref())))
+        .collect()
+}
+
+fn raw_query_parameters(query: Option<&str>) -> HashMap<String, Vec<String>> {
+    let mut params: HashMap<String, Vec<String>> = HashMap::new();
+    for pair in query.unwrap_or_default().split('&') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params
+            .entry(key.to_string())
+            .or_default()
+            .push(value.to_string());
+    }
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use chrono::TimeZone;
+
+    fn request_parts(uri: &str) -> Parts {
+        let (parts, _) = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("x-custom", "1")
+            .header("x-custom", "2")
+            .header("host", "localhost:9000")
+            .body(())
+            .unwrap()
+            .into_parts();
+        parts
+    }
+
+    fn http_request<'a>(parts: &'a Parts, path: &'a str) -> HttpRequest<'a> {
+        HttpRequest {
+            function_name: "func-name",
+            parts,
+            path,
+            path_parameters: HashMap::from([("id".to_string(), "42".to_string())]),
+            body: Some("hello".into()),
+            is_base64_encoded: false,
+            request_id: "req-id",
+            time: Utc.with_ymd_and_hms(2024, 3, 5, 10, 30, 0).unwrap(),
+        }
+    }
+
+    fn alb_event(parts: &Parts, body: Option<String>, multi_value: bool) -> Value {
+        let req = HttpRequest {
+            body,
+            path_parameters: HashMap::new(),
+            ..http_request(parts, "/foo")
+        };
+        translator(EventFormat::Alb, multi_value)
+            .translate(&req)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_alb_event_single_value() {
+        let (parts, _) = Request::get("/foo?a=1&a=2&b=hello%20world")
+            .header("x-custom", "first")
+            .header("x-custom", "second")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let event = alb_event(&parts, None, false);
+        assert_eq!(event["httpMethod"], "GET");
+        assert_eq!(event["path"], "/foo");
+        assert_eq!(event["body"], "");
+        assert_eq!(event["queryStringParameters"]["a"], "2");
+        assert_eq!(event["queryStringParameters"]["b"], "hello%20world");
+        assert_eq!(event["headers"]["x-custom"], "second");
+        assert!(event.get("multiValueQueryStringParameters").is_none());
+        assert!(event.get("multiValueHeaders").is_none());
+        assert_eq!(
+            event["requestContext"]["elb"]["targetGroupArn"],
+            "arn:aws:elasticloadbalancing:us-east-1:123456789012:targetgroup/func-name/cargo-lambda"
+        );
+    }
+
+    #[test]
+    fn test_alb_event_multi_value() {
+        let (parts, _) = Request::post("/foo?a=1&a=2&b=hello%20world&c")
+            .header("x-custom", "first")
+            .header("x-custom", "second")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let event = alb_event(&parts, Some("hello".into()), true);
+        assert_eq!(event["httpMethod"], "POST");
+        assert_eq!(event["body"], "hello");
+        assert_eq!(
+            event["multiValueQueryStringParameters"]["a"],
+            json!(["1", "2"])
+        );
+        assert_eq!(
+            event["multiValueQueryStringParameters"]["b"],
+            json!(["hello%20world"])
+        );
+        assert_eq!(event["multiValueQueryStringParameters"]["c"], json!([""]));
+        assert_eq!(
+            event["multiValueHeaders"]["x-custom"],
+            json!(["first", "second"])
+        );
+        assert!(event.get("queryStringParameters").is_none());
+        assert!(event.get("headers").is_none());
+    }
+
+    #[test]
+    fn test_function_url_event() {
+        let parts = request_parts("/foo?name=c");
+        let req = http_request(&parts, "/foo");
+
+        let event = translator(EventFormat::FunctionUrl, false)
+            .translate(&req)
+            .unwrap();
+        assert_eq!(event["version"], "2.0");
+        assert_eq!(event["rawPath"], "/foo");
+        assert_eq!(event["rawQueryString"], "name=c");
+        assert_eq!(event["pathParameters"]["id"], "42");
+        assert_eq!(event["requestContext"]["requestId"], "req-id");
+        assert_eq!(event["requestContext"]["http"]["method"], "POST");
+        assert!(event["requestContext"]["apiId"].is_null());
+
+        let event = translator(EventFormat::ApigwHttp, false)
+            .translate(&req)
+            .unwrap();
+        assert_eq!(event["requestContext"]["apiId"], "cargo-lambda");
+    }
+
+    #[test]
+    fn test_rest_api_event() {
+        let parts = request_parts("/foo?name=c");
+        let req = http_request(&parts, "/foo");
+
+        let event = translator(EventFormat::ApigwRest, false)
+            .translate(&req)
+            .unwrap();
+        assert_eq!(event["httpMethod"], "POST");
+        assert_eq!(event["path"], "/foo");
+        assert_eq!(event["queryStringParameters"]["name"], "c");
+        assert_eq!(event["multiValueHeaders"]["x-custom"], json!(["1", "2"]));
+        assert_eq!(event["pathParameters"]["id"], "42");
+        assert_eq!(event["requestContext"]["stage"], "$default");
+        assert_eq!(event["requestContext"]["identity"]["sourceIp"], "127.0.0.1");
+        assert_eq!(
+            event["requestContext"]["requestTimeEpoch"],
+            1709634600000_i64
+        );
+    }
+
+    #[test]
+    fn test_mapping_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mapping.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "route": "${method} ${path}",
+                "host": "${header.Host}",
+                "params": "${pathParameters}",
+                "id": "${pathParameters.id}",
+                "payload": {"body": "${body}", "encoded": "${isBase64Encoded}"},
+                "missing": "${header.x-missing}",
+                "tags": ["static", "${query.name}"],
+                "version": 1
+            }"#,
+        )
+        .unwrap();
+
+        let parts = request_parts("/foo?name=c");
+        let req = http_request(&parts, "/foo");
+
+        let event = MappingTranslator::load(&path)
+            .unwrap()
+            .translate(&req)
+            .unwrap();
+        assert_eq!(
+            json!({
+                "route": "POST /foo",
+                "host": "localhost:9000",
+                "params": {"id": "42"},
+                "id": "42",
+                "payload": {"body": "hello", "encoded": false},
+                "missing": null,
+                "tags": ["static", "c"],
+                "version": 1
+            }),
+            event
+        );
+    }
+
+    #[test]
+    fn test_request_context_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("context.json");
+        std::fs::write(
+            &path,
+            r#"{"authorizer": {"jwt": {"claims": {"sub": "user-1"}}}, "http": {"sourceIp": "10.0.0.1"}}"#,
+        )
+        .unwrap();
+
+        let translator = RequestContextTranslator {
+            translator: translator(EventFormat::FunctionUrl, false),
+            context: RequestContext::load(&path).unwrap(),
+        };
+
+        let parts = request_parts("/foo");
+        let req = http_request(&parts, "/foo");
+
+        let event = translator.translate(&req).unwrap();
+        let context = &event["requestContext"];
+        assert_eq!(context["authorizer"]["jwt"]["claims"]["sub"], "user-1");
+        assert_eq!(context["http"]["sourceIp"], "10.0.0.1");
+        assert_eq!(context["http"]["method"], "POST");
+        assert_eq!(context["requestId"], "req-id");
+    }
+
+    #[test]
+    fn test_mapping_invalid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mapping.json");
+        std::fs::write(&path, "not json").unwrap();
+
+        assert!(MappingTranslator::load(&path).is_err());
+        assert!(MappingTranslator::load(&dir.path().join("missing.json")).is_err());
+    }
+}
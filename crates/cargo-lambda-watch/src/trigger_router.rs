@@ -1,22 +1,18 @@
 use crate::{
     RefRuntimeState,
     error::ServerError,
+    jwt::{self, JwtError},
     requests::*,
     runtime::{LAMBDA_RUNTIME_AWS_REQUEST_ID, LAMBDA_RUNTIME_XRAY_TRACE_HEADER},
+    translators::HttpRequest,
 };
-use aws_lambda_events::{
-    apigw::{
-        ApiGatewayV2httpRequest, ApiGatewayV2httpRequestContext,
-        ApiGatewayV2httpRequestContextHttpDescription, ApiGatewayV2httpResponse,
-    },
-    encodings::Body as LambdaBody,
-};
+use aws_lambda_events::{apigw::ApiGatewayV2httpResponse, encodings::Body as LambdaBody};
 use axum::{
     Router,
     body::Body,
     extract::{Extension, Path, State},
-    http::{HeaderValue, Request, response::Builder},
-    response::Response,
+    http::{HeaderValue, Request, header::ToStrError, response::Builder},
+    response::{IntoResponse, Response},
     routing::{any, post},
 };
 use base64::{Engine as _, engine::general_purpose as b64};
@@ -30,12 +26,29 @@ use opentelemetry::{
     Context, KeyValue, global,
     trace::{TraceContextExt, Tracer},
 };
-use query_map::QueryMap;
 use std::collections::{HashMap, HashSet};
-use tokio::sync::{mpsc::Sender, oneshot};
+use tokio::sync::{
+    mpsc::{
+        Sender,
+        error::{SendError, TrySendError},
+    },
+    oneshot,
+};
 
 const LAMBDA_URL_PREFIX: &str = "lambda-url";
 
+/// Header that AWS uses to return the id of a request to clients.
+const AMZN_REQUEST_ID_HEADER: &str = "x-amzn-requestid";
+
+/// Header that AWS uses to return the type of error to clients.
+const AMZN_ERROR_TYPE_HEADER: &str = "x-amzn-errortype";
+
+/// Header that AWS uses to tell clients that the function returned an error.
+const AMZ_FUNCTION_ERROR_HEADER: &str = "x-amz-function-error";
+
+/// Seconds that clients should wait before retrying throttled invocations.
+const THROTTLING_RETRY_AFTER: &str = "1";
+
 pub(crate) fn routes() -> Router<RefRuntimeState> {
     Router::new()
         .route(
@@ -60,6 +73,14 @@ async fn furls_handler(
         extract_path_parameters(uri.path(), &parts.method, &state);
     tracing::trace!(%function_name, %path, "received request in furls handler");
 
+    if state.alb_options.is_health_check(&path) {
+        tracing::debug!(%path, "responding to load balancer health check");
+        return Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::empty())
+            .map_err(ServerError::ResponseBuild);
+    }
+
     if function_name == DEFAULT_PACKAGE_FUNCTION && !state.is_default_function_enabled() {
         return respond_with_disabled_default_function(&state, false);
     }
@@ -70,6 +91,18 @@ async fn furls_handler(
         }
     }
 
+    let jwt_claims = match &state.jwt_authorizer {
+        None => None,
+        Some(authorizer) => match authorizer.authorize(&parts.headers) {
+            Ok(claims) => Some(claims),
+            Err(error) => return Ok(respond_with_unauthorized(&error)),
+        },
+    };
+
+    let Some(_invocation) = state.track_invocation() else {
+        return Ok(respond_with_server_draining());
+    };
+
     let headers = &parts.headers;
 
     let body = body
@@ -96,80 +129,44 @@ async fn furls_handler(
         (Some(body), true)
     };
 
-    let query_string_parameters = uri
-        .query()
-        .unwrap_or_default()
-        .parse::<QueryMap>()
-        .unwrap_or_default();
-
-    let cookies = headers.get("cookie").map(|c| {
-        c.to_str()
-            .unwrap_or_default()
-            .split("; ")
-            .map(|s| s.trim().to_string())
-            .collect()
-    });
-
-    let req_id = headers
-        .get(LAMBDA_RUNTIME_AWS_REQUEST_ID)
-        .expect("missing request id")
-        .to_str()
-        .expect("invalid request id format");
-
-    let time = Utc::now();
-
     if !path.starts_with('/') {
         path = format!("/{path}");
     }
 
-    let request_context = ApiGatewayV2httpRequestContext {
-        stage: Some("$default".into()),
-        route_key: Some("$default".into()),
-        request_id: Some(req_id.into()),
-        domain_name: Some("localhost".into()),
-        domain_prefix: Some(function_name.clone()),
-        http: ApiGatewayV2httpRequestContextHttpDescription {
-            method: parts.method.clone(),
-            path: Some(path.clone()),
-            protocol: Some("http".into()),
-            source_ip: Some("127.0.0.1".into()),
-            user_agent: Some("cargo-lambda".into()),
-        },
-        time: Some(time.format("%d/%b/%Y:%T %z").to_string()),
-        time_epoch: time.timestamp(),
-        account_id: None,
-        authorizer: None,
-        authentication: None,
-        apiid: None,
-    };
+    let request_id = request_id(headers)?;
 
-    let event = ApiGatewayV2httpRequest {
-        version: Some("2.0".into()),
-        route_key: Some("$default".into()),
-        raw_path: Some(path),
-        raw_query_string: uri.query().map(String::from),
-        headers: headers.clone(),
+    let request = HttpRequest {
+        function_name: &function_name,
+        parts: &parts,
+        path: &path,
+        path_parameters,
         body,
-        request_context,
-        cookies,
-        query_string_parameters,
         is_base64_encoded,
-        path_parameters,
-        ..Default::default()
+        request_id: &request_id,
+        time: Utc::now(),
     };
-    let event = serde_json::to_string(&event).map_err(ServerError::SerializationError)?;
+    let mut event = state
+        .event_translator
+        .translate(&request)
+        .map_err(ServerError::SerializationError)?;
+    if let Some(claims) = &jwt_claims {
+        jwt::add_claims(&mut event, claims);
+    }
+    let event = event.to_string();
 
+    let display_name = state.function_display_name(&function_name);
     let req = Request::from_parts(parts, event.into());
-    let resp = schedule_invocation(&cmd_tx, function_name, req).await?;
+    let resp = schedule_invocation(&state, &cmd_tx, function_name, req).await?;
     let status_code = resp
         .extensions()
         .get::<StatusCode>()
         .cloned()
         .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    log_invocation(&display_name, &request_id, status_code);
 
     let (info, mut body) = resp.into_parts();
 
-    let mut builder = Response::builder().status(status_code);
+    let mut builder = response_builder(status_code, &request_id);
 
     let response = if status_code == StatusCode::OK {
         if is_streaming_response(&info.headers) {
@@ -194,6 +191,7 @@ async fn invoke_handler(
     Path(function_name): Path<String>,
     req: Request<Body>,
 ) -> Result<Response<Body>, ServerError> {
+    let function_name = state.invoked_function_name(function_name);
     tracing::debug!(%function_name, "invocation received");
 
     if function_name == DEFAULT_PACKAGE_FUNCTION && !state.is_default_function_enabled() {
@@ -207,16 +205,27 @@ async fn invoke_handler(
         }
     }
 
-    let resp = schedule_invocation(&cmd_tx, function_name, req).await?;
+    let Some(_invocation) = state.track_invocation() else {
+        return Ok(respond_with_server_draining());
+    };
+
+    let request_id = request_id(req.headers())?;
+    let display_name = state.function_display_name(&function_name);
+    let resp = schedule_invocation(&state, &cmd_tx, function_name, req).await?;
     let status_code = resp
         .extensions()
         .get::<StatusCode>()
         .cloned()
         .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    log_invocation(&display_name, &request_id, status_code);
 
     let (info, mut body) = resp.into_parts();
 
-    let mut builder = Response::builder().status(status_code);
+    let mut builder = if state.rie_compatible {
+        rie_response_builder(status_code, &request_id)
+    } else {
+        response_builder(status_code, &request_id)
+    };
 
     if is_streaming_response(&info.headers) && status_code == StatusCode::OK {
         let status = create_streaming_response(&mut builder, &mut body).await?;
@@ -226,7 +235,51 @@ async fn invoke_handler(
     builder.body(body).map_err(ServerError::ResponseBuild)
 }
 
+fn response_builder(status_code: StatusCode, request_id: &str) -> Builder {
+    let builder = Response::builder()
+        .status(status_code)
+        .header(AMZN_REQUEST_ID_HEADER, request_id);
+
+    if status_code == StatusCode::TOO_MANY_REQUESTS {
+        builder
+            .header(header::RETRY_AFTER, THROTTLING_RETRY_AFTER)
+            .header(AMZN_ERROR_TYPE_HEADER, "TooManyRequestsException")
+    } else {
+        builder
+    }
+}
+
+/// The Runtime Interface Emulator answers function errors with a `200 OK` status,
+/// like Lambda's Invoke API does, and flags them with a header.
+fn rie_response_builder(status_code: StatusCode, request_id: &str) -> Builder {
+    if status_code == StatusCode::INTERNAL_SERVER_ERROR {
+        response_builder(StatusCode::OK, request_id).header(AMZ_FUNCTION_ERROR_HEADER, "Unhandled")
+    } else {
+        response_builder(status_code, request_id)
+    }
+}
+
+/// Returns the id assigned to a request. The server assigns ids to all the
+/// requests that don't have one, and the functions receive them as their
+/// invocation ids.
+fn request_id(headers: &HeaderMap) -> Result<String, ToStrError> {
+    let request_id = headers
+        .get(LAMBDA_RUNTIME_AWS_REQUEST_ID)
+        .expect("missing request id")
+        .to_str()?;
+    Ok(request_id.to_string())
+}
+
+fn log_invocation(function_name: &str, request_id: &str, status_code: StatusCode) {
+    if status_code == StatusCode::OK {
+        tracing::info!(function = %function_name, %request_id, "invocation completed");
+    } else {
+        tracing::warn!(function = %function_name, %request_id, status = %status_code, "invocation failed");
+    }
+}
+
 async fn schedule_invocation(
+    state: &RefRuntimeState,
     cmd_tx: &Sender<Action>,
     function_name: String,
     mut req: Request<Body>,
@@ -254,16 +307,29 @@ async fn schedule_invocation(
         function_name
     };
 
+    // The execution is reserved until the function responds.
+    let Some(_execution) = state.concurrency.acquire(&function_name) else {
+        tracing::warn!(%function_name, "the function reached its reserved concurrency, throttling the invocation");
+        return Ok(concurrency_throttled_response());
+    };
+
     let req = InvokeRequest {
         function_name,
         req,
         resp_tx,
+        attempts: 0,
     };
 
-    cmd_tx
-        .send(Action::Invoke(req))
-        .await
-        .map_err(|e| ServerError::SendActionMessage(Box::new(e)))?;
+    match cmd_tx.try_send(Action::Invoke(req)) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => {
+            tracing::warn!("the scheduler's queue is full, rejecting the invocation");
+            return Ok(throttled_response());
+        }
+        Err(TrySendError::Closed(action)) => {
+            return Err(ServerError::SendActionMessage(Box::new(SendError(action))));
+        }
+    }
 
     let resp = resp_rx.await.map_err(ServerError::ReceiveFunctionMessage)?;
 
@@ -412,7 +478,7 @@ async fn create_buffered_response(
     Ok((status, resp_body))
 }
 
-fn respond_with_disabled_default_function(
+pub(crate) fn respond_with_disabled_default_function(
     state: &RefRuntimeState,
     invoke_call: bool,
 ) -> Result<Response<Body>, ServerError> {
@@ -436,7 +502,7 @@ fn respond_with_disabled_default_function(
         .map_err(ServerError::ResponseBuild)
 }
 
-fn respond_with_missing_function(
+pub(crate) fn respond_with_missing_function(
     binaries: &HashSet<String>,
 ) -> Result<Response<Body>, ServerError> {
     let detail = "that function doesn't exist as a binary in your project";
@@ -455,6 +521,33 @@ fn respond_with_missing_function(
         .map_err(ServerError::ResponseBuild)
 }
 
+/// Reject the request like API Gateway does when the JWT authorizer denies it.
+fn respond_with_unauthorized(error: &JwtError) -> Response<Body> {
+    tracing::warn!(%error, "rejecting request without a valid token");
+
+    let body = Body::from(serde_json::json!({ "message": "Unauthorized" }).to_string());
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        body,
+    )
+        .into_response()
+}
+
+fn respond_with_server_draining() -> Response<Body> {
+    let detail = "the server is shutting down, and it doesn't accept new invocations";
+    tracing::warn!(detail);
+
+    let body = Body::from(
+        serde_json::json!({
+            "title": "Server shutting down",
+            "detail": detail,
+        })
+        .to_string(),
+    );
+    (StatusCode::SERVICE_UNAVAILABLE, body).into_response()
+}
+
 #[cfg(test)]
 mod test {
     use std::{
@@ -466,7 +559,9 @@ mod test {
 
     use crate::RuntimeState;
 
-    use super::extract_path_parameters;
+    use super::{
+        StatusCode, extract_path_parameters, request_id, response_builder, rie_response_builder,
+    };
     use cargo_lambda_metadata::{
         DEFAULT_PACKAGE_FUNCTION,
         cargo::{
@@ -475,7 +570,7 @@ mod test {
         },
         config::{ConfigOptions, load_config_without_cli_flags},
     };
-    use http::Method;
+    use http::{HeaderMap, Method};
 
     #[test]
     fn test_extract_path_parameters() {
@@ -601,4 +696,89 @@ mod test {
         assert_eq!("/orgs/123/posts/456", path);
         assert!(params.is_empty());
     }
+
+    #[test]
+    fn test_request_id() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "lambda-runtime-aws-request-id",
+            "a1b2c3d4-e5f6".parse().unwrap(),
+        );
+        assert_eq!("a1b2c3d4-e5f6", request_id(&headers).unwrap());
+
+        headers.insert(
+            "lambda-runtime-aws-request-id",
+            http::HeaderValue::from_bytes(b"\xff").unwrap(),
+        );
+        assert!(request_id(&headers).is_err());
+    }
+
+    #[test]
+    fn test_throttled_response_headers() {
+        let resp = response_builder(StatusCode::TOO_MANY_REQUESTS, "req-1")
+            .body(())
+            .unwrap();
+        assert_eq!("req-1", resp.headers()["x-amzn-requestid"]);
+        assert_eq!("1", resp.headers()["retry-after"]);
+        assert_eq!(
+            "TooManyRequestsException",
+            resp.headers()["x-amzn-errortype"]
+        );
+
+        let resp = response_builder(StatusCode::OK, "req-1").body(()).unwrap();
+        assert!(!resp.headers().contains_key("retry-after"));
+    }
+
+    #[test]
+    fn test_rie_response_headers() {
+        let resp = rie_response_builder(StatusCode::INTERNAL_SERVER_ERROR, "req-1")
+            .body(())
+            .unwrap();
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("req-1", resp.headers()["x-amzn-requestid"]);
+        assert_eq!("Unhandled", resp.headers()["x-amz-function-error"]);
+
+        let resp = rie_response_builder(StatusCode::OK, "req-1")
+            .body(())
+            .unwrap();
+        assert_eq!(StatusCode::OK, resp.status());
+        assert!(!resp.headers().contains_key("x-amz-function-error"));
+
+        let resp = rie_response_builder(StatusCode::TOO_MANY_REQUESTS, "req-1")
+            .body(())
+            .unwrap();
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, resp.status());
+    }
+
+    #[test]
+    fn test_rie_function_name() {
+        let state = RuntimeState::new(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+            None,
+            PathBuf::new(),
+            HashSet::from(["basic-lambda".to_string()]),
+            None,
+        );
+        assert_eq!("function", state.invoked_function_name("function".into()));
+
+        let state = state.with_rie_compatible(true);
+        assert_eq!(
+            DEFAULT_PACKAGE_FUNCTION,
+            state.invoked_function_name("function".into())
+        );
+        assert_eq!(
+            "basic-lambda",
+            state.invoked_function_name("basic-lambda".into())
+        );
+
+        let state = RuntimeState::new(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+            None,
+            PathBuf::new(),
+            HashSet::from(["function".to_string()]),
+            None,
+        )
+        .with_rie_compatible(true);
+        assert_eq!("function", state.invoked_function_name("function".into()));
+    }
 }
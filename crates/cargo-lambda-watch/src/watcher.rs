@@ -1,38 +1,69 @@
-use crate::{error::ServerError, requests::NextEvent, state::ExtensionCache};
+use crate::{
+    activation::SD_LISTEN_VARS,
+    error::ServerError,
+    faults::{OUT_OF_MEMORY_ERROR, OUT_OF_MEMORY_MESSAGE},
+    requests::NextEvent,
+    sandbox::Sandbox,
+    session::{Session, SessionProcess},
+    state::RuntimeState,
+};
 use cargo_lambda_metadata::{
-    cargo::load_metadata,
+    cargo::{load_metadata, watch::WatchConfig},
     config::{ConfigOptions, load_config_without_cli_flags},
 };
 // use cargo_lambda_metadata::cargo::function_environment_metadata;
-use ignore::create_filter;
+use ignore::{BinaryFilterer, create_filter};
 use ignore_files::IgnoreFile;
 use std::{collections::HashMap, convert::Infallible, path::PathBuf, sync::Arc, time::Duration};
 use tracing::{debug, error, trace};
 use watchexec::{
     ErrorHook, Watchexec,
-    action::{Action, Outcome, PreSpawn},
+    action::{Action, Outcome, PostSpawn, PreSpawn},
     command::Command,
     config::{InitConfig, RuntimeConfig},
     error::RuntimeError,
-    event::{Event, Priority, ProcessEnd},
+    event::{Event, Priority, ProcessEnd, Source, Tag},
     handler::SyncFnHandler,
     signal::source::MainSignal,
 };
 
 pub(crate) mod ignore;
+pub(crate) mod output;
+use output::{FunctionOutput, LogFile, line_prefix};
 
 #[derive(Clone, Debug, Default)]
 pub(crate) struct WatcherConfig {
     pub runtime_api: String,
     pub name: String,
+    pub display_name: String,
     pub bin_name: Option<String>,
     pub base: PathBuf,
     pub manifest_path: PathBuf,
     pub ignore_files: Vec<IgnoreFile>,
+    pub ignore_globs: Vec<String>,
     pub ignore_changes: bool,
     pub only_lambda_apis: bool,
     pub env: HashMap<String, String>,
     pub wait: bool,
+    pub log_dir: Option<PathBuf>,
+    pub log_prefix: Option<LogPrefixConfig>,
+    pub session: Option<Arc<Session>>,
+    pub binaries: Arc<HashMap<String, WatchConfig>>,
+    /// Binary built outside the server, that runs instead of `cargo run`
+    pub bin_path: Option<PathBuf>,
+    /// Memory size, in megabytes, that the functions report to the runtime
+    pub memory_size: u64,
+    /// Sandbox that limits the resources of the functions
+    pub sandbox: Option<Sandbox>,
+}
+
+/// Configuration to prefix the output of the functions with their names.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LogPrefixConfig {
+    /// Width to pad the function names to
+    pub width: usize,
+    /// Whether the prefix uses colors
+    pub color: bool,
 }
 
 impl WatcherConfig {
@@ -48,10 +79,10 @@ impl WatcherConfig {
 pub(crate) async fn new(
     cmd: Command,
     wc: WatcherConfig,
-    ext_cache: ExtensionCache,
+    state: RuntimeState,
 ) -> Result<Arc<Watchexec>, ServerError> {
     let init = crate::watcher::init();
-    let runtime = crate::watcher::runtime(cmd, wc, ext_cache).await?;
+    let runtime = crate::watcher::runtime(cmd, wc, state).await?;
 
     let wx = Watchexec::new(init, runtime).map_err(ServerError::WatcherError)?;
     wx.send_event(Event::default(), Priority::Urgent)
@@ -61,6 +92,21 @@ pub(crate) async fn new(
     Ok(wx)
 }
 
+/// Ask watchexec to stop the function's process and exit its main task.
+pub(crate) async fn terminate(wx: &Watchexec) -> Result<(), ServerError> {
+    let event = Event {
+        tags: vec![
+            Tag::Source(Source::Internal),
+            Tag::Signal(MainSignal::Terminate),
+        ],
+        metadata: Default::default(),
+    };
+
+    wx.send_event(event, Priority::Urgent)
+        .await
+        .map_err(ServerError::WatcherError)
+}
+
 fn init() -> InitConfig {
     let mut config = InitConfig::default();
     config.on_error(SyncFnHandler::from(
@@ -90,17 +136,48 @@ fn init() -> InitConfig {
 async fn runtime(
     cmd: Command,
     wc: WatcherConfig,
-    ext_cache: ExtensionCache,
+    state: RuntimeState,
 ) -> Result<RuntimeConfig, ServerError> {
     let mut config = RuntimeConfig::default();
 
-    config.pathset([wc.base.clone()]);
     config.commands(vec![cmd]);
 
-    config.filterer(create_filter(&wc.base, &wc.ignore_files, wc.ignore_changes).await?);
+    match (
+        &wc.bin_path,
+        wc.bin_path.as_deref().and_then(|p| p.parent()),
+    ) {
+        (Some(bin_path), Some(dir)) if !wc.ignore_changes => {
+            config.pathset([dir]);
+            config.filterer(Arc::new(BinaryFilterer(bin_path.clone())));
+        }
+        _ => {
+            config.pathset([wc.base.clone()]);
+            config.filterer(
+                create_filter(
+                    &wc.base,
+                    &wc.ignore_files,
+                    &wc.ignore_globs,
+                    wc.ignore_changes,
+                )
+                .await?,
+            );
+        }
+    }
 
     config.action_throttle(Duration::from_secs(3));
 
+    let mut output = FunctionOutput::default();
+    if let Some(dir) = &wc.log_dir {
+        let log_file = LogFile::open(dir, &wc.display_name).map_err(ServerError::OpenLogFile)?;
+        output = output.with_log_file(log_file);
+    }
+    if let Some(prefix) = &wc.log_prefix {
+        output = output.with_prefix(line_prefix(&wc.display_name, prefix.width, prefix.color));
+    }
+
+    let faults = state.faults.clone();
+    let reload = state.reload.clone();
+    let function_name = wc.name.clone();
     config.on_action(move |action: Action| {
         let signals: Vec<MainSignal> = action.events.iter().flat_map(|e| e.signals()).collect();
         let has_paths = action
@@ -110,6 +187,8 @@ async fn runtime(
             .next()
             .is_some();
 
+        let internal_event = action.events.iter().any(|e| e.is_internal());
+
         let empty_event = action
             .events
             .iter()
@@ -122,18 +201,23 @@ async fn runtime(
             ?signals,
             has_paths,
             empty_event,
+            internal_event,
             "watcher action received"
         );
 
-        let ext_cache = ext_cache.clone();
+        let state = state.clone();
+        let function_name = function_name.clone();
         async move {
-            if signals.contains(&MainSignal::Terminate) {
-                action.outcome(Outcome::both(Outcome::Stop, Outcome::Exit));
-                return Ok(());
-            }
-
-            if signals.contains(&MainSignal::Interrupt) {
-                action.outcome(Outcome::both(Outcome::Stop, Outcome::Exit));
+            if signals.contains(&MainSignal::Terminate) || signals.contains(&MainSignal::Interrupt)
+            {
+                // Signals sent to Cargo Lambda are handled by the scheduler,
+                // which terminates the function after the server drains
+                // the invocations in flight.
+                if internal_event {
+                    action.outcome(Outcome::both(Outcome::Stop, Outcome::Exit));
+                } else {
+                    action.outcome(Outcome::DoNothing);
+                }
                 return Ok(());
             }
 
@@ -150,6 +234,7 @@ async fn runtime(
 
                 let completion = action.events.iter().flat_map(|e| e.completions()).next();
                 if let Some(status) = completion {
+                    state.reload.function_exited(&function_name);
                     match status {
                         Some(ProcessEnd::ExitError(sig)) => {
                             error!(code = ?sig, "command exited");
@@ -166,14 +251,36 @@ async fn runtime(
                         _ => {}
                     };
 
-                    action.outcome(Outcome::DoNothing);
+                    // Functions killed because they ran out of memory start again
+                    // on the next invocation, like Lambda does after an error.
+                    if state.faults.take_out_of_memory(&function_name) {
+                        state
+                            .fail_invocations(
+                                &function_name,
+                                OUT_OF_MEMORY_ERROR,
+                                OUT_OF_MEMORY_MESSAGE,
+                            )
+                            .await;
+                        action.outcome(Outcome::if_running(Outcome::DoNothing, Outcome::Start));
+                        return Ok(());
+                    }
+
+                    // Restart the function if it was processing invocations,
+                    // so the callers don't wait for a response that never comes.
+                    let message = exit_reason(status);
+                    if state.recover_invocations(&function_name, &message).await {
+                        action.outcome(Outcome::if_running(Outcome::DoNothing, Outcome::Start));
+                    } else {
+                        action.outcome(Outcome::DoNothing);
+                    }
                     return Ok(());
                 }
             }
 
             if !empty_event {
                 let event = NextEvent::shutdown("recompiling function");
-                ext_cache.send_event(event).await?;
+                state.ext_cache.send_event(event).await?;
+                state.reload.function_rebuilding(&function_name);
             }
             let when_running = Outcome::both(Outcome::Stop, Outcome::Start);
             action.outcome(Outcome::if_running(when_running, Outcome::Start));
@@ -182,12 +289,32 @@ async fn runtime(
         }
     });
 
+    let session = wc.session.clone();
+    let name = wc.name.clone();
+    config.on_post_spawn(move |postspawn: PostSpawn| {
+        let process = SessionProcess {
+            id: postspawn.id,
+            grouped: postspawn.grouped,
+        };
+        if let Some(session) = &session {
+            session.add_process(&name, process.clone());
+        }
+
+        faults.function_started(&name);
+        reload.function_started(&name);
+        faults.watch_memory(&name, process);
+
+        async { Ok::<(), Infallible>(()) }
+    });
+
     config.on_pre_spawn(move |prespawn: PreSpawn| {
         let name = wc.name.clone();
         let runtime_api = wc.runtime_api.clone();
         let manifest_path = wc.manifest_path.clone();
         let bin_name = wc.bin_name.clone();
         let base_env = wc.env.clone();
+        let memory_size = wc.memory_size;
+        let output = output.clone();
 
         async move {
             trace!("loading watch environment metadata");
@@ -197,11 +324,20 @@ async fn runtime(
             if let Some(mut command) = prespawn.command().await {
                 command
                     .env("AWS_LAMBDA_FUNCTION_VERSION", "1")
-                    .env("AWS_LAMBDA_FUNCTION_MEMORY_SIZE", "4096")
+                    .env("AWS_LAMBDA_FUNCTION_MEMORY_SIZE", memory_size.to_string())
                     .envs(base_env)
                     .envs(new_env)
                     .env("AWS_LAMBDA_RUNTIME_API", &runtime_api)
                     .env("AWS_LAMBDA_FUNCTION_NAME", &name);
+                for var in SD_LISTEN_VARS {
+                    command.env_remove(var);
+                }
+
+                if output.is_captured() {
+                    if let Err(error) = output.capture(&mut command) {
+                        error!(?error, function = ?name, "failed to capture the function's output");
+                    }
+                }
             }
 
             Ok::<(), Infallible>(())
@@ -211,6 +347,21 @@ async fn runtime(
     Ok(config)
 }
 
+/// Describe how the function's process ended, with the message that Lambda
+/// returns when a runtime exits before responding to an invocation.
+fn exit_reason(status: Option<ProcessEnd>) -> String {
+    let error = match status {
+        Some(ProcessEnd::ExitError(code)) => format!("exit status {code}"),
+        Some(ProcessEnd::ExitSignal(sig)) => format!("signal: {sig}"),
+        Some(ProcessEnd::ExitStop(code)) => format!("stopped: {code}"),
+        Some(ProcessEnd::Exception(code)) => format!("exception: {code}"),
+        Some(ProcessEnd::Continued) | Some(ProcessEnd::Success) | None => {
+            return "Runtime exited without providing a reason".into();
+        }
+    };
+    format!("Runtime exited with error: {error}")
+}
+
 fn reload_env(manifest_path: &PathBuf, bin_name: &Option<String>) -> HashMap<String, String> {
     let metadata = match load_metadata(manifest_path) {
         Ok(metadata) => metadata,
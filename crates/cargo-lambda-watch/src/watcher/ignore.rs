@@ -1,4 +1,8 @@
-use std::{collections::HashSet, path::Path, sync::Arc};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use ignore::Match;
 use ignore_files::{IgnoreFile, IgnoreFilter};
@@ -51,6 +55,7 @@ pub(crate) async fn discover_files(base: &Path) -> Vec<IgnoreFile> {
 pub(crate) async fn create_filter(
     base: &Path,
     files: &[IgnoreFile],
+    globs: &[String],
     ignore_changes: bool,
 ) -> Result<Arc<IgnoreFilterer>, ServerError> {
     trace!(?files, "creating watcher ignore filterer");
@@ -70,6 +75,12 @@ pub(crate) async fn create_filter(
     filter
         .add_globs(&["target/*", "target*"], Some(&base.to_path_buf()))
         .map_err(ServerError::InvalidIgnoreFiles)?;
+    if !globs.is_empty() {
+        let globs = globs.iter().map(String::as_str).collect::<Vec<_>>();
+        filter
+            .add_globs(&globs, Some(&base.to_path_buf()))
+            .map_err(ServerError::InvalidIgnoreFiles)?;
+    }
     filters.push(filter);
 
     for file in files {
@@ -151,9 +162,27 @@ impl Filterer for IgnoreFilterer {
     }
 }
 
+/// A Watchexec [`Filterer`] that only passes the changes to a binary built outside
+/// the server. The directory of the binary is watched, instead of the binary itself,
+/// because build tools replace the file when they build it again.
+#[derive(Clone, Debug)]
+pub struct BinaryFilterer(pub PathBuf);
+
+impl Filterer for BinaryFilterer {
+    /// Filter an event. Events without paths, like signals and process completions, always pass.
+    fn check_event(&self, event: &Event, _priority: Priority) -> Result<bool, RuntimeError> {
+        let mut paths = event.paths().peekable();
+        if paths.peek().is_none() {
+            return Ok(true);
+        }
+
+        Ok(paths.any(|(path, _)| path == self.0))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{io::Write, path::PathBuf};
+    use std::io::Write;
 
     use watchexec::event::Tag;
 
@@ -211,9 +240,41 @@ mod tests {
         assert!(!filterer.check_event(&event, Priority::Normal).unwrap());
     }
 
+    #[test]
+    fn test_binary_filterer() {
+        let filterer = BinaryFilterer(PathBuf::from("/project/target/lambda/orders/bootstrap"));
+
+        for (path, pass) in [
+            ("/project/target/lambda/orders/bootstrap", true),
+            ("/project/target/lambda/orders/bootstrap.zip", false),
+            ("/project/src/main.rs", false),
+        ] {
+            let event = Event {
+                tags: vec![Tag::Path {
+                    path: path.into(),
+                    file_type: Some(FileType::File),
+                }],
+                ..Default::default()
+            };
+            assert_eq!(
+                pass,
+                filterer.check_event(&event, Priority::Normal).unwrap(),
+                "{path}"
+            );
+        }
+
+        assert!(
+            filterer
+                .check_event(&Event::default(), Priority::Normal)
+                .unwrap()
+        );
+    }
+
     #[tokio::test]
     async fn test_create_filter_with_default_target_dir() {
-        let filter = create_filter(Path::new("."), &[], false).await.unwrap();
+        let filter = create_filter(Path::new("."), &[], &[], false)
+            .await
+            .unwrap();
         assert_eq!(filter.0.len(), 1);
 
         let event = Event {
@@ -237,7 +298,7 @@ mod tests {
             applies_to: None,
         };
 
-        let filter = create_filter(Path::new("."), &[ignore_file], false)
+        let filter = create_filter(Path::new("."), &[ignore_file], &[], false)
             .await
             .unwrap();
         assert_eq!(filter.0.len(), 2);
@@ -260,4 +321,33 @@ mod tests {
         };
         assert!(!filter.check_event(&event, Priority::Normal).unwrap());
     }
+
+    #[tokio::test]
+    async fn test_create_filter_with_globs() {
+        let globs = vec!["*.md".to_string(), "fixtures/**".to_string()];
+        let filter = create_filter(Path::new("."), &[], &globs, false)
+            .await
+            .unwrap();
+        assert_eq!(filter.0.len(), 1);
+
+        for (path, pass) in [
+            ("./README.md", false),
+            ("./docs/guide.md", false),
+            ("./fixtures/event.json", false),
+            ("./src/main.rs", true),
+        ] {
+            let event = Event {
+                tags: vec![Tag::Path {
+                    path: path.into(),
+                    file_type: Some(FileType::File),
+                }],
+                ..Default::default()
+            };
+            assert_eq!(
+                pass,
+                filter.check_event(&event, Priority::Normal).unwrap(),
+                "{path}"
+            );
+        }
+    }
 }
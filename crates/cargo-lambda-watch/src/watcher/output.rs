@@ -0,0 +1,261 @@
+use std::{
+    fs::{File, OpenOptions, create_dir_all, rename},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+use tokio::process::Command;
+use tracing::error;
+
+const MAX_LOG_FILE_SIZE: u64 = 10 * 1024 * 1024;
+const MAX_ROTATED_FILES: usize = 5;
+
+#[derive(Clone, Copy, Debug)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// LogFile writes the output of a function to `<name>.log`.
+/// The file is rotated when it grows beyond its maximum size,
+/// keeping up to `MAX_ROTATED_FILES` old files as `<name>.log.N`.
+#[derive(Debug)]
+pub(crate) struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl LogFile {
+    pub(crate) fn open(dir: &Path, name: &str) -> io::Result<LogFile> {
+        Self::open_with_max_size(dir, name, MAX_LOG_FILE_SIZE)
+    }
+
+    fn open_with_max_size(dir: &Path, name: &str, max_size: u64) -> io::Result<LogFile> {
+        create_dir_all(dir)?;
+
+        let path = dir.join(format!("{name}.log"));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(LogFile {
+            path,
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for idx in (1..MAX_ROTATED_FILES).rev() {
+            let from = rotated_path(&self.path, idx);
+            if from.exists() {
+                rename(&from, rotated_path(&self.path, idx + 1))?;
+            }
+        }
+        rename(&self.path, rotated_path(&self.path, 1))?;
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, idx: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{idx}"));
+    PathBuf::from(path)
+}
+
+const PREFIX_COLORS: [u8; 10] = [36, 33, 32, 35, 34, 96, 93, 92, 95, 94];
+
+/// Render the prefix for a function's output, like `basic-lambda | `.
+/// Names are padded to `width`, so the output of all functions is aligned.
+/// The color is picked from the name, so a function keeps the same color
+/// after it's restarted.
+pub(crate) fn line_prefix(name: &str, width: usize, color: bool) -> Vec<u8> {
+    let prefix = format!("{name:<width$} | ");
+    if !color {
+        return prefix.into_bytes();
+    }
+
+    let idx = name.bytes().map(usize::from).sum::<usize>() % PREFIX_COLORS.len();
+    format!("\x1b[{}m{prefix}\x1b[0m", PREFIX_COLORS[idx]).into_bytes()
+}
+
+/// FunctionOutput copies the output of a function's process
+/// to the console, and to the function's log file.
+/// Console lines can be prefixed to identify the function
+/// that printed them.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FunctionOutput {
+    log_file: Option<Arc<Mutex<LogFile>>>,
+    prefix: Option<Arc<[u8]>>,
+}
+
+impl FunctionOutput {
+    pub(crate) fn with_log_file(mut self, log_file: LogFile) -> FunctionOutput {
+        self.log_file = Some(Arc::new(Mutex::new(log_file)));
+        self
+    }
+
+    pub(crate) fn with_prefix(mut self, prefix: Vec<u8>) -> FunctionOutput {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Whether the process' output needs to be captured,
+    /// otherwise the process writes directly to the console.
+    pub(crate) fn is_captured(&self) -> bool {
+        self.log_file.is_some() || self.prefix.is_some()
+    }
+
+    /// Redirect the command's stdout and stderr to pipes,
+    /// and copy everything that the process writes in the background.
+    pub(crate) fn capture(&self, command: &mut Command) -> io::Result<()> {
+        let (out_reader, out_writer) = os_pipe::pipe()?;
+        let (err_reader, err_writer) = os_pipe::pipe()?;
+
+        command.stdout(out_writer).stderr(err_writer);
+
+        self.copy_in_background(out_reader, Stream::Stdout);
+        self.copy_in_background(err_reader, Stream::Stderr);
+
+        Ok(())
+    }
+
+    fn copy_in_background<R: Read + Send + 'static>(&self, reader: R, stream: Stream) {
+        let output = self.clone();
+        thread::spawn(move || {
+            if let Err(error) = output.copy(reader, stream) {
+                error!(?error, ?stream, "failed to copy the function's output");
+            }
+        });
+    }
+
+    fn copy<R: Read>(&self, reader: R, stream: Stream) -> io::Result<()> {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+
+            match stream {
+                Stream::Stdout => self.write_console(&mut io::stdout().lock(), &line)?,
+                Stream::Stderr => self.write_console(&mut io::stderr().lock(), &line)?,
+            }
+
+            if let Some(log_file) = &self.log_file {
+                let mut log_file = log_file
+                    .lock()
+                    .map_err(|_| io::Error::other("log file lock poisoned"))?;
+                log_file.write_line(&line)?;
+            }
+        }
+    }
+
+    fn write_console<W: Write>(&self, console: &mut W, line: &[u8]) -> io::Result<()> {
+        let Some(prefix) = &self.prefix else {
+            return console.write_all(line);
+        };
+
+        // Terminate partial lines, so the next
+        // line starts with a prefix too.
+        console.write_all(prefix)?;
+        console.write_all(line)?;
+        if !line.ends_with(b"\n") {
+            console.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read_to_string;
+
+    #[test]
+    fn test_log_file_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log_file = LogFile::open_with_max_size(dir.path(), "basic-lambda", 10).unwrap();
+
+        log_file.write_line(b"line one\n").unwrap();
+        log_file.write_line(b"line two\n").unwrap();
+        log_file.write_line(b"line three\n").unwrap();
+
+        let path = dir.path().join("basic-lambda.log");
+        assert_eq!("line three\n", read_to_string(&path).unwrap());
+        assert_eq!(
+            "line two\n",
+            read_to_string(dir.path().join("basic-lambda.log.1")).unwrap()
+        );
+        assert_eq!(
+            "line one\n",
+            read_to_string(dir.path().join("basic-lambda.log.2")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_output_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_file = LogFile::open(dir.path(), "basic-lambda").unwrap();
+        let output = FunctionOutput::default().with_log_file(log_file);
+
+        output
+            .copy(&b"hello\nworld"[..], Stream::Stdout)
+            .expect("failed to copy output");
+
+        let path = dir.path().join("basic-lambda.log");
+        assert_eq!("hello\nworld", read_to_string(path).unwrap());
+    }
+
+    #[test]
+    fn test_line_prefix() {
+        assert_eq!(b"api   | ".to_vec(), line_prefix("api", 5, false));
+        assert_eq!(
+            b"basic-lambda | ".to_vec(),
+            line_prefix("basic-lambda", 5, false)
+        );
+
+        let prefix = String::from_utf8(line_prefix("api", 5, true)).unwrap();
+        assert!(prefix.starts_with("\x1b["));
+        assert!(prefix.ends_with("api   | \x1b[0m"));
+        assert_eq!(
+            prefix,
+            String::from_utf8(line_prefix("api", 5, true)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_console_with_prefix() {
+        let output = FunctionOutput::default().with_prefix(line_prefix("api", 3, false));
+
+        let mut console = Vec::new();
+        output.write_console(&mut console, b"hello\n").unwrap();
+        output.write_console(&mut console, b"world").unwrap();
+        assert_eq!(
+            "api | hello\napi | world\n",
+            String::from_utf8(console).unwrap()
+        );
+    }
+}
@@ -70,11 +70,15 @@ function sidebar() {
             items: [
                 { text: 'Supported commands', link: '/commands/introduction' },
                 { text: 'cargo lambda build', link: '/commands/build' },
+                { text: 'cargo lambda completions', link: '/commands/completions' },
+                { text: 'cargo lambda config', link: '/commands/config' },
                 { text: 'cargo lambda deploy', link: '/commands/deploy' },
                 { text: 'cargo lambda init', link: '/commands/init' },
                 { text: 'cargo lambda invoke', link: '/commands/invoke' },
                 { text: 'cargo lambda new', link: '/commands/new' },
+                { text: 'cargo lambda symbolicate', link: '/commands/symbolicate' },
                 { text: 'cargo lambda system', link: '/commands/system' },
+                { text: 'cargo lambda validate', link: '/commands/validate' },
                 { text: 'cargo lambda watch', link: '/commands/watch' },
             ]
         }
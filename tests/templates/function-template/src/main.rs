@@ -72,6 +72,9 @@ async fn function_handler(_event: Request) -> Result<Response<Body>, Error> {
 /// - https://github.com/aws-samples/serverless-rust-demo/
 async fn function_handler(event: LambdaEvent<{{ event_type }}>) -> Result<(), Error> {
     // Extract some useful information from the request
+{%- if event_type_snippet %}
+{{ event_type_snippet | rstrip }}
+{%- endif %}
 
     Ok(())
 }